@@ -0,0 +1,59 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks parsing scene files with very large parameter lists, like those of inline triangle
+//! meshes.
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use pbrt::core::{
+    api_test::MockAPI,
+    parser::{create_from_string, parse, ByteSourceTokenizer},
+};
+
+/// Returns a scene with a single "point3 P" parameter holding `n` vertices.
+fn point_list_scene(n: usize) -> String {
+    let mut s = String::from("Camera \"perspective\" \"point3 P\" [\n");
+    for i in 0..n {
+        let f = i as f32 * 0.25;
+        writeln!(s, "{} {} {}", f, -f, f * 0.5).unwrap();
+    }
+    s.push_str("]\n");
+    s
+}
+
+fn parse_point_list(c: &mut Criterion) {
+    let scene = point_list_scene(1_000_000);
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.bench_function("point3 P 1M vertices", |b| {
+        b.iter(|| {
+            let mut api = MockAPI::default();
+            parse(create_from_string(scene.as_bytes()), &mut api).expect("parse");
+            api
+        })
+    });
+    group.bench_function("point3 P 1M vertices streamed", |b| {
+        b.iter(|| {
+            let mut api = MockAPI::default();
+            parse(ByteSourceTokenizer::new(scene.as_bytes()), &mut api).expect("parse");
+            api
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_point_list);
+criterion_main!(benches);
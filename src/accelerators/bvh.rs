@@ -0,0 +1,492 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bounding volume hierarchy, an [Primitive] that partitions primitives into a binary tree of
+//! bounding boxes.
+//!
+//! [Primitive]: crate::core::primitive::Primitive
+
+use std::{fmt, sync::Arc};
+
+use log::{info, warn};
+
+use crate::{
+    core::{
+        geometry::{Bounds3f, Point3f, Ray, Vector3f},
+        interaction::SurfaceInteraction,
+        paramset::ParamSet,
+        primitive::Primitive,
+    },
+    Float,
+};
+
+/// Number of buckets candidate split planes are binned into by the surface area heuristic.
+const N_BUCKETS: usize = 12;
+
+/// Algorithm used to partition primitives into two groups at each interior node of a [BVHAccel].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// Partition where the surface area heuristic estimates ray intersection tests are cheapest.
+    Sah,
+    /// Partition at the midpoint of the primitives' centroids along the longest axis.
+    Middle,
+    /// Partition into two equally sized groups along the longest axis.
+    EqualCounts,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct BVHPrimitiveInfo {
+    primitive_number: usize,
+    bounds: Bounds3f,
+    centroid: Point3f,
+}
+
+enum BVHBuildNode {
+    Leaf {
+        bounds: Bounds3f,
+        first_prim_offset: usize,
+        n_primitives: usize,
+    },
+    Interior {
+        bounds: Bounds3f,
+        children: [Box<BVHBuildNode>; 2],
+        split_axis: usize,
+    },
+}
+
+/// Node of the flattened tree.  Interior nodes' first child immediately follows them, so only the
+/// offset of the second child is stored.
+#[derive(Copy, Clone, Debug)]
+struct LinearBVHNode {
+    bounds: Bounds3f,
+    /// For leaves the offset of the first primitive, for interior nodes the second child's.
+    offset: usize,
+    /// Zero for interior nodes.
+    n_primitives: u16,
+    axis: u8,
+}
+
+/// BVHAccel is a bounding volume hierarchy over a set of primitives, stored as a flattened tree
+/// in depth first order.
+pub struct BVHAccel {
+    max_prims_in_node: usize,
+    split_method: SplitMethod,
+    primitives: Vec<Arc<dyn Primitive>>,
+    nodes: Vec<LinearBVHNode>,
+}
+
+impl fmt::Debug for BVHAccel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BVHAccel")
+            .field("max_prims_in_node", &self.max_prims_in_node)
+            .field("split_method", &self.split_method)
+            .field("primitives", &self.primitives.len())
+            .field("nodes", &self.nodes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl BVHAccel {
+    /// Builds a BVH over `primitives`.  Leaves will hold at most `max_prims_in_node` primitives,
+    /// up to a limit of 255, unless the primitives can't be split further.
+    pub fn new(
+        primitives: Vec<Arc<dyn Primitive>>,
+        max_prims_in_node: usize,
+        split_method: SplitMethod,
+    ) -> BVHAccel {
+        let mut bvh = BVHAccel {
+            max_prims_in_node: max_prims_in_node.clamp(1, 255),
+            split_method,
+            primitives: Vec::new(),
+            nodes: Vec::new(),
+        };
+        if primitives.is_empty() {
+            return bvh;
+        }
+
+        // Initialize _primitive_info_ array for primitives
+        let mut primitive_info: Vec<_> = primitives
+            .iter()
+            .enumerate()
+            .map(|(primitive_number, p)| {
+                let bounds = p.world_bound();
+                BVHPrimitiveInfo {
+                    primitive_number,
+                    bounds,
+                    centroid: bounds.p_min * 0.5 + bounds.p_max * 0.5,
+                }
+            })
+            .collect();
+
+        // Build BVH tree for primitives using _primitive_info_
+        let mut total_nodes = 0;
+        let mut ordered_prims = Vec::with_capacity(primitives.len());
+        let root = bvh.recursive_build(
+            &primitives,
+            &mut primitive_info,
+            &mut total_nodes,
+            &mut ordered_prims,
+        );
+        bvh.primitives = ordered_prims;
+        info!(
+            "BVH created with {} nodes for {} primitives",
+            total_nodes,
+            bvh.primitives.len()
+        );
+
+        // Compute representation of depth-first traversal of BVH tree
+        bvh.nodes.reserve(total_nodes);
+        flatten_bvh_tree(&root, &mut bvh.nodes);
+        debug_assert_eq!(bvh.nodes.len(), total_nodes);
+        bvh
+    }
+
+    fn recursive_build(
+        &self,
+        primitives: &[Arc<dyn Primitive>],
+        primitive_info: &mut [BVHPrimitiveInfo],
+        total_nodes: &mut usize,
+        ordered_prims: &mut Vec<Arc<dyn Primitive>>,
+    ) -> BVHBuildNode {
+        *total_nodes += 1;
+        // Compute bounds of all primitives in BVH node
+        let bounds = primitive_info
+            .iter()
+            .fold(Bounds3f::empty(), |b, pi| Bounds3f::union(&b, &pi.bounds));
+        let n_primitives = primitive_info.len();
+        let mut make_leaf = |primitive_info: &[BVHPrimitiveInfo]| {
+            let first_prim_offset = ordered_prims.len();
+            ordered_prims.extend(
+                primitive_info
+                    .iter()
+                    .map(|pi| Arc::clone(&primitives[pi.primitive_number])),
+            );
+            BVHBuildNode::Leaf {
+                bounds,
+                first_prim_offset,
+                n_primitives,
+            }
+        };
+        if n_primitives == 1 {
+            return make_leaf(primitive_info);
+        }
+
+        // Compute bound of primitive centroids, choose split dimension _dim_
+        let centroid_bounds = primitive_info.iter().fold(Bounds3f::empty(), |b, pi| {
+            Bounds3f::union_point(&b, pi.centroid)
+        });
+        let dim = centroid_bounds.maximum_extent();
+        if centroid_bounds.p_max[dim] == centroid_bounds.p_min[dim] {
+            // All centroids are at the same position, there's no meaningful way to split them.
+            return make_leaf(primitive_info);
+        }
+
+        // Partition primitives into two sets and build children
+        let equal_counts = |primitive_info: &mut [BVHPrimitiveInfo]| {
+            let mid = primitive_info.len() / 2;
+            primitive_info.select_nth_unstable_by(mid, |a, b| {
+                a.centroid[dim]
+                    .partial_cmp(&b.centroid[dim])
+                    .expect("NaN centroid")
+            });
+            mid
+        };
+        let mid = match self.split_method {
+            SplitMethod::Middle => {
+                let p_mid = (centroid_bounds.p_min[dim] + centroid_bounds.p_max[dim]) / 2.;
+                let mid = partition(primitive_info, |pi| pi.centroid[dim] < p_mid);
+                if mid == 0 || mid == n_primitives {
+                    // Fall back to equal counts when the centroids are clumped on one side.
+                    equal_counts(primitive_info)
+                } else {
+                    mid
+                }
+            }
+            SplitMethod::EqualCounts => equal_counts(primitive_info),
+            SplitMethod::Sah => {
+                if n_primitives <= 2 {
+                    equal_counts(primitive_info)
+                } else {
+                    let bucket_of = |pi: &BVHPrimitiveInfo| {
+                        let b = (N_BUCKETS as Float * centroid_bounds.offset(pi.centroid)[dim])
+                            as usize;
+                        b.min(N_BUCKETS - 1)
+                    };
+                    // Allocate _BucketInfo_ for SAH partition buckets
+                    let mut counts = [0; N_BUCKETS];
+                    let mut bucket_bounds = [Bounds3f::empty(); N_BUCKETS];
+                    for pi in primitive_info.iter() {
+                        let b = bucket_of(pi);
+                        counts[b] += 1;
+                        bucket_bounds[b] = Bounds3f::union(&bucket_bounds[b], &pi.bounds);
+                    }
+
+                    // Compute costs for splitting after each bucket, and find the cheapest
+                    let mut min_cost = Float::INFINITY;
+                    let mut min_cost_split_bucket = 0;
+                    for i in 0..N_BUCKETS - 1 {
+                        let (b0, count0) = (0..=i).fold((Bounds3f::empty(), 0), |(b, c), j| {
+                            (Bounds3f::union(&b, &bucket_bounds[j]), c + counts[j])
+                        });
+                        let (b1, count1) = (i + 1..N_BUCKETS)
+                            .fold((Bounds3f::empty(), 0), |(b, c), j| {
+                                (Bounds3f::union(&b, &bucket_bounds[j]), c + counts[j])
+                            });
+                        let cost = 1.
+                            + (count0 as Float * surface_area(&b0)
+                                + count1 as Float * surface_area(&b1))
+                                / bounds.surface_area();
+                        if cost < min_cost {
+                            min_cost = cost;
+                            min_cost_split_bucket = i;
+                        }
+                    }
+
+                    // Either create leaf or split primitives at selected SAH bucket
+                    let leaf_cost = n_primitives as Float;
+                    if n_primitives > self.max_prims_in_node || min_cost < leaf_cost {
+                        partition(primitive_info, |pi| bucket_of(pi) <= min_cost_split_bucket)
+                    } else {
+                        return make_leaf(primitive_info);
+                    }
+                }
+            }
+        };
+
+        let (left, right) = primitive_info.split_at_mut(mid);
+        let c0 = self.recursive_build(primitives, left, total_nodes, ordered_prims);
+        let c1 = self.recursive_build(primitives, right, total_nodes, ordered_prims);
+        BVHBuildNode::Interior {
+            bounds,
+            children: [Box::new(c0), Box::new(c1)],
+            split_axis: dim,
+        }
+    }
+}
+
+/// Surface area of `b`, treating empty bounds as having no area.
+fn surface_area(b: &Bounds3f) -> Float {
+    if b.p_min.x > b.p_max.x {
+        0.
+    } else {
+        b.surface_area()
+    }
+}
+
+/// Reorders `v` so all the elements `pred` returns true for come first, returning how many there
+/// are.
+fn partition<T, F>(v: &mut [T], pred: F) -> usize
+where
+    F: Fn(&T) -> bool,
+{
+    let mut first = 0;
+    for i in 0..v.len() {
+        if pred(&v[i]) {
+            v.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+/// Appends `node` and its descendants to `nodes` in depth first order, returning `node`'s offset.
+fn flatten_bvh_tree(node: &BVHBuildNode, nodes: &mut Vec<LinearBVHNode>) -> usize {
+    let offset = nodes.len();
+    match node {
+        BVHBuildNode::Leaf {
+            bounds,
+            first_prim_offset,
+            n_primitives,
+        } => nodes.push(LinearBVHNode {
+            bounds: *bounds,
+            offset: *first_prim_offset,
+            n_primitives: *n_primitives as u16,
+            axis: 0,
+        }),
+        BVHBuildNode::Interior {
+            bounds,
+            children,
+            split_axis,
+        } => {
+            nodes.push(LinearBVHNode {
+                bounds: *bounds,
+                offset: 0,
+                n_primitives: 0,
+                axis: *split_axis as u8,
+            });
+            flatten_bvh_tree(&children[0], nodes);
+            nodes[offset].offset = flatten_bvh_tree(&children[1], nodes);
+        }
+    }
+    offset
+}
+
+impl BVHAccel {
+    /// Walks the tree front to back along `r`, calling `f` with each primitive in a leaf whose
+    /// bounds `r` passes through.  Traversal stops early if `f` returns true.
+    fn traverse<F>(&self, r: &Ray, mut f: F)
+    where
+        F: FnMut(&Arc<dyn Primitive>) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_dir = Vector3f::from([1. / r.d.x, 1. / r.d.y, 1. / r.d.z]);
+        let dir_is_neg = [inv_dir.x < 0., inv_dir.y < 0., inv_dir.z < 0.];
+        // Follow ray through BVH nodes to find primitive intersections
+        let mut to_visit = [0; 64];
+        let mut to_visit_offset = 0;
+        let mut current_node_index = 0;
+        loop {
+            let node = &self.nodes[current_node_index];
+            // Check ray against BVH node
+            if node.bounds.intersect_p_precomputed(r, &inv_dir, dir_is_neg) {
+                if node.n_primitives > 0 {
+                    // Intersect ray with primitives in leaf BVH node
+                    let start = node.offset;
+                    let end = start + node.n_primitives as usize;
+                    for p in &self.primitives[start..end] {
+                        if f(p) {
+                            return;
+                        }
+                    }
+                } else {
+                    // Put far BVH node on _to_visit_ stack, advance to near node
+                    if dir_is_neg[node.axis as usize] {
+                        to_visit[to_visit_offset] = current_node_index + 1;
+                        current_node_index = node.offset;
+                    } else {
+                        to_visit[to_visit_offset] = node.offset;
+                        current_node_index += 1;
+                    }
+                    to_visit_offset += 1;
+                    continue;
+                }
+            }
+            if to_visit_offset == 0 {
+                break;
+            }
+            to_visit_offset -= 1;
+            current_node_index = to_visit[to_visit_offset];
+        }
+    }
+}
+
+impl Primitive for BVHAccel {
+    fn world_bound(&self) -> Bounds3f {
+        match self.nodes.first() {
+            Some(root) => root.bounds,
+            None => Bounds3f::empty(),
+        }
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        let mut isect = None;
+        self.traverse(r, |p| {
+            // Primitives shorten `r.t_max` on a hit, so later hits are always closer.
+            if let Some(si) = p.intersect(r) {
+                isect = Some(si);
+            }
+            false
+        });
+        isect
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        let mut hit = false;
+        self.traverse(r, |p| {
+            hit = p.intersect_p(r);
+            hit
+        });
+        hit
+    }
+}
+
+/// Creates a [BVHAccel] over `prims`, reading "splitmethod" and "maxnodeprims" from `params`.
+pub fn create_bvh_accelerator(prims: Vec<Arc<dyn Primitive>>, params: &ParamSet) -> BVHAccel {
+    let split_method_name = params.find_one_string("splitmethod", "sah");
+    let split_method = match split_method_name.as_str() {
+        "sah" => SplitMethod::Sah,
+        "middle" => SplitMethod::Middle,
+        "equal" => SplitMethod::EqualCounts,
+        "hlbvh" => {
+            warn!("BVH split method \"hlbvh\" not implemented.  Using \"sah\".");
+            SplitMethod::Sah
+        }
+        _ => {
+            warn!(
+                "BVH split method \"{}\" unknown.  Using \"sah\".",
+                split_method_name
+            );
+            SplitMethod::Sah
+        }
+    };
+    let max_prims_in_node = params.find_one_int("maxnodeprims", 4).max(1) as usize;
+    BVHAccel::new(prims, max_prims_in_node, split_method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BVHAccel, SplitMethod};
+    use crate::{
+        accelerators::testutils::{brute_force, random_boxes, random_ray},
+        core::{
+            geometry::{Ray, Vector3f},
+            primitive::Primitive,
+            rng::Rng,
+        },
+        Float,
+    };
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng::new(0);
+        let prims = random_boxes(&mut rng, 500);
+        for split_method in &[
+            SplitMethod::Sah,
+            SplitMethod::Middle,
+            SplitMethod::EqualCounts,
+        ] {
+            let bvh = BVHAccel::new(prims.clone(), 4, *split_method);
+            let mut hits = 0;
+            for _ in 0..1000 {
+                let r = random_ray(&mut rng);
+                let want = brute_force(&prims, &r.clone());
+                let got = bvh.intersect(&r).map(|_| r.t_max.get());
+                assert_eq!(got, want, "{:?} {:?}", split_method, r);
+                let r = Ray {
+                    t_max: Float::INFINITY.into(),
+                    ..r
+                };
+                assert_eq!(bvh.intersect_p(&r), want.is_some());
+                if want.is_some() {
+                    hits += 1;
+                }
+            }
+            // Make sure the test exercises both outcomes.
+            assert!(hits > 0 && hits < 1000, "{} hits", hits);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let bvh = BVHAccel::new(Vec::new(), 4, SplitMethod::Sah);
+        let r = Ray {
+            d: Vector3f::from([0., 0., 1.]),
+            ..Default::default()
+        };
+        assert!(bvh.intersect(&r).is_none());
+        assert!(!bvh.intersect_p(&r));
+    }
+}
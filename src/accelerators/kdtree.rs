@@ -0,0 +1,575 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! K-d tree, an [Primitive] that recursively splits space with axis aligned planes chosen by the
+//! surface area heuristic.
+//!
+//! [Primitive]: crate::core::primitive::Primitive
+
+use std::{fmt, sync::Arc};
+
+use log::info;
+
+use crate::{
+    core::{
+        geometry::{Bounds3f, Ray, Vector3f},
+        interaction::SurfaceInteraction,
+        paramset::ParamSet,
+        primitive::Primitive,
+    },
+    Float,
+};
+
+/// Maximum depth of the stack of nodes still to visit during traversal.
+const MAX_TODO: usize = 64;
+
+/// Node of the tree.  Interior nodes' below child immediately follows them, so only the offset of
+/// the above child is stored.
+#[derive(Copy, Clone, Debug)]
+enum KdAccelNode {
+    Leaf {
+        /// Offset of the node's first entry in `KdTreeAccel::primitive_indices`.
+        primitive_indices_offset: usize,
+        n_primitives: usize,
+    },
+    Interior {
+        split_axis: u8,
+        split_pos: Float,
+        above_child: usize,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeType {
+    // Declared first so starts sort before ends at the same position.
+    Start,
+    End,
+}
+
+/// Where a primitive's bounds start or end along the axis being split.
+#[derive(Copy, Clone, Debug)]
+struct BoundEdge {
+    t: Float,
+    prim_num: usize,
+    edge_type: EdgeType,
+}
+
+/// Node still to visit during traversal, along with the parametric range of the ray inside it.
+#[derive(Copy, Clone, Debug, Default)]
+struct KdToDo {
+    node: usize,
+    t_min: Float,
+    t_max: Float,
+}
+
+/// KdTreeAccel partitions space into a binary tree of axis aligned regions, stored as a flattened
+/// array in depth first order.  Primitives straddling a split are referenced from both sides.
+pub struct KdTreeAccel {
+    isect_cost: Float,
+    traversal_cost: Float,
+    max_prims: usize,
+    empty_bonus: Float,
+    primitives: Vec<Arc<dyn Primitive>>,
+    primitive_indices: Vec<usize>,
+    nodes: Vec<KdAccelNode>,
+    bounds: Bounds3f,
+}
+
+impl fmt::Debug for KdTreeAccel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KdTreeAccel")
+            .field("isect_cost", &self.isect_cost)
+            .field("traversal_cost", &self.traversal_cost)
+            .field("max_prims", &self.max_prims)
+            .field("empty_bonus", &self.empty_bonus)
+            .field("primitives", &self.primitives.len())
+            .field("nodes", &self.nodes.len())
+            .field("bounds", &self.bounds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KdTreeAccel {
+    /// Builds a k-d tree over `primitives`.  `isect_cost` and `traversal_cost` are the relative
+    /// costs the surface area heuristic assigns to intersecting a primitive and traversing an
+    /// interior node, and `empty_bonus`, in [0, 1], favors splits that leave one side empty.
+    /// Nodes with at most `max_prims` primitives aren't split further.  A `max_depth` <= 0
+    /// chooses a depth based on the number of primitives.
+    pub fn new(
+        primitives: Vec<Arc<dyn Primitive>>,
+        isect_cost: Float,
+        traversal_cost: Float,
+        empty_bonus: Float,
+        max_prims: usize,
+        max_depth: isize,
+    ) -> KdTreeAccel {
+        let mut kd = KdTreeAccel {
+            isect_cost,
+            traversal_cost,
+            max_prims: max_prims.max(1),
+            empty_bonus,
+            primitives,
+            primitive_indices: Vec::new(),
+            nodes: Vec::new(),
+            bounds: Bounds3f::empty(),
+        };
+        if kd.primitives.is_empty() {
+            return kd;
+        }
+        let max_depth = if max_depth <= 0 {
+            (8. + 1.3 * (kd.primitives.len() as Float).log2()).round() as usize
+        } else {
+            max_depth as usize
+        };
+
+        // Compute bounds for kd-tree construction
+        let prim_bounds: Vec<_> = kd.primitives.iter().map(|p| p.world_bound()).collect();
+        kd.bounds = prim_bounds
+            .iter()
+            .fold(Bounds3f::empty(), |b, pb| Bounds3f::union(&b, pb));
+
+        // Start recursive construction of kd-tree
+        let prim_nums = (0..kd.primitives.len()).collect();
+        kd.build_tree(kd.bounds, &prim_bounds, prim_nums, max_depth, 0);
+        info!(
+            "KdTree created with {} nodes for {} primitives",
+            kd.nodes.len(),
+            kd.primitives.len()
+        );
+        kd
+    }
+
+    fn make_leaf(&mut self, prim_nums: &[usize]) {
+        self.nodes.push(KdAccelNode::Leaf {
+            primitive_indices_offset: self.primitive_indices.len(),
+            n_primitives: prim_nums.len(),
+        });
+        self.primitive_indices.extend_from_slice(prim_nums);
+    }
+
+    /// Appends the subtree for the primitives in `prim_nums`, which all overlap `node_bounds`, to
+    /// `self.nodes`.  `bad_refines` counts the splits above this node that the heuristic
+    /// predicted would cost more than not splitting.
+    fn build_tree(
+        &mut self,
+        node_bounds: Bounds3f,
+        all_prim_bounds: &[Bounds3f],
+        prim_nums: Vec<usize>,
+        depth: usize,
+        mut bad_refines: usize,
+    ) {
+        // Initialize leaf node if termination criteria met
+        let n_primitives = prim_nums.len();
+        if n_primitives <= self.max_prims || depth == 0 {
+            self.make_leaf(&prim_nums);
+            return;
+        }
+
+        // Choose split axis position for interior node
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_cost = Float::INFINITY;
+        let old_cost = self.isect_cost * n_primitives as Float;
+        let inv_total_sa = 1. / node_bounds.surface_area();
+        let d = node_bounds.diagonal();
+
+        // Choose which axis to split along, retrying the others if no split is found
+        let mut axis = node_bounds.maximum_extent();
+        let mut edges = Vec::with_capacity(2 * n_primitives);
+        for _retries in 0..3 {
+            // Initialize edges for _axis_
+            edges.clear();
+            for &pn in &prim_nums {
+                let bounds = &all_prim_bounds[pn];
+                edges.push(BoundEdge {
+                    t: bounds.p_min[axis],
+                    prim_num: pn,
+                    edge_type: EdgeType::Start,
+                });
+                edges.push(BoundEdge {
+                    t: bounds.p_max[axis],
+                    prim_num: pn,
+                    edge_type: EdgeType::End,
+                });
+            }
+            edges.sort_by(|e0, e1| {
+                e0.t.partial_cmp(&e1.t)
+                    .expect("NaN primitive bounds")
+                    .then(e0.edge_type.cmp(&e1.edge_type))
+            });
+
+            // Compute cost of all splits for _axis_ to find best
+            let mut n_below = 0;
+            let mut n_above = n_primitives;
+            let (other_axis0, other_axis1) = ((axis + 1) % 3, (axis + 2) % 3);
+            for (i, edge) in edges.iter().enumerate() {
+                if edge.edge_type == EdgeType::End {
+                    n_above -= 1;
+                }
+                let edge_t = edge.t;
+                if edge_t > node_bounds.p_min[axis] && edge_t < node_bounds.p_max[axis] {
+                    // Compute cost for split at _i_th edge
+                    let below_sa = 2.
+                        * (d[other_axis0] * d[other_axis1]
+                            + (edge_t - node_bounds.p_min[axis])
+                                * (d[other_axis0] + d[other_axis1]));
+                    let above_sa = 2.
+                        * (d[other_axis0] * d[other_axis1]
+                            + (node_bounds.p_max[axis] - edge_t)
+                                * (d[other_axis0] + d[other_axis1]));
+                    let p_below = below_sa * inv_total_sa;
+                    let p_above = above_sa * inv_total_sa;
+                    let eb = if n_above == 0 || n_below == 0 {
+                        self.empty_bonus
+                    } else {
+                        0.
+                    };
+                    let cost = self.traversal_cost
+                        + self.isect_cost
+                            * (1. - eb)
+                            * (p_below * n_below as Float + p_above * n_above as Float);
+
+                    // Update best split if this is lowest cost so far
+                    if cost < best_cost {
+                        best_cost = cost;
+                        best = Some((axis, i));
+                    }
+                }
+                if edge.edge_type == EdgeType::Start {
+                    n_below += 1;
+                }
+            }
+            debug_assert!(n_below == n_primitives && n_above == 0);
+
+            if best.is_some() {
+                break;
+            }
+            axis = (axis + 1) % 3;
+        }
+
+        // Create leaf if no good splits were found
+        if best_cost > old_cost {
+            bad_refines += 1;
+        }
+        let (best_axis, best_offset) = match best {
+            Some(best) if !(best_cost > 4. * old_cost && n_primitives < 16) && bad_refines < 3 => {
+                best
+            }
+            _ => {
+                self.make_leaf(&prim_nums);
+                return;
+            }
+        };
+        if best_axis != axis {
+            // `edges` holds the last axis tried, recompute them for the best one.
+            edges.clear();
+            for &pn in &prim_nums {
+                let bounds = &all_prim_bounds[pn];
+                edges.push(BoundEdge {
+                    t: bounds.p_min[best_axis],
+                    prim_num: pn,
+                    edge_type: EdgeType::Start,
+                });
+                edges.push(BoundEdge {
+                    t: bounds.p_max[best_axis],
+                    prim_num: pn,
+                    edge_type: EdgeType::End,
+                });
+            }
+            edges.sort_by(|e0, e1| {
+                e0.t.partial_cmp(&e1.t)
+                    .expect("NaN primitive bounds")
+                    .then(e0.edge_type.cmp(&e1.edge_type))
+            });
+        }
+
+        // Classify primitives with respect to split
+        let prims0: Vec<_> = edges[..best_offset]
+            .iter()
+            .filter(|e| e.edge_type == EdgeType::Start)
+            .map(|e| e.prim_num)
+            .collect();
+        let prims1: Vec<_> = edges[best_offset + 1..]
+            .iter()
+            .filter(|e| e.edge_type == EdgeType::End)
+            .map(|e| e.prim_num)
+            .collect();
+
+        // Recursively initialize children nodes
+        let t_split = edges[best_offset].t;
+        let mut bounds0 = node_bounds;
+        let mut bounds1 = node_bounds;
+        bounds0.p_max[best_axis] = t_split;
+        bounds1.p_min[best_axis] = t_split;
+        let node_num = self.nodes.len();
+        // Placeholder until the above child's offset is known.
+        self.nodes.push(KdAccelNode::Interior {
+            split_axis: best_axis as u8,
+            split_pos: t_split,
+            above_child: 0,
+        });
+        self.build_tree(bounds0, all_prim_bounds, prims0, depth - 1, bad_refines);
+        let above = self.nodes.len();
+        self.nodes[node_num] = KdAccelNode::Interior {
+            split_axis: best_axis as u8,
+            split_pos: t_split,
+            above_child: above,
+        };
+        self.build_tree(bounds1, all_prim_bounds, prims1, depth - 1, bad_refines);
+    }
+
+    /// Walks the tree front to back along `r`, calling `f` with each primitive in a leaf `r`
+    /// passes through.  Traversal stops early if `f` returns true.
+    fn traverse<F>(&self, r: &Ray, mut f: F)
+    where
+        F: FnMut(&Arc<dyn Primitive>) -> bool,
+    {
+        // Compute initial parametric range of ray inside kd-tree extent
+        if self.nodes.is_empty() {
+            return;
+        }
+        let (mut t_min, mut t_max) = match self.bounds.intersect_p(r) {
+            Some(range) => range,
+            None => return,
+        };
+
+        // Prepare to traverse kd-tree for ray
+        let inv_dir = Vector3f::from([1. / r.d.x, 1. / r.d.y, 1. / r.d.z]);
+        let mut todo = [KdToDo::default(); MAX_TODO];
+        let mut todo_pos = 0;
+
+        // Traverse kd-tree nodes in order for ray
+        let mut node = 0;
+        loop {
+            // Bail out if we found a hit closer than the current node
+            if r.t_max.get() < t_min {
+                break;
+            }
+            match self.nodes[node] {
+                KdAccelNode::Interior {
+                    split_axis,
+                    split_pos,
+                    above_child,
+                } => {
+                    // Compute parametric distance along ray to split plane
+                    let axis = split_axis as usize;
+                    let t_plane = (split_pos - r.o[axis]) * inv_dir[axis];
+
+                    // Get node children pointers for ray
+                    let below_first =
+                        r.o[axis] < split_pos || (r.o[axis] == split_pos && r.d[axis] <= 0.);
+                    let (first_child, second_child) = if below_first {
+                        (node + 1, above_child)
+                    } else {
+                        (above_child, node + 1)
+                    };
+
+                    // Advance to next child node, possibly enqueue other child
+                    if t_plane > t_max || t_plane <= 0. {
+                        node = first_child;
+                    } else if t_plane < t_min {
+                        node = second_child;
+                    } else {
+                        // Enqueue _second_child_ in todo list
+                        todo[todo_pos] = KdToDo {
+                            node: second_child,
+                            t_min: t_plane,
+                            t_max,
+                        };
+                        todo_pos += 1;
+                        node = first_child;
+                        t_max = t_plane;
+                    }
+                    continue;
+                }
+                KdAccelNode::Leaf {
+                    primitive_indices_offset,
+                    n_primitives,
+                } => {
+                    // Check for intersections inside leaf node
+                    let indices = &self.primitive_indices
+                        [primitive_indices_offset..primitive_indices_offset + n_primitives];
+                    for &i in indices {
+                        if f(&self.primitives[i]) {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            // Grab next node to process from todo list
+            if todo_pos == 0 {
+                break;
+            }
+            todo_pos -= 1;
+            node = todo[todo_pos].node;
+            t_min = todo[todo_pos].t_min;
+            t_max = todo[todo_pos].t_max;
+        }
+    }
+}
+
+impl Primitive for KdTreeAccel {
+    fn world_bound(&self) -> Bounds3f {
+        self.bounds
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        let mut isect = None;
+        self.traverse(r, |p| {
+            // Primitives shorten `r.t_max` on a hit, so later hits are always closer.
+            if let Some(si) = p.intersect(r) {
+                isect = Some(si);
+            }
+            false
+        });
+        isect
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        let mut hit = false;
+        self.traverse(r, |p| {
+            hit = p.intersect_p(r);
+            hit
+        });
+        hit
+    }
+}
+
+/// Creates a [KdTreeAccel] over `prims`, reading "intersectcost", "traversalcost", "emptybonus",
+/// "maxprims" and "maxdepth" from `params`.
+pub fn create_kdtree_accelerator(prims: Vec<Arc<dyn Primitive>>, params: &ParamSet) -> KdTreeAccel {
+    let isect_cost = params.find_one_int("intersectcost", 80) as Float;
+    let trav_cost = params.find_one_int("traversalcost", 1) as Float;
+    let empty_bonus = params.find_one_float("emptybonus", 0.5);
+    let max_prims = params.find_one_int("maxprims", 1).max(1) as usize;
+    let max_depth = params.find_one_int("maxdepth", -1);
+    KdTreeAccel::new(
+        prims,
+        isect_cost,
+        trav_cost,
+        empty_bonus,
+        max_prims,
+        max_depth,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use log::info;
+
+    use super::{create_kdtree_accelerator, KdTreeAccel};
+    use crate::{
+        accelerators::{
+            bvh::{BVHAccel, SplitMethod},
+            testutils::{brute_force, bumpy_sphere, random_boxes, random_ray},
+        },
+        core::{
+            geometry::{Ray, Vector3f},
+            paramset::ParamSet,
+            primitive::Primitive,
+            rng::Rng,
+        },
+        Float,
+    };
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng::new(0);
+        let prims = random_boxes(&mut rng, 500);
+        for (max_prims, max_depth) in [(1, -1), (4, -1), (1, 3)] {
+            let kd = KdTreeAccel::new(prims.clone(), 80., 1., 0.5, max_prims, max_depth);
+            let mut hits = 0;
+            for _ in 0..1000 {
+                let r = random_ray(&mut rng);
+                let want = brute_force(&prims, &r.clone());
+                let got = kd.intersect(&r).map(|_| r.t_max.get());
+                assert_eq!(got, want, "{:?}", r);
+                let r = Ray {
+                    t_max: Float::INFINITY.into(),
+                    ..r
+                };
+                assert_eq!(kd.intersect_p(&r), want.is_some());
+                if want.is_some() {
+                    hits += 1;
+                }
+            }
+            // Make sure the test exercises both outcomes.
+            assert!(hits > 0 && hits < 1000, "{} hits", hits);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let kd = create_kdtree_accelerator(Vec::new(), &ParamSet::default());
+        let r = Ray {
+            d: Vector3f::from([0., 0., 1.]),
+            ..Default::default()
+        };
+        assert!(kd.intersect(&r).is_none());
+        assert!(!kd.intersect_p(&r));
+    }
+
+    /// Traces the same rays through a k-d tree and a BVH built over a triangle mesh, checking
+    /// they agree and logging how long each took.
+    fn compare_with_bvh(n_theta: usize, n_phi: usize, n_rays: usize) {
+        let prims = bumpy_sphere(n_theta, n_phi);
+        let start = Instant::now();
+        let kd = create_kdtree_accelerator(prims.clone(), &ParamSet::default());
+        let kd_build = start.elapsed();
+        let start = Instant::now();
+        let bvh = BVHAccel::new(prims.clone(), 4, SplitMethod::Sah);
+        let bvh_build = start.elapsed();
+
+        let mut rng = Rng::new(1);
+        let rays: Vec<_> = (0..n_rays).map(|_| random_ray(&mut rng)).collect();
+        let trace = |accel: &dyn Primitive| {
+            let start = Instant::now();
+            let hits: Vec<_> = rays
+                .iter()
+                .map(|r| {
+                    let r = r.clone();
+                    accel.intersect(&r).map(|_| r.t_max.get())
+                })
+                .collect();
+            (hits, start.elapsed())
+        };
+        let (kd_hits, kd_trace) = trace(&kd);
+        let (bvh_hits, bvh_trace) = trace(&bvh);
+        assert_eq!(kd_hits, bvh_hits);
+        assert!(kd_hits.iter().any(Option::is_some));
+        info!(
+            "{} triangles, {} rays: kdtree build {:?} trace {:?}, bvh build {:?} trace {:?}",
+            prims.len(),
+            n_rays,
+            kd_build,
+            kd_trace,
+            bvh_build,
+            bvh_trace
+        );
+    }
+
+    #[test]
+    fn matches_bvh_on_triangle_mesh() {
+        compare_with_bvh(32, 64, 2000);
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture perf_vs_bvh` to compare timings.
+    #[test]
+    #[ignore]
+    fn perf_vs_bvh() {
+        compare_with_bvh(256, 512, 200_000);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Primitives and rays shared by the acceleration structure tests.
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        geometry::{cross, dot, Bounds3f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        primitive::Primitive,
+        rng::Rng,
+    },
+    float::consts::PI,
+    Float,
+};
+
+/// Axis aligned box that reports hits on its surface.
+#[derive(Debug)]
+pub(crate) struct TestBox {
+    pub(crate) bounds: Bounds3f,
+}
+
+impl Primitive for TestBox {
+    fn world_bound(&self) -> Bounds3f {
+        self.bounds
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        let (t0, _) = self.bounds.intersect_p(r)?;
+        r.t_max.set(t0);
+        Some(SurfaceInteraction {
+            it: Interaction {
+                p: r.at(t0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        self.bounds.intersect_p(r).is_some()
+    }
+}
+
+/// Single sided triangle, intersected with the Möller-Trumbore algorithm.
+#[derive(Debug)]
+pub(crate) struct TestTriangle {
+    p: [Point3f; 3],
+}
+
+impl TestTriangle {
+    /// Returns the parametric distance along `r` of its intersection with the triangle.
+    fn hit(&self, r: &Ray) -> Option<Float> {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+        let pvec = cross(r.d, e2);
+        let det = dot(e1, pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1. / det;
+        let tvec = r.o - self.p[0];
+        let u = dot(tvec, pvec) * inv_det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+        let qvec = cross(tvec, e1);
+        let v = dot(r.d, qvec) * inv_det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+        let t = dot(e2, qvec) * inv_det;
+        if t <= 0. || t >= r.t_max.get() {
+            return None;
+        }
+        Some(t)
+    }
+}
+
+impl Primitive for TestTriangle {
+    fn world_bound(&self) -> Bounds3f {
+        Bounds3f::union_point(&Bounds3f::from([self.p[0], self.p[1]]), self.p[2])
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        let t = self.hit(r)?;
+        r.t_max.set(t);
+        Some(SurfaceInteraction {
+            it: Interaction {
+                p: r.at(t),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        self.hit(r).is_some()
+    }
+}
+
+/// Returns `n` randomly placed boxes in [-10, 10]^3.
+pub(crate) fn random_boxes(rng: &mut Rng, n: usize) -> Vec<Arc<dyn Primitive>> {
+    (0..n)
+        .map(|_| {
+            let p = Point3f::from([
+                rng.uniform_float() * 20. - 10.,
+                rng.uniform_float() * 20. - 10.,
+                rng.uniform_float() * 20. - 10.,
+            ]);
+            let size = rng.uniform_float() * 0.5 + 0.1;
+            let b: Arc<dyn Primitive> = Arc::new(TestBox {
+                bounds: Bounds3f::from([p, p + Vector3f::from([size, size, size])]),
+            });
+            b
+        })
+        .collect()
+}
+
+/// Returns a triangulated bumpy sphere of radius about 8 centered at the origin, with
+/// `2 * n_theta * n_phi` triangles.
+pub(crate) fn bumpy_sphere(n_theta: usize, n_phi: usize) -> Vec<Arc<dyn Primitive>> {
+    let vertex = |i: usize, j: usize| {
+        let theta = PI * i as Float / n_theta as Float;
+        let phi = 2. * PI * j as Float / n_phi as Float;
+        let r = 8. + 0.5 * (5. * theta).sin() * (7. * phi).cos();
+        Point3f::from([
+            r * theta.sin() * phi.cos(),
+            r * theta.sin() * phi.sin(),
+            r * theta.cos(),
+        ])
+    };
+    let mut tris: Vec<Arc<dyn Primitive>> = Vec::with_capacity(2 * n_theta * n_phi);
+    for i in 0..n_theta {
+        for j in 0..n_phi {
+            let (p00, p01) = (vertex(i, j), vertex(i, j + 1));
+            let (p10, p11) = (vertex(i + 1, j), vertex(i + 1, j + 1));
+            tris.push(Arc::new(TestTriangle { p: [p00, p10, p11] }));
+            tris.push(Arc::new(TestTriangle { p: [p00, p11, p01] }));
+        }
+    }
+    tris
+}
+
+/// Returns a ray starting outside of [-15, 15]^2 x [-20, ...) aimed at a random point in
+/// [-10, 10]^3.
+pub(crate) fn random_ray(rng: &mut Rng) -> Ray {
+    let o = Point3f::from([
+        rng.uniform_float() * 30. - 15.,
+        rng.uniform_float() * 30. - 15.,
+        -20.,
+    ]);
+    let target = Point3f::from([
+        rng.uniform_float() * 20. - 10.,
+        rng.uniform_float() * 20. - 10.,
+        rng.uniform_float() * 20. - 10.,
+    ]);
+    Ray {
+        o,
+        d: target - o,
+        ..Default::default()
+    }
+}
+
+/// Closest hit found by testing every primitive.
+pub(crate) fn brute_force(prims: &[Arc<dyn Primitive>], r: &Ray) -> Option<Float> {
+    let mut hit = None;
+    for p in prims {
+        if p.intersect(r).is_some() {
+            hit = Some(r.t_max.get());
+        }
+    }
+    hit
+}
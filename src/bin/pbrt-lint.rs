@@ -0,0 +1,161 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::process;
+
+use anyhow::Result;
+use structopt::{self, StructOpt};
+
+use pbrt::core::lint::{Finding, LintAPI};
+
+#[derive(Clone, Debug, Default, StructOpt)]
+#[structopt(
+    name = "pbrt-lint",
+    about = "Validates pbrt scene files without rendering them"
+)]
+pub struct Options {
+    #[structopt(long = "json")]
+    /// Print the findings as a JSON array instead of one per line.
+    pub json: bool,
+    #[structopt(short = "v", long = "verbose")]
+    /// Print out more detailed logging information.
+    pub verbose: bool,
+    /// Scene files to check, `-` reads the scene from stdin.
+    pub scene_files: Vec<String>,
+}
+
+/// Returns `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Returns `findings` as a JSON array of objects, one per line.  This matches how the library's
+/// optional serde support serializes a `Finding`, without making serde_json a dependency.
+fn json_report(findings: &[Finding]) -> String {
+    let mut out = String::from("[\n");
+    for (i, f) in findings.iter().enumerate() {
+        out.push_str(&format!(
+            "  {{\"file\": {}, \"kind\": {}, \"directive\": {}, \"message\": {}}}{}\n",
+            json_string(&f.file),
+            json_string(f.kind.name()),
+            json_string(&f.directive),
+            json_string(&f.message),
+            if i + 1 < findings.len() { "," } else { "" }
+        ));
+    }
+    out.push_str("]\n");
+    out
+}
+
+fn main() -> Result<()> {
+    let flags = Options::from_args();
+    // Findings are the output, only show the renderer's own logging when asked.
+    stderrlog::new()
+        .verbosity(if flags.verbose { 3 } else { 0 })
+        .init()?;
+
+    if flags.scene_files.is_empty() {
+        println!("One or more scene files required.\n");
+        Options::clap().print_help()?;
+        process::exit(1);
+    }
+
+    let mut lint = LintAPI::default();
+    for f in &flags.scene_files {
+        lint.lint_file(f);
+    }
+    if flags.json {
+        print!("{}", json_report(lint.findings()));
+    } else {
+        for f in lint.findings() {
+            println!("{}", f);
+        }
+    }
+    if !lint.findings().is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pbrt::core::lint::FindingKind;
+
+    use super::*;
+
+    #[test]
+    fn json_string_escapes() {
+        for s in &[
+            "",
+            "plain",
+            "\"quoted\"",
+            "back\\slash",
+            "line\nbreak\r\ttab",
+            "bell\u{7}and\u{1f}unit",
+            "unicode \u{e9}\u{1f600}",
+        ] {
+            let json = json_string(s);
+            let got: String = serde_json::from_str(&json).expect(&json);
+            assert_eq!(&got, s, "{}", json);
+        }
+        assert_eq!(json_string("a\"b\\c\u{1}"), r#""a\"b\\c\u0001""#);
+    }
+
+    #[test]
+    fn json_report_parses() {
+        assert_eq!(json_report(&[]), "[\n]\n");
+        let findings = vec![
+            Finding {
+                file: "a \"b\".pbrt".to_string(),
+                kind: FindingKind::UnknownType,
+                directive: "Shape".to_string(),
+                message: "shape \"blob\\1\" unknown".to_string(),
+            },
+            Finding {
+                file: String::new(),
+                kind: FindingKind::UnbalancedBlock,
+                directive: "WorldEnd".to_string(),
+                message: "missing\nAttributeEnd".to_string(),
+            },
+        ];
+        let report: serde_json::Value =
+            serde_json::from_str(&json_report(&findings)).expect("invalid JSON report");
+        let report = report.as_array().expect("findings array");
+        assert_eq!(report.len(), findings.len());
+        for (got, want) in report.iter().zip(&findings) {
+            assert_eq!(got["file"], want.file.as_str());
+            assert_eq!(got["kind"], want.kind.name());
+            assert_eq!(got["directive"], want.directive.as_str());
+            assert_eq!(got["message"], want.message.as_str());
+        }
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            report,
+            &serde_json::to_value(&findings).unwrap().as_array().unwrap()[..]
+        );
+    }
+}
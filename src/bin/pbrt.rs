@@ -11,7 +11,7 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::process;
+use std::{env, process};
 
 use anyhow::{Context, Result};
 use log::info;
@@ -19,14 +19,18 @@ use structopt::{self, StructOpt};
 
 use pbrt::{
     self,
-    core::api::{PbrtAPI, API},
+    core::{
+        api::{PbrtAPI, API},
+        film::ToneMapper,
+        geometry::Bounds2i,
+    },
 };
 
 #[derive(Clone, Debug, Default, StructOpt)]
 #[structopt(name = "pbrt", about = "Rust implementation of http://pbrt.org/")]
 pub struct Options {
     #[structopt(short = "n", long = "nthreads")]
-    /// Use specified number of threads for rendering.
+    /// Use specified number of threads for rendering, by default one per core.
     pub num_threads: Option<u32>,
     #[structopt(long = "quick")]
     /// Automatically reduce a number of quality settings to render more quickly.
@@ -40,9 +44,40 @@ pub struct Options {
     #[structopt(short = "o", long = "outfile")]
     /// Write the final image to the given filename.
     pub image_file: Option<String>,
+    #[structopt(long = "no-mmap")]
+    /// Read scene files into memory instead of memory mapping them.
+    pub no_mmap: bool,
+    #[structopt(long = "cat")]
+    /// Print a reformatted version of the input file(s) to standard output instead of rendering.
+    pub cat: bool,
+    #[structopt(long = "toply")]
+    /// Like --cat, but also write triangle meshes to PLY files in the current directory, named
+    /// with the prefix in $PLY_PREFIX, "mesh" by default.
+    pub to_ply: bool,
+    #[structopt(long = "tonemap", default_value = "clamp")]
+    /// Tone mapping operator for 8-bit images, one of "clamp", "reinhard" or "exposure=<stops>".
+    pub tone_mapper: ToneMapper,
+    #[structopt(long = "pixelbounds", use_delimiter = true, number_of_values = 4)]
+    /// Only render the pixels from x0 to x1 and y0 to y1, given as "x0,x1,y0,y1".  The upper
+    /// bounds are exclusive.
+    pub pixel_bounds: Option<Vec<isize>>,
+    /// Scene files to render, `-` reads the scene from stdin.
     pub scene_files: Vec<String>,
 }
 
+/// Prints the render settings parsed from the scene, in scene file syntax.
+fn print_settings(pbrt: &PbrtAPI) {
+    for (directive, (name, params)) in &[
+        ("Film", pbrt.film_settings()),
+        ("Camera", pbrt.camera_settings()),
+        ("Sampler", pbrt.sampler_settings()),
+        ("Integrator", pbrt.integrator_settings()),
+        ("Accelerator", pbrt.accelerator_settings()),
+    ] {
+        println!("  {} \"{}\" {}", directive, name, params);
+    }
+}
+
 fn main() -> Result<()> {
     let flags = Options::from_args();
     let verbosity = if flags.verbose {
@@ -69,19 +104,31 @@ fn main() -> Result<()> {
 
     info!("Options: {:#?}", &flags);
     let opts = pbrt::Options {
-        num_threads: flags.num_threads.unwrap_or(1),
+        num_threads: flags.num_threads.unwrap_or(0),
         quick_render: flags.quick_render,
         quiet: flags.quiet,
         verbose: flags.verbose,
         image_file: flags.image_file.unwrap_or_else(|| "".to_owned()),
+        no_mmap: flags.no_mmap,
+        cat: flags.cat,
+        to_ply: flags.to_ply,
+        tone_mapper: flags.tone_mapper,
+        pixel_bounds: flags
+            .pixel_bounds
+            .map(|b| Bounds2i::from([[b[0], b[2]], [b[1], b[3]]])),
     };
     let pbrt = &mut PbrtAPI::from(opts.clone());
+    // Like the C++ implementation, name the PLY files written by --toply after $PLY_PREFIX.
+    if let Ok(prefix) = env::var("PLY_PREFIX") {
+        pbrt.set_ply_prefix(&prefix);
+    }
     pbrt.init();
     for f in &flags.scene_files {
-        pbrt.parse_file(&f)
+        pbrt.parse_file(f)
             .with_context(|| format!("failed to parse {}", f))?;
-        if opts.verbose {
-            println!("Rendered {}\n{:#?}", f, pbrt);
+        if opts.verbose && !opts.cat && !opts.to_ply {
+            println!("Rendered {}", f);
+            print_settings(pbrt);
         }
     }
     pbrt.cleanup();
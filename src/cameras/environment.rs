@@ -0,0 +1,243 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Camera] implementation for an equirectangular projection of the full sphere of directions.
+//!
+//! [Camera]: crate::core::camera::Camera
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        camera::{shutter_times, Camera, CameraData, CameraSample},
+        film::Film,
+        geometry::{Point3f, Ray, Vector3f},
+        medium::Medium,
+        paramset::ParamSet,
+        transform::AnimatedTransform,
+    },
+    float::consts::PI,
+    Float,
+};
+
+/// EnvironmentCamera traces rays in every direction from a single point, mapping the film's x
+/// axis to the azimuth φ in [0, 2π) and its y axis to the polar angle θ in [0, π] measured from
+/// the camera space +y axis.  The result is an equirectangular panorama.
+#[derive(Debug, Clone)]
+pub struct EnvironmentCamera {
+    data: CameraData,
+}
+
+impl EnvironmentCamera {
+    /// Create a new `EnvironmentCamera` positioned by `camera_to_world`.
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        shutter_open: Float,
+        shutter_close: Float,
+        film: Arc<Film>,
+        medium: Option<Arc<dyn Medium>>,
+    ) -> EnvironmentCamera {
+        EnvironmentCamera {
+            data: CameraData::new(camera_to_world, shutter_open, shutter_close, film, medium),
+        }
+    }
+}
+
+impl Camera for EnvironmentCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> (Float, Ray) {
+        // Compute environment camera ray direction
+        let res = self.data.film.full_resolution;
+        let theta = PI * sample.p_film.y / res.y as Float;
+        let phi = 2. * PI * sample.p_film.x / res.x as Float;
+        let dir = Vector3f::from([
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        ]);
+        let ray = Ray::new(
+            Point3f::from([0., 0., 0.]),
+            dir,
+            Float::INFINITY,
+            self.data.time(sample.time),
+            self.data.medium.clone(),
+        );
+        (1., self.data.camera_to_world.transform_ray(&ray))
+    }
+
+    fn film(&self) -> Arc<Film> {
+        Arc::clone(&self.data.film)
+    }
+
+    fn shutter_open(&self) -> Float {
+        self.data.shutter_open
+    }
+
+    fn shutter_close(&self) -> Float {
+        self.data.shutter_close
+    }
+}
+
+/// Creates an `EnvironmentCamera` from the given `params`.  Only the shutter times are read, the
+/// film's resolution determines the mapping of pixels to directions.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use pbrt::{
+///     cameras::environment::create_environment_camera,
+///     core::{
+///         camera::{Camera, CameraSample},
+///         film::create_film,
+///         paramset::ParamSet,
+///         transform::{AnimatedTransform, Transform},
+///     },
+///     filters::r#box::BoxFilter,
+///     Options,
+/// };
+///
+/// let film = create_film(
+///     &ParamSet::default(),
+///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+///     &Options::default(),
+/// )
+/// .unwrap();
+/// let camera = create_environment_camera(
+///     &ParamSet::default(),
+///     &AnimatedTransform::from(Transform::identity()),
+///     Arc::new(film),
+///     None,
+/// );
+/// // The top row of the image looks straight up.
+/// let (w, r) = camera.generate_ray(&CameraSample {
+///     p_film: [100., 0.].into(),
+///     ..Default::default()
+/// });
+/// assert_eq!(w, 1.);
+/// assert_eq!(r.o, [0., 0., 0.].into());
+/// assert!((r.d.y - 1.).abs() < 1e-5);
+/// ```
+pub fn create_environment_camera(
+    params: &ParamSet,
+    cam2world: &AnimatedTransform,
+    film: Arc<Film>,
+    medium: Option<Arc<dyn Medium>>,
+) -> EnvironmentCamera {
+    let (shutter_open, shutter_close) = shutter_times(params);
+    EnvironmentCamera::new(*cam2world, shutter_open, shutter_close, film, medium)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::create_environment_camera;
+    use crate::{
+        core::{
+            camera::{Camera, CameraSample},
+            film::create_film,
+            geometry::{Point2f, Vector3f},
+            paramset::ParamSet,
+            transform::{AnimatedTransform, Transform},
+        },
+        filters::r#box::BoxFilter,
+        Degree, Float, Options,
+    };
+
+    #[test]
+    fn opposite_directions() {
+        let film = Arc::new(
+            create_film(
+                &ParamSet::default(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                &Options::default(),
+            )
+            .unwrap(),
+        );
+        let (w, h) = (
+            film.full_resolution.x as Float,
+            film.full_resolution.y as Float,
+        );
+        let cam2world = Transform::rotate(Degree::from(30.), [1., 1., 0.]);
+        let camera = create_environment_camera(
+            &ParamSet::default(),
+            &AnimatedTransform::from(cam2world),
+            film,
+            None,
+        );
+        let dir = |x: Float, y: Float| {
+            let (wt, r) = camera.generate_ray(&CameraSample {
+                p_film: Point2f::from([x, y]),
+                ..Default::default()
+            });
+            assert_eq!(wt, 1.);
+            assert!((r.d.length() - 1.).abs() < 1e-5);
+            r.d
+        };
+        let close = |a: Vector3f, b: Vector3f| (a - b).length() < 1e-4;
+
+        // Points half the image width apart on the horizon look in opposite directions.
+        for x in [0., w / 4., w / 3.] {
+            let (left, right) = (dir(x, h / 2.), dir(x + w / 2., h / 2.));
+            assert!(close(left, -right), "{:?} {:?}", left, right);
+        }
+        // The top and bottom rows look in opposite directions along camera space y.
+        let up = cam2world.transform_vector(Vector3f::from([0., 1., 0.]));
+        assert!(close(dir(w / 3., 0.), up));
+        assert!(close(dir(w / 3., h), -up));
+        // The left and right edges meet at the seam of the panorama.
+        assert!(close(dir(0., h / 3.), dir(w, h / 3.)));
+    }
+
+    #[test]
+    fn center_and_poles() {
+        let film = Arc::new(
+            create_film(
+                &ParamSet::default(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                &Options::default(),
+            )
+            .unwrap(),
+        );
+        let (w, h) = (
+            film.full_resolution.x as Float,
+            film.full_resolution.y as Float,
+        );
+        let camera = create_environment_camera(
+            &ParamSet::default(),
+            &AnimatedTransform::from(Transform::identity()),
+            film,
+            None,
+        );
+        let dir = |x: Float, y: Float| {
+            camera
+                .generate_ray(&CameraSample {
+                    p_film: Point2f::from([x, y]),
+                    ..Default::default()
+                })
+                .1
+                .d
+        };
+        let close = |a: Vector3f, b: [Float; 3]| (a - Vector3f::from(b)).length() < 1e-4;
+
+        // φ = π at the center of the image, so it looks down -x on the horizon.
+        assert!(close(dir(w / 2., h / 2.), [-1., 0., 0.]));
+        // A quarter of the way across is φ = π/2, looking down +z.
+        assert!(close(dir(w / 4., h / 2.), [0., 0., 1.]));
+        // All four corners map to the poles.
+        assert!(close(dir(0., 0.), [0., 1., 0.]));
+        assert!(close(dir(w, 0.), [0., 1., 0.]));
+        assert!(close(dir(0., h), [0., -1., 0.]));
+        assert!(close(dir(w, h), [0., -1., 0.]));
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Camera] implementation for an orthographic projection.
+//!
+//! [Camera]: crate::core::camera::Camera
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        camera::{
+            screen_window, shutter_times, Camera, CameraData, CameraSample, ProjectiveCameraData,
+        },
+        film::Film,
+        geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential, Vector3f},
+        medium::Medium,
+        paramset::ParamSet,
+        sampling::concentric_sample_disk,
+        transform::{AnimatedTransform, Transform},
+    },
+    Float,
+};
+
+/// OrthographicCamera projects the scene onto the film along parallel rays, so objects appear
+/// the same size regardless of their distance from the camera.  All rays travel down the camera
+/// space z axis.
+#[derive(Debug, Clone)]
+pub struct OrthographicCamera {
+    data: ProjectiveCameraData,
+    dx_camera: Vector3f,
+    dy_camera: Vector3f,
+}
+
+impl OrthographicCamera {
+    /// Create a new `OrthographicCamera` imaging the region `screen_window` of the camera's
+    /// x-y plane.  A non-zero `lens_radius` simulates a lens focused at `focal_distance`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        screen_window: Bounds2f,
+        shutter_open: Float,
+        shutter_close: Float,
+        lens_radius: Float,
+        focal_distance: Float,
+        film: Arc<Film>,
+        medium: Option<Arc<dyn Medium>>,
+    ) -> OrthographicCamera {
+        let data = ProjectiveCameraData::new(
+            CameraData::new(camera_to_world, shutter_open, shutter_close, film, medium),
+            Transform::orthographic(0., 1.),
+            screen_window,
+            lens_radius,
+            focal_distance,
+        );
+        // Compute differential changes in origin for orthographic camera rays
+        let dx_camera = data
+            .raster_to_camera
+            .transform_vector(Vector3f::from([1., 0., 0.]));
+        let dy_camera = data
+            .raster_to_camera
+            .transform_vector(Vector3f::from([0., 1., 0.]));
+        OrthographicCamera {
+            data,
+            dx_camera,
+            dy_camera,
+        }
+    }
+
+    /// Returns the camera space ray for `sample`, before any lens effects.
+    fn camera_ray(&self, sample: &CameraSample) -> Ray {
+        Ray::new(
+            self.data.raster_to_camera(sample.p_film),
+            Vector3f::from([0., 0., 1.]),
+            Float::INFINITY,
+            self.data.camera.time(sample.time),
+            self.data.camera.medium.clone(),
+        )
+    }
+
+    /// Returns the origin and direction of a ray from a point on the lens chosen by `p_lens`
+    /// that passes through the plane of focus where a ray from `o` along +z would.
+    fn lens_ray(&self, o: Point3f, p_lens: Point2f) -> (Point3f, Vector3f) {
+        let p_lens = concentric_sample_disk(p_lens) * self.data.lens_radius;
+        // The ray travels along +z, so it reaches the plane of focus at the focal distance.
+        let p_focus = o + Vector3f::from([0., 0., self.data.focal_distance]);
+        let o = Point3f::from([o.x + p_lens.x, o.y + p_lens.y, 0.]);
+        (o, (p_focus - o).normalize())
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> (Float, Ray) {
+        let mut ray = self.camera_ray(sample);
+        // Modify ray for depth of field
+        if self.data.lens_radius > 0. {
+            let (o, d) = self.lens_ray(ray.o, sample.p_lens);
+            ray.o = o;
+            ray.d = d;
+        }
+        (1., self.data.camera.camera_to_world.transform_ray(&ray))
+    }
+
+    fn generate_ray_differential(&self, sample: &CameraSample) -> (Float, RayDifferential) {
+        let mut ray = RayDifferential::from(self.camera_ray(sample));
+        let o = ray.o;
+        // Modify ray for depth of field
+        if self.data.lens_radius > 0. {
+            let (lens_o, d) = self.lens_ray(o, sample.p_lens);
+            ray.ray.o = lens_o;
+            ray.ray.d = d;
+            // Compute _OrthographicCamera_ ray differentials accounting for lens
+            let (rx_origin, rx_direction) = self.lens_ray(o + self.dx_camera, sample.p_lens);
+            let (ry_origin, ry_direction) = self.lens_ray(o + self.dy_camera, sample.p_lens);
+            ray.rx_origin = rx_origin;
+            ray.rx_direction = rx_direction;
+            ray.ry_origin = ry_origin;
+            ray.ry_direction = ry_direction;
+        } else {
+            ray.rx_origin = ray.o + self.dx_camera;
+            ray.ry_origin = ray.o + self.dy_camera;
+            ray.rx_direction = ray.d;
+            ray.ry_direction = ray.d;
+        }
+        ray.has_differentials = true;
+        (
+            1.,
+            self.data
+                .camera
+                .camera_to_world
+                .transform_ray_differential(&ray),
+        )
+    }
+
+    fn film(&self) -> Arc<Film> {
+        Arc::clone(&self.data.camera.film)
+    }
+
+    fn shutter_open(&self) -> Float {
+        self.data.camera.shutter_open
+    }
+
+    fn shutter_close(&self) -> Float {
+        self.data.camera.shutter_close
+    }
+}
+
+/// Creates an `OrthographicCamera` from the given `params`.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use pbrt::{
+///     cameras::orthographic::create_orthographic_camera,
+///     core::{
+///         camera::{Camera, CameraSample},
+///         film::create_film,
+///         paramset::ParamSet,
+///         transform::{AnimatedTransform, Transform},
+///     },
+///     filters::r#box::BoxFilter,
+///     Options,
+/// };
+///
+/// let film = create_film(
+///     &ParamSet::default(),
+///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+///     &Options::default(),
+/// )
+/// .unwrap();
+/// let camera = create_orthographic_camera(
+///     &ParamSet::default(),
+///     &AnimatedTransform::from(Transform::identity()),
+///     Arc::new(film),
+///     None,
+/// );
+/// // The center of the film is at the origin, looking straight down the z-axis.
+/// let (w, r) = camera.generate_ray(&CameraSample {
+///     p_film: [640., 360.].into(),
+///     ..Default::default()
+/// });
+/// assert_eq!(w, 1.);
+/// assert!(r.o.x.abs() < 1e-5 && r.o.y.abs() < 1e-5);
+/// assert_eq!(r.d, [0., 0., 1.].into());
+/// ```
+pub fn create_orthographic_camera(
+    params: &ParamSet,
+    cam2world: &AnimatedTransform,
+    film: Arc<Film>,
+    medium: Option<Arc<dyn Medium>>,
+) -> OrthographicCamera {
+    let (shutter_open, shutter_close) = shutter_times(params);
+    let lens_radius = params.find_one_float("lensradius", 0.);
+    let focal_distance = params.find_one_float("focaldistance", 1e6);
+    let screen = screen_window(params, &film);
+    OrthographicCamera::new(
+        *cam2world,
+        screen,
+        shutter_open,
+        shutter_close,
+        lens_radius,
+        focal_distance,
+        film,
+        medium,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::create_orthographic_camera;
+    use crate::{
+        core::{
+            camera::{Camera, CameraSample},
+            film::create_film,
+            geometry::{Point2f, Point3f, Vector3f},
+            paramset::ParamSet,
+            transform::{AnimatedTransform, Transform},
+        },
+        filters::r#box::BoxFilter,
+        Degree, Options,
+    };
+
+    fn film() -> Arc<crate::core::film::Film> {
+        Arc::new(
+            create_film(
+                &ParamSet::default(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                &Options::default(),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn parallel_rays() {
+        let cam2world = Transform::rotate(Degree::from(90.), [0., 1., 0.]);
+        let camera = create_orthographic_camera(
+            &ParamSet::default(),
+            &AnimatedTransform::from(cam2world),
+            film(),
+            None,
+        );
+        let want = cam2world.transform_vector(Vector3f::from([0., 0., 1.]));
+        let mut origins = Vec::new();
+        for p_film in [[0., 0.], [640., 360.], [1279., 719.], [100., 600.]] {
+            let sample = CameraSample {
+                p_film: Point2f::from(p_film),
+                ..Default::default()
+            };
+            let (w, r) = camera.generate_ray(&sample);
+            assert_eq!(w, 1.);
+            assert!((r.d - want).length() < 1e-5, "{:?} != {:?}", r.d, want);
+            origins.push(r.o);
+
+            let (_, rd) = camera.generate_ray_differential(&sample);
+            assert!(rd.has_differentials);
+            assert!((rd.rx_direction - want).length() < 1e-5);
+            assert!((rd.ry_direction - want).length() < 1e-5);
+            assert!((rd.rx_origin - rd.o).length() > 0.);
+        }
+        // Different film positions give different origins.
+        assert!((origins[0] - origins[2]).length() > 1.);
+    }
+
+    #[test]
+    fn raster_positions() {
+        // The default 1280x720 film maps to the screen window [-16/9, 16/9] x [-1, 1], with
+        // raster y increasing downwards.
+        let camera = create_orthographic_camera(
+            &ParamSet::default(),
+            &AnimatedTransform::from(Transform::translate([0., 0., -5.])),
+            film(),
+            None,
+        );
+        let aspect = 1280. / 720.;
+        for (p_film, want) in [
+            ([0., 0.], [-aspect, 1., -5.]),
+            ([1280., 720.], [aspect, -1., -5.]),
+            ([640., 360.], [0., 0., -5.]),
+            ([960., 180.], [aspect / 2., 0.5, -5.]),
+        ] {
+            let (_, r) = camera.generate_ray(&CameraSample {
+                p_film: Point2f::from(p_film),
+                ..Default::default()
+            });
+            let want = Point3f::from(want);
+            assert!(
+                (r.o - want).length() < 1e-4,
+                "{:?}: {} != {}",
+                p_film,
+                r.o,
+                want
+            );
+            assert_eq!(r.d, Vector3f::from([0., 0., 1.]));
+        }
+    }
+
+    #[test]
+    fn lens_focuses() {
+        let mut params = ParamSet::default();
+        params.add_float("lensradius", vec![0.5]);
+        params.add_float("focaldistance", vec![10.]);
+        let camera = create_orthographic_camera(
+            &params,
+            &AnimatedTransform::from(Transform::identity()),
+            film(),
+            None,
+        );
+        // Rays through different points on the lens meet at the plane of focus.
+        let sample = |p_lens: [crate::Float; 2]| CameraSample {
+            p_film: Point2f::from([320., 200.]),
+            p_lens: Point2f::from(p_lens),
+            ..Default::default()
+        };
+        let (_, r0) = camera.generate_ray(&sample([0.1, 0.2]));
+        let (_, r1) = camera.generate_ray(&sample([0.9, 0.7]));
+        assert!((r0.o - r1.o).length() > 0.1);
+        let p0 = r0.at(10. / r0.d.z);
+        let p1 = r1.at(10. / r1.d.z);
+        assert!((p0 - p1).length() < 1e-4, "{} != {}", p0, p1);
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Camera] implementation for a pinhole camera with perspective projection.
+//!
+//! [Camera]: crate::core::camera::Camera
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        camera::{
+            screen_window, shutter_times, Camera, CameraData, CameraSample, ProjectiveCameraData,
+        },
+        film::Film,
+        geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential, Vector3f},
+        medium::Medium,
+        paramset::ParamSet,
+        sampling::concentric_sample_disk,
+        transform::{AnimatedTransform, Transform},
+    },
+    Float,
+};
+
+/// PerspectiveCamera projects the scene onto the film through a single point, objects further
+/// away from the camera appear smaller.
+#[derive(Debug, Clone)]
+pub struct PerspectiveCamera {
+    data: ProjectiveCameraData,
+    dx_camera: Vector3f,
+    dy_camera: Vector3f,
+}
+
+impl PerspectiveCamera {
+    /// Create a new `PerspectiveCamera` with a field of view of `fov` degrees across the shorter
+    /// axis of `screen_window`.  A non-zero `lens_radius` simulates a thin lens focused at
+    /// `focal_distance`, otherwise the camera is a pinhole.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        screen_window: Bounds2f,
+        shutter_open: Float,
+        shutter_close: Float,
+        lens_radius: Float,
+        focal_distance: Float,
+        fov: Float,
+        film: Arc<Film>,
+        medium: Option<Arc<dyn Medium>>,
+    ) -> PerspectiveCamera {
+        let data = ProjectiveCameraData::new(
+            CameraData::new(camera_to_world, shutter_open, shutter_close, film, medium),
+            Transform::perspective(fov, 1e-2, 1000.),
+            screen_window,
+            lens_radius,
+            focal_distance,
+        );
+        // Compute differential changes in origin for perspective camera rays
+        let origin = data.raster_to_camera(Point2f::from([0., 0.]));
+        let dx_camera = data.raster_to_camera(Point2f::from([1., 0.])) - origin;
+        let dy_camera = data.raster_to_camera(Point2f::from([0., 1.])) - origin;
+        PerspectiveCamera {
+            data,
+            dx_camera,
+            dy_camera,
+        }
+    }
+
+    /// Returns the origin and direction of a ray from a point on the lens chosen by `p_lens`
+    /// that passes through the plane of focus where the pinhole ray along `d` would.
+    fn lens_ray(&self, d: Vector3f, p_lens: Point2f) -> (Point3f, Vector3f) {
+        let p_lens = concentric_sample_disk(p_lens) * self.data.lens_radius;
+        // Compute point on plane of focus
+        let ft = self.data.focal_distance / d.z;
+        let p_focus = Point3f::from([0., 0., 0.]) + d * ft;
+        let o = Point3f::from([p_lens.x, p_lens.y, 0.]);
+        (o, (p_focus - o).normalize())
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> (Float, Ray) {
+        // Compute raster and camera sample positions
+        let p_camera = self.data.raster_to_camera(sample.p_film);
+        let mut ray = Ray::new(
+            Point3f::from([0., 0., 0.]),
+            Vector3f::from(p_camera).normalize(),
+            Float::INFINITY,
+            self.data.camera.time(sample.time),
+            self.data.camera.medium.clone(),
+        );
+        // Modify ray for depth of field
+        if self.data.lens_radius > 0. {
+            let (o, d) = self.lens_ray(ray.d, sample.p_lens);
+            ray.o = o;
+            ray.d = d;
+        }
+        (1., self.data.camera.camera_to_world.transform_ray(&ray))
+    }
+
+    fn generate_ray_differential(&self, sample: &CameraSample) -> (Float, RayDifferential) {
+        let p_camera = self.data.raster_to_camera(sample.p_film);
+        let dir = Vector3f::from(p_camera);
+        let mut ray = RayDifferential::from(Ray::new(
+            Point3f::from([0., 0., 0.]),
+            dir.normalize(),
+            Float::INFINITY,
+            self.data.camera.time(sample.time),
+            self.data.camera.medium.clone(),
+        ));
+        // Modify ray for depth of field
+        if self.data.lens_radius > 0. {
+            let (o, d) = self.lens_ray(ray.d, sample.p_lens);
+            ray.ray.o = o;
+            ray.ray.d = d;
+            // Compute _PerspectiveCamera_ ray differentials accounting for lens
+            let (rx_origin, rx_direction) =
+                self.lens_ray((dir + self.dx_camera).normalize(), sample.p_lens);
+            let (ry_origin, ry_direction) =
+                self.lens_ray((dir + self.dy_camera).normalize(), sample.p_lens);
+            ray.rx_origin = rx_origin;
+            ray.rx_direction = rx_direction;
+            ray.ry_origin = ry_origin;
+            ray.ry_direction = ry_direction;
+        } else {
+            ray.rx_origin = ray.o;
+            ray.ry_origin = ray.o;
+            ray.rx_direction = (dir + self.dx_camera).normalize();
+            ray.ry_direction = (dir + self.dy_camera).normalize();
+        }
+        ray.has_differentials = true;
+        (
+            1.,
+            self.data
+                .camera
+                .camera_to_world
+                .transform_ray_differential(&ray),
+        )
+    }
+
+    fn film(&self) -> Arc<Film> {
+        Arc::clone(&self.data.camera.film)
+    }
+
+    fn shutter_open(&self) -> Float {
+        self.data.camera.shutter_open
+    }
+
+    fn shutter_close(&self) -> Float {
+        self.data.camera.shutter_close
+    }
+}
+
+/// Creates a `PerspectiveCamera` from the given `params`.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use pbrt::{
+///     cameras::perspective::create_perspective_camera,
+///     core::{
+///         camera::{Camera, CameraSample},
+///         film::create_film,
+///         paramset::ParamSet,
+///         transform::{AnimatedTransform, Transform},
+///     },
+///     filters::r#box::BoxFilter,
+///     Options,
+/// };
+///
+/// let film = create_film(
+///     &ParamSet::default(),
+///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+///     &Options::default(),
+/// )
+/// .unwrap();
+/// let camera = create_perspective_camera(
+///     &ParamSet::default(),
+///     &AnimatedTransform::from(Transform::identity()),
+///     Arc::new(film),
+///     None,
+/// );
+/// // The center of the film looks straight down the z-axis.
+/// let (w, r) = camera.generate_ray(&CameraSample {
+///     p_film: [640., 360.].into(),
+///     ..Default::default()
+/// });
+/// assert_eq!(w, 1.);
+/// assert!((r.d.z - 1.).abs() < 1e-5);
+/// ```
+pub fn create_perspective_camera(
+    params: &ParamSet,
+    cam2world: &AnimatedTransform,
+    film: Arc<Film>,
+    medium: Option<Arc<dyn Medium>>,
+) -> PerspectiveCamera {
+    let (shutter_open, shutter_close) = shutter_times(params);
+    let lens_radius = params.find_one_float("lensradius", 0.);
+    let focal_distance = params.find_one_float("focaldistance", 1e6);
+    let screen = screen_window(params, &film);
+    let mut fov = params.find_one_float("fov", 90.);
+    let halffov = params.find_one_float("halffov", -1.);
+    if halffov > 0. {
+        // hack for structure synth, which exports half of the full fov
+        fov = 2. * halffov;
+    }
+    PerspectiveCamera::new(
+        *cam2world,
+        screen,
+        shutter_open,
+        shutter_close,
+        lens_radius,
+        focal_distance,
+        fov,
+        film,
+        medium,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::create_perspective_camera;
+    use crate::{
+        core::{
+            camera::{Camera, CameraSample},
+            film::{create_film, Film},
+            geometry::Point2f,
+            paramset::ParamSet,
+            transform::{AnimatedTransform, Transform},
+        },
+        filters::r#box::BoxFilter,
+        Float, Options,
+    };
+
+    fn film() -> Arc<Film> {
+        Arc::new(
+            create_film(
+                &ParamSet::default(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                &Options::default(),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn sample(p_lens: [Float; 2]) -> CameraSample {
+        CameraSample {
+            p_film: Point2f::from([320., 200.]),
+            p_lens: Point2f::from(p_lens),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lens_focuses() {
+        let mut params = ParamSet::default();
+        params.add_float("lensradius", vec![0.5]);
+        params.add_float("focaldistance", vec![10.]);
+        let camera = create_perspective_camera(
+            &params,
+            &AnimatedTransform::from(Transform::identity()),
+            film(),
+            None,
+        );
+        // Rays through different points on the lens meet at the plane of focus.
+        let (_, r0) = camera.generate_ray(&sample([0.1, 0.2]));
+        let (_, r1) = camera.generate_ray(&sample([0.9, 0.7]));
+        assert!((r0.o - r1.o).length() > 0.1);
+        let p0 = r0.at(10. / r0.d.z);
+        let p1 = r1.at(10. / r1.d.z);
+        assert!((p0 - p1).length() < 1e-3, "{} != {}", p0, p1);
+
+        let (_, rd0) = camera.generate_ray_differential(&sample([0.1, 0.2]));
+        let (_, rd1) = camera.generate_ray_differential(&sample([0.9, 0.7]));
+        assert_eq!(rd0.o, r0.o);
+        assert_eq!(rd0.d, r0.d);
+        let px0 = rd0.rx_origin + rd0.rx_direction * (10. / rd0.rx_direction.z);
+        let px1 = rd1.rx_origin + rd1.rx_direction * (10. / rd1.rx_direction.z);
+        assert!((px0 - px1).length() < 1e-3, "{} != {}", px0, px1);
+    }
+
+    #[test]
+    fn zero_radius_is_pinhole() {
+        let mut params = ParamSet::default();
+        params.add_float("lensradius", vec![0.]);
+        params.add_float("focaldistance", vec![10.]);
+        let lens = create_perspective_camera(
+            &params,
+            &AnimatedTransform::from(Transform::identity()),
+            film(),
+            None,
+        );
+        let pinhole = create_perspective_camera(
+            &ParamSet::default(),
+            &AnimatedTransform::from(Transform::identity()),
+            film(),
+            None,
+        );
+        for p_lens in [[0.1, 0.2], [0.9, 0.7]] {
+            let (_, r) = lens.generate_ray(&sample(p_lens));
+            let (_, want) = pinhole.generate_ray(&sample([0.5, 0.5]));
+            assert_eq!(r.o, want.o);
+            assert_eq!(r.d, want.d);
+
+            let (_, r) = lens.generate_ray_differential(&sample(p_lens));
+            let (_, want) = pinhole.generate_ray_differential(&sample([0.5, 0.5]));
+            assert_eq!(r.o, want.o);
+            assert_eq!(r.d, want.d);
+            assert_eq!(r.rx_origin, want.rx_origin);
+            assert_eq!(r.rx_direction, want.rx_direction);
+            assert_eq!(r.ry_direction, want.ry_direction);
+        }
+    }
+
+    #[test]
+    fn shutter_time() {
+        let mut params = ParamSet::default();
+        params.add_float("shutteropen", vec![1.]);
+        params.add_float("shutterclose", vec![3.]);
+        let camera = create_perspective_camera(
+            &params,
+            &AnimatedTransform::from(Transform::identity()),
+            film(),
+            None,
+        );
+        for (time, want) in [(0., 1.), (0.25, 1.5), (0.5, 2.)] {
+            let (_, r) = camera.generate_ray(&CameraSample {
+                time,
+                ..sample([0.5, 0.5])
+            });
+            assert_eq!(r.time, want);
+            let (_, r) = camera.generate_ray_differential(&CameraSample {
+                time,
+                ..sample([0.5, 0.5])
+            });
+            assert_eq!(r.time, want);
+        }
+    }
+}
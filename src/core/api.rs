@@ -16,9 +16,11 @@
 //! member functions as it interprets a scene file.
 
 use std::{
+    cell::Cell,
     collections::HashMap,
+    fmt,
     fs::File,
-    io,
+    io::{self, Read, Write},
     ops::{Index, IndexMut},
     path::Path,
     process::exit,
@@ -30,19 +32,62 @@ use memmap::MmapOptions;
 use thiserror::Error;
 
 use crate::{
+    accelerators::{
+        bvh::{create_bvh_accelerator, BVHAccel, SplitMethod},
+        kdtree::create_kdtree_accelerator,
+    },
+    cameras::{
+        environment::create_environment_camera, orthographic::create_orthographic_camera,
+        perspective::create_perspective_camera,
+    },
     core::{
+        camera::Camera,
+        film::{create_film, Film},
         filter::Filter,
-        light::Light,
+        geometry::{Bounds2i, Point2i},
+        imageio::clear_image_cache,
+        integrator::Integrator,
+        light::{AreaLight, Light},
+        material::Material,
         medium::{Medium, MediumInterface},
+        parallel::init_threads,
         paramset::{ParamSet, TextureParams},
-        parser::{self, create_from_string, parse},
+        parser::{self, create_from_string, parse, ByteSourceTokenizer},
+        plyutil::{self, write_ply_file},
+        primitive::{GeometricPrimitive, Primitive, TransformedPrimitive},
+        sampler::Sampler,
+        scene::Scene,
+        shape::Shape,
         spectrum::Spectrum,
         texture::Texture,
-        transform::Transform,
+        transform::{AnimatedTransform, Matrix4x4, Transform, TransformCache},
     },
     filters::r#box::BoxFilter,
-    lights::infinite::create_infinite_light,
-    textures::constant,
+    integrators::{
+        directlighting::create_direct_lighting_integrator, path::create_path_integrator,
+        volpath::create_volpath_integrator, whitted::create_whitted_integrator,
+    },
+    lights::{
+        diffuse::{create_diffuse_area_light, DiffuseAreaLight},
+        infinite::create_infinite_light,
+        point::create_point_light,
+    },
+    materials::{
+        glass::create_glass_material, matte::create_matte_material, metal::create_metal_material,
+        mirror::create_mirror_material, plastic::create_plastic_material,
+        substrate::create_substrate_material, uber::create_uber_material,
+    },
+    media::{grid::create_grid_density_medium, homogeneous::create_homogeneous_medium},
+    samplers::{
+        maxmindist::create_max_min_dist_sampler, random::create_random_sampler,
+        stratified::create_stratified_sampler, zerotwosequence::create_zero_two_sequence_sampler,
+    },
+    shapes::{
+        hyperboloid::create_hyperboloid_shape, loopsubdiv::create_loop_subdiv,
+        objmesh::create_obj_mesh, paraboloid::create_paraboloid_shape, plymesh::create_ply_mesh,
+        sphere::create_sphere_shape, triangle::create_triangle_mesh_shape,
+    },
+    textures::{constant, dots, fbm, image, uv, windy, wrinkled},
     Degree, Float, Options,
 };
 
@@ -57,11 +102,26 @@ pub enum Error {
     /// [parser]: crate::core::parser
     #[error("parse error")]
     Parser(#[from] parser::Error),
+    /// A part of the rendering pipeline, named by the first field, couldn't be created from the
+    /// scene description.  The second field describes why.
+    #[error("unable to create {0}: {1}")]
+    Create(&'static str, String),
     /// Unknown errors, wraps a string for human consumption.
     #[error("unknown error")]
     Unhandled(String),
 }
 
+/// An image rendered by [PbrtAPI::render_string].
+#[derive(Clone, Debug)]
+pub struct RenderedImage {
+    /// Final RGB values, three per pixel in scanline order, covering `crop_bounds`.
+    pub rgb: Vec<Float>,
+    /// Resolution of the full image.
+    pub resolution: Point2i,
+    /// The pixels of the full image covered by `rgb`.
+    pub crop_bounds: Bounds2i,
+}
+
 /// Trait describing all the global state machine modifiers that can be called while parsing a
 /// scene.  There is a concrete implementation in [PbrtAPI] that implements the rendered as
 /// described in the book.  All of the methods have stub implementations that call
@@ -105,11 +165,29 @@ pub trait API {
     fn light_source(&mut self, _name: &str, _params: ParamSet);
     /// Sets the current transforms to look at the given directions.
     fn look_at(&mut self, _eye: [Float; 3], _look: [Float; 3], _up: [Float; 3]);
+    /// Stores the material described by `params` under `name`, the "type" parameter names the
+    /// material's implementation.
+    fn make_named_material(&mut self, _name: &str, _params: ParamSet);
     /// Creates a medium with the given `params` and stores it as a named media under `name`.
     fn make_named_medium(&mut self, _name: &str, _params: &mut ParamSet);
+    /// Sets the current material to `name` described by `params`.
+    fn material(&mut self, _name: &str, _params: ParamSet);
     /// Specifies the current inside and outside media by the names given.  Cameras and lights
     /// without geometry ignore the `inside_name`.
     fn medium_interface(&mut self, _inside_name: &str, _outside_name: &str);
+    /// Sets the current material to the one stored under `name` by [make_named_material].
+    ///
+    /// [make_named_material]: API::make_named_material
+    fn named_material(&mut self, _name: &str);
+    /// Starts the definition of the object instance `name`.  Shapes up to the matching
+    /// [object_end] are added to the instance instead of the scene.
+    ///
+    /// [object_end]: API::object_end
+    fn object_begin(&mut self, _name: &str);
+    /// Ends the current object instance definition.
+    fn object_end(&mut self);
+    /// Adds a copy of the object instance `name` to the scene, placed with the current transform.
+    fn object_instance(&mut self, _name: &str);
     /// Parse a scene file at `path` on the file-system.  This will parse the contents of the file
     /// generating an inmemory representation of the scene, and trigger the rendering and output of
     /// the image.
@@ -121,12 +199,16 @@ pub trait API {
     fn parse_string(&mut self, _data: &[u8]) -> Result<(), Error>;
     /// Sets the renderer's filter settings to `name` & `params`.
     fn pixel_filter(&mut self, _name: &str, _params: ParamSet);
+    /// Flips the orientation of the surface normals of subsequent shapes.
+    fn reverse_orientation(&mut self);
     /// Rotates the currently active transform matrix by the given values.
     fn rotate(&mut self, _angle: Degree, _ax: Float, _ay: Float, _az: Float);
     /// Sets the renderer's sampler settings to `name` & `params`.
     fn sampler(&mut self, _name: &str, _params: ParamSet);
     /// Scales the currently active transform matrix by the given values.
     fn scale(&mut self, _sx: Float, _sy: Float, _sz: Float);
+    /// Creates the shape `name` described by `params` when `Shape` found in scene.
+    fn shape(&mut self, _name: &str, _params: ParamSet);
     /// Called when the parser sees a `Texture` line.
     fn texture(&mut self, _name: &str, _kind: &str, _texname: &str, _params: ParamSet);
     /// Called when parser sees a `TransformBegin` keyword
@@ -159,24 +241,35 @@ const START_TRANSFORM_BITS: usize = 1;
 const END_TRANSFORM_BITS: usize = 2;
 const ALL_TRANSFORMS_BITS: usize = (1 << MAX_TRANSFORMS) - 1;
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 struct TransformSet {
     t: [Transform; MAX_TRANSFORMS],
+    // Cached result of `is_animated`, `None` when a slot may have changed since it was computed.
+    animated: Cell<Option<bool>>,
+}
+
+impl Default for TransformSet {
+    fn default() -> TransformSet {
+        TransformSet {
+            t: [Transform::identity(); MAX_TRANSFORMS],
+            animated: Cell::new(Some(false)),
+        }
+    }
 }
 
 impl TransformSet {
     fn is_animated(&self) -> bool {
-        for i in 0..(MAX_TRANSFORMS - 1) {
-            if self.t[i] != self.t[i + 1] {
-                return true;
-            }
+        if let Some(animated) = self.animated.get() {
+            return animated;
         }
-        false
+        let animated = (0..(MAX_TRANSFORMS - 1)).any(|i| self.t[i] != self.t[i + 1]);
+        self.animated.set(Some(animated));
+        animated
     }
     fn inverse(&self) -> TransformSet {
         let mut t_inv: TransformSet = Default::default();
         for i in 0..MAX_TRANSFORMS {
-            t_inv.t[i] = self.t[i].inverse();
+            t_inv[i] = self.t[i].inverse();
         }
         t_inv
     }
@@ -185,14 +278,25 @@ impl TransformSet {
 impl Index<usize> for TransformSet {
     type Output = Transform;
     fn index(&self, idx: usize) -> &Transform {
-        debug_assert!(idx < ALL_TRANSFORMS_BITS);
+        debug_assert!(
+            idx < MAX_TRANSFORMS,
+            "transform index {} out of range, TransformSet holds {} transforms",
+            idx,
+            MAX_TRANSFORMS
+        );
         &self.t[idx]
     }
 }
 
 impl IndexMut<usize> for TransformSet {
     fn index_mut(&mut self, idx: usize) -> &mut Transform {
-        debug_assert!(idx < ALL_TRANSFORMS_BITS);
+        debug_assert!(
+            idx < MAX_TRANSFORMS,
+            "transform index {} out of range, TransformSet holds {} transforms",
+            idx,
+            MAX_TRANSFORMS
+        );
+        self.animated.set(None);
         &mut self.t[idx]
     }
 }
@@ -214,13 +318,14 @@ struct RenderOptions {
     camera_name: String,
     camera_params: ParamSet,
     camera_to_world: TransformSet,
+    camera_medium: String,
     named_media: HashMap<String, Arc<dyn Medium>>,
     lights: Vec<Arc<dyn Light>>,
     have_scattering_media: bool,
-    /* TODO(wathiede):
-     * std::vector<std::shared_ptr<Primitive>> primitives;
-     * std::map<std::string, std::vector<std::shared_ptr<Primitive>>> instances;
-     * std::vector<std::shared_ptr<Primitive>> *currentInstance = nullptr; */
+    primitives: Vec<Arc<dyn Primitive>>,
+    instances: HashMap<String, Vec<Arc<dyn Primitive>>>,
+    // Name of the instance being defined, if inside an ObjectBegin/ObjectEnd block.
+    current_instance: Option<String>,
 }
 
 impl Default for RenderOptions {
@@ -241,44 +346,176 @@ impl Default for RenderOptions {
             camera_name: "perspective".to_owned(),
             camera_params: Default::default(),
             camera_to_world: Default::default(),
+            camera_medium: String::new(),
             named_media: HashMap::new(),
             lights: Vec::new(),
             have_scattering_media: false,
+            primitives: Vec::new(),
+            instances: HashMap::new(),
+            current_instance: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    fn make_film(&self, opt: &Options) -> Result<Film, Error> {
+        let filter = make_filter(&self.filter_name, &self.filter_params);
+        make_film(&self.film_name, &self.film_params, filter, opt)
+    }
+
+    fn make_camera(&self, film: Arc<Film>) -> Result<Arc<dyn Camera>, Error> {
+        let medium = if self.camera_medium.is_empty() {
+            None
+        } else {
+            let medium = self.named_media.get(&self.camera_medium).cloned();
+            if medium.is_none() {
+                error!("Named medium '{}' undefined.", self.camera_medium);
+            }
+            medium
+        };
+        make_camera(
+            &self.camera_name,
+            &self.camera_params,
+            &self.camera_to_world,
+            self.transform_start_time,
+            self.transform_end_time,
+            film,
+            medium,
+        )
+        .ok_or_else(|| Error::Create("camera", format!("camera '{}' unknown", self.camera_name)))
+    }
+
+    fn make_integrator(
+        &self,
+        camera: Arc<dyn Camera>,
+        opt: &Options,
+    ) -> Result<Box<dyn Integrator>, Error> {
+        let sampler = make_sampler(
+            &self.sampler_name,
+            &self.sampler_params,
+            &camera.film(),
+            opt,
+        );
+
+        let integrator: Box<dyn Integrator> = match self.integrator_name.as_str() {
+            "whitted" => Box::new(create_whitted_integrator(
+                &self.integrator_params,
+                sampler,
+                camera,
+            )),
+            "directlighting" => Box::new(create_direct_lighting_integrator(
+                &self.integrator_params,
+                sampler,
+                camera,
+            )),
+            "path" => Box::new(create_path_integrator(
+                &self.integrator_params,
+                sampler,
+                camera,
+            )),
+            "volpath" => Box::new(create_volpath_integrator(
+                &self.integrator_params,
+                sampler,
+                camera,
+            )),
+            "bdpt" | "mlt" | "ambientocclusion" | "sppm" => {
+                return Err(Error::Create(
+                    "integrator",
+                    format!("integrator '{}' not implemented", self.integrator_name),
+                ))
+            }
+            _ => {
+                return Err(Error::Create(
+                    "integrator",
+                    format!("integrator '{}' unknown", self.integrator_name),
+                ))
+            }
+        };
+
+        if self.have_scattering_media
+            && self.integrator_name != "volpath"
+            && self.integrator_name != "bdpt"
+            && self.integrator_name != "mlt"
+        {
+            warn!(
+                "Scene has scattering media but \"{}\" integrator doesn't support volume scattering. Consider using \"volpath\", \"bdpt\", or \"mlt\".",
+                self.integrator_name
+            );
         }
+
+        self.integrator_params.report_unused();
+        // Warn if no light sources are defined
+        if self.lights.is_empty() {
+            warn!("No light sources defined in scene; rendering a black image.");
+        }
+        Ok(integrator)
+    }
+
+    /// Builds the scene from the world block's primitives and lights, moving them into it.
+    fn make_scene(&mut self) -> Option<Scene> {
+        let primitives = std::mem::take(&mut self.primitives);
+        let lights = std::mem::take(&mut self.lights);
+        let accelerator =
+            make_accelerator(&self.accelerator_name, primitives, &self.accelerator_params)?;
+        Some(Scene::new(accelerator, lights))
+    }
+
+    /// Drops everything created inside the world block, so the next one starts empty.
+    fn clear_world(&mut self) {
+        self.lights.clear();
+        self.primitives.clear();
+        self.instances.clear();
+        self.current_instance = None;
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 struct GraphicsState {
     current_inside_medium: String,
     current_outside_medium: String,
     // TODO(wathiede):
     // // Graphics State Methods
     // std::shared_ptr<Material> CreateMaterial(const ParamSet &params);
-    // MediumInterface CreateMediumInterface();
 
     // // Graphics State
     float_textures: HashMap<String, Arc<dyn Texture<Float>>>,
     specturm_textures: HashMap<String, Arc<dyn Texture<Spectrum>>>,
-    /* ParamSet materialParams;
-     * std::string material = "matte";
-     * std::map<std::string, std::shared_ptr<Material>> namedMaterials;
-     * std::string currentNamedMaterial;
-     * ParamSet areaLightParams;
-     * std::string areaLight;
-     * bool reverseOrientation = false; */
+    material_params: ParamSet,
+    material: String,
+    // Material name and parameters stored by `MakeNamedMaterial`, keyed by the material's name.
+    named_materials: HashMap<String, (String, ParamSet)>,
+    area_light_params: ParamSet,
+    area_light: String,
+    reverse_orientation: bool,
+}
+
+impl Default for GraphicsState {
+    fn default() -> GraphicsState {
+        GraphicsState {
+            current_inside_medium: String::new(),
+            current_outside_medium: String::new(),
+            float_textures: HashMap::new(),
+            specturm_textures: HashMap::new(),
+            material_params: Default::default(),
+            material: "matte".to_owned(),
+            named_materials: HashMap::new(),
+            area_light_params: Default::default(),
+            area_light: String::new(),
+            reverse_orientation: false,
+        }
+    }
 }
 
 impl GraphicsState {
     fn create_medium_interface(&mut self, render_options: &RenderOptions) -> MediumInterface {
         let mut m = MediumInterface::default();
-        if self.current_inside_medium.is_empty() {
+        if !self.current_inside_medium.is_empty() {
             match render_options.named_media.get(&self.current_inside_medium) {
                 Some(medium) => m.inside = Some(Arc::clone(medium)),
                 None => error!("Named medium '{}' undefined.", self.current_inside_medium),
             }
         }
-        if self.current_outside_medium.is_empty() {
+        if !self.current_outside_medium.is_empty() {
             match render_options.named_media.get(&self.current_outside_medium) {
                 Some(medium) => m.outside = Some(Arc::clone(medium)),
                 None => error!("Named medium '{}' undefined.", self.current_outside_medium),
@@ -335,12 +572,14 @@ fn make_light(
     name: &str,
     params: &ParamSet,
     light2world: &Transform,
-    _medium_interface: &MediumInterface,
+    medium_interface: &MediumInterface,
+    opt: &Options,
 ) -> Option<Arc<dyn Light>> {
     Some(match name {
-        "infinite" | "exinfinite" => create_infinite_light(light2world, params),
-        "point" | "spot" | "goniometric" | "projection" | "distant" => {
-            todo!("only infinite and exinfinite lights are currently implemented")
+        "point" => create_point_light(light2world, medium_interface, params),
+        "infinite" | "exinfinite" => create_infinite_light(light2world, params, opt),
+        "spot" | "goniometric" | "projection" | "distant" => {
+            todo!("only point, infinite and exinfinite lights are currently implemented")
         }
         _ => {
             warn!("Light '{}' unknown.", name);
@@ -350,8 +589,115 @@ fn make_light(
     })
 }
 
+fn make_shapes(
+    name: &str,
+    object2world: &Transform,
+    world2object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<dyn Shape>> {
+    let shape: Arc<dyn Shape> = match name {
+        "sphere" => Arc::new(create_sphere_shape(
+            object2world,
+            world2object,
+            reverse_orientation,
+            params,
+        )),
+        "paraboloid" => Arc::new(create_paraboloid_shape(
+            object2world,
+            world2object,
+            reverse_orientation,
+            params,
+        )),
+        "hyperboloid" => Arc::new(create_hyperboloid_shape(
+            object2world,
+            world2object,
+            reverse_orientation,
+            params,
+        )),
+        "trianglemesh" => {
+            return create_triangle_mesh_shape(
+                object2world,
+                world2object,
+                reverse_orientation,
+                params,
+            )
+            .into_iter()
+            .map(|t| t as Arc<dyn Shape>)
+            .collect()
+        }
+        "plymesh" => {
+            return create_ply_mesh(object2world, reverse_orientation, params)
+                .into_iter()
+                .map(|t| t as Arc<dyn Shape>)
+                .collect()
+        }
+        "objmesh" => {
+            return create_obj_mesh(object2world, reverse_orientation, params)
+                .into_iter()
+                .map(|t| t as Arc<dyn Shape>)
+                .collect()
+        }
+        "loopsubdiv" => {
+            return create_loop_subdiv(object2world, world2object, reverse_orientation, params)
+                .into_iter()
+                .map(|t| t as Arc<dyn Shape>)
+                .collect()
+        }
+        "cylinder" | "disk" | "cone" | "curve" | "heightfield" | "nurbs" => {
+            unimplemented!("Shape type '{}' not implemented", name)
+        }
+        _ => {
+            warn!("Shape '{}' unknown.", name);
+            return Vec::new();
+        }
+    };
+    vec![shape]
+}
+
+fn make_area_light(
+    name: &str,
+    light2world: &Transform,
+    medium_interface: &MediumInterface,
+    params: &ParamSet,
+    shape: Arc<dyn Shape>,
+    opt: &Options,
+) -> Option<Arc<DiffuseAreaLight>> {
+    match name {
+        "diffuse" => Some(create_diffuse_area_light(
+            light2world,
+            medium_interface,
+            params,
+            shape,
+            opt,
+        )),
+        _ => {
+            warn!("Area light '{}' unknown.", name);
+            params.report_unused();
+            None
+        }
+    }
+}
+
+/// Destination of the scene written when `Options::cat` or `Options::to_ply` is set, stdout
+/// unless overridden with `PbrtAPI::set_cat_output`.
+struct CatOutput(Box<dyn Write + Send>);
+
+impl Default for CatOutput {
+    fn default() -> CatOutput {
+        CatOutput(Box::new(io::stdout()))
+    }
+}
+
+impl fmt::Debug for CatOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CatOutput")
+    }
+}
+
 /// PbrtAPI is the top-level global container for all rendering functionality.
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct PbrtAPI {
     opt: Options,
     current_api_state: APIState,
@@ -363,8 +709,16 @@ pub struct PbrtAPI {
     pushed_graphics_states: Vec<GraphicsState>,
     pushed_transforms: Vec<TransformSet>,
     pushed_active_transform_bits: Vec<usize>,
-    /* TODO(wathiede):
-     * static TransformCache transformCache; */
+    transform_cache: TransformCache,
+    cat_indent: usize,
+    cat_output: CatOutput,
+    ply_prefix: String,
+    ply_count: usize,
+    // Set by `render_string` so `world_end` keeps the rendered film instead of writing it to disk.
+    render_to_memory: bool,
+    rendered: Option<Result<Arc<Film>, Error>>,
+    // Set by `parse_string_no_render` so `world_end` doesn't render at all.
+    skip_render: bool,
 }
 
 impl From<Options> for PbrtAPI {
@@ -381,6 +735,14 @@ impl From<Options> for PbrtAPI {
             pushed_graphics_states: Vec::new(),
             pushed_transforms: Vec::new(),
             pushed_active_transform_bits: Vec::new(),
+            transform_cache: Default::default(),
+            cat_indent: 0,
+            cat_output: Default::default(),
+            ply_prefix: "mesh".to_string(),
+            ply_count: 0,
+            render_to_memory: false,
+            rendered: None,
+            skip_render: false,
         }
     }
 }
@@ -389,10 +751,29 @@ impl API for PbrtAPI {
     /// Parse a scene file at `path` on the file-system.  This will parse the contents of the file
     /// generating an inmemory representation of the scene, and trigger the rendering and output of
     /// the image.
+    ///
+    /// A `path` of `-` reads the scene from stdin.  Files are memory mapped unless
+    /// `Options::no_mmap` is set, falling back to streaming them when they can't be mapped, e.g.
+    /// pipes or some network filesystems.  Stdin is always streamed.
     fn parse_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
-        let f = File::open(&path)?;
-        let mmap = unsafe { MmapOptions::new().map(&f)? };
-        self.parse_string(&mmap)
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            return self.parse_reader(io::stdin().lock());
+        }
+        let f = File::open(path)?;
+        if !self.opt.no_mmap {
+            // The mapping is only read while parsing, modifying the file underneath it at the same
+            // time is unsupported.
+            match unsafe { MmapOptions::new().map(&f) } {
+                Ok(mmap) => return self.parse_string(&mmap),
+                Err(err) => info!(
+                    "Failed to mmap {}, reading it instead: {}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        self.parse_reader(f)
     }
 
     /// Moves the internal statemachine from `APIState::Uninitialized` to `APIState::OptionsBlock`.
@@ -403,6 +784,7 @@ impl API for PbrtAPI {
         }
         self.current_api_state = APIState::OptionsBlock;
         self.render_options = Default::default();
+        init_threads(self.opt.num_threads as usize);
     }
 
     /// Reset the internal state of self.
@@ -419,70 +801,78 @@ impl API for PbrtAPI {
     /// Called when parser sees a `WorldBegin` keyword
     fn world_begin(&mut self) {
         verify_options!(self, "pbrt.world_begin");
+        self.cat(format_args!("WorldBegin"));
         self.current_api_state = APIState::WorldBlock;
+        // Left over when the previous world block was parsed with `parse_string_no_render`.
+        self.render_options.clear_world();
         for i in 0..MAX_TRANSFORMS {
-            self.current_transform[i] = Default::default();
+            self.current_transform[i] = Transform::identity();
         }
         self.active_transform_bits = ALL_TRANSFORMS_BITS;
         self.named_coordinate_systems
-            .insert("world".to_owned(), self.current_transform);
+            .insert("world".to_owned(), self.current_transform.clone());
     }
 
     /// Called when parser sees a `WorldEnd` keyword
     fn world_end(&mut self) {
         verify_world!(self, "pbrt.world_end");
-        // TODO(wathiede): call everything
-        // // Ensure there are no pushed graphics states
-        // while (pushedGraphicsStates.size()) {
-        //     Warning("Missing end to pbrtAttributeBegin()");
-        //     pushedGraphicsStates.pop_back();
-        //     pushedTransforms.pop_back();
-        // }
-        // while (pushedTransforms.size()) {
-        //     Warning("Missing end to pbrtTransformBegin()");
-        //     pushedTransforms.pop_back();
-        // }
-
-        // // Create scene and render
-        // if (PbrtOptions.cat || PbrtOptions.toPly) {
-        //     printf("%*sWorldEnd\n", catIndentCount, "");
-        // } else {
-        //     std::unique_ptr<Integrator> integrator(renderOptions->MakeIntegrator());
-        //     std::unique_ptr<Scene> scene(renderOptions->MakeScene());
-        //     if (scene && integrator) integrator->Render(*scene);
-        // }
-
-        // // Clean up after rendering
-        // graphicsState = GraphicsState();
-        // transformCache.Clear();
-        self.current_api_state = APIState::OptionsBlock;
+        // Ensure there are no pushed graphics states
+        while self.pushed_graphics_states.pop().is_some() {
+            warn!("Missing end to pbrt.attribute_begin()");
+            self.pushed_transforms.pop();
+            self.pushed_active_transform_bits.pop();
+        }
+        while self.pushed_transforms.pop().is_some() {
+            warn!("Missing end to pbrt.transform_begin()");
+            self.pushed_active_transform_bits.pop();
+        }
 
-        // MergeWorkerThreadStats();
-        // ReportThreadStats();
-        // if (!PbrtOptions.quiet && !PbrtOptions.cat && !PbrtOptions.toPly) {
-        //     PrintStats(stdout);
-        //     ReportProfilerResults(stdout);
-        // }
+        // Create scene and render
+        if self.cat_enabled() {
+            self.cat(format_args!("WorldEnd"));
+        } else if self.skip_render {
+            info!("Skipping rendering");
+        } else {
+            let result = self.render_film();
+            if self.render_to_memory {
+                self.rendered = Some(result);
+            } else if let Err(err) = result {
+                error!("Rendering failed: {}", err);
+            }
+        }
 
-        // for (int i = 0; i < MaxTransforms; ++i) curTransform[i] = Transform();
-        // activeTransformBits = AllTransformsBits;
-        // namedCoordinateSystems.erase(namedCoordinateSystems.begin(),
-        //                              namedCoordinateSystems.end());
-        // ImageTexture<Float, Float>::ClearCache();
-        // ImageTexture<RGBSpectrum, Spectrum>::ClearCache();
+        // Clean up after rendering.  The world is left for inspection when rendering was skipped.
+        if !self.skip_render {
+            self.render_options.clear_world();
+        }
+        self.graphics_state = Default::default();
+        self.current_api_state = APIState::OptionsBlock;
+
+        for i in 0..MAX_TRANSFORMS {
+            self.current_transform[i] = Transform::identity();
+        }
+        self.active_transform_bits = ALL_TRANSFORMS_BITS;
+        self.named_coordinate_systems.clear();
+        self.transform_cache.clear();
+        clear_image_cache();
     }
 
     /// Creates area light when `AreaLightSource` found in scene.
-    fn area_light_source(&mut self, _name: &str, _params: ParamSet) {
-        todo!("core::api::PbrtAPI::area_light_source");
+    fn area_light_source(&mut self, name: &str, params: ParamSet) {
+        verify_world!(self, "pbrt.area_light_source");
+        self.cat_named("AreaLightSource", name, &params);
+        self.graphics_state.area_light = name.to_string();
+        self.graphics_state.area_light_params = params;
     }
 
     /// Called when parser sees a `AttributeBegin` keyword
     fn attribute_begin(&mut self) {
         verify_world!(self, "pbrt.attribute_begin");
+        self.cat(format_args!("AttributeBegin"));
+        self.cat_indent += 4;
         self.pushed_graphics_states
             .push(self.graphics_state.clone());
-        self.pushed_transforms.push(self.current_transform);
+        self.pushed_transforms.push(self.current_transform.clone());
         self.pushed_active_transform_bits
             .push(self.active_transform_bits);
     }
@@ -490,6 +880,8 @@ impl API for PbrtAPI {
     /// Called when parser sees a `AttributeEnd` keyword
     fn attribute_end(&mut self) {
         verify_world!(self, "pbrt.attribute_end");
+        self.cat_indent = self.cat_indent.saturating_sub(4);
+        self.cat(format_args!("AttributeEnd"));
         if self.pushed_graphics_states.is_empty()
             || self.pushed_transforms.is_empty()
             || self.pushed_active_transform_bits.is_empty()
@@ -505,7 +897,9 @@ impl API for PbrtAPI {
     /// Called when parser sees a `TransformBegin` keyword
     fn transform_begin(&mut self) {
         verify_world!(self, "pbrt.transform_begin");
-        self.pushed_transforms.push(self.current_transform);
+        self.cat(format_args!("TransformBegin"));
+        self.cat_indent += 4;
+        self.pushed_transforms.push(self.current_transform.clone());
         self.pushed_active_transform_bits
             .push(self.active_transform_bits);
     }
@@ -513,6 +907,8 @@ impl API for PbrtAPI {
     /// Called when parser sees a `TransformEnd` keyword
     fn transform_end(&mut self) {
         verify_world!(self, "pbrt.transform_end");
+        self.cat_indent = self.cat_indent.saturating_sub(4);
+        self.cat(format_args!("TransformEnd"));
         if self.pushed_transforms.is_empty() || self.pushed_active_transform_bits.is_empty() {
             error!("Unmatched pbrt.transform_end() encountered. Ignoring it.");
             return;
@@ -524,6 +920,10 @@ impl API for PbrtAPI {
     /// Called when the parser sees a `Texture` line.
     fn texture(&mut self, name: &str, kind: &str, texname: &str, params: ParamSet) {
         verify_world!(self, "pbrt.texture");
+        if self.cat_enabled() {
+            let line = format!("Texture \"{}\" \"{}\" \"{}\"", name, kind, texname);
+            self.cat_named(&line, "", &params);
+        }
         info!(
             "Creating texture name {} kind {} texname {} paramset {:?}",
             name, kind, texname, params
@@ -587,6 +987,7 @@ impl API for PbrtAPI {
     /// ```
     fn identity(&mut self) {
         verify_initialized!(self, "identity");
+        self.cat(format_args!("Identity"));
         self.for_active_transforms_mut(|ct| *ct = Transform::identity());
     }
 
@@ -612,6 +1013,7 @@ impl API for PbrtAPI {
     /// ```
     fn translate(&mut self, dx: Float, dy: Float, dz: Float) {
         verify_initialized!(self, "translate");
+        self.cat(format_args!("Translate {} {} {}", dx, dy, dz));
         self.for_active_transforms_mut(|ct| {
             // TODO(wathiede): is it wrong to clone ct? I needed to convert a &mut to a non-mutable
             // type.
@@ -670,12 +1072,17 @@ impl API for PbrtAPI {
     /// ```
     fn rotate(&mut self, angle: Degree, ax: Float, ay: Float, az: Float) {
         verify_initialized!(self, "pbrt.rotate");
+        self.cat(format_args!("Rotate {} {} {} {}", angle.0, ax, ay, az));
         self.for_active_transforms_mut(|ct| *ct = *ct * Transform::rotate(angle, [ax, ay, az]));
     }
 
     /// Sets the current transforms to look at the given directions.
     fn look_at(&mut self, eye: [Float; 3], look: [Float; 3], up: [Float; 3]) {
         verify_initialized!(self, "pbrt.look_at");
+        self.cat(format_args!(
+            "LookAt {} {} {}  {} {} {}  {} {} {}",
+            eye[0], eye[1], eye[2], look[0], look[1], look[2], up[0], up[1], up[2]
+        ));
         info!("eye: {:?} look: {:?} up: {:?}", eye, look, up);
         let look_at = Transform::look_at(eye, look, up);
         self.for_active_transforms_mut(|ct| *ct = *ct * look_at);
@@ -684,15 +1091,108 @@ impl API for PbrtAPI {
     /// Creates light when `LightSource` found in scene.
     fn light_source(&mut self, name: &str, params: ParamSet) {
         verify_world!(self, "pbrt.light_source");
+        self.cat_named("LightSource", name, &params);
         self.warn_if_animated_transform("pbrt.light_source");
         let mi = self
             .graphics_state
             .create_medium_interface(&self.render_options);
-        match make_light(name, &params, &self.current_transform[0], &mi) {
+        let light2world = self
+            .transform_cache
+            .lookup_transform(&self.current_transform[0]);
+        match make_light(name, &params, &light2world, &mi, &self.opt) {
             None => error!("light_source: light type '{}' unknown.", name),
             Some(lt) => self.render_options.lights.push(lt),
         };
     }
+    /// Creates the shape `name` described by `params` when `Shape` found in scene.  Each shape
+    /// is added to the scene as a primitive with the current material, and as an area light if an
+    /// `AreaLightSource` is active.
+    fn shape(&mut self, name: &str, params: ParamSet) {
+        verify_world!(self, "pbrt.shape");
+        if self.cat_enabled() {
+            if self.opt.to_ply && name == "trianglemesh" {
+                self.cat_ply_mesh(&params);
+            } else {
+                self.cat_named("Shape", name, &params);
+            }
+            return;
+        }
+        // TODO(wathiede): support animated shapes.
+        self.warn_if_animated_transform("pbrt.shape");
+        let obj_to_world = self
+            .transform_cache
+            .lookup_transform(&self.current_transform[0]);
+        let world_to_obj = self
+            .transform_cache
+            .lookup_transform(&self.current_transform[0].inverse());
+        let shapes = make_shapes(
+            name,
+            &obj_to_world,
+            &world_to_obj,
+            self.graphics_state.reverse_orientation,
+            &params,
+        );
+        if shapes.is_empty() {
+            return;
+        }
+        let tp = TextureParams::new(
+            params.clone(),
+            self.graphics_state.material_params.clone(),
+            self.graphics_state.float_textures.clone(),
+            self.graphics_state.specturm_textures.clone(),
+        );
+        let mtl = make_material(&self.graphics_state.material, &tp);
+        params.report_unused();
+        let mi = self
+            .graphics_state
+            .create_medium_interface(&self.render_options);
+        if let Some(name) = &self.render_options.current_instance {
+            if !self.graphics_state.area_light.is_empty() {
+                warn!("Area lights not supported with object instancing");
+            }
+            let instance = self
+                .render_options
+                .instances
+                .get_mut(name)
+                .expect("current instance is always in instances");
+            for s in shapes {
+                instance.push(Arc::new(GeometricPrimitive::new(
+                    s,
+                    mtl.clone(),
+                    None,
+                    mi.clone(),
+                )));
+            }
+            return;
+        }
+        for s in shapes {
+            let area: Option<Arc<dyn AreaLight>> = if self.graphics_state.area_light.is_empty() {
+                None
+            } else {
+                make_area_light(
+                    &self.graphics_state.area_light,
+                    &self.current_transform[0],
+                    &mi,
+                    &self.graphics_state.area_light_params,
+                    Arc::clone(&s),
+                    &self.opt,
+                )
+                .map(|area| {
+                    self.render_options.lights.push(area.clone());
+                    area as Arc<dyn AreaLight>
+                })
+            };
+            self.render_options
+                .primitives
+                .push(Arc::new(GeometricPrimitive::new(
+                    s,
+                    mtl.clone(),
+                    area,
+                    mi.clone(),
+                )));
+        }
+    }
+
     /// Scales the currently active transform matrix by the given values.
     /// # Examples
     /// ```
@@ -715,55 +1215,69 @@ impl API for PbrtAPI {
     /// ```
     fn scale(&mut self, sx: Float, sy: Float, sz: Float) {
         verify_initialized!(self, "pbrt.scale");
+        self.cat(format_args!("Scale {} {} {}", sx, sy, sz));
         self.for_active_transforms_mut(|ct| *ct = *ct * Transform::scale(sx, sy, sz));
     }
 
     /// Multiples the current transform matrix by `transform`.
     fn concat_transform(&mut self, transform: [Float; 16]) {
         verify_initialized!(self, "pbrt.concat_transform");
-        self.for_active_transforms_mut(|ct| *ct = *ct * Transform::from(transform));
+        self.cat(format_args!(
+            "ConcatTransform [ {} ]",
+            join_floats(&transform)
+        ));
+        let t = self.transform_cache.lookup(Matrix4x4::from(transform));
+        self.for_active_transforms_mut(|ct| *ct = *ct * *t);
     }
 
     /// Sets the current transform matrix to `transform`.
     fn transform(&mut self, transform: [Float; 16]) {
         verify_initialized!(self, "pbrt.transform");
-        self.for_active_transforms_mut(|ct| *ct = Transform::from(transform));
+        self.cat(format_args!("Transform [ {} ]", join_floats(&transform)));
+        let t = self.transform_cache.lookup(Matrix4x4::from(transform));
+        self.for_active_transforms_mut(|ct| *ct = *t);
     }
 
     /// Creates a new coordinate system assigning `name` the current transform matrix.
     fn coordinate_system(&mut self, name: &str) {
         verify_initialized!(self, "pbrt.coordinate_system");
+        self.cat(format_args!("CoordinateSystem \"{}\"", name));
         self.named_coordinate_systems
-            .insert(name.to_string(), self.current_transform);
+            .insert(name.to_string(), self.current_transform.clone());
     }
 
     /// Sets the current transform matrix to the one stored under `name`.
     fn coordinate_system_transform(&mut self, name: &str) {
         verify_initialized!(self, "pbrt.coordinate_system_transform");
+        self.cat(format_args!("CoordSysTransform \"{}\"", name));
         match self.named_coordinate_systems.get(name) {
-            Some(t) => self.current_transform = *t,
+            Some(t) => self.current_transform = t.clone(),
             None => warn!("Couldn’t find named coordinate system \"{}\"", name),
         }
     }
 
     /// Sets the active transform bits to `ALL_TRANSFORMS_BITS`.
     fn active_transform_all(&mut self) {
+        self.cat(format_args!("ActiveTransform All"));
         self.active_transform_bits = ALL_TRANSFORMS_BITS;
     }
 
     /// Sets the active transform bits to `END_TRANSFORMS_BITS`.
     fn active_transform_end_time(&mut self) {
+        self.cat(format_args!("ActiveTransform EndTime"));
         self.active_transform_bits = END_TRANSFORM_BITS;
     }
 
     /// Sets the active transform bits to `START_TRANSFORMS_BITS`.
     fn active_transform_start_time(&mut self) {
+        self.cat(format_args!("ActiveTransform StartTime"));
         self.active_transform_bits = START_TRANSFORM_BITS;
     }
 
     /// Sets the start/end times for the transform matrix to `start` & `end`.
     fn transform_times(&mut self, start: Float, end: Float) {
         verify_options!(self, "pbrt.transform_times");
+        self.cat(format_args!("TransformTimes {} {}", start, end));
         self.render_options.transform_start_time = start;
         self.render_options.transform_end_time = end;
     }
@@ -777,6 +1291,7 @@ impl API for PbrtAPI {
     /// Sets the renderer's filter settings to `name` & `params`.
     fn pixel_filter(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.pixel_filter");
+        self.cat_named("PixelFilter", name, &params);
         self.render_options.filter_name = name.to_string();
         self.render_options.filter_params = params;
     }
@@ -784,6 +1299,7 @@ impl API for PbrtAPI {
     /// Sets the renderer's film settings to `name` & `params`.
     fn film(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.film");
+        self.cat_named("Film", name, &params);
         self.render_options.film_name = name.to_string();
         self.render_options.film_params = params;
     }
@@ -791,6 +1307,7 @@ impl API for PbrtAPI {
     /// Sets the renderer's sampler settings to `name` & `params`.
     fn sampler(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.sampler");
+        self.cat_named("Sampler", name, &params);
         self.render_options.sampler_name = name.to_string();
         self.render_options.sampler_params = params;
     }
@@ -798,6 +1315,7 @@ impl API for PbrtAPI {
     /// Sets the renderer's accelerator settings to `name` & `params`.
     fn accelerator(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.accelerator");
+        self.cat_named("Accelerator", name, &params);
         self.render_options.accelerator_name = name.to_string();
         self.render_options.accelerator_params = params;
     }
@@ -805,6 +1323,7 @@ impl API for PbrtAPI {
     /// Sets the renderer's integrator settings to `name` & `params`.
     fn integrator(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.integrator");
+        self.cat_named("Integrator", name, &params);
         self.render_options.integrator_name = name.to_string();
         self.render_options.integrator_params = params;
     }
@@ -812,28 +1331,154 @@ impl API for PbrtAPI {
     /// Sets the renderer's camera settings to `name` & `params`.
     fn camera(&mut self, name: &str, params: ParamSet) {
         verify_options!(self, "pbrt.camera");
+        self.cat_named("Camera", name, &params);
         self.render_options.camera_name = name.to_string();
         self.render_options.camera_params = params;
         self.render_options.camera_to_world = self.current_transform.inverse();
-        self.named_coordinate_systems
-            .insert("camera".to_owned(), self.render_options.camera_to_world);
+        self.render_options.camera_medium = self.graphics_state.current_outside_medium.clone();
+        self.named_coordinate_systems.insert(
+            "camera".to_owned(),
+            self.render_options.camera_to_world.clone(),
+        );
     }
 
     /// Creates a medium with the given `params` and stores it as a named media under `name`.
     fn make_named_medium(&mut self, name: &str, params: &mut ParamSet) {
         verify_initialized!(self, "pbrt.make_named_medium");
+        self.cat_named("MakeNamedMedium", name, params);
         self.warn_if_animated_transform("pbrt.make_named_medium");
         let kind = params.find_one_string("type", "");
-        let medium = make_medium(&kind, params, self.current_transform[0]);
+        match make_medium(&kind, params, self.current_transform[0]) {
+            Some(medium) => {
+                self.render_options
+                    .named_media
+                    .insert(name.to_string(), medium);
+            }
+            None => error!("Unable to create medium \"{}\"", name),
+        }
+    }
+
+    /// Stores the material described by `params` under `name`, the "type" parameter names the
+    /// material's implementation.
+    fn make_named_material(&mut self, name: &str, params: ParamSet) {
+        verify_world!(self, "pbrt.make_named_material");
+        self.cat_named("MakeNamedMaterial", name, &params);
+        let kind = params.find_one_string("type", "");
+        if kind.is_empty() {
+            error!("No parameter string \"type\" found in MakeNamedMaterial");
+            return;
+        }
+        if self.graphics_state.named_materials.contains_key(name) {
+            warn!("Named material \"{}\" redefined.", name);
+        }
+        self.graphics_state
+            .named_materials
+            .insert(name.to_string(), (kind, params));
+    }
+
+    /// Sets the current material to `name` described by `params`.
+    fn material(&mut self, name: &str, params: ParamSet) {
+        verify_world!(self, "pbrt.material");
+        self.cat_named("Material", name, &params);
+        self.graphics_state.material = name.to_string();
+        self.graphics_state.material_params = params;
+    }
+
+    /// Sets the current material to the one stored under `name` by `make_named_material`.
+    fn named_material(&mut self, name: &str) {
+        verify_world!(self, "pbrt.named_material");
+        self.cat(format_args!("NamedMaterial \"{}\"", name));
+        match self.graphics_state.named_materials.get(name) {
+            Some((kind, params)) => {
+                self.graphics_state.material = kind.clone();
+                self.graphics_state.material_params = params.clone();
+            }
+            None => error!("NamedMaterial \"{}\" unknown.", name),
+        }
+    }
+
+    /// Flips the orientation of the surface normals of subsequent shapes.
+    fn reverse_orientation(&mut self) {
+        verify_world!(self, "pbrt.reverse_orientation");
+        self.cat(format_args!("ReverseOrientation"));
+        self.graphics_state.reverse_orientation = !self.graphics_state.reverse_orientation;
+    }
+
+    /// Starts the definition of the object instance `name`.
+    fn object_begin(&mut self, name: &str) {
+        verify_world!(self, "pbrt.object_begin");
+        self.attribute_begin();
+        self.cat(format_args!("ObjectBegin \"{}\"", name));
+        if self.render_options.current_instance.is_some() {
+            error!("ObjectBegin called inside of instance definition");
+        }
         self.render_options
-            .named_media
-            .insert(name.to_string(), medium);
+            .instances
+            .insert(name.to_string(), Vec::new());
+        self.render_options.current_instance = Some(name.to_string());
+    }
+
+    fn object_end(&mut self) {
+        verify_world!(self, "pbrt.object_end");
+        self.cat(format_args!("ObjectEnd"));
+        if self.render_options.current_instance.is_none() {
+            error!("ObjectEnd called outside of instance definition");
+        }
+        self.render_options.current_instance = None;
+        self.attribute_end();
+    }
+
+    fn object_instance(&mut self, name: &str) {
+        verify_world!(self, "pbrt.object_instance");
+        if self.cat_enabled() {
+            self.cat(format_args!("ObjectInstance \"{}\"", name));
+            return;
+        }
+        // Perform object instance error checking
+        if self.render_options.current_instance.is_some() {
+            error!("ObjectInstance can't be called inside instance definition");
+            return;
+        }
+        let ro = &mut self.render_options;
+        let instance = match ro.instances.get_mut(name) {
+            Some(instance) => instance,
+            None => {
+                error!("Unable to find instance named \"{}\"", name);
+                return;
+            }
+        };
+        if instance.is_empty() {
+            return;
+        }
+        if instance.len() > 1 {
+            // Create aggregate for instance _Primitive_s, it's shared by all uses of the instance
+            let prims = std::mem::take(instance);
+            let accel =
+                make_accelerator(&ro.accelerator_name, prims.clone(), &ro.accelerator_params)
+                    .unwrap_or_else(|| {
+                        Arc::new(BVHAccel::new(prims, 1, SplitMethod::Sah)) as Arc<dyn Primitive>
+                    });
+            instance.push(accel);
+        }
+        let instance_to_world = AnimatedTransform::new(
+            self.current_transform[0],
+            ro.transform_start_time,
+            self.current_transform[1],
+            ro.transform_end_time,
+        );
+        let prim = Arc::new(TransformedPrimitive::new(
+            Arc::clone(&instance[0]),
+            instance_to_world,
+        ));
+        ro.primitives.push(prim);
     }
 
-    /// Specifies the current inside and outside media by the names given.  Cameras and lights
-    /// without geometry ignore the `inside_name`.
     fn medium_interface(&mut self, inside_name: &str, outside_name: &str) {
         verify_initialized!(self, "pbrt.medium_interface");
+        self.cat(format_args!(
+            "MediumInterface \"{}\" \"{}\"",
+            inside_name, outside_name
+        ));
         self.graphics_state.current_inside_medium = inside_name.into();
         self.graphics_state.current_outside_medium = outside_name.into();
         self.render_options.have_scattering_media = true;
@@ -847,6 +1492,215 @@ impl Default for PbrtAPI {
 }
 
 impl PbrtAPI {
+    /// Creates the integrator, sampler, camera and scene described by the options and world
+    /// blocks parsed so far, and renders the image.  This is called by `world_end`, but is
+    /// public so users building a scene programmatically can control when rendering happens.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::api::{PbrtAPI, API};
+    ///
+    /// let mut pbrt = PbrtAPI::default();
+    /// pbrt.init();
+    /// pbrt.parse_string(br#"Integrator "bdpt""#).expect("parse");
+    /// pbrt.world_begin();
+    /// // The bidirectional path tracer isn't implemented, so nothing can be rendered.
+    /// assert!(pbrt.render().is_err());
+    /// ```
+    pub fn render(&mut self) -> Result<(), Error> {
+        self.render_film().map(|_| ())
+    }
+
+    /// Parses and renders `scene`, returning the image instead of writing it to disk.  `scene`
+    /// must contain a complete world block, and the API must have been initialized with
+    /// [init](API::init).
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::api::{PbrtAPI, API};
+    ///
+    /// let mut pbrt = PbrtAPI::default();
+    /// pbrt.init();
+    /// let img = pbrt
+    ///     .render_string(
+    ///         br#"
+    ///         Film "image" "integer xresolution" [ 8 ] "integer yresolution" [ 4 ]
+    ///         Sampler "stratified" "integer xsamples" [ 1 ] "integer ysamples" [ 1 ]
+    ///         WorldBegin
+    ///         WorldEnd
+    ///         "#,
+    ///     )
+    ///     .expect("render");
+    /// assert_eq!(img.resolution, [8, 4].into());
+    /// assert_eq!(img.rgb.len(), 3 * 8 * 4);
+    /// // Nothing is in the scene, so the image is black.
+    /// assert!(img.rgb.iter().all(|&c| c == 0.));
+    /// ```
+    pub fn render_string(&mut self, scene: &[u8]) -> Result<RenderedImage, Error> {
+        self.render_to_memory = true;
+        self.rendered = None;
+        let parsed = self.parse_string(scene);
+        self.render_to_memory = false;
+        parsed?;
+        let film = self.rendered.take().unwrap_or_else(|| {
+            Err(Error::Unhandled(
+                "scene has no WorldEnd, nothing was rendered".to_string(),
+            ))
+        })?;
+        let (rgb, crop_bounds) = film.to_rgb_buffer(1.);
+        Ok(RenderedImage {
+            rgb,
+            resolution: film.full_resolution,
+            crop_bounds,
+        })
+    }
+
+    /// Parses the scene read from `r` like [parse_string](API::parse_string), streaming it
+    /// instead of requiring it all in memory.  This is how [parse_file](API::parse_file) reads
+    /// stdin and files that can't be memory mapped, e.g. pipes.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::api::{PbrtAPI, API};
+    ///
+    /// let mut pbrt = PbrtAPI::default();
+    /// pbrt.init();
+    /// pbrt.parse_reader(&br#"Sampler "stratified" "integer xsamples" [ 2 ]"#[..])
+    ///     .expect("parse");
+    /// assert_eq!(pbrt.sampler_settings().0, "stratified");
+    /// ```
+    pub fn parse_reader<R: Read>(&mut self, r: R) -> Result<(), Error> {
+        parse(ByteSourceTokenizer::new(r), self)?;
+        Ok(())
+    }
+
+    /// Parses `data` like [parse_string](API::parse_string), but doesn't render the scene when
+    /// `WorldEnd` is reached.  The options parsed are left for inspection with accessors like
+    /// [camera_settings](PbrtAPI::camera_settings), which is useful for validating scenes.  The
+    /// world block's shapes and lights are kept too, until the next `WorldBegin` or
+    /// [cleanup](API::cleanup) drops them.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::api::{PbrtAPI, API};
+    ///
+    /// let mut pbrt = PbrtAPI::default();
+    /// pbrt.init();
+    /// pbrt.parse_string_no_render(
+    ///     br#"
+    ///     Camera "perspective" "float fov" [ 45 ]
+    ///     WorldBegin
+    ///     Shape "sphere"
+    ///     WorldEnd
+    ///     "#,
+    /// )
+    /// .expect("parse");
+    /// let (name, params) = pbrt.camera_settings();
+    /// assert_eq!(name, "perspective");
+    /// assert_eq!(params.find_one_float("fov", 90.), 45.);
+    /// ```
+    pub fn parse_string_no_render(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.skip_render = true;
+        let parsed = self.parse_string(data);
+        self.skip_render = false;
+        parsed
+    }
+
+    /// Renders the current scene like [render](PbrtAPI::render) and returns the film it was
+    /// rendered to.  When rendering to memory the film's filename is cleared so it isn't written
+    /// to disk.
+    fn render_film(&mut self) -> Result<Arc<Film>, Error> {
+        let mut film = self.render_options.make_film(&self.opt)?;
+        if self.render_to_memory {
+            film.filename.clear();
+        }
+        let film = Arc::new(film);
+        let camera = self.render_options.make_camera(Arc::clone(&film))?;
+        let mut integrator = self.render_options.make_integrator(camera, &self.opt)?;
+        let scene = self.render_options.make_scene().ok_or_else(|| {
+            Error::Create(
+                "scene",
+                format!(
+                    "accelerator '{}' unknown",
+                    self.render_options.accelerator_name
+                ),
+            )
+        })?;
+        integrator.render(&scene);
+        Ok(film)
+    }
+
+    /// Returns the name and parameters of the film set by the most recent `Film` directive, or
+    /// the defaults if there was none.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::api::{PbrtAPI, API};
+    ///
+    /// let mut pbrt = PbrtAPI::default();
+    /// pbrt.init();
+    /// pbrt.parse_string(br#"Film "image" "integer xresolution" [ 32 ]"#)
+    ///     .expect("parse");
+    /// let (name, params) = pbrt.film_settings();
+    /// assert_eq!(name, "image");
+    /// assert_eq!(params.find_one_int("xresolution", 0), 32);
+    /// ```
+    pub fn film_settings(&self) -> (&str, &ParamSet) {
+        (
+            &self.render_options.film_name,
+            &self.render_options.film_params,
+        )
+    }
+
+    /// Returns the name and parameters of the camera set by the most recent `Camera` directive,
+    /// or the defaults if there was none.
+    pub fn camera_settings(&self) -> (&str, &ParamSet) {
+        (
+            &self.render_options.camera_name,
+            &self.render_options.camera_params,
+        )
+    }
+
+    /// Returns the name and parameters of the sampler set by the most recent `Sampler`
+    /// directive, or the defaults if there was none.
+    pub fn sampler_settings(&self) -> (&str, &ParamSet) {
+        (
+            &self.render_options.sampler_name,
+            &self.render_options.sampler_params,
+        )
+    }
+
+    /// Returns the name and parameters of the integrator set by the most recent `Integrator`
+    /// directive, or the defaults if there was none.
+    pub fn integrator_settings(&self) -> (&str, &ParamSet) {
+        (
+            &self.render_options.integrator_name,
+            &self.render_options.integrator_params,
+        )
+    }
+
+    /// Returns the name and parameters of the accelerator set by the most recent `Accelerator`
+    /// directive, or the defaults if there was none.
+    pub fn accelerator_settings(&self) -> (&str, &ParamSet) {
+        (
+            &self.render_options.accelerator_name,
+            &self.render_options.accelerator_params,
+        )
+    }
+
+    /// Returns the lights created by `LightSource` directives in the current world block.
+    /// Rendering moves them into the scene, so this is empty after `WorldEnd`.
+    pub fn lights(&self) -> &[Arc<dyn Light>] {
+        &self.render_options.lights
+    }
+
+    /// Returns the transform saved by `CoordinateSystem` as `name`, or by the renderer for
+    /// "camera" and "world".  If the saved transform is animated, the transform at the start time
+    /// is returned.
+    pub fn named_coordinate_system(&self, name: &str) -> Option<Transform> {
+        self.named_coordinate_systems.get(name).map(|ts| ts[0])
+    }
+
     /// Verifies all the active transforms are equivalent to `t`.
     ///
     /// # Note
@@ -894,6 +1748,110 @@ impl PbrtAPI {
         }
     }
 
+    /// Sets where the scene is written when `Options::cat` or `Options::to_ply` is set, stdout by
+    /// default.
+    pub fn set_cat_output<W: Write + Send + 'static>(&mut self, w: W) {
+        self.cat_output = CatOutput(Box::new(w));
+    }
+
+    /// Sets the prefix of the PLY files triangle meshes are written to when `Options::to_ply` is
+    /// set, "mesh" by default.  Files are named `<prefix>_00001.ply`, `<prefix>_00002.ply`, etc.
+    pub fn set_ply_prefix(&mut self, prefix: &str) {
+        self.ply_prefix = prefix.to_string();
+    }
+
+    /// Returns true if the scene should be written out instead of rendered.
+    fn cat_enabled(&self) -> bool {
+        self.opt.cat || self.opt.to_ply
+    }
+
+    /// Writes the geometry of the triangle mesh described by `params` to a new binary PLY file,
+    /// and the `plymesh` shape referencing it in place of the mesh.  Files are named with the
+    /// prefix given to [set_ply_prefix].  The mesh is written inline if its geometry is invalid or
+    /// can't be written.
+    ///
+    /// [set_ply_prefix]: PbrtAPI::set_ply_prefix
+    fn cat_ply_mesh(&mut self, params: &ParamSet) {
+        let indices = params.find_int("indices").unwrap_or_default();
+        let p = params.find_point3f("P").unwrap_or_default();
+        let mut uv = params.find_point2f("uv").unwrap_or_default();
+        if uv.is_empty() {
+            uv = params.find_point2f("st").unwrap_or_default();
+        }
+        let mut n = params.find_normal3f("N").unwrap_or_default();
+        if !uv.is_empty() && uv.len() != p.len() {
+            uv.clear();
+        }
+        if !n.is_empty() && n.len() != p.len() {
+            n.clear();
+        }
+        let excess = indices.len() % 3;
+        if indices.is_empty()
+            || excess != 0
+            || indices.iter().any(|&i| i < 0 || i as usize >= p.len())
+        {
+            error!("Invalid trianglemesh, writing it inline");
+            self.cat_named("Shape", "trianglemesh", params);
+            return;
+        }
+        let indices: Vec<_> = indices.into_iter().map(|i| i as usize).collect();
+
+        self.ply_count += 1;
+        let filename = format!("{}_{:05}.ply", self.ply_prefix, self.ply_count);
+        if let Err(err) = write_ply_file(
+            &filename,
+            plyutil::Format::BinaryLittleEndian,
+            &indices,
+            &p,
+            &n,
+            &uv,
+        ) {
+            error!("Failed to write PLY file '{}': {}", filename, err);
+            self.cat_named("Shape", "trianglemesh", params);
+            return;
+        }
+        let mut params = params.clone();
+        for name in &["indices", "P", "uv", "st", "N", "S"] {
+            params.erase(name);
+        }
+        params.add_string("filename", vec![filename]);
+        self.cat_named("Shape", "plymesh", &params);
+    }
+
+    /// Writes `line` at the current indentation when `Options::cat` or `Options::to_ply` is set.
+    fn cat(&mut self, line: fmt::Arguments<'_>) {
+        if !self.cat_enabled() {
+            return;
+        }
+        if let Err(err) = writeln!(
+            self.cat_output.0,
+            "{:indent$}{}",
+            "",
+            line,
+            indent = self.cat_indent
+        ) {
+            error!("Failed to write scene: {}", err);
+        }
+    }
+
+    /// Writes a directive of the form `Directive "name" params...` when `Options::cat` or
+    /// `Options::to_ply` is set.  An empty `name` is omitted.
+    fn cat_named(&mut self, directive: &str, name: &str, params: &ParamSet) {
+        if !self.cat_enabled() {
+            return;
+        }
+        let mut line = directive.to_string();
+        if !name.is_empty() {
+            line += &format!(" \"{}\"", name);
+        }
+        let params = params.to_string();
+        if !params.is_empty() {
+            line += " ";
+            line += &params;
+        }
+        self.cat(format_args!("{}", line));
+    }
+
     fn warn_if_animated_transform(&self, name: &str) {
         if self.current_transform.is_animated() {
             warn!(
@@ -902,6 +1860,14 @@ impl PbrtAPI {
     }
 }
 
+/// Formats `vs` separated by spaces, as used for matrices written by `Options::cat`.
+fn join_floats(vs: &[Float]) -> String {
+    vs.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn make_float_texture(
     name: &str,
     tex2world: &Transform,
@@ -911,8 +1877,14 @@ fn make_float_texture(
         "constant" => Some(Box::new(constant::create_constant_float_texture(
             tex2world, tp,
         ))),
-        "scale" | "mix" | "bilerp" | "imagemap" | "uv" | "checkerboard" | "dots" | "fbm"
-        | "wrinkled" | "marble" | "windy" => {
+        "imagemap" => Some(Box::new(image::create_image_float_texture(tex2world, tp))),
+        "dots" => Some(Box::new(dots::create_dots_float_texture(tex2world, tp))),
+        "fbm" => Some(Box::new(fbm::create_fbm_float_texture(tex2world, tp))),
+        "wrinkled" => Some(Box::new(wrinkled::create_wrinkled_float_texture(
+            tex2world, tp,
+        ))),
+        "windy" => Some(Box::new(windy::create_windy_float_texture(tex2world, tp))),
+        "scale" | "mix" | "bilerp" | "uv" | "checkerboard" | "marble" => {
             unimplemented!("Float texture type '{}' not implemented", name);
         }
         _ => {
@@ -931,8 +1903,19 @@ fn make_spectrum_texture(
         "constant" => Some(Box::new(constant::create_constant_spectrum_texture(
             tex2world, tp,
         ))),
-        "scale" | "mix" | "bilerp" | "imagemap" | "uv" | "checkerboard" | "dots" | "fbm"
-        | "wrinkled" | "marble" | "windy" => {
+        "imagemap" => Some(Box::new(image::create_image_spectrum_texture(
+            tex2world, tp,
+        ))),
+        "uv" => Some(Box::new(uv::create_uv_spectrum_texture(tex2world, tp))),
+        "dots" => Some(Box::new(dots::create_dots_spectrum_texture(tex2world, tp))),
+        "fbm" => Some(Box::new(fbm::create_fbm_spectrum_texture(tex2world, tp))),
+        "wrinkled" => Some(Box::new(wrinkled::create_wrinkled_spectrum_texture(
+            tex2world, tp,
+        ))),
+        "windy" => Some(Box::new(windy::create_windy_spectrum_texture(
+            tex2world, tp,
+        ))),
+        "scale" | "mix" | "bilerp" | "checkerboard" | "marble" => {
             unimplemented!("Spectrum texture type '{}' not implemented", name);
         }
         _ => {
@@ -942,12 +1925,40 @@ fn make_spectrum_texture(
     }
 }
 
-fn make_medium(_name: &str, _params: &mut ParamSet, _medium2world: Transform) -> Arc<dyn Medium> {
-    unimplemented!("make_medium");
+fn make_material(name: &str, mp: &TextureParams) -> Option<Arc<dyn Material>> {
+    let material: Arc<dyn Material> = match name {
+        "" | "none" => return None,
+        "matte" => Arc::new(create_matte_material(mp)),
+        "plastic" => Arc::new(create_plastic_material(mp)),
+        "mirror" => Arc::new(create_mirror_material(mp)),
+        "glass" => Arc::new(create_glass_material(mp)),
+        "metal" => Arc::new(create_metal_material(mp)),
+        "substrate" => Arc::new(create_substrate_material(mp)),
+        "uber" => Arc::new(create_uber_material(mp)),
+        "translucent" | "hair" | "mix" | "subsurface" | "kdsubsurface" | "fourier" | "disney" => {
+            unimplemented!("Material type '{}' not implemented", name)
+        }
+        _ => {
+            warn!("Material \"{}\" unknown. Using \"matte\".", name);
+            Arc::new(create_matte_material(mp))
+        }
+    };
+    Some(material)
+}
+
+fn make_medium(name: &str, params: &ParamSet, medium2world: Transform) -> Option<Arc<dyn Medium>> {
+    let medium: Arc<dyn Medium> = match name {
+        "heterogeneous" => Arc::new(create_grid_density_medium(params, &medium2world)?),
+        "homogeneous" => Arc::new(create_homogeneous_medium(params)),
+        _ => {
+            warn!("Medium \"{}\" unknown.", name);
+            return None;
+        }
+    };
+    params.report_unused();
+    Some(medium)
 }
 
-// TODO(wathiede): remove #[allow(dead_code)] after make_camera is implemented.
-#[allow(dead_code)]
 fn make_filter(name: &str, param_set: &ParamSet) -> Box<dyn Filter> {
     let filter = match name {
         "box" => Box::new(BoxFilter::create_box_filter(param_set)),
@@ -963,16 +1974,153 @@ fn make_filter(name: &str, param_set: &ParamSet) -> Box<dyn Filter> {
     filter
 }
 
+fn make_accelerator(
+    name: &str,
+    prims: Vec<Arc<dyn Primitive>>,
+    params: &ParamSet,
+) -> Option<Arc<dyn Primitive>> {
+    let accel: Arc<dyn Primitive> = match name {
+        "bvh" => Arc::new(create_bvh_accelerator(prims, params)),
+        "kdtree" => Arc::new(create_kdtree_accelerator(prims, params)),
+        _ => {
+            warn!("Accelerator \"{}\" unknown.", name);
+            return None;
+        }
+    };
+    params.report_unused();
+    Some(accel)
+}
+
+fn make_film(
+    name: &str,
+    params: &ParamSet,
+    filter: Box<dyn Filter>,
+    opt: &Options,
+) -> Result<Film, Error> {
+    let mut film = match name {
+        "image" => {
+            create_film(params, filter, opt).map_err(|e| Error::Create("film", e.to_string()))?
+        }
+        _ => return Err(Error::Create("film", format!("film '{}' unknown", name))),
+    };
+    if !opt.image_file.is_empty() {
+        if !params.find_one_string("filename", "").is_empty() {
+            warn!(
+                "Output filename supplied on command line, \"{}\" is overriding filename provided in scene description file, \"{}\".",
+                opt.image_file, film.filename
+            );
+        }
+        film.filename = opt.image_file.clone();
+    }
+    params.report_unused();
+    Ok(film)
+}
+
+fn make_camera(
+    name: &str,
+    params: &ParamSet,
+    cam2world_set: &TransformSet,
+    transform_start: Float,
+    transform_end: Float,
+    film: Arc<Film>,
+    medium: Option<Arc<dyn Medium>>,
+) -> Option<Arc<dyn Camera>> {
+    let animated_cam2world = AnimatedTransform::new(
+        cam2world_set[0],
+        transform_start,
+        cam2world_set[1],
+        transform_end,
+    );
+    let camera: Arc<dyn Camera> = match name {
+        "perspective" => Arc::new(create_perspective_camera(
+            params,
+            &animated_cam2world,
+            film,
+            medium,
+        )),
+        "orthographic" => Arc::new(create_orthographic_camera(
+            params,
+            &animated_cam2world,
+            film,
+            medium,
+        )),
+        "environment" => Arc::new(create_environment_camera(
+            params,
+            &animated_cam2world,
+            film,
+            medium,
+        )),
+        "realistic" => {
+            unimplemented!("Camera type '{}' not implemented", name)
+        }
+        _ => {
+            warn!("Camera \"{}\" unknown.", name);
+            return None;
+        }
+    };
+    params.report_unused();
+    Some(camera)
+}
+
+fn make_sampler(name: &str, params: &ParamSet, _film: &Film, opt: &Options) -> Box<dyn Sampler> {
+    let sampler: Box<dyn Sampler> = match name {
+        "lowdiscrepancy" | "02sequence" => Box::new(create_zero_two_sequence_sampler(params, opt)),
+        "maxmindist" => Box::new(create_max_min_dist_sampler(params, opt)),
+        "random" => Box::new(create_random_sampler(params, opt)),
+        "stratified" => Box::new(create_stratified_sampler(params, opt)),
+        _ => {
+            warn!("Sampler \"{}\" unknown.  Using \"random\".", name);
+            Box::new(create_random_sampler(params, opt))
+        }
+    };
+    params.report_unused();
+    sampler
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::core::{paramset::testutils::make_float_param_set, transform::Matrix4x4};
+    use crate::{
+        core::{
+            geometry::Bounds3f,
+            interaction::Interaction,
+            light::LightFlags,
+            paramset::testutils::{make_float_param_set, make_string_param_set},
+            transform::Matrix4x4,
+        },
+        shapes::sphere::Sphere,
+    };
 
     use super::*;
 
     #[test]
     fn test_transform_set() {
-        let ts: TransformSet = Default::default();
+        let mut ts: TransformSet = Default::default();
+        assert!(!ts.is_animated());
+        ts[1] = Transform::scale(2., 2., 2.);
+        assert!(ts.is_animated());
+        assert!(ts.is_animated());
+        ts[1] = Transform::identity();
         assert!(!ts.is_animated());
+        ts[0] = Transform::translate([1., 0., 0.]);
+        assert!(ts.is_animated());
+        assert!(ts.inverse().is_animated());
+        assert!(!TransformSet::default().inverse().is_animated());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "transform index 2 out of range")]
+    fn test_transform_set_index_out_of_range() {
+        let ts: TransformSet = Default::default();
+        let _ = ts[2];
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "transform index 2 out of range")]
+    fn test_transform_set_index_mut_out_of_range() {
+        let mut ts: TransformSet = Default::default();
+        ts[2] = Transform::identity();
     }
 
     #[test]
@@ -991,7 +2139,7 @@ mod tests {
             )
         );
 
-        pbrt.coordinate_system("two".into());
+        pbrt.coordinate_system("two");
         pbrt.identity();
         pbrt.scale(3., 3., 3.);
         assert_eq!(
@@ -1004,7 +2152,7 @@ mod tests {
             )
         );
 
-        pbrt.coordinate_system_transform("two".into());
+        pbrt.coordinate_system_transform("two");
         assert_eq!(
             pbrt.current_transform.t[0].matrix(),
             Matrix4x4::new(
@@ -1062,4 +2210,627 @@ mod tests {
         assert_eq!(bf.radius(), [1., 0.5].into());
         assert_eq!(bf.inv_radius(), [1., 2.].into());
     }
+
+    #[test]
+    fn test_make_material() {
+        let mp = TextureParams::default();
+        assert!(make_material("none", &mp).is_none());
+        assert!(make_material("", &mp).is_none());
+        let plastic = make_material("plastic", &mp).expect("plastic");
+        assert!(format!("{:?}", plastic).starts_with("PlasticMaterial"));
+        let uber = make_material("uber", &mp).expect("uber");
+        assert!(format!("{:?}", uber).starts_with("UberMaterial"));
+        let substrate = make_material("substrate", &mp).expect("substrate");
+        assert!(format!("{:?}", substrate).starts_with("SubstrateMaterial"));
+        let metal = make_material("metal", &mp).expect("metal");
+        assert!(format!("{:?}", metal).starts_with("MetalMaterial"));
+        let fallback = make_material("no-such-material", &mp).expect("matte fallback");
+        assert!(format!("{:?}", fallback).starts_with("MatteMaterial"));
+    }
+
+    #[test]
+    fn test_settings_accessors() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [ 45 ]
+            Film "image" "integer xresolution" [ 64 ] "integer yresolution" [ 32 ]
+            Sampler "stratified" "integer xsamples" [ 2 ]
+            Integrator "whitted" "integer maxdepth" [ 3 ]
+            Accelerator "kdtree" "integer maxprims" [ 2 ]
+            Scale 2 2 2
+            "#,
+        )
+        .expect("parse");
+        pbrt.coordinate_system("doubled");
+        pbrt.parse_string(br#"WorldBegin LightSource "point" "rgb I" [ 1 1 1 ]"#)
+            .expect("parse");
+
+        let (name, params) = pbrt.camera_settings();
+        assert_eq!(name, "perspective");
+        assert_eq!(params.find_one_float("fov", 0.), 45.);
+        let (name, params) = pbrt.film_settings();
+        assert_eq!(name, "image");
+        assert_eq!(params.find_one_int("yresolution", 0), 32);
+        let (name, params) = pbrt.sampler_settings();
+        assert_eq!(name, "stratified");
+        assert_eq!(params.find_one_int("xsamples", 0), 2);
+        let (name, params) = pbrt.integrator_settings();
+        assert_eq!(name, "whitted");
+        assert_eq!(params.find_one_int("maxdepth", 0), 3);
+        let (name, params) = pbrt.accelerator_settings();
+        assert_eq!(name, "kdtree");
+        assert_eq!(params.find_one_int("maxprims", 0), 2);
+        assert_eq!(pbrt.lights().len(), 1);
+
+        let look_at = Transform::look_at([0., 0., 5.], [0., 0., 0.], [0., 1., 0.]);
+        assert_eq!(
+            pbrt.named_coordinate_system("camera"),
+            Some(look_at.inverse())
+        );
+        assert_eq!(
+            pbrt.named_coordinate_system("doubled"),
+            Some(look_at * Transform::scale(2., 2., 2.))
+        );
+        assert_eq!(
+            pbrt.named_coordinate_system("world"),
+            Some(Transform::identity())
+        );
+        assert_eq!(pbrt.named_coordinate_system("missing"), None);
+    }
+
+    #[test]
+    fn test_default_settings() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        assert_eq!(pbrt.film_settings().0, "image");
+        assert_eq!(pbrt.camera_settings().0, "perspective");
+        assert_eq!(pbrt.sampler_settings().0, "halton");
+        assert_eq!(pbrt.integrator_settings().0, "path");
+        assert_eq!(pbrt.accelerator_settings().0, "bvh");
+        assert!(pbrt.lights().is_empty());
+    }
+
+    #[test]
+    fn test_area_light_source() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            WorldBegin
+            AreaLightSource "diffuse" "rgb L" [ 4 4 4 ] "bool twosided" "true"
+            "#,
+        )
+        .expect("parse");
+        assert_eq!(pbrt.graphics_state.area_light, "diffuse");
+
+        let t = Transform::identity();
+        let sphere = Arc::new(Sphere::new(t, t, false, 1., -1., 1., 360.));
+        let gs = &pbrt.graphics_state;
+        let light = make_area_light(
+            &gs.area_light,
+            &t,
+            &MediumInterface::default(),
+            &gs.area_light_params,
+            sphere.clone(),
+            &pbrt.opt,
+        )
+        .expect("diffuse area light");
+        let it = Interaction {
+            n: [0., 0., 1.].into(),
+            ..Default::default()
+        };
+        assert_eq!(light.l(&it, &[0., 0., -1.].into()), Spectrum::new(4.));
+        assert!(make_area_light(
+            "no-such-light",
+            &t,
+            &MediumInterface::default(),
+            &ParamSet::default(),
+            sphere,
+            &pbrt.opt,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_shape() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            WorldBegin
+            Shape "sphere" "float radius" 2
+            Shape "hyperboloid"
+            Shape "no-such-shape"
+            Shape "trianglemesh" "integer indices" [ 0 1 2 0 2 3 ]
+                "point3 P" [ 0 0 0 1 0 0 1 1 0 0 1 0 ]
+            Shape "plymesh" "string filename" "src/shapes/testdata/triangle.ply"
+            AreaLightSource "diffuse" "rgb L" [ 1 1 1 ]
+            Scale 2 2 2
+            Shape "paraboloid" "float zmax" 2
+            "#,
+        )
+        .expect("parse");
+        let primitives = &pbrt.render_options.primitives;
+        assert_eq!(primitives.len(), 6);
+        assert_eq!(
+            primitives[0].world_bound(),
+            Bounds3f::from([[-2., -2., -2.], [2., 2., 2.]])
+        );
+        assert_eq!(
+            primitives[3].world_bound(),
+            Bounds3f::from([[0., 0., 0.], [1., 1., 0.]])
+        );
+        assert_eq!(
+            primitives[5].world_bound(),
+            Bounds3f::from([[-2., -2., 0.], [2., 2., 4.]])
+        );
+        // Only the shape created after AreaLightSource emits light.
+        assert_eq!(pbrt.lights().len(), 1);
+        assert_eq!(pbrt.lights()[0].flags(), LightFlags::AREA);
+    }
+
+    #[test]
+    fn test_object_instance() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            WorldBegin
+            ObjectBegin "pair"
+            Shape "sphere"
+            Shape "sphere" "float radius" 2
+            ObjectEnd
+            ObjectInstance "pair"
+            Scale 2 2 2
+            ObjectInstance "pair"
+            ObjectInstance "no-such-instance"
+            "#,
+        )
+        .expect("parse");
+        let primitives = &pbrt.render_options.primitives;
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(
+            primitives[0].world_bound(),
+            Bounds3f::from([[-2., -2., -2.], [2., 2., 2.]])
+        );
+        assert_eq!(
+            primitives[1].world_bound(),
+            Bounds3f::from([[-4., -4., -4.], [4., 4., 4.]])
+        );
+        assert!(pbrt.render_options.current_instance.is_none());
+    }
+
+    #[test]
+    fn test_world_blocks_start_empty() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        let options = br#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [ 30 ]
+            Film "image" "integer xresolution" [ 4 ] "integer yresolution" [ 4 ]
+            Sampler "stratified" "integer xsamples" [ 1 ] "integer ysamples" [ 1 ]
+            Integrator "directlighting"
+            "#;
+        pbrt.parse_string(options).expect("parse");
+        let lit = pbrt
+            .render_string(
+                br#"
+                WorldBegin
+                LightSource "point" "point from" [ 0 0 5 ] "rgb I" [ 10 10 10 ]
+                Shape "sphere"
+                WorldEnd
+                "#,
+            )
+            .expect("render");
+        assert!(lit.rgb.iter().any(|&c| c > 0.));
+        assert!(pbrt.render_options.primitives.is_empty());
+        assert!(pbrt.lights().is_empty());
+
+        // Nothing from the first world block may leak into the second.
+        let empty = pbrt.render_string(b"WorldBegin WorldEnd").expect("render");
+        assert!(empty.rgb.iter().all(|&c| c == 0.));
+
+        pbrt.parse_string(
+            br#"
+            WorldBegin
+            LightSource "point" "point from" [ 0 0 5 ]
+            Shape "sphere"
+            WorldEnd
+            WorldBegin
+            ObjectBegin "ball"
+            Shape "sphere"
+            "#,
+        )
+        .expect("parse");
+        assert!(pbrt.render_options.primitives.is_empty());
+        assert!(pbrt.lights().is_empty());
+        assert_eq!(pbrt.render_options.instances.len(), 1);
+        pbrt.parse_string(br#"ObjectEnd Shape "sphere" WorldEnd WorldBegin"#)
+            .expect("parse");
+        assert!(pbrt.render_options.instances.is_empty());
+        assert!(pbrt.render_options.current_instance.is_none());
+    }
+
+    #[test]
+    fn test_parse_string_no_render() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let filename = dir.path().join("out.pfm");
+        let scene = format!(
+            r#"
+            Film "image" "string filename" [ "{}" ]
+                "integer xresolution" [ 4 ] "integer yresolution" [ 4 ]
+            Sampler "stratified" "integer xsamples" [ 1 ] "integer ysamples" [ 1 ]
+            Camera "orthographic"
+            WorldBegin
+            LightSource "point"
+            Shape "sphere"
+            WorldEnd
+            "#,
+            filename.display()
+        );
+        let mut pbrt = PbrtAPI::default();
+        pbrt.init();
+        pbrt.parse_string_no_render(scene.as_bytes())
+            .expect("parse");
+        assert_eq!(pbrt.camera_settings().0, "orthographic");
+        assert_eq!(pbrt.sampler_settings().0, "stratified");
+        assert_eq!(pbrt.render_options.primitives.len(), 1);
+        assert_eq!(pbrt.render_options.lights.len(), 1);
+        assert!(!filename.exists());
+
+        // The next world block replaces the one kept for inspection.
+        pbrt.parse_string_no_render(scene.as_bytes())
+            .expect("parse");
+        assert_eq!(pbrt.render_options.primitives.len(), 1);
+        assert_eq!(pbrt.render_options.lights.len(), 1);
+
+        // Rendering is only skipped for that call, and the scene rendered moves the world's
+        // contents out of the options.
+        pbrt.parse_string(scene.as_bytes()).expect("parse");
+        assert!(filename.exists());
+        assert!(pbrt.render_options.primitives.is_empty());
+        assert!(pbrt.render_options.lights.is_empty());
+    }
+
+    #[test]
+    fn test_make_named_medium() {
+        let mut pbrt = PbrtAPI::default();
+        pbrt.init();
+        let mut params = make_float_param_set("density", vec![0.5, 1.]);
+        params.add_string("type", vec!["heterogeneous".to_string()]);
+        params.add_int("nx", vec![2]);
+        pbrt.make_named_medium("smoke", &mut params);
+        assert!(pbrt.render_options.named_media.contains_key("smoke"));
+
+        // Wrong number of density values.
+        params.add_int("nx", vec![3]);
+        pbrt.make_named_medium("bad", &mut params);
+        assert!(!pbrt.render_options.named_media.contains_key("bad"));
+
+        let mut params = make_string_param_set("type", vec!["homogeneous".to_string()]);
+        pbrt.make_named_medium("fog", &mut params);
+        assert!(pbrt.render_options.named_media.contains_key("fog"));
+    }
+
+    #[test]
+    fn test_transform_cache() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        let m = [
+            1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 1., 2., 3., 1.,
+        ];
+        pbrt.concat_transform(m);
+        pbrt.concat_transform(m);
+        pbrt.transform(m);
+        assert_eq!(pbrt.transform_cache.len(), 1);
+        pbrt.world_begin();
+        pbrt.world_end();
+        assert!(pbrt.transform_cache.is_empty());
+    }
+
+    #[test]
+    fn test_parse_file_no_mmap() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let path = dir.path().join("scene.pbrt");
+        std::fs::write(&path, br#"Sampler "stratified" "integer xsamples" [ 2 ]"#)
+            .expect("write scene");
+        let empty = dir.path().join("empty.pbrt");
+        std::fs::write(&empty, b"").expect("write empty scene");
+        for no_mmap in &[false, true] {
+            let mut pbrt = PbrtAPI::from(Options {
+                no_mmap: *no_mmap,
+                ..Default::default()
+            });
+            pbrt.init();
+            pbrt.parse_file(&path).expect("parse");
+            assert_eq!(pbrt.sampler_settings().0, "stratified");
+            // Empty files can't be mapped.
+            pbrt.parse_file(&empty).expect("parse empty");
+            assert!(pbrt.parse_file(dir.path().join("missing.pbrt")).is_err());
+        }
+    }
+
+    /// `Write` implementation that can be read after being handed to `PbrtAPI::set_cat_output`.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cat() {
+        let buf = SharedBuf::default();
+        let mut pbrt = PbrtAPI::from(Options {
+            cat: true,
+            ..Default::default()
+        });
+        pbrt.set_cat_output(buf.clone());
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            Scale 2 2 2
+            Sampler "stratified" "integer xsamples" [ 4 ]
+            WorldBegin
+            "#,
+        )
+        .expect("parse");
+        let got = String::from_utf8(buf.0.lock().unwrap().clone()).expect("utf8");
+        assert!(got.lines().any(|l| l == "Scale 2 2 2"), "{}", got);
+
+        pbrt.attribute_begin();
+        pbrt.translate(1., 0., -0.5);
+        pbrt.parse_string(br#"Shape "sphere" "float radius" 0.25"#)
+            .expect("parse");
+        pbrt.attribute_end();
+        pbrt.world_end();
+        let got = String::from_utf8(buf.0.lock().unwrap().clone()).expect("utf8");
+        assert_eq!(
+            got,
+            r#"Scale 2 2 2
+Sampler "stratified" "integer xsamples" [ 4 ]
+WorldBegin
+AttributeBegin
+    Translate 1 0 -0.5
+    Shape "sphere" "float radius" [ 0.25 ]
+AttributeEnd
+WorldEnd
+"#
+        );
+        // Shapes aren't created in cat mode.
+        assert!(pbrt.render_options.primitives.is_empty());
+    }
+
+    #[test]
+    fn test_to_ply() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let prefix = dir.path().join("tri");
+        let buf = SharedBuf::default();
+        let mut pbrt = PbrtAPI::from(Options {
+            to_ply: true,
+            ..Default::default()
+        });
+        pbrt.set_cat_output(buf.clone());
+        pbrt.set_ply_prefix(prefix.to_str().expect("utf8 path"));
+        pbrt.init();
+        pbrt.parse_string(
+            br#"
+            WorldBegin
+            Shape "trianglemesh" "integer indices" [ 0 1 2 ]
+                "point P" [ 0 0 0 1 0 0 0 1 0 ] "float alpha" [ 0.5 ]
+            Shape "sphere"
+            WorldEnd
+            "#,
+        )
+        .expect("parse");
+        let filename = format!("{}_00001.ply", prefix.display());
+        let got = String::from_utf8(buf.0.lock().unwrap().clone()).expect("utf8");
+        assert_eq!(
+            got,
+            format!(
+                r#"WorldBegin
+Shape "plymesh" "float alpha" [ 0.5 ] "string filename" [ "{}" ]
+Shape "sphere"
+WorldEnd
+"#,
+                filename
+            )
+        );
+        let (p, _, _, indices) = plyutil::read_ply(&filename).expect("read ply");
+        assert_eq!(p.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_quick_render() {
+        let scene = br#"
+            Film "image" "integer xresolution" [ 64 ] "integer yresolution" [ 32 ]
+            Sampler "stratified" "integer xsamples" [ 4 ] "integer ysamples" [ 4 ]
+            "#;
+        for (quick_render, res, spp) in &[(false, [64, 32], 16), (true, [16, 8], 4)] {
+            let mut pbrt = PbrtAPI::from(Options {
+                quick_render: *quick_render,
+                ..Default::default()
+            });
+            pbrt.init();
+            pbrt.parse_string(scene).expect("parse");
+            let film = pbrt.render_options.make_film(&pbrt.opt).expect("film");
+            let camera = pbrt
+                .render_options
+                .make_camera(Arc::new(film))
+                .expect("camera");
+            let film = camera.film();
+            assert_eq!(film.full_resolution, Point2i::from(*res));
+            let (name, params) = pbrt.sampler_settings();
+            let sampler = make_sampler(name, params, &film, &pbrt.opt);
+            assert_eq!(sampler.samples_per_pixel(), *spp);
+        }
+    }
+
+    #[test]
+    fn test_film_max_sample_luminance() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(
+            br#"Film "image" "integer xresolution" [ 4 ] "integer yresolution" [ 4 ]
+                "float maxsampleluminance" [ 10 ]"#,
+        )
+        .expect("parse");
+        let film = pbrt.render_options.make_film(&pbrt.opt).expect("film");
+        let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+        tile.add_sample([0.5, 0.5].into(), Spectrum::new(1e6), 1.);
+        film.merge_film_tile(tile);
+        let y = film.get_pixel_xyz([0, 0].into())[1];
+        assert!((y - 10.).abs() < 1e-4, "{}", y);
+    }
+
+    #[test]
+    fn test_film_crop_window() {
+        let film = |crop: &str, pixel_bounds: Option<Bounds2i>| {
+            let mut pbrt = PbrtAPI::from(Options {
+                pixel_bounds,
+                ..Default::default()
+            });
+            pbrt.init();
+            pbrt.parse_string(
+                format!(
+                    r#"Film "image" "integer xresolution" [ 8 ] "integer yresolution" [ 4 ]
+                        "float cropwindow" [ {} ]"#,
+                    crop
+                )
+                .as_bytes(),
+            )
+            .expect("parse");
+            pbrt.render_options.make_film(&pbrt.opt)
+        };
+        let bounds =
+            |crop, pixel_bounds| film(crop, pixel_bounds).expect("film").cropped_pixel_bounds;
+
+        // Swapped coordinates are reordered.
+        assert_eq!(
+            bounds("0.75 0.25 1 0.5", None),
+            Bounds2i::from([[2, 2], [6, 4]])
+        );
+        // Coordinates outside of [0, 1] are clamped.
+        assert_eq!(
+            bounds("-0.5 0.5 0.5 1.5", None),
+            Bounds2i::from([[0, 2], [4, 4]])
+        );
+        // Pixel bounds are intersected with the crop window.
+        assert_eq!(
+            bounds("0.5 1 0 1", Some(Bounds2i::from([[2, 1], [6, 3]]))),
+            Bounds2i::from([[4, 1], [6, 3]])
+        );
+
+        // Windows covering no pixels are rejected with the values given.
+        let err = film("0.5 0.5 0.25 0.75", None).unwrap_err().to_string();
+        assert!(err.contains("[ 0.5, 0.25 ] - [ 0.5, 0.75 ]"), "{}", err);
+        let err = film("0 1 0 1", Some(Bounds2i::from([[8, 0], [10, 4]])))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("pixel bounds"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_sampler_falls_back_to_random() {
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        pbrt.parse_string(br#"Sampler "pmj02bn" "integer pixelsamples" [ 8 ]"#)
+            .expect("parse");
+        let film = pbrt.render_options.make_film(&pbrt.opt).expect("film");
+        let (name, params) = pbrt.sampler_settings();
+        let sampler = make_sampler(name, params, &film, &pbrt.opt);
+        assert_eq!(sampler.samples_per_pixel(), 8);
+    }
+
+    #[test]
+    fn test_init_threads_once() {
+        assert_eq!(Options::default().num_threads, 0);
+        // The first init configures the global thread pool, or finds it already configured by
+        // another test.  Either way, later inits asking for the same pool are quiet no-ops.
+        PbrtAPI::default().init();
+        let ((), messages) = crate::core::logtest::capture(|| PbrtAPI::default().init());
+        assert!(
+            messages.iter().all(|(level, _)| *level > log::Level::Warn),
+            "{:?}",
+            messages
+        );
+    }
+
+    #[test]
+    fn test_render_thread_count_independent() {
+        // Each tile's sampler is seeded from its position, so the image doesn't depend on how
+        // many threads render it.  Each render runs on a pool of its own, leaving the global
+        // thread pool shared by the other tests alone.
+        let render = |n| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .expect("thread pool");
+            pool.install(|| {
+                let mut pbrt = PbrtAPI::default();
+                pbrt.init();
+                pbrt.render_string(
+                    br#"
+                    LookAt 0 0 5  0 0 0  0 1 0
+                    Camera "perspective" "float fov" [ 30 ]
+                    Film "image" "integer xresolution" [ 40 ] "integer yresolution" [ 24 ]
+                    Sampler "stratified" "integer xsamples" [ 2 ] "integer ysamples" [ 2 ]
+                    Integrator "path"
+                    WorldBegin
+                    LightSource "point" "point from" [ 0 2 5 ] "rgb I" [ 10 10 10 ]
+                    Shape "sphere" "float radius" [ 1 ]
+                    Shape "sphere" "float radius" [ 20 ]
+                    WorldEnd
+                    "#,
+                )
+                .expect("render")
+                .rgb
+            })
+        };
+        let single = render(1);
+        assert!(single.iter().any(|&c| c > 0.));
+        assert_eq!(single, render(4));
+    }
+
+    #[test]
+    fn test_render_string() {
+        let scene = br#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [ 30 ]
+            Film "image" "integer xresolution" [ 16 ] "integer yresolution" [ 8 ]
+                "float cropwindow" [ 0 0.5 0 1 ]
+            Sampler "stratified" "integer xsamples" [ 1 ] "integer ysamples" [ 1 ]
+                "bool jitter" "false"
+            Integrator "directlighting"
+            WorldBegin
+            LightSource "point" "point from" [ 0 0 5 ] "rgb I" [ 10 10 10 ]
+            Shape "sphere" "float radius" [ 1 ]
+            WorldEnd
+            "#;
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        let img = pbrt.render_string(scene).expect("render");
+        assert_eq!(img.resolution, Point2i::from([16, 8]));
+        assert_eq!(img.crop_bounds, Bounds2i::from([[0, 0], [8, 8]]));
+        assert_eq!(img.rgb.len(), 3 * 8 * 8);
+        // The sphere covers the right edge of the left half of the image, the corner is empty.
+        let pixel = |x: usize, y: usize| &img.rgb[3 * (y * 8 + x)..3 * (y * 8 + x + 1)];
+        assert!(pixel(7, 4).iter().all(|&c| c > 0.), "{:?}", pixel(7, 4));
+        assert_eq!(pixel(0, 0), &[0., 0., 0.]);
+
+        // Scenes that don't render report why.
+        let mut pbrt: PbrtAPI = Default::default();
+        pbrt.init();
+        assert!(pbrt.render_string(br#"Film "image""#).is_err());
+    }
 }
@@ -13,7 +13,10 @@
 // limitations under the License.
 
 //! Implementation of [API] for testing.
-use std::path::Path;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
 
 use crate::{
     core::{
@@ -24,151 +27,484 @@ use crate::{
     Degree, Float,
 };
 
-/// MockAPI is an empty implementation of [API] used for testing the parser.
+/// Call records a single invocation of an [API] method on [MockAPI], along with its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    /// [API::accelerator]
+    Accelerator(String, ParamSet),
+    /// [API::active_transform_all]
+    ActiveTransformAll,
+    /// [API::active_transform_end_time]
+    ActiveTransformEndTime,
+    /// [API::active_transform_start_time]
+    ActiveTransformStartTime,
+    /// [API::area_light_source]
+    AreaLightSource(String, ParamSet),
+    /// [API::attribute_begin]
+    AttributeBegin,
+    /// [API::attribute_end]
+    AttributeEnd,
+    /// [API::camera]
+    Camera(String, ParamSet),
+    /// [API::cleanup]
+    Cleanup,
+    /// [API::concat_transform]
+    ConcatTransform([Float; 16]),
+    /// [API::coordinate_system]
+    CoordinateSystem(String),
+    /// [API::coordinate_system_transform]
+    CoordinateSystemTransform(String),
+    /// [API::film]
+    Film(String, ParamSet),
+    /// [API::identity]
+    Identity,
+    /// [API::init]
+    Init,
+    /// [API::integrator]
+    Integrator(String, ParamSet),
+    /// [API::light_source]
+    LightSource(String, ParamSet),
+    /// [API::look_at], the eye, look and up vectors.
+    LookAt([Float; 3], [Float; 3], [Float; 3]),
+    /// [API::make_named_material]
+    MakeNamedMaterial(String, ParamSet),
+    /// [API::make_named_medium]
+    MakeNamedMedium(String, ParamSet),
+    /// [API::material]
+    Material(String, ParamSet),
+    /// [API::medium_interface], the inside and outside medium names.
+    MediumInterface(String, String),
+    /// [API::named_material]
+    NamedMaterial(String),
+    /// [API::object_begin]
+    ObjectBegin(String),
+    /// [API::object_end]
+    ObjectEnd,
+    /// [API::object_instance]
+    ObjectInstance(String),
+    /// [API::parse_file]
+    ParseFile(PathBuf),
+    /// [API::parse_string]
+    ParseString(Vec<u8>),
+    /// [API::pixel_filter]
+    PixelFilter(String, ParamSet),
+    /// [API::reverse_orientation]
+    ReverseOrientation,
+    /// [API::rotate], the angle in degrees and the axis.
+    Rotate(Float, [Float; 3]),
+    /// [API::sampler]
+    Sampler(String, ParamSet),
+    /// [API::scale]
+    Scale([Float; 3]),
+    /// [API::shape]
+    Shape(String, ParamSet),
+    /// [API::texture], the texture's name, kind, texture type and parameters.
+    Texture(String, String, String, ParamSet),
+    /// [API::transform_begin]
+    TransformBegin,
+    /// [API::transform_end]
+    TransformEnd,
+    /// [API::transform]
+    Transform([Float; 16]),
+    /// [API::transform_times]
+    TransformTimes(Float, Float),
+    /// [API::translate]
+    Translate([Float; 3]),
+    /// [API::world_begin]
+    WorldBegin,
+    /// [API::world_end]
+    WorldEnd,
+}
+
+impl Call {
+    /// Returns the name of the [API] method that was called, e.g. "sampler".
+    pub fn method(&self) -> &'static str {
+        match self {
+            Call::Accelerator(..) => "accelerator",
+            Call::ActiveTransformAll => "active_transform_all",
+            Call::ActiveTransformEndTime => "active_transform_end_time",
+            Call::ActiveTransformStartTime => "active_transform_start_time",
+            Call::AreaLightSource(..) => "area_light_source",
+            Call::AttributeBegin => "attribute_begin",
+            Call::AttributeEnd => "attribute_end",
+            Call::Camera(..) => "camera",
+            Call::Cleanup => "cleanup",
+            Call::ConcatTransform(..) => "concat_transform",
+            Call::CoordinateSystem(..) => "coordinate_system",
+            Call::CoordinateSystemTransform(..) => "coordinate_system_transform",
+            Call::Film(..) => "film",
+            Call::Identity => "identity",
+            Call::Init => "init",
+            Call::Integrator(..) => "integrator",
+            Call::LightSource(..) => "light_source",
+            Call::LookAt(..) => "look_at",
+            Call::MakeNamedMaterial(..) => "make_named_material",
+            Call::MakeNamedMedium(..) => "make_named_medium",
+            Call::Material(..) => "material",
+            Call::MediumInterface(..) => "medium_interface",
+            Call::NamedMaterial(..) => "named_material",
+            Call::ObjectBegin(..) => "object_begin",
+            Call::ObjectEnd => "object_end",
+            Call::ObjectInstance(..) => "object_instance",
+            Call::ParseFile(..) => "parse_file",
+            Call::ParseString(..) => "parse_string",
+            Call::PixelFilter(..) => "pixel_filter",
+            Call::ReverseOrientation => "reverse_orientation",
+            Call::Rotate(..) => "rotate",
+            Call::Sampler(..) => "sampler",
+            Call::Scale(..) => "scale",
+            Call::Shape(..) => "shape",
+            Call::Texture(..) => "texture",
+            Call::TransformBegin => "transform_begin",
+            Call::TransformEnd => "transform_end",
+            Call::Transform(..) => "transform",
+            Call::TransformTimes(..) => "transform_times",
+            Call::Translate(..) => "translate",
+            Call::WorldBegin => "world_begin",
+            Call::WorldEnd => "world_end",
+        }
+    }
+
+    /// Returns the first name argument of the call, if the method takes one.  For example the
+    /// implementation name for `sampler` or the coordinate system name for `coordinate_system`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Call::Accelerator(name, _)
+            | Call::AreaLightSource(name, _)
+            | Call::Camera(name, _)
+            | Call::CoordinateSystem(name)
+            | Call::CoordinateSystemTransform(name)
+            | Call::Film(name, _)
+            | Call::Integrator(name, _)
+            | Call::LightSource(name, _)
+            | Call::MakeNamedMaterial(name, _)
+            | Call::MakeNamedMedium(name, _)
+            | Call::Material(name, _)
+            | Call::MediumInterface(name, _)
+            | Call::NamedMaterial(name)
+            | Call::ObjectBegin(name)
+            | Call::ObjectInstance(name)
+            | Call::PixelFilter(name, _)
+            | Call::Sampler(name, _)
+            | Call::Shape(name, _)
+            | Call::Texture(name, ..) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the `ParamSet` passed to the call, if the method takes one.
+    pub fn params(&self) -> Option<&ParamSet> {
+        match self {
+            Call::Accelerator(_, params)
+            | Call::AreaLightSource(_, params)
+            | Call::Camera(_, params)
+            | Call::Film(_, params)
+            | Call::Integrator(_, params)
+            | Call::LightSource(_, params)
+            | Call::MakeNamedMaterial(_, params)
+            | Call::MakeNamedMedium(_, params)
+            | Call::Material(_, params)
+            | Call::PixelFilter(_, params)
+            | Call::Sampler(_, params)
+            | Call::Shape(_, params)
+            | Call::Texture(.., params) => Some(params),
+            _ => None,
+        }
+    }
+}
+
+/// MockAPI is an implementation of [API] used for testing the parser.  Every method call is
+/// recorded in `calls`, in order, so tests can assert what the parser delivered.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{
+///     api_test::{Call, MockAPI},
+///     parser::{create_from_string, parse},
+/// };
+///
+/// let mut api = MockAPI::default();
+/// parse(
+///     create_from_string(br#"Sampler "halton" "integer pixelsamples" 16 WorldBegin"#),
+///     &mut api,
+/// )
+/// .expect("parse");
+/// let sampler = api.assert_called_once_with_name("sampler", "halton");
+/// assert_eq!(
+///     sampler.params().expect("params").find_one_int("pixelsamples", 0),
+///     16
+/// );
+/// assert_eq!(api.calls.last(), Some(&Call::WorldBegin));
+/// ```
 #[derive(Default)]
-pub struct MockAPI {}
+pub struct MockAPI {
+    /// Every call made on this `MockAPI`, oldest first.
+    pub calls: Vec<Call>,
+    parse_results: VecDeque<Result<(), Error>>,
+}
+
+impl MockAPI {
+    /// Queues `result` to be returned by the next call to `parse_file` or `parse_string`.  Results
+    /// are returned in the order they're pushed, once they run out `Err(parser::Error::EOF)` is
+    /// returned.
+    pub fn push_parse_result(&mut self, result: Result<(), Error>) {
+        self.parse_results.push_back(result);
+    }
+
+    /// Returns the recorded calls to the [API] method named `method`.
+    pub fn calls_to(&self, method: &str) -> Vec<&Call> {
+        self.calls.iter().filter(|c| c.method() == method).collect()
+    }
+
+    /// Asserts `method` was called exactly once, with `name` as its name argument, and returns
+    /// the recorded call.
+    pub fn assert_called_once_with_name(&self, method: &str, name: &str) -> &Call {
+        let calls = self.calls_to(method);
+        assert_eq!(
+            calls.len(),
+            1,
+            "expected one call to {}, got {:?}",
+            method,
+            calls
+        );
+        assert_eq!(calls[0].name(), Some(name), "wrong name for {}", method);
+        calls[0]
+    }
+
+    /// Asserts `method` was never called.
+    pub fn assert_not_called(&self, method: &str) {
+        let calls = self.calls_to(method);
+        assert!(
+            calls.is_empty(),
+            "expected no calls to {}, got {:?}",
+            method,
+            calls
+        );
+    }
+
+    fn next_parse_result(&mut self) -> Result<(), Error> {
+        self.parse_results
+            .pop_front()
+            .unwrap_or_else(|| Err(ParserError::EOF.into()))
+    }
+}
+
 impl API for MockAPI {
     /// Sets the renderer's accelerator settings to `name` & `params`.
-    fn accelerator(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn accelerator(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Accelerator(name.to_string(), params));
     }
     /// Sets the active transform bits to `ALL_TRANSFORMS_BITS`.
     fn active_transform_all(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::ActiveTransformAll);
     }
     /// Sets the active transform bits to `END_TRANSFORMS_BITS`.
     fn active_transform_end_time(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::ActiveTransformEndTime);
     }
     /// Sets the active transform bits to `START_TRANSFORMS_BITS`.
     fn active_transform_start_time(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::ActiveTransformStartTime);
     }
     /// Creates area light when `AreaLightSource` found in scene.
-    fn area_light_source(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn area_light_source(&mut self, name: &str, params: ParamSet) {
+        self.calls
+            .push(Call::AreaLightSource(name.to_string(), params));
     }
     /// Called when parser sees a `AttributeBegin` keyword
     fn attribute_begin(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::AttributeBegin);
     }
     /// Called when parser sees a `AttributeEnd` keyword
     fn attribute_end(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::AttributeEnd);
     }
     /// Sets the renderer's camera settings to `name` & `params`.
-    fn camera(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn camera(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Camera(name.to_string(), params));
     }
     /// Reset the internal state of self.
     fn cleanup(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::Cleanup);
     }
     /// Multiples the current transform matrix by `transform`.
-    fn concat_transform(&mut self, _transform: [Float; 16]) {
-        // unimplemented!()
+    fn concat_transform(&mut self, transform: [Float; 16]) {
+        self.calls.push(Call::ConcatTransform(transform));
     }
     /// Creates a new coordinate system assigning `name` the current transform matrix.
-    fn coordinate_system(&mut self, _name: &str) {
-        // unimplemented!()
+    fn coordinate_system(&mut self, name: &str) {
+        self.calls.push(Call::CoordinateSystem(name.to_string()));
     }
     /// Sets the current transform matrix to the one stored under `name`.
-    fn coordinate_system_transform(&mut self, _name: &str) {
-        // unimplemented!()
+    fn coordinate_system_transform(&mut self, name: &str) {
+        self.calls
+            .push(Call::CoordinateSystemTransform(name.to_string()));
     }
     /// Sets the renderer's film settings to `name` & `params`.
-    fn film(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn film(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Film(name.to_string(), params));
     }
     /// Sets the currently active transform matrix by the given values.
     fn identity(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::Identity);
     }
     /// Moves the internal statemachine from `APIState::Uninitialized` to `APIState::OptionsBlock`.
     /// This function must be called before most of the API will work.
     fn init(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::Init);
     }
     /// Sets the renderer's integrator settings to `name` & `params`.
-    fn integrator(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn integrator(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Integrator(name.to_string(), params));
     }
     /// Creates light when `LightSource` found in scene.
-    fn light_source(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn light_source(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::LightSource(name.to_string(), params));
     }
     /// Sets the current transforms to look at the given directions.
-    fn look_at(&mut self, _eye: [Float; 3], _look: [Float; 3], _up: [Float; 3]) {
-        // unimplemented!()
+    fn look_at(&mut self, eye: [Float; 3], look: [Float; 3], up: [Float; 3]) {
+        self.calls.push(Call::LookAt(eye, look, up));
+    }
+    /// Stores the material described by `params` under `name`.
+    fn make_named_material(&mut self, name: &str, params: ParamSet) {
+        self.calls
+            .push(Call::MakeNamedMaterial(name.to_string(), params));
     }
     /// Creates a medium with the given `params` and stores it as a named media under `name`.
-    fn make_named_medium(&mut self, _name: &str, _params: &mut ParamSet) {
-        // unimplemented!()
+    fn make_named_medium(&mut self, name: &str, params: &mut ParamSet) {
+        self.calls
+            .push(Call::MakeNamedMedium(name.to_string(), params.clone()));
+    }
+    /// Sets the current material to `name` described by `params`.
+    fn material(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Material(name.to_string(), params));
     }
     /// Specifies the current inside and outside media by the names given.  Cameras and lights
     /// without geometry ignore the `inside_name`.
-    fn medium_interface(&mut self, _inside_name: &str, _outside_name: &str) {
-        // unimplemented!()
-    }
-    /// Parse a scene file at `path` on the file-system.  This will parse the contents of the file
-    /// generating an inmemory representation of the scene, and trigger the rendering and output of
-    /// the image.
-    fn parse_file<P: AsRef<Path>>(&mut self, _path: P) -> Result<(), Error> {
-        Err(ParserError::EOF.into())
-    }
-    /// Parse a scene file represented as text stored in `data`.  This will parse the contents of
-    /// data generating an inmemory representation of the scene, and trigger the rendering and
-    /// output of
-    /// the image.
-    fn parse_string(&mut self, _data: &[u8]) -> Result<(), Error> {
-        Err(ParserError::EOF.into())
+    fn medium_interface(&mut self, inside_name: &str, outside_name: &str) {
+        self.calls.push(Call::MediumInterface(
+            inside_name.to_string(),
+            outside_name.to_string(),
+        ));
+    }
+    /// Sets the current material to the one stored under `name`.
+    fn named_material(&mut self, name: &str) {
+        self.calls.push(Call::NamedMaterial(name.to_string()));
+    }
+    /// Starts the definition of the named object instance.
+    fn object_begin(&mut self, name: &str) {
+        self.calls.push(Call::ObjectBegin(name.to_string()));
+    }
+    /// Ends the current object instance definition.
+    fn object_end(&mut self) {
+        self.calls.push(Call::ObjectEnd);
+    }
+    /// Adds an instance of the named object to the scene.
+    fn object_instance(&mut self, name: &str) {
+        self.calls.push(Call::ObjectInstance(name.to_string()));
+    }
+    /// Records the call and returns the next result queued with [push_parse_result].
+    ///
+    /// [push_parse_result]: MockAPI::push_parse_result
+    fn parse_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.calls
+            .push(Call::ParseFile(path.as_ref().to_path_buf()));
+        self.next_parse_result()
+    }
+    /// Records the call and returns the next result queued with [push_parse_result].
+    ///
+    /// [push_parse_result]: MockAPI::push_parse_result
+    fn parse_string(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.calls.push(Call::ParseString(data.to_vec()));
+        self.next_parse_result()
     }
     /// Sets the renderer's filter settings to `name` & `params`.
-    fn pixel_filter(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn pixel_filter(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::PixelFilter(name.to_string(), params));
+    }
+    /// Flips the orientation of the surface normals of subsequent shapes.
+    fn reverse_orientation(&mut self) {
+        self.calls.push(Call::ReverseOrientation);
     }
     /// Rotates the currently active transform matrix by the given values.
-    fn rotate(&mut self, _angle: Degree, _ax: Float, _ay: Float, _az: Float) {
-        // unimplemented!()
+    fn rotate(&mut self, angle: Degree, ax: Float, ay: Float, az: Float) {
+        self.calls.push(Call::Rotate(angle.0, [ax, ay, az]));
     }
     /// Sets the renderer's sampler settings to `name` & `params`.
-    fn sampler(&mut self, _name: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn sampler(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Sampler(name.to_string(), params));
     }
     /// Scales the currently active transform matrix by the given values.
-    fn scale(&mut self, _sx: Float, _sy: Float, _sz: Float) {
-        // unimplemented!()
+    fn scale(&mut self, sx: Float, sy: Float, sz: Float) {
+        self.calls.push(Call::Scale([sx, sy, sz]));
+    }
+    /// Creates the shape `name` described by `params` when `Shape` found in scene.
+    fn shape(&mut self, name: &str, params: ParamSet) {
+        self.calls.push(Call::Shape(name.to_string(), params));
     }
     /// Called when the parser sees a `Texture` line.
-    fn texture(&mut self, _name: &str, _kind: &str, _texname: &str, _params: ParamSet) {
-        // unimplemented!()
+    fn texture(&mut self, name: &str, kind: &str, texname: &str, params: ParamSet) {
+        self.calls.push(Call::Texture(
+            name.to_string(),
+            kind.to_string(),
+            texname.to_string(),
+            params,
+        ));
     }
     /// Called when parser sees a `TransformBegin` keyword
     fn transform_begin(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::TransformBegin);
     }
     /// Called when parser sees a `TransformEnd` keyword
     fn transform_end(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::TransformEnd);
     }
     /// Sets the current transform matrix to `transform`.
-    fn transform(&mut self, _transform: [Float; 16]) {
-        // unimplemented!()
+    fn transform(&mut self, transform: [Float; 16]) {
+        self.calls.push(Call::Transform(transform));
     }
     /// Sets the start/end times for the transform matrix to `start` & `end`.
-    fn transform_times(&mut self, _start: Float, _end: Float) {
-        // unimplemented!()
+    fn transform_times(&mut self, start: Float, end: Float) {
+        self.calls.push(Call::TransformTimes(start, end));
     }
     /// Translates the currently active transform matrix by the given values.
-    fn translate(&mut self, _dx: Float, _dy: Float, _dz: Float) {
-        // unimplemented!()
+    fn translate(&mut self, dx: Float, dy: Float, dz: Float) {
+        self.calls.push(Call::Translate([dx, dy, dz]));
     }
     /// Called when parser sees a `WorldBegin` keyword
     fn world_begin(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::WorldBegin);
     }
     /// Called when parser sees a `WorldEnd` keyword
     fn world_end(&mut self) {
-        // unimplemented!()
+        self.calls.push(Call::WorldEnd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Call, MockAPI};
+    use crate::core::{
+        api::{Error, API},
+        parser::Error as ParserError,
+    };
+
+    #[test]
+    fn scripted_parse_results() {
+        let mut api = MockAPI::default();
+        api.push_parse_result(Ok(()));
+        assert!(api.parse_file("scene.pbrt").is_ok());
+        assert!(matches!(
+            api.parse_string(b"WorldBegin"),
+            Err(Error::Parser(ParserError::EOF))
+        ));
+        assert_eq!(
+            api.calls,
+            vec![
+                Call::ParseFile("scene.pbrt".into()),
+                Call::ParseString(b"WorldBegin".to_vec()),
+            ]
+        );
+        api.assert_not_called("world_begin");
     }
 }
@@ -0,0 +1,338 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Traits and helper types shared by the various camera models.
+
+use std::{fmt::Debug, mem::swap, sync::Arc};
+
+use log::{error, warn};
+
+use crate::{
+    core::{
+        film::Film,
+        geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential},
+        medium::Medium,
+        paramset::ParamSet,
+        transform::{AnimatedTransform, Transform},
+    },
+    lerp, Float,
+};
+
+/// CameraSample holds all the sample values needed to generate a camera ray.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CameraSample {
+    /// Point on the film, in raster space, that the generated ray carries radiance to.
+    pub p_film: Point2f,
+    /// Point on the lens the ray passes through, in [0, 1)^2.
+    pub p_lens: Point2f,
+    /// Time the ray is sampled at, in [0, 1).  Cameras map it to their shutter interval.
+    pub time: Float,
+}
+
+/// Camera is the interface all camera models implement to turn film samples into rays.
+pub trait Camera: Debug + Send + Sync {
+    /// Computes the world space ray corresponding to `sample`.  The returned `Float` is a weight
+    /// for how much the radiance arriving along the ray contributes to the film, zero means the
+    /// ray should be ignored.
+    fn generate_ray(&self, sample: &CameraSample) -> (Float, Ray);
+
+    /// Like [generate_ray], but also computes the rays for samples shifted one pixel in x and y on
+    /// the film.  The default implementation calls [generate_ray] three times.
+    ///
+    /// [generate_ray]: Camera::generate_ray
+    fn generate_ray_differential(&self, sample: &CameraSample) -> (Float, RayDifferential) {
+        let (wt, ray) = self.generate_ray(sample);
+        if wt == 0. {
+            return (wt, ray.into());
+        }
+
+        // Find camera ray after shifting one pixel in the x direction
+        let mut wtx = 0.;
+        let mut rx = None;
+        for eps in &[0.05, -0.05] {
+            let mut sshift = *sample;
+            sshift.p_film.x += eps;
+            let (w, r) = self.generate_ray(&sshift);
+            if w != 0. {
+                wtx = w;
+                rx = Some((ray.o + (r.o - ray.o) / *eps, ray.d + (r.d - ray.d) / *eps));
+                break;
+            }
+        }
+
+        // Find camera ray after shifting one pixel in the y direction
+        let mut wty = 0.;
+        let mut ry = None;
+        for eps in &[0.05, -0.05] {
+            let mut sshift = *sample;
+            sshift.p_film.y += eps;
+            let (w, r) = self.generate_ray(&sshift);
+            if w != 0. {
+                wty = w;
+                ry = Some((ray.o + (r.o - ray.o) / *eps, ray.d + (r.d - ray.d) / *eps));
+                break;
+            }
+        }
+
+        let mut rd = RayDifferential::from(ray);
+        if let (Some((rx_origin, rx_direction)), Some((ry_origin, ry_direction))) = (rx, ry) {
+            if wtx != 0. && wty != 0. {
+                rd.rx_origin = rx_origin;
+                rd.rx_direction = rx_direction;
+                rd.ry_origin = ry_origin;
+                rd.ry_direction = ry_direction;
+                rd.has_differentials = true;
+            }
+        }
+        (wt, rd)
+    }
+
+    /// Returns the `Film` this camera records to.
+    fn film(&self) -> Arc<Film>;
+
+    /// Returns the time the camera's shutter opens.
+    fn shutter_open(&self) -> Float;
+
+    /// Returns the time the camera's shutter closes.
+    fn shutter_close(&self) -> Float;
+}
+
+/// CameraData holds the state common to all camera implementations.
+#[derive(Debug, Clone)]
+pub struct CameraData {
+    /// Places the camera in the scene, possibly moving over time.
+    pub camera_to_world: AnimatedTransform,
+    /// Time the shutter opens.
+    pub shutter_open: Float,
+    /// Time the shutter closes.
+    pub shutter_close: Float,
+    /// The film the camera records to.
+    pub film: Arc<Film>,
+    /// The medium the camera is inside of, if any.
+    pub medium: Option<Arc<dyn Medium>>,
+}
+
+impl CameraData {
+    /// Create `CameraData` with the given parameters.
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        shutter_open: Float,
+        shutter_close: Float,
+        film: Arc<Film>,
+        medium: Option<Arc<dyn Medium>>,
+    ) -> CameraData {
+        if camera_to_world.interpolate(shutter_open).has_scale()
+            || camera_to_world.interpolate(shutter_close).has_scale()
+        {
+            warn!(
+                "Scaling detected in world-to-camera transformation!\n\
+                 The system has numerous assumptions, implicit and explicit,\n\
+                 that this transform will have no scale factors in it.\n\
+                 Proceed at your own risk; your image may have errors or\n\
+                 the system may crash as a result of this."
+            );
+        }
+        CameraData {
+            camera_to_world,
+            shutter_open,
+            shutter_close,
+            film,
+            medium,
+        }
+    }
+
+    /// Maps `time`, in [0, 1), into the shutter interval.
+    pub fn time(&self, time: Float) -> Float {
+        lerp(time, self.shutter_open, self.shutter_close)
+    }
+}
+
+/// ProjectiveCameraData holds the transforms shared by cameras that can be modeled with a 4x4
+/// projection matrix.
+#[derive(Debug, Clone)]
+pub struct ProjectiveCameraData {
+    /// Data shared by all cameras.
+    pub camera: CameraData,
+    /// Projection from camera space to screen space.
+    pub camera_to_screen: Transform,
+    /// Maps raster space points, i.e. pixel coordinates on the film, to camera space.
+    pub raster_to_camera: Transform,
+    /// Maps screen space points to raster space.
+    pub screen_to_raster: Transform,
+    /// Maps raster space points to screen space.
+    pub raster_to_screen: Transform,
+    /// Radius of the camera's lens, zero for a pinhole.
+    pub lens_radius: Float,
+    /// Distance to the plane of focus.
+    pub focal_distance: Float,
+}
+
+impl ProjectiveCameraData {
+    /// Create `ProjectiveCameraData` from the camera's projection `camera_to_screen` and the
+    /// extent of the image in screen space given by `screen_window`.
+    pub fn new(
+        camera: CameraData,
+        camera_to_screen: Transform,
+        screen_window: Bounds2f,
+        lens_radius: Float,
+        focal_distance: Float,
+    ) -> ProjectiveCameraData {
+        // Compute projective camera screen transformations
+        let res = camera.film.full_resolution;
+        let screen_to_raster = Transform::scale(res.x as Float, res.y as Float, 1.)
+            * Transform::scale(
+                1. / (screen_window.p_max.x - screen_window.p_min.x),
+                1. / (screen_window.p_min.y - screen_window.p_max.y),
+                1.,
+            )
+            * Transform::translate([-screen_window.p_min.x, -screen_window.p_max.y, 0.]);
+        let raster_to_screen = screen_to_raster.inverse();
+        let raster_to_camera = camera_to_screen.inverse() * raster_to_screen;
+        ProjectiveCameraData {
+            camera,
+            camera_to_screen,
+            raster_to_camera,
+            screen_to_raster,
+            raster_to_screen,
+            lens_radius,
+            focal_distance,
+        }
+    }
+
+    /// Convenience to map a point on the film, in raster space, to camera space.
+    pub fn raster_to_camera(&self, p_film: Point2f) -> Point3f {
+        self.raster_to_camera
+            .transform_point(Point3f::from([p_film.x, p_film.y, 0.]))
+    }
+}
+
+/// Reads the shutter interval from `params`, swapping the values if they're reversed.
+pub(crate) fn shutter_times(params: &ParamSet) -> (Float, Float) {
+    let mut shutter_open = params.find_one_float("shutteropen", 0.);
+    let mut shutter_close = params.find_one_float("shutterclose", 1.);
+    if shutter_close < shutter_open {
+        warn!(
+            "Shutter close time [{}] < shutter open [{}].  Swapping them.",
+            shutter_close, shutter_open
+        );
+        swap(&mut shutter_open, &mut shutter_close);
+    }
+    (shutter_open, shutter_close)
+}
+
+/// Computes the extent of the image in screen space, the shorter axis spans [-1, 1] unless
+/// `params` has a "screenwindow" value.
+pub(crate) fn screen_window(params: &ParamSet, film: &Film) -> Bounds2f {
+    let frame = params.find_one_float(
+        "frameaspectratio",
+        film.full_resolution.x as Float / film.full_resolution.y as Float,
+    );
+    let mut screen = if frame > 1. {
+        Bounds2f::from([[-frame, -1.], [frame, 1.]])
+    } else {
+        Bounds2f::from([[-1., -1. / frame], [1., 1. / frame]])
+    };
+    if let Some(sw) = params.find_float("screenwindow") {
+        if sw.len() == 4 {
+            screen = Bounds2f::from([[sw[0], sw[2]], [sw[1], sw[3]]]);
+        } else {
+            error!("\"screenwindow\" should have four values");
+        }
+    }
+    screen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::geometry::Vector3f, filters::r#box::BoxFilter};
+
+    /// Shoots rays straight down +z from the film position, or no ray at all for film positions
+    /// with x past `edge`.
+    #[derive(Debug)]
+    struct ParallelCamera {
+        film: Arc<Film>,
+        edge: Float,
+    }
+
+    impl Camera for ParallelCamera {
+        fn generate_ray(&self, sample: &CameraSample) -> (Float, Ray) {
+            let p = sample.p_film;
+            let ray = Ray::new(
+                Point3f::from([p.x, 2. * p.y, 0.]),
+                Vector3f::from([0., 0., 1.]),
+                Float::INFINITY,
+                0.,
+                None,
+            );
+            (if p.x > self.edge { 0. } else { 1. }, ray)
+        }
+
+        fn film(&self) -> Arc<Film> {
+            Arc::clone(&self.film)
+        }
+
+        fn shutter_open(&self) -> Float {
+            0.
+        }
+
+        fn shutter_close(&self) -> Float {
+            1.
+        }
+    }
+
+    fn camera(edge: Float) -> ParallelCamera {
+        ParallelCamera {
+            film: Arc::new(Film::new(
+                [4, 4].into(),
+                [[0., 0.], [1., 1.]].into(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                35.,
+                "".to_string(),
+                1.,
+                Float::INFINITY,
+            )),
+            edge,
+        }
+    }
+
+    fn assert_close(a: Point3f, b: Point3f) {
+        assert!((a - b).length() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn default_ray_differential() {
+        let sample = CameraSample {
+            p_film: [1.5, 2.5].into(),
+            ..Default::default()
+        };
+        let (wt, rd) = camera(Float::INFINITY).generate_ray_differential(&sample);
+        assert_eq!(wt, 1.);
+        assert!(rd.has_differentials);
+        assert_close(rd.rx_origin, [2.5, 5., 0.].into());
+        assert_close(rd.ry_origin, [1.5, 7., 0.].into());
+        assert_eq!(rd.rx_direction, rd.ray.d);
+
+        // Shifting right leaves the film, so the differential is found by shifting left.
+        let (wt, rd) = camera(1.52).generate_ray_differential(&sample);
+        assert_eq!(wt, 1.);
+        assert!(rd.has_differentials);
+        assert_close(rd.rx_origin, [2.5, 5., 0.].into());
+
+        // No ray, no differentials.
+        let (wt, rd) = camera(1.).generate_ray_differential(&sample);
+        assert_eq!(wt, 0.);
+        assert!(!rd.has_differentials);
+    }
+}
@@ -58,10 +58,7 @@ pub fn directory_containing<P: AsRef<Path>>(filename: P) -> PathBuf {
 
 /// Set global search directory used by functions in this module to `dirname`.
 pub fn set_search_directory<P: AsRef<Path>>(dirname: Option<P>) {
-    *SEARCH_DIRECTORY.lock().unwrap() = match dirname {
-        Some(d) => Some(d.as_ref().to_path_buf()),
-        None => None,
-    };
+    *SEARCH_DIRECTORY.lock().unwrap() = dirname.map(|d| d.as_ref().to_path_buf());
 }
 
 /// Performs case insensitive comparison of `ext` to end of `path`.
@@ -76,8 +73,7 @@ pub fn set_search_directory<P: AsRef<Path>>(dirname: Option<P>) {
 /// assert!(!has_extension("/path/to/file.ext", "html"));
 pub fn has_extension<P: AsRef<Path>>(path: P, ext: P) -> bool {
     if let Some(p_ext) = path.as_ref().extension() {
-        return p_ext.to_ascii_lowercase()
-            == AsRef::<OsStr>::as_ref(ext.as_ref()).to_ascii_lowercase();
+        return p_ext.eq_ignore_ascii_case(AsRef::<OsStr>::as_ref(ext.as_ref()));
     }
     false
 }
@@ -16,29 +16,164 @@
 
 use std::{
     convert::TryInto,
-    sync::{Arc, Mutex},
+    fmt,
+    str::FromStr,
+    sync::{Mutex, MutexGuard},
 };
 
-use log::info;
+use log::{error, info, warn};
+use thiserror::Error;
 
 use crate::{
+    clamp,
     core::{
         filter::Filter,
         geometry::{Bounds2f, Bounds2i, Point2f, Point2i, Vector2f},
-        imageio::write_image,
+        imageio::{is_8_bit, write_image},
+        parallel::parallel_for_2d,
+        paramset::ParamSet,
         spectrum::{xyz_to_rgb, Spectrum},
     },
-    Float,
+    Float, Options,
 };
 
 const FILTER_TABLE_WIDTH: usize = 16;
 
+/// Error type for films that would cover no pixels.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The crop window, as given by the user, selects none of the film's pixels.
+    #[error("crop window {crop_window} covers no pixels of the {resolution} film")]
+    EmptyCropWindow {
+        /// The crop window as given by the user, before it was clamped to [0, 1].
+        crop_window: Bounds2f,
+        /// Resolution of the full film.
+        resolution: Point2i,
+    },
+    /// The pixel bounds don't overlap the pixels selected by the crop window.
+    #[error("pixel bounds {pixel_bounds} don't overlap the film's cropped pixel bounds {cropped_pixel_bounds}")]
+    EmptyPixelBounds {
+        /// The requested pixel bounds.
+        pixel_bounds: Bounds2i,
+        /// The bounds selected by the crop window.
+        cropped_pixel_bounds: Bounds2i,
+    },
+}
+
+/// Returns true if `b` contains no pixels.
+fn is_empty(b: &Bounds2i) -> bool {
+    b.p_min.x >= b.p_max.x || b.p_min.y >= b.p_max.y
+}
+
+/// Returns `crop` with its components clamped to [0, 1] and min/max swapped where inverted, so the
+/// film always covers a valid region.  A warning is logged if anything had to change.
+fn sanitize_crop_window(crop: Bounds2f) -> Bounds2f {
+    let c = |v: Float| clamp(v, 0., 1.);
+    let (x0, x1) = (c(crop.p_min.x), c(crop.p_max.x));
+    let (y0, y1) = (c(crop.p_min.y), c(crop.p_max.y));
+    let fixed = Bounds2f {
+        p_min: Point2f::from((x0.min(x1), y0.min(y1))),
+        p_max: Point2f::from((x0.max(x1), y0.max(y1))),
+    };
+    if fixed != crop {
+        warn!("Invalid crop window {}, using {}", crop, fixed);
+    }
+    fixed
+}
+
+/// Callback registered with [set_update_callback], called with the RGB values of a region of the
+/// image and the bounds of that region.
+///
+/// [set_update_callback]: Film::set_update_callback
+pub type UpdateCallback = Box<dyn Fn(&[Float], Bounds2i) + Send + Sync>;
+
+/// Order in which [iter_tiles_ordered] visits the tiles covering a `Film`'s sample bounds.
+///
+/// [iter_tiles_ordered]: Film::iter_tiles_ordered
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Left to right, then top to bottom.
+    #[default]
+    Scanline,
+    /// Along a Z-order curve, which keeps consecutive tiles close to each other on the film.
+    Morton,
+}
+
+/// Operator mapping the linear, unbounded RGB values of a `Film` to the [0, 1] range of 8-bit
+/// image formats.  Floating point formats store the linear values and aren't tone mapped.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::film::ToneMapper;
+///
+/// assert_eq!(ToneMapper::Clamp.map(4.), 1.);
+/// assert_eq!(ToneMapper::Reinhard.map(4.), 0.8);
+/// assert_eq!(ToneMapper::ExposureGamma { exposure: -2. }.map(2.), 0.5);
+/// assert_eq!("exposure=-2".parse(), Ok(ToneMapper::ExposureGamma { exposure: -2. }));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ToneMapper {
+    /// Values above one saturate to white.
+    #[default]
+    Clamp,
+    /// Maps `v` to `v / (1 + v)`, compressing highlights while keeping dark values almost linear.
+    Reinhard,
+    /// Scales values by 2^`exposure`, i.e. `exposure` is in stops, before clamping.  The sRGB
+    /// gamma curve is applied afterwards like for every other operator.
+    ExposureGamma {
+        /// Exposure adjustment in stops.
+        exposure: Float,
+    },
+}
+
+impl ToneMapper {
+    /// Returns the tone mapped value of the linear color component `v`.
+    pub fn map(&self, v: Float) -> Float {
+        match self {
+            ToneMapper::Clamp => clamp(v, 0., 1.),
+            ToneMapper::Reinhard => {
+                let v = v.max(0.);
+                v / (1. + v)
+            }
+            ToneMapper::ExposureGamma { exposure } => clamp(v * exposure.exp2(), 0., 1.),
+        }
+    }
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    /// Parses "clamp", "reinhard" or "exposure=<stops>".
+    fn from_str(s: &str) -> Result<ToneMapper, String> {
+        match s {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            _ => match s.strip_prefix("exposure=") {
+                Some(exposure) => exposure
+                    .parse()
+                    .map(|exposure| ToneMapper::ExposureGamma { exposure })
+                    .map_err(|e| format!("invalid exposure '{}': {}", exposure, e)),
+                None => Err(format!(
+                    "unknown tone mapper '{}', expected 'clamp', 'reinhard' or 'exposure=<stops>'",
+                    s
+                )),
+            },
+        }
+    }
+}
+
 #[derive(Default)]
 /// Pixel type for `FilmTile`, represents an intermediate pixel type before being merged back into
 /// `Film`.
 pub struct FilmTilePixel {
     contrib_sum: Spectrum,
     filter_weight_sum: Float,
+    // Unfiltered luminance statistics of the samples landing in this pixel, used to estimate the
+    // pixel's variance for adaptive sampling.
+    luminance_sum: Float,
+    luminance_sq_sum: Float,
+    sample_count: u32,
 }
 
 #[derive(Debug, Default)]
@@ -50,6 +185,9 @@ pub struct Pixel {
     // TOOD(wathiede): make this AtomicFloat if that proves necessary.
     // splat_xyz: [AtomicFloat; 3],
     splat_xyz: [Float; 3],
+    luminance_sum: Float,
+    luminance_sq_sum: Float,
+    sample_count: u32,
     /* TODO(wathiede): figure how how to do this and if it is worth it to prevent unaligned struct.
      * _pad: Float, */
 }
@@ -70,9 +208,40 @@ pub struct Film {
     scale: Float,
     /// cropped_pixel_bounds represents the portion of the `Film` to render
     pub cropped_pixel_bounds: Bounds2i,
-    pixels: Arc<Mutex<Vec<Pixel>>>,
+    // One lock per scanline of `cropped_pixel_bounds`, so tiles covering different rows can be
+    // merged concurrently.
+    pixels: Vec<Mutex<Vec<Pixel>>>,
     filter_table: Vec<Float>,
     max_sample_luminance: Float,
+    /// If set, [write_image] also writes a grayscale image of each pixel's filter weight sum to
+    /// this path.  See [write_heatmap].
+    ///
+    /// [write_image]: Film::write_image
+    /// [write_heatmap]: Film::write_heatmap
+    pub weight_image_filename: Option<String>,
+    /// Operator [write_image] applies to the image when it is stored in an 8-bit format.
+    ///
+    /// [write_image]: Film::write_image
+    pub tone_mapper: ToneMapper,
+    update_callback: Mutex<Option<UpdateCallback>>,
+}
+
+impl fmt::Debug for Film {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Film")
+            .field("full_resolution", &self.full_resolution)
+            .field("cropped_pixel_bounds", &self.cropped_pixel_bounds)
+            .field("diagonal_m", &self.diagonal_m)
+            .field("filename", &self.filename)
+            .field("scale", &self.scale)
+            .field("max_sample_luminance", &self.max_sample_luminance)
+            .field("weight_image_filename", &self.weight_image_filename)
+            .field(
+                "update_callback",
+                &self.update_callback.lock().unwrap().is_some(),
+            )
+            .finish_non_exhaustive()
+    }
 }
 
 impl Film {
@@ -89,6 +258,7 @@ impl Film {
         max_sample_luminance: Float,
     ) -> Film {
         let full_resolution = resolution;
+        let crop_window = sanitize_crop_window(crop_window);
         let cropped_pixel_bounds = Bounds2i::from((
             Point2i::from((
                 (full_resolution.x as Float * crop_window.p_min.x).ceil() as isize,
@@ -103,11 +273,10 @@ impl Film {
             "Created film with full resolution {}. Crop window of {} -> croppedPixelBounds {}",
             resolution, crop_window, cropped_pixel_bounds
         );
-        let pixels = Arc::new(Mutex::new(
-            (0..cropped_pixel_bounds.area())
-                .map(|_| Pixel::default())
-                .collect(),
-        ));
+        let extent = cropped_pixel_bounds.diagonal();
+        let pixels = (0..extent.y.max(0))
+            .map(|_| Mutex::new((0..extent.x.max(0)).map(|_| Pixel::default()).collect()))
+            .collect();
         // TODO(wathiede): increment global stats like:
         // filmPixelMemory += croppedPixelBounds.Area() * sizeof(Pixel);
         let w = FILTER_TABLE_WIDTH as Float;
@@ -133,7 +302,97 @@ impl Film {
             filter_table,
             scale,
             max_sample_luminance,
+            weight_image_filename: None,
+            tone_mapper: ToneMapper::default(),
+            update_callback: Mutex::new(None),
+        }
+    }
+
+    /// Like [new], but returns an error instead of a `Film` without any pixels when `crop_window`
+    /// covers none of the film.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{core::film::Film, filters::r#box::BoxFilter};
+    ///
+    /// let film = Film::try_new(
+    ///     [8, 8].into(),
+    ///     [[0.5, 0.25], [0.5, 0.75]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// assert_eq!(
+    ///     film.unwrap_err().to_string(),
+    ///     "crop window [ [ 0.5, 0.25 ] - [ 0.5, 0.75 ] ] covers no pixels of the [ 8, 8 ] film"
+    /// );
+    /// ```
+    ///
+    /// [new]: Film::new
+    pub fn try_new(
+        resolution: Point2i,
+        crop_window: Bounds2f,
+        filter: Box<dyn Filter>,
+        diagonal_mm: Float,
+        filename: String,
+        scale: Float,
+        max_sample_luminance: Float,
+    ) -> Result<Film, Error> {
+        let film = Film::new(
+            resolution,
+            crop_window,
+            filter,
+            diagonal_mm,
+            filename,
+            scale,
+            max_sample_luminance,
+        );
+        if is_empty(&film.cropped_pixel_bounds) {
+            return Err(Error::EmptyCropWindow {
+                crop_window,
+                resolution,
+            });
         }
+        Ok(film)
+    }
+
+    /// Restricts the pixels of the `Film` to those in both `pixel_bounds` and the bounds selected
+    /// by its crop window.  This discards anything accumulated in the `Film` so far, so it should
+    /// be called before rendering.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{core::{film::Film, geometry::Bounds2i}, filters::r#box::BoxFilter};
+    ///
+    /// let mut film = Film::new(
+    ///     [8, 8].into(),
+    ///     [[0.5, 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// film.set_pixel_bounds(Bounds2i::from([[2, 2], [6, 6]])).unwrap();
+    /// assert_eq!(film.cropped_pixel_bounds, Bounds2i::from([[4, 2], [6, 6]]));
+    /// assert!(film.set_pixel_bounds(Bounds2i::from([[0, 0], [2, 2]])).is_err());
+    /// ```
+    pub fn set_pixel_bounds(&mut self, pixel_bounds: Bounds2i) -> Result<(), Error> {
+        let bounds = Bounds2i::intersect(&self.cropped_pixel_bounds, &pixel_bounds);
+        if is_empty(&bounds) {
+            return Err(Error::EmptyPixelBounds {
+                pixel_bounds,
+                cropped_pixel_bounds: self.cropped_pixel_bounds,
+            });
+        }
+        let extent = bounds.diagonal();
+        self.pixels = (0..extent.y)
+            .map(|_| Mutex::new((0..extent.x).map(|_| Pixel::default()).collect()))
+            .collect();
+        self.cropped_pixel_bounds = bounds;
+        Ok(())
     }
 
     /// Return the bounding box for sampling this `Film`.
@@ -228,7 +487,8 @@ impl Film {
 
     /// Create a `FilmTile` representing the subregion of this `Film` denoted by `sample_bounds`.
     /// The `FilmTile` should have its pixels contributed to the `Film` by calling
-    /// `merge_film_tile`.
+    /// `merge_film_tile`.  Returns `None` if samples in `sample_bounds` can't contribute to any
+    /// pixel in the crop window.
     ///
     /// # Examples
     /// ```
@@ -251,17 +511,21 @@ impl Film {
     /// // Tile bigger than Film's crop area gets clipped to Film's crop area.
     /// assert_eq!(
     ///     film.get_film_tile(Bounds2i::from([[0, 0], [1920, 1080]]))
+    ///         .unwrap()
     ///         .get_pixel_bounds(),
     ///     Bounds2i::from([[1920 / 4, 1080 / 4], [3 * 1920 / 4, 3 * 1080 / 4]])
     /// );
     /// // Tile smaller than Film's crop area is the given bound expanded by half the filter size.
     /// assert_eq!(
     ///     film.get_film_tile(Bounds2i::from([[500, 500], [600, 600]]))
+    ///         .unwrap()
     ///         .get_pixel_bounds(),
     ///     Bounds2i::from([[492, 492], [608, 608]])
     /// );
+    /// // Tile entirely outside the Film's crop area.
+    /// assert!(film.get_film_tile(Bounds2i::from([[0, 0], [100, 100]])).is_none());
     /// ```
-    pub fn get_film_tile(&self, sample_bounds: Bounds2i) -> FilmTile {
+    pub fn get_film_tile(&self, sample_bounds: Bounds2i) -> Option<FilmTile<'_>> {
         let half_pixel = Vector2f::from([0.5, 0.5]);
         let float_bounds = Bounds2f::from(sample_bounds);
         let p0 = Point2i::from((float_bounds.p_min - half_pixel - self.filter.radius()).ceil());
@@ -271,13 +535,183 @@ impl Film {
         );
         let tile_pixel_bounds =
             Bounds2i::intersect(&Bounds2i::from([p0, p1]), &self.cropped_pixel_bounds);
-        FilmTile::new(
+        if tile_pixel_bounds.p_min.x >= tile_pixel_bounds.p_max.x
+            || tile_pixel_bounds.p_min.y >= tile_pixel_bounds.p_max.y
+        {
+            return None;
+        }
+        Some(FilmTile::new(
             tile_pixel_bounds,
             self.filter.radius(),
             &self.filter_table,
             FILTER_TABLE_WIDTH,
             self.max_sample_luminance,
-        )
+        ))
+    }
+
+    /// Returns the number of tiles of `tile_size` x `tile_size` pixels needed to cover
+    /// [get_sample_bounds] in x and y.
+    ///
+    /// [get_sample_bounds]: Film::get_sample_bounds
+    fn tile_count(&self, tile_size: usize) -> Point2i {
+        assert!(tile_size > 0, "tile_size must be positive");
+        let tile_size = tile_size as isize;
+        let extent = self.get_sample_bounds().diagonal();
+        Point2i::from([
+            0.max((extent.x + tile_size - 1) / tile_size),
+            0.max((extent.y + tile_size - 1) / tile_size),
+        ])
+    }
+
+    /// Returns the sample bounds of tile `tile` when [get_sample_bounds] is split into tiles of
+    /// `tile_size` pixels.  Tiles along the right and bottom edges are clipped to the sample bounds.
+    ///
+    /// [get_sample_bounds]: Film::get_sample_bounds
+    fn tile_bounds(&self, tile_size: usize, tile: Point2i) -> Bounds2i {
+        let sample_bounds = self.get_sample_bounds();
+        let tile_size = tile_size as isize;
+        let p_min = Point2i::from([
+            sample_bounds.p_min.x + tile.x * tile_size,
+            sample_bounds.p_min.y + tile.y * tile_size,
+        ]);
+        let p_max = Point2i::from([
+            (p_min.x + tile_size).min(sample_bounds.p_max.x),
+            (p_min.y + tile_size).min(sample_bounds.p_max.y),
+        ]);
+        Bounds2i { p_min, p_max }
+    }
+
+    /// Returns an iterator of tiles, at most `tile_size` x `tile_size` pixels, that exactly cover
+    /// [get_sample_bounds] without overlapping.  Tiles are visited in scanline order, see
+    /// [iter_tiles_ordered] to choose a different order.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, geometry::Bounds2i},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let filter = BoxFilter::new([0.5, 0.5].into());
+    /// let film = Film::new(
+    ///     [40, 20].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(filter),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let tiles: Vec<_> = film.iter_tiles(16).collect();
+    /// assert_eq!(
+    ///     tiles,
+    ///     vec![
+    ///         Bounds2i::from([[0, 0], [16, 16]]),
+    ///         Bounds2i::from([[16, 0], [32, 16]]),
+    ///         Bounds2i::from([[32, 0], [40, 16]]),
+    ///         Bounds2i::from([[0, 16], [16, 20]]),
+    ///         Bounds2i::from([[16, 16], [32, 20]]),
+    ///         Bounds2i::from([[32, 16], [40, 20]]),
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// [get_sample_bounds]: Film::get_sample_bounds
+    /// [iter_tiles_ordered]: Film::iter_tiles_ordered
+    pub fn iter_tiles(&self, tile_size: usize) -> impl Iterator<Item = Bounds2i> + '_ {
+        self.iter_tiles_ordered(tile_size, TileOrder::Scanline)
+    }
+
+    /// Like [iter_tiles], but visits the tiles in the given `order`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         film::{Film, TileOrder},
+    ///         geometry::Bounds2i,
+    ///     },
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let filter = BoxFilter::new([0.5, 0.5].into());
+    /// let film = Film::new(
+    ///     [4, 4].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(filter),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let tiles: Vec<_> = film.iter_tiles_ordered(1, TileOrder::Morton).take(5).collect();
+    /// assert_eq!(
+    ///     tiles,
+    ///     vec![
+    ///         Bounds2i::from([[0, 0], [1, 1]]),
+    ///         Bounds2i::from([[1, 0], [2, 1]]),
+    ///         Bounds2i::from([[0, 1], [1, 2]]),
+    ///         Bounds2i::from([[1, 1], [2, 2]]),
+    ///         Bounds2i::from([[2, 0], [3, 1]]),
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// [iter_tiles]: Film::iter_tiles
+    pub fn iter_tiles_ordered(
+        &self,
+        tile_size: usize,
+        order: TileOrder,
+    ) -> impl Iterator<Item = Bounds2i> + '_ {
+        let n_tiles = self.tile_count(tile_size);
+        let mut tiles: Vec<Point2i> = Bounds2i::from([[0, 0].into(), n_tiles]).iter().collect();
+        if n_tiles.x == 0 || n_tiles.y == 0 {
+            tiles.clear();
+        }
+        if order == TileOrder::Morton {
+            tiles.sort_by_key(|t| encode_morton_2(t.x as u32, t.y as u32));
+        }
+        tiles
+            .into_iter()
+            .map(move |t| self.tile_bounds(tile_size, t))
+    }
+
+    /// Calls `func` for every tile yielded by [iter_tiles], spreading the work across threads with
+    /// [parallel_for_2d].  This lets an integrator express its rendering loop as a closure over a
+    /// single tile's sample bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{core::film::Film, filters::r#box::BoxFilter};
+    /// use std::sync::atomic::{AtomicIsize, Ordering};
+    ///
+    /// let filter = BoxFilter::new([0.5, 0.5].into());
+    /// let film = Film::new(
+    ///     [40, 20].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(filter),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let area = AtomicIsize::new(0);
+    /// film.parallel_for_tiles(16, |tile_bounds| {
+    ///     area.fetch_add(tile_bounds.area(), Ordering::Relaxed);
+    /// });
+    /// assert_eq!(area.load(Ordering::Relaxed), 40 * 20);
+    /// ```
+    ///
+    /// [iter_tiles]: Film::iter_tiles
+    /// [parallel_for_2d]: crate::core::parallel::parallel_for_2d
+    pub fn parallel_for_tiles<F>(&self, tile_size: usize, func: F)
+    where
+        F: Fn(Bounds2i) + Sync + Send,
+    {
+        parallel_for_2d(
+            |tile| func(self.tile_bounds(tile_size, tile)),
+            self.tile_count(tile_size),
+        );
     }
 
     /// Merge a `FilmTile` into the `Film`.
@@ -304,8 +738,8 @@ impl Film {
     ///     1.,
     /// );
     ///
-    /// let left = film.get_film_tile(Bounds2i::from([[0, 0], [10, 10]]));
-    /// let right = film.get_film_tile(Bounds2i::from([[10, 0], [10, 10]]));
+    /// let left = film.get_film_tile(Bounds2i::from([[0, 0], [10, 10]])).unwrap();
+    /// let right = film.get_film_tile(Bounds2i::from([[10, 0], [10, 10]])).unwrap();
     /// // spawn threads and render to left and right.  Then merge the results back into the film.
     /// film.merge_film_tile(left);
     /// film.merge_film_tile(right);
@@ -313,16 +747,225 @@ impl Film {
     pub fn merge_film_tile(&self, tile: FilmTile) {
         // TODO(wathiede): ProfilePhase p(Prof::MergeFilmTile);
         info!("Merging film tile {}", tile.pixel_bounds);
-        let mut pixels = self.pixels.lock().unwrap();
-        for pixel in tile.get_pixel_bounds().iter() {
-            let tile_pixel = tile.get_pixel(pixel);
-            let merge_pixel = &mut pixels[self.pixel_offset(pixel)];
-            let xyz = tile_pixel.contrib_sum.to_xyz();
-            for (i, item) in xyz.iter().enumerate() {
-                merge_pixel.xyz[i] += item;
+        let bounds = tile.get_pixel_bounds();
+        let preview = self.update_callback.lock().unwrap().is_some();
+        let mut rgb = Vec::with_capacity(if preview {
+            3 * bounds.area() as usize
+        } else {
+            0
+        });
+        // Only one scanline is locked at a time, so merges of tiles that overlap other rows can
+        // proceed in parallel.
+        for y in bounds.p_min.y..bounds.p_max.y {
+            let (row, _) = self.pixel_index(Point2i::from([bounds.p_min.x, y]));
+            let mut scanline = self.pixels[row].lock().unwrap();
+            for x in bounds.p_min.x..bounds.p_max.x {
+                let pixel = Point2i::from([x, y]);
+                let tile_pixel = tile.get_pixel(pixel);
+                let merge_pixel = &mut scanline[self.pixel_index(pixel).1];
+                // Drop non-finite contributions so one bad sample can't poison the pixel for
+                // the rest of the render.
+                let xyz = tile_pixel.contrib_sum.sanitize().to_xyz();
+                for (i, item) in xyz.iter().enumerate() {
+                    merge_pixel.xyz[i] += item;
+                }
+                merge_pixel.filter_weight_sum += tile_pixel.filter_weight_sum;
+                if tile_pixel.luminance_sq_sum.is_finite() {
+                    merge_pixel.luminance_sum += tile_pixel.luminance_sum;
+                    merge_pixel.luminance_sq_sum += tile_pixel.luminance_sq_sum;
+                }
+                merge_pixel.sample_count += tile_pixel.sample_count;
+                if preview {
+                    rgb.extend_from_slice(&self.pixel_rgb(merge_pixel));
+                }
             }
-            merge_pixel.filter_weight_sum += tile_pixel.filter_weight_sum;
         }
+        if preview {
+            if let Some(cb) = self.update_callback.lock().unwrap().as_ref() {
+                cb(&rgb, bounds);
+            }
+        }
+    }
+
+    /// Registers `cb` to be called after each [merge_film_tile] with the RGB values, three per
+    /// pixel in scanline order, of the pixels the merged tile covers, and the bounds of those
+    /// pixels.  Values are normalized by their filter weights and scaled like [write_image], but
+    /// don't include splats.  This allows previewing an image while it renders.  Any previously
+    /// registered callback is replaced.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use pbrt::{
+    ///     core::{film::Film, geometry::Bounds2i, spectrum::Spectrum},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [4, 4].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let updates = Arc::new(Mutex::new(Vec::new()));
+    /// let u = Arc::clone(&updates);
+    /// film.set_update_callback(Box::new(move |rgb, bounds| {
+    ///     assert_eq!(rgb.len(), 3 * bounds.area() as usize);
+    ///     u.lock().unwrap().push(bounds);
+    /// }));
+    /// let mut tile = film.get_film_tile(Bounds2i::from([[0, 0], [2, 2]])).unwrap();
+    /// tile.add_sample([0.5, 0.5].into(), Spectrum::new(1.), 1.);
+    /// let merged = tile.get_pixel_bounds();
+    /// film.merge_film_tile(tile);
+    /// assert_eq!(*updates.lock().unwrap(), vec![merged]);
+    /// ```
+    ///
+    /// [merge_film_tile]: Film::merge_film_tile
+    /// [write_image]: Film::write_image
+    pub fn set_update_callback(&self, cb: UpdateCallback) {
+        *self.update_callback.lock().unwrap() = Some(cb);
+    }
+
+    /// Returns the RGB value of `pixel` normalized by its filter weight and scaled by `scale`.
+    fn pixel_rgb(&self, pixel: &Pixel) -> [Float; 3] {
+        let mut rgb = xyz_to_rgb(pixel.xyz);
+        if pixel.filter_weight_sum != 0. {
+            let inv_wt = 1. / pixel.filter_weight_sum;
+            rgb.iter_mut().for_each(|c| *c = (*c * inv_wt).max(0.));
+        }
+        rgb.iter_mut().for_each(|c| *c *= self.scale);
+        rgb
+    }
+
+    /// Locks every scanline, in order, for operations that need a consistent view of the whole
+    /// image.  Merges hold at most one scanline lock at a time, so this can't deadlock with them.
+    fn lock_pixels(&self) -> Vec<MutexGuard<'_, Vec<Pixel>>> {
+        self.pixels.iter().map(|row| row.lock().unwrap()).collect()
+    }
+
+    /// Returns the pixel at `p` from scanlines locked with [lock_pixels].
+    ///
+    /// [lock_pixels]: Film::lock_pixels
+    fn locked_pixel<'a>(&self, pixels: &'a [MutexGuard<'_, Vec<Pixel>>], p: Point2i) -> &'a Pixel {
+        let (row, col) = self.pixel_index(p);
+        &pixels[row][col]
+    }
+
+    /// Estimates the variance of the value of pixel `p` from the luminance of the samples added
+    /// to it so far.  The returned value is the variance of the pixel's mean, so it shrinks as more
+    /// samples are taken.  Pixels with fewer than two samples borrow the samples of their
+    /// immediate neighbors to estimate the spread.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, geometry::Bounds2i, spectrum::Spectrum},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [4, 4].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+    /// for v in &[0., 1., 0., 1.] {
+    ///     tile.add_sample([1.5, 1.5].into(), Spectrum::new(*v), 1.);
+    ///     tile.add_sample([2.5, 2.5].into(), Spectrum::new(0.5), 1.);
+    /// }
+    /// film.merge_film_tile(tile);
+    /// assert!(film.variance_estimate([1, 1].into()) > 0.);
+    /// assert!(film.variance_estimate([2, 2].into()) < 1e-6);
+    /// ```
+    pub fn variance_estimate(&self, p: Point2i) -> Float {
+        let pixels = self.lock_pixels();
+        self.variance_estimate_locked(&pixels, p)
+    }
+
+    fn variance_estimate_locked(&self, pixels: &[MutexGuard<'_, Vec<Pixel>>], p: Point2i) -> Float {
+        let pixel = self.locked_pixel(pixels, p);
+        let (mut n, mut sum, mut sq_sum) = (
+            pixel.sample_count as Float,
+            pixel.luminance_sum,
+            pixel.luminance_sq_sum,
+        );
+        if pixel.sample_count < 2 {
+            let neighborhood = Bounds2i::intersect(
+                &Bounds2i::from([[p.x - 1, p.y - 1], [p.x + 2, p.y + 2]]),
+                &self.cropped_pixel_bounds,
+            );
+            n = 0.;
+            sum = 0.;
+            sq_sum = 0.;
+            for np in neighborhood.iter() {
+                let neighbor = self.locked_pixel(pixels, np);
+                n += neighbor.sample_count as Float;
+                sum += neighbor.luminance_sum;
+                sq_sum += neighbor.luminance_sq_sum;
+            }
+        }
+        if n < 2. {
+            return 0.;
+        }
+        let sample_variance = ((sq_sum - sum * sum / n) / (n - 1.)).max(0.);
+        sample_variance / (pixel.sample_count.max(1) as Float)
+    }
+
+    /// Computes how many additional samples each pixel needs for its [variance_estimate] to drop
+    /// to `threshold`.  Pixels that have already converged get zero.  The returned `Vec` has one
+    /// entry per pixel in `cropped_pixel_bounds`, in the order visited by `Bounds2i::iter`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, spectrum::Spectrum},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [2, 1].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+    /// for v in &[0., 1., 0., 1.] {
+    ///     tile.add_sample([0.5, 0.5].into(), Spectrum::new(0.5), 1.);
+    ///     tile.add_sample([1.5, 0.5].into(), Spectrum::new(*v), 1.);
+    /// }
+    /// film.merge_film_tile(tile);
+    /// let budget = film.to_sample_budget_map(0.01);
+    /// assert_eq!(budget[0], 0);
+    /// assert!(budget[1] > 0);
+    /// ```
+    ///
+    /// [variance_estimate]: Film::variance_estimate
+    pub fn to_sample_budget_map(&self, threshold: Float) -> Vec<usize> {
+        let pixels = self.lock_pixels();
+        self.cropped_pixel_bounds
+            .iter()
+            .map(|p| {
+                let variance = self.variance_estimate_locked(&pixels, p);
+                if variance <= threshold {
+                    return 0;
+                }
+                // The variance of the mean falls off as 1/n, so this is the total sample count
+                // that brings the estimate down to the threshold.
+                let n = self.locked_pixel(&pixels, p).sample_count.max(1) as Float;
+                ((n * variance / threshold).ceil() - n).max(1.) as usize
+            })
+            .collect()
     }
 
     /// set_image allows the caller to directly set the pixel values of the entire `Film`
@@ -330,21 +973,118 @@ impl Film {
         unimplemented!()
     }
 
-    /// add_splat adds the contributions of `v` to the `Film` at `p`
-    pub fn add_splat(&self, _p: &Point2f, _v: Spectrum) {
-        unimplemented!()
+    /// add_splat adds the contributions of `v` to the `Film` at `p`.  Unlike samples added
+    /// through a [FilmTile], splats aren't filtered and are scaled by `splat_scale` when the image
+    /// is finalized.  Like samples, splats brighter than the film's maximum sample luminance are
+    /// scaled down to it.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, spectrum::Spectrum},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [2, 1].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     10.,
+    /// );
+    /// film.add_splat(&[1.5, 0.5].into(), Spectrum::new(1e6));
+    /// let (rgb, _) = film.to_rgb_buffer(1.);
+    /// assert_eq!(&rgb[..3], &[0., 0., 0.]);
+    /// assert!((Spectrum::from_rgb([rgb[3], rgb[4], rgb[5]]).y() - 10.).abs() < 1e-3);
+    /// ```
+    pub fn add_splat(&self, p: &Point2f, v: Spectrum) {
+        // TODO(wathiede): ProfilePhase pp(Prof::SplatFilm);
+        if v.has_nans() {
+            error!(
+                "Ignoring splatted spectrum with NaN values at ({}, {})",
+                p.x, p.y
+            );
+            return;
+        }
+        let y = v.y();
+        if y.is_infinite() {
+            error!(
+                "Ignoring splatted spectrum with infinite luminance at ({}, {})",
+                p.x, p.y
+            );
+            return;
+        }
+        let pi = Point2i::from(p.floor());
+        if !self.cropped_pixel_bounds.inside_exclusive(pi) {
+            return;
+        }
+        let v = if y > self.max_sample_luminance {
+            v * (self.max_sample_luminance / y)
+        } else {
+            v
+        };
+        let xyz = v.to_xyz();
+        let (row, col) = self.pixel_index(pi);
+        let pixel = &mut self.pixels[row].lock().unwrap()[col];
+        for (splat, c) in pixel.splat_xyz.iter_mut().zip(xyz.iter()) {
+            *splat += c;
+        }
     }
 
-    /// write_image stores the contents of the `Film` to the disk path specifed at construction
-    /// time.
-    pub fn write_image(&self, splat_scale: Float) {
-        info!("Converting image to RGB and computing final weighted pixel values");
-        let mut rgb: Vec<Float> = (0..(3 * self.cropped_pixel_bounds.area() as usize))
-            .map(|_| 0.)
-            .collect();
-        let mut pixels = self.pixels.lock().unwrap();
+    /// Returns the final RGB values of the `Film`, three per pixel in scanline order, and the
+    /// bounds of the pixels they cover.  Each pixel is normalized by its filter weight, has its
+    /// splats, weighted by `splat_scale`, added, and is multiplied by the film's scale.  These are
+    /// the values [write_image] stores.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, geometry::Bounds2i, spectrum::{xyz_to_rgb, Spectrum}},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [4, 2].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     2.,
+    ///     1.,
+    /// );
+    /// let mut tile = film.get_film_tile(Bounds2i::from([[0, 0], [4, 2]])).unwrap();
+    /// tile.add_sample([1.5, 0.5].into(), Spectrum::from_rgb([0.25, 0.5, 0.75]), 1.);
+    /// film.merge_film_tile(tile);
+    ///
+    /// let (rgb, bounds) = film.to_rgb_buffer(1.);
+    /// assert_eq!(bounds, Bounds2i::from([[0, 0], [4, 2]]));
+    /// assert_eq!(rgb.len(), 3 * 4 * 2);
+    /// let want = xyz_to_rgb(film.get_pixel_xyz([1, 0].into()));
+    /// for c in 0..3 {
+    ///     assert!((rgb[3 + c] - 2. * want[c]).abs() < 1e-5);
+    /// }
+    /// assert_eq!(&rgb[..3], &[0., 0., 0.]);
+    /// ```
+    ///
+    /// [write_image]: Film::write_image
+    pub fn to_rgb_buffer(&self, splat_scale: Float) -> (Vec<Float>, Bounds2i) {
+        let pixels = self.lock_pixels();
+        (
+            self.final_rgb(&pixels, splat_scale),
+            self.cropped_pixel_bounds,
+        )
+    }
+
+    /// Computes the values returned by [to_rgb_buffer] from scanlines locked with [lock_pixels].
+    ///
+    /// [to_rgb_buffer]: Film::to_rgb_buffer
+    /// [lock_pixels]: Film::lock_pixels
+    fn final_rgb(&self, pixels: &[MutexGuard<'_, Vec<Pixel>>], splat_scale: Float) -> Vec<Float> {
+        let mut rgb: Vec<Float> = vec![0.; 3 * self.cropped_pixel_bounds.area() as usize];
         for (offset, p) in self.cropped_pixel_bounds.iter().enumerate() {
-            let pixel = &mut pixels[self.pixel_offset(p)];
+            let pixel = self.locked_pixel(pixels, p);
             let c = xyz_to_rgb(pixel.xyz);
             rgb[3 * offset] = c[0];
             rgb[3 * offset + 1] = c[1];
@@ -370,12 +1110,71 @@ impl Film {
             rgb[3 * offset + 1] *= self.scale;
             rgb[3 * offset + 2] *= self.scale;
         }
+        rgb
+    }
+
+    /// write_image stores the contents of the `Film` to the disk path specifed at construction
+    /// time.  A `Film` with an empty filename isn't written, which is how images rendered to memory
+    /// skip the disk.  8-bit images are tone mapped with the film's `tone_mapper`.
+    pub fn write_image(&self, splat_scale: Float) {
+        self.write_image_tonemapped(splat_scale, self.tone_mapper);
+    }
+
+    /// Like [write_image], but 8-bit images are tone mapped with `tm` instead of the film's
+    /// `tone_mapper`.
+    ///
+    /// [write_image]: Film::write_image
+    pub fn write_image_tonemapped(&self, splat_scale: Float, tm: ToneMapper) {
+        if !self.filename.is_empty() {
+            info!("Converting image to RGB and computing final weighted pixel values");
+            let pixels = self.lock_pixels();
+            let mut rgb = self.final_rgb(&pixels, splat_scale);
+            if is_8_bit(&self.filename) {
+                rgb.iter_mut().for_each(|v| *v = tm.map(*v));
+            }
+            info!(
+                "Writing image {} with bounds {}",
+                self.filename, self.cropped_pixel_bounds
+            );
+            write_image(
+                &self.filename,
+                &rgb,
+                self.cropped_pixel_bounds,
+                self.full_resolution,
+            );
+        }
+
+        if let Some(weight_image_filename) = &self.weight_image_filename {
+            self.write_heatmap(weight_image_filename);
+        }
+    }
+
+    /// write_heatmap stores a grayscale image of each pixel's filter weight sum to `filename`.
+    /// Values are normalized so the pixel with the largest weight sum is white.  A `Film` with no
+    /// samples produces an all black image.
+    pub fn write_heatmap(&self, filename: &str) {
+        let weights: Vec<Float> = {
+            let pixels = self.lock_pixels();
+            self.cropped_pixel_bounds
+                .iter()
+                .map(|p| self.locked_pixel(&pixels, p).filter_weight_sum)
+                .collect()
+        };
+        let max_weight = weights.iter().cloned().fold(0., Float::max);
+        let inv_max = if max_weight > 0. { 1. / max_weight } else { 0. };
+        let rgb: Vec<Float> = weights
+            .iter()
+            .flat_map(|w| {
+                let v = (w * inv_max).max(0.);
+                vec![v, v, v]
+            })
+            .collect();
         info!(
-            "Writing image {} with bounds {}",
-            self.filename, self.cropped_pixel_bounds
+            "Writing weight heatmap {} with bounds {}",
+            filename, self.cropped_pixel_bounds
         );
         write_image(
-            &self.filename,
+            filename,
             &rgb,
             self.cropped_pixel_bounds,
             self.full_resolution,
@@ -387,26 +1186,29 @@ impl Film {
         unimplemented!()
     }
 
-    fn pixel_offset(&self, p: Point2i) -> usize {
+    /// Returns the scanline and column in `pixels` of `p`.
+    fn pixel_index(&self, p: Point2i) -> (usize, usize) {
         debug_assert!(
             self.cropped_pixel_bounds.inside_exclusive(p),
             "p {} outside {}",
             p,
             self.cropped_pixel_bounds
         );
-        let width = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
-        ((p.x - self.cropped_pixel_bounds.p_min.x)
-            + (p.y - self.cropped_pixel_bounds.p_min.y) * width)
-            .try_into()
-            .unwrap()
+        (
+            (p.y - self.cropped_pixel_bounds.p_min.y)
+                .try_into()
+                .unwrap(),
+            (p.x - self.cropped_pixel_bounds.p_min.x)
+                .try_into()
+                .unwrap(),
+        )
     }
 
     /// Not public in the C++ implementation, but necessary for docttest.
     pub fn get_pixel_xyz(&self, p: Point2i) -> [Float; 3] {
         debug_assert!(self.cropped_pixel_bounds.inside_exclusive(p));
-        let offset = self.pixel_offset(p);
-        let pixels = self.pixels.lock().unwrap();
-        pixels[offset].xyz
+        let (row, col) = self.pixel_index(p);
+        self.pixels[row].lock().unwrap()[col].xyz
     }
 
     /*
@@ -419,6 +1221,102 @@ impl Film {
     */
 }
 
+/// Creates a `Film` from the given `params` and `filter`.  If "weightimagefilename" is set, a
+/// heatmap of the per-pixel filter weights is written there alongside the image.  When
+/// `opt.quick_render` is set the resolution is quartered along each axis, and `opt.pixel_bounds`
+/// further restricts the pixels selected by "cropwindow".  An error is returned if no pixels are
+/// left to render.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{film::create_film, geometry::Bounds2i, paramset::testutils::make_int_param_set},
+///     filters::r#box::BoxFilter,
+///     Options,
+/// };
+///
+/// let params = make_int_param_set("xresolution", vec![64]);
+/// let film = create_film(
+///     &params,
+///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+///     &Options::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(film.cropped_pixel_bounds, Bounds2i::from([[0, 0], [64, 720]]));
+///
+/// let quick = Options {
+///     quick_render: true,
+///     ..Default::default()
+/// };
+/// let film = create_film(&params, Box::new(BoxFilter::new([0.5, 0.5].into())), &quick).unwrap();
+/// assert_eq!(film.cropped_pixel_bounds, Bounds2i::from([[0, 0], [16, 180]]));
+/// ```
+pub fn create_film(
+    params: &ParamSet,
+    filter: Box<dyn Filter>,
+    opt: &Options,
+) -> Result<Film, Error> {
+    // The C++ implementation defaults to EXR, which can't be written yet.
+    let filename = params.find_one_string("filename", "pbrt.png");
+    let mut xres = params.find_one_int("xresolution", 1280);
+    let mut yres = params.find_one_int("yresolution", 720);
+    if opt.quick_render {
+        xres = (xres / 4).max(1);
+        yres = (yres / 4).max(1);
+    }
+    let mut crop = Bounds2f::from([[0., 0.], [1., 1.]]);
+    if let Some(cr) = params.find_float("cropwindow") {
+        if cr.len() == 4 {
+            // Not built with `from`, which would silently reorder inverted coordinates.
+            crop = Bounds2f {
+                p_min: Point2f::from([cr[0], cr[2]]),
+                p_max: Point2f::from([cr[1], cr[3]]),
+            };
+        } else {
+            error!(
+                "{} values supplied for \"cropwindow\". Expected 4.",
+                cr.len()
+            );
+        }
+    }
+
+    let scale = params.find_one_float("scale", 1.);
+    let diagonal = params.find_one_float("diagonal", 35.);
+    let max_sample_luminance = params.find_one_float("maxsampleluminance", Float::INFINITY);
+    let weight_image_filename = params.find_one_string("weightimagefilename", "");
+    let mut film = Film::try_new(
+        Point2i::from([xres, yres]),
+        crop,
+        filter,
+        diagonal,
+        filename,
+        scale,
+        max_sample_luminance,
+    )?;
+    if let Some(pixel_bounds) = opt.pixel_bounds {
+        film.set_pixel_bounds(pixel_bounds)?;
+    }
+    if !weight_image_filename.is_empty() {
+        film.weight_image_filename = Some(weight_image_filename);
+    }
+    film.tone_mapper = opt.tone_mapper;
+    Ok(film)
+}
+
+/// Interleaves the bits of `x` and `y` to compute their position along a Z-order curve.
+fn encode_morton_2(x: u32, y: u32) -> u64 {
+    fn left_shift_2(x: u32) -> u64 {
+        let mut x = x as u64;
+        x = (x | (x << 16)) & 0x0000_ffff_0000_ffff;
+        x = (x | (x << 8)) & 0x00ff_00ff_00ff_00ff;
+        x = (x | (x << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+        x = (x | (x << 1)) & 0x5555_5555_5555_5555;
+        x
+    }
+    (left_shift_2(y) << 1) | left_shift_2(x)
+}
+
 /// FilmTile represents a subarea of `Film` within the `Film`'s configured sampling bounds.  It
 /// allows rendering of portions of the `Film` to be handed off to separate threads, and the final
 /// assembly of the full image is handled by passing the `FilmTile` back to the `Film` via
@@ -427,31 +1325,124 @@ impl Film {
 /// [merge_film_tile]: Film::merge_film_tile
 pub struct FilmTile<'ft> {
     pixel_bounds: Bounds2i,
-    _filter_radius: Vector2f,
-    _inv_filter_radius: Vector2f,
-    _filter_table: &'ft Vec<Float>,
-    _filter_table_size: usize,
-    _max_sample_luminance: Float,
+    filter_radius: Vector2f,
+    inv_filter_radius: Vector2f,
+    filter_table: &'ft [Float],
+    filter_table_size: usize,
+    max_sample_luminance: Float,
     pixels: Vec<FilmTilePixel>,
 }
 
 impl<'ft> FilmTile<'ft> {
     fn new(
         pixel_bounds: Bounds2i,
-        _filter_radius: Vector2f,
-        _filter_table: &'ft Vec<Float>,
-        _filter_table_size: usize,
-        _max_sample_luminance: Float,
+        filter_radius: Vector2f,
+        filter_table: &'ft [Float],
+        filter_table_size: usize,
+        max_sample_luminance: Float,
     ) -> FilmTile<'ft> {
         let pixel_count = 0.max(pixel_bounds.area());
         FilmTile {
             pixel_bounds,
-            _filter_radius,
-            _inv_filter_radius: [1. / _filter_radius.x, 1. / _filter_radius.y].into(),
-            _filter_table,
-            _filter_table_size,
+            filter_radius,
+            inv_filter_radius: [1. / filter_radius.x, 1. / filter_radius.y].into(),
+            filter_table,
+            filter_table_size,
             pixels: (0..pixel_count).map(|_| FilmTilePixel::default()).collect(),
-            _max_sample_luminance,
+            max_sample_luminance,
+        }
+    }
+
+    /// Adds the radiance `l` carried by a camera ray that sampled the film at `p_film` to every
+    /// pixel within the filter's radius, weighted by the filter and `sample_weight`.  Radiance
+    /// brighter than the film's maximum sample luminance is scaled down to it, trading a little
+    /// bias for the removal of fireflies.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{film::Film, spectrum::Spectrum},
+    ///     filters::r#box::BoxFilter,
+    /// };
+    ///
+    /// let film = Film::new(
+    ///     [4, 4].into(),
+    ///     [[0., 0.], [1., 1.]].into(),
+    ///     Box::new(BoxFilter::new([0.5, 0.5].into())),
+    ///     35.0,
+    ///     "output.png".to_string(),
+    ///     1.,
+    ///     1.,
+    /// );
+    /// let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+    /// tile.add_sample([1.5, 1.5].into(), Spectrum::new(1.), 1.);
+    /// film.merge_film_tile(tile);
+    /// assert_eq!(film.get_pixel_xyz([1, 1].into()), Spectrum::new(1.).to_xyz());
+    /// assert_eq!(film.get_pixel_xyz([2, 2].into()), [0., 0., 0.]);
+    /// ```
+    pub fn add_sample(&mut self, p_film: Point2f, l: Spectrum, sample_weight: Float) {
+        // TODO(wathiede): ProfilePhase _(Prof::AddFilmSample);
+        let y = l.y();
+        let l = if y > self.max_sample_luminance {
+            l * (self.max_sample_luminance / y)
+        } else {
+            l
+        };
+
+        // Record unfiltered statistics in the pixel containing the sample.
+        let p_pixel = Point2i::from(p_film.floor());
+        if self.pixel_bounds.inside_exclusive(p_pixel) {
+            let y = l.y() * sample_weight;
+            let pixel = self.get_pixel_mut(p_pixel);
+            pixel.luminance_sum += y;
+            pixel.luminance_sq_sum += y * y;
+            pixel.sample_count += 1;
+        }
+
+        // Compute sample's raster bounds
+        let p_film_discrete = p_film - Vector2f::from([0.5, 0.5]);
+        let p0 = Point2i::from((p_film_discrete - self.filter_radius).ceil());
+        let p1 =
+            Point2i::from((p_film_discrete + self.filter_radius).floor()) + Point2i::from([1, 1]);
+        let p0 = Point2i::max(p0, self.pixel_bounds.p_min);
+        let p1 = Point2i::min(p1, self.pixel_bounds.p_max);
+        if p0.x >= p1.x || p0.y >= p1.y {
+            return;
+        }
+
+        // Precompute $x$ and $y$ filter table offsets
+        let table_size = self.filter_table_size;
+        let ifx: Vec<usize> = (p0.x..p1.x)
+            .map(|x| {
+                let fx = ((x as Float - p_film_discrete.x)
+                    * self.inv_filter_radius.x
+                    * table_size as Float)
+                    .abs();
+                (fx.floor() as usize).min(table_size - 1)
+            })
+            .collect();
+        let ify: Vec<usize> = (p0.y..p1.y)
+            .map(|y| {
+                let fy = ((y as Float - p_film_discrete.y)
+                    * self.inv_filter_radius.y
+                    * table_size as Float)
+                    .abs();
+                (fy.floor() as usize).min(table_size - 1)
+            })
+            .collect();
+
+        // Loop over filter support and add sample to pixel arrays
+        for y in p0.y..p1.y {
+            for x in p0.x..p1.x {
+                // Evaluate filter value at $(x,y)$ pixel
+                let offset = ify[(y - p0.y) as usize] * table_size + ifx[(x - p0.x) as usize];
+                let filter_weight = self.filter_table[offset];
+
+                // Update pixel values with filtered sample contribution
+                let pixel = self.get_pixel_mut(Point2i::from([x, y]));
+                pixel.contrib_sum += l.clone() * (sample_weight * filter_weight);
+                pixel.filter_weight_sum += filter_weight;
+            }
         }
     }
 
@@ -490,16 +1481,135 @@ impl<'ft> FilmTile<'ft> {
 
 #[cfg(test)]
 mod test {
+    use std::sync::{Arc, Mutex};
+
     use crate::{
         core::{
-            film::{Film, FilmTile},
-            geometry::Bounds2i,
+            film::{Film, FilmTile, TileOrder, ToneMapper},
+            geometry::{Bounds2f, Bounds2i, Point2f, Point2i},
+            rng::Rng,
             spectrum::Spectrum,
         },
         filters::r#box::BoxFilter,
         Float,
     };
 
+    /// Asserts that `tiles` cover every pixel in `bounds` exactly once.
+    fn assert_tiles_cover(bounds: Bounds2i, tiles: &[Bounds2i]) {
+        let width = (bounds.p_max.x - bounds.p_min.x) as usize;
+        let mut counts = vec![0; bounds.area() as usize];
+        for tile in tiles {
+            assert!(tile.area() > 0, "empty tile {}", tile);
+            for p in tile.iter() {
+                assert!(
+                    bounds.inside_exclusive(p),
+                    "{} from tile {} outside {}",
+                    p,
+                    tile,
+                    bounds
+                );
+                let offset =
+                    (p.x - bounds.p_min.x) as usize + (p.y - bounds.p_min.y) as usize * width;
+                counts[offset] += 1;
+            }
+        }
+        for (offset, count) in counts.iter().enumerate() {
+            assert_eq!(
+                *count,
+                1,
+                "pixel {} covered {} times",
+                Point2i::from([
+                    bounds.p_min.x + (offset % width) as isize,
+                    bounds.p_min.y + (offset / width) as isize
+                ]),
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn iter_tiles() {
+        for &(res, crop) in &[
+            ([64, 64], [[0., 0.], [1., 1.]]),
+            ([100, 37], [[0., 0.], [1., 1.]]),
+            ([1, 1], [[0., 0.], [1., 1.]]),
+            ([123, 77], [[0.1, 0.2], [0.7, 0.9]]),
+        ] {
+            for radius in &[0.5, 2.] {
+                let film = Film::new(
+                    res.into(),
+                    Bounds2f::from(crop),
+                    Box::new(BoxFilter::new([*radius, *radius].into())),
+                    35.0,
+                    "output.png".to_string(),
+                    1.,
+                    1.,
+                );
+                let sample_bounds = film.get_sample_bounds();
+                for &tile_size in &[1, 7, 16, 32, 1000] {
+                    for &order in &[TileOrder::Scanline, TileOrder::Morton] {
+                        let tiles: Vec<_> = film.iter_tiles_ordered(tile_size, order).collect();
+                        assert_tiles_cover(sample_bounds, &tiles);
+                    }
+                    let tiles = Mutex::new(Vec::new());
+                    film.parallel_for_tiles(tile_size, |b| tiles.lock().unwrap().push(b));
+                    assert_tiles_cover(sample_bounds, &tiles.into_inner().unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling_statistics() {
+        // Left half of the film is a flat color, right half is noise.
+        const WIDTH: isize = 16;
+        const HEIGHT: isize = 8;
+        let film = Film::new(
+            [WIDTH, HEIGHT].into(),
+            [[0., 0.], [1., 1.]].into(),
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "output.png".to_string(),
+            1.,
+            1.,
+        );
+        let mut rng = Rng::new(0);
+        let sample = |rng: &mut Rng, p: Point2i| {
+            let p_film = Point2f::from([
+                p.x as Float + rng.uniform_float(),
+                p.y as Float + rng.uniform_float(),
+            ]);
+            let l = if p.x < WIDTH / 2 {
+                Spectrum::new(0.5)
+            } else {
+                Spectrum::new(rng.uniform_float())
+            };
+            (p_film, l)
+        };
+        for tile_bounds in film.iter_tiles(4) {
+            let mut tile = film.get_film_tile(tile_bounds).unwrap();
+            for p in tile_bounds.iter() {
+                for _ in 0..8 {
+                    let (p_film, l) = sample(&mut rng, p);
+                    tile.add_sample(p_film, l, 1.);
+                }
+            }
+            film.merge_film_tile(tile);
+        }
+
+        let budget = film.to_sample_budget_map(1e-3);
+        for (p, extra) in film.cropped_pixel_bounds.iter().zip(budget.iter()) {
+            let variance = film.variance_estimate(p);
+            if p.x < WIDTH / 2 {
+                assert!(variance < 1e-6, "flat pixel {} variance {}", p, variance);
+                assert_eq!(*extra, 0, "flat pixel {}", p);
+            } else {
+                assert!(variance > 1e-3, "noisy pixel {} variance {}", p, variance);
+                assert!(*extra > 0, "noisy pixel {}", p);
+            }
+        }
+    }
+
     #[test]
     fn merge_film_tile() {
         fn fill(t: &mut FilmTile, c: &Spectrum) {
@@ -521,8 +1631,12 @@ mod test {
             1.,
         );
 
-        let mut left = film.get_film_tile(Bounds2i::from([[0, 0], [100, 10]]));
-        let mut right = film.get_film_tile(Bounds2i::from([[100, 0], [200, 10]]));
+        let mut left = film
+            .get_film_tile(Bounds2i::from([[0, 0], [100, 10]]))
+            .unwrap();
+        let mut right = film
+            .get_film_tile(Bounds2i::from([[100, 0], [200, 10]]))
+            .unwrap();
         let green = Spectrum::from_rgb([0., 1., 0.]);
         let red = Spectrum::from_rgb([1., 0., 0.]);
         fill(&mut left, &green);
@@ -534,6 +1648,119 @@ mod test {
         assert_eq!(film.get_pixel_xyz([196, 4].into()), red.to_xyz());
     }
 
+    #[test]
+    fn parallel_merge_matches_serial() {
+        let new_film = || {
+            Film::new(
+                [67, 45].into(),
+                [[0., 0.], [1., 1.]].into(),
+                // Wide enough that neighboring tiles overlap when merged.
+                Box::new(BoxFilter::new([2., 2.].into())),
+                35.0,
+                "unused.png".to_string(),
+                1.,
+                Float::INFINITY,
+            )
+        };
+        fn render_tile(film: &Film, tile_bounds: Bounds2i) -> FilmTile<'_> {
+            let mut rng = Rng::new((tile_bounds.p_min.y * 1000 + tile_bounds.p_min.x) as u64);
+            let mut tile = film.get_film_tile(tile_bounds).unwrap();
+            for p in tile_bounds.iter() {
+                for _ in 0..4 {
+                    let p_film = Point2f::from([
+                        p.x as Float + rng.uniform_float(),
+                        p.y as Float + rng.uniform_float(),
+                    ]);
+                    tile.add_sample(p_film, Spectrum::new(rng.uniform_float()), 1.);
+                }
+            }
+            tile
+        }
+
+        let serial = new_film();
+        for tile_bounds in serial.iter_tiles(8) {
+            serial.merge_film_tile(render_tile(&serial, tile_bounds));
+        }
+        let parallel = new_film();
+        parallel.parallel_for_tiles(8, |tile_bounds| {
+            parallel.merge_film_tile(render_tile(&parallel, tile_bounds));
+        });
+
+        let serial_pixels = serial.lock_pixels();
+        let parallel_pixels = parallel.lock_pixels();
+        for p in serial.cropped_pixel_bounds.iter() {
+            let want = serial.locked_pixel(&serial_pixels, p);
+            let got = parallel.locked_pixel(&parallel_pixels, p);
+            assert_eq!(want.sample_count, got.sample_count, "pixel {}", p);
+            // Floating point addition isn't associative, so merge order may perturb the sums.
+            let close = |a: Float, b: Float| (a - b).abs() <= 1e-4 * a.abs().max(1.);
+            assert!(
+                close(want.filter_weight_sum, got.filter_weight_sum),
+                "pixel {} weight {} != {}",
+                p,
+                want.filter_weight_sum,
+                got.filter_weight_sum
+            );
+            for i in 0..3 {
+                assert!(
+                    close(want.xyz[i], got.xyz[i]),
+                    "pixel {} xyz {:?} != {:?}",
+                    p,
+                    want.xyz,
+                    got.xyz
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn write_heatmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let heatmap = dir.path().join("weights.png");
+        let filter = BoxFilter::new([8., 8.].into());
+        let mut film = Film::new(
+            [200, 10].into(),
+            [[0., 0.], [1., 1.]].into(),
+            Box::new(filter),
+            35.0,
+            dir.path().join("image.png").to_str().unwrap().to_string(),
+            1.,
+            1.,
+        );
+        film.weight_image_filename = Some(heatmap.to_str().unwrap().to_string());
+
+        // An empty film mustn't divide by zero.
+        film.write_image(1.);
+        let img = image::open(&heatmap).unwrap().to_rgb8();
+        assert!(img.pixels().all(|p| p.0 == [0, 0, 0]));
+
+        let mut left = film
+            .get_film_tile(Bounds2i::from([[0, 0], [100, 10]]))
+            .unwrap();
+        let mut right = film
+            .get_film_tile(Bounds2i::from([[100, 0], [200, 10]]))
+            .unwrap();
+        for t in [&mut left, &mut right] {
+            for pt in t.get_pixel_bounds().iter() {
+                t.get_pixel_mut(pt).filter_weight_sum = 1.;
+            }
+        }
+        film.merge_film_tile(left);
+        film.merge_film_tile(right);
+        film.write_image(1.);
+
+        let img = image::open(&heatmap).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (200, 10));
+        for x in [99, 100] {
+            assert_ne!(
+                img.get_pixel(x, 5).0,
+                [0, 0, 0],
+                "pixel ({}, 5) is black",
+                x
+            );
+        }
+    }
+
     #[test]
     fn merge_film_tile_rainbow() {
         const WIDTH: isize = 200;
@@ -561,12 +1788,227 @@ mod test {
             }
         }
 
-        let mut left = film.get_film_tile(Bounds2i::from([[0, 0], [WIDTH / 2, HEIGHT]]));
-        let mut right = film.get_film_tile(Bounds2i::from([[WIDTH / 2, 0], [WIDTH, HEIGHT]]));
+        let mut left = film
+            .get_film_tile(Bounds2i::from([[0, 0], [WIDTH / 2, HEIGHT]]))
+            .unwrap();
+        let mut right = film
+            .get_film_tile(Bounds2i::from([[WIDTH / 2, 0], [WIDTH, HEIGHT]]))
+            .unwrap();
         fill(&mut left);
         fill(&mut right);
         film.merge_film_tile(left);
         film.merge_film_tile(right);
         film.write_image(1.);
     }
+
+    #[test]
+    fn inverted_crop_window() {
+        let film = Film::new(
+            [8, 8].into(),
+            Bounds2f {
+                p_min: [0.75, 0.75].into(),
+                p_max: [0.25, 0.25].into(),
+            },
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "unused.png".to_string(),
+            1.,
+            Float::INFINITY,
+        );
+        assert_eq!(film.cropped_pixel_bounds, Bounds2i::from([[2, 2], [6, 6]]));
+
+        let film = Film::new(
+            [8, 8].into(),
+            Bounds2f {
+                p_min: [-1., 0.5].into(),
+                p_max: [2., 0.].into(),
+            },
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "unused.png".to_string(),
+            1.,
+            Float::INFINITY,
+        );
+        assert_eq!(film.cropped_pixel_bounds, Bounds2i::from([[0, 0], [8, 4]]));
+        assert!(film.cropped_pixel_bounds.area() > 0);
+    }
+
+    #[test]
+    fn film_tile_outside_crop_window() {
+        let film = Film::new(
+            [16, 16].into(),
+            [[0.5, 0.5], [1., 1.]].into(),
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "unused.png".to_string(),
+            1.,
+            Float::INFINITY,
+        );
+        assert!(film
+            .get_film_tile(Bounds2i::from([[0, 0], [4, 4]]))
+            .is_none());
+        assert!(film
+            .get_film_tile(Bounds2i::from([[0, 8], [7, 16]]))
+            .is_none());
+        // Touching the crop window is enough.
+        let tile = film
+            .get_film_tile(Bounds2i::from([[0, 0], [8, 8]]))
+            .expect("tile overlapping crop window");
+        assert_eq!(tile.get_pixel_bounds(), Bounds2i::from([[8, 8], [9, 9]]));
+    }
+
+    #[test]
+    fn update_callback() {
+        let film = Film::new(
+            [8, 8].into(),
+            [[0., 0.], [1., 1.]].into(),
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "unused.png".to_string(),
+            2.,
+            Float::INFINITY,
+        );
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let u = Arc::clone(&updates);
+        film.set_update_callback(Box::new(move |rgb, bounds| {
+            u.lock().unwrap().push((bounds, rgb.to_vec()));
+        }));
+
+        let tiles = [
+            Bounds2i::from([[0, 0], [4, 4]]),
+            Bounds2i::from([[4, 2], [8, 5]]),
+        ];
+        for tile_bounds in &tiles {
+            let mut tile = film.get_film_tile(*tile_bounds).unwrap();
+            for p in tile_bounds.iter() {
+                let p_film = Point2f::from([p.x as Float + 0.5, p.y as Float + 0.5]);
+                tile.add_sample(p_film, Spectrum::new(0.25), 1.);
+            }
+            film.merge_film_tile(tile);
+        }
+
+        // Merged regions extend past the sampled tiles by the filter's radius.
+        let want_bounds = [
+            Bounds2i::from([[0, 0], [5, 5]]),
+            Bounds2i::from([[3, 1], [8, 6]]),
+        ];
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 2);
+        for (i, (bounds, rgb)) in updates.iter().enumerate() {
+            assert_eq!(*bounds, want_bounds[i]);
+            assert_eq!(rgb.len(), 3 * bounds.area() as usize);
+            for (p, c) in bounds.iter().zip(rgb.chunks(3)) {
+                // Constant gray samples scaled by the film's scale of 2, black where no tile
+                // merged so far has samples.
+                let sampled = tiles[..=i].iter().any(|t| t.inside_exclusive(p));
+                let want = if sampled { 0.5 } else { 0. };
+                for v in c {
+                    assert!((v - want).abs() < 1e-3, "pixel {} {:?} != {}", p, c, want);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merge_nan_sample() {
+        let film = Film::new(
+            [4, 4].into(),
+            [[0., 0.], [1., 1.]].into(),
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            "unused.png".to_string(),
+            1.,
+            Float::INFINITY,
+        );
+        let bounds = Bounds2i::from([[0, 0], [4, 4]]);
+        let p = Point2i::from([1, 2]);
+        let p_film = Point2f::from([1.5, 2.5]);
+
+        let mut tile = film.get_film_tile(bounds).unwrap();
+        tile.add_sample(p_film, Spectrum::new(0.5), 1.);
+        film.merge_film_tile(tile);
+        let want = film.get_pixel_xyz(p);
+
+        let mut tile = film.get_film_tile(bounds).unwrap();
+        tile.add_sample(p_film, Spectrum::new(Float::NAN), 1.);
+        film.merge_film_tile(tile);
+        let got = film.get_pixel_xyz(p);
+        assert!(got.iter().all(|v| v.is_finite()), "{:?}", got);
+        // The bad sample contributes nothing rather than replacing the good one.
+        assert_eq!(got, want);
+        let pixels = film.lock_pixels();
+        let pixel = film.locked_pixel(&pixels, p);
+        assert!(pixel.luminance_sum.is_finite() && pixel.luminance_sq_sum.is_finite());
+    }
+
+    #[test]
+    fn max_sample_luminance() {
+        let y = |max_sample_luminance| {
+            let film = Film::new(
+                [4, 4].into(),
+                [[0., 0.], [1., 1.]].into(),
+                Box::new(BoxFilter::new([0.5, 0.5].into())),
+                35.0,
+                "unused.png".to_string(),
+                1.,
+                max_sample_luminance,
+            );
+            let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+            tile.add_sample([1.5, 2.5].into(), Spectrum::from_rgb([2e6, 1e6, 5e5]), 1.);
+            film.merge_film_tile(tile);
+            film.get_pixel_xyz([1, 2].into())[1]
+        };
+        let want = Spectrum::from_rgb([2e6, 1e6, 5e5]).y();
+        assert!(want > 1e5);
+        let got = y(10.);
+        assert!((got - 10.).abs() < 1e-4, "clamped luminance {}", got);
+        let got = y(Float::INFINITY);
+        assert!((got - want).abs() / want < 1e-6, "{} != {}", got, want);
+    }
+
+    #[test]
+    fn write_image_tonemapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let filename = dir.path().join("image.png");
+        let film = Film::new(
+            [2, 1].into(),
+            [[0., 0.], [1., 1.]].into(),
+            Box::new(BoxFilter::new([0.5, 0.5].into())),
+            35.0,
+            filename.to_str().unwrap().to_string(),
+            1.,
+            Float::INFINITY,
+        );
+        let mut tile = film.get_film_tile(film.get_sample_bounds()).unwrap();
+        tile.add_sample([0.5, 0.5].into(), Spectrum::new(4.), 1.);
+        tile.add_sample([1.5, 0.5].into(), Spectrum::new(0.25), 1.);
+        film.merge_film_tile(tile);
+
+        let write = |tm| {
+            film.write_image_tonemapped(1., tm);
+            let img = image::open(&filename).unwrap().to_rgb8();
+            (img.get_pixel(0, 0).0, img.get_pixel(1, 0).0)
+        };
+        let (bright, dark) = write(ToneMapper::Clamp);
+        assert_eq!(bright, [255, 255, 255]);
+        let (reinhard_bright, reinhard_dark) = write(ToneMapper::Reinhard);
+        assert!(reinhard_bright[1] < 255, "{:?}", reinhard_bright);
+        assert!(reinhard_dark[1] < dark[1], "{:?} {:?}", reinhard_dark, dark);
+        assert!(reinhard_dark[1] < reinhard_bright[1]);
+        let (bright, dark) = write(ToneMapper::ExposureGamma { exposure: -2. });
+        assert_eq!(bright, [255, 255, 255]);
+        assert!(dark[1] < reinhard_dark[1]);
+    }
+
+    #[test]
+    fn parse_tone_mapper() {
+        assert_eq!("clamp".parse(), Ok(ToneMapper::Clamp));
+        assert_eq!("reinhard".parse(), Ok(ToneMapper::Reinhard));
+        assert_eq!(
+            "exposure=1.5".parse(),
+            Ok(ToneMapper::ExposureGamma { exposure: 1.5 })
+        );
+        assert!("exposure=bright".parse::<ToneMapper>().is_err());
+        assert!("filmic".parse::<ToneMapper>().is_err());
+    }
 }
@@ -19,7 +19,7 @@ use crate::{
 };
 
 /// Trait `Filter` describes a sampling strategy.
-pub trait Filter {
+pub trait Filter: Send + Sync {
     /// evaluate the filter at the given point `p`.
     fn evaluate(&self, p: Point2f) -> Float;
     /// return the radius this filter was created with.
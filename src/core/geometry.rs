@@ -15,10 +15,10 @@
 //! Types and utilities for dealing with 2D and 3D, integer and float data types.
 use std::{
     fmt,
-    ops::{Add, Div, Mul, Sub},
+    ops::{Add, Div, Mul, Neg, Sub},
 };
 
-use crate::{float, Float};
+use crate::Float;
 
 mod bounds;
 pub use crate::core::geometry::bounds::{Bounds2, Bounds2f, Bounds2i, Bounds3, Bounds3f, Bounds3i};
@@ -29,8 +29,14 @@ pub use crate::core::geometry::normal::{Normal3, Normal3f};
 mod point;
 pub use crate::core::geometry::point::{Point2, Point2f, Point2i, Point3, Point3f, Point3i};
 
+mod ray;
+pub use crate::core::geometry::ray::{offset_ray_origin, Ray, RayDifferential};
+
 mod vector;
-pub use crate::core::geometry::vector::{cross, Vector2, Vector2f, Vector2i, Vector3f, Vector3i};
+pub use crate::core::geometry::vector::{
+    abs_dot, coordinate_system, cross, dot, spherical_direction, spherical_phi, spherical_theta,
+    Vector2, Vector2f, Vector2i, Vector3, Vector3f, Vector3i,
+};
 
 /// Trait for ensuring methods present on only `{float}` or `{integer}` types have appropriate
 /// implementations as necessary for this crate.
@@ -43,6 +49,7 @@ where
         + Add<Output = Self>
         + Div<Output = Self>
         + Mul<Output = Self>
+        + Neg<Output = Self>
         + Sub<Output = Self>,
 {
     /// Returns true if this value is NaN.
@@ -149,10 +156,10 @@ impl Number for Float {
         self.is_nan()
     }
     fn min_value() -> Self {
-        float::MIN
+        Float::MIN
     }
     fn max_value() -> Self {
-        float::MAX
+        Float::MAX
     }
     fn max(self, other: Self) -> Self {
         if self > other {
@@ -175,10 +182,10 @@ impl Number for isize {
         false
     }
     fn min_value() -> Self {
-        std::isize::MIN
+        isize::MIN
     }
     fn max_value() -> Self {
-        std::isize::MAX
+        isize::MAX
     }
     fn max(self, other: Self) -> Self {
         if self > other {
@@ -17,14 +17,16 @@ use std::fmt;
 
 use crate::{
     core::geometry::{
-        point::{Point2, Point2i, Point3},
-        vector::Vector2,
+        point::{Point2, Point2i, Point3, Point3f},
+        ray::Ray,
+        vector::{Vector2, Vector3, Vector3f},
         Number,
     },
     Float,
 };
 
 /// Generic type for and 2D bounding boxes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Bounds2<T>
 where
@@ -419,3 +421,229 @@ where
             && p.z < self.p_max.z
     }
 }
+
+impl<T> Bounds3<T>
+where
+    T: Number,
+{
+    /// Returns a `Bounds3` that contains no points.  The union of an empty bounds with any other
+    /// bounds or point is the other bounds or point.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Bounds3f, Point3f};
+    ///
+    /// let b = Bounds3f::union_point(&Bounds3f::empty(), Point3f::from([1., 2., 3.]));
+    /// assert_eq!(b, Bounds3f::from([[1., 2., 3.], [1., 2., 3.]]));
+    /// ```
+    pub fn empty() -> Self {
+        let min = T::min_value();
+        let max = T::max_value();
+        Bounds3 {
+            p_min: Point3::from([max, max, max]),
+            p_max: Point3::from([min, min, min]),
+        }
+    }
+
+    /// Returns the smallest bounds containing both `b1` and `b2`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Bounds3i;
+    ///
+    /// let b1 = Bounds3i::from([[0, 0, 0], [1, 1, 1]]);
+    /// let b2 = Bounds3i::from([[2, -1, 0], [3, 0, 4]]);
+    /// assert_eq!(
+    ///     Bounds3i::union(&b1, &b2),
+    ///     Bounds3i::from([[0, -1, 0], [3, 1, 4]])
+    /// );
+    /// ```
+    pub fn union(b1: &Bounds3<T>, b2: &Bounds3<T>) -> Self {
+        // Like `intersect` for `Bounds2`, assign directly so unioning with `empty()` works.
+        Bounds3 {
+            p_min: Point3::min(b1.p_min, b2.p_min),
+            p_max: Point3::max(b1.p_max, b2.p_max),
+        }
+    }
+
+    /// Returns the smallest bounds containing both `b` and `p`.
+    pub fn union_point(b: &Bounds3<T>, p: Point3<T>) -> Self {
+        Bounds3 {
+            p_min: Point3::min(b.p_min, p),
+            p_max: Point3::max(b.p_max, p),
+        }
+    }
+
+    /// `diagonal` computes the `Vector3` from `p_min` to `p_max`.
+    pub fn diagonal(&self) -> Vector3<T> {
+        self.p_max - self.p_min
+    }
+
+    /// Returns the index of the axis, 0 for x, 1 for y and 2 for z, along which the bounds are
+    /// longest.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Bounds3f;
+    ///
+    /// let b = Bounds3f::from([[0., 0., 0.], [1., 3., 2.]]);
+    /// assert_eq!(b.maximum_extent(), 1);
+    /// ```
+    pub fn maximum_extent(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl Bounds3f {
+    /// Computes the surface area of the six faces of the bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Bounds3f;
+    ///
+    /// let b = Bounds3f::from([[0., 0., 0.], [1., 2., 3.]]);
+    /// assert_eq!(b.surface_area(), 22.);
+    /// ```
+    pub fn surface_area(&self) -> Float {
+        let d = self.diagonal();
+        2. * (d.x * d.y + d.x * d.z + d.y * d.z)
+    }
+
+    /// Returns the center and radius of a sphere that bounds `self`.  Empty bounds have a zero
+    /// radius.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Bounds3f, Point3f};
+    ///
+    /// let b = Bounds3f::from([[0., 0., 0.], [2., 4., 4.]]);
+    /// assert_eq!(b.bounding_sphere(), (Point3f::from([1., 2., 2.]), 3.));
+    /// assert_eq!(Bounds3f::empty().bounding_sphere().1, 0.);
+    /// ```
+    pub fn bounding_sphere(&self) -> (Point3f, Float) {
+        let center = (self.p_min + self.p_max) / 2.;
+        let inside = (0..3).all(|i| center[i] >= self.p_min[i] && center[i] <= self.p_max[i]);
+        let radius = if inside {
+            Point3f::distance(center, self.p_max)
+        } else {
+            0.
+        };
+        (center, radius)
+    }
+
+    /// Returns the position of `p` relative to the corners of the bounds, where `p_min` is at
+    /// (0, 0, 0) and `p_max` is at (1, 1, 1).
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Bounds3f, Point3f, Vector3f};
+    ///
+    /// let b = Bounds3f::from([[0., 0., 0.], [2., 4., 8.]]);
+    /// assert_eq!(
+    ///     b.offset(Point3f::from([1., 1., 1.])),
+    ///     Vector3f::from([0.5, 0.25, 0.125])
+    /// );
+    /// ```
+    pub fn offset(&self, p: Point3f) -> Vector3f {
+        let mut o = p - self.p_min;
+        if self.p_max.x > self.p_min.x {
+            o.x /= self.p_max.x - self.p_min.x;
+        }
+        if self.p_max.y > self.p_min.y {
+            o.y /= self.p_max.y - self.p_min.y;
+        }
+        if self.p_max.z > self.p_min.z {
+            o.z /= self.p_max.z - self.p_min.z;
+        }
+        o
+    }
+
+    /// Returns the parametric range `(t0, t1)` along `ray` that is inside the bounds, or `None`
+    /// if the ray misses the bounds or only hits them beyond `ray.t_max`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Bounds3f, Point3f, Ray, Vector3f};
+    ///
+    /// let b = Bounds3f::from([[-1., -1., -1.], [1., 1., 1.]]);
+    /// let r = Ray {
+    ///     o: Point3f::from([0., 0., -5.]),
+    ///     d: Vector3f::from([0., 0., 1.]),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(b.intersect_p(&r), Some((4., 6.)));
+    ///
+    /// let r = Ray {
+    ///     o: Point3f::from([2., 0., -5.]),
+    ///     d: Vector3f::from([0., 0., 1.]),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(b.intersect_p(&r), None);
+    /// ```
+    pub fn intersect_p(&self, ray: &Ray) -> Option<(Float, Float)> {
+        let mut t0 = 0.;
+        let mut t1 = ray.t_max.get();
+        for i in 0..3 {
+            // Update interval for _i_th bounding box slab
+            let inv_ray_dir = 1. / ray.d[i];
+            let mut t_near = (self.p_min[i] - ray.o[i]) * inv_ray_dir;
+            let mut t_far = (self.p_max[i] - ray.o[i]) * inv_ray_dir;
+            if t_near > t_far {
+                std::mem::swap(&mut t_near, &mut t_far);
+            }
+            t0 = if t_near > t0 { t_near } else { t0 };
+            t1 = if t_far < t1 { t_far } else { t1 };
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+
+    /// Fast ray-box test for when the reciprocal of the ray direction, and whether each of its
+    /// components is negative, have been precomputed.  Used when traversing acceleration
+    /// structures, where the same ray is tested against many boxes.
+    pub fn intersect_p_precomputed(
+        &self,
+        ray: &Ray,
+        inv_dir: &Vector3f,
+        dir_is_neg: [bool; 3],
+    ) -> bool {
+        let corner = |neg: bool| if neg { &self.p_max } else { &self.p_min };
+        // Check for ray intersection against x and y slabs
+        let mut t_min = (corner(dir_is_neg[0]).x - ray.o.x) * inv_dir.x;
+        let mut t_max = (corner(!dir_is_neg[0]).x - ray.o.x) * inv_dir.x;
+        let ty_min = (corner(dir_is_neg[1]).y - ray.o.y) * inv_dir.y;
+        let ty_max = (corner(!dir_is_neg[1]).y - ray.o.y) * inv_dir.y;
+        if t_min > ty_max || ty_min > t_max {
+            return false;
+        }
+        if ty_min > t_min {
+            t_min = ty_min;
+        }
+        if ty_max < t_max {
+            t_max = ty_max;
+        }
+
+        // Check for ray intersection against z slab
+        let tz_min = (corner(dir_is_neg[2]).z - ray.o.z) * inv_dir.z;
+        let tz_max = (corner(!dir_is_neg[2]).z - ray.o.z) * inv_dir.z;
+        if t_min > tz_max || tz_min > t_max {
+            return false;
+        }
+        if tz_min > t_min {
+            t_min = tz_min;
+        }
+        if tz_max < t_max {
+            t_max = tz_max;
+        }
+        t_min < ray.t_max.get() && t_max > 0.
+    }
+}
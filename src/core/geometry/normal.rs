@@ -14,9 +14,15 @@
 
 //! Types and utilities for dealing with 2D and 3D, integer and float data types.
 
-use crate::{core::geometry::Number, Float};
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::{
+    core::geometry::{dot, Number, Vector3, Vector3f},
+    Float,
+};
 
 /// Generic type for any 3D normal.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Normal3<T> {
     /// The x coordinate of this normal.
@@ -51,3 +57,119 @@ where
 
 /// 3D normal type with `Float` members.
 pub type Normal3f = Normal3<Float>;
+
+impl<T> From<Vector3<T>> for Normal3<T>
+where
+    T: Number,
+{
+    fn from(v: Vector3<T>) -> Self {
+        Normal3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl<T> Neg for Normal3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Normal3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+impl<T> Add for Normal3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Normal3 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl<T> Sub for Normal3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Normal3 {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl<T> Mul<T> for Normal3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Normal3 {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Normal3f {
+    /// Compute the squared length of the `Normal3f`.
+    pub fn length_squared(&self) -> Float {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
+
+    /// Compute the length of the `Normal3f`.
+    pub fn length(&self) -> Float {
+        self.length_squared().sqrt()
+    }
+
+    /// Compute a unit length normal pointing in the same direction as `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Normal3f;
+    ///
+    /// let n: Normal3f = [0., 0., 2.].into();
+    /// assert_eq!(n.normalize(), [0., 0., 1.].into());
+    /// ```
+    pub fn normalize(&self) -> Normal3f {
+        *self * self.length().recip()
+    }
+
+    /// Flip `self` if needed so it lies in the same hemisphere as `v`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Normal3f, Vector3f};
+    ///
+    /// let n: Normal3f = [0., 0., 1.].into();
+    /// assert_eq!(n.face_forward(Vector3f::from([0., 1., -1.])), [0., 0., -1.].into());
+    /// assert_eq!(n.face_forward(Vector3f::from([0., 1., 1.])), n);
+    /// ```
+    pub fn face_forward(&self, v: Vector3f) -> Normal3f {
+        if dot(Vector3f::from(*self), v) < 0. {
+            -*self
+        } else {
+            *self
+        }
+    }
+}
@@ -15,15 +15,19 @@
 //! Types and utilities for dealing with 2D and 3D, integer and float data types.
 use std::{
     fmt,
-    ops::{Add, Div, Mul, Sub},
+    ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Sub, SubAssign},
 };
 
 use crate::{
-    core::geometry::{vector::Vector2, Number},
+    core::geometry::{
+        vector::{Vector2, Vector3},
+        Number,
+    },
     Float,
 };
 
 /// Generic type for any 2D point.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Point2<T>
 where
@@ -330,6 +334,7 @@ impl From<Point2f> for Point2i {
 }
 
 /// Generic type for any 3D point.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Point3<T> {
     /// The x coordinate of this point.
@@ -375,6 +380,67 @@ where
         let z = p1.z.max(p2.z);
         Point3 { x, y, z }
     }
+
+    /// Returns a new `Point3` whose x, y and z are taken from the coordinates of `self` at indices
+    /// `x`, `y` and `z` respectively.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Point3f;
+    ///
+    /// let p = Point3f::from([1., 2., 3.]);
+    /// assert_eq!(p.permute(2, 1, 0), Point3f::from([3., 2., 1.]));
+    /// ```
+    pub fn permute(&self, x: usize, y: usize, z: usize) -> Point3<T> {
+        Point3 {
+            x: self[x],
+            y: self[y],
+            z: self[z],
+        }
+    }
+}
+
+impl<T> Index<usize> for Point3<T> {
+    type Output = T;
+
+    /// Access the coordinates of the point by index, 0 is x, 1 is y and 2 is z.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Point3i;
+    ///
+    /// let p = Point3i::from([1, 2, 3]);
+    /// assert_eq!(p[1], 2);
+    /// ```
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index {} out of range for Point3", i),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Point3<T> {
+    /// Mutably access the coordinates of the point by index, 0 is x, 1 is y and 2 is z.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Point3i;
+    ///
+    /// let mut p = Point3i::from([1, 2, 3]);
+    /// p[1] = 5;
+    /// assert_eq!(p, Point3i::from([1, 5, 3]));
+    /// ```
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("index {} out of range for Point3", i),
+        }
+    }
 }
 
 impl<T> From<[T; 3]> for Point3<T>
@@ -432,3 +498,207 @@ impl Point3f {
 
 /// 3D point type with `isize` members.
 pub type Point3i = Point3<isize>;
+
+impl<T> Add<Vector3<T>> for Point3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    /// Offset the point by `rhs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Point3f, Vector3f};
+    ///
+    /// let p: Point3f = [1., 2., 3.].into();
+    /// let v: Vector3f = [1., 1., 1.].into();
+    /// assert_eq!(p + v, [2., 3., 4.].into());
+    /// ```
+    fn add(self, rhs: Vector3<T>) -> Self::Output {
+        Point3 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl<T> AddAssign<Vector3<T>> for Point3<T>
+where
+    T: Number,
+{
+    fn add_assign(&mut self, rhs: Vector3<T>) {
+        self.x = self.x + rhs.x;
+        self.y = self.y + rhs.y;
+        self.z = self.z + rhs.z;
+    }
+}
+
+impl<T> Add for Point3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    /// Component-wise sum of two points.  This isn't geometrically meaningful on its own, but is
+    /// used to compute weighted sums of points.
+    fn add(self, rhs: Self) -> Self::Output {
+        Point3 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl<T> Sub for Point3<T>
+where
+    T: Number,
+{
+    type Output = Vector3<T>;
+
+    /// Mathematically a point minus a point is a vector.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Point3f, Vector3f};
+    ///
+    /// let p1: Point3f = [1., 2., 3.].into();
+    /// let p2: Point3f = [4., 6., 8.].into();
+    /// assert_eq!(p2 - p1, Vector3f::from([3., 4., 5.]));
+    /// ```
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vector3 {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl<T> Sub<Vector3<T>> for Point3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Vector3<T>) -> Self::Output {
+        Point3 {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl<T> SubAssign<Vector3<T>> for Point3<T>
+where
+    T: Number,
+{
+    fn sub_assign(&mut self, rhs: Vector3<T>) {
+        self.x = self.x - rhs.x;
+        self.y = self.y - rhs.y;
+        self.z = self.z - rhs.z;
+    }
+}
+
+impl<T> Mul<T> for Point3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Point3 {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl<T> Div<T> for Point3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    fn div(self, rhs: T) -> Self::Output {
+        Point3 {
+            x: self.x / rhs,
+            y: self.y / rhs,
+            z: self.z / rhs,
+        }
+    }
+}
+
+impl<T> From<Vector3<T>> for Point3<T>
+where
+    T: Number,
+{
+    fn from(v: Vector3<T>) -> Self {
+        Point3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl<T> fmt::Display for Point3<T>
+where
+    T: Number,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[ {}, {}, {} ]", self.x, self.y, self.z)
+    }
+}
+
+impl Point3f {
+    /// Returns the distance between `p1` and `p2`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Point3f;
+    ///
+    /// let p1: Point3f = [1., 1., 1.].into();
+    /// let p2: Point3f = [1., 4., 5.].into();
+    /// assert_eq!(Point3f::distance(p1, p2), 5.);
+    /// ```
+    pub fn distance(p1: Point3f, p2: Point3f) -> Float {
+        (p1 - p2).length()
+    }
+
+    /// Returns the squared distance between `p1` and `p2`.
+    pub fn distance_squared(p1: Point3f, p2: Point3f) -> Float {
+        (p1 - p2).length_squared()
+    }
+
+    /// Linearly interpolate between `p0` and `p1`, returning `p0` when `t` is 0 and `p1` when `t`
+    /// is 1.
+    pub fn lerp(t: Float, p0: Point3f, p1: Point3f) -> Point3f {
+        p0 * (1. - t) + p1 * t
+    }
+
+    /// Returns a new point containing the absolute value of each coordinate.
+    pub fn abs(&self) -> Point3f {
+        [self.x.abs(), self.y.abs(), self.z.abs()].into()
+    }
+}
+
+impl<T> Index<usize> for Point2<T>
+where
+    T: Number,
+{
+    type Output = T;
+
+    /// Access the coordinates of the point by index, 0 is x and 1 is y.
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("index {} out of range for Point2", i),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for semi-infinite lines used to query the scene.
+use std::{cell::Cell, ops::Deref, sync::Arc};
+
+use crate::{
+    core::{
+        geometry::{dot, Normal3f, Point3f, Vector3f},
+        medium::Medium,
+    },
+    next_float_down, next_float_up, Float,
+};
+
+/// Returns the origin for a ray leaving `p` in direction `w`.  `p` is pushed along the normal `n`,
+/// to the side `w` points to, just far enough to clear the error bounds `p_error` of the point,
+/// so the new ray can't re-intersect the surface it leaves.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::geometry::{offset_ray_origin, Normal3f, Point3f, Vector3f};
+///
+/// let p = Point3f::from([1., 2., 0.]);
+/// let p_error = Vector3f::from([1e-4, 1e-4, 1e-4]);
+/// let n = Normal3f::from([0., 0., 1.]);
+/// let up = offset_ray_origin(p, p_error, n, Vector3f::from([0., 1., 1.]));
+/// assert!(up.z > 1e-4);
+/// let down = offset_ray_origin(p, p_error, n, Vector3f::from([0., 1., -1.]));
+/// assert!(down.z < -1e-4);
+/// // Without any error there's nothing to offset.
+/// assert_eq!(offset_ray_origin(p, Vector3f::default(), n, Vector3f::from([0., 0., 1.])), p);
+/// ```
+pub fn offset_ray_origin(p: Point3f, p_error: Vector3f, n: Normal3f, w: Vector3f) -> Point3f {
+    let n = Vector3f::from(n);
+    let d = dot(n.abs(), p_error);
+    let mut offset = n * d;
+    if dot(w, n) < 0. {
+        offset = -offset;
+    }
+    let mut po = p + offset;
+    // Round offset point _po_ away from _p_
+    for (po, offset) in [
+        (&mut po.x, offset.x),
+        (&mut po.y, offset.y),
+        (&mut po.z, offset.z),
+    ] {
+        if offset > 0. {
+            *po = next_float_up(*po);
+        } else if offset < 0. {
+            *po = next_float_down(*po);
+        }
+    }
+    po
+}
+
+/// A semi-infinite line starting at `o` in direction `d`.  Only the segment between `o` and
+/// `o + d * t_max` is considered for intersection tests.
+#[derive(Clone, Debug)]
+pub struct Ray {
+    /// Origin of the ray.
+    pub o: Point3f,
+    /// Direction of the ray, not necessarily normalized.
+    pub d: Vector3f,
+    /// Parametric end of the ray.  Intersection routines shorten this as closer hits are found,
+    /// which is why it is stored in a `Cell`.
+    pub t_max: Cell<Float>,
+    /// Time associated with the ray, used for animated transforms.
+    pub time: Float,
+    /// Medium containing the origin of the ray.
+    pub medium: Option<Arc<dyn Medium>>,
+}
+
+impl Default for Ray {
+    fn default() -> Ray {
+        Ray {
+            o: Default::default(),
+            d: Default::default(),
+            t_max: Cell::new(Float::INFINITY),
+            time: 0.,
+            medium: None,
+        }
+    }
+}
+
+impl Ray {
+    /// Create a new `Ray` with the given parameters.
+    pub fn new(
+        o: Point3f,
+        d: Vector3f,
+        t_max: Float,
+        time: Float,
+        medium: Option<Arc<dyn Medium>>,
+    ) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(t_max),
+            time,
+            medium,
+        }
+    }
+
+    /// Returns the point along the ray at parametric distance `t`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Point3f, Ray, Vector3f};
+    ///
+    /// let r = Ray {
+    ///     o: Point3f::from([1., 2., 3.]),
+    ///     d: Vector3f::from([0., 0., 2.]),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(r.at(1.5), Point3f::from([1., 2., 6.]));
+    /// ```
+    pub fn at(&self, t: Float) -> Point3f {
+        self.o + self.d * t
+    }
+
+    /// Returns true if any component of the ray is NaN.
+    pub fn has_nans(&self) -> bool {
+        self.o.x.is_nan()
+            || self.o.y.is_nan()
+            || self.o.z.is_nan()
+            || self.d.x.is_nan()
+            || self.d.y.is_nan()
+            || self.d.z.is_nan()
+            || self.t_max.get().is_nan()
+    }
+}
+
+/// A `Ray` with two auxiliary rays offset by one pixel in x and y on the film.  Used to estimate
+/// the footprint of a camera ray for texture filtering.
+#[derive(Clone, Debug, Default)]
+pub struct RayDifferential {
+    /// The main ray.
+    pub ray: Ray,
+    /// True if the `rx_*` and `ry_*` members are valid.
+    pub has_differentials: bool,
+    /// Origin of the ray offset in x.
+    pub rx_origin: Point3f,
+    /// Origin of the ray offset in y.
+    pub ry_origin: Point3f,
+    /// Direction of the ray offset in x.
+    pub rx_direction: Vector3f,
+    /// Direction of the ray offset in y.
+    pub ry_direction: Vector3f,
+}
+
+impl From<Ray> for RayDifferential {
+    fn from(ray: Ray) -> RayDifferential {
+        RayDifferential {
+            ray,
+            ..Default::default()
+        }
+    }
+}
+
+impl Deref for RayDifferential {
+    type Target = Ray;
+
+    fn deref(&self) -> &Ray {
+        &self.ray
+    }
+}
+
+impl RayDifferential {
+    /// Scale the offset rays for a sample spacing of `s`, so camera rays generated at more than
+    /// one sample per pixel have footprints that match the actual spacing of samples.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::{Point3f, Ray, RayDifferential, Vector3f};
+    ///
+    /// let mut rd = RayDifferential {
+    ///     ray: Ray {
+    ///         d: Vector3f::from([0., 0., 1.]),
+    ///         ..Default::default()
+    ///     },
+    ///     has_differentials: true,
+    ///     rx_origin: Point3f::from([1., 0., 0.]),
+    ///     ry_origin: Point3f::from([0., 1., 0.]),
+    ///     rx_direction: Vector3f::from([0., 0., 1.]),
+    ///     ry_direction: Vector3f::from([0., 0., 1.]),
+    /// };
+    /// rd.scale_differentials(0.5);
+    /// assert_eq!(rd.rx_origin, Point3f::from([0.5, 0., 0.]));
+    /// assert_eq!(rd.ry_origin, Point3f::from([0., 0.5, 0.]));
+    /// ```
+    pub fn scale_differentials(&mut self, s: Float) {
+        let o = self.ray.o;
+        let d = self.ray.d;
+        self.rx_origin = o + (self.rx_origin - o) * s;
+        self.ry_origin = o + (self.ry_origin - o) * s;
+        self.rx_direction = d + (self.rx_direction - d) * s;
+        self.ry_direction = d + (self.ry_direction - d) * s;
+    }
+}
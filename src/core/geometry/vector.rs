@@ -13,11 +13,16 @@
 // limitations under the License.
 
 //! Types and utilities for dealing with 2D and 3D, integer and float data types.
-use std::ops::{Div, Sub};
+use std::ops::{Add, AddAssign, Div, Index, Mul, MulAssign, Neg, Sub};
 
-use crate::{core::geometry::Number, Float};
+use crate::{
+    core::geometry::{Normal3, Number, Point3},
+    float::consts::PI,
+    Float,
+};
 
 /// Generic type for any 2D vector.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vector2<T> {
     /// The x coordinate of this vector.
@@ -82,6 +87,7 @@ pub type Vector2f = Vector2<Float>;
 pub type Vector2i = Vector2<isize>;
 
 /// Generic type for any 3D vector.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vector3<T>
 where
@@ -107,6 +113,81 @@ where
     fn has_nans(&self) -> bool {
         self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
     }
+
+    /// Returns the index of the component with the largest value.  Ties resolve to the lowest
+    /// index.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3f;
+    ///
+    /// let v = Vector3f::from([1., -5., 2.]);
+    /// assert_eq!(v.max_dimension(), 2);
+    /// assert_eq!(v.abs().max_dimension(), 1);
+    ///
+    /// assert_eq!(Vector3f::from([3., 3., 1.]).max_dimension(), 0);
+    /// assert_eq!(Vector3f::from([3., 1., 3.]).max_dimension(), 0);
+    /// assert_eq!(Vector3f::from([1., 3., 3.]).max_dimension(), 1);
+    /// assert_eq!(Vector3f::from([3., 3., 3.]).max_dimension(), 0);
+    /// ```
+    pub fn max_dimension(&self) -> usize {
+        if self.x >= self.y {
+            if self.x >= self.z {
+                0
+            } else {
+                2
+            }
+        } else if self.y >= self.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns a new `Vector3` whose x, y and z are taken from the components of `self` at
+    /// indices `x`, `y` and `z` respectively.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3i;
+    ///
+    /// let v = Vector3i::from([1, 2, 3]);
+    /// assert_eq!(v.permute(2, 1, 0), Vector3i::from([3, 2, 1]));
+    /// assert_eq!(v.permute(1, 2, 0), Vector3i::from([2, 3, 1]));
+    /// ```
+    pub fn permute(&self, x: usize, y: usize, z: usize) -> Vector3<T> {
+        Vector3 {
+            x: self[x],
+            y: self[y],
+            z: self[z],
+        }
+    }
+}
+
+impl<T> Index<usize> for Vector3<T>
+where
+    T: Number,
+{
+    type Output = T;
+
+    /// Access the components of the vector by index, 0 is x, 1 is y and 2 is z.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3f;
+    ///
+    /// let v = Vector3f::from([1., 2., 3.]);
+    /// assert_eq!(v[0], 1.);
+    /// assert_eq!(v[2], 3.);
+    /// ```
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index {} out of range for Vector3", i),
+        }
+    }
 }
 
 impl<T> From<[T; 3]> for Vector3<T>
@@ -147,6 +228,23 @@ pub type Vector3f = Vector3<Float>;
 
 // TODO(wathiede): Make this generic over float vs int.
 impl Vector3f {
+    /// Returns a new `Vector3f` containing the absolute value of each component.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3f;
+    ///
+    /// let v = Vector3f::from([1., -5., 2.]);
+    /// assert_eq!(v.abs(), Vector3f::from([1., 5., 2.]));
+    /// ```
+    pub fn abs(&self) -> Vector3f {
+        Vector3 {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs(),
+        }
+    }
+
     /// Compute a unit vector form self.
     ///
     /// # Examples
@@ -207,7 +305,7 @@ impl Vector3f {
 }
 
 // TODO(wathiede): Make this generic over float vs int.
-impl<'a> Div<Float> for &'a Vector3f {
+impl Div<Float> for &Vector3f {
     type Output = Vector3f;
 
     fn div(self, rhs: Float) -> Vector3f {
@@ -297,7 +395,7 @@ impl Vector3i {
 }
 
 // TODO(wathiede): Make this generic over float vs int.
-impl<'a> Div<Float> for &'a Vector3i {
+impl Div<Float> for &Vector3i {
     type Output = Vector3i;
 
     fn div(self, rhs: Float) -> Vector3i {
@@ -322,3 +420,258 @@ where
     ]
     .into()
 }
+
+impl<T> Add for Vector3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    /// Implement `+` for Vector3<T> + Vector3<T>
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3f;
+    ///
+    /// let v1: Vector3f = [1., 2., 3.].into();
+    /// let v2: Vector3f = [4., 5., 6.].into();
+    /// assert_eq!(v1 + v2, [5., 7., 9.].into());
+    /// ```
+    fn add(self, rhs: Self) -> Self::Output {
+        Vector3 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl<T> AddAssign for Vector3<T>
+where
+    T: Number,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        self.x = self.x + rhs.x;
+        self.y = self.y + rhs.y;
+        self.z = self.z + rhs.z;
+    }
+}
+
+impl<T> Mul<T> for Vector3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    /// Scale each component of the vector by `rhs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3f;
+    ///
+    /// let v: Vector3f = [1., 2., 3.].into();
+    /// assert_eq!(v * 2., [2., 4., 6.].into());
+    /// ```
+    fn mul(self, rhs: T) -> Self::Output {
+        Vector3 {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Mul<Vector3f> for Float {
+    type Output = Vector3f;
+
+    fn mul(self, rhs: Vector3f) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl<T> MulAssign<T> for Vector3<T>
+where
+    T: Number,
+{
+    fn mul_assign(&mut self, rhs: T) {
+        self.x = self.x * rhs;
+        self.y = self.y * rhs;
+        self.z = self.z * rhs;
+    }
+}
+
+impl Div<Float> for Vector3f {
+    type Output = Vector3f;
+
+    fn div(self, rhs: Float) -> Vector3f {
+        Div::div(&self, rhs)
+    }
+}
+
+impl<T> Neg for Vector3<T>
+where
+    T: Number,
+{
+    type Output = Self;
+
+    /// Flip the direction of the vector.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::geometry::Vector3i;
+    ///
+    /// let v: Vector3i = [1, -2, 3].into();
+    /// assert_eq!(-v, [-1, 2, -3].into());
+    /// ```
+    fn neg(self) -> Self::Output {
+        Vector3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+impl<T> From<Point3<T>> for Vector3<T>
+where
+    T: Number,
+{
+    fn from(p: Point3<T>) -> Self {
+        Vector3 {
+            x: p.x,
+            y: p.y,
+            z: p.z,
+        }
+    }
+}
+
+impl<T> From<Normal3<T>> for Vector3<T>
+where
+    T: Number,
+{
+    fn from(n: Normal3<T>) -> Self {
+        Vector3 {
+            x: n.x,
+            y: n.y,
+            z: n.z,
+        }
+    }
+}
+
+impl Vector3f {
+    /// Returns the value of the smallest component.
+    pub fn min_component(&self) -> Float {
+        self.x.min(self.y.min(self.z))
+    }
+
+    /// Returns the value of the largest component.
+    pub fn max_component(&self) -> Float {
+        self.x.max(self.y.max(self.z))
+    }
+}
+
+/// Compute the dot product of two 3D vectors.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::geometry::{dot, Vector3f};
+///
+/// let v1: Vector3f = [1., 2., 3.].into();
+/// let v2: Vector3f = [4., -5., 6.].into();
+/// assert_eq!(dot(v1, v2), 12.);
+/// ```
+pub fn dot<T>(v1: Vector3<T>, v2: Vector3<T>) -> T
+where
+    T: Number,
+{
+    v1.x * v2.x + v1.y * v2.y + v1.z * v2.z
+}
+
+/// Compute the absolute value of the dot product of two 3D vectors.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::geometry::{abs_dot, Vector3f};
+///
+/// let v1: Vector3f = [1., 0., 0.].into();
+/// let v2: Vector3f = [-2., 1., 0.].into();
+/// assert_eq!(abs_dot(v1, v2), 2.);
+/// ```
+pub fn abs_dot(v1: Vector3f, v2: Vector3f) -> Float {
+    dot(v1, v2).abs()
+}
+
+/// Compute the vector pointing in the direction of `theta` and `phi` in the coordinate frame
+/// formed by `x`, `y` and `z`.
+pub fn spherical_direction(
+    sin_theta: Float,
+    cos_theta: Float,
+    phi: Float,
+    x: Vector3f,
+    y: Vector3f,
+    z: Vector3f,
+) -> Vector3f {
+    x * (sin_theta * phi.cos()) + y * (sin_theta * phi.sin()) + z * cos_theta
+}
+
+/// Returns the polar angle, in [0, π], of the unit vector `v`.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::geometry::{spherical_theta, Vector3f},
+///     float::consts::FRAC_PI_2,
+/// };
+///
+/// assert_eq!(spherical_theta(Vector3f::from([0., 0., 1.])), 0.);
+/// assert_eq!(spherical_theta(Vector3f::from([1., 0., 0.])), FRAC_PI_2);
+/// ```
+pub fn spherical_theta(v: Vector3f) -> Float {
+    v.z.clamp(-1., 1.).acos()
+}
+
+/// Returns the azimuthal angle, in [0, 2π), of the vector `v`.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::geometry::{spherical_phi, Vector3f},
+///     float::consts::{FRAC_PI_2, PI},
+/// };
+///
+/// assert_eq!(spherical_phi(Vector3f::from([0., 1., 0.])), FRAC_PI_2);
+/// assert_eq!(spherical_phi(Vector3f::from([0., -1., 0.])), 3. * FRAC_PI_2);
+/// assert_eq!(spherical_phi(Vector3f::from([-1., 0., 0.])), PI);
+/// ```
+pub fn spherical_phi(v: Vector3f) -> Float {
+    let p = v.y.atan2(v.x);
+    if p < 0. {
+        p + 2. * PI
+    } else {
+        p
+    }
+}
+
+/// Compute two unit vectors that, together with the unit vector `v1`, form an orthonormal
+/// coordinate system.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::geometry::{coordinate_system, dot, Vector3f};
+///
+/// let v1 = Vector3f::from([1., -2., 3.]).normalize();
+/// let (v2, v3) = coordinate_system(v1);
+/// assert!(dot(v1, v2).abs() < 1e-6);
+/// assert!(dot(v1, v3).abs() < 1e-6);
+/// assert!(dot(v2, v3).abs() < 1e-6);
+/// assert!((v2.length() - 1.).abs() < 1e-6);
+/// assert!((v3.length() - 1.).abs() < 1e-6);
+/// ```
+pub fn coordinate_system(v1: Vector3f) -> (Vector3f, Vector3f) {
+    let v2 = if v1.x.abs() > v1.y.abs() {
+        Vector3f::from([-v1.z, 0., v1.x]) / (v1.x * v1.x + v1.z * v1.z).sqrt()
+    } else {
+        Vector3f::from([0., v1.z, -v1.y]) / (v1.y * v1.y + v1.z * v1.z).sqrt()
+    };
+    (v2, cross(v1, v2))
+}
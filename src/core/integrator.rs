@@ -0,0 +1,1039 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Integrators compute the radiance arriving at the film and drive the rendering of an image.
+
+use std::{fmt::Debug, ptr, sync::Arc};
+
+use log::{error, info};
+
+use crate::{
+    core::{
+        camera::Camera,
+        film::{Film, FilmTile},
+        geometry::{abs_dot, Bounds2i, Point2f, Point2i, RayDifferential, Vector3f},
+        interaction::{Interaction, MediumInteraction, SurfaceInteraction},
+        light::{is_delta_light, AreaLight, Light},
+        paramset::ParamSet,
+        reflection::BxDFType,
+        sampler::Sampler,
+        sampling::{power_heuristic, Distribution1D},
+        scene::Scene,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+/// Size, in pixels, of the square tiles the image is split into for rendering.
+const TILE_SIZE: usize = 16;
+
+/// Integrator is the interface for all rendering algorithms.
+pub trait Integrator: Debug {
+    /// Called once before rendering starts, after the scene has been constructed.  `sampler` is
+    /// the prototype all per-tile samplers are cloned from, so integrators may configure it here.
+    fn preprocess(&mut self, _scene: &Scene, _sampler: &mut dyn Sampler) {}
+
+    /// Renders an image of `scene`.
+    fn render(&mut self, scene: &Scene);
+}
+
+/// SamplerIntegratorData holds the state common to all [SamplerIntegrator]s.
+#[derive(Debug)]
+pub struct SamplerIntegratorData {
+    /// Camera used to generate rays and that holds the `Film` rendered to.
+    pub camera: Arc<dyn Camera>,
+    /// Prototype sampler, cloned for each tile rendered.
+    pub sampler: Box<dyn Sampler>,
+    /// Subset of the film's pixels to render.
+    pub pixel_bounds: Bounds2i,
+    /// Upper bound on the number of extra samples each pixel may receive in the adaptive pass.
+    /// Zero disables adaptive sampling.
+    pub max_adaptive_samples: usize,
+    /// Pixels whose estimated variance is at or below this threshold receive no extra samples in
+    /// the adaptive pass.
+    pub variance_threshold: Float,
+}
+
+impl SamplerIntegratorData {
+    /// Create `SamplerIntegratorData`, reading the settings common to all sampler integrators
+    /// from `params`: "pixelbounds", "maxadaptivesamples" and "variancethreshold".
+    pub fn new(
+        camera: Arc<dyn Camera>,
+        sampler: Box<dyn Sampler>,
+        params: &ParamSet,
+    ) -> SamplerIntegratorData {
+        let film = camera.film();
+        let mut pixel_bounds = film.get_sample_bounds();
+        if let Some(pb) = params.find_int("pixelbounds") {
+            if pb.len() != 4 {
+                error!(
+                    "Expected four values for \"pixelbounds\" parameter. Got {}.",
+                    pb.len()
+                );
+            } else {
+                pixel_bounds = Bounds2i::intersect(
+                    &pixel_bounds,
+                    &Bounds2i::from([[pb[0], pb[2]], [pb[1], pb[3]]]),
+                );
+                if pixel_bounds.area() == 0 {
+                    error!("Degenerate \"pixelbounds\" specified.");
+                }
+            }
+        }
+        let max_adaptive_samples = params.find_one_int("maxadaptivesamples", 0).max(0) as usize;
+        let variance_threshold = params.find_one_float("variancethreshold", 0.01);
+        SamplerIntegratorData {
+            camera,
+            sampler,
+            pixel_bounds,
+            max_adaptive_samples,
+            variance_threshold,
+        }
+    }
+}
+
+/// SamplerIntegrator is an [Integrator] whose rendering is driven by a stream of samples from a
+/// [Sampler], each of which generates a camera ray whose incident radiance is computed by [li].
+///
+/// [li]: SamplerIntegrator::li
+pub trait SamplerIntegrator: Integrator + Send + Sync {
+    /// Returns the state shared by all sampler integrators.
+    fn data(&self) -> &SamplerIntegratorData;
+
+    /// Computes the radiance arriving at the origin of `ray`.  `depth` is the number of bounces
+    /// the ray has made since it left the camera.
+    fn li(
+        &self,
+        ray: &RayDifferential,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+    ) -> Spectrum;
+
+    /// Renders `scene` tile by tile, calling [li] for every camera sample, then writes the image
+    /// to disk.  Implementations of [Integrator::render] for sampler integrators delegate here.
+    ///
+    /// If "maxadaptivesamples" is non-zero, a second pass gives extra samples to the pixels whose
+    /// estimated variance after the first pass is above "variancethreshold".
+    ///
+    /// [li]: SamplerIntegrator::li
+    fn render_samples(&mut self, scene: &Scene)
+    where
+        Self: Sized,
+    {
+        let mut sampler = self.data().sampler.clone_sampler(0);
+        self.preprocess(scene, sampler.as_mut());
+        let data = self.data();
+        let film = data.camera.film();
+        let sample_bounds = film.get_sample_bounds();
+        let sample_extent = sample_bounds.diagonal();
+        let n_tiles_x = (sample_extent.x as usize).div_ceil(TILE_SIZE);
+        let n_tiles = n_tiles_x * (sample_extent.y as usize).div_ceil(TILE_SIZE);
+        // Each tile gets a deterministic seed from its position, so renders are reproducible
+        // regardless of the order tiles are processed in.
+        let tile_seed = |tile_bounds: Bounds2i| {
+            let x = (tile_bounds.p_min.x - sample_bounds.p_min.x) as usize / TILE_SIZE;
+            let y = (tile_bounds.p_min.y - sample_bounds.p_min.y) as usize / TILE_SIZE;
+            y * n_tiles_x + x
+        };
+        info!("Rendering {} tiles", n_tiles);
+
+        film.parallel_for_tiles(TILE_SIZE, |tile_bounds| {
+            let mut tile_sampler = sampler.clone_sampler(tile_seed(tile_bounds));
+            let mut film_tile = match film.get_film_tile(tile_bounds) {
+                Some(film_tile) => film_tile,
+                None => return,
+            };
+            for pixel in tile_bounds.iter() {
+                tile_sampler.start_pixel(pixel);
+                if !data.pixel_bounds.inside_exclusive(pixel) {
+                    continue;
+                }
+                loop {
+                    self.render_sample(scene, tile_sampler.as_mut(), &mut film_tile, pixel);
+                    if !tile_sampler.start_next_sample() {
+                        break;
+                    }
+                }
+            }
+            film.merge_film_tile(film_tile);
+        });
+
+        if data.max_adaptive_samples > 0 {
+            self.render_adaptive_pass(scene, sampler.as_ref(), &film, n_tiles, tile_seed);
+        }
+        info!("Rendering finished");
+
+        film.write_image(1.);
+    }
+
+    /// Gives extra samples to the pixels of `film` that haven't converged after the first pass.
+    /// Not meant to be called directly, see [render_samples].
+    ///
+    /// [render_samples]: SamplerIntegrator::render_samples
+    fn render_adaptive_pass<F>(
+        &self,
+        scene: &Scene,
+        sampler: &dyn Sampler,
+        film: &Film,
+        n_tiles: usize,
+        tile_seed: F,
+    ) where
+        Self: Sized,
+        F: Fn(Bounds2i) -> usize + Sync + Send,
+    {
+        let data = self.data();
+        let budget = film.to_sample_budget_map(data.variance_threshold);
+        let cropped = film.cropped_pixel_bounds;
+        let width = (cropped.p_max.x - cropped.p_min.x) as usize;
+        info!(
+            "Adaptive pass adding up to {} samples to {} pixels",
+            data.max_adaptive_samples,
+            budget.iter().filter(|&&n| n > 0).count()
+        );
+        let spp = sampler.samples_per_pixel().max(1);
+        film.parallel_for_tiles(TILE_SIZE, |tile_bounds| {
+            // A pixel needing more than `spp` extra samples exhausts its sampler, so each round of
+            // `spp` samples gets its own sampler.  Offset the seeds by the round so neither the
+            // first pass nor earlier rounds' sample values are replayed, e.g. by samplers that
+            // seed each pixel from its position.
+            let seed = tile_seed(tile_bounds);
+            let mut round_samplers: Vec<Box<dyn Sampler>> = Vec::new();
+            let mut film_tile = match film.get_film_tile(tile_bounds) {
+                Some(film_tile) => film_tile,
+                None => return,
+            };
+            for pixel in tile_bounds.iter() {
+                if !data.pixel_bounds.inside_exclusive(pixel) || !cropped.inside_exclusive(pixel) {
+                    continue;
+                }
+                let offset = (pixel.y - cropped.p_min.y) as usize * width
+                    + (pixel.x - cropped.p_min.x) as usize;
+                let extra = budget[offset].min(data.max_adaptive_samples);
+                if extra == 0 {
+                    continue;
+                }
+                for round in 0..extra.div_ceil(spp) {
+                    if round_samplers.len() == round {
+                        round_samplers.push(sampler.clone_sampler((round + 1) * n_tiles + seed));
+                    }
+                    let tile_sampler = round_samplers[round].as_mut();
+                    tile_sampler.start_pixel(pixel);
+                    for i in 0..(extra - round * spp).min(spp) {
+                        if i > 0 {
+                            tile_sampler.start_next_sample();
+                        }
+                        self.render_sample(scene, tile_sampler, &mut film_tile, pixel);
+                    }
+                }
+            }
+            film.merge_film_tile(film_tile);
+        });
+    }
+
+    /// Traces a single camera sample for `pixel` and adds its radiance to `film_tile`.
+    fn render_sample(
+        &self,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        film_tile: &mut FilmTile,
+        pixel: Point2i,
+    ) {
+        let data = self.data();
+        // Initialize _CameraSample_ for current sample
+        let camera_sample = sampler.get_camera_sample(pixel);
+
+        // Generate camera ray for current sample
+        let (ray_weight, mut ray) = data.camera.generate_ray_differential(&camera_sample);
+        ray.scale_differentials(1. / (sampler.samples_per_pixel() as Float).sqrt());
+
+        // Evaluate radiance along camera ray
+        let mut l = if ray_weight > 0. {
+            self.li(&ray, scene, sampler, 0)
+        } else {
+            Spectrum::new(0.)
+        };
+
+        // Issue warning if unexpected radiance value returned
+        let y = l.y();
+        if l.has_nans() {
+            error!(
+                "Not-a-number radiance value returned for pixel ({}, {}). Setting to black.",
+                pixel.x, pixel.y
+            );
+            l = Spectrum::new(0.);
+        } else if y < -1e-5 {
+            error!(
+                "Negative luminance value, {}, returned for pixel ({}, {}). Setting to black.",
+                y, pixel.x, pixel.y
+            );
+            l = Spectrum::new(0.);
+        } else if y.is_infinite() {
+            error!(
+                "Infinite luminance value returned for pixel ({}, {}). Setting to black.",
+                pixel.x, pixel.y
+            );
+            l = Spectrum::new(0.);
+        }
+
+        // Add camera ray's contribution to image
+        film_tile.add_sample(camera_sample.p_film, l, ray_weight);
+    }
+
+    /// Returns the radiance arriving at `isect` via perfect specular reflection.
+    fn specular_reflect(
+        &self,
+        ray: &RayDifferential,
+        isect: &SurfaceInteraction,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+    ) -> Spectrum {
+        self.specular_scatter(
+            ray,
+            isect,
+            scene,
+            sampler,
+            depth,
+            BxDFType::REFLECTION | BxDFType::SPECULAR,
+        )
+    }
+
+    /// Returns the radiance arriving at `isect` via perfect specular transmission.
+    fn specular_transmit(
+        &self,
+        ray: &RayDifferential,
+        isect: &SurfaceInteraction,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+    ) -> Spectrum {
+        self.specular_scatter(
+            ray,
+            isect,
+            scene,
+            sampler,
+            depth,
+            BxDFType::TRANSMISSION | BxDFType::SPECULAR,
+        )
+    }
+
+    /// Shared implementation of [specular_reflect] and [specular_transmit], samples the BSDF
+    /// lobes of `bxdf_type` and recursively traces the scattered ray.
+    ///
+    /// [specular_reflect]: SamplerIntegrator::specular_reflect
+    /// [specular_transmit]: SamplerIntegrator::specular_transmit
+    fn specular_scatter(
+        &self,
+        _ray: &RayDifferential,
+        isect: &SurfaceInteraction,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+        bxdf_type: BxDFType,
+    ) -> Spectrum {
+        let bsdf = match &isect.bsdf {
+            Some(bsdf) => bsdf,
+            None => return Spectrum::new(0.),
+        };
+        // Compute specular reflection direction _wi_ and BSDF value
+        let s = bsdf.sample_f(&isect.wo, sampler.get_2d(), bxdf_type);
+        let ns = Vector3f::from(isect.shading.n);
+        let cos = abs_dot(s.wi, ns);
+        if s.pdf > 0. && !s.f.is_black() && cos != 0. {
+            // TODO(wathiede): compute ray differentials for the specular ray.
+            let rd = RayDifferential::from(isect.spawn_ray(&s.wi));
+            s.f * self.li(&rd, scene, sampler, depth + 1) * (cos / s.pdf)
+        } else {
+            Spectrum::new(0.)
+        }
+    }
+}
+
+/// ScatteringPoint is a path vertex direct lighting is estimated at, either a point on a surface
+/// whose BSDF has been computed or a point in a participating medium.
+#[derive(Clone, Copy, Debug)]
+pub enum ScatteringPoint<'a> {
+    /// Light scatters according to the surface's BSDF.
+    Surface(&'a SurfaceInteraction),
+    /// Light scatters according to the medium's phase function.
+    Medium(&'a MediumInteraction),
+}
+
+impl<'a> From<&'a SurfaceInteraction> for ScatteringPoint<'a> {
+    fn from(isect: &'a SurfaceInteraction) -> ScatteringPoint<'a> {
+        ScatteringPoint::Surface(isect)
+    }
+}
+
+impl<'a> From<&'a MediumInteraction> for ScatteringPoint<'a> {
+    fn from(mi: &'a MediumInteraction) -> ScatteringPoint<'a> {
+        ScatteringPoint::Medium(mi)
+    }
+}
+
+impl ScatteringPoint<'_> {
+    /// Returns the interaction at the scattering point.
+    pub fn interaction(&self) -> &Interaction {
+        match self {
+            ScatteringPoint::Surface(isect) => isect,
+            ScatteringPoint::Medium(mi) => mi,
+        }
+    }
+}
+
+/// Computes the direct lighting at `it` by taking `n_light_samples[i]` samples from each light
+/// `scene.lights[i]` and summing the contributions.  The samples come from two 2D sample arrays
+/// per light, requested before rendering, when the sampler has them.  If `handle_media` is true,
+/// light is attenuated by the participating media between the light and `it`.
+pub fn uniform_sample_all_lights<'a>(
+    it: impl Into<ScatteringPoint<'a>>,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    n_light_samples: &[usize],
+    handle_media: bool,
+) -> Spectrum {
+    let it = it.into();
+    let mut l = Spectrum::new(0.);
+    for (light, &n_samples) in scene.lights.iter().zip(n_light_samples) {
+        // Accumulate contribution of _j_th light to _L_
+        let u_light_array = sampler.get_2d_array(n_samples).map(<[_]>::to_vec);
+        let u_scattering_array = sampler.get_2d_array(n_samples).map(<[_]>::to_vec);
+        let mut ld = Spectrum::new(0.);
+        for k in 0..n_samples {
+            // Without requested arrays, e.g. beyond the depth they were requested for, draw the
+            // samples one at a time.
+            let (u_light, u_scattering) = match (&u_light_array, &u_scattering_array) {
+                (Some(u_light), Some(u_scattering)) => (u_light[k], u_scattering[k]),
+                _ => (sampler.get_2d(), sampler.get_2d()),
+            };
+            ld += estimate_direct(
+                it,
+                u_scattering,
+                light.as_ref(),
+                u_light,
+                scene,
+                sampler,
+                handle_media,
+                false,
+            );
+        }
+        l += ld / n_samples.max(1) as Float;
+    }
+    l
+}
+
+/// Computes the direct lighting at `it` by taking a single sample from one randomly chosen light
+/// in `scene`.  Lights are chosen according to `light_distrib` if given, otherwise uniformly, and
+/// the result is divided by the probability of the chosen light to account for the lights not
+/// sampled.  If `handle_media` is true, light is attenuated by the participating media between
+/// the light and `it`.
+pub fn uniform_sample_one_light<'a>(
+    it: impl Into<ScatteringPoint<'a>>,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    handle_media: bool,
+    light_distrib: Option<&Distribution1D>,
+) -> Spectrum {
+    // Randomly choose a single light to sample, _light_
+    let n_lights = scene.lights.len();
+    if n_lights == 0 {
+        return Spectrum::new(0.);
+    }
+    let (light_num, light_pdf) = match light_distrib {
+        Some(distrib) => distrib.sample_discrete(sampler.get_1d()),
+        None => (
+            ((sampler.get_1d() * n_lights as Float) as usize).min(n_lights - 1),
+            1. / n_lights as Float,
+        ),
+    };
+    if light_pdf == 0. {
+        return Spectrum::new(0.);
+    }
+    let light = &scene.lights[light_num];
+    let u_light = sampler.get_2d();
+    let u_scattering = sampler.get_2d();
+    estimate_direct(
+        it.into(),
+        u_scattering,
+        light.as_ref(),
+        u_light,
+        scene,
+        sampler,
+        handle_media,
+        false,
+    ) / light_pdf
+}
+
+/// Returns true if `area` and `light` refer to the same light object.
+fn is_same_light(area: &dyn AreaLight, light: &dyn Light) -> bool {
+    ptr::eq(
+        area as *const dyn AreaLight as *const (),
+        light as *const dyn Light as *const (),
+    )
+}
+
+/// Estimates the direct lighting at `it` from `light` using multiple importance sampling.  One
+/// sample is taken from the light with `u_light` and one from the BSDF, or phase function, with
+/// `u_scattering`, and the two are combined with the power heuristic.  Specular BSDF lobes are
+/// only included if `specular` is true.  If `handle_media` is true, light is attenuated by the
+/// participating media it passes through, with `sampler` used to estimate the transmittance.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_direct(
+    it: ScatteringPoint,
+    u_scattering: Point2f,
+    light: &dyn Light,
+    u_light: Point2f,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    handle_media: bool,
+    specular: bool,
+) -> Spectrum {
+    let bsdf_flags = if specular {
+        BxDFType::ALL
+    } else {
+        BxDFType::ALL & !BxDFType::SPECULAR
+    };
+    let intr = it.interaction();
+    let delta_light = is_delta_light(light.flags());
+    let mut ld = Spectrum::new(0.);
+
+    // Sample light source with multiple importance sampling
+    let ls = light.sample_li(intr, u_light);
+    if ls.pdf > 0. && !ls.li.is_black() {
+        // Compute BSDF or phase function's value for light sample
+        let (f, scattering_pdf) = match it {
+            ScatteringPoint::Surface(isect) => match &isect.bsdf {
+                Some(bsdf) => (
+                    bsdf.f(&isect.wo, &ls.wi, bsdf_flags)
+                        * abs_dot(ls.wi, Vector3f::from(isect.shading.n)),
+                    bsdf.pdf(&isect.wo, &ls.wi, bsdf_flags),
+                ),
+                None => return ld,
+            },
+            ScatteringPoint::Medium(mi) => {
+                let p = mi.phase.p(&mi.wo, &ls.wi);
+                (Spectrum::new(p), p)
+            }
+        };
+        if !f.is_black() {
+            // Compute effect of visibility for light source sample
+            let li = if handle_media {
+                ls.li * ls.vis.tr(scene, sampler)
+            } else if ls.vis.unoccluded(scene) {
+                ls.li
+            } else {
+                Spectrum::new(0.)
+            };
+
+            // Add light's contribution to reflected radiance
+            if !li.is_black() {
+                if delta_light {
+                    ld += f * li / ls.pdf;
+                } else {
+                    let weight = power_heuristic(1, ls.pdf, 1, scattering_pdf);
+                    ld += f * li * (weight / ls.pdf);
+                }
+            }
+        }
+    }
+
+    // Sample BSDF with multiple importance sampling
+    if !delta_light {
+        let (f, wi, scattering_pdf, sampled_specular) = match it {
+            ScatteringPoint::Surface(isect) => match &isect.bsdf {
+                Some(bsdf) => {
+                    let bs = bsdf.sample_f(&isect.wo, u_scattering, bsdf_flags);
+                    (
+                        bs.f * abs_dot(bs.wi, Vector3f::from(isect.shading.n)),
+                        bs.wi,
+                        bs.pdf,
+                        bs.sampled_type.contains(BxDFType::SPECULAR),
+                    )
+                }
+                None => return ld,
+            },
+            ScatteringPoint::Medium(mi) => {
+                let (p, wi) = mi.phase.sample_p(&mi.wo, u_scattering);
+                (Spectrum::new(p), wi, p, false)
+            }
+        };
+        if !f.is_black() && scattering_pdf > 0. {
+            let mut weight = 1.;
+            if !sampled_specular {
+                let light_pdf = light.pdf_li(intr, &wi);
+                if light_pdf == 0. {
+                    return ld;
+                }
+                weight = power_heuristic(1, scattering_pdf, 1, light_pdf);
+            }
+
+            // Find intersection and compute transmittance
+            let ray = RayDifferential::from(intr.spawn_ray(&wi));
+            let (light_isect, tr) = if handle_media {
+                scene.intersect_tr(&ray, sampler)
+            } else {
+                (scene.intersect(&ray), Spectrum::new(1.))
+            };
+
+            // Add light contribution from the BSDF sample
+            let li = match light_isect {
+                // Only count emission if the ray hit the light being sampled.
+                Some(light_isect) => match &light_isect.area_light {
+                    Some(area) if is_same_light(area.as_ref(), light) => light_isect.le(&-wi),
+                    _ => Spectrum::new(0.),
+                },
+                None => light.le(&ray),
+            };
+            if !li.is_black() {
+                ld += f * li * tr * (weight / scattering_pdf);
+            }
+        }
+    }
+    ld
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        cameras::orthographic::OrthographicCamera,
+        core::{
+            geometry::{Bounds2f, Point3f, Ray, Vector2f},
+            material::TransportMode,
+            paramset::testutils::make_int_param_set,
+            primitive::{GeometricPrimitive, Primitive},
+            rng::Rng,
+            transform::Transform,
+        },
+        filters::r#box::BoxFilter,
+        float::consts::PI,
+        lights::{diffuse::DiffuseAreaLight, point::PointLight},
+        materials::matte::MatteMaterial,
+        samplers::{random::RandomSampler, stratified::StratifiedSampler},
+        shapes::sphere::Sphere,
+        textures::constant::ConstantTexture,
+    };
+
+    /// Returns the same radiance for every ray, and counts calls to `preprocess`.
+    #[derive(Debug)]
+    struct ConstantIntegrator {
+        data: SamplerIntegratorData,
+        preprocessed: usize,
+    }
+
+    impl Integrator for ConstantIntegrator {
+        fn preprocess(&mut self, _scene: &Scene, _sampler: &mut dyn Sampler) {
+            self.preprocessed += 1;
+        }
+
+        fn render(&mut self, scene: &Scene) {
+            self.render_samples(scene);
+        }
+    }
+
+    impl SamplerIntegrator for ConstantIntegrator {
+        fn data(&self) -> &SamplerIntegratorData {
+            &self.data
+        }
+
+        fn li(
+            &self,
+            _ray: &RayDifferential,
+            _scene: &Scene,
+            _sampler: &mut dyn Sampler,
+            _depth: usize,
+        ) -> Spectrum {
+            Spectrum::new(0.5)
+        }
+    }
+
+    /// Returns a film of the given `resolution`.  An empty filename keeps the image from being
+    /// written.
+    fn test_film(resolution: [isize; 2]) -> Arc<Film> {
+        Arc::new(Film::new(
+            Point2i::from(resolution),
+            Bounds2f::from([[0., 0.], [1., 1.]]),
+            Box::new(BoxFilter::new(Vector2f::from([0.5, 0.5]))),
+            35.,
+            String::new(),
+            1.,
+            Float::INFINITY,
+        ))
+    }
+
+    /// Returns an orthographic camera looking down +z that records to `film`.
+    fn test_camera(film: &Arc<Film>) -> Arc<dyn Camera> {
+        Arc::new(OrthographicCamera::new(
+            Transform::identity().into(),
+            Bounds2f::from([[-1., -1.], [1., 1.]]),
+            0.,
+            1.,
+            0.,
+            1e6,
+            Arc::clone(film),
+            None,
+        ))
+    }
+
+    fn empty_scene() -> Scene {
+        Scene::new(
+            Arc::new(BVHAccel::new(Vec::new(), 1, SplitMethod::Sah)),
+            Vec::new(),
+        )
+    }
+
+    /// Returns the next 1D sample value as its radiance, and records every value returned.
+    #[derive(Debug)]
+    struct SampleValueIntegrator {
+        data: SamplerIntegratorData,
+        values: Mutex<Vec<Float>>,
+    }
+
+    impl Integrator for SampleValueIntegrator {
+        fn render(&mut self, scene: &Scene) {
+            self.render_samples(scene);
+        }
+    }
+
+    impl SamplerIntegrator for SampleValueIntegrator {
+        fn data(&self) -> &SamplerIntegratorData {
+            &self.data
+        }
+
+        fn li(
+            &self,
+            _ray: &RayDifferential,
+            _scene: &Scene,
+            sampler: &mut dyn Sampler,
+            _depth: usize,
+        ) -> Spectrum {
+            let v = sampler.get_1d();
+            self.values.lock().unwrap().push(v);
+            Spectrum::new(v)
+        }
+    }
+
+    #[test]
+    fn render_samples_calls_li_for_pixel_bounds() {
+        let film = test_film([40, 20]);
+        let camera = test_camera(&film);
+        let sampler = Box::new(StratifiedSampler::new(2, 2, true, 4));
+        let params = make_int_param_set("pixelbounds", vec![10, 30, 5, 15]);
+        let mut integrator = ConstantIntegrator {
+            data: SamplerIntegratorData::new(camera, sampler, &params),
+            preprocessed: 0,
+        };
+        let scene = empty_scene();
+        integrator.render(&scene);
+        assert_eq!(integrator.preprocessed, 1);
+
+        let pixel_bounds = Bounds2i::from([[10, 5], [30, 15]]);
+        let (rgb, bounds) = film.to_rgb_buffer(1.);
+        for (p, c) in bounds.iter().zip(rgb.chunks(3)) {
+            let inside = pixel_bounds.inside_exclusive(p);
+            for v in c {
+                if inside {
+                    assert!((v - 0.5).abs() < 1e-5, "pixel {} is {:?}", p, c);
+                } else {
+                    assert_eq!(*v, 0., "pixel {} is {:?}", p, c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_pass_takes_new_samples() {
+        // Every pixel is noisy, so each gets more adaptive samples than a sampler round holds.
+        // The random sampler seeds each pixel from its position, so restarting the pixel on the
+        // same sampler would replay earlier values.
+        let film = test_film([4, 4]);
+        let sampler = Box::new(RandomSampler::new(2, 0));
+        let params = make_int_param_set("maxadaptivesamples", vec![7]);
+        let mut integrator = SampleValueIntegrator {
+            data: SamplerIntegratorData::new(test_camera(&film), sampler, &params),
+            values: Mutex::new(Vec::new()),
+        };
+        integrator.render(&empty_scene());
+
+        let mut values = integrator.values.into_inner().unwrap();
+        assert!(
+            values.len() > 4 * 4 * 2 + 4 * 4 * 2,
+            "{} samples",
+            values.len()
+        );
+        let n = values.len();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values.dedup();
+        assert_eq!(values.len(), n, "sample values were replayed");
+    }
+
+    /// Returns noisy radiance for rays through the left half of the film and constant radiance
+    /// for the right half, and counts the samples taken in each column of pixels.
+    #[derive(Debug)]
+    struct NoisyLeftIntegrator {
+        data: SamplerIntegratorData,
+        width: usize,
+        column_samples: Mutex<Vec<usize>>,
+    }
+
+    impl NoisyLeftIntegrator {
+        fn new(width: usize, film: &Arc<Film>, params: &ParamSet) -> NoisyLeftIntegrator {
+            let sampler = Box::new(StratifiedSampler::new(2, 2, true, 4));
+            NoisyLeftIntegrator {
+                data: SamplerIntegratorData::new(test_camera(film), sampler, params),
+                width,
+                column_samples: Mutex::new(vec![0; width]),
+            }
+        }
+    }
+
+    impl Integrator for NoisyLeftIntegrator {
+        fn render(&mut self, scene: &Scene) {
+            self.render_samples(scene);
+        }
+    }
+
+    impl SamplerIntegrator for NoisyLeftIntegrator {
+        fn data(&self) -> &SamplerIntegratorData {
+            &self.data
+        }
+
+        fn li(
+            &self,
+            ray: &RayDifferential,
+            _scene: &Scene,
+            sampler: &mut dyn Sampler,
+            _depth: usize,
+        ) -> Spectrum {
+            // The test camera maps the film to [-1, 1] in x.
+            let column = ((ray.o.x + 1.) / 2. * self.width as Float) as usize;
+            self.column_samples.lock().unwrap()[column] += 1;
+            if ray.o.x < 0. {
+                Spectrum::new(sampler.get_1d())
+            } else {
+                Spectrum::new(0.5)
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_pass_samples_noisy_pixels() {
+        let (width, height) = (8, 4);
+        let film = test_film([width as isize, height]);
+        let mut params = ParamSet::default();
+        params.add_int("maxadaptivesamples", vec![16]);
+        params.add_float("variancethreshold", vec![1e-4]);
+        let mut integrator = NoisyLeftIntegrator::new(width, &film, &params);
+        integrator.render(&empty_scene());
+
+        // The first pass takes 4 samples in each of the column's 4 pixels.
+        let first_pass = 4 * height as usize;
+        let column_samples = integrator.column_samples.into_inner().unwrap();
+        for (column, &n) in column_samples.iter().enumerate() {
+            if column < width / 2 {
+                assert!(n > first_pass, "noisy column {} got {} samples", column, n);
+            } else {
+                assert_eq!(
+                    n, first_pass,
+                    "converged column {} got extra samples",
+                    column
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_pass_disabled_matches_non_adaptive() {
+        let render = |params: &ParamSet| {
+            let film = test_film([8, 4]);
+            let mut integrator = NoisyLeftIntegrator::new(8, &film, params);
+            integrator.render(&empty_scene());
+            film.to_rgb_buffer(1.).0
+        };
+        let mut params = ParamSet::default();
+        params.add_int("maxadaptivesamples", vec![0]);
+        params.add_float("variancethreshold", vec![1e-4]);
+        let adaptive = render(&params);
+        let non_adaptive = render(&ParamSet::default());
+        let bits = |rgb: &[Float]| rgb.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&adaptive), bits(&non_adaptive));
+    }
+
+    /// Returns a white matte sphere of `radius` centered at `center`.
+    fn matte_sphere(center: [Float; 3], radius: Float) -> Arc<dyn Primitive> {
+        let o2w = Transform::translate(center);
+        let sphere = Arc::new(Sphere::new(
+            o2w,
+            o2w.inverse(),
+            false,
+            radius,
+            -radius,
+            radius,
+            360.,
+        ));
+        let matte = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(1.))),
+            Arc::new(ConstantTexture::new(0.)),
+            None,
+        ));
+        Arc::new(GeometricPrimitive::new(
+            sphere,
+            Some(matte),
+            None,
+            Default::default(),
+        ))
+    }
+
+    /// Returns a scene with a white matte unit sphere centered at z=5, lit by `n_lights` point
+    /// lights of intensity 10 at the origin.
+    fn lit_sphere(n_lights: usize) -> Scene {
+        let lights = (0..n_lights)
+            .map(|_| {
+                Arc::new(PointLight::new(
+                    &Transform::identity(),
+                    Default::default(),
+                    Spectrum::new(10.),
+                )) as Arc<dyn Light>
+            })
+            .collect();
+        Scene::new(
+            Arc::new(BVHAccel::new(
+                vec![matte_sphere([0., 0., 5.], 1.)],
+                1,
+                SplitMethod::Sah,
+            )),
+            lights,
+        )
+    }
+
+    /// Returns the point on the sphere closest to the lights, with its BSDF computed.
+    fn hit(scene: &Scene) -> SurfaceInteraction {
+        hit_from(scene, [0., 0., 0.], [0., 0., 1.])
+    }
+
+    /// Returns the first surface hit by the ray from `o` in direction `d`, with its BSDF computed.
+    fn hit_from(scene: &Scene, o: [Float; 3], d: [Float; 3]) -> SurfaceInteraction {
+        let ray = RayDifferential::from(Ray::new(
+            Point3f::from(o),
+            Vector3f::from(d),
+            Float::INFINITY,
+            0.,
+            None,
+        ));
+        let mut isect = scene.intersect(&ray).expect("ray missed sphere");
+        isect.compute_scattering_functions(&ray, false, TransportMode::Radiance);
+        isect
+    }
+
+    fn assert_close(got: &Spectrum, want: Float) {
+        let rgb = got.to_rgb();
+        for c in rgb.iter() {
+            assert!((c - want).abs() < 1e-4, "got {:?} want {}", rgb, want);
+        }
+    }
+
+    #[test]
+    fn uniform_sample_all_lights_point_light() {
+        // Lambertian f = 1/π, the light is 4 units away straight along the normal.
+        let want = 10. / 16. / PI;
+        let scene = lit_sphere(2);
+        let isect = hit(&scene);
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let l = uniform_sample_all_lights(&isect, &scene, &mut sampler, &[1, 4], false);
+        assert_close(&l, 2. * want);
+        let l = uniform_sample_all_lights(&isect, &scene, &mut sampler, &[0, 1], false);
+        assert_close(&l, want);
+    }
+
+    #[test]
+    fn uniform_sample_one_light_point_light() {
+        // Only one light is sampled, but scaling by the light count makes up for the other.
+        let want = 2. * 10. / 16. / PI;
+        let scene = lit_sphere(2);
+        let isect = hit(&scene);
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        for _ in 0..4 {
+            let l = uniform_sample_one_light(&isect, &scene, &mut sampler, false, None);
+            assert_close(&l, want);
+        }
+
+        let scene = lit_sphere(0);
+        let isect = hit(&scene);
+        let l = uniform_sample_one_light(&isect, &scene, &mut sampler, false, None);
+        assert!(l.is_black());
+    }
+
+    #[test]
+    fn estimate_direct_sphere_light() {
+        // A spherical light of radius r at distance d along the normal of a white Lambertian
+        // surface reflects L (r / d)^2.
+        let (r, d) = (0.5, 3.);
+        let o2w = Transform::translate([0., -1. - d, 0.]);
+        let shape = Arc::new(Sphere::new(o2w, o2w.inverse(), false, r, -r, r, 360.));
+        let light = Arc::new(DiffuseAreaLight::new(
+            &Transform::identity(),
+            Default::default(),
+            Spectrum::new(1.),
+            1,
+            shape.clone(),
+            false,
+        ));
+        let light_prim = Arc::new(GeometricPrimitive::new(
+            shape,
+            None,
+            Some(Arc::clone(&light) as Arc<dyn AreaLight>),
+            Default::default(),
+        ));
+        let scene = Scene::new(
+            Arc::new(BVHAccel::new(
+                vec![matte_sphere([0., 0., 0.], 1.), light_prim],
+                1,
+                SplitMethod::Sah,
+            )),
+            vec![Arc::clone(&light) as Arc<dyn Light>],
+        );
+        let isect = hit_from(&scene, [0., -2., 0.], [0., 1., 0.]);
+
+        let mut rng = Rng::new(0);
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let mut u = || Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+        const N: usize = 4096;
+        let mut sum = Spectrum::new(0.);
+        for _ in 0..N {
+            let (u_scattering, u_light) = (u(), u());
+            sum += estimate_direct(
+                (&isect).into(),
+                u_scattering,
+                light.as_ref(),
+                u_light,
+                &scene,
+                &mut sampler,
+                false,
+                false,
+            );
+        }
+        let want = (r / d) * (r / d);
+        let got = (sum / N as Float).to_rgb()[0];
+        assert!(
+            (got - want).abs() < 0.03 * want,
+            "got {} want {}",
+            got,
+            want
+        );
+    }
+}
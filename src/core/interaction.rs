@@ -1,4 +1,4 @@
-// Copyright 2018 Google LLC
+// Copyright 2019 Google LLC
 //
 // Licensed under the Apache License, Version 2.0 (the "License");
 // you may not use this file except in compliance with the License.
@@ -12,12 +12,421 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Stub type for flushing out [PbrtAPI].  TODO(wathiede): actually implement and document.
-//!
-//! [PbrtAPI]: crate::core::api::PbrtAPI
+//! Types describing the local geometry where a ray interacts with a surface.
 
-/// Stub type for flushing out [PbrtAPI].  TODO(wathiede): actually implement and document.
-///
-/// [PbrtAPI]: crate::core::api::PbrtAPI
-#[derive(Default)]
-pub struct SurfaceInteraction {}
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::{
+    core::{
+        geometry::{
+            cross, dot, offset_ray_origin, Normal3f, Point2f, Point3f, Ray, RayDifferential,
+            Vector3f,
+        },
+        light::AreaLight,
+        material::{Material, TransportMode},
+        medium::{Medium, MediumInterface, PhaseFunction},
+        reflection::BSDF,
+        shape::ShapeData,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+/// Fraction of the segment rays spawned towards a point stop short of it, to avoid intersecting
+/// the surface the target point lies on.
+const SHADOW_EPSILON: Float = 0.0001;
+
+/// Interaction holds the data common to any point a ray scatters at.
+#[derive(Clone, Debug, Default)]
+pub struct Interaction {
+    /// Location of the interaction in world space.
+    pub p: Point3f,
+    /// Time the interaction occurred at.
+    pub time: Float,
+    /// Conservative bound on the floating-point error accumulated computing `p`.
+    pub p_error: Vector3f,
+    /// Outgoing direction, i.e. the negated direction of the ray that found this interaction.
+    pub wo: Vector3f,
+    /// Surface normal at `p`, zero for interactions in participating media.
+    pub n: Normal3f,
+    /// Media on either side of the surface at `p`, if any.
+    pub medium_interface: Option<MediumInterface>,
+}
+
+impl Interaction {
+    /// Returns true if this interaction is on a surface, as opposed to inside a medium.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Normal3f, interaction::Interaction};
+    ///
+    /// let mut it = Interaction::default();
+    /// assert!(!it.is_surface_interaction());
+    /// it.n = Normal3f::from([0., 0., 1.]);
+    /// assert!(it.is_surface_interaction());
+    /// ```
+    pub fn is_surface_interaction(&self) -> bool {
+        self.n != Normal3f::default()
+    }
+
+    /// Returns a ray leaving this interaction in direction `d`.  The origin is offset past the
+    /// error bounds of `p` so the ray doesn't re-intersect the surface it leaves.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::{Normal3f, Point3f, Vector3f},
+    ///     interaction::Interaction,
+    /// };
+    ///
+    /// let it = Interaction {
+    ///     n: Normal3f::from([0., 0., 1.]),
+    ///     p_error: Vector3f::from([1e-6, 1e-6, 1e-6]),
+    ///     ..Default::default()
+    /// };
+    /// let r = it.spawn_ray(&Vector3f::from([0., 0., -1.]));
+    /// assert!(r.o.z < 0.);
+    /// assert!(r.t_max.get().is_infinite());
+    /// ```
+    pub fn spawn_ray(&self, d: &Vector3f) -> Ray {
+        Ray::new(
+            offset_ray_origin(self.p, self.p_error, self.n, *d),
+            *d,
+            Float::INFINITY,
+            self.time,
+            self.get_medium(d),
+        )
+    }
+
+    /// Returns a ray from this interaction that stops just short of `p2`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point3f, interaction::Interaction};
+    ///
+    /// let it = Interaction::default();
+    /// let r = it.spawn_ray_to(Point3f::from([0., 0., 2.]));
+    /// assert!(r.at(r.t_max.get()).z < 2.);
+    /// assert!(r.at(r.t_max.get()).z > 1.99);
+    /// ```
+    pub fn spawn_ray_to(&self, p2: Point3f) -> Ray {
+        let origin = offset_ray_origin(self.p, self.p_error, self.n, p2 - self.p);
+        let d = p2 - origin;
+        Ray::new(
+            origin,
+            d,
+            1. - SHADOW_EPSILON,
+            self.time,
+            self.get_medium(&d),
+        )
+    }
+
+    /// Returns a ray from this interaction that stops just short of the interaction `it`.  Both
+    /// ends are offset past their points' error bounds, so neither surface is intersected.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::{Normal3f, Point3f, Vector3f},
+    ///     interaction::Interaction,
+    /// };
+    ///
+    /// let p_error = Vector3f::from([1e-4, 1e-4, 1e-4]);
+    /// let floor = Interaction {
+    ///     n: Normal3f::from([0., 0., 1.]),
+    ///     p_error,
+    ///     ..Default::default()
+    /// };
+    /// let ceiling = Interaction {
+    ///     p: Point3f::from([0., 0., 2.]),
+    ///     n: Normal3f::from([0., 0., -1.]),
+    ///     p_error,
+    ///     ..Default::default()
+    /// };
+    /// let r = floor.spawn_ray_to_interaction(&ceiling);
+    /// assert!(r.o.z > 0.);
+    /// assert!(r.at(r.t_max.get()).z < 2.);
+    /// ```
+    pub fn spawn_ray_to_interaction(&self, it: &Interaction) -> Ray {
+        let origin = offset_ray_origin(self.p, self.p_error, self.n, it.p - self.p);
+        let target = offset_ray_origin(it.p, it.p_error, it.n, origin - it.p);
+        let d = target - origin;
+        Ray::new(
+            origin,
+            d,
+            1. - SHADOW_EPSILON,
+            self.time,
+            self.get_medium(&d),
+        )
+    }
+
+    /// Returns the medium a ray leaving this interaction in direction `w` travels through, the
+    /// outside medium if `w` is on the side the normal points to, otherwise the inside one.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Normal3f, Vector3f},
+    ///         interaction::Interaction,
+    ///         medium::{Medium, MediumInterface},
+    ///         spectrum::Spectrum,
+    ///     },
+    ///     media::homogeneous::HomogeneousMedium,
+    /// };
+    ///
+    /// let fog: Arc<dyn Medium> = Arc::new(HomogeneousMedium::new(
+    ///     Spectrum::new(1.),
+    ///     Spectrum::new(1.),
+    ///     0.,
+    /// ));
+    /// let it = Interaction {
+    ///     n: Normal3f::from([0., 0., 1.]),
+    ///     medium_interface: Some(MediumInterface {
+    ///         inside: Some(fog),
+    ///         outside: None,
+    ///     }),
+    ///     ..Default::default()
+    /// };
+    /// assert!(it.get_medium(&Vector3f::from([0., 0., -1.])).is_some());
+    /// assert!(it.get_medium(&Vector3f::from([0., 0., 1.])).is_none());
+    /// ```
+    pub fn get_medium(&self, w: &Vector3f) -> Option<Arc<dyn Medium>> {
+        let mi = self.medium_interface.as_ref()?;
+        if dot(*w, Vector3f::from(self.n)) > 0. {
+            mi.outside.clone()
+        } else {
+            mi.inside.clone()
+        }
+    }
+}
+
+/// Shading holds the possibly perturbed geometry, e.g. from bump mapping or interpolated vertex
+/// normals, used when shading a [SurfaceInteraction].
+#[derive(Clone, Debug, Default)]
+pub struct Shading {
+    /// Shading normal.
+    pub n: Normal3f,
+    /// Partial derivative of the shading position with respect to u.
+    pub dpdu: Vector3f,
+    /// Partial derivative of the shading position with respect to v.
+    pub dpdv: Vector3f,
+    /// Partial derivative of the shading normal with respect to u.
+    pub dndu: Normal3f,
+    /// Partial derivative of the shading normal with respect to v.
+    pub dndv: Normal3f,
+}
+
+/// SurfaceInteraction describes the local geometry at a point where a ray hit a surface.  It
+/// derefs to the [Interaction] holding the data common to all interactions.
+#[derive(Debug, Default)]
+pub struct SurfaceInteraction {
+    /// Data common to all interactions.
+    pub it: Interaction,
+    /// Parametric (u, v) coordinates of the hit point on the surface.
+    pub uv: Point2f,
+    /// Partial derivative of the position with respect to u.
+    pub dpdu: Vector3f,
+    /// Partial derivative of the position with respect to v.
+    pub dpdv: Vector3f,
+    /// Partial derivative of the normal with respect to u.
+    pub dndu: Normal3f,
+    /// Partial derivative of the normal with respect to v.
+    pub dndv: Normal3f,
+    /// Geometry used for shading, initially the same as the true geometry.
+    pub shading: Shading,
+    /// Material of the surface hit, set by the primitive that was intersected.
+    pub material: Option<Arc<dyn Material>>,
+    /// Light emitted by the surface hit, set by the primitive that was intersected.
+    pub area_light: Option<Arc<dyn AreaLight>>,
+    /// Scattering functions at the hit point, set by [compute_scattering_functions].
+    ///
+    /// [compute_scattering_functions]: SurfaceInteraction::compute_scattering_functions
+    pub bsdf: Option<BSDF>,
+}
+
+impl SurfaceInteraction {
+    /// Create a new `SurfaceInteraction` for a hit on `shape` at `p` with parametric coordinates
+    /// `uv`.  The normal is computed from the partial derivatives `dpdu` and `dpdv` and flipped
+    /// if the shape's orientation is reversed or its transform swaps handedness.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        p: Point3f,
+        p_error: Vector3f,
+        uv: Point2f,
+        wo: Vector3f,
+        dpdu: Vector3f,
+        dpdv: Vector3f,
+        dndu: Normal3f,
+        dndv: Normal3f,
+        time: Float,
+        shape: &ShapeData,
+    ) -> SurfaceInteraction {
+        let mut n = Normal3f::from(cross(dpdu, dpdv).normalize());
+        // Adjust normal based on orientation and handedness
+        if shape.reverse_orientation ^ shape.transform_swaps_handedness {
+            n = -n;
+        }
+        SurfaceInteraction {
+            it: Interaction {
+                p,
+                time,
+                p_error,
+                wo,
+                n,
+                medium_interface: None,
+            },
+            uv,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            shading: Shading {
+                n,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+            },
+            material: None,
+            area_light: None,
+            bsdf: None,
+        }
+    }
+
+    /// Replaces the shading geometry with the given partial derivatives, e.g. from interpolated
+    /// vertex normals.  When `orientation_is_authoritative` the geometric normal is flipped to lie
+    /// in the same hemisphere as the new shading normal, otherwise the shading normal is flipped
+    /// to match the geometric normal.
+    pub fn set_shading_geometry(
+        &mut self,
+        dpdus: Vector3f,
+        dpdvs: Vector3f,
+        dndus: Normal3f,
+        dndvs: Normal3f,
+        orientation_is_authoritative: bool,
+    ) {
+        // Compute _shading.n_ for _SurfaceInteraction_
+        self.shading.n = Normal3f::from(cross(dpdus, dpdvs).normalize());
+        if orientation_is_authoritative {
+            self.it.n = self.it.n.face_forward(Vector3f::from(self.shading.n));
+        } else {
+            self.shading.n = self.shading.n.face_forward(Vector3f::from(self.it.n));
+        }
+        // Initialize _shading_ partial derivative values
+        self.shading.dpdu = dpdus;
+        self.shading.dpdv = dpdvs;
+        self.shading.dndu = dndus;
+        self.shading.dndv = dndvs;
+    }
+
+    /// Asks the surface's material, if any, to initialize `bsdf` for this hit point.
+    pub fn compute_scattering_functions(
+        &mut self,
+        _ray: &RayDifferential,
+        allow_multiple_lobes: bool,
+        mode: TransportMode,
+    ) {
+        // TODO(wathiede): compute ray differentials for texture filtering.
+        if let Some(material) = self.material.clone() {
+            material.compute_scattering_functions(self, mode, allow_multiple_lobes);
+        }
+    }
+
+    /// Returns the radiance emitted from the hit point in direction `w`, zero if the surface
+    /// isn't an area light.
+    pub fn le(&self, w: &Vector3f) -> Spectrum {
+        match &self.area_light {
+            Some(area) => area.l(&self.it, w),
+            None => Spectrum::new(0.),
+        }
+    }
+}
+
+impl Deref for SurfaceInteraction {
+    type Target = Interaction;
+    fn deref(&self) -> &Interaction {
+        &self.it
+    }
+}
+
+impl DerefMut for SurfaceInteraction {
+    fn deref_mut(&mut self) -> &mut Interaction {
+        &mut self.it
+    }
+}
+
+/// MediumInteraction describes a point where light scatters inside a participating medium.  It
+/// derefs to the [Interaction] holding the data common to all interactions; its normal is zero.
+#[derive(Clone, Debug)]
+pub struct MediumInteraction {
+    /// Data common to all interactions.
+    pub it: Interaction,
+    /// Phase function describing how light scatters at this point.
+    pub phase: Arc<dyn PhaseFunction>,
+}
+
+impl MediumInteraction {
+    /// Create a new `MediumInteraction` at `p`, inside `medium`, with outgoing direction `wo` and
+    /// scattering described by `phase`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use pbrt::core::{
+    ///     geometry::{Point3f, Vector3f},
+    ///     interaction::MediumInteraction,
+    ///     medium::HenyeyGreenstein,
+    /// };
+    ///
+    /// let mi = MediumInteraction::new(
+    ///     Point3f::from([1., 2., 3.]),
+    ///     Vector3f::from([0., 0., 1.]),
+    ///     0.,
+    ///     None,
+    ///     Arc::new(HenyeyGreenstein::new(0.)),
+    /// );
+    /// assert_eq!(mi.p, Point3f::from([1., 2., 3.]));
+    /// assert!(!mi.is_surface_interaction());
+    /// ```
+    pub fn new(
+        p: Point3f,
+        wo: Vector3f,
+        time: Float,
+        medium: Option<Arc<dyn Medium>>,
+        phase: Arc<dyn PhaseFunction>,
+    ) -> MediumInteraction {
+        MediumInteraction {
+            it: Interaction {
+                p,
+                time,
+                wo,
+                medium_interface: Some(MediumInterface {
+                    inside: medium.clone(),
+                    outside: medium,
+                }),
+                ..Default::default()
+            },
+            phase,
+        }
+    }
+}
+
+impl Deref for MediumInteraction {
+    type Target = Interaction;
+    fn deref(&self) -> &Interaction {
+        &self.it
+    }
+}
+
+impl DerefMut for MediumInteraction {
+    fn deref_mut(&mut self) -> &mut Interaction {
+        &mut self.it
+    }
+}
@@ -16,31 +16,186 @@
 
 use std::fmt::Debug;
 
-use crate::core::medium::MediumInterface;
+use bitflags::bitflags;
 
-/// Flags for the various light types.
-#[derive(Debug)]
-pub enum LightFlags {
-    /// Light uses delta sampling, type position.
-    DeltaPosition,
-    /// Light uses delta sampling, type direction.
-    DeltaDirection,
-    /// Is an area light.
-    Area,
-    /// Is a light at infinite distance.
-    Infinite,
+use crate::{
+    core::{
+        geometry::{Normal3f, Point2f, Ray, RayDifferential, Vector3f},
+        interaction::Interaction,
+        medium::MediumInterface,
+        sampler::Sampler,
+        scene::Scene,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+bitflags! {
+    /// Flags for the various light types.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct LightFlags: u8 {
+        /// Light uses delta sampling, type position.
+        const DELTA_POSITION = 1 << 0;
+        /// Light uses delta sampling, type direction.
+        const DELTA_DIRECTION = 1 << 1;
+        /// Is an area light.
+        const AREA = 1 << 2;
+        /// Is a light at infinite distance.
+        const INFINITE = 1 << 3;
+    }
+}
+
+/// Returns true if lights with `flags` are described by a delta distribution, i.e. they can't be
+/// hit by randomly sampled rays.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::light::{is_delta_light, LightFlags};
+///
+/// assert!(is_delta_light(LightFlags::DELTA_POSITION));
+/// assert!(is_delta_light(LightFlags::DELTA_DIRECTION));
+/// assert!(!is_delta_light(LightFlags::AREA));
+/// assert!(!is_delta_light(LightFlags::INFINITE));
+/// ```
+pub fn is_delta_light(flags: LightFlags) -> bool {
+    flags.intersects(LightFlags::DELTA_POSITION | LightFlags::DELTA_DIRECTION)
+}
+
+/// VisibilityTester answers whether two points can see each other.  It is returned by
+/// [Light::sample_li] so the, possibly expensive, shadow ray is only traced if the sample
+/// contributes.
+#[derive(Clone, Debug, Default)]
+pub struct VisibilityTester {
+    /// One end of the segment to test.
+    pub p0: Interaction,
+    /// The other end of the segment to test.
+    pub p1: Interaction,
+}
+
+impl VisibilityTester {
+    /// Create a new `VisibilityTester` for the segment between `p0` and `p1`.
+    pub fn new(p0: Interaction, p1: Interaction) -> VisibilityTester {
+        VisibilityTester { p0, p1 }
+    }
+
+    /// Returns true if no geometry in `scene` blocks the segment between `p0` and `p1`.
+    pub fn unoccluded(&self, scene: &Scene) -> bool {
+        !scene.intersect_p(&self.p0.spawn_ray_to_interaction(&self.p1))
+    }
+
+    /// Returns the fraction of light transmitted along the segment between `p0` and `p1` by the
+    /// participating media it passes through.  Surfaces with no material only delimit media and
+    /// don't block the segment, any other surface does and the result is black.
+    pub fn tr(&self, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
+        let mut ray = self.p0.spawn_ray_to_interaction(&self.p1);
+        let mut tr = Spectrum::new(1.);
+        loop {
+            let isect = scene.intersect(&ray);
+            // Handle opaque surface along ray's path
+            if let Some(isect) = &isect {
+                if isect.material.is_some() {
+                    return Spectrum::new(0.);
+                }
+            }
+
+            // Update transmittance for current ray segment
+            if let Some(medium) = &ray.medium {
+                tr *= medium.tr(&ray, sampler);
+            }
+
+            // Generate next ray segment or return final transmittance
+            match isect {
+                Some(isect) => ray = isect.spawn_ray_to_interaction(&self.p1),
+                None => return tr,
+            }
+        }
+    }
+}
+
+/// LiSample is the result of sampling the incident illumination from a light at a point.
+#[derive(Clone, Debug, Default)]
+pub struct LiSample {
+    /// Radiance arriving from the light, assuming nothing blocks it.
+    pub li: Spectrum,
+    /// Direction, in world space, the light arrives from.
+    pub wi: Vector3f,
+    /// Probability density of having sampled `wi`, with respect to solid angle.
+    pub pdf: Float,
+    /// Tests whether the light is occluded.
+    pub vis: VisibilityTester,
+}
+
+/// LeSample is the result of sampling a ray leaving a light, as used by integrators that trace
+/// paths starting at the lights.
+#[derive(Clone, Debug, Default)]
+pub struct LeSample {
+    /// Radiance carried by `ray`.
+    pub le: Spectrum,
+    /// Ray leaving the light, in world space.
+    pub ray: Ray,
+    /// Surface normal of the light at the ray's origin.
+    pub n_light: Normal3f,
+    /// Probability density, with respect to area, of having sampled the ray's origin.
+    pub pdf_pos: Float,
+    /// Probability density, with respect to solid angle, of having sampled the ray's direction.
+    pub pdf_dir: Float,
+}
+
+/// Light is the interface all light sources implement.
+pub trait Light: Debug + Send + Sync {
+    /// Returns what kind of light this is.
+    fn flags(&self) -> LightFlags;
+
+    /// Called once the scene is constructed, before rendering begins, so lights can compute
+    /// anything that depends on the scene, e.g. its bounds.
+    fn preprocess(&self, _scene: &Scene) {}
+
+    /// Returns the radiance arriving along `ray` from a light that surrounds the scene, for rays
+    /// that escape the scene without hitting anything.  Lights with finite extent return no
+    /// radiance.
+    fn le(&self, _ray: &RayDifferential) -> Spectrum {
+        Spectrum::new(0.)
+    }
+
+    /// Samples a direction the light arrives at `ref_` from, using the uniform sample `u`.  A
+    /// sample with zero `pdf` or black `li` carries no light.
+    fn sample_li(&self, ref_: &Interaction, u: Point2f) -> LiSample;
+
+    /// Returns the probability density, with respect to solid angle, of [sample_li] sampling the
+    /// direction `wi` from `ref_`.
+    ///
+    /// [sample_li]: Light::sample_li
+    fn pdf_li(&self, ref_: &Interaction, wi: &Vector3f) -> Float;
+
+    /// Samples a ray leaving the light, using `u1` and `u2` for its origin and direction, at
+    /// `time`.  Lights that don't support sampling emitted rays return a sample with zero pdfs.
+    fn sample_le(&self, _u1: Point2f, _u2: Point2f, _time: Float) -> LeSample {
+        LeSample::default()
+    }
+
+    /// Returns the total power emitted by the light.  Integrators may use it to decide which
+    /// lights to sample most often.
+    fn power(&self) -> Spectrum;
+
+    /// Returns the number of samples integrators should take from this light per shading point.
+    fn n_samples(&self) -> usize {
+        1
+    }
 }
 
-/// Stub type for flushing out [PbrtAPI].  TODO(wathiede): actually implement and document.
+/// AreaLight is a [Light] attached to a [Shape] that emits light from its surface.
 ///
-/// [PbrtAPI]: crate::core::api::PbrtAPI
-pub trait Light: Debug {}
+/// [Shape]: crate::core::shape::Shape
+pub trait AreaLight: Light {
+    /// Returns the radiance emitted from the surface point `intr` in the outgoing direction `w`.
+    fn l(&self, intr: &Interaction, w: &Vector3f) -> Spectrum;
+}
 
 /// LightData holds data common to various `Light` implementations.
 #[derive(Debug)]
 pub struct LightData {
     flags: LightFlags,
-    n_samples: isize,
+    n_samples: usize,
     medium_interface: MediumInterface,
 }
 
@@ -48,17 +203,125 @@ pub struct LightData {
 // STAT_COUNTER("Scene/Lights", numLights);
 // STAT_COUNTER("Scene/AreaLights", numAreaLights);
 impl LightData {
-    /// Construct `LightData` from given parameters.
+    /// Returns what kind of light this is.
+    pub fn flags(&self) -> LightFlags {
+        self.flags
+    }
+
+    /// Returns the number of samples integrators should take from this light per shading point.
+    pub fn n_samples(&self) -> usize {
+        self.n_samples
+    }
+
+    /// Returns the media inside and outside the light.
+    pub fn medium_interface(&self) -> Option<MediumInterface> {
+        Some(self.medium_interface.clone())
+    }
+
+    /// Construct `LightData` from given parameters.  At least one sample is always taken.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::light::{LightData, LightFlags};
+    ///
+    /// let ld = LightData::new(LightFlags::AREA, 0, Default::default());
+    /// assert_eq!(ld.flags(), LightFlags::AREA);
+    /// assert_eq!(ld.n_samples(), 1);
+    /// ```
     pub fn new(
         flags: LightFlags,
-        n_samples: isize,
+        n_samples: usize,
         medium_interface: MediumInterface,
     ) -> LightData {
         // TODO(wathiede): increment STAT_COUNTER for numLights.
         LightData {
             flags,
-            n_samples,
+            n_samples: n_samples.max(1),
             medium_interface,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::VisibilityTester;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        core::{
+            geometry::Point3f,
+            interaction::Interaction,
+            primitive::{GeometricPrimitive, Primitive},
+            rng::Rng,
+            scene::Scene,
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        materials::matte::MatteMaterial,
+        samplers::stratified::StratifiedSampler,
+        shapes::sphere::Sphere,
+        textures::constant::ConstantTexture,
+        Float,
+    };
+
+    fn scene(primitives: Vec<Arc<dyn Primitive>>) -> Scene {
+        Scene::new(
+            Arc::new(BVHAccel::new(primitives, 1, SplitMethod::Sah)),
+            Vec::new(),
+        )
+    }
+
+    fn tester(p0: [Float; 3], p1: [Float; 3]) -> VisibilityTester {
+        let it = |p: [Float; 3]| Interaction {
+            p: Point3f::from(p),
+            ..Default::default()
+        };
+        VisibilityTester::new(it(p0), it(p1))
+    }
+
+    #[test]
+    fn empty_scene_is_unoccluded() {
+        let scene = scene(Vec::new());
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let mut rng = Rng::new(0);
+        let mut p = || {
+            [
+                rng.uniform_float() * 20. - 10.,
+                rng.uniform_float() * 20. - 10.,
+                rng.uniform_float() * 20. - 10.,
+            ]
+        };
+        for _ in 0..100 {
+            let vis = tester(p(), p());
+            assert!(vis.unoccluded(&scene));
+            assert_eq!(vis.tr(&scene, &mut sampler), Spectrum::new(1.));
+        }
+    }
+
+    #[test]
+    fn opaque_sphere_occludes() {
+        let o2w = Transform::identity();
+        let sphere = Arc::new(Sphere::new(o2w, o2w, false, 1., -1., 1., 360.));
+        let matte = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(0.5))),
+            Arc::new(ConstantTexture::new(0.)),
+            None,
+        ));
+        let scene = scene(vec![Arc::new(GeometricPrimitive::new(
+            sphere,
+            Some(matte),
+            None,
+            Default::default(),
+        ))]);
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+
+        let blocked = tester([0., 0., -5.], [0., 0., 5.]);
+        assert!(!blocked.unoccluded(&scene));
+        assert!(blocked.tr(&scene, &mut sampler).is_black());
+
+        let clear = tester([2., 0., -5.], [2., 0., 5.]);
+        assert!(clear.unoccluded(&scene));
+        assert_eq!(clear.tr(&scene, &mut sampler), Spectrum::new(1.));
+    }
+}
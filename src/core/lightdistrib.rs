@@ -0,0 +1,319 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Strategies for choosing which light to sample when estimating direct lighting.  Scenes with
+//! many lights converge much faster when the lights that contribute most to a point are sampled
+//! more often.  [create_light_sample_distribution] picks a [LightDistribution] by name.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use log::error;
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{Bounds3f, Point2f, Point3f},
+        interaction::Interaction,
+        light::Light,
+        rng::Rng,
+        sampling::Distribution1D,
+        scene::Scene,
+    },
+    lerp, Float,
+};
+
+/// Maximum number of voxels along the largest axis of the scene's bounds used by
+/// [SpatialLightDistribution].
+const MAX_VOXELS: usize = 64;
+
+/// Number of points in each voxel the lights' contributions are estimated at.
+const VOXEL_SAMPLES: usize = 128;
+
+/// LightDistribution provides the distribution to choose lights from when shading a point.
+pub trait LightDistribution: Debug + Send + Sync {
+    /// Returns the distribution over `scene.lights` to sample from for a point at `p`.
+    fn lookup(&self, p: Point3f) -> Arc<Distribution1D>;
+}
+
+/// UniformLightDistribution samples all lights with equal probability, regardless of position.
+#[derive(Debug)]
+pub struct UniformLightDistribution {
+    distrib: Arc<Distribution1D>,
+}
+
+impl UniformLightDistribution {
+    /// Create a `UniformLightDistribution` over the lights in `scene`.
+    pub fn new(scene: &Scene) -> UniformLightDistribution {
+        UniformLightDistribution {
+            distrib: Arc::new(Distribution1D::new(&vec![1.; scene.lights.len()])),
+        }
+    }
+}
+
+impl LightDistribution for UniformLightDistribution {
+    fn lookup(&self, _p: Point3f) -> Arc<Distribution1D> {
+        Arc::clone(&self.distrib)
+    }
+}
+
+/// PowerLightDistribution samples lights proportionally to their emitted power, regardless of
+/// position.
+#[derive(Debug)]
+pub struct PowerLightDistribution {
+    distrib: Arc<Distribution1D>,
+}
+
+impl PowerLightDistribution {
+    /// Create a `PowerLightDistribution` over the lights in `scene`.
+    pub fn new(scene: &Scene) -> PowerLightDistribution {
+        let power: Vec<Float> = scene.lights.iter().map(|l| l.power().y()).collect();
+        PowerLightDistribution {
+            distrib: Arc::new(Distribution1D::new(&power)),
+        }
+    }
+}
+
+impl LightDistribution for PowerLightDistribution {
+    fn lookup(&self, _p: Point3f) -> Arc<Distribution1D> {
+        Arc::clone(&self.distrib)
+    }
+}
+
+/// SpatialLightDistribution divides the scene's bounds into a grid of voxels and samples lights
+/// proportionally to an estimate of their contribution to points in the voxel being shaded.
+/// Distributions are computed the first time a voxel is looked up, then cached.
+#[derive(Debug)]
+pub struct SpatialLightDistribution {
+    lights: Vec<Arc<dyn Light>>,
+    world_bound: Bounds3f,
+    n_voxels: [usize; 3],
+    voxels: Mutex<HashMap<[usize; 3], Arc<Distribution1D>>>,
+}
+
+impl SpatialLightDistribution {
+    /// Create a `SpatialLightDistribution` over the lights in `scene`, with at most `max_voxels`
+    /// voxels along the largest axis of its bounds.
+    pub fn new(scene: &Scene, max_voxels: usize) -> SpatialLightDistribution {
+        // Compute the number of voxels so that the widest scene bounding box dimension has
+        // _max_voxels_ voxels and the other dimensions have a number of voxels so that voxels
+        // are roughly cube shaped.
+        let world_bound = scene.world_bound();
+        let diag = world_bound.diagonal();
+        let bmax = diag[world_bound.maximum_extent()];
+        let mut n_voxels = [1; 3];
+        for (i, n) in n_voxels.iter_mut().enumerate() {
+            if bmax > 0. {
+                *n = ((diag[i] / bmax * max_voxels as Float).round() as usize).max(1);
+            }
+        }
+        SpatialLightDistribution {
+            lights: scene.lights.clone(),
+            world_bound,
+            n_voxels,
+            voxels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the grid coordinates of the voxel containing `p`, clamped to the grid.
+    fn voxel(&self, p: Point3f) -> [usize; 3] {
+        let offset = self.world_bound.offset(p);
+        let mut pi = [0; 3];
+        for (i, v) in pi.iter_mut().enumerate() {
+            let n = self.n_voxels[i] as isize;
+            *v = clamp((offset[i] * n as Float) as isize, 0, n - 1) as usize;
+        }
+        pi
+    }
+
+    /// Estimates each light's contribution to the voxel at `pi` by sampling it from a set of
+    /// points spread over the voxel, keeping the largest value seen for each light.
+    fn compute_distribution(&self, pi: [usize; 3]) -> Distribution1D {
+        let b = &self.world_bound;
+        let corner = |o: [usize; 3]| {
+            let t = |i: usize| o[i] as Float / self.n_voxels[i] as Float;
+            Point3f::from([
+                lerp(t(0), b.p_min.x, b.p_max.x),
+                lerp(t(1), b.p_min.y, b.p_max.y),
+                lerp(t(2), b.p_min.z, b.p_max.z),
+            ])
+        };
+        let p0 = corner(pi);
+        let p1 = corner([pi[0] + 1, pi[1] + 1, pi[2] + 1]);
+
+        // Seed by voxel so the distribution doesn't depend on the order voxels are visited in.
+        let mut rng =
+            Rng::new(((pi[2] * self.n_voxels[1] + pi[1]) * self.n_voxels[0] + pi[0]) as u64);
+        let mut light_contrib: Vec<Float> = vec![0.; self.lights.len()];
+        for _ in 0..VOXEL_SAMPLES {
+            let p = Point3f::from([
+                lerp(rng.uniform_float(), p0.x, p1.x),
+                lerp(rng.uniform_float(), p0.y, p1.y),
+                lerp(rng.uniform_float(), p0.z, p1.z),
+            ]);
+            let intr = Interaction {
+                p,
+                ..Default::default()
+            };
+            let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+            for (light, contrib) in self.lights.iter().zip(light_contrib.iter_mut()) {
+                let ls = light.sample_li(&intr, u);
+                if ls.pdf > 0. {
+                    // Occlusion is ignored, the estimate is only meant to be roughly right.
+                    *contrib = contrib.max(ls.li.y() / ls.pdf);
+                }
+            }
+        }
+
+        // We don't want to leave any lights with a zero probability; it's possible that a light
+        // contributes to points in the voxel even though we didn't find such a point when sampling
+        // above.  Therefore, compute a minimum (small) weight and ensure that all lights are
+        // given at least the corresponding probability.
+        let n = light_contrib.len().max(1) as Float;
+        let avg_contrib = light_contrib.iter().sum::<Float>() / n;
+        let min_contrib = if avg_contrib > 0. {
+            0.001 * avg_contrib
+        } else {
+            1.
+        };
+        for c in light_contrib.iter_mut() {
+            *c = c.max(min_contrib);
+        }
+        Distribution1D::new(&light_contrib)
+    }
+}
+
+impl LightDistribution for SpatialLightDistribution {
+    fn lookup(&self, p: Point3f) -> Arc<Distribution1D> {
+        let pi = self.voxel(p);
+        if let Some(distrib) = self.voxels.lock().unwrap().get(&pi) {
+            return Arc::clone(distrib);
+        }
+        // Compute without holding the lock, so other threads can look up voxels meanwhile.  If
+        // another thread computed the same voxel first, its distribution is kept.
+        let distrib = Arc::new(self.compute_distribution(pi));
+        Arc::clone(self.voxels.lock().unwrap().entry(pi).or_insert(distrib))
+    }
+}
+
+/// Creates the [LightDistribution] named `name` for `scene`, one of "uniform", "power" or
+/// "spatial".  Unknown names log an error and use "uniform".
+pub fn create_light_sample_distribution(name: &str, scene: &Scene) -> Box<dyn LightDistribution> {
+    match name {
+        "uniform" => Box::new(UniformLightDistribution::new(scene)),
+        _ if scene.lights.len() == 1 => Box::new(UniformLightDistribution::new(scene)),
+        "power" => Box::new(PowerLightDistribution::new(scene)),
+        "spatial" => Box::new(SpatialLightDistribution::new(scene, MAX_VOXELS)),
+        _ => {
+            error!(
+                "Light sample distribution type \"{}\" unknown. Using \"uniform\".",
+                name
+            );
+            Box::new(UniformLightDistribution::new(scene))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        core::{
+            primitive::{GeometricPrimitive, Primitive},
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        lights::point::PointLight,
+        shapes::sphere::Sphere,
+    };
+
+    /// Returns a scene with point lights of the given intensities spread along the x axis, 20
+    /// units apart, and a small sphere next to each so the scene's bounds cover them.
+    fn scene(intensities: &[Float]) -> Scene {
+        let mut prims: Vec<Arc<dyn Primitive>> = Vec::new();
+        let mut lights: Vec<Arc<dyn Light>> = Vec::new();
+        for (i, &intensity) in intensities.iter().enumerate() {
+            let t = Transform::translate([20. * i as Float, 0., 0.]);
+            lights.push(Arc::new(PointLight::new(
+                &t,
+                Default::default(),
+                Spectrum::new(intensity),
+            )));
+            let o2w = t * Transform::translate([0., 0., 2.]);
+            prims.push(Arc::new(GeometricPrimitive::new(
+                Arc::new(Sphere::new(o2w, o2w.inverse(), false, 1., -1., 1., 360.)),
+                None,
+                None,
+                Default::default(),
+            )));
+        }
+        Scene::new(Arc::new(BVHAccel::new(prims, 1, SplitMethod::Sah)), lights)
+    }
+
+    #[test]
+    fn uniform() {
+        let scene = scene(&[1., 4., 16.]);
+        let d = create_light_sample_distribution("uniform", &scene).lookup(Point3f::default());
+        for i in 0..3 {
+            assert!((d.discrete_pdf(i) - 1. / 3.).abs() < 1e-6);
+        }
+        // Unknown strategies fall back to uniform.
+        let d = create_light_sample_distribution("bogus", &scene).lookup(Point3f::default());
+        assert!((d.discrete_pdf(2) - 1. / 3.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn power() {
+        let scene = scene(&[1., 4.]);
+        let d = create_light_sample_distribution("power", &scene).lookup(Point3f::default());
+        const N: usize = 10_000;
+        let mut counts = [0; 2];
+        for i in 0..N {
+            let (light, _) = d.sample_discrete((i as Float + 0.5) / N as Float);
+            counts[light] += 1;
+        }
+        let ratio = counts[1] as Float / counts[0] as Float;
+        assert!((ratio - 4.).abs() < 0.01, "counts {:?}", counts);
+        assert!((d.discrete_pdf(1) - 0.8).abs() < 1e-5);
+    }
+
+    #[test]
+    fn spatial() {
+        let scene = scene(&[1., 1.]);
+        let ld = create_light_sample_distribution("spatial", &scene);
+        let near_first = ld.lookup(Point3f::from([1., 0., 2.]));
+        let near_second = ld.lookup(Point3f::from([19., 0., 2.]));
+        assert!(
+            near_first.discrete_pdf(0) > 0.9,
+            "{}",
+            near_first.discrete_pdf(0)
+        );
+        assert!(
+            near_second.discrete_pdf(1) > 0.9,
+            "{}",
+            near_second.discrete_pdf(1)
+        );
+        // Every light keeps a chance of being sampled.
+        assert!(near_first.discrete_pdf(1) > 0.);
+        // Lookups in the same voxel share the cached distribution.
+        assert!(Arc::ptr_eq(
+            &near_first,
+            &ld.lookup(Point3f::from([1., 0., 2.]))
+        ));
+    }
+}
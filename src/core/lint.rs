@@ -0,0 +1,898 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scene validation without rendering.  [LintAPI] implements [API] by recording what a scene
+//! declares and references, reporting problems as [Finding]s instead of building render state.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    core::{
+        api::{Error, API},
+        fileutil::resolve_filename,
+        paramset::{ParamSet, SpectrumSource, Value},
+        parser::{self, create_from_string, parse, ByteSourceTokenizer},
+        spectrum::named_spectrum,
+    },
+    Degree, Float,
+};
+
+/// The category of problem a [Finding] describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum FindingKind {
+    /// A directive that isn't part of the scene description format.
+    UnknownDirective,
+    /// Any other error that stopped the scene from being parsed.
+    Parse,
+    /// An implementation name, e.g. of a shape or material, that pbrt doesn't know.
+    UnknownType,
+    /// A parameter the directive's implementation never reads.
+    UnusedParameter,
+    /// A texture, mesh or spectrum file that doesn't exist.
+    MissingFile,
+    /// A named material, medium, texture, coordinate system or object that was never defined.
+    UndefinedReference,
+    /// A block end without a matching begin, or a block left open.
+    UnbalancedBlock,
+}
+
+impl FindingKind {
+    /// Returns the name used for this kind in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FindingKind::UnknownDirective => "unknown-directive",
+            FindingKind::Parse => "parse",
+            FindingKind::UnknownType => "unknown-type",
+            FindingKind::UnusedParameter => "unused-parameter",
+            FindingKind::MissingFile => "missing-file",
+            FindingKind::UndefinedReference => "undefined-reference",
+            FindingKind::UnbalancedBlock => "unbalanced-block",
+        }
+    }
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single problem found by [LintAPI].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finding {
+    /// The scene file the problem was found in, `-` for stdin and empty for strings.
+    pub file: String,
+    /// The category of the problem.
+    pub kind: FindingKind,
+    /// The directive the problem was found in, e.g. `Shape`.
+    pub directive: String,
+    /// Human readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.file.is_empty() {
+            write!(f, "{}: ", self.file)?;
+        }
+        write!(f, "{} [{}]: {}", self.directive, self.kind, self.message)
+    }
+}
+
+/// The blocks that must be closed by a matching directive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Block {
+    World,
+    Attribute,
+    Transform,
+    Object,
+}
+
+impl Block {
+    fn begin(&self) -> &'static str {
+        match self {
+            Block::World => "WorldBegin",
+            Block::Attribute => "AttributeBegin",
+            Block::Transform => "TransformBegin",
+            Block::Object => "ObjectBegin",
+        }
+    }
+
+    fn end(&self) -> &'static str {
+        match self {
+            Block::World => "WorldEnd",
+            Block::Attribute => "AttributeEnd",
+            Block::Transform => "TransformEnd",
+            Block::Object => "ObjectEnd",
+        }
+    }
+}
+
+/// Names defined while linting a single scene.
+#[derive(Debug, Default)]
+struct SceneState {
+    blocks: Vec<Block>,
+    coordinate_systems: HashSet<String>,
+    float_textures: HashSet<String>,
+    spectrum_textures: HashSet<String>,
+    // Material type stored by `MakeNamedMaterial`, keyed by the material's name.
+    named_materials: HashMap<String, String>,
+    named_media: HashSet<String>,
+    objects: HashSet<String>,
+    material: String,
+}
+
+/// Parameters read by all cameras.
+const CAMERA_PARAMS: &[&str] = &[
+    "frameaspectratio",
+    "screenwindow",
+    "shutteropen",
+    "shutterclose",
+];
+/// Parameters read by all integrators.
+const INTEGRATOR_PARAMS: &[&str] = &["maxadaptivesamples", "pixelbounds", "variancethreshold"];
+/// Parameters read by all textures' mappings.
+const MAPPING_PARAMS: &[&str] = &["mapping", "udelta", "uscale", "vdelta", "vscale"];
+/// Parameters read by all shapes that use curved surfaces.
+const QUADRIC_PARAMS: &[&str] = &["phimax", "radius", "zmax", "zmin"];
+
+/// Returns the parameters the implementation `name` of `directive` reads, or `None` if pbrt
+/// doesn't know `name`.  Named materials and media are checked as `Material` and
+/// `MakeNamedMedium` respectively.
+fn known_params(directive: &str, name: &str) -> Option<&'static [&'static str]> {
+    Some(match (directive, name) {
+        ("Accelerator", "bvh") => &["maxnodeprims", "splitmethod"],
+        ("Accelerator", "kdtree") => &[
+            "emptybonus",
+            "intersectcost",
+            "maxdepth",
+            "maxprims",
+            "traversalcost",
+        ],
+        ("AreaLightSource", "diffuse") => &["L", "nsamples", "samples", "scale", "twosided"],
+        ("Camera", "environment") => &[],
+        ("Camera", "orthographic") => &["focaldistance", "lensradius"],
+        ("Camera", "perspective") => &["focaldistance", "fov", "halffov", "lensradius"],
+        ("Film", "image") => &[
+            "cropwindow",
+            "diagonal",
+            "filename",
+            "maxsampleluminance",
+            "scale",
+            "weightimagefilename",
+            "xresolution",
+            "yresolution",
+        ],
+        ("Integrator", "directlighting") => &["maxdepth", "strategy"],
+        ("Integrator", "path") => &["lightsamplestrategy", "maxdepth", "rrthreshold"],
+        ("Integrator", "volpath") => &["maxdepth", "rrthreshold"],
+        ("Integrator", "whitted") => &["maxdepth"],
+        ("LightSource", "infinite") | ("LightSource", "exinfinite") => {
+            &["L", "mapname", "nsamples", "samples", "scale"]
+        }
+        ("LightSource", "point") => &["I", "from", "scale"],
+        ("MakeNamedMedium", "heterogeneous") => &[
+            "density", "g", "nx", "ny", "nz", "p0", "p1", "scale", "sigma_a", "sigma_s",
+        ],
+        ("MakeNamedMedium", "homogeneous") => &["g", "scale", "sigma_a", "sigma_s"],
+        ("Material", "") | ("Material", "none") => &[],
+        ("Material", "glass") => &[
+            "Kr",
+            "Kt",
+            "bumpmap",
+            "eta",
+            "index",
+            "remaproughness",
+            "uroughness",
+            "vroughness",
+        ],
+        ("Material", "matte") => &["Kd", "bumpmap", "sigma"],
+        ("Material", "metal") => &[
+            "bumpmap",
+            "eta",
+            "k",
+            "remaproughness",
+            "roughness",
+            "uroughness",
+            "vroughness",
+        ],
+        ("Material", "mirror") => &["Kr", "bumpmap"],
+        ("Material", "plastic") => &["Kd", "Ks", "bumpmap", "remaproughness", "roughness"],
+        ("Material", "substrate") => &[
+            "Kd",
+            "Ks",
+            "bumpmap",
+            "remaproughness",
+            "uroughness",
+            "vroughness",
+        ],
+        ("Material", "uber") => &[
+            "Kd",
+            "Kr",
+            "Ks",
+            "Kt",
+            "bumpmap",
+            "eta",
+            "index",
+            "opacity",
+            "remaproughness",
+            "roughness",
+            "uroughness",
+            "vroughness",
+        ],
+        ("PixelFilter", "box") => &["xwidth", "ywidth"],
+        ("Sampler", "02sequence") | ("Sampler", "lowdiscrepancy") | ("Sampler", "maxmindist") => {
+            &["dimensions", "pixelsamples"]
+        }
+        ("Sampler", "random") => &["pixelsamples"],
+        ("Sampler", "stratified") => &["dimensions", "jitter", "xsamples", "ysamples"],
+        ("Shape", "hyperboloid") | ("Shape", "paraboloid") | ("Shape", "sphere") => QUADRIC_PARAMS,
+        ("Shape", "loopsubdiv") => &["P", "indices", "levels", "nlevels"],
+        ("Shape", "objmesh") | ("Shape", "plymesh") => &["filename"],
+        ("Shape", "trianglemesh") => &["N", "P", "S", "indices", "st", "uv"],
+        ("Texture", "constant") => &["value"],
+        ("Texture", "dots") => &["inside", "outside"],
+        ("Texture", "fbm") | ("Texture", "wrinkled") => &["octaves", "roughness"],
+        ("Texture", "imagemap") => &[
+            "filename",
+            "gamma",
+            "maxanisotropy",
+            "scale",
+            "trilinear",
+            "wrap",
+        ],
+        ("Texture", "uv") | ("Texture", "windy") => &[],
+        _ => return None,
+    })
+}
+
+/// Returns the parameters every implementation of `directive` reads.
+fn common_params(directive: &str) -> &'static [&'static str] {
+    match directive {
+        "Camera" => CAMERA_PARAMS,
+        "Integrator" => INTEGRATOR_PARAMS,
+        "Texture" => MAPPING_PARAMS,
+        _ => &[],
+    }
+}
+
+/// `LintAPI` validates scenes without rendering them.  Parsing a scene records the names it
+/// declares and checks every reference, parameter and file against them, collecting problems as
+/// [Finding]s.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::lint::{FindingKind, LintAPI};
+///
+/// let mut lint = LintAPI::default();
+/// lint.lint_string(br#"WorldBegin NamedMaterial "red" WorldEnd"#);
+/// assert_eq!(lint.findings().len(), 1);
+/// assert_eq!(lint.findings()[0].kind, FindingKind::UndefinedReference);
+/// ```
+#[derive(Debug, Default)]
+pub struct LintAPI {
+    findings: Vec<Finding>,
+    file: String,
+    // Relative file references are resolved against the directory of the scene being linted.
+    scene_directory: PathBuf,
+    state: SceneState,
+}
+
+impl LintAPI {
+    /// Returns the problems found so far, in the order they were found.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Lints the scene file at `path`, `-` reads the scene from stdin.  Each file is checked as a
+    /// complete scene, names defined in one file aren't visible in the next.
+    pub fn lint_file<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.start(
+            &path.display().to_string(),
+            path.parent().unwrap_or_else(|| Path::new("")),
+        );
+        let res = self.parse_file(path);
+        self.finish(res);
+    }
+
+    /// Lints the scene in `data`, relative file references are resolved against the current
+    /// directory.
+    pub fn lint_string(&mut self, data: &[u8]) {
+        self.start("", Path::new(""));
+        let res = self.parse_string(data);
+        self.finish(res);
+    }
+
+    fn start(&mut self, file: &str, scene_directory: &Path) {
+        self.file = file.to_string();
+        self.scene_directory = scene_directory.to_path_buf();
+        self.state = SceneState {
+            material: "matte".to_string(),
+            ..Default::default()
+        };
+    }
+
+    fn finish(&mut self, res: Result<(), Error>) {
+        if let Err(err) = res {
+            let (kind, message) = match err {
+                Error::Parser(parser::Error::UnknownDirective(tok)) => (
+                    FindingKind::UnknownDirective,
+                    format!("\"{}\" unknown", tok),
+                ),
+                Error::Parser(err) => (FindingKind::Parse, err.to_string()),
+                err => (FindingKind::Parse, err.to_string()),
+            };
+            self.report(kind, "", message);
+        }
+        while let Some(block) = self.state.blocks.pop() {
+            self.report(
+                FindingKind::UnbalancedBlock,
+                block.begin(),
+                format!("missing {}", block.end()),
+            );
+        }
+    }
+
+    fn report<S: Into<String>>(&mut self, kind: FindingKind, directive: &str, message: S) {
+        self.findings.push(Finding {
+            file: self.file.clone(),
+            kind,
+            directive: directive.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn begin(&mut self, block: Block) {
+        self.state.blocks.push(block);
+    }
+
+    fn end(&mut self, block: Block) {
+        match self.state.blocks.iter().rposition(|&b| b == block) {
+            None => self.report(
+                FindingKind::UnbalancedBlock,
+                block.end(),
+                format!("no matching {}", block.begin()),
+            ),
+            Some(idx) => {
+                // Report, and close, any blocks opened inside this one that are still open.
+                while self.state.blocks.len() > idx + 1 {
+                    let inner = self.state.blocks.pop().expect("inner block");
+                    self.report(
+                        FindingKind::UnbalancedBlock,
+                        inner.begin(),
+                        format!("missing {} before {}", inner.end(), block.end()),
+                    );
+                }
+                self.state.blocks.pop();
+            }
+        }
+    }
+
+    /// Checks the implementation `name` of `directive` exists and reads all of `params`, along
+    /// with the files and textures `params` refer to.  `extra` lists parameters read in addition
+    /// to the implementation's.
+    fn check_params(&mut self, directive: &str, name: &str, params: &ParamSet, extra: &[&str]) {
+        let kind = if directive == "MakeNamedMaterial" {
+            "Material"
+        } else {
+            directive
+        };
+        let known = match known_params(kind, name) {
+            Some(known) => known,
+            None => {
+                self.report(
+                    FindingKind::UnknownType,
+                    directive,
+                    format!("{} type \"{}\" unknown", kind, name),
+                );
+                return;
+            }
+        };
+        for p in known.iter().chain(common_params(kind)).chain(extra) {
+            params.mark_used(p);
+        }
+        if params.report_unused() {
+            for p in params.unused() {
+                self.report(
+                    FindingKind::UnusedParameter,
+                    directive,
+                    format!("parameter \"{}\" unused by \"{}\"", p, name),
+                );
+            }
+        }
+        self.check_references(directive, params);
+    }
+
+    /// Checks the textures and spectrum files referred to by `params` exist.
+    fn check_references(&mut self, directive: &str, params: &ParamSet) {
+        let mut items: Vec<_> = params.iter().collect();
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        for psi in items {
+            if let Value::Texture(pl) = &psi.values {
+                for tex in &pl.0 {
+                    if !self.state.float_textures.contains(tex)
+                        && !self.state.spectrum_textures.contains(tex)
+                    {
+                        self.report(
+                            FindingKind::UndefinedReference,
+                            directive,
+                            format!("texture \"{}\" for \"{}\" undefined", tex, psi.name),
+                        );
+                    }
+                }
+            }
+            if let Some(SpectrumSource::SampledFiles(files)) = &psi.source {
+                for f in files {
+                    if named_spectrum(f).is_none() {
+                        self.check_file(directive, &psi.name, f);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reports a finding if `filename`, given for the parameter `param`, doesn't exist.
+    fn check_file(&mut self, directive: &str, param: &str, filename: &str) {
+        if filename.is_empty() {
+            return;
+        }
+        let mut path = resolve_filename(filename);
+        if path.is_relative() {
+            path = self.scene_directory.join(path);
+        }
+        if !path.exists() {
+            self.report(
+                FindingKind::MissingFile,
+                directive,
+                format!("file \"{}\" for \"{}\" not found", filename, param),
+            );
+        }
+    }
+
+    /// Checks the file named by the string parameter `param` exists.
+    fn check_file_param(&mut self, directive: &str, params: &ParamSet, param: &str) {
+        if let Some(Value::String(pl)) = params
+            .iter()
+            .find(|psi| psi.name == param)
+            .map(|psi| &psi.values)
+        {
+            for f in &pl.0 {
+                self.check_file(directive, param, f);
+            }
+        }
+    }
+
+    fn check_medium(&mut self, directive: &str, name: &str) {
+        if !name.is_empty() && !self.state.named_media.contains(name) {
+            self.report(
+                FindingKind::UndefinedReference,
+                directive,
+                format!("medium \"{}\" undefined", name),
+            );
+        }
+    }
+}
+
+impl API for LintAPI {
+    fn accelerator(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Accelerator", name, &params, &[]);
+    }
+    fn active_transform_all(&mut self) {}
+    fn active_transform_end_time(&mut self) {}
+    fn active_transform_start_time(&mut self) {}
+    fn area_light_source(&mut self, name: &str, params: ParamSet) {
+        self.check_params("AreaLightSource", name, &params, &[]);
+    }
+    fn attribute_begin(&mut self) {
+        self.begin(Block::Attribute);
+    }
+    fn attribute_end(&mut self) {
+        self.end(Block::Attribute);
+    }
+    fn camera(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Camera", name, &params, &[]);
+        self.state.coordinate_systems.insert("camera".to_string());
+    }
+    fn cleanup(&mut self) {}
+    fn concat_transform(&mut self, _transform: [Float; 16]) {}
+    fn coordinate_system(&mut self, name: &str) {
+        self.state.coordinate_systems.insert(name.to_string());
+    }
+    fn coordinate_system_transform(&mut self, name: &str) {
+        if !self.state.coordinate_systems.contains(name) {
+            self.report(
+                FindingKind::UndefinedReference,
+                "CoordSysTransform",
+                format!("coordinate system \"{}\" undefined", name),
+            );
+        }
+    }
+    fn film(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Film", name, &params, &[]);
+    }
+    fn identity(&mut self) {}
+    fn init(&mut self) {}
+    fn integrator(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Integrator", name, &params, &[]);
+    }
+    fn light_source(&mut self, name: &str, params: ParamSet) {
+        self.check_params("LightSource", name, &params, &[]);
+        self.check_file_param("LightSource", &params, "mapname");
+    }
+    fn look_at(&mut self, _eye: [Float; 3], _look: [Float; 3], _up: [Float; 3]) {}
+    fn make_named_material(&mut self, name: &str, params: ParamSet) {
+        let kind = params.find_one_string("type", "");
+        if kind.is_empty() {
+            self.report(
+                FindingKind::UnknownType,
+                "MakeNamedMaterial",
+                format!("no \"type\" parameter for material \"{}\"", name),
+            );
+        } else {
+            self.check_params("MakeNamedMaterial", &kind, &params, &[]);
+        }
+        self.state.named_materials.insert(name.to_string(), kind);
+    }
+    fn make_named_medium(&mut self, name: &str, params: &mut ParamSet) {
+        let kind = params.find_one_string("type", "");
+        self.check_params("MakeNamedMedium", &kind, params, &[]);
+        self.state.named_media.insert(name.to_string());
+    }
+    fn material(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Material", name, &params, &[]);
+        self.state.material = name.to_string();
+    }
+    fn medium_interface(&mut self, inside_name: &str, outside_name: &str) {
+        self.check_medium("MediumInterface", inside_name);
+        if outside_name != inside_name {
+            self.check_medium("MediumInterface", outside_name);
+        }
+    }
+    fn named_material(&mut self, name: &str) {
+        match self.state.named_materials.get(name) {
+            Some(kind) => self.state.material = kind.clone(),
+            None => self.report(
+                FindingKind::UndefinedReference,
+                "NamedMaterial",
+                format!("material \"{}\" undefined", name),
+            ),
+        }
+    }
+    fn object_begin(&mut self, name: &str) {
+        self.begin(Block::Object);
+        self.state.objects.insert(name.to_string());
+    }
+    fn object_end(&mut self) {
+        self.end(Block::Object);
+    }
+    fn object_instance(&mut self, name: &str) {
+        if !self.state.objects.contains(name) {
+            self.report(
+                FindingKind::UndefinedReference,
+                "ObjectInstance",
+                format!("object \"{}\" undefined", name),
+            );
+        }
+    }
+    fn parse_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            parse(ByteSourceTokenizer::new(io::stdin().lock()), self)?;
+        } else {
+            parse(ByteSourceTokenizer::new(File::open(path)?), self)?;
+        }
+        Ok(())
+    }
+    fn parse_string(&mut self, data: &[u8]) -> Result<(), Error> {
+        parse(create_from_string(data), self)?;
+        Ok(())
+    }
+    fn pixel_filter(&mut self, name: &str, params: ParamSet) {
+        self.check_params("PixelFilter", name, &params, &[]);
+    }
+    fn reverse_orientation(&mut self) {}
+    fn rotate(&mut self, _angle: Degree, _ax: Float, _ay: Float, _az: Float) {}
+    fn sampler(&mut self, name: &str, params: ParamSet) {
+        self.check_params("Sampler", name, &params, &[]);
+    }
+    fn scale(&mut self, _sx: Float, _sy: Float, _sz: Float) {}
+    fn shape(&mut self, name: &str, params: ParamSet) {
+        // Shapes can override the parameters of the current material.
+        let material = known_params("Material", &self.state.material).unwrap_or(&[]);
+        self.check_params("Shape", name, &params, material);
+        if name == "plymesh" || name == "objmesh" {
+            self.check_file_param("Shape", &params, "filename");
+        }
+    }
+    fn texture(&mut self, name: &str, kind: &str, texname: &str, params: ParamSet) {
+        if !["float", "color", "spectrum"].contains(&kind) {
+            self.report(
+                FindingKind::UnknownType,
+                "Texture",
+                format!("texture kind \"{}\" unknown", kind),
+            );
+            return;
+        }
+        self.check_params("Texture", texname, &params, &[]);
+        if texname == "imagemap" {
+            self.check_file_param("Texture", &params, "filename");
+        }
+        if kind == "float" {
+            self.state.float_textures.insert(name.to_string());
+        } else {
+            self.state.spectrum_textures.insert(name.to_string());
+        }
+    }
+    fn transform_begin(&mut self) {
+        self.begin(Block::Transform);
+    }
+    fn transform_end(&mut self) {
+        self.end(Block::Transform);
+    }
+    fn transform(&mut self, _transform: [Float; 16]) {}
+    fn transform_times(&mut self, _start: Float, _end: Float) {}
+    fn translate(&mut self, _dx: Float, _dy: Float, _dz: Float) {}
+    fn world_begin(&mut self) {
+        self.begin(Block::World);
+    }
+    fn world_end(&mut self) {
+        self.end(Block::World);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Lints the fixture `name` and returns its findings as (kind, directive, message) tuples.
+    fn lint(name: &str) -> Vec<(FindingKind, String, String)> {
+        let mut lint = LintAPI::default();
+        lint.lint_file(format!("src/core/testdata/lint/{}", name));
+        lint.findings()
+            .iter()
+            .map(|f| (f.kind, f.directive.clone(), f.message.clone()))
+            .collect()
+    }
+
+    fn finding(kind: FindingKind, directive: &str, message: &str) -> (FindingKind, String, String) {
+        (kind, directive.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(lint("clean.pbrt"), vec![]);
+    }
+
+    #[test]
+    fn unknown_directive() {
+        assert_eq!(
+            lint("unknown_directive.pbrt"),
+            vec![
+                finding(FindingKind::UnknownDirective, "", "\"Sphere\" unknown"),
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "WorldBegin",
+                    "missing WorldEnd"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_type() {
+        assert_eq!(
+            lint("unknown_type.pbrt"),
+            vec![
+                finding(
+                    FindingKind::UnknownType,
+                    "Camera",
+                    "Camera type \"fisheye\" unknown"
+                ),
+                finding(
+                    FindingKind::UnknownType,
+                    "Material",
+                    "Material type \"velvet\" unknown"
+                ),
+                finding(
+                    FindingKind::UnknownType,
+                    "Shape",
+                    "Shape type \"blob\" unknown"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_parameter() {
+        assert_eq!(
+            lint("unused.pbrt"),
+            vec![
+                finding(
+                    FindingKind::UnusedParameter,
+                    "Camera",
+                    "parameter \"fvo\" unused by \"perspective\""
+                ),
+                finding(
+                    FindingKind::UnusedParameter,
+                    "Material",
+                    "parameter \"sigma\" unused by \"plastic\""
+                ),
+                finding(
+                    FindingKind::UnusedParameter,
+                    "Shape",
+                    "parameter \"raduis\" unused by \"sphere\""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_file() {
+        assert_eq!(
+            lint("missing_files.pbrt"),
+            vec![
+                finding(
+                    FindingKind::MissingFile,
+                    "LightSource",
+                    "file \"missing-sky.exr\" for \"mapname\" not found"
+                ),
+                finding(
+                    FindingKind::MissingFile,
+                    "Texture",
+                    "file \"missing-wood.png\" for \"filename\" not found"
+                ),
+                finding(
+                    FindingKind::MissingFile,
+                    "Material",
+                    "file \"missing-eta.spd\" for \"eta\" not found"
+                ),
+                finding(
+                    FindingKind::MissingFile,
+                    "Shape",
+                    "file \"missing.ply\" for \"filename\" not found"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_reference() {
+        assert_eq!(
+            lint("undefined.pbrt"),
+            vec![
+                finding(
+                    FindingKind::UndefinedReference,
+                    "CoordSysTransform",
+                    "coordinate system \"camera\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "CoordSysTransform",
+                    "coordinate system \"floor\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "NamedMaterial",
+                    "material \"red\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "MediumInterface",
+                    "medium \"fog\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "MediumInterface",
+                    "medium \"air\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "Material",
+                    "texture \"checks\" for \"Kd\" undefined"
+                ),
+                finding(
+                    FindingKind::UndefinedReference,
+                    "ObjectInstance",
+                    "object \"ball\" undefined"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced_block() {
+        assert_eq!(
+            lint("unbalanced.pbrt"),
+            vec![
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "AttributeEnd",
+                    "no matching AttributeBegin"
+                ),
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "TransformBegin",
+                    "missing TransformEnd before AttributeEnd"
+                ),
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "ObjectEnd",
+                    "no matching ObjectBegin"
+                ),
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "AttributeBegin",
+                    "missing AttributeEnd"
+                ),
+                finding(
+                    FindingKind::UnbalancedBlock,
+                    "WorldBegin",
+                    "missing WorldEnd"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_are_linted_independently() {
+        let mut lint = LintAPI::default();
+        lint.lint_string(br#"WorldBegin MakeNamedMaterial "red" "string type" "matte" WorldEnd"#);
+        lint.lint_string(br#"WorldBegin NamedMaterial "red" WorldEnd"#);
+        assert_eq!(lint.findings().len(), 1, "{:?}", lint.findings());
+        assert_eq!(
+            lint.findings()[0].to_string(),
+            "NamedMaterial [undefined-reference]: material \"red\" undefined"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let finding = Finding {
+            file: "scene.pbrt".to_string(),
+            kind: FindingKind::UndefinedReference,
+            directive: "NamedMaterial".to_string(),
+            message: "material \"shiny\" undefined".to_string(),
+        };
+        let json = serde_json::to_value(&finding).expect("serialize");
+        assert_eq!(json["kind"], "undefined-reference");
+        assert_eq!(json["message"], "material \"shiny\" undefined");
+        let got: Finding = serde_json::from_value(json).expect("deserialize");
+        assert_eq!(got, finding);
+
+        // Kinds are serialized by the names used in reports.
+        for kind in &[
+            FindingKind::UnknownDirective,
+            FindingKind::Parse,
+            FindingKind::UnknownType,
+            FindingKind::UnusedParameter,
+            FindingKind::MissingFile,
+            FindingKind::UndefinedReference,
+            FindingKind::UnbalancedBlock,
+        ] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.name());
+        }
+    }
+}
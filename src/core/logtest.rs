@@ -0,0 +1,85 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test logger that lets tests assert on the messages code logs.  Only one logger can be
+//! installed per process, so every test that wants log output uses this one.
+use std::{cell::RefCell, sync::Once};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+thread_local! {
+    // Messages logged on this thread while inside `capture`.
+    static CAPTURED: RefCell<Option<Vec<(Level, String)>>> = const { RefCell::new(None) };
+}
+
+/// Prints records to stderr, unless the logging thread is capturing them.
+struct TestLogger;
+
+impl Log for TestLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= Level::Debug
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let captured = CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+            Some(messages) => {
+                messages.push((record.level(), record.args().to_string()));
+                true
+            }
+            None => false,
+        });
+        if !captured && self.enabled(record.metadata()) {
+            eprintln!("{} - {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: TestLogger = TestLogger;
+static INIT: Once = Once::new();
+
+/// Installs the test logger, if it isn't already.
+pub fn init() {
+    INIT.call_once(|| {
+        log::set_logger(&LOGGER).expect("failed to init logging");
+        log::set_max_level(LevelFilter::Debug);
+    });
+}
+
+/// Calls `f`, returning its result and the messages logged on the current thread while it ran.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<(Level, String)>) {
+    init();
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+    let r = f();
+    let messages = CAPTURED.with(|captured| captured.borrow_mut().take().unwrap_or_default());
+    (r, messages)
+}
+
+/// Calls `f` and asserts it logged exactly one warning, containing `want`.
+pub fn assert_warns<R>(want: &str, f: impl FnOnce() -> R) -> R {
+    let (r, messages) = capture(f);
+    let warnings: Vec<_> = messages
+        .iter()
+        .filter(|(level, _)| *level == Level::Warn)
+        .map(|(_, msg)| msg)
+        .collect();
+    assert!(
+        warnings.len() == 1 && warnings[0].contains(want),
+        "expected one warning containing {:?}, got {:?}",
+        want,
+        messages
+    );
+    r
+}
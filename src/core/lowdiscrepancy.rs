@@ -0,0 +1,323 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Low discrepancy point sets generated with base-2 generator matrices.  Points are randomized
+//! with digital scrambling, which preserves their stratification.
+
+use crate::{
+    core::{
+        geometry::Point2f,
+        rng::{Rng, ONE_MINUS_EPSILON},
+    },
+    Float,
+};
+
+/// Generator matrix for the van der Corput sequence, the base-2 radical inverse.  Column `i` is
+/// stored as a 32-bit value with its first row in the most significant bit.
+pub const C_VAN_DER_CORPUT: [u32; 32] = [
+    0x80000000, 0x40000000, 0x20000000, 0x10000000, 0x8000000, 0x4000000, 0x2000000, 0x1000000,
+    0x800000, 0x400000, 0x200000, 0x100000, 0x80000, 0x40000, 0x20000, 0x10000, 0x8000, 0x4000,
+    0x2000, 0x1000, 0x800, 0x400, 0x200, 0x100, 0x80, 0x40, 0x20, 0x10, 0x8, 0x4, 0x2, 0x1,
+];
+
+/// Generator matrices for the first two dimensions of the Sobol' sequence, which together form
+/// a (0,2)-sequence.
+pub const C_SOBOL: [[u32; 32]; 2] = [
+    C_VAN_DER_CORPUT,
+    [
+        0x80000000, 0xc0000000, 0xa0000000, 0xf0000000, 0x88000000, 0xcc000000, 0xaa000000,
+        0xff000000, 0x80800000, 0xc0c00000, 0xa0a00000, 0xf0f00000, 0x88880000, 0xcccc0000,
+        0xaaaa0000, 0xffff0000, 0x80008000, 0xc000c000, 0xa000a000, 0xf000f000, 0x88008800,
+        0xcc00cc00, 0xaa00aa00, 0xff00ff00, 0x80808080, 0xc0c0c0c0, 0xa0a0a0a0, 0xf0f0f0f0,
+        0x88888888, 0xcccccccc, 0xaaaaaaaa, 0xffffffff,
+    ],
+];
+
+/// Generator matrices for the second dimension of 2^m point (0,m,2)-nets whose first dimension
+/// is i / 2^m, indexed by m.  Each was found by a randomized search over matrices with the net
+/// property for the one maximizing the minimum distance between the points on the unit torus.
+/// Only the first m columns of each are used, points differing in the remaining columns would
+/// not be part of the same net.
+pub const C_MAX_MIN_DIST: [[u32; 32]; 17] = [
+    [
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x80000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x80000000, 0xc0000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xa0000000, 0x40000000, 0x80000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xc0000000, 0x40000000, 0xe0000000, 0xf0000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0,
+    ],
+    [
+        0xb8000000, 0x58000000, 0x98000000, 0xf0000000, 0x8000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0,
+    ],
+    [
+        0x98000000, 0x44000000, 0xbc000000, 0xac000000, 0x2c000000, 0xfc000000, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x82000000, 0x6c000000, 0xa2000000, 0xb2000000, 0x42000000, 0x6000000, 0x2000000, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x80000000, 0x5e000000, 0xf4000000, 0x18000000, 0x78000000, 0x20000000, 0x48000000,
+        0xe1000000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x98000000, 0x54800000, 0xb4000000, 0x35000000, 0x3c000000, 0x84000000, 0xba000000,
+        0xe8000000, 0x57800000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x80400000, 0xf5000000, 0xa7400000, 0x93000000, 0xe000000, 0xff000000, 0x9f000000,
+        0x98000000, 0xffc00000, 0x400000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xa7800000, 0x66400000, 0x47c00000, 0x50a00000, 0x1d400000, 0x48e00000, 0x1ea00000,
+        0x8c00000, 0x5f200000, 0xfe00000, 0x48600000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xb0300000, 0xc9800000, 0x38200000, 0xe1c00000, 0x59b00000, 0x64800000, 0xaeb00000,
+        0x91800000, 0xee000000, 0x3ff00000, 0x79800000, 0x200000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xf7e80000, 0x87d00000, 0x2f100000, 0xbb780000, 0x47f00000, 0xea900000, 0xd4800000,
+        0x3f500000, 0x21200000, 0xd2900000, 0x10200000, 0xcf680000, 0x2c900000, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0xfa440000, 0xb6e80000, 0x86500000, 0x5cc80000, 0x3a340000, 0xec000000, 0x3e780000,
+        0xd3380000, 0x731c0000, 0xc5a00000, 0xcf9c0000, 0x70c40000, 0x70540000, 0xe7400000, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    ],
+    [
+        0x91880000, 0xfd640000, 0xc68a0000, 0xd1dc0000, 0xcf4e0000, 0x6a780000, 0xf8f20000,
+        0xf9300000, 0xfdb20000, 0x70e80000, 0x725c0000, 0x35420000, 0x65560000, 0xed0a0000,
+        0x3da80000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0,
+    ],
+    [
+        0xac830000, 0x6d4e0000, 0x953d0000, 0x44eb0000, 0xb56d0000, 0xa86c0000, 0xaf430000,
+        0x7cab0000, 0x3c360000, 0x991c0000, 0x11d70000, 0x6b110000, 0x136e0000, 0xab010000,
+        0xb6d0000, 0xce6b0000, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0,
+    ],
+];
+
+/// Converts the 32-bit fixed point value `v` to a `Float` in [0, 1).
+#[allow(clippy::excessive_precision)]
+fn to_float(v: u32) -> Float {
+    ONE_MINUS_EPSILON.min(v as Float * 2.3283064365386963e-10)
+}
+
+/// Returns the `a`th point generated by the matrix `c`, with its digits XOR'd with `scramble`.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::lowdiscrepancy::{sample_generator_matrix, C_VAN_DER_CORPUT};
+///
+/// // The van der Corput sequence mirrors the binary digits of the index about the radix point.
+/// assert_eq!(sample_generator_matrix(&C_VAN_DER_CORPUT, 1, 0), 0.5);
+/// assert_eq!(sample_generator_matrix(&C_VAN_DER_CORPUT, 6, 0), 0.375);
+/// ```
+pub fn sample_generator_matrix(c: &[u32; 32], mut a: u32, scramble: u32) -> Float {
+    let mut v = scramble;
+    let mut i = 0;
+    while a != 0 {
+        if a & 1 != 0 {
+            v ^= c[i];
+        }
+        a >>= 1;
+        i += 1;
+    }
+    to_float(v)
+}
+
+/// Fills `p` with the first `p.len()` points generated by the matrix `c`, scrambled by
+/// `scramble`.  The points are computed incrementally in Gray code order, so they aren't in the
+/// same order as [sample_generator_matrix] produces, but together form the same set.
+///
+/// [sample_generator_matrix]: crate::core::lowdiscrepancy::sample_generator_matrix
+pub fn gray_code_sample_1d(c: &[u32; 32], scramble: u32, p: &mut [Float]) {
+    let mut v = scramble;
+    for (i, p) in p.iter_mut().enumerate() {
+        *p = to_float(v);
+        v ^= c[(i + 1).trailing_zeros() as usize];
+    }
+}
+
+/// 2D version of [gray_code_sample_1d] using the matrix `c0` for the first dimension and `c1`
+/// for the second.
+///
+/// [gray_code_sample_1d]: crate::core::lowdiscrepancy::gray_code_sample_1d
+pub fn gray_code_sample_2d(c0: &[u32; 32], c1: &[u32; 32], scramble: [u32; 2], p: &mut [Point2f]) {
+    let mut v = scramble;
+    for (i, p) in p.iter_mut().enumerate() {
+        *p = Point2f::from([to_float(v[0]), to_float(v[1])]);
+        let ctz = (i + 1).trailing_zeros() as usize;
+        v[0] ^= c0[ctz];
+        v[1] ^= c1[ctz];
+    }
+}
+
+/// Randomly permutes the blocks of `block_size` consecutive values in `samples`.
+fn shuffle_blocks<T>(samples: &mut [T], block_size: usize, rng: &mut Rng) {
+    let count = samples.len() / block_size;
+    for i in 0..count {
+        let other = i + rng.uniform_u32_threshold((count - i) as u32) as usize;
+        for j in 0..block_size {
+            samples.swap(block_size * i + j, block_size * other + j);
+        }
+    }
+}
+
+/// Fills `samples` with `n_pixel_samples` blocks of `n_samples_per_pixel_sample` scrambled van
+/// der Corput points.  The values within each block and the order of the blocks are shuffled
+/// so there's no correlation with other dimensions.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{lowdiscrepancy::van_der_corput, rng::Rng};
+///
+/// let mut rng = Rng::new(0);
+/// let mut samples = [0.; 8];
+/// van_der_corput(1, 8, &mut samples, &mut rng);
+/// // Each of the 8 strata of [0, 1) has one point.
+/// let mut strata: Vec<_> = samples.iter().map(|s| (s * 8.) as usize).collect();
+/// strata.sort();
+/// assert_eq!(strata, (0..8).collect::<Vec<_>>());
+/// ```
+pub fn van_der_corput(
+    n_samples_per_pixel_sample: usize,
+    n_pixel_samples: usize,
+    samples: &mut [Float],
+    rng: &mut Rng,
+) {
+    let scramble = rng.uniform_u32();
+    let total_samples = n_samples_per_pixel_sample * n_pixel_samples;
+    gray_code_sample_1d(&C_VAN_DER_CORPUT, scramble, &mut samples[..total_samples]);
+    // Randomly shuffle 1D sample points
+    for block in samples[..total_samples].chunks_mut(n_samples_per_pixel_sample) {
+        shuffle_blocks(block, 1, rng);
+    }
+    shuffle_blocks(
+        &mut samples[..total_samples],
+        n_samples_per_pixel_sample,
+        rng,
+    );
+}
+
+/// 2D version of [van_der_corput] that generates scrambled points from the (0,2)-sequence
+/// formed by the first two Sobol' dimensions.
+///
+/// [van_der_corput]: crate::core::lowdiscrepancy::van_der_corput
+pub fn sobol_2d(
+    n_samples_per_pixel_sample: usize,
+    n_pixel_samples: usize,
+    samples: &mut [Point2f],
+    rng: &mut Rng,
+) {
+    let scramble = [rng.uniform_u32(), rng.uniform_u32()];
+    let total_samples = n_samples_per_pixel_sample * n_pixel_samples;
+    gray_code_sample_2d(
+        &C_SOBOL[0],
+        &C_SOBOL[1],
+        scramble,
+        &mut samples[..total_samples],
+    );
+    // Randomly shuffle 2D sample points
+    for block in samples[..total_samples].chunks_mut(n_samples_per_pixel_sample) {
+        shuffle_blocks(block, 1, rng);
+    }
+    shuffle_blocks(
+        &mut samples[..total_samples],
+        n_samples_per_pixel_sample,
+        rng,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_code_matches_generator_matrix() {
+        let mut p = [0.; 16];
+        gray_code_sample_1d(&C_SOBOL[1], 0x1234_5678, &mut p);
+        let mut want: Vec<_> = (0..16)
+            .map(|a| sample_generator_matrix(&C_SOBOL[1], a, 0x1234_5678))
+            .collect();
+        let mut got = p.to_vec();
+        want.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        got.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn sobol_2d_is_elementary() {
+        // Every 16 point block of a (0,2)-sequence has one point in each elementary interval of
+        // area 1/16, for all shapes of interval.
+        let mut rng = Rng::new(1);
+        let mut samples = [Point2f::default(); 16];
+        sobol_2d(1, 16, &mut samples, &mut rng);
+        for log_x in 0..=4 {
+            let (nx, ny) = (1 << log_x, 1 << (4 - log_x));
+            let mut cells = vec![0; 16];
+            for p in &samples {
+                let x = (p.x * nx as Float) as usize;
+                let y = (p.y * ny as Float) as usize;
+                cells[y * nx + x] += 1;
+            }
+            assert_eq!(cells, vec![1; 16], "{}x{} intervals", nx, ny);
+        }
+    }
+
+    #[test]
+    fn max_min_dist_nets() {
+        // The points (i / 2^m, C i) have one point in each elementary interval of area 2^-m.
+        for (m, c) in C_MAX_MIN_DIST.iter().enumerate().take(11) {
+            let n = 1 << m;
+            for log_x in 0..=m {
+                let (nx, ny) = (1 << log_x, 1 << (m - log_x));
+                let mut cells = vec![0; n];
+                for i in 0..n {
+                    let x = i * nx / n;
+                    let y = (sample_generator_matrix(c, i as u32, 0) * ny as Float) as usize;
+                    cells[y * nx + x] += 1;
+                }
+                assert_eq!(cells, vec![1; n], "m = {}, {}x{} intervals", m, nx, ny);
+            }
+        }
+    }
+}
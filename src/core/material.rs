@@ -0,0 +1,186 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Defines the trait all materials implement.  See [materials] for the currently implemented
+//! materials.
+//!
+//! [materials]: crate::materials
+
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    core::{
+        geometry::{cross, Normal3f, Vector2f, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        texture::Texture,
+    },
+    Float,
+};
+
+/// TransportMode indicates whether the quantity being carried along a path is radiance, i.e. the
+/// path started at the camera, or importance, i.e. the path started at a light.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransportMode {
+    /// Path started at the camera.
+    Radiance,
+    /// Path started at a light.
+    Importance,
+}
+
+/// Material is the interface for describing how light scatters at a surface.
+pub trait Material: Debug + Send + Sync {
+    /// Sets `si.bsdf` to the scattering functions of this material at the hit point `si`.
+    /// `allow_multiple_lobes` indicates whether the material should use BxDFs that aggregate
+    /// multiple types of scattering into one, when such BxDFs are available.
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        mode: TransportMode,
+        allow_multiple_lobes: bool,
+    );
+}
+
+/// Perturbs the shading geometry of `si` by the displacement texture `d`, giving the appearance
+/// of small scale surface detail without changing the surface's geometry.  Materials call this
+/// before computing their BSDF when they've been given a "bumpmap" texture.
+///
+/// The displacement's partial derivatives are estimated with forward differences in u and v.
+/// `SurfaceInteraction` doesn't track ray differentials, so a fixed small offset is used for the
+/// differences.
+pub fn bump(d: &Arc<dyn Texture<Float>>, si: &mut SurfaceInteraction) {
+    // Offset used to compute the forward differences of the displacement.
+    const DELTA: Float = 0.0005;
+    // Returns a copy of `si`'s geometry moved by `dpd` along the surface, with its normal tilted
+    // by `dnd` and its (u, v) offset by `duv`, for evaluating `d` at the shifted point.
+    let shifted = |dpd: Vector3f, dnd: Normal3f, duv: Vector2f| -> SurfaceInteraction {
+        let n = Vector3f::from(cross(si.shading.dpdu, si.shading.dpdv)) + Vector3f::from(dnd);
+        SurfaceInteraction {
+            it: Interaction {
+                p: si.p + dpd,
+                n: Normal3f::from(n.normalize()),
+                ..si.it.clone()
+            },
+            uv: si.uv + duv,
+            dpdu: si.dpdu,
+            dpdv: si.dpdv,
+            dndu: si.dndu,
+            dndv: si.dndv,
+            shading: si.shading.clone(),
+            ..Default::default()
+        }
+    };
+
+    // Shift _si_ _du_ in the $u$ direction
+    let du = DELTA;
+    let u_displace = d.evaluate(&shifted(
+        si.shading.dpdu * du,
+        si.dndu * du,
+        Vector2f::from([du, 0.]),
+    ));
+    // Shift _si_ _dv_ in the $v$ direction
+    let dv = DELTA;
+    let v_displace = d.evaluate(&shifted(
+        si.shading.dpdv * dv,
+        si.dndv * dv,
+        Vector2f::from([0., dv]),
+    ));
+    let displace = d.evaluate(si);
+
+    // Compute bump-mapped differential geometry
+    let n = Vector3f::from(si.shading.n);
+    let dpdu = si.shading.dpdu
+        + n * ((u_displace - displace) / du)
+        + Vector3f::from(si.shading.dndu) * displace;
+    let dpdv = si.shading.dpdv
+        + n * ((v_displace - displace) / dv)
+        + Vector3f::from(si.shading.dndv) * displace;
+    let (dndu, dndv) = (si.shading.dndu, si.shading.dndv);
+    si.set_shading_geometry(dpdu, dpdv, dndu, dndv, false);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::bump;
+    use crate::{
+        core::{
+            geometry::{dot, Normal3f, Point2f, Vector3f},
+            interaction::{Interaction, Shading, SurfaceInteraction},
+            texture::Texture,
+        },
+        textures::constant::ConstantTexture,
+        Float,
+    };
+
+    /// Displacement increasing linearly with u.
+    #[derive(Debug)]
+    struct Ramp(Float);
+
+    impl Texture<Float> for Ramp {
+        fn evaluate(&self, si: &SurfaceInteraction) -> Float {
+            self.0 * si.uv.x
+        }
+    }
+
+    /// A point in the middle of the unit quad in the xy plane, parameterized by x and y.
+    fn quad() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 0., 1.]);
+        let dpdu = Vector3f::from([1., 0., 0.]);
+        let dpdv = Vector3f::from([0., 1., 0.]);
+        SurfaceInteraction {
+            it: Interaction {
+                p: [0.5, 0.5, 0.].into(),
+                n,
+                ..Default::default()
+            },
+            uv: Point2f::from([0.5, 0.5]),
+            dpdu,
+            dpdv,
+            shading: Shading {
+                n,
+                dpdu,
+                dpdv,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ramp_tilts_normal() {
+        let k = 0.5;
+        let mut si = quad();
+        let d: Arc<dyn Texture<Float>> = Arc::new(Ramp(k));
+        bump(&d, &mut si);
+        // The bumped surface is z = k * x, whose normal is (-k, 0, 1) normalized.
+        let want = Vector3f::from([-k, 0., 1.]).normalize();
+        let got = Vector3f::from(si.shading.n);
+        assert!(dot(got, want) > 1. - 1e-5, "got {:?} want {:?}", got, want);
+        assert!((dot(got, Vector3f::from([0., 0., 1.])).acos() - k.atan()).abs() < 1e-3);
+        // Bumping only changes the shading geometry.
+        assert_eq!(si.n, Normal3f::from([0., 0., 1.]));
+    }
+
+    #[test]
+    fn constant_changes_nothing() {
+        let mut si = quad();
+        let d: Arc<dyn Texture<Float>> = Arc::new(ConstantTexture::new(0.25));
+        bump(&d, &mut si);
+        let want = quad();
+        assert_eq!(si.shading.n, want.shading.n);
+        assert_eq!(si.shading.dpdu, want.shading.dpdu);
+        assert_eq!(si.shading.dpdv, want.shading.dpdv);
+    }
+}
@@ -16,18 +16,253 @@
 
 use std::{fmt::Debug, sync::Arc};
 
-// TODO(wathiede): This is a virtual base class in C++, can we make it a trait?  How do you have a
-// collection of trait objects?
-/// Stub type for flushing out [PbrtAPI].  TODO(wathiede): actually implement and document.
+use crate::{
+    core::{
+        geometry::{coordinate_system, dot, spherical_direction, Point2f, Ray, Vector3f},
+        interaction::MediumInteraction,
+        sampler::Sampler,
+        spectrum::Spectrum,
+    },
+    float::consts::{FRAC_1_PI, PI},
+    Float,
+};
+
+/// Medium is the interface for regions of space filled with particles that absorb and scatter
+/// light.
+pub trait Medium: Debug + Send + Sync {
+    /// Returns the fraction of light transmitted along `ray` from its origin to `ray.t_max`.
+    /// Media that can't compute it in closed form return a stochastic estimate using `sampler`.
+    fn tr(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Spectrum;
+
+    /// Samples a scattering event along `ray`, between its origin and `ray.t_max`.  Returns the
+    /// interaction where scattering occurs, or `None` if the ray passes through, along with the
+    /// transmittance up to that point divided by the probability of the sample.  When scattering
+    /// occurs the value also includes the scattering coefficient.
+    fn sample(&self, ray: &Ray, sampler: &mut dyn Sampler)
+        -> (Spectrum, Option<MediumInteraction>);
+}
+
+/// PhaseFunction describes the distribution of directions light scatters in at a point in a
+/// medium.  Like BSDFs, both directions point away from the point.
+pub trait PhaseFunction: Debug + Send + Sync {
+    /// Returns the density of light arriving from `wi` that is scattered towards `wo`.
+    fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float;
+
+    /// Samples an incident direction for the outgoing direction `wo` using the uniform sample
+    /// `u`.  Returns the value of the phase function, which is also the sample's density, and the
+    /// direction.
+    fn sample_p(&self, wo: &Vector3f, u: Point2f) -> (Float, Vector3f);
+}
+
+/// Evaluates the Henyey-Greenstein phase function for the cosine of the angle between the
+/// directions, `cos_theta`, and asymmetry `g`.
+///
+/// # Examples
+/// ```
+/// use pbrt::{core::medium::phase_hg, float::consts::PI};
 ///
-/// [PbrtAPI]: crate::core::api::PbrtAPI
-pub trait Medium: Debug {}
+/// // Isotropic scattering.
+/// assert!((phase_hg(0.3, 0.) - 1. / (4. * PI)).abs() < 1e-6);
+/// // Forward scattering favors directions with cosines near -1, as wo points away from the
+/// // scattering point.
+/// assert!(phase_hg(-0.9, 0.5) > phase_hg(0.9, 0.5));
+/// ```
+pub fn phase_hg(cos_theta: Float, g: Float) -> Float {
+    let denom = 1. + g * g + 2. * g * cos_theta;
+    FRAC_1_PI / 4. * (1. - g * g) / (denom * denom.sqrt())
+}
+
+/// HenyeyGreenstein is the phase function with a single parameter `g` in (-1, 1), the average
+/// cosine of the scattering angle.  Negative values give back scattering, zero isotropic
+/// scattering, and positive values forward scattering.
+#[derive(Clone, Copy, Debug)]
+pub struct HenyeyGreenstein {
+    g: Float,
+}
+
+impl HenyeyGreenstein {
+    /// Create a new `HenyeyGreenstein` phase function with asymmetry `g`.
+    pub fn new(g: Float) -> HenyeyGreenstein {
+        HenyeyGreenstein { g }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        phase_hg(dot(*wo, *wi), self.g)
+    }
+
+    fn sample_p(&self, wo: &Vector3f, u: Point2f) -> (Float, Vector3f) {
+        // Compute $\cos \theta$ for Henyey--Greenstein sample
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u[0]
+        } else {
+            let sqr_term = (1. - g * g) / (1. + g - 2. * g * u[0]);
+            -(1. + g * g - sqr_term * sqr_term) / (2. * g)
+        };
 
-#[derive(Debug, Default)]
-/// MediumInterface defines the border between two media.
+        // Compute direction _wi_ for Henyey--Greenstein sample
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * u[1];
+        let (v1, v2) = coordinate_system(*wo);
+        let wi = spherical_direction(sin_theta, cos_theta, phi, v1, v2, *wo);
+        (phase_hg(cos_theta, g), wi)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// MediumInterface defines the border between two media.  `None` represents vacuum, so the
+/// default interface has vacuum on both sides.
 pub struct MediumInterface {
     /// The `Medium` inside the object.
     pub inside: Option<Arc<dyn Medium>>,
     /// The `Medium` outside the object.
     pub outside: Option<Arc<dyn Medium>>,
 }
+
+impl MediumInterface {
+    /// Create a `MediumInterface` with `medium` on both sides.
+    pub fn new_both(medium: Arc<dyn Medium>) -> MediumInterface {
+        MediumInterface {
+            inside: Some(Arc::clone(&medium)),
+            outside: Some(medium),
+        }
+    }
+
+    /// Returns true if the media on either side differ, compared by identity rather than by
+    /// their scattering properties.
+    pub fn is_medium_transition(&self) -> bool {
+        match (&self.inside, &self.outside) {
+            (Some(inside), Some(outside)) => !Arc::ptr_eq(inside, outside),
+            (None, None) => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{rng::Rng, sampling::uniform_sample_sphere};
+
+    /// A medium that doesn't attenuate or scatter light.
+    #[derive(Debug)]
+    struct Clear;
+
+    impl Medium for Clear {
+        fn tr(&self, _ray: &Ray, _sampler: &mut dyn Sampler) -> Spectrum {
+            Spectrum::new(1.)
+        }
+
+        fn sample(
+            &self,
+            _ray: &Ray,
+            _sampler: &mut dyn Sampler,
+        ) -> (Spectrum, Option<MediumInteraction>) {
+            (Spectrum::new(1.), None)
+        }
+    }
+
+    #[test]
+    fn medium_transition() {
+        let medium: Arc<dyn Medium> = Arc::new(Clear);
+        assert!(!MediumInterface::default().is_medium_transition());
+        assert!(!MediumInterface::new_both(Arc::clone(&medium)).is_medium_transition());
+        let inside_only = MediumInterface {
+            inside: Some(Arc::clone(&medium)),
+            outside: None,
+        };
+        assert!(inside_only.is_medium_transition());
+        // Media are compared by identity, two identical media are still a transition.
+        let other: Arc<dyn Medium> = Arc::new(Clear);
+        let two = MediumInterface {
+            inside: Some(medium),
+            outside: Some(other),
+        };
+        assert!(two.is_medium_transition());
+    }
+
+    #[test]
+    fn hg_normalized() {
+        let mut rng = Rng::new(0);
+        let wo = Vector3f::from([0.3, -0.4, 0.5]).normalize();
+        for &g in &[-0.7, -0.2, 0., 0.3, 0.8] {
+            let hg = HenyeyGreenstein::new(g);
+            // Integrate over the sphere with uniformly distributed directions.
+            const N: usize = 100_000;
+            let sum: Float = (0..N)
+                .map(|_| {
+                    let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+                    hg.p(&wo, &uniform_sample_sphere(u))
+                })
+                .sum();
+            let integral = sum * 4. * PI / N as Float;
+            assert!(
+                (integral - 1.).abs() < 0.05,
+                "g {} integral {}",
+                g,
+                integral
+            );
+        }
+    }
+
+    #[test]
+    fn hg_sample_matches_p() {
+        let wo = Vector3f::from([0., 0.6, 0.8]);
+        for &g in &[-0.5, 0., 0.5] {
+            let hg = HenyeyGreenstein::new(g);
+            let mut mean_cos = 0.;
+            const N: usize = 32;
+            for i in 0..N {
+                for j in 0..N {
+                    let u = Point2f::from([
+                        (i as Float + 0.5) / N as Float,
+                        (j as Float + 0.5) / N as Float,
+                    ]);
+                    let (pdf, wi) = hg.sample_p(&wo, u);
+                    assert!((wi.length() - 1.).abs() < 1e-4);
+                    assert!((pdf - hg.p(&wo, &wi)).abs() < 1e-3 * pdf.max(1.));
+                    mean_cos += dot(wo, wi);
+                }
+            }
+            // The mean cosine of the scattering angle is g, and wo points the other way.
+            mean_cos /= (N * N) as Float;
+            assert!((mean_cos + g).abs() < 0.02, "g {} mean cos {}", g, mean_cos);
+        }
+    }
+
+    #[test]
+    fn hg_isotropic() {
+        // With g = 0 every pair of directions is equally likely.
+        let hg = HenyeyGreenstein::new(0.);
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let wo = uniform_sample_sphere([rng.uniform_float(), rng.uniform_float()].into());
+            let wi = uniform_sample_sphere([rng.uniform_float(), rng.uniform_float()].into());
+            assert!((hg.p(&wo, &wi) - 1. / (4. * PI)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn hg_isotropic_sampling_is_uniform() {
+        // With g = 0, directions are uniform on the sphere: each octant gets an eighth of them.
+        let hg = HenyeyGreenstein::new(0.);
+        let wo = Vector3f::from([1., 0., 0.]);
+        let mut rng = Rng::new(0);
+        let mut octants = [0usize; 8];
+        const N: usize = 80_000;
+        for _ in 0..N {
+            let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+            let (pdf, wi) = hg.sample_p(&wo, u);
+            assert!((pdf - FRAC_1_PI / 4.).abs() < 1e-6);
+            let i =
+                (wi.x > 0.) as usize | ((wi.y > 0.) as usize) << 1 | ((wi.z > 0.) as usize) << 2;
+            octants[i] += 1;
+        }
+        for (i, &n) in octants.iter().enumerate() {
+            let frac = n as Float / N as Float;
+            assert!((frac - 0.125).abs() < 0.01, "octant {} has {}", i, frac);
+        }
+    }
+}
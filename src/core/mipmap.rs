@@ -13,13 +13,25 @@
 // limitations under the License.
 
 //! Module mimmap provides tools for building image pyramids for efficient texture lookups.
+use std::{
+    fmt::Debug,
+    ops::{Add, Mul},
+};
+
 use lazy_static::lazy_static;
 
-use crate::{core::geometry::Point2i, Float};
+use crate::{
+    core::{
+        geometry::{Point2f, Point2i, Vector2f},
+        spectrum::CoefficientSpectrum,
+    },
+    float::consts::PI,
+    Float,
+};
 
 /// ImageWrap describes the mipmap sampling behavior when the sample is outside the range of [0,
 /// 1].
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ImageWrap {
     /// Wrap around.
     Repeat,
@@ -29,6 +41,34 @@ pub enum ImageWrap {
     Clamp,
 }
 
+/// Texel is implemented by the types a `MIPMap` can store.  Filtering computes weighted sums of
+/// texels, and the resampling filter can ring below zero, so texels must be clampable.
+pub trait Texel:
+    Clone + Debug + Default + Add<Output = Self> + Mul<Float, Output = Self> + Send + Sync
+{
+    /// Returns a copy of `self` with negative values set to zero.
+    fn clamp_nonnegative(&self) -> Self;
+}
+
+impl Texel for Float {
+    fn clamp_nonnegative(&self) -> Float {
+        self.max(0.)
+    }
+}
+
+impl<const N: usize> Texel for CoefficientSpectrum<N> {
+    fn clamp_nonnegative(&self) -> CoefficientSpectrum<N> {
+        CoefficientSpectrum::clamp_nonnegative(self)
+    }
+}
+
+/// One level of the image pyramid.
+#[derive(Debug)]
+struct Level<T> {
+    resolution: Point2i,
+    texels: Vec<T>,
+}
+
 /// MIPMap holds an image pyramid to efficiently sample texture maps at various resolutions.
 #[derive(Debug)]
 pub struct MIPMap<T> {
@@ -37,7 +77,7 @@ pub struct MIPMap<T> {
     wrap_mode: ImageWrap,
     resolution: Point2i,
     // TODO(wathiede): C++ uses a BlockedArray here, which is fancy.  Fake it for the time being.
-    pyramid: Vec<Vec<T>>,
+    pyramid: Vec<Level<T>>,
 }
 
 const WEIGHT_LUT_SIZE: usize = 128;
@@ -51,18 +91,400 @@ lazy_static! {
         .collect::<Vec<Float>>();
 }
 
-impl<T> MIPMap<T> {
-    // TODO(wathiede): add builder when we need to set do_trilinear, max_anisotropy, or wrap_mode.
-    /// Create a MIPMap for the texture represented by `data` of size `resolution`.
-    pub fn new(resolution: &Point2i, data: Vec<T>) -> Self {
-        let _ = MIPMap {
-            resolution: *resolution,
-            // TODO(wathiede): build actual pyramid,
-            pyramid: vec![data],
-            do_trilinear: false,
-            max_anisotropy: 8.,
-            wrap_mode: ImageWrap::Repeat,
+/// Filter weights for the four texels of the original image nearest to one texel of a resampled
+/// image.
+#[derive(Debug)]
+struct ResampleWeight {
+    first_texel: isize,
+    weight: [Float; 4],
+}
+
+/// Windowed sinc filter with `tau` cycles of the sinc function, evaluated at `x` in [-1, 1].
+fn lanczos(x: Float, tau: Float) -> Float {
+    let x = x.abs();
+    if x < 1e-5 {
+        return 1.;
+    }
+    if x > 1. {
+        return 0.;
+    }
+    let x = x * PI;
+    let s = (x * tau).sin() / (x * tau);
+    let lanczos = x.sin() / x;
+    s * lanczos
+}
+
+/// Returns the weights for resampling a row of `old_res` texels to `new_res` texels.
+fn resample_weights(old_res: isize, new_res: isize) -> Vec<ResampleWeight> {
+    let filter_width = 2.;
+    (0..new_res)
+        .map(|i| {
+            // Compute image resampling weights for _i_th texel
+            let center = (i as Float + 0.5) * old_res as Float / new_res as Float;
+            let first_texel = ((center - filter_width) + 0.5).floor() as isize;
+            let mut weight = [0.; 4];
+            for (j, w) in weight.iter_mut().enumerate() {
+                let pos = (first_texel + j as isize) as Float + 0.5;
+                *w = lanczos((pos - center) / filter_width, 2.);
+            }
+            // Normalize filter weights for texel resampling
+            let inv_sum_wts = 1. / weight.iter().sum::<Float>();
+            weight.iter_mut().for_each(|w| *w *= inv_sum_wts);
+            ResampleWeight {
+                first_texel,
+                weight,
+            }
+        })
+        .collect()
+}
+
+/// Applies `wrap_mode` to the texel coordinate `i` in a row of `n` texels, `None` means the
+/// texel is black.
+fn wrap_index(i: isize, n: isize, wrap_mode: ImageWrap) -> Option<isize> {
+    match wrap_mode {
+        ImageWrap::Repeat => Some(i.rem_euclid(n)),
+        ImageWrap::Clamp => Some(i.max(0).min(n - 1)),
+        ImageWrap::Black if i < 0 || i >= n => None,
+        ImageWrap::Black => Some(i),
+    }
+}
+
+/// Returns the weighted sum of two texels.
+fn lerp_texel<T: Texel>(t: Float, v1: T, v2: T) -> T {
+    v1 * (1. - t) + v2 * t
+}
+
+impl<T: Texel> MIPMap<T> {
+    /// Create a MIPMap for the texture represented by `data` of size `resolution`, stored a row
+    /// at a time.  Images whose dimensions aren't powers of two are resampled up to the next
+    /// power of two.  Filtered lookups use trilinear interpolation when `do_trilinear` is set,
+    /// otherwise an elliptically weighted average whose eccentricity is limited to
+    /// `max_anisotropy`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::mipmap::{ImageWrap, MIPMap};
+    ///
+    /// let mipmap = MIPMap::new(
+    ///     &[4, 2].into(),
+    ///     vec![0., 1., 2., 3., 4., 5., 6., 7.],
+    ///     false,
+    ///     8.,
+    ///     ImageWrap::Repeat,
+    /// );
+    /// assert_eq!(mipmap.levels(), 3);
+    /// assert_eq!(mipmap.texel(0, 1, 1), 5.);
+    /// // The coarsest level is the average of the whole image.
+    /// assert_eq!(mipmap.texel(2, 0, 0), 3.5);
+    /// ```
+    pub fn new(
+        resolution: &Point2i,
+        data: Vec<T>,
+        do_trilinear: bool,
+        max_anisotropy: Float,
+        wrap_mode: ImageWrap,
+    ) -> Self {
+        let mut resolution = *resolution;
+        let mut data = data;
+        if !(resolution.x as usize).is_power_of_two() || !(resolution.y as usize).is_power_of_two()
+        {
+            // Resample image to power-of-two resolution
+            let res_pow2 = Point2i::from([
+                (resolution.x as usize).next_power_of_two() as isize,
+                (resolution.y as usize).next_power_of_two() as isize,
+            ]);
+            // Resample image in $s$ direction
+            let s_weights = resample_weights(resolution.x, res_pow2.x);
+            let mut resampled = vec![T::default(); (res_pow2.x * res_pow2.y) as usize];
+            for t in 0..resolution.y {
+                for (s, sw) in s_weights.iter().enumerate() {
+                    // Compute texel $(s,t)$ in $s$-zoomed image
+                    let mut v = T::default();
+                    for (j, w) in sw.weight.iter().enumerate() {
+                        if let Some(orig_s) =
+                            wrap_index(sw.first_texel + j as isize, resolution.x, wrap_mode)
+                        {
+                            v = v + data[(t * resolution.x + orig_s) as usize].clone() * *w;
+                        }
+                    }
+                    resampled[(t * res_pow2.x) as usize + s] = v;
+                }
+            }
+            // Resample image in $t$ direction
+            let t_weights = resample_weights(resolution.y, res_pow2.y);
+            let mut work = vec![T::default(); res_pow2.y as usize];
+            for s in 0..res_pow2.x {
+                for (t, tw) in t_weights.iter().enumerate() {
+                    let mut v = T::default();
+                    for (j, w) in tw.weight.iter().enumerate() {
+                        if let Some(offset) =
+                            wrap_index(tw.first_texel + j as isize, resolution.y, wrap_mode)
+                        {
+                            v = v + resampled[(offset * res_pow2.x + s) as usize].clone() * *w;
+                        }
+                    }
+                    work[t] = v.clamp_nonnegative();
+                }
+                for (t, v) in work.iter().enumerate() {
+                    resampled[(t as isize * res_pow2.x + s) as usize] = v.clone();
+                }
+            }
+            resolution = res_pow2;
+            data = resampled;
+        }
+        let mut mipmap = MIPMap {
+            do_trilinear,
+            max_anisotropy,
+            wrap_mode,
+            resolution,
+            pyramid: vec![Level {
+                resolution,
+                texels: data,
+            }],
         };
-        todo!("MIPMap::new()");
+        // Initialize levels of _MIPMap_ from image
+        let n_levels = 1 + resolution.x.max(resolution.y).trailing_zeros() as usize;
+        for i in 1..n_levels {
+            // Initialize $i$th _MIPMap_ level from $i-1$st level
+            let prev = mipmap.pyramid[i - 1].resolution;
+            let res = Point2i::from([(prev.x / 2).max(1), (prev.y / 2).max(1)]);
+            let mut texels = Vec::with_capacity((res.x * res.y) as usize);
+            for t in 0..res.y {
+                for s in 0..res.x {
+                    texels.push(
+                        (mipmap.texel(i - 1, 2 * s, 2 * t)
+                            + mipmap.texel(i - 1, 2 * s + 1, 2 * t)
+                            + mipmap.texel(i - 1, 2 * s, 2 * t + 1)
+                            + mipmap.texel(i - 1, 2 * s + 1, 2 * t + 1))
+                            * 0.25,
+                    );
+                }
+            }
+            mipmap.pyramid.push(Level {
+                resolution: res,
+                texels,
+            });
+        }
+        mipmap
+    }
+
+    /// Returns the resolution of the finest level, after any resampling to a power of two.
+    pub fn resolution(&self) -> Point2i {
+        self.resolution
+    }
+
+    /// Returns the number of levels in the image pyramid.
+    pub fn levels(&self) -> usize {
+        self.pyramid.len()
+    }
+
+    /// Returns the texel at `(s, t)` in pyramid `level`, applying the wrap mode to coordinates
+    /// outside the image.
+    pub fn texel(&self, level: usize, s: isize, t: isize) -> T {
+        let l = &self.pyramid[level];
+        match (
+            wrap_index(s, l.resolution.x, self.wrap_mode),
+            wrap_index(t, l.resolution.y, self.wrap_mode),
+        ) {
+            (Some(s), Some(t)) => l.texels[(t * l.resolution.x + s) as usize].clone(),
+            _ => T::default(),
+        }
+    }
+
+    /// Bilinearly interpolates the four texels of `level` around `st`.
+    fn triangle(&self, level: usize, st: Point2f) -> T {
+        let level = level.min(self.levels() - 1);
+        let res = self.pyramid[level].resolution;
+        let s = st.x * res.x as Float - 0.5;
+        let t = st.y * res.y as Float - 0.5;
+        let (s0, t0) = (s.floor(), t.floor());
+        let (ds, dt) = (s - s0, t - t0);
+        let (s0, t0) = (s0 as isize, t0 as isize);
+        self.texel(level, s0, t0) * ((1. - ds) * (1. - dt))
+            + self.texel(level, s0, t0 + 1) * ((1. - ds) * dt)
+            + self.texel(level, s0 + 1, t0) * (ds * (1. - dt))
+            + self.texel(level, s0 + 1, t0 + 1) * (ds * dt)
+    }
+
+    /// Returns the texture filtered over a square of side `width` around `st`, by interpolating
+    /// between the two pyramid levels whose texel spacing is closest to `width`.
+    pub fn lookup(&self, st: Point2f, width: Float) -> T {
+        // Compute MIPMap level for trilinear filtering
+        let level = (self.levels() - 1) as Float + width.max(1e-8).log2();
+        // Perform trilinear interpolation at appropriate MIPMap level
+        if level < 0. {
+            self.triangle(0, st)
+        } else if level >= (self.levels() - 1) as Float {
+            self.texel(self.levels() - 1, 0, 0)
+        } else {
+            let i_level = level.floor();
+            let delta = level - i_level;
+            let i_level = i_level as usize;
+            lerp_texel(
+                delta,
+                self.triangle(i_level, st),
+                self.triangle(i_level + 1, st),
+            )
+        }
+    }
+
+    /// Returns the texture filtered over the footprint with axes `dst0` and `dst1` around `st`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::mipmap::{ImageWrap, MIPMap};
+    ///
+    /// let mipmap = MIPMap::new(&[2, 2].into(), vec![0., 1., 2., 3.], false, 8., ImageWrap::Clamp);
+    /// // An infinitesimal footprint interpolates the finest level.
+    /// let v = mipmap.lookup_differentials([0.5, 0.5].into(), [0., 0.].into(), [0., 0.].into());
+    /// assert_eq!(v, 1.5);
+    /// // A footprint covering the image averages it.
+    /// let v = mipmap.lookup_differentials([0.5, 0.5].into(), [2., 0.].into(), [0., 2.].into());
+    /// assert_eq!(v, 1.5);
+    /// ```
+    pub fn lookup_differentials(&self, st: Point2f, dst0: Vector2f, dst1: Vector2f) -> T {
+        if self.do_trilinear {
+            let width = 2.
+                * dst0
+                    .x
+                    .abs()
+                    .max(dst0.y.abs())
+                    .max(dst1.x.abs())
+                    .max(dst1.y.abs());
+            return self.lookup(st, width);
+        }
+        // Compute ellipse minor and major axes
+        let (mut dst0, mut dst1) = (dst0, dst1);
+        let length = |v: Vector2f| (v.x * v.x + v.y * v.y).sqrt();
+        if length(dst0) < length(dst1) {
+            std::mem::swap(&mut dst0, &mut dst1);
+        }
+        let major_length = length(dst0);
+        let mut minor_length = length(dst1);
+
+        // Clamp ellipse eccentricity if too large
+        if minor_length * self.max_anisotropy < major_length && minor_length > 0. {
+            let scale = major_length / (minor_length * self.max_anisotropy);
+            dst1 = Vector2f::from([dst1.x * scale, dst1.y * scale]);
+            minor_length *= scale;
+        }
+        if minor_length == 0. {
+            return self.triangle(0, st);
+        }
+
+        // Choose level of detail for EWA lookup and perform EWA filtering
+        let lod = ((self.levels() - 1) as Float + minor_length.log2()).max(0.);
+        let ilod = lod.floor();
+        let delta = lod - ilod;
+        let ilod = ilod as usize;
+        lerp_texel(
+            delta,
+            self.ewa(ilod, st, dst0, dst1),
+            self.ewa(ilod + 1, st, dst0, dst1),
+        )
+    }
+
+    /// Filters `level` with a Gaussian over the ellipse with axes `dst0` and `dst1` around `st`.
+    fn ewa(&self, level: usize, st: Point2f, dst0: Vector2f, dst1: Vector2f) -> T {
+        if level >= self.levels() {
+            return self.texel(self.levels() - 1, 0, 0);
+        }
+        // Convert EWA coordinates to appropriate scale for level
+        let res = self.pyramid[level].resolution;
+        let (w, h) = (res.x as Float, res.y as Float);
+        let (s, t) = (st.x * w - 0.5, st.y * h - 0.5);
+        let (ds0, dt0) = (dst0.x * w, dst0.y * h);
+        let (ds1, dt1) = (dst1.x * w, dst1.y * h);
+
+        // Compute ellipse coefficients to bound EWA filter region
+        let mut a = dt0 * dt0 + dt1 * dt1 + 1.;
+        let mut b = -2. * (ds0 * dt0 + ds1 * dt1);
+        let mut c = ds0 * ds0 + ds1 * ds1 + 1.;
+        let inv_f = 1. / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        // Compute the ellipse's $(s,t)$ bounding box in texture space
+        let det = -b * b + 4. * a * c;
+        let inv_det = 1. / det;
+        let u_sqrt = (det * c).sqrt();
+        let v_sqrt = (a * det).sqrt();
+        let s0 = (s - 2. * inv_det * u_sqrt).ceil() as isize;
+        let s1 = (s + 2. * inv_det * u_sqrt).floor() as isize;
+        let t0 = (t - 2. * inv_det * v_sqrt).ceil() as isize;
+        let t1 = (t + 2. * inv_det * v_sqrt).floor() as isize;
+
+        // Scan over ellipse bound and compute quadratic equation
+        let mut sum = T::default();
+        let mut sum_wts = 0.;
+        for it in t0..=t1 {
+            let tt = it as Float - t;
+            for is in s0..=s1 {
+                let ss = is as Float - s;
+                // Compute squared radius and filter texel if inside ellipse
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1. {
+                    let index = ((r2 * WEIGHT_LUT_SIZE as Float) as usize).min(WEIGHT_LUT_SIZE - 1);
+                    let weight = WEIGHT_LUT[index];
+                    sum = sum + self.texel(level, is, it) * weight;
+                    sum_wts += weight;
+                }
+            }
+        }
+        sum * (1. / sum_wts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_non_power_of_two() {
+        // A constant image stays constant when resampled.
+        let mipmap = MIPMap::new(&[3, 5].into(), vec![0.5; 15], false, 8., ImageWrap::Clamp);
+        assert_eq!(mipmap.resolution(), Point2i::from([4, 8]));
+        assert_eq!(mipmap.levels(), 4);
+        for t in 0..8 {
+            for s in 0..4 {
+                assert!((mipmap.texel(0, s, t) - 0.5).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let data = vec![1., 2., 3., 4.];
+        let repeat = MIPMap::new(&[2, 2].into(), data.clone(), false, 8., ImageWrap::Repeat);
+        assert_eq!(repeat.texel(0, -1, 0), 2.);
+        assert_eq!(repeat.texel(0, 2, 3), 3.);
+        let clamp = MIPMap::new(&[2, 2].into(), data.clone(), false, 8., ImageWrap::Clamp);
+        assert_eq!(clamp.texel(0, -1, 0), 1.);
+        assert_eq!(clamp.texel(0, 2, 3), 4.);
+        let black = MIPMap::new(&[2, 2].into(), data, false, 8., ImageWrap::Black);
+        assert_eq!(black.texel(0, -1, 0), 0.);
+        assert_eq!(black.texel(0, 1, 1), 4.);
+    }
+
+    #[test]
+    fn trilinear() {
+        let data: Vec<Float> = (0..16).map(|i| i as Float).collect();
+        let mipmap = MIPMap::new(&[4, 4].into(), data, true, 8., ImageWrap::Repeat);
+        let st = Point2f::from([0.3, 0.6]);
+        // Wide footprints reach the average of the image.
+        let avg = 7.5;
+        assert_eq!(mipmap.lookup(st, 1.), avg);
+        assert_eq!(
+            mipmap.lookup_differentials(st, [0.5, 0.].into(), [0., 0.25].into()),
+            avg
+        );
+        // Footprints between levels interpolate between them.
+        let width: Float = 0.375;
+        let delta = 2. + width.log2();
+        let v = mipmap.lookup(st, width);
+        let (fine, coarse) = (mipmap.lookup(st, 0.25), mipmap.lookup(st, 0.5));
+        let want = fine * (1. - delta) + coarse * delta;
+        assert!((v - want).abs() < 1e-4, "{} != {}", v, want);
     }
 }
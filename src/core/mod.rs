@@ -18,6 +18,7 @@
 pub mod api;
 // Public so pbrt-compare can use it.
 pub mod api_test;
+pub mod camera;
 pub mod error;
 pub mod fileutil;
 pub mod film;
@@ -25,15 +26,30 @@ pub mod filter;
 pub mod floatfile;
 pub mod geometry;
 pub mod imageio;
+pub mod integrator;
 pub mod interaction;
 pub mod light;
+pub mod lightdistrib;
+pub mod lint;
+#[cfg(test)]
+mod logtest;
+pub mod lowdiscrepancy;
+pub mod material;
 pub mod medium;
 pub mod mipmap;
+pub mod objutil;
 pub mod parallel;
 pub mod paramset;
 pub mod parser;
+pub mod plyutil;
+pub mod primitive;
+pub mod quaternion;
+pub mod reflection;
 pub mod rng;
+pub mod sampler;
 pub mod sampling;
+pub mod scene;
+pub mod shape;
 pub mod sobolmatrices;
 pub mod spectrum;
 pub mod texture;
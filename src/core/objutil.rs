@@ -0,0 +1,259 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities for reading triangle meshes from Wavefront [OBJ] files.
+//!
+//! [OBJ]: http://paulbourke.net/dataformats/obj/
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::{
+    core::geometry::{Normal3f, Point2f, Point3f},
+    Float,
+};
+
+/// Error type for reading OBJ files.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Standard `io::Error` generated.
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+    /// A statement in the file couldn't be parsed, the line number is 1-based.
+    #[error("line {0}: {1}")]
+    Parse(usize, String),
+}
+
+/// Mesh data read from an OBJ file.  `n` and `uv` are empty unless every face vertex references
+/// a normal or texture coordinate respectively.
+#[derive(Debug, Default)]
+struct ObjMesh {
+    p: Vec<Point3f>,
+    n: Vec<Normal3f>,
+    uv: Vec<Point2f>,
+    indices: Vec<usize>,
+}
+
+/// Parses the first `N` whitespace separated values of `args` as floats.
+fn parse_floats<'a, const N: usize>(
+    mut args: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<[Float; N], Error> {
+    let mut v = [0.; N];
+    for x in v.iter_mut() {
+        let arg = args
+            .next()
+            .ok_or_else(|| Error::Parse(line, format!("expected {} values", N)))?;
+        *x = arg
+            .parse()
+            .map_err(|_| Error::Parse(line, format!("bad value '{}'", arg)))?;
+    }
+    Ok(v)
+}
+
+/// Converts the 1-based, or negative relative, OBJ index `idx` into a 0-based index into a list
+/// with `len` entries.
+fn resolve_index(idx: &str, len: usize, line: usize) -> Result<usize, Error> {
+    let i: isize = idx
+        .parse()
+        .map_err(|_| Error::Parse(line, format!("bad index '{}'", idx)))?;
+    let resolved = if i < 0 { len as isize + i } else { i - 1 };
+    if resolved < 0 || resolved >= len as isize {
+        return Err(Error::Parse(line, format!("index {} out of range", idx)));
+    }
+    Ok(resolved as usize)
+}
+
+/// Reads the mesh stored in the OBJ file `r`.
+fn parse_obj<R: BufRead>(r: R) -> Result<ObjMesh, Error> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    // Each distinct position/uv/normal combination used by a face becomes a mesh vertex.
+    let mut vertices: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut vertex_map = HashMap::new();
+    let mut indices = Vec::new();
+    for (i, l) in r.lines().enumerate() {
+        let l = l?;
+        let line = i + 1;
+        let mut args = l.split_whitespace();
+        match args.next() {
+            Some("v") => positions.push(Point3f::from(parse_floats::<3>(args, line)?)),
+            Some("vn") => normals.push(Normal3f::from(parse_floats::<3>(args, line)?)),
+            Some("vt") => uvs.push(Point2f::from(parse_floats::<2>(args, line)?)),
+            Some("f") => {
+                let mut face = Vec::new();
+                for v in args {
+                    let mut refs = v.split('/');
+                    let p = resolve_index(refs.next().unwrap_or(""), positions.len(), line)?;
+                    let uv = match refs.next() {
+                        Some(idx) if !idx.is_empty() => Some(resolve_index(idx, uvs.len(), line)?),
+                        _ => None,
+                    };
+                    let n = match refs.next() {
+                        Some(idx) if !idx.is_empty() => {
+                            Some(resolve_index(idx, normals.len(), line)?)
+                        }
+                        _ => None,
+                    };
+                    let key = (p, uv, n);
+                    let idx = *vertex_map.entry(key).or_insert_with(|| {
+                        vertices.push(key);
+                        vertices.len() - 1
+                    });
+                    face.push(idx);
+                }
+                if face.len() < 3 {
+                    return Err(Error::Parse(
+                        line,
+                        format!("face with {} vertices", face.len()),
+                    ));
+                }
+                // Triangulate polygons as a fan around the first vertex.
+                for j in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[j], face[j + 1]]);
+                }
+            }
+            // TODO(wathiede): support materials from "mtllib" and "usemtl".
+            _ => (),
+        }
+    }
+    let mut mesh = ObjMesh {
+        p: vertices.iter().map(|(p, _, _)| positions[*p]).collect(),
+        indices,
+        ..Default::default()
+    };
+    if vertices.iter().all(|(_, uv, _)| uv.is_some()) {
+        mesh.uv = vertices
+            .iter()
+            .filter_map(|(_, uv, _)| uv.map(|uv| uvs[uv]))
+            .collect();
+    }
+    if vertices.iter().all(|(_, _, n)| n.is_some()) {
+        mesh.n = vertices
+            .iter()
+            .filter_map(|(_, _, n)| n.map(|n| normals[n]))
+            .collect();
+    }
+    Ok(mesh)
+}
+
+/// Reads the polygon mesh stored in the OBJ file at `path`.  Only `v`, `vn`, `vt` and `f`
+/// statements are interpreted.  The vertex positions, normals and parametric coordinates are
+/// returned along with three vertex indices per triangle, with one vertex for each distinct
+/// `v/vt/vn` combination referenced by a face.  Normals and parametric coordinates are empty if
+/// some face vertices don't have them.  Polygons with more than three vertices are split into
+/// triangles.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::objutil::read_obj;
+///
+/// let (p, n, uv, indices) = read_obj("src/shapes/testdata/cube.obj").expect("read");
+/// assert_eq!(p.len(), 8);
+/// assert!(n.is_empty() && uv.is_empty());
+/// assert_eq!(indices.len(), 3 * 12);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn read_obj<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Vec<Point3f>, Vec<Normal3f>, Vec<Point2f>, Vec<usize>), Error> {
+    let ObjMesh { p, n, uv, indices } = parse_obj(BufReader::new(File::open(path)?))?;
+    Ok((p, n, uv, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::parse_obj;
+    use crate::core::geometry::{Normal3f, Point2f, Point3f};
+
+    #[test]
+    fn cube() {
+        let obj = b"# unit cube
+mtllib cube.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+usemtl white
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 2 3 7 6
+f 3 4 8 7
+f 4 1 5 8
+";
+        let mesh = parse_obj(Cursor::new(&obj[..])).expect("read obj");
+        assert_eq!(mesh.p.len(), 8);
+        assert_eq!(mesh.indices.len(), 3 * 12);
+        assert_eq!(mesh.p[6], Point3f::from([1., 1., 1.]));
+        assert_eq!(&mesh.indices[..6], &[0, 1, 2, 0, 2, 3]);
+        assert!(mesh.n.is_empty());
+        assert!(mesh.uv.is_empty());
+    }
+
+    #[test]
+    fn vertex_attributes() {
+        let obj = b"v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+vn 0 0 -1
+f 1/1/1 2/2/1 3/3/1
+f -3/-3/-1 -1/-1/-1 -2/-2/-1
+";
+        let mesh = parse_obj(Cursor::new(&obj[..])).expect("read obj");
+        // The second face reuses positions with a different normal, so has its own vertices.
+        assert_eq!(mesh.p.len(), 6);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(mesh.uv[1], Point2f::from([1., 0.]));
+        assert_eq!(mesh.n[0], Normal3f::from([0., 0., 1.]));
+        assert_eq!(mesh.n[3], Normal3f::from([0., 0., -1.]));
+        assert_eq!(mesh.p[4], Point3f::from([0., 1., 0.]));
+
+        // Normals without texture coordinates, and a vertex missing its normal.
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3\n";
+        let mesh = parse_obj(Cursor::new(&obj[..])).expect("read obj");
+        assert!(mesh.n.is_empty());
+        assert!(mesh.uv.is_empty());
+    }
+
+    #[test]
+    fn bad_files() {
+        for obj in &[
+            &b"v 0 0\n"[..],
+            b"v 0 0 zero\n",
+            b"v 0 0 0\nv 1 0 0\nf 1 2\n",
+            b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n",
+            b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n",
+            b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n",
+        ] {
+            assert!(parse_obj(Cursor::new(obj)).is_err(), "{:?}", obj);
+        }
+    }
+}
@@ -14,9 +14,12 @@
 
 //! Utilities for dealing with parallel programming in pbrt.
 
-use std::sync::atomic::Ordering;
+use std::sync::{atomic::Ordering, OnceLock};
 
-use crate::Float;
+use log::{info, warn};
+use rayon::prelude::*;
+
+use crate::{core::geometry::Point2i, Float};
 
 #[cfg(feature = "float-as-double")]
 mod float {
@@ -97,6 +100,57 @@ impl AtomicFloat {
             }
         }
     }
+
+    /// Stores `v` in this `AtomicFloat`, replacing the current value.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::parallel::AtomicFloat;
+    ///
+    /// let af = AtomicFloat::from(8.);
+    /// af.set(-2.);
+    /// assert_eq!(-2., af.get());
+    /// ```
+    pub fn set(&self, v: Float) {
+        self.bits.store(v.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Atomically replaces the value in this `AtomicFloat` with `v` if `v` is larger.  Like [add],
+    /// it is implemented with a compare-exchange loop on the integer representation.
+    ///
+    /// [add]: AtomicFloat::add
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{core::parallel::AtomicFloat, Float};
+    /// use rayon::prelude::*;
+    ///
+    /// let af = AtomicFloat::from(0.);
+    /// (0..10000).into_par_iter().for_each(|i| {
+    ///     af.max(i as Float);
+    /// });
+    /// assert_eq!(9999., af.get());
+    ///
+    /// af.max(-1.);
+    /// assert_eq!(9999., af.get());
+    /// ```
+    pub fn max(&self, v: Float) {
+        let mut old_bits = self.bits.load(Ordering::Relaxed);
+        loop {
+            if Float::from_bits(old_bits) >= v {
+                break;
+            }
+            match self.bits.compare_exchange_weak(
+                old_bits,
+                v.to_bits(),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(x) => old_bits = x,
+            }
+        }
+    }
 }
 
 impl From<AtomicFloat> for Float {
@@ -104,3 +158,74 @@ impl From<AtomicFloat> for Float {
         Float::from_bits(af.bits.load(Ordering::Relaxed))
     }
 }
+
+// Threads asked for by the first call to `init_threads`, the only call that can take effect.
+static REQUESTED_THREADS: OnceLock<usize> = OnceLock::new();
+
+/// Configures the global rayon thread pool, which all parallel work in pbrt runs on, to use `n`
+/// threads.  When `n` is zero one thread per core is used.  [API::init] calls this with
+/// `Options::num_threads`.  The global pool can only be configured once, so only the first call
+/// has an effect, and later calls asking for a different number of threads log a warning.  If
+/// parallel work was done before the first call, rayon already built the pool with its defaults
+/// and a warning is logged too.  Code sharing a process with others, like tests, should render
+/// inside `rayon::ThreadPool::install` to pick its own thread count.
+///
+/// [API::init]: crate::core::api::API::init
+pub fn init_threads(n: usize) {
+    let mut first = false;
+    let requested = *REQUESTED_THREADS.get_or_init(|| {
+        first = true;
+        n
+    });
+    if !first {
+        if requested != n {
+            warn!(
+                "Thread pool already configured for {} threads, ignoring request for {}",
+                requested, n
+            );
+        }
+        return;
+    }
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(n)
+        .build_global()
+    {
+        Ok(()) => info!("Using {} threads", rayon::current_num_threads()),
+        Err(err) => warn!(
+            "Unable to use {} threads, keeping {}: {}",
+            n,
+            rayon::current_num_threads(),
+            err
+        ),
+    }
+}
+
+/// Calls `func` once for each point in the half-open range `[0, count.x) x [0, count.y)`.  Work
+/// is spread across the current rayon thread pool, so `func` may be called concurrently and in any
+/// order.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2i, parallel::parallel_for_2d};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// let visited = AtomicUsize::new(0);
+/// parallel_for_2d(
+///     |_p| {
+///         visited.fetch_add(1, Ordering::Relaxed);
+///     },
+///     Point2i::from([3, 4]),
+/// );
+/// assert_eq!(visited.load(Ordering::Relaxed), 12);
+/// ```
+pub fn parallel_for_2d<F>(func: F, count: Point2i)
+where
+    F: Fn(Point2i) + Sync + Send,
+{
+    if count.x <= 0 || count.y <= 0 {
+        return;
+    }
+    (0..count.x * count.y)
+        .into_par_iter()
+        .for_each(|i| func(Point2i::from([i % count.x, i / count.x])));
+}
@@ -18,26 +18,31 @@
 //! Generic storage types created by parser and passed to factory functions when building a scene.
 
 use std::{
-    cell::RefCell,
     collections::HashMap,
-    fmt::{Debug, Formatter, Result},
+    fmt::{Debug, Display, Formatter, Result},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use log::info;
+use log::{error, info, warn};
+use thiserror::Error;
 
 use crate::{
     core::{
         geometry::{Normal3f, Point2f, Point3f, Vector2f, Vector3f},
-        spectrum::Spectrum,
+        spectrum::{blackbody_normalized, cie_lambda, named_spectrum, Spectrum, SpectrumType},
         texture::Texture,
     },
+    textures::constant::ConstantTexture,
     Float,
 };
 
 pub mod testutils;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq)]
 pub struct ParamList<T>(pub Vec<T>);
 
@@ -47,24 +52,30 @@ impl<T> From<Vec<T>> for ParamList<T> {
     }
 }
 
+/// Maximum number of values printed by `ParamList`'s `Debug` implementation, longer lists are
+/// truncated so logging large meshes or spectra stays readable.
+const PARAM_LIST_DEBUG_LIMIT: usize = 8;
+
 impl<T> Debug for ParamList<T>
 where
     T: Debug,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let v = &self.0;
-        if v.is_empty() {
-            write!(f, "<>")?;
+        write!(f, "<")?;
+        for (i, v) in self.0.iter().take(PARAM_LIST_DEBUG_LIMIT).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:?}", v)?;
         }
-        let mut it = v.iter();
-        write!(f, "<{:?}", it.next().unwrap())?;
-        for i in it {
-            write!(f, " {:?}", i)?;
+        if self.0.len() > PARAM_LIST_DEBUG_LIMIT {
+            write!(f, " … (+{} more)", self.0.len() - PARAM_LIST_DEBUG_LIMIT)?;
         }
         write!(f, ">")
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Bool(ParamList<bool>),
@@ -80,11 +91,101 @@ pub enum Value {
     Spectrum(ParamList<Spectrum>),
 }
 
+impl Value {
+    /// Returns the name of this value's type as it's written in scene files.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Float(_) => "float",
+            Value::Int(_) => "integer",
+            Value::Point2f(_) => "point2",
+            Value::Vector2f(_) => "vector2",
+            Value::Point3f(_) => "point3",
+            Value::Vector3f(_) => "vector3",
+            Value::Normal3f(_) => "normal",
+            Value::String(_) => "string",
+            Value::Texture(_) => "texture",
+            Value::Spectrum(_) => "spectrum",
+        }
+    }
+}
+
+/// Error returned when a parameter is looked up as a different type than it was declared with.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("parameter \"{name}\" is a {actual}, expected a {expected}")]
+pub struct ParamTypeError {
+    /// Name of the parameter.
+    pub name: String,
+    /// Type the parameter was requested as.
+    pub expected: &'static str,
+    /// Type the parameter was declared with.
+    pub actual: &'static str,
+}
+
+impl ParamTypeError {
+    fn new(name: &str, expected: &'static str, actual: &Value) -> ParamTypeError {
+        ParamTypeError {
+            name: name.to_string(),
+            expected,
+            actual: actual.type_name(),
+        }
+    }
+}
+
+/// How a spectral parameter was declared in the scene file.  Every form is converted to a
+/// [Spectrum] when parsed, this keeps the declaration and its original values so the parameter
+/// can be written back out as it was given.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpectrumSource {
+    /// `"rgb name" [r g b ...]`, also written `"color name"`.
+    Rgb(Vec<Float>),
+    /// `"xyz name" [x y z ...]`.
+    Xyz(Vec<Float>),
+    /// `"blackbody name" [temperature scale ...]`.
+    Blackbody(Vec<Float>),
+    /// `"spectrum name" [lambda value ...]`.
+    Sampled(Vec<Float>),
+    /// `"spectrum name" "file.spd"`.
+    SampledFiles(Vec<String>),
+}
+
+/// LookedUp records whether a [ParamSetItem] has been read by a factory function.  It's atomic so
+/// a `ParamSet` can be shared between threads while still being updated through `&self`.
+#[derive(Debug, Default)]
+struct LookedUp(AtomicBool);
+
+impl LookedUp {
+    fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for LookedUp {
+    fn clone(&self) -> Self {
+        LookedUp(AtomicBool::new(self.get()))
+    }
+}
+
+impl PartialEq for LookedUp {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct ParamSetItem {
     pub name: String,
     pub values: Value,
-    looked_up: RefCell<bool>,
+    /// For spectra parsed from a scene file, the form they were declared in.
+    pub source: Option<SpectrumSource>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    looked_up: LookedUp,
 }
 
 impl ParamSetItem {
@@ -93,7 +194,121 @@ impl ParamSetItem {
         ParamSetItem {
             name: String::from(name),
             values: values.clone(),
-            looked_up: RefCell::new(false),
+            source: None,
+            looked_up: Default::default(),
+        }
+    }
+}
+
+/// Writes `values` space separated, wrapping each in `"` if `quote` is set.
+fn write_values<T: Display>(f: &mut Formatter<'_>, values: &[T], quote: bool) -> Result {
+    write!(f, "[")?;
+    for v in values {
+        if quote {
+            write!(f, " \"{}\"", v)?;
+        } else {
+            write!(f, " {}", v)?;
+        }
+    }
+    write!(f, " ]")
+}
+
+impl Display for ParamSetItem {
+    /// Formats the parameter as it would be written in a scene file.  Spectra are written in the
+    /// form they were declared, or as RGB if they weren't parsed from a scene.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::paramset::{ParamSet, ParamSetItem, Value};
+    ///
+    /// let psi = ParamSetItem::new("fov", &Value::Float(vec![45., 0.5].into()));
+    /// assert_eq!(psi.to_string(), r#""float fov" [ 45 0.5 ]"#);
+    ///
+    /// let mut ps = ParamSet::default();
+    /// ps.add_xyz_spectrum("L", vec![0.25, 0.5, 1.]);
+    /// assert_eq!(ps.to_string(), r#""xyz L" [ 0.25 0.5 1 ]"#);
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let flatten2 = |vs: &[(Float, Float)]| -> Vec<Float> {
+            vs.iter().flat_map(|&(x, y)| [x, y]).collect()
+        };
+        let flatten3 = |vs: &[(Float, Float, Float)]| -> Vec<Float> {
+            vs.iter().flat_map(|&(x, y, z)| [x, y, z]).collect()
+        };
+        let name = &self.name;
+        match &self.values {
+            Value::Bool(pl) => {
+                write!(f, "\"bool {}\" ", name)?;
+                write_values(f, &pl.0, true)
+            }
+            Value::Float(pl) => {
+                write!(f, "\"float {}\" ", name)?;
+                write_values(f, &pl.0, false)
+            }
+            Value::Int(pl) => {
+                write!(f, "\"integer {}\" ", name)?;
+                write_values(f, &pl.0, false)
+            }
+            Value::Point2f(pl) => {
+                write!(f, "\"point2 {}\" ", name)?;
+                let vs: Vec<_> = pl.0.iter().map(|p| (p.x, p.y)).collect();
+                write_values(f, &flatten2(&vs), false)
+            }
+            Value::Vector2f(pl) => {
+                write!(f, "\"vector2 {}\" ", name)?;
+                let vs: Vec<_> = pl.0.iter().map(|v| (v.x, v.y)).collect();
+                write_values(f, &flatten2(&vs), false)
+            }
+            Value::Point3f(pl) => {
+                write!(f, "\"point3 {}\" ", name)?;
+                let vs: Vec<_> = pl.0.iter().map(|p| (p.x, p.y, p.z)).collect();
+                write_values(f, &flatten3(&vs), false)
+            }
+            Value::Vector3f(pl) => {
+                write!(f, "\"vector3 {}\" ", name)?;
+                let vs: Vec<_> = pl.0.iter().map(|v| (v.x, v.y, v.z)).collect();
+                write_values(f, &flatten3(&vs), false)
+            }
+            Value::Normal3f(pl) => {
+                write!(f, "\"normal {}\" ", name)?;
+                let vs: Vec<_> = pl.0.iter().map(|n| (n.x, n.y, n.z)).collect();
+                write_values(f, &flatten3(&vs), false)
+            }
+            Value::String(pl) => {
+                write!(f, "\"string {}\" ", name)?;
+                write_values(f, &pl.0, true)
+            }
+            Value::Texture(pl) => {
+                write!(f, "\"texture {}\" ", name)?;
+                write_values(f, &pl.0, true)
+            }
+            Value::Spectrum(pl) => match &self.source {
+                Some(SpectrumSource::Rgb(vs)) => {
+                    write!(f, "\"rgb {}\" ", name)?;
+                    write_values(f, vs, false)
+                }
+                Some(SpectrumSource::Xyz(vs)) => {
+                    write!(f, "\"xyz {}\" ", name)?;
+                    write_values(f, vs, false)
+                }
+                Some(SpectrumSource::Blackbody(vs)) => {
+                    write!(f, "\"blackbody {}\" ", name)?;
+                    write_values(f, vs, false)
+                }
+                Some(SpectrumSource::Sampled(vs)) => {
+                    write!(f, "\"spectrum {}\" ", name)?;
+                    write_values(f, vs, false)
+                }
+                Some(SpectrumSource::SampledFiles(files)) => {
+                    write!(f, "\"spectrum {}\" ", name)?;
+                    write_values(f, files, true)
+                }
+                None => {
+                    write!(f, "\"rgb {}\" ", name)?;
+                    let vs: Vec<_> = pl.0.iter().flat_map(|s| s.to_rgb()).collect();
+                    write_values(f, &vs, false)
+                }
+            },
         }
     }
 }
@@ -105,6 +320,7 @@ impl ParamSetItem {
 /// and method signatures.
 ///
 /// [PbrtAPI]: crate::core::api::PbrtAPI
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct ParamSet {
     values: HashMap<String, ParamSetItem>,
@@ -129,13 +345,18 @@ fn iter3d(items: &[Float]) -> impl Iterator<Item = (Float, Float, Float)> + '_ {
 
 impl ParamSet {
     fn add(&mut self, name: &str, values: Value) {
+        self.add_with_source(name, values, None)
+    }
+
+    fn add_with_source(&mut self, name: &str, values: Value, source: Option<SpectrumSource>) {
         let name = String::from_str(name).unwrap();
         self.values.insert(
             name.clone(),
             ParamSetItem {
                 name,
                 values,
-                looked_up: RefCell::new(false),
+                source,
+                looked_up: Default::default(),
             },
         );
     }
@@ -174,36 +395,94 @@ impl ParamSet {
 
     pub fn add_rgb_spectrum(&mut self, name: &str, values: Vec<Float>) {
         assert_eq!(values.len() % 3, 0);
-        let values = iter3d(&values)
+        let spectra = iter3d(&values)
             .map(|(r, g, b)| {
                 let rgb: [Float; 3] = [r, g, b];
-                Spectrum::from_rgb(rgb)
+                // pbrt-v3 treats every RGB parameter as a reflectance, even for lights.
+                Spectrum::from_rgb_typed(rgb, SpectrumType::Reflectance)
             })
             .collect();
-        self.add(name, Value::Spectrum(ParamList(values)))
+        self.add_with_source(
+            name,
+            Value::Spectrum(ParamList(spectra)),
+            Some(SpectrumSource::Rgb(values)),
+        )
     }
 
     pub fn add_xyz_spectrum(&mut self, name: &str, values: Vec<Float>) {
         assert_eq!(values.len() % 3, 0);
-        let values = iter3d(&values)
+        let spectra = iter3d(&values)
             .map(|(x, y, z)| {
                 let xyz: [Float; 3] = [x, y, z];
                 Spectrum::from_xyz(xyz)
             })
             .collect();
-        self.add(name, Value::Spectrum(ParamList(values)))
+        self.add_with_source(
+            name,
+            Value::Spectrum(ParamList(spectra)),
+            Some(SpectrumSource::Xyz(values)),
+        )
     }
 
-    pub fn add_blackbody(&mut self, _name: &str, _values: Vec<Float>) {
-        todo!("core::paramset::Paramset::add_blackbody");
+    /// Adds a blackbody emission spectrum for each (temperature in Kelvin, scale) pair in
+    /// `values`.  Spectra are normalized so their peak value is `scale`.
+    pub fn add_blackbody(&mut self, name: &str, values: Vec<Float>) {
+        assert_eq!(values.len() % 2, 0);
+        let lambda: Vec<_> = cie_lambda().collect();
+        let spectra = values
+            .chunks_exact(2)
+            .map(|tv| {
+                let le = blackbody_normalized(&lambda, tv[0]);
+                Spectrum::from_sampled(&lambda, &le) * tv[1]
+            })
+            .collect();
+        self.add_with_source(
+            name,
+            Value::Spectrum(ParamList(spectra)),
+            Some(SpectrumSource::Blackbody(values)),
+        )
     }
 
-    pub fn add_sampled_spectrum_files(&mut self, _name: &str, _values: Vec<String>) {
-        todo!("core::paramset::Paramset::add_sampled_spectrum_files");
+    pub fn add_sampled_spectrum_files(&mut self, name: &str, values: Vec<String>) {
+        // Built-in spectra, like the metals' indices of refraction, can be used in place of files.
+        let spectra: Option<Vec<_>> = values.iter().map(|v| named_spectrum(v)).collect();
+        match spectra {
+            Some(spectra) => self.add_with_source(
+                name,
+                Value::Spectrum(ParamList(spectra)),
+                Some(SpectrumSource::SampledFiles(values)),
+            ),
+            None => {
+                // Reading SPD files isn't supported yet, keep the file names so the parameter
+                // can still be written out and checked.
+                warn!(
+                    "Sampled spectrum files {:?} for \"{}\" aren't supported, using black",
+                    values, name
+                );
+                let spectra = values.iter().map(|_| Spectrum::new(0.)).collect();
+                self.add_with_source(
+                    name,
+                    Value::Spectrum(ParamList(spectra)),
+                    Some(SpectrumSource::SampledFiles(values)),
+                )
+            }
+        }
     }
 
-    pub fn add_sampled_spectrum(&mut self, _name: &str, _values: Vec<Float>) {
-        todo!("core::paramset::Paramset::add_sampled_spectrum");
+    /// Adds a single spectrum given by the (wavelength in nm, value) pairs in `values`.
+    pub fn add_sampled_spectrum(&mut self, name: &str, values: Vec<Float>) {
+        assert_eq!(values.len() % 2, 0);
+        let (lambda, v): (Vec<_>, Vec<_>) = values.chunks_exact(2).map(|lv| (lv[0], lv[1])).unzip();
+        let spectra = if lambda.is_empty() {
+            Vec::new()
+        } else {
+            vec![Spectrum::from_sampled(&lambda, &v)]
+        };
+        self.add_with_source(
+            name,
+            Value::Spectrum(ParamList(spectra)),
+            Some(SpectrumSource::Sampled(values)),
+        )
     }
 
     pub fn add_string(&mut self, name: &str, values: Vec<String>) {
@@ -214,10 +493,38 @@ impl ParamSet {
         self.add(name, Value::Texture(ParamList(vec![value])))
     }
 
+    /// Removes the parameter `name`, returning true if it was present.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::paramset::testutils::make_float_param_set;
+    ///
+    /// let mut ps = make_float_param_set("radius", vec![2.]);
+    /// assert!(ps.erase("radius"));
+    /// assert!(!ps.erase("radius"));
+    /// assert_eq!(ps.find_one_float("radius", 1.), 1.);
+    /// ```
+    pub fn erase(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    /// Returns the parameters in this set in no particular order.  Unlike the `find_*` methods,
+    /// iterating doesn't mark the parameters as used.
+    pub fn iter(&self) -> impl Iterator<Item = &ParamSetItem> {
+        self.values.values()
+    }
+
+    /// Marks the parameter `name` as used without reading its value, if present.
+    pub(crate) fn mark_used(&self, name: &str) {
+        if let Some(psi) = self.values.get(name) {
+            psi.looked_up.set();
+        }
+    }
+
     fn find(&self, name: &str) -> Option<Value> {
         // Defer unwrapping to call site or consider to use a macro.
         self.values.get(name).map(|psi| {
-            *psi.looked_up.borrow_mut() = true;
+            psi.looked_up.set();
             psi.values.clone()
         })
     }
@@ -235,10 +542,25 @@ impl ParamSet {
     /// assert_eq!(ps.find_one_bool("non-existent", false), false);
     /// ```
     pub fn find_one_bool(&self, name: &str, default: bool) -> bool {
+        self.try_find_one_bool(name, default).unwrap_or_else(|err| {
+            warn!("{}, using default", err);
+            default
+        })
+    }
+
+    /// try_find_one_bool is like [find_one_bool] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `bool`.
+    ///
+    /// [find_one_bool]: ParamSet::find_one_bool
+    pub fn try_find_one_bool(
+        &self,
+        name: &str,
+        default: bool,
+    ) -> std::result::Result<bool, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Bool(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Bool(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "bool", &v)),
         }
     }
 
@@ -255,10 +577,26 @@ impl ParamSet {
     /// assert_eq!(ps.find_one_float("non-existent", 2.), 2.);
     /// ```
     pub fn find_one_float(&self, name: &str, default: Float) -> Float {
+        self.try_find_one_float(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_float is like [find_one_float] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `float`.
+    ///
+    /// [find_one_float]: ParamSet::find_one_float
+    pub fn try_find_one_float(
+        &self,
+        name: &str,
+        default: Float,
+    ) -> std::result::Result<Float, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Float(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Float(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "float", &v)),
         }
     }
 
@@ -275,10 +613,25 @@ impl ParamSet {
     /// assert_eq!(ps.find_one_int("non-existent", 2), 2);
     /// ```
     pub fn find_one_int(&self, name: &str, default: isize) -> isize {
+        self.try_find_one_int(name, default).unwrap_or_else(|err| {
+            warn!("{}, using default", err);
+            default
+        })
+    }
+
+    /// try_find_one_int is like [find_one_int] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `integer`.
+    ///
+    /// [find_one_int]: ParamSet::find_one_int
+    pub fn try_find_one_int(
+        &self,
+        name: &str,
+        default: isize,
+    ) -> std::result::Result<isize, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Int(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Int(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "integer", &v)),
         }
     }
 
@@ -301,10 +654,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_point2f(&self, name: &str, default: Point2f) -> Point2f {
+        self.try_find_one_point2f(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_point2f is like [find_one_point2f] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `point2`.
+    ///
+    /// [find_one_point2f]: ParamSet::find_one_point2f
+    pub fn try_find_one_point2f(
+        &self,
+        name: &str,
+        default: Point2f,
+    ) -> std::result::Result<Point2f, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Point2f(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Point2f(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "point2", &v)),
         }
     }
 
@@ -327,10 +696,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_vector2f(&self, name: &str, default: Vector2f) -> Vector2f {
+        self.try_find_one_vector2f(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_vector2f is like [find_one_vector2f] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `vector2`.
+    ///
+    /// [find_one_vector2f]: ParamSet::find_one_vector2f
+    pub fn try_find_one_vector2f(
+        &self,
+        name: &str,
+        default: Vector2f,
+    ) -> std::result::Result<Vector2f, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Vector2f(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Vector2f(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "vector2", &v)),
         }
     }
 
@@ -353,10 +738,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_point3f(&self, name: &str, default: Point3f) -> Point3f {
+        self.try_find_one_point3f(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_point3f is like [find_one_point3f] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `point3`.
+    ///
+    /// [find_one_point3f]: ParamSet::find_one_point3f
+    pub fn try_find_one_point3f(
+        &self,
+        name: &str,
+        default: Point3f,
+    ) -> std::result::Result<Point3f, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Point3f(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Point3f(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "point3", &v)),
         }
     }
 
@@ -379,10 +780,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_vector3f(&self, name: &str, default: Vector3f) -> Vector3f {
+        self.try_find_one_vector3f(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_vector3f is like [find_one_vector3f] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `vector3`.
+    ///
+    /// [find_one_vector3f]: ParamSet::find_one_vector3f
+    pub fn try_find_one_vector3f(
+        &self,
+        name: &str,
+        default: Vector3f,
+    ) -> std::result::Result<Vector3f, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Vector3f(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Vector3f(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "vector3", &v)),
         }
     }
 
@@ -405,10 +822,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_normal3f(&self, name: &str, default: Normal3f) -> Normal3f {
+        self.try_find_one_normal3f(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_normal3f is like [find_one_normal3f] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `normal`.
+    ///
+    /// [find_one_normal3f]: ParamSet::find_one_normal3f
+    pub fn try_find_one_normal3f(
+        &self,
+        name: &str,
+        default: Normal3f,
+    ) -> std::result::Result<Normal3f, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Normal3f(pl)) => pl.0.first().map_or(default, |v| *v),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Normal3f(pl)) => Ok(pl.0.first().map_or(default, |v| *v)),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "normal", &v)),
         }
     }
 
@@ -431,10 +864,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_spectrum(&self, name: &str, default: Spectrum) -> Spectrum {
+        self.try_find_one_spectrum(name, default.clone())
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default
+            })
+    }
+
+    /// try_find_one_spectrum is like [find_one_spectrum] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `spectrum`.
+    ///
+    /// [find_one_spectrum]: ParamSet::find_one_spectrum
+    pub fn try_find_one_spectrum(
+        &self,
+        name: &str,
+        default: Spectrum,
+    ) -> std::result::Result<Spectrum, ParamTypeError> {
         match self.find(name) {
-            Some(Value::Spectrum(pl)) => pl.0.first().map_or(default, |v| v.clone()),
-            None => default,
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::Spectrum(pl)) => Ok(pl.0.first().map_or(default, |v| v.clone())),
+            None => Ok(default),
+            Some(v) => Err(ParamTypeError::new(name, "spectrum", &v)),
         }
     }
 
@@ -454,10 +903,26 @@ impl ParamSet {
     /// );
     /// ```
     pub fn find_one_string(&self, name: &str, default: &str) -> String {
+        self.try_find_one_string(name, default)
+            .unwrap_or_else(|err| {
+                warn!("{}, using default", err);
+                default.to_string()
+            })
+    }
+
+    /// try_find_one_string is like [find_one_string] but returns a [ParamTypeError] when the
+    /// parameter isn't of type `string`.
+    ///
+    /// [find_one_string]: ParamSet::find_one_string
+    pub fn try_find_one_string(
+        &self,
+        name: &str,
+        default: &str,
+    ) -> std::result::Result<String, ParamTypeError> {
         match self.find(name) {
-            Some(Value::String(pl)) => pl.0.first().map_or(default.to_string(), |v| v.clone()),
-            None => default.to_string(),
-            _ => panic!("Unexpected type returned from find"),
+            Some(Value::String(pl)) => Ok(pl.0.first().map_or(default, |v| v.as_str()).to_string()),
+            None => Ok(default.to_string()),
+            Some(v) => Err(ParamTypeError::new(name, "string", &v)),
         }
     }
 
@@ -491,11 +956,12 @@ impl ParamSet {
 
     /// find_one_texture will return the first parameter in the set for the given
     /// `name`.  If no values are found `default` is returned. If the value by that
-    /// name is found but isn't of type `String` then `default` will be returned.
+    /// name is found but isn't a texture reference then `default` will be returned, as material
+    /// parameters like "Kd" may be given either as a value or as a texture.
     ///
     /// # Examples
     /// ```
-    /// use pbrt::core::paramset::testutils::make_texture_param_set;
+    /// use pbrt::core::paramset::testutils::{make_float_param_set, make_texture_param_set};
     ///
     /// let ps = make_texture_param_set("value", vec!["found".to_string()]);
     /// assert_eq!(ps.find_one_texture("value", "default"), "found".to_string());
@@ -503,12 +969,149 @@ impl ParamSet {
     ///     ps.find_one_texture("non-existent", "default"),
     ///     "default".to_string()
     /// );
+    /// let ps = make_float_param_set("value", vec![1.]);
+    /// assert_eq!(ps.find_one_texture("value", "default"), "default".to_string());
     /// ```
     pub fn find_one_texture(&self, name: &str, default: &str) -> String {
         match self.find(name) {
             Some(Value::Texture(pl)) => pl.0.first().map_or(default.to_string(), |v| v.clone()),
-            None => default.to_string(),
-            _ => panic!("Unexpected type returned from find"),
+            _ => default.to_string(),
+        }
+    }
+
+    /// find_float will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::paramset::testutils::make_float_param_set;
+    ///
+    /// let ps = make_float_param_set("value", vec![1., 2.]);
+    /// assert_eq!(ps.find_float("value"), Some(vec![1., 2.]));
+    /// assert_eq!(ps.find_float("non-existent"), None);
+    /// ```
+    pub fn find_float(&self, name: &str) -> Option<Vec<Float>> {
+        match self.find(name) {
+            Some(Value::Float(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "float", &v));
+                None
+            }
+        }
+    }
+
+    /// find_int will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::paramset::testutils::make_int_param_set;
+    ///
+    /// let ps = make_int_param_set("value", vec![1, 2]);
+    /// assert_eq!(ps.find_int("value"), Some(vec![1, 2]));
+    /// assert_eq!(ps.find_int("non-existent"), None);
+    /// ```
+    pub fn find_int(&self, name: &str) -> Option<Vec<isize>> {
+        match self.find(name) {
+            Some(Value::Int(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "integer", &v));
+                None
+            }
+        }
+    }
+
+    /// find_point2f will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point2f, paramset::testutils::make_point2f_param_set};
+    ///
+    /// let v = Point2f::from([1., 2.]);
+    /// let ps = make_point2f_param_set("value", vec![v, v]);
+    /// assert_eq!(ps.find_point2f("value"), Some(vec![v, v]));
+    /// assert_eq!(ps.find_point2f("non-existent"), None);
+    /// ```
+    pub fn find_point2f(&self, name: &str) -> Option<Vec<Point2f>> {
+        match self.find(name) {
+            Some(Value::Point2f(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "point2", &v));
+                None
+            }
+        }
+    }
+
+    /// find_point3f will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point3f, paramset::testutils::make_point3f_param_set};
+    ///
+    /// let v = Point3f::from([1., 2., 3.]);
+    /// let ps = make_point3f_param_set("value", vec![v, v]);
+    /// assert_eq!(ps.find_point3f("value"), Some(vec![v, v]));
+    /// assert_eq!(ps.find_point3f("non-existent"), None);
+    /// ```
+    pub fn find_point3f(&self, name: &str) -> Option<Vec<Point3f>> {
+        match self.find(name) {
+            Some(Value::Point3f(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "point3", &v));
+                None
+            }
+        }
+    }
+
+    /// find_vector3f will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Vector3f, paramset::testutils::make_vector3f_param_set};
+    ///
+    /// let v = Vector3f::from([1., 2., 3.]);
+    /// let ps = make_vector3f_param_set("value", vec![v, v]);
+    /// assert_eq!(ps.find_vector3f("value"), Some(vec![v, v]));
+    /// assert_eq!(ps.find_vector3f("non-existent"), None);
+    /// ```
+    pub fn find_vector3f(&self, name: &str) -> Option<Vec<Vector3f>> {
+        match self.find(name) {
+            Some(Value::Vector3f(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "vector3", &v));
+                None
+            }
+        }
+    }
+
+    /// find_normal3f will return all the parameters in the set for the given `name`.  If no values
+    /// are found `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Normal3f, paramset::testutils::make_normal3f_param_set};
+    ///
+    /// let v = Normal3f::from([0., 0., 1.]);
+    /// let ps = make_normal3f_param_set("value", vec![v, v]);
+    /// assert_eq!(ps.find_normal3f("value"), Some(vec![v, v]));
+    /// assert_eq!(ps.find_normal3f("non-existent"), None);
+    /// ```
+    pub fn find_normal3f(&self, name: &str) -> Option<Vec<Normal3f>> {
+        match self.find(name) {
+            Some(Value::Normal3f(pl)) => Some(pl.0),
+            None => None,
+            Some(v) => {
+                warn!("{}, ignoring it", ParamTypeError::new(name, "normal", &v));
+                None
+            }
         }
     }
 
@@ -517,16 +1120,33 @@ impl ParamSet {
     /// Useful after parsing a scene to see what configuration data was superfluous, or for
     /// detecting incomplete implementations of scene factory fuctions.
     pub fn report_unused(&self) -> bool {
-        let mut unused = false;
         info!("report_unused");
-
-        for (key, val) in &self.values {
-            if !(*val.looked_up.borrow()) {
-                info!("* '{}' not used", key);
-                unused = true
-            }
+        let unused = self.unused();
+        for key in &unused {
+            info!("* '{}' not used", key);
         }
+        !unused.is_empty()
+    }
 
+    /// Returns the names of the parameters that have not been accessed, sorted.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::paramset::testutils::make_float_param_set;
+    ///
+    /// let ps = make_float_param_set("radius", vec![2.]);
+    /// assert_eq!(ps.unused(), vec!["radius"]);
+    /// ps.find_one_float("radius", 1.);
+    /// assert!(ps.unused().is_empty());
+    /// ```
+    pub fn unused(&self) -> Vec<&str> {
+        let mut unused: Vec<_> = self
+            .values
+            .iter()
+            .filter(|(_, val)| !val.looked_up.get())
+            .map(|(key, _)| key.as_str())
+            .collect();
+        unused.sort_unstable();
         unused
     }
 }
@@ -535,20 +1155,36 @@ impl From<Vec<ParamSetItem>> for ParamSet {
     fn from(psis: Vec<ParamSetItem>) -> Self {
         let mut ps: ParamSet = Default::default();
         for psi in &psis {
-            ps.add(&psi.name, psi.values.clone())
+            ps.add_with_source(&psi.name, psi.values.clone(), psi.source.clone())
         }
         ps
     }
 }
 
+impl Display for ParamSet {
+    /// Formats the parameters as they would be written in a scene file, sorted by name so the
+    /// output is stable.
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut names: Vec<_> = self.values.keys().collect();
+        names.sort();
+        for (i, name) in names.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", self.values[name])?;
+        }
+        Ok(())
+    }
+}
+
 /// `TextureParams` represent values necessary to create a new [Texture].
 /// TODO(wathiede): currently only a stub, textures not implemented.
 ///
 /// [Texture]: crate::core::texture::Texture
 #[derive(Default)]
 pub struct TextureParams {
-    _float_textures: HashMap<String, Arc<dyn Texture<Float>>>,
-    _specturm_textures: HashMap<String, Arc<dyn Texture<Spectrum>>>,
+    float_textures: HashMap<String, Arc<dyn Texture<Float>>>,
+    specturm_textures: HashMap<String, Arc<dyn Texture<Spectrum>>>,
     geom_params: ParamSet,
     material_params: ParamSet,
 }
@@ -563,8 +1199,8 @@ impl TextureParams {
         specturm_textures: HashMap<String, Arc<dyn Texture<Spectrum>>>,
     ) -> TextureParams {
         TextureParams {
-            _float_textures: float_textures,
-            _specturm_textures: specturm_textures,
+            float_textures,
+            specturm_textures,
             geom_params,
             material_params,
         }
@@ -579,6 +1215,41 @@ impl TextureParams {
             .find_one_float(name, self.material_params.find_one_float(name, default))
     }
 
+    /// find_int will return the first integer value with the given `name` in this
+    /// `TextureParams`'s `geom_params` set, if none is found, it will find the first integer value
+    /// in the `material_params` set.  If no value is found there, the provided `default` will be
+    /// returned.
+    pub fn find_int(&self, name: &str, default: isize) -> isize {
+        self.geom_params
+            .find_one_int(name, self.material_params.find_one_int(name, default))
+    }
+
+    /// find_bool will return the first `bool` value with the given `name` in this
+    /// `TextureParams`'s `geom_params` set, if none is found, it will find the first `bool` value
+    /// in the `material_params` set.  If no value is found there, the provided `default` will be
+    /// returned.
+    pub fn find_bool(&self, name: &str, default: bool) -> bool {
+        self.geom_params
+            .find_one_bool(name, self.material_params.find_one_bool(name, default))
+    }
+
+    /// find_string will return the first `String` value with the given `name` in this
+    /// `TextureParams`'s `geom_params` set, if none is found, it will find the first `String`
+    /// value in the `material_params` set.  If no value is found there, the provided `default`
+    /// will be returned.
+    pub fn find_string(&self, name: &str, default: &str) -> String {
+        self.geom_params
+            .find_one_string(name, &self.material_params.find_one_string(name, default))
+    }
+
+    /// find_filename is like [find_string], but for parameters naming files.
+    ///
+    /// [find_string]: TextureParams::find_string
+    pub fn find_filename(&self, name: &str, default: &str) -> String {
+        self.geom_params
+            .find_one_filename(name, &self.material_params.find_one_filename(name, default))
+    }
+
     /// find_spectrum will return the first `Spectrum` value with the given `name` in this
     /// `TextureParams`'s `geom_params` set, if none is found, it will find the first `Spectrum`
     /// value in the `material_params` set.  If no value is found there, the provided `default`
@@ -587,12 +1258,222 @@ impl TextureParams {
         self.geom_params
             .find_one_spectrum(name, self.material_params.find_one_spectrum(name, default))
     }
+
+    /// Returns the name of the texture parameter `name`, looking in `geom_params` first, then
+    /// `material_params`.
+    fn find_texture_name(&self, name: &str) -> Option<String> {
+        let tex_name = self.geom_params.find_one_texture(name, "");
+        let tex_name = if tex_name.is_empty() {
+            self.material_params.find_one_texture(name, "")
+        } else {
+            tex_name
+        };
+        if tex_name.is_empty() {
+            None
+        } else {
+            Some(tex_name)
+        }
+    }
+
+    /// get_spectrum_texture returns the named spectrum texture referenced by the parameter
+    /// `name`.  If the parameter isn't a texture reference, a [ConstantTexture] holding the
+    /// parameter's `Spectrum` value, or `default` if there is none, is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     paramset::{testutils::make_spectrum_param_set, TextureParams},
+    ///     spectrum::Spectrum,
+    ///     texture::Texture,
+    /// };
+    ///
+    /// let tp = TextureParams::new(
+    ///     make_spectrum_param_set("Kd", vec![Spectrum::from(0.25)]),
+    ///     Default::default(),
+    ///     Default::default(),
+    ///     Default::default(),
+    /// );
+    /// let si = Default::default();
+    /// assert_eq!(
+    ///     tp.get_spectrum_texture("Kd", Spectrum::from(0.5))
+    ///         .evaluate(&si),
+    ///     Spectrum::from(0.25)
+    /// );
+    /// assert_eq!(
+    ///     tp.get_spectrum_texture("Ks", Spectrum::from(0.5))
+    ///         .evaluate(&si),
+    ///     Spectrum::from(0.5)
+    /// );
+    /// ```
+    ///
+    /// [ConstantTexture]: crate::textures::constant::ConstantTexture
+    pub fn get_spectrum_texture(
+        &self,
+        name: &str,
+        default: Spectrum,
+    ) -> Arc<dyn Texture<Spectrum>> {
+        if let Some(tex_name) = self.find_texture_name(name) {
+            match self.specturm_textures.get(&tex_name) {
+                Some(t) => return Arc::clone(t),
+                None => error!(
+                    "Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
+                    tex_name, name
+                ),
+            }
+        }
+        Arc::new(ConstantTexture::new(self.find_spectrum(name, default)))
+    }
+
+    /// get_float_texture returns the named float texture referenced by the parameter `name`.  If
+    /// the parameter isn't a texture reference, a [ConstantTexture] holding the parameter's
+    /// `Float` value, or `default` if there is none, is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::{collections::HashMap, sync::Arc};
+    ///
+    /// use pbrt::{
+    ///     core::{
+    ///         paramset::{testutils::make_texture_param_set, TextureParams},
+    ///         texture::Texture,
+    ///     },
+    ///     textures::constant::ConstantTexture,
+    ///     Float,
+    /// };
+    ///
+    /// let mut float_textures: HashMap<String, Arc<dyn Texture<Float>>> = HashMap::new();
+    /// float_textures.insert("rough".to_string(), Arc::new(ConstantTexture::new(0.75)));
+    /// let tp = TextureParams::new(
+    ///     make_texture_param_set("sigma", vec!["rough".to_string()]),
+    ///     Default::default(),
+    ///     float_textures,
+    ///     Default::default(),
+    /// );
+    /// let si = Default::default();
+    /// assert_eq!(tp.get_float_texture("sigma", 0.).evaluate(&si), 0.75);
+    /// assert_eq!(tp.get_float_texture("bumpmap", 0.5).evaluate(&si), 0.5);
+    /// ```
+    ///
+    /// [ConstantTexture]: crate::textures::constant::ConstantTexture
+    pub fn get_float_texture(&self, name: &str, default: Float) -> Arc<dyn Texture<Float>> {
+        if let Some(tex_name) = self.find_texture_name(name) {
+            match self.float_textures.get(&tex_name) {
+                Some(t) => return Arc::clone(t),
+                None => error!(
+                    "Couldn't find float texture named \"{}\" for parameter \"{}\"",
+                    tex_name, name
+                ),
+            }
+        }
+        Arc::new(ConstantTexture::new(self.find_float(name, default)))
+    }
+
+    /// get_float_texture_or_none is like [get_float_texture] but returns `None` when `name`
+    /// isn't set, letting callers fall back to another parameter.
+    ///
+    /// [get_float_texture]: TextureParams::get_float_texture
+    pub fn get_float_texture_or_none(&self, name: &str) -> Option<Arc<dyn Texture<Float>>> {
+        if let Some(tex_name) = self.find_texture_name(name) {
+            match self.float_textures.get(&tex_name) {
+                Some(t) => return Some(Arc::clone(t)),
+                None => {
+                    error!(
+                        "Couldn't find float texture named \"{}\" for parameter \"{}\"",
+                        tex_name, name
+                    );
+                    return None;
+                }
+            }
+        }
+        self.geom_params
+            .find_float(name)
+            .or_else(|| self.material_params.find_float(name))
+            .and_then(|v| v.first().copied())
+            .map(|v| Arc::new(ConstantTexture::new(v)) as Arc<dyn Texture<Float>>)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::core::logtest::assert_warns;
+
+    #[test]
+    fn param_list_debug() {
+        let pl: ParamList<Float> = ParamList((0..100).map(|i| i as Float).collect());
+        assert_eq!(
+            format!("{:?}", pl),
+            "<0.0 1.0 2.0 3.0 4.0 5.0 6.0 7.0 … (+92 more)>"
+        );
+        let pl: ParamList<Float> = ParamList(vec![1., 2.]);
+        assert_eq!(format!("{:?}", pl), "<1.0 2.0>");
+        let pl: ParamList<Float> = ParamList(vec![]);
+        assert_eq!(format!("{:?}", pl), "<>");
+        let pl = ParamList(vec![Spectrum::from_rgb([0.25, 0.5, 1.])]);
+        assert_eq!(format!("{:?}", pl), "<rgb(0.25, 0.5, 1)>");
+    }
+
+    /// Writes `ps` out as a scene file directive and parses it back.
+    fn reparse(ps: &ParamSet) -> ParamSet {
+        use crate::core::{
+            api_test::MockAPI,
+            parser::{create_from_string, parse},
+        };
+
+        let scene = format!(r#"LightSource "point" {}"#, ps);
+        let mut api = MockAPI::default();
+        parse(create_from_string(scene.as_bytes()), &mut api)
+            .unwrap_or_else(|e| panic!("parsing '{}': {}", scene, e));
+        api.assert_called_once_with_name("light_source", "point")
+            .params()
+            .expect("params")
+            .clone()
+    }
+
+    #[test]
+    fn blackbody_round_trip() {
+        let mut ps = ParamSet::default();
+        ps.add_blackbody("I", vec![6500., 1., 2000., 10.]);
+        assert!(
+            ps.to_string()
+                .contains(r#""blackbody I" [ 6500 1 2000 10 ]"#),
+            "{}",
+            ps
+        );
+        assert_eq!(reparse(&ps), ps);
+
+        let spectra = match ps.find("I") {
+            Some(Value::Spectrum(pl)) => pl.0,
+            v => panic!("unexpected value {:?}", v),
+        };
+        assert_eq!(spectra.len(), 2);
+        // A 6500K blackbody is close to white, a 2000K one is orange.
+        let [r, g, b] = spectra[0].to_rgb();
+        assert!(
+            (r - g).abs() < 0.15 * g && (b - g).abs() < 0.15 * g,
+            "{:?}",
+            [r, g, b]
+        );
+        let [r, g, b] = spectra[1].to_rgb();
+        assert!(r > g && g > b, "{:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn sampled_spectrum_round_trip() {
+        let mut ps = ParamSet::default();
+        ps.add_sampled_spectrum("L", vec![300., 2., 800., 2.]);
+        assert!(
+            ps.to_string().contains(r#""spectrum L" [ 300 2 800 2 ]"#),
+            "{}",
+            ps
+        );
+        assert_eq!(reparse(&ps), ps);
+
+        let l = ps.find_one_spectrum("L", Spectrum::new(0.));
+        assert!((l.y() - 2.).abs() < 1e-3, "{}", l);
+    }
+
     #[test]
     fn test_param_set() {
         let ps: ParamSet = vec![ParamSetItem::new(
@@ -640,6 +1521,59 @@ mod tests {
         assert!(ps.report_unused());
     }
 
+    #[test]
+    fn type_mismatch() {
+        let mut ps = ParamSet::default();
+        ps.add_string("radius", vec!["foo".to_string()]);
+        assert_eq!(
+            ps.try_find_one_float("radius", 1.),
+            Err(ParamTypeError {
+                name: "radius".to_string(),
+                expected: "float",
+                actual: "string",
+            })
+        );
+        assert_eq!(
+            ps.try_find_one_float("radius", 1.).unwrap_err().to_string(),
+            "parameter \"radius\" is a string, expected a float"
+        );
+        // The non-failing lookups log a warning and fall back to the default.
+        let radius = assert_warns(
+            "parameter \"radius\" is a string, expected a float, using default",
+            || ps.find_one_float("radius", 1.),
+        );
+        assert_eq!(radius, 1.);
+        let radius = assert_warns(
+            "parameter \"radius\" is a string, expected a float, ignoring it",
+            || ps.find_float("radius"),
+        );
+        assert_eq!(radius, None);
+        assert_eq!(
+            ps.try_find_one_string("radius", "bar"),
+            Ok("foo".to_string())
+        );
+        assert_eq!(ps.try_find_one_int("missing", 3), Ok(3));
+    }
+
+    #[test]
+    fn param_set_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ParamSet>();
+        assert_send_sync::<TextureParams>();
+    }
+
+    #[test]
+    fn looked_up_across_threads() {
+        let mut ps = ParamSet::default();
+        ps.add_float("radius", vec![2.]);
+        ps.add_float("zmin", vec![-1.]);
+        std::thread::scope(|s| {
+            s.spawn(|| assert_eq!(ps.find_one_float("radius", 1.), 2.));
+            s.spawn(|| assert_eq!(ps.find_one_float("zmin", 1.), -1.));
+        });
+        assert!(!ps.report_unused());
+    }
+
     #[test]
     fn test_param_set_find() {
         let ps: ParamSet = vec![
@@ -658,4 +1592,27 @@ mod tests {
         // let test3: String = "one".to_owned();
         // assert_eq!(ps.find("test3").unwrap_or("one").first(), test3);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut ps = ParamSet::default();
+        ps.add_float("fov", vec![45., 30.]);
+        ps.add_string("filename", vec!["out.png".to_string()]);
+        ps.add_rgb_spectrum("Kd", vec![0.1, 0.2, 0.3]);
+        ps.add_point3f("P", vec![Point3f::from([1., 2., 3.])]);
+        let json = serde_json::to_string(&ps).expect("serialize");
+        let got: ParamSet = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(got, ps);
+
+        // Lookups aren't serialized, a deserialized ParamSet starts with none.
+        ps.find_one_float("fov", 0.);
+        let got: ParamSet = serde_json::from_str(&serde_json::to_string(&ps).unwrap()).unwrap();
+        assert!(!json.contains("looked_up"));
+        assert_ne!(got, ps);
+        got.find_one_float("fov", 0.);
+        assert_eq!(got, ps);
+
+        assert!(serde_json::from_str::<Spectrum>("[1.0, 2.0]").is_err());
+    }
 }
@@ -87,7 +87,7 @@ pub fn make_int(name: &str, vals: Vec<isize>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_point2f_param_set;
 /// use pbrt::core::geometry::Point2f;
-
+///
 /// let ps = make_point2f_param_set("value", vec![Point2f::from([1., 1.])]);
 /// assert_eq!(ps.find_one_point2f("value", Point2f::from([2., 2.])), Point2f::from([1., 1.]));
 /// assert_eq!(ps.find_one_point2f("non-existent", Point2f::from([2., 2.])), Point2f::from([2., 2.]));
@@ -107,7 +107,7 @@ pub fn make_point2f(name: &str, vals: Vec<Point2f>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_vector2f_param_set;
 /// use pbrt::core::geometry::Vector2f;
-
+///
 /// let ps = make_vector2f_param_set("value", vec![Vector2f::from([1., 1.])]);
 /// assert_eq!(ps.find_one_vector2f("value", Vector2f::from([2., 2.])), Vector2f::from([1., 1.]));
 /// assert_eq!(ps.find_one_vector2f("non-existent", Vector2f::from([2., 2.])), Vector2f::from([2., 2.]));
@@ -127,7 +127,7 @@ pub fn make_vector2f(name: &str, vals: Vec<Vector2f>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_point3f_param_set;
 /// use pbrt::core::geometry::Point3f;
-
+///
 /// let ps = make_point3f_param_set("value", vec![Point3f::from([1., 1., 1.])]);
 /// assert_eq!(ps.find_one_point3f("value", Point3f::from([2., 2., 2.])), Point3f::from([1., 1., 1.]));
 /// assert_eq!(ps.find_one_point3f("non-existent", Point3f::from([2., 2., 2.])), Point3f::from([2., 2., 2.]));
@@ -147,7 +147,7 @@ pub fn make_point3f(name: &str, vals: Vec<Point3f>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_vector3f_param_set;
 /// use pbrt::core::geometry::Vector3f;
-
+///
 /// let ps = make_vector3f_param_set("value", vec![Vector3f::from([1., 1., 1.])]);
 /// assert_eq!(ps.find_one_vector3f("value", Vector3f::from([2., 2., 2.])), Vector3f::from([1., 1., 1.]));
 /// assert_eq!(ps.find_one_vector3f("non-existent", Vector3f::from([2., 2., 2.])), Vector3f::from([2., 2., 2.]));
@@ -167,7 +167,7 @@ pub fn make_vector3f(name: &str, vals: Vec<Vector3f>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_normal3f_param_set;
 /// use pbrt::core::geometry::Normal3f;
-
+///
 /// let ps = make_normal3f_param_set("value", vec![Normal3f::from([1., 1., 1.])]);
 /// assert_eq!(ps.find_one_normal3f("value", Normal3f::from([2., 2., 2.])), Normal3f::from([1., 1., 1.]));
 /// assert_eq!(ps.find_one_normal3f("non-existent", Normal3f::from([2., 2., 2.])), Normal3f::from([2., 2., 2.]));
@@ -187,7 +187,7 @@ pub fn make_normal3f(name: &str, vals: Vec<Normal3f>) -> ParamSetItem {
 /// ```
 /// use pbrt::core::paramset::testutils::make_spectrum_param_set;
 /// use pbrt::core::spectrum::Spectrum;
-
+///
 /// let ps = make_spectrum_param_set("value", vec![Spectrum::from_rgb([1., 1., 1.])]);
 /// assert_eq!(ps.find_one_spectrum("value", Spectrum::from_rgb([2., 2., 2.])), Spectrum::from_rgb([1., 1., 1.]));
 /// assert_eq!(ps.find_one_spectrum("non-existent", Spectrum::from_rgb([2., 2., 2.])), Spectrum::from_rgb([2., 2., 2.]));
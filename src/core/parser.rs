@@ -13,9 +13,16 @@
 // limitations under the License.
 
 //! Utilities for parsing pbrt scene files.
-use std::convert::TryFrom;
+//!
+//! Scenes are split into tokens by a [TokenSource].  [Tokenizer] hands out slices of a scene
+//! that is already in memory without copying, except for strings containing escape sequences,
+//! and [ByteSourceTokenizer] streams scenes from any [Read], like a pipe or a file too large to
+//! map, at the cost of copying each token.
+//!
+//! [Read]: std::io::Read
+use std::{borrow::Cow, convert::TryFrom, io::Read};
 
-use log::{error, warn};
+use log::{debug, error, warn};
 use thiserror::Error;
 
 use crate::{
@@ -24,7 +31,7 @@ use crate::{
         geometry::{Normal3f, Point2f, Point3f, Vector2f, Vector3f},
         paramset::ParamSet,
     },
-    Float,
+    Degree, Float,
 };
 
 /// Error type for tokenization and parsing errors.
@@ -51,12 +58,28 @@ pub enum Error {
     /// Mixed string and numeric parameters found.
     #[error("mixed string and numeric parameters")]
     MixedParameters,
+    /// Reading the scene failed.  Holds the description of the underlying `std::io::Error`.
+    #[error("read error: {0}")]
+    Io(String),
+    /// A directive name that isn't part of the scene description format.
+    #[error("unknown directive '{0}'")]
+    UnknownDirective(String),
     /// Hit a part of the parser not yet implemented.
     // TODO(wathiede): remove this when Parser::parse() is complete.
     #[error("have not yet implemented '{0}'")]
     NotImplemented(String),
 }
 
+/// TokenSource is implemented by the tokenizers the parser reads scenes from.
+pub trait TokenSource {
+    /// The type of the tokens produced, either borrowed from the scene or owned.
+    type Token: AsRef<str>;
+
+    /// Returns the next token, `None` at the end of the input.  Comments are returned as tokens
+    /// starting with '#', quoted strings keep their quotes.
+    fn next_token(&mut self) -> Option<Result<Self::Token, Error>>;
+}
+
 /// Tokenizer holds state necessary to tokenize a pbrt scene file.
 pub struct Tokenizer<'a> {
     data: &'a [u8],
@@ -64,7 +87,7 @@ pub struct Tokenizer<'a> {
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
-    type Item = Result<&'a str, Error>;
+    type Item = Result<Cow<'a, str>, Error>;
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let token_start = self.pos;
@@ -77,7 +100,7 @@ impl<'a> Iterator for Tokenizer<'a> {
                     let mut have_escaped = false;
                     loop {
                         match self.get_byte() {
-                            Some(byte) if byte == b'"' => break,
+                            Some(b'"') => break,
                             None => return Some(Err(Error::EOF)),
                             Some(b'\n') => return Some(Err(Error::UnterminatedString)),
                             Some(b'\\') => {
@@ -92,62 +115,57 @@ impl<'a> Iterator for Tokenizer<'a> {
 
                     if !have_escaped {
                         return self.token(token_start);
-                    } else {
-                        unimplemented!();
-                        /*
-                        sEscaped.clear();
-                        for (const char *p = tokenStart; p < pos; ++p) {
-                        if (*p != '\\')
-                        sEscaped.push_back(*p);
-                        else {
-                        ++p;
-                        CHECK_LT(p, pos);
-                        sEscaped.push_back(decodeEscaped(*p));
-                        }
-                        }
-                        return {sEscaped.data(), sEscaped.size()};
-                        */
                     }
+                    return Some(self.escaped_token(token_start));
                 }
 
                 Some(b'[') | Some(b']') => {
                     return self.token(token_start);
                 }
                 Some(b'#') => {
+                    // Comments run to the end of the line, which may be terminated by "\n",
+                    // "\r\n" or "\r".  The terminator is whitespace, so it's consumed here
+                    // rather than put back, and isn't part of the returned token.
+                    let mut token_end = self.data.len();
                     while let Some(ch) = self.get_byte() {
-                        match ch {
-                            b'\n' | b'\r' => {
-                                self.unget_byte();
-                                break;
-                            }
-                            _ => (),
+                        if ch == b'\n' || ch == b'\r' {
+                            token_end = self.pos - 1;
+                            break;
                         }
                     }
                     return Some(
-                        std::str::from_utf8(&self.data[token_start..self.pos]).map_err(Error::from),
+                        std::str::from_utf8(&self.data[token_start..token_end])
+                            .map(Cow::Borrowed)
+                            .map_err(Error::from),
                     );
                 }
                 _ => {
                     // Regular statement or numeric token; scan until we hit a
-                    // space, opening quote, or bracket.
+                    // space, opening quote, bracket, or the start of a comment.
                     while let Some(byte) = self.get_byte() {
                         match byte {
-                            b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']' => {
+                            b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']' | b'#' => {
                                 self.unget_byte();
                                 break;
                             }
                             _ => (),
                         }
                     }
-                    return Some(
-                        std::str::from_utf8(&self.data[token_start..self.pos]).map_err(Error::from),
-                    );
+                    return self.token(token_start);
                 }
             }
         }
     }
 }
 
+impl<'a> TokenSource for Tokenizer<'a> {
+    type Token = Cow<'a, str>;
+
+    fn next_token(&mut self) -> Option<Result<Cow<'a, str>, Error>> {
+        self.next()
+    }
+}
+
 impl<'a> Tokenizer<'a> {
     fn get_byte(&mut self) -> Option<u8> {
         // TODO(wathiede): should we track location information?
@@ -164,8 +182,30 @@ impl<'a> Tokenizer<'a> {
         self.pos -= 1;
     }
 
-    fn token(&mut self, token_start: usize) -> Option<Result<&'a str, Error>> {
-        Some(std::str::from_utf8(&self.data[token_start..self.pos]).map_err(Error::from))
+    fn token(&mut self, token_start: usize) -> Option<Result<Cow<'a, str>, Error>> {
+        Some(
+            std::str::from_utf8(&self.data[token_start..self.pos])
+                .map(Cow::Borrowed)
+                .map_err(Error::from),
+        )
+    }
+
+    /// Returns a copy of the quoted string starting at `token_start` with its escape sequences
+    /// decoded.  The string has already been scanned, so every backslash is followed by a byte.
+    fn escaped_token(&self, token_start: usize) -> Result<Cow<'a, str>, Error> {
+        let mut tok = Vec::with_capacity(self.pos - token_start);
+        let mut bytes = self.data[token_start..self.pos].iter();
+        while let Some(&byte) = bytes.next() {
+            if byte == b'\\' {
+                let escaped = bytes.next().expect("escape at end of scanned string");
+                tok.push(decode_escaped(*escaped)?);
+            } else {
+                tok.push(byte);
+            }
+        }
+        String::from_utf8(tok)
+            .map(Cow::Owned)
+            .map_err(|err| Error::StrError(err.utf8_error()))
     }
 }
 
@@ -176,39 +216,399 @@ Tokenizer {
 }
 */
 
-/// Creates a [Tokenizer] from the scene file in `data`.
+/// UTF-8 byte order mark some editors, mostly on Windows, write at the start of text files.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Creates a [Tokenizer] from the scene file in `data`.  A leading UTF-8 byte order mark is
+/// skipped.
 ///
 /// [Tokenizer]: crate::core::parser::Tokenizer
 pub fn create_from_string(data: &[u8]) -> Tokenizer<'_> {
+    let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
     Tokenizer { data, pos: 0 }
 }
 
+/// Default size of the buffer [ByteSourceTokenizer] reads through.
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// ByteSourceTokenizer tokenizes a pbrt scene file read incrementally from any `Read`, so
+/// scenes don't need to fit in memory.  Tokens are split and escape sequences in quoted strings
+/// decoded exactly as [Tokenizer] does, but tokens are returned as owned `String`s.
+pub struct ByteSourceTokenizer<R: Read> {
+    reader: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    started: bool,
+}
+
+impl<R: Read> ByteSourceTokenizer<R> {
+    /// Creates a `ByteSourceTokenizer` reading from `reader` through a 64 KiB buffer.
+    pub fn new(reader: R) -> ByteSourceTokenizer<R> {
+        ByteSourceTokenizer::with_capacity(DEFAULT_BUFFER_SIZE, reader)
+    }
+
+    /// Creates a `ByteSourceTokenizer` reading from `reader` through a buffer of `capacity` bytes.
+    /// Tokens may be longer than the buffer.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::parser::{ByteSourceTokenizer, TokenSource};
+    ///
+    /// let mut t = ByteSourceTokenizer::with_capacity(4, &b"Film \"image\" # comment"[..]);
+    /// assert_eq!(t.next_token(), Some(Ok("Film".to_string())));
+    /// assert_eq!(t.next_token(), Some(Ok(r#""image""#.to_string())));
+    /// assert_eq!(t.next_token(), Some(Ok("# comment".to_string())));
+    /// assert_eq!(t.next_token(), None);
+    /// ```
+    pub fn with_capacity(capacity: usize, reader: R) -> ByteSourceTokenizer<R> {
+        ByteSourceTokenizer {
+            reader,
+            // The byte order mark check needs room for the whole mark.
+            buf: vec![0; capacity.max(UTF8_BOM.len())].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            started: false,
+        }
+    }
+
+    /// Reads more data, keeping the unconsumed bytes, until at least `n` are buffered or the
+    /// reader is exhausted.  The last consumed byte is kept too, so it can be put back.
+    fn fill(&mut self, n: usize) -> Result<(), Error> {
+        if self.pos > 1 {
+            self.buf.copy_within(self.pos - 1..self.len, 0);
+            self.len -= self.pos - 1;
+            self.pos = 1;
+        }
+        while self.len - self.pos < n && self.len < self.buf.len() {
+            match self.reader.read(&mut self.buf[self.len..]) {
+                Ok(0) => break,
+                Ok(read) => self.len += read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(Error::Io(err.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    fn get_byte(&mut self) -> Result<Option<u8>, Error> {
+        if self.pos == self.len {
+            self.fill(1)?;
+            if self.pos == self.len {
+                return Ok(None);
+            }
+        }
+        let byte = self.buf[self.pos];
+        self.pos += 1;
+        Ok(Some(byte))
+    }
+
+    fn unget_byte(&mut self) {
+        self.pos -= 1;
+    }
+
+    fn skip_bom(&mut self) -> Result<(), Error> {
+        self.fill(UTF8_BOM.len())?;
+        if self.buf[self.pos..self.len].starts_with(UTF8_BOM) {
+            self.pos += UTF8_BOM.len();
+        }
+        Ok(())
+    }
+
+    fn token(&mut self) -> Result<Option<String>, Error> {
+        if !self.started {
+            self.started = true;
+            self.skip_bom()?;
+        }
+        let mut tok = Vec::new();
+        loop {
+            let byte = match self.get_byte()? {
+                // EOF
+                None => return Ok(None),
+                Some(byte) => byte,
+            };
+            match byte {
+                b' ' | b'\n' | b'\t' | b'\r' => (),
+                b'"' => {
+                    // scan to closing quote
+                    tok.push(byte);
+                    loop {
+                        match self.get_byte()? {
+                            Some(b'"') => break,
+                            None => return Err(Error::EOF),
+                            Some(b'\n') => return Err(Error::UnterminatedString),
+                            Some(b'\\') => match self.get_byte()? {
+                                None => return Err(Error::EOF),
+                                Some(escaped) => tok.push(decode_escaped(escaped)?),
+                            },
+                            Some(byte) => tok.push(byte),
+                        }
+                    }
+                    tok.push(b'"');
+                    break;
+                }
+                b'[' | b']' => {
+                    tok.push(byte);
+                    break;
+                }
+                b'#' => {
+                    // Comments run to the end of the line, the terminator isn't part of the token.
+                    tok.push(byte);
+                    while let Some(ch) = self.get_byte()? {
+                        if ch == b'\n' || ch == b'\r' {
+                            break;
+                        }
+                        tok.push(ch);
+                    }
+                    break;
+                }
+                _ => {
+                    // Regular statement or numeric token; scan until we hit a
+                    // space, opening quote, bracket, or the start of a comment.
+                    tok.push(byte);
+                    while let Some(byte) = self.get_byte()? {
+                        match byte {
+                            b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']' | b'#' => {
+                                self.unget_byte();
+                                break;
+                            }
+                            _ => tok.push(byte),
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        String::from_utf8(tok)
+            .map(Some)
+            .map_err(|err| Error::StrError(err.utf8_error()))
+    }
+}
+
+/// Returns the character represented by the escape sequence `\c`.
+fn decode_escaped(c: u8) -> Result<u8, Error> {
+    Ok(match c {
+        b'b' => b'\x08',
+        b'f' => b'\x0c',
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'\\' => b'\\',
+        b'\'' => b'\'',
+        b'"' => b'"',
+        _ => {
+            return Err(Error::Syntax(format!(
+                "unexpected escaped character '{}'",
+                c as char
+            )))
+        }
+    })
+}
+
+impl<R: Read> Iterator for ByteSourceTokenizer<R> {
+    type Item = Result<String, Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.token().transpose()
+    }
+}
+
+impl<R: Read> TokenSource for ByteSourceTokenizer<R> {
+    type Token = String;
+
+    fn next_token(&mut self) -> Option<Result<String, Error>> {
+        self.next()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum Token {
     Optional,
     Required,
 }
 
-#[derive(Default, Debug)]
-struct ParamListItem<'a> {
-    name: String,
-    double_values: Vec<f64>,
-    string_values: Vec<&'a str>,
+/// Tuples groups numeric values into `N`-tuples of `T` as they're parsed, so large lists of
+/// points or normals never exist as a flat list of numbers.
+#[derive(Debug)]
+struct Tuples<T, const N: usize> {
+    values: Vec<T>,
+    partial: [Float; N],
+    len: usize,
 }
 
-impl<'a> ParamListItem<'a> {
-    fn size(&self) -> usize {
-        self.double_values.len() + self.string_values.len()
+impl<T: From<[Float; N]>, const N: usize> Tuples<T, N> {
+    fn new() -> Tuples<T, N> {
+        Tuples {
+            values: Vec::new(),
+            partial: [0.; N],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, v: Float) {
+        self.partial[self.len] = v;
+        self.len += 1;
+        if self.len == N {
+            self.values.push(T::from(self.partial));
+            self.len = 0;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.len == 0
+    }
+
+    /// Returns the whole tuples, warning when trailing values were dropped.  `kind` and `name`
+    /// describe the parameter in the warning.
+    fn into_values(self, kind: &str, name: &str) -> Vec<T> {
+        if self.len != 0 {
+            warn!(
+                "Excess {} values given with parameter '{}'. Ignoring last {} of them.",
+                kind, name, self.len
+            );
+        }
+        self.values
     }
 }
 
-struct Parser<'a> {
-    file_stack: Vec<Tokenizer<'a>>,
-    unget_token: Option<&'a str>,
+/// Numbers holds the numeric values of a parameter in the representation its declared type is
+/// stored as in a `ParamSet`.
+#[derive(Debug)]
+enum Numbers {
+    Int(Vec<isize>),
+    Float(Vec<Float>),
+    Point2(Tuples<Point2f, 2>),
+    Vector2(Tuples<Vector2f, 2>),
+    Point3(Tuples<Point3f, 3>),
+    Vector3(Tuples<Vector3f, 3>),
+    Normal(Tuples<Normal3f, 3>),
+}
+
+impl Numbers {
+    fn new(p_type: Option<ParamType>) -> Numbers {
+        match p_type {
+            Some(ParamType::Int) => Numbers::Int(Vec::new()),
+            Some(ParamType::Point2) => Numbers::Point2(Tuples::new()),
+            Some(ParamType::Vector2) => Numbers::Vector2(Tuples::new()),
+            Some(ParamType::Point3) => Numbers::Point3(Tuples::new()),
+            Some(ParamType::Vector3) => Numbers::Vector3(Tuples::new()),
+            Some(ParamType::Normal) => Numbers::Normal(Tuples::new()),
+            _ => Numbers::Float(Vec::new()),
+        }
+    }
+
+    /// Parses `val` and appends it to the values of the parameter `name`.
+    fn push(&mut self, name: &str, val: &str) -> Result<(), Error> {
+        match self {
+            Numbers::Int(v) => v.push(parse_int(name, val)?),
+            Numbers::Float(v) => v.push(val.parse()?),
+            Numbers::Point2(t) => t.push(val.parse()?),
+            Numbers::Vector2(t) => t.push(val.parse()?),
+            Numbers::Point3(t) => t.push(val.parse()?),
+            Numbers::Vector3(t) => t.push(val.parse()?),
+            Numbers::Normal(t) => t.push(val.parse()?),
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Numbers::Int(v) => v.is_empty(),
+            Numbers::Float(v) => v.is_empty(),
+            Numbers::Point2(t) => t.is_empty(),
+            Numbers::Vector2(t) => t.is_empty(),
+            Numbers::Point3(t) => t.is_empty(),
+            Numbers::Vector3(t) => t.is_empty(),
+            Numbers::Normal(t) => t.is_empty(),
+        }
+    }
+}
+
+/// Parses `val`, a value of the integer parameter `name`.  Values that aren't integers are rounded
+/// to the nearest one, and values too large for an `isize` are clamped.  Either way a warning is
+/// logged, as they usually point to a bug in whatever wrote the scene.
+fn parse_int(name: &str, val: &str) -> Result<isize, Error> {
+    if let Ok(i) = val.parse() {
+        return Ok(i);
+    }
+    let (i, problem) = round_int(val.parse()?);
+    if let Some(problem) = problem {
+        warn!(
+            "Value {} of integer parameter '{}' {}, using {}.",
+            val, name, problem, i
+        );
+    }
+    Ok(i)
+}
+
+/// Rounds `f` to the nearest `isize`, saturating at the limits of the type.  The second value
+/// describes why the conversion wasn't exact, if it wasn't.
+fn round_int(f: f64) -> (isize, Option<&'static str>) {
+    let rounded = f.round();
+    let i = rounded as isize;
+    if !(rounded >= isize::MIN as f64 && rounded < isize::MAX as f64) {
+        (i, Some("is out of range"))
+    } else if rounded != f {
+        (i, Some("is not an integer"))
+    } else {
+        (i, None)
+    }
+}
+
+/// ParamListItem accumulates the values of a single parameter.  The parameter's declaration is
+/// looked up before any values are read, so numeric values are parsed straight into the
+/// representation the `ParamSet` stores, avoiding intermediate copies that huge inline meshes
+/// can't afford.  String values are kept as the quoted tokens `T` they were read as.
+#[derive(Debug)]
+struct ParamListItem<'a, T> {
+    name: &'a str,
+    p_type: Option<(ParamType, &'a str)>,
+    numbers: Numbers,
+    string_values: Vec<T>,
+}
+
+impl<'a, T: AsRef<str>> ParamListItem<'a, T> {
+    fn new(name: &'a str) -> ParamListItem<'a, T> {
+        let p_type = lookup_type(name);
+        ParamListItem {
+            name,
+            p_type,
+            numbers: Numbers::new(p_type.map(|(p_type, _)| p_type)),
+            string_values: Vec::new(),
+        }
+    }
+
+    fn add_value(&mut self, val: T) -> Result<(), Error> {
+        if is_quoted_string(val.as_ref()) {
+            if !self.numbers.is_empty() {
+                return Err(Error::MixedParameters);
+            }
+            self.string_values.push(val);
+        } else {
+            if !self.string_values.is_empty() {
+                return Err(Error::MixedParameters);
+            }
+            let name = self.p_type.map_or(self.name, |(_, p_name)| p_name);
+            self.numbers.push(name, val.as_ref())?;
+        }
+        Ok(())
+    }
 }
 
-impl<'a> Parser<'a> {
-    fn parse<A: API>(t: Tokenizer, api: &mut A) -> Result<(), Error> {
+/// Returns the quoted string `values` without their quotes.
+fn unquoted<T: AsRef<str>>(values: &[T]) -> impl Iterator<Item = &str> {
+    values.iter().map(|s| {
+        let s = s.as_ref();
+        &s[1..s.len() - 1]
+    })
+}
+
+struct Parser<S: TokenSource> {
+    file_stack: Vec<S>,
+    unget_token: Option<S::Token>,
+}
+
+impl<S: TokenSource> Parser<S> {
+    fn parse<A: API>(t: S, api: &mut A) -> Result<(), Error> {
         let mut p = Parser {
             file_stack: vec![t],
             unget_token: None,
@@ -222,95 +622,103 @@ impl<'a> Parser<'a> {
                 Some(tok) => tok,
             };
             let tok = tok?;
-            match tok {
+            match tok.as_ref() {
                 "Accelerator" => p.basic_param_list_entrypoint(|n, p| api.accelerator(n, p))?,
-                "ActiveTransform" => {
-                    return Err(Error::NotImplemented("ActiveTransform".to_string()))
-                }
+                "ActiveTransform" => match p.next_token(Token::Required) {
+                    None => return Err(Error::EOF),
+                    Some(tok) => match tok?.as_ref() {
+                        "All" => api.active_transform_all(),
+                        "EndTime" => api.active_transform_end_time(),
+                        "StartTime" => api.active_transform_start_time(),
+                        tok => return Err(Error::Syntax(tok.to_string())),
+                    },
+                },
                 "AreaLightSource" => {
-                    return Err(Error::NotImplemented("AreaLightSource".to_string()))
+                    p.basic_param_list_entrypoint(|n, p| api.area_light_source(n, p))?
                 }
-                "AttrbuteBegin" => api.attribute_begin(),
+                "AttributeBegin" => api.attribute_begin(),
                 "AttributeEnd" => api.attribute_end(),
                 "Camera" => p.basic_param_list_entrypoint(|n, p| api.camera(n, p))?,
-                "ConcatTransform" => {
-                    return Err(Error::NotImplemented("ConcatTransform".to_string()))
-                }
-                "CoordinateSystem" => {
-                    return Err(Error::NotImplemented("CoordinateSystem".to_string()))
-                }
-                "CoordSysTransform" => {
-                    return Err(Error::NotImplemented("CoordSysTransform".to_string()))
-                }
+                "ConcatTransform" => api.concat_transform(p.next_matrix()?),
+                "CoordinateSystem" => api.coordinate_system(&p.next_string()?),
+                "CoordSysTransform" => api.coordinate_system_transform(&p.next_string()?),
                 "Film" => p.basic_param_list_entrypoint(|n, p| api.film(n, p))?,
-                "Identity" => return Err(Error::NotImplemented("Identity".to_string())),
+                "Identity" => api.identity(),
                 "Include" => return Err(Error::NotImplemented("Include".to_string())),
                 "Integrator" => p.basic_param_list_entrypoint(|n, p| api.integrator(n, p))?,
                 "LightSource" => p.basic_param_list_entrypoint(|n, p| api.light_source(n, p))?,
                 "LookAt" => {
-                    let mut eye: [Float; 3] = Default::default();
-                    for i in &mut eye {
-                        let tok = p.next_token(Token::Required).unwrap_or(Ok(""))?;
-                        *i = tok.parse()?;
-                    }
+                    let eye = [p.next_float()?, p.next_float()?, p.next_float()?];
 
-                    let mut look: [Float; 3] = Default::default();
-                    for i in &mut look {
-                        let tok = p.next_token(Token::Required).unwrap_or(Ok(""))?;
-                        *i = tok.parse()?;
-                    }
+                    let look = [p.next_float()?, p.next_float()?, p.next_float()?];
 
-                    let mut up: [Float; 3] = Default::default();
-                    for i in &mut up {
-                        let tok = p.next_token(Token::Required).unwrap_or(Ok(""))?;
-                        *i = tok.parse()?;
-                    }
+                    let up = [p.next_float()?, p.next_float()?, p.next_float()?];
                     api.look_at(eye, look, up);
                 }
                 "MakeNamedMaterial" => {
-                    return Err(Error::NotImplemented("MakeNamedMaterial".to_string()))
+                    p.basic_param_list_entrypoint(|n, p| api.make_named_material(n, p))?
                 }
                 "MakeNamedMedium" => {
-                    return Err(Error::NotImplemented("MakeNamedMedium".to_string()))
+                    p.basic_param_list_entrypoint(|n, mut p| api.make_named_medium(n, &mut p))?
                 }
-                "Material" => return Err(Error::NotImplemented("Material".to_string())),
+                "Material" => p.basic_param_list_entrypoint(|n, p| api.material(n, p))?,
                 "MediumInterface" => {
-                    return Err(Error::NotImplemented("MediumInterface".to_string()))
-                }
-                "NamedMaterial" => return Err(Error::NotImplemented("NamedMaterial".to_string())),
-                "ObjectBegin" => return Err(Error::NotImplemented("ObjectBegin".to_string())),
-                "ObjectEnd" => return Err(Error::NotImplemented("ObjectEnd".to_string())),
-                "ObjectInstance" => {
-                    return Err(Error::NotImplemented("ObjectInstance".to_string()))
+                    let inside = p.next_string()?;
+                    // The outside medium is optional, when missing it's the same as the inside.
+                    let outside = match p.next_token(Token::Optional) {
+                        None => inside.clone(),
+                        Some(tok) => {
+                            let tok = tok?;
+                            if is_quoted_string(tok.as_ref()) {
+                                dequote_string(tok.as_ref())?.to_string()
+                            } else {
+                                p.unget_token = Some(tok);
+                                inside.clone()
+                            }
+                        }
+                    };
+                    api.medium_interface(&inside, &outside);
                 }
-                "PixelFilter" => return Err(Error::NotImplemented("PixelFilter".to_string())),
-                "ReverseOrientation" => {
-                    return Err(Error::NotImplemented("ReverseOrientation".to_string()))
+                "NamedMaterial" => api.named_material(&p.next_string()?),
+                "ObjectBegin" => api.object_begin(&p.next_string()?),
+                "ObjectEnd" => api.object_end(),
+                "ObjectInstance" => api.object_instance(&p.next_string()?),
+                "PixelFilter" => p.basic_param_list_entrypoint(|n, p| api.pixel_filter(n, p))?,
+                "ReverseOrientation" => api.reverse_orientation(),
+                "Rotate" => {
+                    let v = [
+                        p.next_float()?,
+                        p.next_float()?,
+                        p.next_float()?,
+                        p.next_float()?,
+                    ];
+                    api.rotate(Degree(v[0]), v[1], v[2], v[3]);
                 }
-                "Rotate" => return Err(Error::NotImplemented("Rotate".to_string())),
                 "Sampler" => p.basic_param_list_entrypoint(|n, p| api.sampler(n, p))?,
                 "Scale" => {
-                    let mut v: [Float; 3] = Default::default();
-                    for i in &mut v {
-                        let tok = p.next_token(Token::Required).unwrap_or(Ok(""))?;
-                        *i = tok.parse()?;
-                    }
+                    let v = [p.next_float()?, p.next_float()?, p.next_float()?];
                     api.scale(v[0], v[1], v[2]);
                 }
-                "Shape" => return Err(Error::NotImplemented("Shape".to_string())),
-                "Texture" => return Err(Error::NotImplemented("Texture".to_string())),
-                "Transform" => return Err(Error::NotImplemented("Transform".to_string())),
-                "TransformBegin" => {
-                    return Err(Error::NotImplemented("TransformBegin".to_string()))
+                "Shape" => p.basic_param_list_entrypoint(|n, p| api.shape(n, p))?,
+                "Texture" => {
+                    let name = p.next_string()?;
+                    let kind = p.next_string()?;
+                    p.basic_param_list_entrypoint(|n, p| api.texture(&name, &kind, n, p))?
                 }
-                "TransformEnd" => return Err(Error::NotImplemented("TransformEnd".to_string())),
+                "Transform" => api.transform(p.next_matrix()?),
+                "TransformBegin" => api.transform_begin(),
+                "TransformEnd" => api.transform_end(),
                 "TransformTimes" => {
-                    return Err(Error::NotImplemented("TransformTimes".to_string()))
+                    let v = [p.next_float()?, p.next_float()?];
+                    api.transform_times(v[0], v[1]);
+                }
+                "Translate" => {
+                    let v = [p.next_float()?, p.next_float()?, p.next_float()?];
+                    api.translate(v[0], v[1], v[2]);
                 }
-                "Translate" => return Err(Error::NotImplemented("Translate".to_string())),
                 "WorldBegin" => api.world_begin(),
                 "WorldEnd" => api.world_end(),
-                _ => return Err(Error::Syntax(tok.to_string())),
+                tok => return Err(Error::UnknownDirective(tok.to_string())),
             }
         }
         Ok(())
@@ -319,35 +727,76 @@ impl<'a> Parser<'a> {
     // if they add more options upstream.
     /// Fetches the next token from the underlying data.  `None` returned at EOF. If data is
     /// available, the inner `Result` will indicate if the token was successfully parsed from the
-    /// data.
-    fn next_token(&mut self, flags: Token) -> Option<Result<&'a str, Error>> {
+    /// data.  Comments are skipped, so callers never see them.
+    fn next_token(&mut self, flags: Token) -> Option<Result<S::Token, Error>> {
         if let Some(token) = self.unget_token.take() {
             return Some(Ok(token));
         }
 
-        // TODO(wathiede): make this file_stack.last() instead of .pop()?  Trying to use .last()
-        // fights the borrow checker.
-        let tok = match self.file_stack.pop() {
-            None => {
-                if flags == Token::Required {
-                    return Some(Err(Error::EOF));
+        // Loop rather than recurse past comments and finished files, scenes may have long runs of
+        // comment lines.
+        loop {
+            let tok = match self.file_stack.last_mut() {
+                None => {
+                    if flags == Token::Required {
+                        return Some(Err(Error::EOF));
+                    }
+                    return None;
                 }
-                return None;
+                Some(last) => last.next_token(),
+            };
+            match tok {
+                // We've reached EOF in the current file. Anything more to parse?
+                None => {
+                    self.file_stack.pop();
+                }
+                Some(Ok(tok)) if tok.as_ref().starts_with('#') => (),
+                Some(tok) => return Some(tok),
             }
-            Some(mut last) => {
-                let tok = last.next();
-                self.file_stack.push(last);
-                tok
+        }
+    }
+
+    /// Reads the next token, which is required, as a `Float`.
+    fn next_float(&mut self) -> Result<Float, Error> {
+        match self.next_token(Token::Required) {
+            None => Err(Error::EOF),
+            Some(tok) => Ok(tok?.as_ref().parse()?),
+        }
+    }
+
+    /// Reads a bracketed list of the 16 values of a 4x4 matrix, as used by `Transform` and
+    /// `ConcatTransform`.
+    fn next_matrix(&mut self) -> Result<[Float; 16], Error> {
+        match self.next_token(Token::Required) {
+            None => return Err(Error::EOF),
+            Some(tok) => {
+                let tok = tok?;
+                if tok.as_ref() != "[" {
+                    return Err(Error::Syntax(tok.as_ref().to_string()));
+                }
             }
-        };
-        match tok {
-            // We've reached EOF in the current file. Anything more to parse?
-            None => {
-                self.file_stack.pop();
-                self.next_token(flags)
+        }
+        let mut m = [0.; 16];
+        for v in m.iter_mut() {
+            *v = self.next_float()?;
+        }
+        match self.next_token(Token::Required) {
+            None => Err(Error::EOF),
+            Some(tok) => {
+                let tok = tok?;
+                if tok.as_ref() != "]" {
+                    return Err(Error::Syntax(tok.as_ref().to_string()));
+                }
+                Ok(m)
             }
-            Some(Ok(tok)) if tok.starts_with('#') => self.next_token(flags),
-            Some(tok) => Some(tok),
+        }
+    }
+
+    /// Reads the next token, which is required, as a quoted string and returns it without quotes.
+    fn next_string(&mut self) -> Result<String, Error> {
+        match self.next_token(Token::Required) {
+            None => Err(Error::EOF),
+            Some(tok) => Ok(dequote_string(tok?.as_ref())?.to_string()),
         }
     }
 
@@ -360,54 +809,32 @@ impl<'a> Parser<'a> {
             };
             let decl = decl?;
 
-            if !is_quoted_string(decl) {
+            if !is_quoted_string(decl.as_ref()) {
                 self.unget_token = Some(decl);
                 return Ok(ps);
             }
 
-            let mut item = ParamListItem {
-                name: dequote_string(decl)?.to_string(),
-                ..ParamListItem::default()
-            };
-
-            // TODO(wathiede): The C++ version uses an arena allocator to manage double_values and
-            // string_values.  Profile this at some point and see if the rust version needs a
-            // similar optimization.
-            let mut add_val = |val| -> Result<(), Error> {
-                if is_quoted_string(val) {
-                    if !item.double_values.is_empty() {
-                        return Err(Error::MixedParameters);
-                    }
-                    let val = dequote_string(val)?;
-                    item.string_values.push(val);
-                } else {
-                    if !item.string_values.is_empty() {
-                        return Err(Error::MixedParameters);
-                    }
-                    item.double_values.push(val.parse::<f64>()?);
-                }
-                Ok(())
-            };
+            let mut item = ParamListItem::new(dequote_string(decl.as_ref())?);
 
             let val = match self.next_token(Token::Required) {
                 None => return Ok(ps),
                 Some(val) => val,
             };
             let val = val?;
-            if val == "[" {
+            if val.as_ref() == "[" {
                 loop {
                     let val = match self.next_token(Token::Required) {
                         None => return Ok(ps),
                         Some(val) => val,
                     };
                     let val = val?;
-                    if val == "]" {
+                    if val.as_ref() == "]" {
                         break;
                     }
-                    add_val(val)?;
+                    item.add_value(val)?;
                 }
             } else {
-                add_val(val)?;
+                item.add_value(val)?;
             }
             add_param(&mut ps, item);
         }
@@ -422,14 +849,15 @@ impl<'a> Parser<'a> {
             Some(token) => token,
         };
         let token = token?;
-        let n = dequote_string(token)?;
+        let n = dequote_string(token.as_ref())?;
         let params = self.parse_params()?;
+        debug!("{} {:?}", n, params);
         api_func(n, params);
         Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum ParamType {
     Int,
     Bool,
@@ -501,40 +929,8 @@ fn lookup_type(decl: &str) -> Option<(ParamType, &str)> {
     Some((p_type, p_name))
 }
 
-fn add_param(ps: &mut ParamSet, item: ParamListItem) {
-    // TODO(wathiede): rewrite these using slice::chunk_exact().
-    fn iter2d(items: &[f64]) -> impl Iterator<Item = (Float, Float)> + '_ {
-        let xs =
-            items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &v)| if i % 2 == 0 { Some(v as Float) } else { None });
-        let ys =
-            items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &v)| if i % 2 == 1 { Some(v as Float) } else { None });
-        xs.zip(ys)
-    }
-    fn iter3d(items: &[f64]) -> impl Iterator<Item = (Float, Float, Float)> + '_ {
-        let xs =
-            items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &v)| if i % 3 == 0 { Some(v as Float) } else { None });
-        let ys =
-            items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &v)| if i % 3 == 1 { Some(v as Float) } else { None });
-        let zs =
-            items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &v)| if i % 3 == 2 { Some(v as Float) } else { None });
-        xs.zip(ys).zip(zs).map(|((x, y), z)| (x, y, z))
-    }
-    match lookup_type(&item.name) {
+fn add_param<T: AsRef<str>>(ps: &mut ParamSet, item: ParamListItem<T>) {
+    match item.p_type {
         Some((p_type, p_name)) => {
             match p_type {
                 ParamType::Texture | ParamType::String | ParamType::Bool => {
@@ -568,162 +964,76 @@ fn add_param(ps: &mut ParamSet, item: ParamListItem) {
                 // Spectrum can be strings or numeric.
                 ParamType::Spectrum => (),
             };
-            let n_items = item.size();
-
-            match p_type {
-                ParamType::Int => {
-                    ps.add_int(
-                        p_name,
-                        item.double_values.iter().map(|f| *f as isize).collect(),
-                    );
-                }
-                ParamType::Bool => ps.add_bool(
+            match (p_type, item.numbers) {
+                (ParamType::Int, Numbers::Int(values)) => ps.add_int(p_name, values),
+                (ParamType::Bool, _) => ps.add_bool(
                     p_name,
-                    item.string_values
-                        .iter()
-                        // TODO |&s| and drop the *s
-                        .map(|s| match *s {
+                    unquoted(&item.string_values)
+                        .map(|s| match s {
                             "true" => true,
                             "false" => false,
                             _ => {
                                 warn!(
                                     "Value '{}' unknown for Boolean parameter '{}'. Using 'false'.",
-                                    s, item.name
+                                    s, p_name
                                 );
                                 false
                             }
                         })
                         .collect(),
                 ),
-                ParamType::Float => {
-                    ps.add_float(
-                        p_name,
-                        item.double_values.iter().map(|f| *f as Float).collect(),
-                    );
+                (ParamType::Float, Numbers::Float(values)) => ps.add_float(p_name, values),
+                (ParamType::Point2, Numbers::Point2(t)) => {
+                    ps.add_point2f(p_name, t.into_values("point2", p_name))
                 }
-                ParamType::Point2 => {
-                    if (n_items % 2) != 0 {
-                        warn!("Excess values given with point2 parameter '{}'. Ignoring last one of them.", item.name);
-                    }
-                    ps.add_point2f(
-                        p_name,
-                        iter2d(&item.double_values).map(Point2f::from).collect(),
-                    );
+                (ParamType::Vector2, Numbers::Vector2(t)) => {
+                    ps.add_vector2f(p_name, t.into_values("vector2", p_name))
                 }
-                ParamType::Vector2 => {
-                    if (n_items % 2) != 0 {
-                        warn!("Excess values given with vector2 parameter '{}'. Ignoring last one of them.", item.name);
-                    }
-                    ps.add_vector2f(
-                        p_name,
-                        iter2d(&item.double_values).map(Vector2f::from).collect(),
-                    );
+                (ParamType::Point3, Numbers::Point3(t)) => {
+                    ps.add_point3f(p_name, t.into_values("point3", p_name))
                 }
-                ParamType::Point3 => {
-                    if (n_items % 3) != 0 {
-                        warn!("Excess values given with point3 parameter '{}'. Ignoring last {} of them.", item.name, n_items%3);
-                    }
-                    ps.add_point3f(
-                        p_name,
-                        iter3d(&item.double_values).map(Point3f::from).collect(),
-                    );
+                (ParamType::Vector3, Numbers::Vector3(t)) => {
+                    ps.add_vector3f(p_name, t.into_values("vector3", p_name))
                 }
-                ParamType::Vector3 => {
-                    if (n_items % 3) != 0 {
-                        warn!("Excess values given with vector3 parameter '{}'. Ignoring last {} of them.", item.name, n_items%3);
-                    }
-                    ps.add_vector3f(
-                        p_name,
-                        iter3d(&item.double_values).map(Vector3f::from).collect(),
-                    );
+                (ParamType::Normal, Numbers::Normal(t)) => {
+                    ps.add_normal3f(p_name, t.into_values("normal", p_name))
                 }
-                ParamType::Normal => {
-                    if (n_items % 3) != 0 {
-                        warn!("Excess values given with normal parameter '{}'. Ignoring last {} of them.", item.name, n_items%3);
-                    }
-                    ps.add_normal3f(
-                        p_name,
-                        iter3d(&item.double_values).map(Normal3f::from).collect(),
-                    );
+                (ParamType::Rgb, Numbers::Float(values)) => {
+                    let values = whole_tuples(&values, 3, "RGB", p_name);
+                    ps.add_rgb_spectrum(p_name, values.to_vec());
                 }
-                ParamType::Rgb => {
-                    if (n_items % 3) != 0 {
-                        warn!("Excess RGB values given with parameter '{}'. Ignoring last {} of them.", item.name, n_items%3);
-                    }
-                    let end = n_items - n_items % 3;
-                    ps.add_rgb_spectrum(
-                        p_name,
-                        item.double_values
-                            .iter()
-                            .take(end)
-                            .map(|&f| f as Float)
-                            .collect(),
-                    );
+                (ParamType::Xyz, Numbers::Float(values)) => {
+                    let values = whole_tuples(&values, 3, "XYZ", p_name);
+                    ps.add_xyz_spectrum(p_name, values.to_vec());
                 }
-                ParamType::Xyz => {
-                    if (n_items % 3) != 0 {
-                        warn!("Excess XYZ values given with parameter '{}'. Ignoring last {} of them.", item.name, n_items%3);
-                    }
-                    let end = n_items - n_items % 3;
-                    ps.add_rgb_spectrum(
-                        p_name,
-                        item.double_values
-                            .iter()
-                            .take(end)
-                            .map(|&f| f as Float)
-                            .collect(),
-                    );
-                }
-                ParamType::Blackbody => {
-                    if (n_items % 2) != 0 {
-                        warn!(
-                            "Excess value given with blackbody parameter '{}'. Ignoring extra one.",
-                            item.name
-                        );
-                    }
-                    let end = n_items - n_items % 2;
-                    ps.add_blackbody(
-                        p_name,
-                        item.double_values
-                            .iter()
-                            .take(end)
-                            .map(|&f| f as Float)
-                            .collect(),
-                    );
+                (ParamType::Blackbody, Numbers::Float(values)) => {
+                    let values = whole_tuples(&values, 2, "blackbody", p_name);
+                    ps.add_blackbody(p_name, values.to_vec());
                 }
-                ParamType::Spectrum => {
+                (ParamType::Spectrum, Numbers::Float(values)) => {
                     if !item.string_values.is_empty() {
                         ps.add_sampled_spectrum_files(
                             p_name,
-                            item.string_values.iter().map(|s| s.to_string()).collect(),
-                        );
-                    } else {
-                        if (n_items % 2) != 0 {
-                            warn!(
-                                    "Non-even number of values given with sampled spectrum '{}'. Ignoring extra.",
-                                    item.name
-                                );
-                        }
-                        let end = n_items - n_items % 2;
-                        ps.add_sampled_spectrum(
-                            p_name,
-                            item.double_values
-                                .iter()
-                                .take(end)
-                                .map(|&f| f as Float)
+                            unquoted(&item.string_values)
+                                .map(|s| s.to_string())
                                 .collect(),
                         );
+                    } else {
+                        let values = whole_tuples(&values, 2, "sampled spectrum", p_name);
+                        ps.add_sampled_spectrum(p_name, values.to_vec());
                     }
                 }
-                ParamType::String => {
+                (ParamType::String, _) => {
                     ps.add_string(
                         p_name,
-                        item.string_values.iter().map(|s| s.to_string()).collect(),
+                        unquoted(&item.string_values)
+                            .map(|s| s.to_string())
+                            .collect(),
                     );
                 }
-                ParamType::Texture => {
-                    if n_items == 1 {
-                        ps.add_texture(p_name, item.string_values[0].to_string());
+                (ParamType::Texture, _) => {
+                    if item.string_values.len() == 1 {
+                        ps.add_texture(p_name, unquoted(&item.string_values).collect());
                     } else {
                         error!(
                             "Only one string allowed for 'texture' paramter '{}'",
@@ -731,12 +1041,27 @@ fn add_param(ps: &mut ParamSet, item: ParamListItem) {
                         );
                     }
                 }
+                // ParamListItem::new picks the representation from the declared type.
+                (p_type, numbers) => unreachable!("{:?} values stored as {:?}", p_type, numbers),
             }
         }
         None => warn!("Type of parameter '{}' is unknown", item.name),
     }
 }
 
+/// Returns the prefix of `values` made of whole `n`-tuples, warning when trailing values are
+/// dropped.  `kind` and `name` describe the parameter in the warning.
+fn whole_tuples<'v>(values: &'v [Float], n: usize, kind: &str, name: &str) -> &'v [Float] {
+    let excess = values.len() % n;
+    if excess != 0 {
+        warn!(
+            "Excess {} values given with parameter '{}'. Ignoring last {} of them.",
+            kind, name, excess
+        );
+    }
+    &values[..values.len() - excess]
+}
+
 fn is_quoted_string(s: &str) -> bool {
     s.len() >= 2 && s.starts_with('"') && s.ends_with('"')
 }
@@ -749,44 +1074,37 @@ fn dequote_string(s: &str) -> Result<&str, Error> {
 }
 
 /// Parse the tokens provided by `t` and called the appropriate methos on `a`.
-pub fn parse<A: API>(t: Tokenizer, api: &mut A) -> Result<(), Error> {
+pub fn parse<S: TokenSource, A: API>(t: S, api: &mut A) -> Result<(), Error> {
     Parser::parse(t, api)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Once;
-
     use super::*;
 
     use pretty_assertions::assert_eq;
 
-    use crate::core::api_test::MockAPI;
-
-    static LOGGING: Once = Once::new();
+    use crate::core::{
+        api_test::{Call, MockAPI},
+        logtest::{self, assert_warns},
+    };
 
     fn init_logging() {
-        LOGGING.call_once(|| {
-            stderrlog::new()
-                .verbosity(3)
-                .timestamp(stderrlog::Timestamp::Millisecond)
-                .init()
-                .expect("failed to init logging");
-        });
+        logtest::init();
     }
 
     #[test]
     fn tokenizer() {
         init_logging();
         let mut t = create_from_string(r#"Sampler "halton" "integer pixelsamples" 128"#.as_bytes());
-        assert_eq!(Some(Ok("Sampler")), t.next());
-        assert_eq!(Some(Ok(r#""halton""#)), t.next());
-        assert_eq!(Some(Ok(r#""integer pixelsamples""#)), t.next());
-        assert_eq!(Some(Ok("128")), t.next());
+        assert_eq!(Some(Ok("Sampler".into())), t.next());
+        assert_eq!(Some(Ok(r#""halton""#.into())), t.next());
+        assert_eq!(Some(Ok(r#""integer pixelsamples""#.into())), t.next());
+        assert_eq!(Some(Ok("128".into())), t.next());
         assert_eq!(None, t.next());
 
         let mut t = create_from_string(r#"Sampler "128"#.as_bytes());
-        assert_eq!(Some(Ok("Sampler")), t.next());
+        assert_eq!(Some(Ok("Sampler".into())), t.next());
         assert_eq!(Some(Err(Error::EOF)), t.next());
     }
 
@@ -797,6 +1115,668 @@ mod tests {
         let t = create_from_string(r#"Sampler "halton" "integer pixelsamples" 128"#.as_bytes());
         let res = parse(t, &mut api);
         assert!(res.is_ok(), "error from parse: {}", res.err().unwrap());
+        let sampler = api.assert_called_once_with_name("sampler", "halton");
+        assert_eq!(
+            sampler
+                .params()
+                .expect("sampler params")
+                .find_one_int("pixelsamples", 0),
+            128
+        );
+        assert_eq!(api.calls.len(), 1);
+    }
+
+    #[test]
+    fn parser_shape() {
+        init_logging();
+        let mut api = MockAPI::default();
+        let t = create_from_string(
+            br#"Shape "hyperboloid" "float radius" 2 "float zmin" -1 "float zmax" 3"#,
+        );
+        let res = parse(t, &mut api);
+        assert!(res.is_ok(), "error from parse: {}", res.err().unwrap());
+        let shape = api.assert_called_once_with_name("shape", "hyperboloid");
+        let params = shape.params().expect("shape params");
+        assert_eq!(params.find_one_float("radius", 0.), 2.);
+        assert_eq!(params.find_one_float("zmin", 0.), -1.);
+        assert_eq!(params.find_one_float("zmax", 0.), 3.);
+    }
+
+    #[test]
+    fn tokenizer_comments() {
+        init_logging();
+        let mut t = create_from_string(b"# first\r\nWorldBegin # second\rWorldEnd\n# last");
+        assert_eq!(Some(Ok("# first".into())), t.next());
+        assert_eq!(Some(Ok("WorldBegin".into())), t.next());
+        assert_eq!(Some(Ok("# second".into())), t.next());
+        assert_eq!(Some(Ok("WorldEnd".into())), t.next());
+        assert_eq!(Some(Ok("# last".into())), t.next());
+        assert_eq!(None, t.next());
+    }
+
+    #[test]
+    fn tokenizer_bom() {
+        init_logging();
+        let mut t = create_from_string(b"\xEF\xBB\xBFFilm \"image\"");
+        assert_eq!(Some(Ok("Film".into())), t.next());
+        assert_eq!(Some(Ok(r#""image""#.into())), t.next());
+        assert_eq!(None, t.next());
+    }
+
+    #[test]
+    fn parser_windows_line_endings() {
+        init_logging();
+        let scene = "\u{feff}# Exported on Windows\r\n\
+                     Film \"image\" \"integer xresolution\" [ 8 ]\r\n\
+                     Sampler \"halton\" # trailing comment\r\n\
+                     \"integer pixelsamples\" 4\r\n\
+                     WorldBegin\r\n\
+                     WorldEnd\r\n";
+        for input in [scene.to_string(), scene.replace("\r\n", "\r")] {
+            let mut api = MockAPI::default();
+            let res = parse(create_from_string(input.as_bytes()), &mut api);
+            assert!(
+                res.is_ok(),
+                "error from parse {:?}: {}",
+                input,
+                res.unwrap_err()
+            );
+            let sampler = api.assert_called_once_with_name("sampler", "halton");
+            assert_eq!(
+                sampler
+                    .params()
+                    .expect("sampler params")
+                    .find_one_int("pixelsamples", 0),
+                4
+            );
+            assert_eq!(api.calls.last(), Some(&Call::WorldEnd));
+        }
+    }
+
+    #[test]
+    fn parser_trailing_comment() {
+        init_logging();
+        for input in [
+            "WorldBegin\nWorldEnd\n# no newline",
+            "Sampler \"halton\" \"integer pixelsamples\" 4 # no newline",
+            "# only a comment",
+        ] {
+            let mut api = MockAPI::default();
+            let res = parse(create_from_string(input.as_bytes()), &mut api);
+            assert!(
+                res.is_ok(),
+                "error from parse {:?}: {}",
+                input,
+                res.unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn tokenizer_comment_after_token() {
+        init_logging();
+        let mut t = create_from_string(b"[ 45# degrees\n]# end");
+        assert_eq!(Some(Ok("[".into())), t.next());
+        assert_eq!(Some(Ok("45".into())), t.next());
+        assert_eq!(Some(Ok("# degrees".into())), t.next());
+        assert_eq!(Some(Ok("]".into())), t.next());
+        assert_eq!(Some(Ok("# end".into())), t.next());
+        assert_eq!(None, t.next());
+    }
+
+    #[test]
+    fn param_list_comments() {
+        use crate::core::paramset::{ParamSetItem, Value};
+        init_logging();
+
+        let want: ParamSet = vec![
+            ParamSetItem::new("radius", &Value::Float(vec![1.].into())),
+            ParamSetItem::new("indices", &Value::Int(vec![0, 1, 2].into())),
+        ]
+        .into();
+        for input in [
+            r#""sphere" "float radius" [ 1.0 ] # meters
+               "integer indices" [ 0 1 2 ]"#,
+            r#""sphere" "float radius" # meters
+               1.0 "integer indices" [ # first
+               0 1 # second
+               2 ] # done"#,
+            r#""sphere" # shape
+               "float radius" 1.0# meters
+               "integer indices" [ 0 1 2# last
+               ]# done"#,
+        ] {
+            let mut p = Parser {
+                file_stack: vec![create_from_string(input.as_bytes())],
+                unget_token: None,
+            };
+            p.basic_param_list_entrypoint(|n, p| {
+                assert_eq!("sphere", n, "for input '{}'", input);
+                assert_eq!(want, p, "for input '{}'", input);
+            })
+            .unwrap_or_else(|e| panic!("for input '{}': {}", input, e));
+        }
+    }
+
+    #[test]
+    fn parser_comments_between_directives() {
+        init_logging();
+        let mut scene = String::from("# header\n");
+        // Enough comment lines to overflow the stack if they were skipped recursively.
+        for i in 0..100_000 {
+            scene += &format!("# comment {}\n", i);
+        }
+        scene += "Camera # inline\n \"perspective\" # inline\n \"float fov\" 45# inline\n\
+                  Scale 1 # x\n 2 # y\n 3 # z\n\
+                  WorldBegin # start\n\
+                  # nothing here\n\
+                  WorldEnd # end";
+        let mut api = MockAPI::default();
+        let res = parse(create_from_string(scene.as_bytes()), &mut api);
+        assert!(res.is_ok(), "error from parse: {}", res.unwrap_err());
+    }
+
+    #[test]
+    fn parser_comment_at_eof() {
+        init_logging();
+        // Comments don't satisfy a required token, so these are still truncated.
+        for input in [
+            r#"Camera "perspective" "float fov" [ 45 # unterminated"#,
+            r#"Camera "perspective" "float fov" # no value"#,
+            "Scale 1 2 # missing z",
+            "Camera # no name",
+        ] {
+            let mut api = MockAPI::default();
+            let res = parse(create_from_string(input.as_bytes()), &mut api);
+            assert!(
+                matches!(res, Err(Error::EOF) | Err(Error::Unquoted(_))),
+                "for input {:?}: {:?}",
+                input,
+                res
+            );
+        }
+    }
+
+    #[test]
+    fn parse_object_instancing() {
+        init_logging();
+        let mut api = MockAPI::default();
+        let res = parse(
+            create_from_string(
+                &br#"ObjectBegin "box" Shape "sphere" ObjectEnd ObjectInstance "box""#[..],
+            ),
+            &mut api,
+        );
+        assert!(res.is_ok(), "error from parse: {}", res.unwrap_err());
+        assert_eq!(api.calls_to("object_begin")[0].name(), Some("box"));
+        assert_eq!(api.calls_to("object_end").len(), 1);
+        assert_eq!(api.calls_to("object_instance")[0].name(), Some("box"));
+
+        let mut api = MockAPI::default();
+        let res = parse(create_from_string(&b"ObjectInstance box"[..]), &mut api);
+        assert!(matches!(res, Err(Error::Unquoted(_))), "{:?}", res);
+    }
+
+    #[test]
+    fn parse_all_directives() {
+        init_logging();
+        let mut api = MockAPI::default();
+        let res = parse(
+            create_from_string(
+                &br#"
+ActiveTransform StartTime
+ConcatTransform [ 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1 ]
+CoordinateSystem "origin"
+CoordSysTransform "origin"
+Identity
+PixelFilter "box"
+Rotate 90 0 0 1
+Transform [ 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1 ]
+TransformTimes 0 1
+Translate 1 2 3
+MakeNamedMedium "fog" "string type" "homogeneous"
+WorldBegin
+AttributeBegin
+TransformBegin
+MakeNamedMaterial "red" "string type" "matte"
+Material "plastic"
+NamedMaterial "red"
+MediumInterface "fog" ""
+MediumInterface "fog"
+ReverseOrientation
+Texture "checks" "spectrum" "imagemap" "string filename" "checks.png"
+TransformEnd
+AttributeEnd
+WorldEnd
+"#[..],
+            ),
+            &mut api,
+        );
+        assert!(res.is_ok(), "error from parse: {}", res.unwrap_err());
+        assert_eq!(api.calls_to("active_transform_start_time").len(), 1);
+        assert_eq!(api.calls_to("concat_transform").len(), 1);
+        assert_eq!(api.calls_to("coordinate_system")[0].name(), Some("origin"));
+        assert_eq!(
+            api.calls_to("coordinate_system_transform")[0].name(),
+            Some("origin")
+        );
+        api.assert_called_once_with_name("pixel_filter", "box");
+        assert_eq!(api.calls_to("rotate")[0], &Call::Rotate(90., [0., 0., 1.]));
+        assert_eq!(
+            api.calls_to("transform_times")[0],
+            &Call::TransformTimes(0., 1.)
+        );
+        assert_eq!(api.calls_to("translate")[0], &Call::Translate([1., 2., 3.]));
+        api.assert_called_once_with_name("make_named_medium", "fog");
+        api.assert_called_once_with_name("make_named_material", "red");
+        api.assert_called_once_with_name("material", "plastic");
+        api.assert_called_once_with_name("named_material", "red");
+        assert_eq!(
+            api.calls_to("medium_interface"),
+            vec![
+                &Call::MediumInterface("fog".to_string(), "".to_string()),
+                &Call::MediumInterface("fog".to_string(), "fog".to_string()),
+            ]
+        );
+        assert_eq!(api.calls_to("reverse_orientation").len(), 1);
+        assert_eq!(api.calls_to("attribute_begin").len(), 1);
+        assert_eq!(api.calls_to("transform_end").len(), 1);
+        let texture = &api.calls_to("texture")[0];
+        assert_eq!(texture.name(), Some("checks"));
+
+        let mut api = MockAPI::default();
+        let res = parse(create_from_string(&b"Sphere"[..]), &mut api);
+        assert_eq!(res, Err(Error::UnknownDirective("Sphere".to_string())));
+    }
+
+    #[test]
+    fn whole_tuples() {
+        init_logging();
+        let values = [1., 2., 3., 4., 5., 6., 7.];
+        assert_eq!(super::whole_tuples(&values, 3, "RGB", "Kd"), &values[..6]);
+        assert_eq!(
+            super::whole_tuples(&values, 2, "point2", "uv"),
+            &values[..6]
+        );
+        assert_eq!(
+            super::whole_tuples(&values[..6], 3, "RGB", "Kd"),
+            &values[..6]
+        );
+        assert_eq!(
+            super::whole_tuples(&values[..2], 3, "RGB", "Kd"),
+            &[] as &[Float]
+        );
+    }
+
+    /// Reads from the wrapped slice at most `max` bytes at a time, like a pipe might.
+    struct ShortReads<'a> {
+        data: &'a [u8],
+        max: usize,
+    }
+
+    impl<'a> Read for ShortReads<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.max).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn byte_source_tokenizer_matches_tokenizer() {
+        init_logging();
+        for input in [
+            &br#"Sampler "halton" "integer pixelsamples" 128"#[..],
+            b"# first\r\nWorldBegin # second\rWorldEnd\n# last",
+            b"\xEF\xBB\xBFFilm \"image\"",
+            b"\xEF\xBB",
+            b"[ 45# degrees\n]# end",
+            b"  Shape\t\"sphere\"[1 2]3 \"\"",
+            br#"Texture "a\"b" "spectrum" "imagemap" "string filename" "c:\\tex.png""#,
+            b"Sampler \"128",
+            b"",
+        ] {
+            let want: Vec<_> = create_from_string(input)
+                .map(|t| t.map(Cow::into_owned))
+                .collect();
+            for capacity in [1, 4, 7, 4096] {
+                for max in [1, 2, 5, usize::MAX] {
+                    let got: Vec<_> = ByteSourceTokenizer::with_capacity(
+                        capacity,
+                        ShortReads { data: input, max },
+                    )
+                    .collect();
+                    assert_eq!(
+                        want,
+                        got,
+                        "input {:?} capacity {} max read {}",
+                        String::from_utf8_lossy(input),
+                        capacity,
+                        max
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn byte_source_tokenizer_errors() {
+        init_logging();
+        let mut t = ByteSourceTokenizer::new(&br#""a\"b\\c\td" "bad\q""#[..]);
+        assert_eq!(t.next(), Some(Ok("\"a\"b\\c\td\"".to_string())));
+        assert!(matches!(t.next(), Some(Err(Error::Syntax(_)))));
+
+        let mut t = ByteSourceTokenizer::new(&b"Film \xff"[..]);
+        assert_eq!(t.next(), Some(Ok("Film".to_string())));
+        assert!(matches!(t.next(), Some(Err(Error::StrError(_)))));
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken"))
+            }
+        }
+        let mut api = MockAPI::default();
+        assert_eq!(
+            parse(ByteSourceTokenizer::new(Broken), &mut api),
+            Err(Error::Io("broken".to_string()))
+        );
+    }
+
+    #[test]
+    fn tokenizer_escapes() {
+        init_logging();
+        let input = br#""a\"b\\c\td" "\b\f\n\r\'" "plain""#;
+        let want = ["\"a\"b\\c\td\"", "\"\x08\x0c\n\r'\"", "\"plain\""];
+        let got: Vec<_> = create_from_string(input)
+            .map(|t| t.expect("token").into_owned())
+            .collect();
+        assert_eq!(got, want);
+        let got: Vec<_> = ByteSourceTokenizer::new(&input[..])
+            .map(|t| t.expect("token"))
+            .collect();
+        assert_eq!(got, want);
+
+        // Strings without escapes are still borrowed from the scene.
+        assert!(matches!(
+            create_from_string(br#""plain""#).next(),
+            Some(Ok(Cow::Borrowed(_)))
+        ));
+
+        let input = br#""bad\q""#;
+        assert!(matches!(
+            create_from_string(input).next(),
+            Some(Err(Error::Syntax(_)))
+        ));
+        assert!(matches!(
+            ByteSourceTokenizer::new(&input[..]).next(),
+            Some(Err(Error::Syntax(_)))
+        ));
+    }
+
+    #[test]
+    fn parse_streamed_large_scene() {
+        init_logging();
+        let mut scene =
+            String::from("Film \"image\" \"string filename\" \"big.png\"\nWorldBegin\n");
+        for mesh in 0..4 {
+            scene += "Shape \"trianglemesh\" \"point3 P\" [";
+            for i in 0..50_000 {
+                let f = (mesh * 50_000 + i) as Float * 0.125;
+                scene += &format!(" {} {} {}", f, -f, f * 0.5);
+            }
+            scene += " ] \"integer indices\" [";
+            for i in 0..50_000 {
+                scene += &format!(" {}", i);
+            }
+            scene += " ] # mesh\n";
+        }
+        scene += "WorldEnd\n";
+        assert!(scene.len() > 4 << 20, "scene only {} bytes", scene.len());
+
+        let mut want = MockAPI::default();
+        parse(create_from_string(scene.as_bytes()), &mut want).expect("parse in memory");
+        let mut got = MockAPI::default();
+        parse(
+            ByteSourceTokenizer::with_capacity(
+                4096,
+                ShortReads {
+                    data: scene.as_bytes(),
+                    max: 1000,
+                },
+            ),
+            &mut got,
+        )
+        .expect("parse streamed");
+        assert_eq!(want.calls.len(), 7);
+        // Compare with == rather than assert_eq!, the diff of a mismatch is unreadably large.
+        assert!(want.calls == got.calls, "streamed calls differ");
+    }
+
+    #[test]
+    fn large_param_lists() {
+        init_logging();
+        const N: usize = 100_000;
+        let value = |i: usize| i as Float * 0.25 - 1000.;
+        let mut input = String::from(r#""trianglemesh" "point3 P" ["#);
+        for i in 0..N {
+            input.push_str(&format!(
+                " {} {} {}",
+                value(3 * i),
+                value(3 * i + 1),
+                value(3 * i + 2)
+            ));
+        }
+        input.push_str(r#" ] "integer indices" ["#);
+        for i in 0..N {
+            input.push_str(&format!(" {}", i));
+        }
+        input.push_str(r#" ] "float uv" ["#);
+        for i in 0..N {
+            input.push_str(&format!(" {}", value(i)));
+        }
+        input.push_str(" ]");
+
+        let mut want = ParamSet::default();
+        want.add_point3f(
+            "P",
+            (0..N)
+                .map(|i| Point3f::from([value(3 * i), value(3 * i + 1), value(3 * i + 2)]))
+                .collect(),
+        );
+        want.add_int("indices", (0..N as isize).collect());
+        want.add_float("uv", (0..N).map(value).collect());
+
+        let mut p = Parser {
+            file_stack: vec![create_from_string(input.as_bytes())],
+            unget_token: None,
+        };
+        p.basic_param_list_entrypoint(|n, p| {
+            assert_eq!(n, "trianglemesh");
+            // Compare with == rather than assert_eq!, the diff of a mismatch is unreadably large.
+            assert!(want == p, "parsed ParamSet differs from generated values");
+        })
+        .expect("parse");
+    }
+
+    #[test]
+    fn integer_rounding() {
+        assert_eq!(round_int(16.), (16, None));
+        assert_eq!(round_int(-3.), (-3, None));
+        assert_eq!(round_int(15.9), (16, Some("is not an integer")));
+        assert_eq!(round_int(-2.5), (-3, Some("is not an integer")));
+        assert_eq!(round_int(1e300), (isize::MAX, Some("is out of range")));
+        assert_eq!(round_int(-1e300), (isize::MIN, Some("is out of range")));
+        assert_eq!(round_int(f64::NAN), (0, Some("is out of range")));
+
+        // Values that fit in an isize are exact even when an f64 couldn't hold them.
+        assert_eq!(
+            parse_int("indices", "9007199254740993").unwrap(),
+            (1 << 53) + 1
+        );
+        assert_eq!(
+            parse_int("indices", "99999999999999999999").unwrap(),
+            isize::MAX
+        );
+        assert_eq!(parse_int("pixelsamples", "1e2").unwrap(), 100);
+        assert!(parse_int("pixelsamples", "four").is_err());
+        assert_eq!(
+            assert_warns(
+                "Value 15.9 of integer parameter 'pixelsamples' is not an integer, using 16.",
+                || parse_int("pixelsamples", "15.9")
+            ),
+            Ok(16)
+        );
+        assert_eq!(
+            assert_warns(
+                "Value 1e300 of integer parameter 'seed' is out of range",
+                || { parse_int("seed", "1e300") }
+            ),
+            Ok(isize::MAX)
+        );
+
+        let mut p = Parser {
+            file_stack: vec![create_from_string(
+                br#""random" "integer pixelsamples" [ 15.9 ] "integer seed" [ 7 ]"#,
+            )],
+            unget_token: None,
+        };
+        assert_warns("integer parameter 'pixelsamples' is not an integer", || {
+            p.basic_param_list_entrypoint(|_, ps| {
+                assert_eq!(ps.find_one_int("pixelsamples", 0), 16);
+                assert_eq!(ps.find_one_int("seed", 0), 7);
+            })
+        })
+        .expect("parse");
+    }
+
+    #[test]
+    fn add_param_spectra() {
+        use crate::core::{
+            paramset::{ParamSetItem, Value},
+            spectrum::Spectrum,
+        };
+        init_logging();
+
+        let mut xyz = ParamSet::default();
+        xyz.add_xyz_spectrum("L", vec![0.2, 0.3, 0.4]);
+        let mut rgb = ParamSet::default();
+        // The seventh value doesn't make a whole triple and is dropped.
+        rgb.add_rgb_spectrum("Kd", vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        for (input, want, warning) in [
+            (r#""diffuse" "xyz L" [ 0.2 0.3 0.4 ]"#, xyz, None),
+            (
+                r#""matte" "rgb Kd" [ 0.1 0.2 0.3 0.4 0.5 0.6 0.7 ]"#,
+                rgb,
+                Some("Excess RGB values given with parameter 'Kd'. Ignoring last 1 of them."),
+            ),
+            (
+                r#""trianglemesh" "point P" [ 1 2 3 4 ]"#,
+                vec![ParamSetItem::new(
+                    "P",
+                    &Value::Point3f(vec![[1., 2., 3.].into()].into()),
+                )]
+                .into(),
+                Some("Excess point3 values given with parameter 'P'. Ignoring last 1 of them."),
+            ),
+        ] {
+            let mut p = Parser {
+                file_stack: vec![create_from_string(input.as_bytes())],
+                unget_token: None,
+            };
+            let (res, messages) = logtest::capture(|| {
+                p.basic_param_list_entrypoint(|_, p| assert_eq!(want, p, "for input '{}'", input))
+            });
+            res.unwrap_or_else(|e| panic!("for input '{}': {}", input, e));
+            let warnings: Vec<_> = messages
+                .iter()
+                .filter(|(level, _)| *level == log::Level::Warn)
+                .map(|(_, msg)| msg.as_str())
+                .collect();
+            assert_eq!(
+                warnings,
+                warning.into_iter().collect::<Vec<_>>(),
+                "for input '{}'",
+                input
+            );
+        }
+
+        let mut p = Parser {
+            file_stack: vec![create_from_string(br#""diffuse" "xyz L" [ 0.2 0.3 0.4 ]"#)],
+            unget_token: None,
+        };
+        p.basic_param_list_entrypoint(|_, p| {
+            assert_eq!(
+                p.find_one_spectrum("L", Spectrum::new(0.)),
+                Spectrum::from_xyz([0.2, 0.3, 0.4])
+            )
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn param_set_round_trip() {
+        use crate::core::{
+            paramset::{ParamSetItem, Value},
+            spectrum::Spectrum,
+        };
+        init_logging();
+
+        let parse_params = |input: &str| {
+            let mut p = Parser {
+                file_stack: vec![create_from_string(input.as_bytes())],
+                unget_token: None,
+            };
+            let mut got = None;
+            p.basic_param_list_entrypoint(|_, p| got = Some(p))
+                .unwrap_or_else(|e| panic!("for input '{}': {}", input, e));
+            got.unwrap()
+        };
+        let input = r#""test"
+            "rgb Kd" [ 0.1 0.2 0.3 ]
+            "color Ks" [ 0.5 0.5 0.5 ]
+            "xyz L" [ 0.2 0.3 0.4 ]
+            "float fov" [ 45 ]
+            "integer indices" [ 0 1 2 ]
+            "bool on" "true"
+            "point2 uv" [ 0 1 ]
+            "vector2 v2" [ 1 0.5 ]
+            "point3 P" [ 1 2 3 4 5 6 ]
+            "vector3 up" [ 0 1 0 ]
+            "normal N" [ 0 0 1 ]
+            "string filename" "foo.exr"
+            "texture reflectance" "checks""#;
+        let ps = parse_params(input);
+        let text = ps.to_string();
+        // Spectra are written in the form they were declared, "color" is an alias for "rgb".
+        for want in [
+            r#""rgb Kd" [ 0.1 0.2 0.3 ]"#,
+            r#""rgb Ks" [ 0.5 0.5 0.5 ]"#,
+            r#""xyz L" [ 0.2 0.3 0.4 ]"#,
+        ] {
+            assert!(text.contains(want), "{} not in {}", want, text);
+        }
+        let reparsed = parse_params(&format!(r#""test" {}"#, text));
+        assert_eq!(ps, reparsed, "serialized as {}", text);
+        assert_eq!(ps.to_string(), reparsed.to_string());
+
+        // Spectra without a declaration are written as RGB.
+        let ps: ParamSet = vec![ParamSetItem::new(
+            "L",
+            &Value::Spectrum(vec![Spectrum::from_xyz([0.2, 0.3, 0.4])].into()),
+        )]
+        .into();
+        let text = ps.to_string();
+        assert!(text.starts_with(r#""rgb L" ["#), "{}", text);
+        let reparsed = parse_params(&format!(r#""test" {}"#, text));
+        let (want, got) = (
+            ps.find_one_spectrum("L", Spectrum::new(0.)).to_rgb(),
+            reparsed.find_one_spectrum("L", Spectrum::new(0.)).to_rgb(),
+        );
+        for i in 0..3 {
+            assert!((want[i] - got[i]).abs() < 1e-5, "{:?} != {:?}", want, got);
+        }
     }
 
     #[test]
@@ -809,7 +1789,7 @@ mod tests {
             want: (&'static str, ParamSet),
         }
 
-        for TestData { input, want } in vec![
+        for TestData { input, want } in [
             TestData {
                 input: r#""perspective" "float fov" 45"#,
                 want: (
@@ -875,7 +1855,7 @@ mod tests {
                 assert_eq!(want.0, n, "for input '{}'", input);
                 assert_eq!(want.1, p, "for input '{}'", input);
             })
-            .expect(&format!("for input '{}'", input));
+            .unwrap_or_else(|_| panic!("for input '{}'", input));
         }
     }
 }
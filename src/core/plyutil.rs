@@ -0,0 +1,685 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities for reading and writing triangle meshes in [PLY] files.
+//!
+//! [PLY]: http://paulbourke.net/dataformats/ply/
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use log::warn;
+use thiserror::Error;
+
+use crate::{
+    core::geometry::{Normal3f, Point2f, Point3f},
+    Float,
+};
+
+/// Encoding of the vertex and face data in a PLY file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Whitespace separated human readable values.
+    Ascii,
+    /// Little endian binary values, more compact and faster to read than `Ascii`.
+    BinaryLittleEndian,
+    /// Big endian binary values.
+    BinaryBigEndian,
+}
+
+/// Error type for reading PLY files.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Standard `io::Error` generated.
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+    /// The file's header is malformed or uses unsupported features.
+    #[error("bad PLY header: {0}")]
+    Header(String),
+    /// The file's data doesn't match its header.
+    #[error("bad PLY data: {0}")]
+    Data(String),
+}
+
+/// Mesh data read from a PLY file.  `n` and `uv` are empty if the file doesn't have them.
+#[derive(Debug, Default)]
+struct PlyMesh {
+    p: Vec<Point3f>,
+    n: Vec<Normal3f>,
+    uv: Vec<Point2f>,
+    indices: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType, Error> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::Char,
+            "uchar" | "uint8" => ScalarType::UChar,
+            "short" | "int16" => ScalarType::Short,
+            "ushort" | "uint16" => ScalarType::UShort,
+            "int" | "int32" => ScalarType::Int,
+            "uint" | "uint32" => ScalarType::UInt,
+            "float" | "float32" => ScalarType::Float,
+            "double" | "float64" => ScalarType::Double,
+            _ => return Err(Error::Header(format!("unknown property type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Char | ScalarType::UChar => 1,
+            ScalarType::Short | ScalarType::UShort => 2,
+            ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+            ScalarType::Double => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    /// The type of the list's length followed by the type of its items.
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parses the PLY header from `r`, leaving `r` positioned at the start of the data.
+fn read_header<R: BufRead>(r: &mut R) -> Result<(Format, Vec<Element>), Error> {
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> Result<(), Error> {
+        line.clear();
+        if r.read_line(line)? == 0 {
+            return Err(Error::Header("missing end_header".to_string()));
+        }
+        Ok(())
+    };
+
+    next_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(Error::Header("missing 'ply' magic".to_string()));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        next_line(&mut line)?;
+        let words: Vec<_> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", f, _version] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(Error::Header(format!("unknown format '{}'", f))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| Error::Header(format!("bad element count '{}'", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| Error::Header("property before element".to_string()))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::List(
+                        ScalarType::parse(count_ty)?,
+                        ScalarType::parse(item_ty)?,
+                    ),
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| Error::Header("property before element".to_string()))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::Scalar(ScalarType::parse(ty)?),
+                }),
+            _ => {
+                return Err(Error::Header(format!(
+                    "unexpected line '{}'",
+                    line.trim_end()
+                )))
+            }
+        }
+    }
+    let format = format.ok_or_else(|| Error::Header("missing format".to_string()))?;
+    Ok((format, elements))
+}
+
+/// Source of the numeric values in the body of a PLY file.
+trait ValueReader {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Error>;
+}
+
+/// Reads whitespace separated values from the body of an ascii PLY file.
+struct AsciiReader {
+    data: String,
+    pos: usize,
+}
+
+impl ValueReader for AsciiReader {
+    fn read(&mut self, _ty: ScalarType) -> Result<f64, Error> {
+        let rest = &self.data[self.pos..];
+        let start = rest.len() - rest.trim_start().len();
+        let rest = &rest[start..];
+        let len = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(Error::Data("unexpected end of file".to_string()));
+        }
+        self.pos += start + len;
+        rest[..len]
+            .parse()
+            .map_err(|_| Error::Data(format!("bad value '{}'", &rest[..len])))
+    }
+}
+
+/// Reads values from the body of a binary PLY file.
+struct BinaryReader<R> {
+    r: R,
+    little_endian: bool,
+}
+
+impl<R: Read> ValueReader for BinaryReader<R> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Error> {
+        let mut buf = [0_u8; 8];
+        let buf = &mut buf[..ty.size()];
+        self.r.read_exact(buf).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Data("unexpected end of file".to_string()),
+            _ => Error::IoError(err),
+        })?;
+        if !self.little_endian {
+            buf.reverse();
+        }
+        macro_rules! le {
+            ($t:ty) => {{
+                let mut b = [0_u8; std::mem::size_of::<$t>()];
+                b.copy_from_slice(buf);
+                <$t>::from_le_bytes(b) as f64
+            }};
+        }
+        Ok(match ty {
+            ScalarType::Char => le!(i8),
+            ScalarType::UChar => le!(u8),
+            ScalarType::Short => le!(i16),
+            ScalarType::UShort => le!(u16),
+            ScalarType::Int => le!(i32),
+            ScalarType::UInt => le!(u32),
+            ScalarType::Float => le!(f32),
+            ScalarType::Double => le!(f64),
+        })
+    }
+}
+
+/// Reads all the elements described by `elements` from `values`, keeping the vertex and face
+/// data.
+fn read_body(elements: &[Element], values: &mut dyn ValueReader) -> Result<PlyMesh, Error> {
+    let mut mesh = PlyMesh::default();
+    for element in elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, values, &mut mesh)?,
+            "face" => read_faces(element, values, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for prop in &element.properties {
+                        read_property(&prop.ty, values)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+/// Reads one value of a property, returning all the items if it is a list.
+fn read_property(ty: &PropertyType, values: &mut dyn ValueReader) -> Result<Vec<f64>, Error> {
+    match *ty {
+        PropertyType::Scalar(ty) => Ok(vec![values.read(ty)?]),
+        PropertyType::List(count_ty, item_ty) => {
+            let count = values.read(count_ty)?;
+            if count < 0. {
+                return Err(Error::Data(format!("negative list length {}", count)));
+            }
+            (0..count as usize).map(|_| values.read(item_ty)).collect()
+        }
+    }
+}
+
+fn read_vertices(
+    element: &Element,
+    values: &mut dyn ValueReader,
+    mesh: &mut PlyMesh,
+) -> Result<(), Error> {
+    // Index of each property of interest in `element.properties`.
+    let find = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    };
+    let (x, y, z) = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => {
+            return Err(Error::Header(
+                "vertex element requires x, y and z properties".to_string(),
+            ))
+        }
+    };
+    let normal = match (find(&["nx"]), find(&["ny"]), find(&["nz"])) {
+        (Some(nx), Some(ny), Some(nz)) => Some((nx, ny, nz)),
+        _ => None,
+    };
+    let uv = match (
+        find(&["u", "s", "texture_u", "texture_s"]),
+        find(&["v", "t", "texture_v", "texture_t"]),
+    ) {
+        (Some(u), Some(v)) => Some((u, v)),
+        _ => None,
+    };
+
+    let mut vals = vec![0.; element.properties.len()];
+    for _ in 0..element.count {
+        for (val, prop) in vals.iter_mut().zip(&element.properties) {
+            // Only the first item of list properties on vertices is kept.
+            *val = read_property(&prop.ty, values)?
+                .first()
+                .cloned()
+                .unwrap_or_default();
+        }
+        mesh.p.push(Point3f::from([
+            vals[x] as Float,
+            vals[y] as Float,
+            vals[z] as Float,
+        ]));
+        if let Some((nx, ny, nz)) = normal {
+            mesh.n.push(Normal3f::from([
+                vals[nx] as Float,
+                vals[ny] as Float,
+                vals[nz] as Float,
+            ]));
+        }
+        if let Some((u, v)) = uv {
+            mesh.uv
+                .push(Point2f::from([vals[u] as Float, vals[v] as Float]));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    values: &mut dyn ValueReader,
+    mesh: &mut PlyMesh,
+) -> Result<(), Error> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+        .ok_or_else(|| {
+            Error::Header("face element requires a vertex_indices property".to_string())
+        })?;
+    for _ in 0..element.count {
+        for (i, prop) in element.properties.iter().enumerate() {
+            let vals = read_property(&prop.ty, values)?;
+            if i != indices {
+                continue;
+            }
+            if vals.len() < 3 {
+                warn!("Ignoring PLY face with {} vertices", vals.len());
+                continue;
+            }
+            // Split polygons into a fan of triangles.
+            for j in 1..vals.len() - 1 {
+                for &v in &[vals[0], vals[j], vals[j + 1]] {
+                    if v < 0. || v as usize >= mesh.p.len() {
+                        return Err(Error::Data(format!(
+                            "vertex index {} out of range, {} vertices",
+                            v,
+                            mesh.p.len()
+                        )));
+                    }
+                    mesh.indices.push(v as usize);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the mesh stored in the PLY file `r`.
+fn parse_ply<R: BufRead>(mut r: R) -> Result<PlyMesh, Error> {
+    let (format, elements) = read_header(&mut r)?;
+    if let (Some(f), Some(v)) = (
+        elements.iter().position(|e| e.name == "face"),
+        elements.iter().position(|e| e.name == "vertex"),
+    ) {
+        if f < v {
+            return Err(Error::Header(
+                "face element must come after vertex element".to_string(),
+            ));
+        }
+    }
+    match format {
+        Format::Ascii => {
+            let mut data = String::new();
+            r.read_to_string(&mut data)?;
+            read_body(&elements, &mut AsciiReader { data, pos: 0 })
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => read_body(
+            &elements,
+            &mut BinaryReader {
+                r,
+                little_endian: format == Format::BinaryLittleEndian,
+            },
+        ),
+    }
+}
+
+/// Reads the triangle mesh stored in the ascii or binary PLY file at `path`.  The vertex
+/// positions, normals and parametric coordinates are returned along with three vertex indices
+/// per triangle.  Normals and parametric coordinates are empty if the file doesn't have them.
+/// Polygons with more than three vertices are split into triangles, and any other elements and
+/// properties are skipped.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point3f, plyutil::read_ply};
+///
+/// let (p, n, uv, indices) = read_ply("src/shapes/testdata/triangle.ply").expect("read");
+/// assert_eq!(p.len(), 3);
+/// assert!(n.is_empty() && uv.is_empty());
+/// assert_eq!(indices, vec![0, 1, 2]);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn read_ply<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Vec<Point3f>, Vec<Normal3f>, Vec<Point2f>, Vec<usize>), Error> {
+    let PlyMesh { p, n, uv, indices } = parse_ply(BufReader::new(File::open(path)?))?;
+    Ok((p, n, uv, indices))
+}
+
+/// Writes the triangle mesh with vertex positions `p` and faces `vertex_indices` to `w` as a PLY
+/// file.  `n` and `uv` are written as per-vertex normals and parametric coordinates unless they
+/// are empty.  Values are stored as 32-bit floats.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{
+///     geometry::Point3f,
+///     plyutil::{write_ply, Format},
+/// };
+///
+/// let p = vec![
+///     Point3f::from([0., 0., 0.]),
+///     Point3f::from([1., 0., 0.]),
+///     Point3f::from([0., 1., 0.]),
+/// ];
+/// let mut buf = Vec::new();
+/// write_ply(&mut buf, Format::Ascii, &[0, 1, 2], &p, &[], &[]).expect("write");
+/// let ply = String::from_utf8(buf).expect("utf8");
+/// assert!(ply.starts_with("ply\nformat ascii 1.0\n"));
+/// assert!(ply.ends_with("end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n"));
+/// ```
+pub fn write_ply<W: Write>(
+    mut w: W,
+    format: Format,
+    vertex_indices: &[usize],
+    p: &[Point3f],
+    n: &[Normal3f],
+    uv: &[Point2f],
+) -> io::Result<()> {
+    let format_name = match format {
+        Format::Ascii => "ascii",
+        Format::BinaryLittleEndian => "binary_little_endian",
+        Format::BinaryBigEndian => "binary_big_endian",
+    };
+    writeln!(w, "ply\nformat {} 1.0", format_name)?;
+    writeln!(w, "element vertex {}", p.len())?;
+    let mut properties = vec!["x", "y", "z"];
+    if !n.is_empty() {
+        properties.extend(&["nx", "ny", "nz"]);
+    }
+    if !uv.is_empty() {
+        properties.extend(&["u", "v"]);
+    }
+    for prop in properties {
+        writeln!(w, "property float {}", prop)?;
+    }
+    writeln!(w, "element face {}", vertex_indices.len() / 3)?;
+    writeln!(w, "property list uchar int vertex_indices\nend_header")?;
+
+    for (i, p) in p.iter().enumerate() {
+        let mut vals = vec![p.x, p.y, p.z];
+        if let Some(n) = n.get(i) {
+            vals.extend(&[n.x, n.y, n.z]);
+        }
+        if let Some(uv) = uv.get(i) {
+            vals.extend(&[uv.x, uv.y]);
+        }
+        match format {
+            Format::Ascii => writeln!(
+                w,
+                "{}",
+                vals.iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?,
+            Format::BinaryLittleEndian => {
+                for v in vals {
+                    #[allow(clippy::unnecessary_cast)]
+                    w.write_all(&(v as f32).to_le_bytes())?;
+                }
+            }
+            Format::BinaryBigEndian => {
+                for v in vals {
+                    #[allow(clippy::unnecessary_cast)]
+                    w.write_all(&(v as f32).to_be_bytes())?;
+                }
+            }
+        }
+    }
+    for face in vertex_indices.chunks_exact(3) {
+        match format {
+            Format::Ascii => writeln!(w, "3 {} {} {}", face[0], face[1], face[2])?,
+            Format::BinaryLittleEndian => {
+                w.write_all(&[3])?;
+                for &v in face {
+                    w.write_all(&(v as i32).to_le_bytes())?;
+                }
+            }
+            Format::BinaryBigEndian => {
+                w.write_all(&[3])?;
+                for &v in face {
+                    w.write_all(&(v as i32).to_be_bytes())?;
+                }
+            }
+        }
+    }
+    w.flush()
+}
+
+/// Writes the triangle mesh to the file at `path`, see [write_ply] for details.
+pub fn write_ply_file<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    vertex_indices: &[usize],
+    p: &[Point3f],
+    n: &[Normal3f],
+    uv: &[Point2f],
+) -> io::Result<()> {
+    write_ply(
+        BufWriter::new(File::create(path)?),
+        format,
+        vertex_indices,
+        p,
+        n,
+        uv,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{parse_ply, read_ply, write_ply_file, Format};
+    use crate::core::geometry::{Normal3f, Point2f, Point3f};
+
+    #[test]
+    fn roundtrip() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let p = vec![
+            Point3f::from([0., 0., 0.]),
+            Point3f::from([1., 0., 0.]),
+            Point3f::from([0., 1., 0.5]),
+        ];
+        let n = vec![Normal3f::from([0., 0., 1.]); 3];
+        let uv = vec![
+            Point2f::from([0., 0.]),
+            Point2f::from([1., 0.]),
+            Point2f::from([0., 1.]),
+        ];
+        for format in &[
+            Format::Ascii,
+            Format::BinaryLittleEndian,
+            Format::BinaryBigEndian,
+        ] {
+            let path = dir.path().join(format!("{:?}.ply", format));
+            write_ply_file(&path, *format, &[0, 1, 2], &p, &n, &uv).expect("write ply");
+            let (read_p, read_n, read_uv, indices) = read_ply(&path).expect("read ply");
+            assert_eq!(read_p, p);
+            assert_eq!(read_n, n);
+            assert_eq!(read_uv, uv);
+            assert_eq!(indices, vec![0, 1, 2]);
+
+            write_ply_file(&path, *format, &[0, 1, 2], &p, &[], &[]).expect("write ply");
+            let (read_p, read_n, read_uv, _) = read_ply(&path).expect("read ply");
+            assert_eq!(read_p.len(), 3);
+            assert!(read_n.is_empty());
+            assert!(read_uv.is_empty());
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let ply = b"ply
+format ascii 1.0
+comment a quad split into two triangles
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float u
+property float v
+element material 1
+property uchar red
+element face 1
+property list uchar int vertex_indices
+property uchar flags
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+255
+4 0 1 2 3 7
+";
+        let mesh = parse_ply(Cursor::new(&ply[..])).expect("read ply");
+        assert_eq!(mesh.p.len(), 4);
+        assert_eq!(mesh.p[2], Point3f::from([1., 1., 0.]));
+        assert_eq!(mesh.n[3], Normal3f::from([0., 0., 1.]));
+        assert_eq!(mesh.uv[1], Point2f::from([1., 0.]));
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn binary() {
+        let header = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+element face 1
+property list uchar uint vertex_indices
+end_header
+";
+        let mut ply = header.to_vec();
+        for v in &[0_f64, 0., 0., 1., 0., 0., 0., 1., 0.] {
+            ply.extend_from_slice(&v.to_le_bytes());
+        }
+        ply.push(3);
+        for i in 0_u32..3 {
+            ply.extend_from_slice(&i.to_le_bytes());
+        }
+        let mesh = parse_ply(Cursor::new(&ply)).expect("read ply");
+        assert_eq!(mesh.p[1], Point3f::from([1., 0., 0.]));
+        assert!(mesh.n.is_empty());
+        assert!(mesh.uv.is_empty());
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+
+        // Truncated data.
+        assert!(parse_ply(Cursor::new(&ply[..ply.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn bad_files() {
+        for ply in &[
+            &b"not a ply file\n"[..],
+            b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n0\n",
+            b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+              property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+              end_header\n0 0 0\n3 0 1 2\n",
+            b"ply\nformat ascii 1.0\nelement vertex 1\n",
+        ] {
+            assert!(parse_ply(Cursor::new(ply)).is_err(), "{:?}", ply);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Primitives bridge the geometry of shapes and the rest of the renderer, and are the interface
+//! acceleration structures implement.
+
+use std::{fmt::Debug, sync::Arc};
+
+use crate::core::{
+    geometry::{Bounds3f, Ray},
+    interaction::SurfaceInteraction,
+    light::AreaLight,
+    material::Material,
+    medium::MediumInterface,
+    shape::Shape,
+    transform::AnimatedTransform,
+};
+
+/// Primitive is the interface for anything in the scene rays can be intersected against,
+/// including aggregates of other primitives.
+pub trait Primitive: Debug + Send + Sync {
+    /// Returns a bounding box, in world space, that encloses the primitive.
+    fn world_bound(&self) -> Bounds3f;
+
+    /// Finds the closest intersection of `r` with the primitive, if any.  On a hit, `r.t_max` is
+    /// updated to the parametric distance of the intersection.
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction>;
+
+    /// Returns true if `r` intersects the primitive anywhere before `r.t_max`.  Usually cheaper
+    /// than [intersect] as the closest hit doesn't need to be found, nor the `SurfaceInteraction`
+    /// computed.
+    ///
+    /// [intersect]: Primitive::intersect
+    fn intersect_p(&self, r: &Ray) -> bool;
+}
+
+/// GeometricPrimitive pairs a [Shape] with the [Material] describing its appearance, the
+/// [AreaLight] if the shape emits light, and the media on either side of its surface.
+#[derive(Debug)]
+pub struct GeometricPrimitive {
+    shape: Arc<dyn Shape>,
+    material: Option<Arc<dyn Material>>,
+    area_light: Option<Arc<dyn AreaLight>>,
+    medium_interface: MediumInterface,
+}
+
+impl GeometricPrimitive {
+    /// Create a new `GeometricPrimitive` for `shape`.  Shapes without a `material` only delimit
+    /// participating media, rays pass through them without scattering.
+    pub fn new(
+        shape: Arc<dyn Shape>,
+        material: Option<Arc<dyn Material>>,
+        area_light: Option<Arc<dyn AreaLight>>,
+        medium_interface: MediumInterface,
+    ) -> GeometricPrimitive {
+        GeometricPrimitive {
+            shape,
+            material,
+            area_light,
+            medium_interface,
+        }
+    }
+}
+
+impl Primitive for GeometricPrimitive {
+    fn world_bound(&self) -> Bounds3f {
+        self.shape.world_bound()
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        let (t_hit, mut isect) = self.shape.intersect(r, true)?;
+        r.t_max.set(t_hit);
+        isect.material = self.material.clone();
+        isect.area_light = self.area_light.clone();
+        // Initialize the interaction's medium interface.  Surfaces that don't separate two
+        // different media take the medium of the ray that hit them.
+        let mi = &self.medium_interface;
+        isect.medium_interface = Some(if !mi.is_medium_transition() {
+            MediumInterface {
+                inside: r.medium.clone(),
+                outside: r.medium.clone(),
+            }
+        } else {
+            mi.clone()
+        });
+        Some(isect)
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        self.shape.intersect_p(r, true)
+    }
+}
+
+/// TransformedPrimitive places another primitive, usually an aggregate holding the shapes of an
+/// object instance, in the scene with its own, possibly animated, transform.  This lets the same
+/// geometry appear many times while only being stored once.
+#[derive(Debug)]
+pub struct TransformedPrimitive {
+    primitive: Arc<dyn Primitive>,
+    primitive_to_world: AnimatedTransform,
+}
+
+impl TransformedPrimitive {
+    /// Create a new `TransformedPrimitive` placing `primitive` in the world with
+    /// `primitive_to_world`.
+    pub fn new(
+        primitive: Arc<dyn Primitive>,
+        primitive_to_world: AnimatedTransform,
+    ) -> TransformedPrimitive {
+        TransformedPrimitive {
+            primitive,
+            primitive_to_world,
+        }
+    }
+}
+
+impl Primitive for TransformedPrimitive {
+    fn world_bound(&self) -> Bounds3f {
+        self.primitive_to_world
+            .motion_bounds(&self.primitive.world_bound())
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<SurfaceInteraction> {
+        // Compute _ray_ after transformation by _PrimitiveToWorld_
+        let interpolated_prim_to_world = self.primitive_to_world.interpolate(r.time);
+        let ray = interpolated_prim_to_world.inverse().transform_ray(r);
+        let isect = self.primitive.intersect(&ray)?;
+        r.t_max.set(ray.t_max.get());
+        // Transform instance's intersection data to world space
+        if interpolated_prim_to_world.is_identity() {
+            return Some(isect);
+        }
+        Some(interpolated_prim_to_world.transform_surface_interaction(isect))
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        let interpolated_prim_to_world = self.primitive_to_world.interpolate(r.time);
+        let ray = interpolated_prim_to_world.inverse().transform_ray(r);
+        self.primitive.intersect_p(&ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{GeometricPrimitive, Primitive, TransformedPrimitive};
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        core::{
+            geometry::{Bounds3f, Point3f, Ray, Vector3f},
+            transform::{AnimatedTransform, Transform},
+        },
+        shapes::sphere::Sphere,
+    };
+
+    /// A unit sphere at the origin of instance space, placed at (0, 0, 5) in the world.
+    fn instanced_sphere() -> TransformedPrimitive {
+        let identity = Transform::identity();
+        let sphere = Arc::new(Sphere::new(identity, identity, false, 1., -1., 1., 360.));
+        let prim: Arc<dyn Primitive> = Arc::new(GeometricPrimitive::new(
+            sphere,
+            None,
+            None,
+            Default::default(),
+        ));
+        let instance = Arc::new(BVHAccel::new(vec![prim], 1, SplitMethod::Sah));
+        TransformedPrimitive::new(
+            instance,
+            AnimatedTransform::from(Transform::translate([0., 0., 5.])),
+        )
+    }
+
+    #[test]
+    fn instance_intersects_in_world_space() {
+        let prim = instanced_sphere();
+        assert_eq!(
+            prim.world_bound(),
+            Bounds3f::from([[-1., -1., 4.], [1., 1., 6.]])
+        );
+
+        let r = Ray {
+            o: Point3f::from([0., 0., 0.]),
+            d: Vector3f::from([0., 0., 1.]),
+            ..Default::default()
+        };
+        assert!(prim.intersect_p(&r));
+        let isect = prim.intersect(&r).expect("hit");
+        assert!((r.t_max.get() - 4.).abs() < 1e-4);
+        assert!((isect.p - Point3f::from([0., 0., 4.])).length() < 1e-4);
+        assert!((isect.n.z + 1.).abs() < 1e-4, "n {:?}", isect.n);
+        assert_eq!(isect.shading.n, isect.n);
+
+        // The untransformed sphere would be hit by this ray, the instance isn't.
+        let miss = Ray {
+            o: Point3f::from([0., 0., -5.]),
+            d: Vector3f::from([0., 0., 1.]),
+            t_max: 6.5.into(),
+            ..Default::default()
+        };
+        assert!(!prim.intersect_p(&miss));
+        assert!(prim.intersect(&miss).is_none());
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Quaternions used to represent and interpolate rotations.
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{dot, Vector3f},
+        transform::{Matrix4x4, Transform},
+    },
+    Float,
+};
+
+/// Quaternion represents a rotation as a vector part `v` and scalar part `w`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quaternion {
+    /// Imaginary (vector) components of the quaternion.
+    pub v: Vector3f,
+    /// Real (scalar) component of the quaternion.
+    pub w: Float,
+}
+
+impl Default for Quaternion {
+    /// The default `Quaternion` represents no rotation.
+    fn default() -> Quaternion {
+        Quaternion {
+            v: Vector3f::from([0., 0., 0.]),
+            w: 1.,
+        }
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Quaternion;
+    fn add(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            v: self.v + rhs.v,
+            w: self.w + rhs.w,
+        }
+    }
+}
+
+impl Sub for Quaternion {
+    type Output = Quaternion;
+    fn sub(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            v: self.v - rhs.v,
+            w: self.w - rhs.w,
+        }
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+    fn neg(self) -> Quaternion {
+        Quaternion {
+            v: -self.v,
+            w: -self.w,
+        }
+    }
+}
+
+impl Mul<Float> for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, f: Float) -> Quaternion {
+        Quaternion {
+            v: self.v * f,
+            w: self.w * f,
+        }
+    }
+}
+
+impl Div<Float> for Quaternion {
+    type Output = Quaternion;
+    fn div(self, f: Float) -> Quaternion {
+        Quaternion {
+            v: self.v / f,
+            w: self.w / f,
+        }
+    }
+}
+
+impl From<Transform> for Quaternion {
+    /// Extracts the rotation from the upper 3x3 portion of `t`, which must be a pure rotation.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{quaternion::Quaternion, transform::Transform},
+    ///     Degree,
+    /// };
+    ///
+    /// let t = Transform::rotate(Degree::from(90.), [0., 0., 1.]);
+    /// let q = Quaternion::from(t);
+    /// assert!((q.w - (0.5 as pbrt::Float).sqrt()).abs() < 1e-5);
+    /// assert!((q.v.z - (0.5 as pbrt::Float).sqrt()).abs() < 1e-5);
+    /// ```
+    fn from(t: Transform) -> Quaternion {
+        let m = t.matrix();
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0. {
+            // Compute w from matrix trace, then xyz
+            // 4w^2 = m[0][0] + m[1][1] + m[2][2] + m[3][3] (but m[3][3] == 1)
+            let s = (trace + 1.).sqrt();
+            let w = s / 2.;
+            let s = 0.5 / s;
+            Quaternion {
+                v: Vector3f::from([
+                    (m[2][1] - m[1][2]) * s,
+                    (m[0][2] - m[2][0]) * s,
+                    (m[1][0] - m[0][1]) * s,
+                ]),
+                w,
+            }
+        } else {
+            // Compute largest of x, y, or z, then remaining components
+            let nxt = [1, 2, 0];
+            let mut q = [0.; 3];
+            let mut i = 0;
+            if m[1][1] > m[0][0] {
+                i = 1;
+            }
+            if m[2][2] > m[i][i] {
+                i = 2;
+            }
+            let j = nxt[i];
+            let k = nxt[j];
+            let mut s = ((m[i][i] - (m[j][j] + m[k][k])) + 1.).sqrt();
+            q[i] = s * 0.5;
+            if s != 0. {
+                s = 0.5 / s;
+            }
+            let w = (m[k][j] - m[j][k]) * s;
+            q[j] = (m[j][i] + m[i][j]) * s;
+            q[k] = (m[k][i] + m[i][k]) * s;
+            Quaternion {
+                v: Vector3f::from(q),
+                w,
+            }
+        }
+    }
+}
+
+impl Quaternion {
+    /// Returns a copy of this `Quaternion` scaled to unit length.
+    pub fn normalize(&self) -> Quaternion {
+        *self / dot_quaternion(*self, *self).sqrt()
+    }
+
+    /// Returns the rotation `Transform` this `Quaternion` represents.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{geometry::Vector3f, quaternion::Quaternion, transform::Transform},
+    ///     Degree,
+    /// };
+    ///
+    /// let t = Transform::rotate(Degree::from(30.), [1., 1., 0.]);
+    /// let v = Vector3f::from([0., 0., 1.]);
+    /// let want = t.transform_vector(v);
+    /// let got = Quaternion::from(t).to_transform().transform_vector(v);
+    /// assert!((want - got).length() < 1e-5);
+    /// ```
+    pub fn to_transform(&self) -> Transform {
+        let (x, y, z) = (self.v.x, self.v.y, self.v.z);
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (x * self.w, y * self.w, z * self.w);
+
+        let m = Matrix4x4::new(
+            [1. - 2. * (yy + zz), 2. * (xy - wz), 2. * (xz + wy), 0.],
+            [2. * (xy + wz), 1. - 2. * (xx + zz), 2. * (yz - wx), 0.],
+            [2. * (xz - wy), 2. * (yz + wx), 1. - 2. * (xx + yy), 0.],
+            [0., 0., 0., 1.],
+        );
+        // Rotation matrices are orthogonal so the transpose is also the inverse.
+        Transform::new(m, m.transpose())
+    }
+}
+
+/// Returns the inner product of `q1` and `q2`.
+pub fn dot_quaternion(q1: Quaternion, q2: Quaternion) -> Float {
+    dot(q1.v, q2.v) + q1.w * q2.w
+}
+
+/// Spherical linear interpolation between `q1` and `q2` by `t` in [0, 1].
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{
+///         quaternion::{slerp, Quaternion},
+///         transform::Transform,
+///     },
+///     Degree,
+/// };
+///
+/// let q1 = Quaternion::default();
+/// let q2 = Quaternion::from(Transform::rotate(Degree::from(90.), [0., 0., 1.]));
+/// let q = slerp(0.5, q1, q2);
+/// let want = Quaternion::from(Transform::rotate(Degree::from(45.), [0., 0., 1.]));
+/// assert!((q.w - want.w).abs() < 1e-5);
+/// assert!((q.v.z - want.v.z).abs() < 1e-5);
+/// ```
+pub fn slerp(t: Float, q1: Quaternion, q2: Quaternion) -> Quaternion {
+    let cos_theta = dot_quaternion(q1, q2);
+    if cos_theta > 0.9995 {
+        (q1 * (1. - t) + q2 * t).normalize()
+    } else {
+        let theta = clamp(cos_theta, -1., 1.).acos();
+        let thetap = theta * t;
+        let qperp = (q2 - q1 * cos_theta).normalize();
+        q1 * thetap.cos() + qperp * thetap.sin()
+    }
+}
@@ -0,0 +1,1828 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types modeling how light scatters at a surface.  A [BSDF] is a collection of [BxDF]s, each of
+//! which models a single kind of reflection or transmission.
+//!
+//! BxDFs operate in the local shading coordinate system, where the shading normal is +z.  The
+//! helper functions here compute trigonometric values of directions in that system.
+
+use std::fmt::Debug;
+
+use bitflags::bitflags;
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{cross, dot, spherical_direction, Normal3f, Point2f, Vector3f},
+        interaction::SurfaceInteraction,
+        material::TransportMode,
+        rng::ONE_MINUS_EPSILON,
+        sampling::cosine_sample_hemisphere,
+        spectrum::Spectrum,
+    },
+    float::consts::{FRAC_1_PI, PI},
+    Float,
+};
+
+/// Returns the cosine of the angle between `w` and the shading normal.
+pub fn cos_theta(w: &Vector3f) -> Float {
+    w.z
+}
+
+/// Returns the absolute value of the cosine of the angle between `w` and the shading normal.
+pub fn abs_cos_theta(w: &Vector3f) -> Float {
+    w.z.abs()
+}
+
+/// Returns the squared cosine of the angle between `w` and the shading normal.
+pub fn cos2_theta(w: &Vector3f) -> Float {
+    w.z * w.z
+}
+
+/// Returns the squared sine of the angle between `w` and the shading normal.
+pub fn sin2_theta(w: &Vector3f) -> Float {
+    (1. - cos2_theta(w)).max(0.)
+}
+
+/// Returns the sine of the angle between `w` and the shading normal.
+pub fn sin_theta(w: &Vector3f) -> Float {
+    sin2_theta(w).sqrt()
+}
+
+/// Returns the tangent of the angle between `w` and the shading normal.
+pub fn tan_theta(w: &Vector3f) -> Float {
+    sin_theta(w) / cos_theta(w)
+}
+
+/// Returns the squared tangent of the angle between `w` and the shading normal.
+pub fn tan2_theta(w: &Vector3f) -> Float {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+/// Returns the cosine of the azimuthal angle of `w` in the shading coordinate system.
+pub fn cos_phi(w: &Vector3f) -> Float {
+    let sin_theta = sin_theta(w);
+    if sin_theta == 0. {
+        1.
+    } else {
+        clamp(w.x / sin_theta, -1., 1.)
+    }
+}
+
+/// Returns the sine of the azimuthal angle of `w` in the shading coordinate system.
+pub fn sin_phi(w: &Vector3f) -> Float {
+    let sin_theta = sin_theta(w);
+    if sin_theta == 0. {
+        0.
+    } else {
+        clamp(w.y / sin_theta, -1., 1.)
+    }
+}
+
+/// Returns the squared cosine of the azimuthal angle of `w`.
+pub fn cos2_phi(w: &Vector3f) -> Float {
+    cos_phi(w) * cos_phi(w)
+}
+
+/// Returns the squared sine of the azimuthal angle of `w`.
+pub fn sin2_phi(w: &Vector3f) -> Float {
+    sin_phi(w) * sin_phi(w)
+}
+
+/// Returns `wo` reflected about the normal `n`.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Vector3f, reflection::reflect};
+///
+/// let wo = Vector3f::from([1., 0., 1.]);
+/// let n = Vector3f::from([0., 0., 1.]);
+/// assert_eq!(reflect(&wo, &n), Vector3f::from([-1., 0., 1.]));
+/// ```
+pub fn reflect(wo: &Vector3f, n: &Vector3f) -> Vector3f {
+    -*wo + *n * (2. * dot(*wo, *n))
+}
+
+/// Returns the direction `wi` refracts to when passing through a boundary with normal `n`, where
+/// `eta` is the ratio of the indices of refraction on the incident and transmitted sides.  `n`
+/// must be in the same hemisphere as `wi`.  Returns `None` when there is total internal
+/// reflection.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Vector3f, reflection::refract};
+///
+/// let n = Vector3f::from([0., 0., 1.]);
+/// // Light at normal incidence isn't bent.
+/// assert_eq!(
+///     refract(&n, &n, 1. / 1.5),
+///     Some(Vector3f::from([0., 0., -1.]))
+/// );
+/// // Light at a grazing angle inside glass is totally internally reflected.
+/// let wi = Vector3f::from([0.9, 0., 0.1]).normalize();
+/// assert_eq!(refract(&wi, &n, 1.5), None);
+/// ```
+pub fn refract(wi: &Vector3f, n: &Vector3f, eta: Float) -> Option<Vector3f> {
+    // Compute _cos_theta_t_ using Snell's law
+    let cos_theta_i = dot(*n, *wi);
+    let sin2_theta_i = (1. - cos_theta_i * cos_theta_i).max(0.);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    // Handle total internal reflection for transmission
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+    Some(-*wi * eta + *n * (eta * cos_theta_i - cos_theta_t))
+}
+
+/// Returns true if `w` and `wp` are on the same side of the surface.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Vector3f, reflection::same_hemisphere};
+///
+/// let up = Vector3f::from([0., 0., 1.]);
+/// assert!(same_hemisphere(&up, &Vector3f::from([1., 0., 1.])));
+/// assert!(!same_hemisphere(&up, &Vector3f::from([1., 0., -1.])));
+/// ```
+pub fn same_hemisphere(w: &Vector3f, wp: &Vector3f) -> bool {
+    w.z * wp.z > 0.
+}
+
+bitflags! {
+    /// Describes the kind of scattering a [BxDF] models, and is used to select subsets of a
+    /// [BSDF]'s BxDFs.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct BxDFType: u8 {
+        /// Scatters light back into the hemisphere it arrived from.
+        const REFLECTION = 1 << 0;
+        /// Scatters light into the opposite hemisphere.
+        const TRANSMISSION = 1 << 1;
+        /// Scatters light evenly in all directions.
+        const DIFFUSE = 1 << 2;
+        /// Scatters light mostly around a preferred direction.
+        const GLOSSY = 1 << 3;
+        /// Scatters light into a single direction.
+        const SPECULAR = 1 << 4;
+        /// All of the above.
+        const ALL = Self::REFLECTION.bits()
+            | Self::TRANSMISSION.bits()
+            | Self::DIFFUSE.bits()
+            | Self::GLOSSY.bits()
+            | Self::SPECULAR.bits();
+    }
+}
+
+/// The result of sampling an incident direction from a [BxDF] or [BSDF].
+#[derive(Clone, Debug, Default)]
+pub struct BxDFSample {
+    /// Value of the distribution function for the pair of directions.
+    pub f: Spectrum,
+    /// The sampled incident direction.
+    pub wi: Vector3f,
+    /// Probability density of having sampled `wi`.  Zero if no direction could be sampled.
+    pub pdf: Float,
+    /// The type of the BxDF that was sampled.
+    pub sampled_type: BxDFType,
+}
+
+/// BxDF is the interface for individual BRDFs and BTDFs.  All directions are in the local shading
+/// coordinate system.
+pub trait BxDF: Debug + Send + Sync {
+    /// Returns the kind of scattering this BxDF models.
+    fn bxdf_type(&self) -> BxDFType;
+
+    /// Returns true if this BxDF's type is a subset of `t`.
+    fn matches_flags(&self, t: BxDFType) -> bool {
+        t.contains(self.bxdf_type())
+    }
+
+    /// Returns the value of the distribution function for the pair of directions `wo` and `wi`.
+    fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum;
+
+    /// Samples an incident direction for the outgoing direction `wo` using the uniform sample
+    /// `u`.  The default implementation cosine-samples the hemisphere `wo` is in.
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        // Cosine-sample the hemisphere, flipping the direction if necessary
+        let mut wi = cosine_sample_hemisphere(u);
+        if wo.z < 0. {
+            wi.z *= -1.;
+        }
+        BxDFSample {
+            f: self.f(wo, &wi),
+            wi,
+            pdf: self.pdf(wo, &wi),
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    /// Returns the probability density of [sample_f] sampling `wi` given `wo`.
+    ///
+    /// [sample_f]: BxDF::sample_f
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if same_hemisphere(wo, wi) {
+            abs_cos_theta(wi) * FRAC_1_PI
+        } else {
+            0.
+        }
+    }
+}
+
+/// LambertianReflection models a perfectly diffuse surface that scatters incident light equally
+/// in all directions.
+#[derive(Debug)]
+pub struct LambertianReflection {
+    r: Spectrum,
+}
+
+impl LambertianReflection {
+    /// Create a `LambertianReflection` with reflectance `r`.
+    pub fn new(r: Spectrum) -> LambertianReflection {
+        LambertianReflection { r }
+    }
+}
+
+impl BxDF for LambertianReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::DIFFUSE
+    }
+
+    fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
+        self.r.clone() * FRAC_1_PI
+    }
+}
+
+/// LambertianTransmission models a perfectly diffuse surface that transmits incident light equally
+/// in all directions of the opposite hemisphere, e.g. thin translucent materials.
+#[derive(Debug)]
+pub struct LambertianTransmission {
+    t: Spectrum,
+}
+
+impl LambertianTransmission {
+    /// Create a `LambertianTransmission` with transmittance `t`.
+    pub fn new(t: Spectrum) -> LambertianTransmission {
+        LambertianTransmission { t }
+    }
+}
+
+impl BxDF for LambertianTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::TRANSMISSION | BxDFType::DIFFUSE
+    }
+
+    fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
+        self.t.clone() * FRAC_1_PI
+    }
+
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        // Cosine-sample the hemisphere opposite to _wo_
+        let mut wi = cosine_sample_hemisphere(u);
+        if wo.z > 0. {
+            wi.z *= -1.;
+        }
+        BxDFSample {
+            f: self.f(wo, &wi),
+            wi,
+            pdf: self.pdf(wo, &wi),
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if same_hemisphere(wo, wi) {
+            0.
+        } else {
+            abs_cos_theta(wi) * FRAC_1_PI
+        }
+    }
+}
+
+/// OrenNayar models rough diffuse surfaces as a collection of symmetric V-shaped Lambertian
+/// microfacets.  `sigma` is the standard deviation of the microfacet orientation angle, in
+/// degrees; when it's zero the model reduces to [LambertianReflection].
+#[derive(Debug)]
+pub struct OrenNayar {
+    r: Spectrum,
+    a: Float,
+    b: Float,
+}
+
+impl OrenNayar {
+    /// Create an `OrenNayar` BRDF with reflectance `r` and microfacet slope deviation `sigma`
+    /// degrees.
+    pub fn new(r: Spectrum, sigma: Float) -> OrenNayar {
+        let sigma = sigma.to_radians();
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            r,
+            a: 1. - (sigma2 / (2. * (sigma2 + 0.33))),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl BxDF for OrenNayar {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::DIFFUSE
+    }
+
+    fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum {
+        let sin_theta_i = sin_theta(wi);
+        let sin_theta_o = sin_theta(wo);
+        // Compute cosine term of Oren-Nayar model
+        let mut max_cos = 0.;
+        if sin_theta_i > 1e-4 && sin_theta_o > 1e-4 {
+            let d_cos = cos_phi(wi) * cos_phi(wo) + sin_phi(wi) * sin_phi(wo);
+            max_cos = d_cos.max(0.);
+        }
+
+        // Compute sine and tangent terms of Oren-Nayar model
+        let (sin_alpha, tan_beta) = if abs_cos_theta(wi) > abs_cos_theta(wo) {
+            (sin_theta_o, sin_theta_i / abs_cos_theta(wi))
+        } else {
+            (sin_theta_i, sin_theta_o / abs_cos_theta(wo))
+        };
+        self.r.clone() * (FRAC_1_PI * (self.a + self.b * max_cos * sin_alpha * tan_beta))
+    }
+}
+
+/// SpecularReflection models a perfectly smooth surface that reflects light only in the mirror
+/// direction.  Because it's described by a delta distribution, `f` and `pdf` always return zero
+/// and all scattering happens through `sample_f`.
+#[derive(Debug)]
+pub struct SpecularReflection {
+    r: Spectrum,
+    fresnel: Box<dyn Fresnel>,
+}
+
+impl SpecularReflection {
+    /// Create a `SpecularReflection` scaled by `r` and reflecting according to `fresnel`.
+    pub fn new(r: Spectrum, fresnel: Box<dyn Fresnel>) -> SpecularReflection {
+        SpecularReflection { r, fresnel }
+    }
+}
+
+impl BxDF for SpecularReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::SPECULAR
+    }
+
+    fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
+        Spectrum::new(0.)
+    }
+
+    fn sample_f(&self, wo: &Vector3f, _u: Point2f) -> BxDFSample {
+        // Compute perfect specular reflection direction
+        let wi = Vector3f::from([-wo.x, -wo.y, wo.z]);
+        BxDFSample {
+            f: self.fresnel.evaluate(cos_theta(&wi)) * self.r.clone() / abs_cos_theta(&wi),
+            wi,
+            pdf: 1.,
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, _wo: &Vector3f, _wi: &Vector3f) -> Float {
+        0.
+    }
+}
+
+/// Returns the refracted direction for `wo` through a smooth boundary with index of refraction
+/// `eta_a` above and `eta_b` below the surface, along with the indices on the incident and
+/// transmitted sides.
+fn specular_refract(wo: &Vector3f, eta_a: Float, eta_b: Float) -> Option<(Vector3f, Float, Float)> {
+    // Figure out which $\eta$ is incident and which is transmitted
+    let entering = cos_theta(wo) > 0.;
+    let (eta_i, eta_t) = if entering {
+        (eta_a, eta_b)
+    } else {
+        (eta_b, eta_a)
+    };
+    let n = Vector3f::from([0., 0., if entering { 1. } else { -1. }]);
+    refract(wo, &n, eta_i / eta_t).map(|wi| (wi, eta_i, eta_t))
+}
+
+/// SpecularTransmission models light passing straight through a perfectly smooth dielectric
+/// boundary, with index of refraction `eta_a` above the surface and `eta_b` below.  Like
+/// [SpecularReflection] it is described by a delta distribution.
+#[derive(Debug)]
+pub struct SpecularTransmission {
+    t: Spectrum,
+    eta_a: Float,
+    eta_b: Float,
+    mode: TransportMode,
+}
+
+impl SpecularTransmission {
+    /// Create a `SpecularTransmission` scaled by `t` for the boundary between `eta_a` and
+    /// `eta_b`.
+    pub fn new(
+        t: Spectrum,
+        eta_a: Float,
+        eta_b: Float,
+        mode: TransportMode,
+    ) -> SpecularTransmission {
+        SpecularTransmission {
+            t,
+            eta_a,
+            eta_b,
+            mode,
+        }
+    }
+}
+
+impl BxDF for SpecularTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::TRANSMISSION | BxDFType::SPECULAR
+    }
+
+    fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
+        Spectrum::new(0.)
+    }
+
+    fn sample_f(&self, wo: &Vector3f, _u: Point2f) -> BxDFSample {
+        // Compute ray direction for specular transmission
+        let (wi, eta_i, eta_t) = match specular_refract(wo, self.eta_a, self.eta_b) {
+            Some(r) => r,
+            None => {
+                return BxDFSample {
+                    sampled_type: self.bxdf_type(),
+                    ..Default::default()
+                }
+            }
+        };
+        let mut ft = self.t.clone() * (1. - fr_dielectric(cos_theta(&wi), self.eta_a, self.eta_b));
+        // Account for non-symmetry with transmission to different medium
+        if self.mode == TransportMode::Radiance {
+            ft = ft * ((eta_i * eta_i) / (eta_t * eta_t));
+        }
+        BxDFSample {
+            f: ft / abs_cos_theta(&wi),
+            wi,
+            pdf: 1.,
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, _wo: &Vector3f, _wi: &Vector3f) -> Float {
+        0.
+    }
+}
+
+/// FresnelSpecular combines [SpecularReflection] and [SpecularTransmission] for a smooth
+/// dielectric boundary into a single BxDF that chooses between reflection and transmission in
+/// proportion to the Fresnel reflectance.
+#[derive(Debug)]
+pub struct FresnelSpecular {
+    r: Spectrum,
+    t: Spectrum,
+    eta_a: Float,
+    eta_b: Float,
+    mode: TransportMode,
+}
+
+impl FresnelSpecular {
+    /// Create a `FresnelSpecular` with reflectance `r` and transmittance `t` for the boundary
+    /// between `eta_a` and `eta_b`.
+    pub fn new(
+        r: Spectrum,
+        t: Spectrum,
+        eta_a: Float,
+        eta_b: Float,
+        mode: TransportMode,
+    ) -> FresnelSpecular {
+        FresnelSpecular {
+            r,
+            t,
+            eta_a,
+            eta_b,
+            mode,
+        }
+    }
+}
+
+impl BxDF for FresnelSpecular {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::TRANSMISSION | BxDFType::SPECULAR
+    }
+
+    fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
+        Spectrum::new(0.)
+    }
+
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        let f = fr_dielectric(cos_theta(wo), self.eta_a, self.eta_b);
+        if u[0] < f {
+            // Compute specular reflection for _FresnelSpecular_
+            let wi = Vector3f::from([-wo.x, -wo.y, wo.z]);
+            return BxDFSample {
+                f: self.r.clone() * (f / abs_cos_theta(&wi)),
+                wi,
+                pdf: f,
+                sampled_type: BxDFType::REFLECTION | BxDFType::SPECULAR,
+            };
+        }
+
+        // Compute specular transmission for _FresnelSpecular_
+        let sampled_type = BxDFType::TRANSMISSION | BxDFType::SPECULAR;
+        let (wi, eta_i, eta_t) = match specular_refract(wo, self.eta_a, self.eta_b) {
+            Some(r) => r,
+            None => {
+                return BxDFSample {
+                    sampled_type,
+                    ..Default::default()
+                }
+            }
+        };
+        let mut ft = self.t.clone() * (1. - f);
+        // Account for non-symmetry with transmission to different medium
+        if self.mode == TransportMode::Radiance {
+            ft = ft * ((eta_i * eta_i) / (eta_t * eta_t));
+        }
+        BxDFSample {
+            f: ft / abs_cos_theta(&wi),
+            wi,
+            pdf: 1. - f,
+            sampled_type,
+        }
+    }
+
+    fn pdf(&self, _wo: &Vector3f, _wi: &Vector3f) -> Float {
+        0.
+    }
+}
+
+/// Computes the Fresnel reflectance of a dielectric interface for unpolarized light.  `cos_theta_i`
+/// is the cosine of the incident angle, negative if the light arrives from the inside of the
+/// surface, and `eta_i` and `eta_t` are the indices of refraction outside and inside the surface.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::reflection::fr_dielectric;
+///
+/// // At normal incidence the reflectance is ((n - 1) / (n + 1))^2.
+/// assert!((fr_dielectric(1., 1., 1.5) - 0.04).abs() < 1e-6);
+/// // Past the critical angle all light is reflected.
+/// assert_eq!(fr_dielectric(-0.1, 1., 1.5), 1.);
+/// ```
+pub fn fr_dielectric(cos_theta_i: Float, eta_i: Float, eta_t: Float) -> Float {
+    let mut cos_theta_i = clamp(cos_theta_i, -1., 1.);
+    let (mut eta_i, mut eta_t) = (eta_i, eta_t);
+    // Potentially swap indices of refraction
+    if cos_theta_i <= 0. {
+        std::mem::swap(&mut eta_i, &mut eta_t);
+        cos_theta_i = cos_theta_i.abs();
+    }
+
+    // Compute _cos_theta_t_ using Snell's law
+    let sin_theta_i = (1. - cos_theta_i * cos_theta_i).max(0.).sqrt();
+    let sin_theta_t = eta_i / eta_t * sin_theta_i;
+    // Handle total internal reflection
+    if sin_theta_t >= 1. {
+        return 1.;
+    }
+    let cos_theta_t = (1. - sin_theta_t * sin_theta_t).max(0.).sqrt();
+    let r_parl = ((eta_t * cos_theta_i) - (eta_i * cos_theta_t))
+        / ((eta_t * cos_theta_i) + (eta_i * cos_theta_t));
+    let r_perp = ((eta_i * cos_theta_i) - (eta_t * cos_theta_t))
+        / ((eta_i * cos_theta_i) + (eta_t * cos_theta_t));
+    (r_parl * r_parl + r_perp * r_perp) / 2.
+}
+
+/// Computes the Fresnel reflectance of the boundary between a dielectric and a conductor, like a
+/// metal.  `eta_i` is the index of refraction of the dielectric, `eta_t` and `k` the index of
+/// refraction and absorption coefficient of the conductor.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{reflection::fr_conductor, spectrum::Spectrum};
+///
+/// // At normal incidence the reflectance is ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2).
+/// let r = fr_conductor(1., &Spectrum::new(1.), &Spectrum::new(2.), &Spectrum::new(1.));
+/// assert!((r.channels()[0] - 2. / 10.).abs() < 1e-6, "{:?}", r);
+/// // Without absorption a conductor behaves like a dielectric.
+/// let r = fr_conductor(1., &Spectrum::new(1.), &Spectrum::new(1.5), &Spectrum::new(0.));
+/// assert!((r.channels()[0] - 0.04).abs() < 1e-6, "{:?}", r);
+/// ```
+pub fn fr_conductor(
+    cos_theta_i: Float,
+    eta_i: &Spectrum,
+    eta_t: &Spectrum,
+    k: &Spectrum,
+) -> Spectrum {
+    let cos_theta_i = clamp(cos_theta_i, -1., 1.);
+    let eta = eta_t.clone() / eta_i.clone();
+    let eta_k = k.clone() / eta_i.clone();
+
+    let cos_theta_i2 = cos_theta_i * cos_theta_i;
+    let sin_theta_i2 = 1. - cos_theta_i2;
+    let eta2 = eta.clone() * eta;
+    let eta_k2 = eta_k.clone() * eta_k;
+
+    let t0 = eta2.clone() - eta_k2.clone() - Spectrum::new(sin_theta_i2);
+    let a2_plus_b2 = (t0.clone() * t0.clone() + eta2 * eta_k2 * 4.).sqrt();
+    let t1 = a2_plus_b2.clone() + Spectrum::new(cos_theta_i2);
+    let a = ((a2_plus_b2.clone() + t0) * 0.5).sqrt();
+    let t2 = a * (2. * cos_theta_i);
+    let rs = (t1.clone() - t2.clone()) / (t1 + t2.clone());
+
+    let t3 = a2_plus_b2 * cos_theta_i2 + Spectrum::new(sin_theta_i2 * sin_theta_i2);
+    let t4 = t2.clone() * sin_theta_i2;
+    let rp = rs.clone() * (t3.clone() - t4.clone()) / (t3 + t4);
+
+    (rp + rs) * 0.5
+}
+
+/// Fresnel computes the fraction of light reflected at a surface boundary.
+pub trait Fresnel: Debug + Send + Sync {
+    /// Returns the reflectance for light arriving with the cosine of the incident angle `cos_i`.
+    fn evaluate(&self, cos_i: Float) -> Spectrum;
+}
+
+/// FresnelDielectric is the [Fresnel] term for boundaries between two dielectric materials, like
+/// air and glass.
+#[derive(Debug, Clone)]
+pub struct FresnelDielectric {
+    eta_i: Float,
+    eta_t: Float,
+}
+
+impl FresnelDielectric {
+    /// Create a `FresnelDielectric` for a boundary with index of refraction `eta_i` on the outside
+    /// and `eta_t` on the inside.
+    pub fn new(eta_i: Float, eta_t: Float) -> FresnelDielectric {
+        FresnelDielectric { eta_i, eta_t }
+    }
+}
+
+impl Fresnel for FresnelDielectric {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        Spectrum::new(fr_dielectric(cos_i, self.eta_i, self.eta_t))
+    }
+}
+
+/// FresnelConductor is the [Fresnel] term for boundaries between a dielectric and a conductor,
+/// like air and a metal.
+#[derive(Debug, Clone)]
+pub struct FresnelConductor {
+    eta_i: Spectrum,
+    eta_t: Spectrum,
+    k: Spectrum,
+}
+
+impl FresnelConductor {
+    /// Create a `FresnelConductor` for a boundary with index of refraction `eta_i` on the outside
+    /// and a conductor with index of refraction `eta_t` and absorption coefficient `k` on the
+    /// inside.
+    pub fn new(eta_i: Spectrum, eta_t: Spectrum, k: Spectrum) -> FresnelConductor {
+        FresnelConductor { eta_i, eta_t, k }
+    }
+}
+
+impl Fresnel for FresnelConductor {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        fr_conductor(cos_i.abs(), &self.eta_i, &self.eta_t, &self.k)
+    }
+}
+
+/// FresnelNoOp reflects all incident light.  Not physically plausible, but handy for some
+/// materials.
+#[derive(Debug, Clone)]
+pub struct FresnelNoOp;
+
+impl Fresnel for FresnelNoOp {
+    fn evaluate(&self, _cos_i: Float) -> Spectrum {
+        Spectrum::new(1.)
+    }
+}
+
+/// MicrofacetDistribution describes the distribution of the orientations of the tiny facets
+/// making up a rough surface.  All directions are in the local shading coordinate system.
+pub trait MicrofacetDistribution: Debug + Send + Sync {
+    /// Returns the differential area of microfacets oriented with the half-angle vector `wh`.
+    fn d(&self, wh: &Vector3f) -> Float;
+
+    /// Returns the invisible, i.e. masked, microfacet area per visible microfacet area seen from
+    /// direction `w`.
+    fn lambda(&self, w: &Vector3f) -> Float;
+
+    /// Returns the fraction of microfacets visible from direction `w`.
+    fn g1(&self, w: &Vector3f) -> Float {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Returns the fraction of microfacets visible from both `wo` and `wi`.
+    fn g(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet orientation, in the same hemisphere as `wo`, using the uniform sample
+    /// `u`.
+    fn sample_wh(&self, wo: &Vector3f, u: Point2f) -> Vector3f;
+
+    /// Returns the probability density of [sample_wh] sampling `wh` given `wo`.
+    ///
+    /// [sample_wh]: MicrofacetDistribution::sample_wh
+    fn pdf(&self, _wo: &Vector3f, wh: &Vector3f) -> Float {
+        self.d(wh) * abs_cos_theta(wh)
+    }
+}
+
+/// BeckmannDistribution is the microfacet distribution from Beckmann and Spizzichino, based on a
+/// Gaussian distribution of microfacet slopes.  `alpha_x` and `alpha_y` control the roughness
+/// along the x and y axes of the shading coordinate system.
+#[derive(Debug, Clone)]
+pub struct BeckmannDistribution {
+    alpha_x: Float,
+    alpha_y: Float,
+}
+
+impl BeckmannDistribution {
+    /// Create a `BeckmannDistribution` with roughness `alpha_x` and `alpha_y`.
+    pub fn new(alpha_x: Float, alpha_y: Float) -> BeckmannDistribution {
+        BeckmannDistribution { alpha_x, alpha_y }
+    }
+
+    /// Maps a user facing roughness value in [0, 1] to the distribution's alpha parameter, giving
+    /// a more perceptually uniform change in appearance.
+    pub fn roughness_to_alpha(roughness: Float) -> Float {
+        let x = roughness.max(1e-3).ln();
+        1.62142
+            + 0.819_955 * x
+            + 0.1734 * x * x
+            + 0.017_120_1 * x * x * x
+            + 0.000_640_711 * x * x * x * x
+    }
+}
+
+impl MicrofacetDistribution for BeckmannDistribution {
+    fn d(&self, wh: &Vector3f) -> Float {
+        let tan2_theta = tan2_theta(wh);
+        if tan2_theta.is_infinite() {
+            return 0.;
+        }
+        let cos4_theta = cos2_theta(wh) * cos2_theta(wh);
+        (-tan2_theta
+            * (cos2_phi(wh) / (self.alpha_x * self.alpha_x)
+                + sin2_phi(wh) / (self.alpha_y * self.alpha_y)))
+            .exp()
+            / (PI * self.alpha_x * self.alpha_y * cos4_theta)
+    }
+
+    fn lambda(&self, w: &Vector3f) -> Float {
+        let abs_tan_theta = tan_theta(w).abs();
+        if abs_tan_theta.is_infinite() {
+            return 0.;
+        }
+        // Compute _alpha_ for direction _w_
+        let alpha = (cos2_phi(w) * self.alpha_x * self.alpha_x
+            + sin2_phi(w) * self.alpha_y * self.alpha_y)
+            .sqrt();
+        let a = 1. / (alpha * abs_tan_theta);
+        if a >= 1.6 {
+            return 0.;
+        }
+        (1. - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+    }
+
+    fn sample_wh(&self, wo: &Vector3f, u: Point2f) -> Vector3f {
+        // Sample full distribution of normals for Beckmann distribution
+        let log_sample = {
+            let l = (1. - u[0]).ln();
+            if l.is_infinite() {
+                0.
+            } else {
+                l
+            }
+        };
+        let (tan2_theta, phi) = if self.alpha_x == self.alpha_y {
+            (-self.alpha_x * self.alpha_x * log_sample, u[1] * 2. * PI)
+        } else {
+            // Compute _tan2_theta_ and _phi_ for anisotropic Beckmann distribution
+            let mut phi = (self.alpha_y / self.alpha_x * (2. * PI * u[1] + 0.5 * PI).tan()).atan();
+            if u[1] > 0.5 {
+                phi += PI;
+            }
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let alpha_x2 = self.alpha_x * self.alpha_x;
+            let alpha_y2 = self.alpha_y * self.alpha_y;
+            (
+                -log_sample / (cos_phi * cos_phi / alpha_x2 + sin_phi * sin_phi / alpha_y2),
+                phi,
+            )
+        };
+
+        // Map sampled Beckmann angles to normal direction _wh_
+        let cos_theta = 1. / (1. + tan2_theta).sqrt();
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let wh = spherical_direction(
+            sin_theta,
+            cos_theta,
+            phi,
+            Vector3f::from([1., 0., 0.]),
+            Vector3f::from([0., 1., 0.]),
+            Vector3f::from([0., 0., 1.]),
+        );
+        if same_hemisphere(wo, &wh) {
+            wh
+        } else {
+            -wh
+        }
+    }
+}
+
+/// MicrofacetReflection is a glossy BRDF modeling a rough surface as a collection of perfectly
+/// specular microfacets.
+#[derive(Debug)]
+pub struct MicrofacetReflection {
+    r: Spectrum,
+    distribution: Box<dyn MicrofacetDistribution>,
+    fresnel: Box<dyn Fresnel>,
+}
+
+impl MicrofacetReflection {
+    /// Create a `MicrofacetReflection` scaled by `r`, whose microfacets are oriented according
+    /// to `distribution` and reflect light according to `fresnel`.
+    pub fn new(
+        r: Spectrum,
+        distribution: Box<dyn MicrofacetDistribution>,
+        fresnel: Box<dyn Fresnel>,
+    ) -> MicrofacetReflection {
+        MicrofacetReflection {
+            r,
+            distribution,
+            fresnel,
+        }
+    }
+}
+
+impl BxDF for MicrofacetReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::GLOSSY
+    }
+
+    fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum {
+        let cos_theta_o = abs_cos_theta(wo);
+        let cos_theta_i = abs_cos_theta(wi);
+        let wh = *wi + *wo;
+        // Handle degenerate cases for microfacet reflection
+        if cos_theta_i == 0. || cos_theta_o == 0. {
+            return Spectrum::new(0.);
+        }
+        if wh.x == 0. && wh.y == 0. && wh.z == 0. {
+            return Spectrum::new(0.);
+        }
+        let wh = wh.normalize();
+        // For the Fresnel call, make sure that wh is in the same hemisphere as the surface normal,
+        // so that TIR is handled correctly.
+        let wh_n = if wh.z < 0. { -wh } else { wh };
+        let f = self.fresnel.evaluate(dot(*wi, wh_n));
+        self.r.clone()
+            * f
+            * (self.distribution.d(&wh) * self.distribution.g(wo, wi)
+                / (4. * cos_theta_i * cos_theta_o))
+    }
+
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        // Sample microfacet orientation _wh_ and reflected direction _wi_
+        if wo.z == 0. {
+            return BxDFSample::default();
+        }
+        let wh = self.distribution.sample_wh(wo, u);
+        if dot(*wo, wh) < 0. {
+            // Should be rare
+            return BxDFSample::default();
+        }
+        let wi = reflect(wo, &wh);
+        if !same_hemisphere(wo, &wi) {
+            return BxDFSample::default();
+        }
+
+        // Compute PDF of _wi_ for microfacet reflection
+        BxDFSample {
+            f: self.f(wo, &wi),
+            wi,
+            pdf: self.distribution.pdf(wo, &wh) / (4. * dot(*wo, wh)),
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let wh = (*wo + *wi).normalize();
+        self.distribution.pdf(wo, &wh) / (4. * dot(*wo, wh))
+    }
+}
+
+/// MicrofacetTransmission is a glossy BTDF modeling light refracted through a rough dielectric
+/// boundary, with index of refraction `eta_a` above the surface and `eta_b` below, as a collection
+/// of perfectly smooth microfacets.
+#[derive(Debug)]
+pub struct MicrofacetTransmission {
+    t: Spectrum,
+    distribution: Box<dyn MicrofacetDistribution>,
+    eta_a: Float,
+    eta_b: Float,
+    fresnel: FresnelDielectric,
+    mode: TransportMode,
+}
+
+impl MicrofacetTransmission {
+    /// Create a `MicrofacetTransmission` scaled by `t`, whose microfacets are oriented according
+    /// to `distribution`, for the boundary between `eta_a` and `eta_b`.
+    pub fn new(
+        t: Spectrum,
+        distribution: Box<dyn MicrofacetDistribution>,
+        eta_a: Float,
+        eta_b: Float,
+        mode: TransportMode,
+    ) -> MicrofacetTransmission {
+        MicrofacetTransmission {
+            t,
+            distribution,
+            eta_a,
+            eta_b,
+            fresnel: FresnelDielectric::new(eta_a, eta_b),
+            mode,
+        }
+    }
+
+    /// Returns the relative index of refraction of the side of the boundary `wi` is on, to that of
+    /// the side `wo` is on.
+    fn eta(&self, wo: &Vector3f) -> Float {
+        if cos_theta(wo) > 0. {
+            self.eta_b / self.eta_a
+        } else {
+            self.eta_a / self.eta_b
+        }
+    }
+}
+
+impl BxDF for MicrofacetTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::TRANSMISSION | BxDFType::GLOSSY
+    }
+
+    fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum {
+        if same_hemisphere(wo, wi) {
+            // transmission only
+            return Spectrum::new(0.);
+        }
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        if cos_theta_i == 0. || cos_theta_o == 0. {
+            return Spectrum::new(0.);
+        }
+
+        // Compute $\wh$ from $\wo$ and $\wi$ for microfacet transmission
+        let eta = self.eta(wo);
+        let wh = (*wo + *wi * eta).normalize();
+        let wh = if wh.z < 0. { -wh } else { wh };
+
+        // Same side?
+        if dot(*wo, wh) * dot(*wi, wh) > 0. {
+            return Spectrum::new(0.);
+        }
+
+        let f = self.fresnel.evaluate(dot(*wo, wh));
+        let sqrt_denom = dot(*wo, wh) + eta * dot(*wi, wh);
+        let factor = if self.mode == TransportMode::Radiance {
+            1. / eta
+        } else {
+            1.
+        };
+        (Spectrum::new(1.) - f)
+            * self.t.clone()
+            * (self.distribution.d(&wh)
+                * self.distribution.g(wo, wi)
+                * eta
+                * eta
+                * dot(*wi, wh).abs()
+                * dot(*wo, wh).abs()
+                * factor
+                * factor
+                / (cos_theta_i * cos_theta_o * sqrt_denom * sqrt_denom))
+                .abs()
+    }
+
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        if wo.z == 0. {
+            return BxDFSample::default();
+        }
+        let wh = self.distribution.sample_wh(wo, u);
+        if dot(*wo, wh) < 0. {
+            // Should be rare
+            return BxDFSample::default();
+        }
+        let eta = 1. / self.eta(wo);
+        let wi = match refract(wo, &wh, eta) {
+            Some(wi) => wi,
+            None => return BxDFSample::default(),
+        };
+        BxDFSample {
+            f: self.f(wo, &wi),
+            wi,
+            pdf: self.pdf(wo, &wi),
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        // Compute $\wh$ from $\wo$ and $\wi$ for microfacet transmission
+        let eta = self.eta(wo);
+        let wh = (*wo + *wi * eta).normalize();
+        if dot(*wo, wh) * dot(*wi, wh) > 0. {
+            return 0.;
+        }
+
+        // Compute change of variables _dwh\_dwi_ for microfacet transmission
+        let sqrt_denom = dot(*wo, wh) + eta * dot(*wi, wh);
+        let dwh_dwi = ((eta * eta * dot(*wi, wh)) / (sqrt_denom * sqrt_denom)).abs();
+        self.distribution.pdf(wo, &wh) * dwh_dwi
+    }
+}
+
+/// FresnelBlend models a diffuse substrate under a glossy specular coating, after Ashikhmin and
+/// Shirley.  `rd` is the diffuse reflectance and `rs` the specular reflectance at normal
+/// incidence; the coating's reflectance increases towards grazing angles following Schlick's
+/// approximation, leaving less light for the diffuse layer.
+#[derive(Debug)]
+pub struct FresnelBlend {
+    rd: Spectrum,
+    rs: Spectrum,
+    distribution: Box<dyn MicrofacetDistribution>,
+}
+
+fn pow5(v: Float) -> Float {
+    (v * v) * (v * v) * v
+}
+
+impl FresnelBlend {
+    /// Create a `FresnelBlend` with diffuse reflectance `rd` and specular reflectance `rs`, whose
+    /// coating microfacets are oriented according to `distribution`.
+    pub fn new(
+        rd: Spectrum,
+        rs: Spectrum,
+        distribution: Box<dyn MicrofacetDistribution>,
+    ) -> FresnelBlend {
+        FresnelBlend {
+            rd,
+            rs,
+            distribution,
+        }
+    }
+
+    fn schlick_fresnel(&self, cos_theta: Float) -> Spectrum {
+        self.rs.clone() + self.one_minus_rs() * pow5(1. - cos_theta)
+    }
+
+    fn one_minus_rs(&self) -> Spectrum {
+        Spectrum::new(1.) + self.rs.clone() * -1.
+    }
+}
+
+impl BxDF for FresnelBlend {
+    fn bxdf_type(&self) -> BxDFType {
+        BxDFType::REFLECTION | BxDFType::GLOSSY
+    }
+
+    fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum {
+        let diffuse = self.rd.clone()
+            * self.one_minus_rs()
+            * ((28. / (23. * PI))
+                * (1. - pow5(1. - 0.5 * abs_cos_theta(wi)))
+                * (1. - pow5(1. - 0.5 * abs_cos_theta(wo))));
+        let wh = *wi + *wo;
+        if wh.x == 0. && wh.y == 0. && wh.z == 0. {
+            return Spectrum::new(0.);
+        }
+        let wh = wh.normalize();
+        let specular = self.schlick_fresnel(dot(*wi, wh))
+            * (self.distribution.d(&wh)
+                / (4. * dot(*wi, wh).abs() * abs_cos_theta(wi).max(abs_cos_theta(wo))));
+        diffuse + specular
+    }
+
+    fn sample_f(&self, wo: &Vector3f, u: Point2f) -> BxDFSample {
+        let wi = if u[0] < 0.5 {
+            // Cosine-sample the hemisphere, flipping the direction if necessary
+            let u = Point2f::from([(2. * u[0]).min(ONE_MINUS_EPSILON), u[1]]);
+            let mut wi = cosine_sample_hemisphere(u);
+            if wo.z < 0. {
+                wi.z *= -1.;
+            }
+            wi
+        } else {
+            // Sample microfacet orientation _wh_ and reflected direction _wi_
+            let u = Point2f::from([(2. * (u[0] - 0.5)).min(ONE_MINUS_EPSILON), u[1]]);
+            let wh = self.distribution.sample_wh(wo, u);
+            let wi = reflect(wo, &wh);
+            if !same_hemisphere(wo, &wi) {
+                return BxDFSample::default();
+            }
+            wi
+        };
+        BxDFSample {
+            f: self.f(wo, &wi),
+            wi,
+            pdf: self.pdf(wo, &wi),
+            sampled_type: self.bxdf_type(),
+        }
+    }
+
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let wh = (*wo + *wi).normalize();
+        let pdf_wh = self.distribution.pdf(wo, &wh);
+        0.5 * (abs_cos_theta(wi) * FRAC_1_PI + pdf_wh / (4. * dot(*wo, wh)))
+    }
+}
+
+/// BSDF is the collection of BxDFs describing the scattering at a point on a surface.  Its
+/// methods take and return directions in world space.
+#[derive(Debug)]
+pub struct BSDF {
+    /// Relative index of refraction over the surface boundary, 1 for opaque surfaces.
+    pub eta: Float,
+    ns: Normal3f,
+    ng: Normal3f,
+    ss: Vector3f,
+    ts: Vector3f,
+    bxdfs: Vec<Box<dyn BxDF>>,
+}
+
+impl BSDF {
+    /// Create an empty `BSDF` whose shading coordinate system is computed from `si`'s shading
+    /// geometry.
+    pub fn new(si: &SurfaceInteraction, eta: Float) -> BSDF {
+        let ns = si.shading.n;
+        let ss = si.shading.dpdu.normalize();
+        BSDF {
+            eta,
+            ns,
+            ng: si.n,
+            ss,
+            ts: cross(Vector3f::from(ns), ss),
+            bxdfs: Vec::new(),
+        }
+    }
+
+    /// Adds `b` to the BxDFs making up this `BSDF`.
+    pub fn add(&mut self, b: Box<dyn BxDF>) {
+        self.bxdfs.push(b);
+    }
+
+    /// Returns the number of BxDFs matching `flags`.
+    pub fn num_components(&self, flags: BxDFType) -> usize {
+        self.bxdfs.iter().filter(|b| b.matches_flags(flags)).count()
+    }
+
+    /// Transforms `v` from world space to the local shading coordinate system.
+    pub fn world_to_local(&self, v: &Vector3f) -> Vector3f {
+        Vector3f::from([
+            dot(*v, self.ss),
+            dot(*v, self.ts),
+            dot(*v, Vector3f::from(self.ns)),
+        ])
+    }
+
+    /// Transforms `v` from the local shading coordinate system to world space.
+    pub fn local_to_world(&self, v: &Vector3f) -> Vector3f {
+        self.ss * v.x + self.ts * v.y + Vector3f::from(self.ns) * v.z
+    }
+
+    /// Returns the value of the BSDF for the pair of world space directions, including only the
+    /// BxDFs matching `flags`.
+    pub fn f(&self, wo_w: &Vector3f, wi_w: &Vector3f, flags: BxDFType) -> Spectrum {
+        let wi = self.world_to_local(wi_w);
+        let wo = self.world_to_local(wo_w);
+        if wo.z == 0. {
+            return Spectrum::new(0.);
+        }
+        let ng = Vector3f::from(self.ng);
+        // Use the geometric normal to decide between reflection and transmission, to avoid light
+        // leaks caused by shading normals.
+        let reflect = dot(*wi_w, ng) * dot(*wo_w, ng) > 0.;
+        let mut f = Spectrum::new(0.);
+        for b in &self.bxdfs {
+            if b.matches_flags(flags)
+                && ((reflect && b.bxdf_type().contains(BxDFType::REFLECTION))
+                    || (!reflect && b.bxdf_type().contains(BxDFType::TRANSMISSION)))
+            {
+                f += b.f(&wo, &wi);
+            }
+        }
+        f
+    }
+
+    /// Samples an incident direction for the world space outgoing direction `wo_world`.  One of
+    /// the BxDFs matching `bsdf_type` is chosen with the first dimension of `u`, then sampled.
+    /// The returned `pdf` and `f` account for all the matching BxDFs.
+    pub fn sample_f(&self, wo_world: &Vector3f, u: Point2f, bsdf_type: BxDFType) -> BxDFSample {
+        // Choose which _BxDF_ to sample
+        let matching_comps = self.num_components(bsdf_type);
+        if matching_comps == 0 {
+            return BxDFSample::default();
+        }
+        let comp = ((u.x * matching_comps as Float).floor() as usize).min(matching_comps - 1);
+        let bxdf = self
+            .bxdfs
+            .iter()
+            .filter(|b| b.matches_flags(bsdf_type))
+            .nth(comp)
+            .expect("matching BxDF");
+
+        // Remap _BxDF_ sample _u_ to [0,1)^2
+        let u_remapped = Point2f::from([
+            (u.x * matching_comps as Float - comp as Float).min(ONE_MINUS_EPSILON),
+            u.y,
+        ]);
+
+        // Sample chosen _BxDF_
+        let wo = self.world_to_local(wo_world);
+        if wo.z == 0. {
+            return BxDFSample::default();
+        }
+        let mut s = bxdf.sample_f(&wo, u_remapped);
+        if s.pdf == 0. {
+            return BxDFSample {
+                sampled_type: s.sampled_type,
+                ..Default::default()
+            };
+        }
+        let wi = s.wi;
+        s.wi = self.local_to_world(&wi);
+
+        // Compute overall PDF with all matching _BxDF_s
+        let is_specular = bxdf.bxdf_type().contains(BxDFType::SPECULAR);
+        if !is_specular && matching_comps > 1 {
+            for b in &self.bxdfs {
+                if !std::ptr::eq(b.as_ref(), bxdf.as_ref()) && b.matches_flags(bsdf_type) {
+                    s.pdf += b.pdf(&wo, &wi);
+                }
+            }
+        }
+        if matching_comps > 1 {
+            s.pdf /= matching_comps as Float;
+        }
+
+        // Compute value of BSDF for sampled direction
+        if !is_specular {
+            let ng = Vector3f::from(self.ng);
+            let reflect = dot(s.wi, ng) * dot(*wo_world, ng) > 0.;
+            let mut f = Spectrum::new(0.);
+            for b in &self.bxdfs {
+                if b.matches_flags(bsdf_type)
+                    && ((reflect && b.bxdf_type().contains(BxDFType::REFLECTION))
+                        || (!reflect && b.bxdf_type().contains(BxDFType::TRANSMISSION)))
+                {
+                    f += b.f(&wo, &wi);
+                }
+            }
+            s.f = f;
+        }
+        s
+    }
+
+    /// Returns the probability density of [sample_f] sampling `wi_world` given `wo_world`.
+    ///
+    /// [sample_f]: BSDF::sample_f
+    pub fn pdf(&self, wo_world: &Vector3f, wi_world: &Vector3f, flags: BxDFType) -> Float {
+        if self.bxdfs.is_empty() {
+            return 0.;
+        }
+        let wo = self.world_to_local(wo_world);
+        let wi = self.world_to_local(wi_world);
+        if wo.z == 0. {
+            return 0.;
+        }
+        let mut pdf = 0.;
+        let mut matching_comps = 0;
+        for b in &self.bxdfs {
+            if b.matches_flags(flags) {
+                matching_comps += 1;
+                pdf += b.pdf(&wo, &wi);
+            }
+        }
+        if matching_comps > 0 {
+            pdf / matching_comps as Float
+        } else {
+            0.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BeckmannDistribution, BxDF, BxDFType, Fresnel, FresnelBlend, FresnelConductor,
+        FresnelDielectric, FresnelNoOp, FresnelSpecular, LambertianReflection,
+        LambertianTransmission, MicrofacetDistribution, MicrofacetReflection,
+        MicrofacetTransmission, OrenNayar, SpecularReflection, SpecularTransmission, BSDF,
+    };
+    use crate::{
+        core::{
+            geometry::{Normal3f, Point2f, Vector3f},
+            interaction::{Interaction, Shading, SurfaceInteraction},
+            material::TransportMode,
+            rng::Rng,
+            sampling::uniform_sample_sphere,
+            spectrum::Spectrum,
+        },
+        float::consts::{FRAC_1_PI, FRAC_PI_2, PI},
+        Float,
+    };
+
+    fn surface() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 1., 0.]);
+        SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lambertian() {
+        let l = LambertianReflection::new(Spectrum::new(0.5));
+        let wo = Vector3f::from([0., 0., 1.]);
+        let s = l.sample_f(&wo, Point2f::from([0.3, 0.7]));
+        assert!(s.wi.z > 0.);
+        assert_eq!(s.f, Spectrum::new(0.5 * FRAC_1_PI));
+        assert!((s.pdf - s.wi.z * FRAC_1_PI).abs() < 1e-6);
+        assert_eq!(l.pdf(&wo, &Vector3f::from([0., 0., -1.])), 0.);
+    }
+
+    #[test]
+    fn lambertian_transmission() {
+        let t = LambertianTransmission::new(Spectrum::new(0.8));
+        assert_eq!(t.bxdf_type(), BxDFType::TRANSMISSION | BxDFType::DIFFUSE);
+        for wo in &[
+            Vector3f::from([0., 0., 1.]),
+            Vector3f::from([0.3, -0.4, -0.5]).normalize(),
+        ] {
+            let s = t.sample_f(wo, Point2f::from([0.3, 0.7]));
+            assert!(s.wi.z * wo.z < 0., "{:?} not opposite {:?}", s.wi, wo);
+            assert_eq!(s.f, Spectrum::new(0.8 * FRAC_1_PI));
+            assert!((s.pdf - s.wi.z.abs() * FRAC_1_PI).abs() < 1e-6);
+            assert_eq!(t.pdf(wo, wo), 0.);
+        }
+
+        // Reciprocity, swapping the directions doesn't change the value.
+        let wo = Vector3f::from([0.2, 0.5, 0.8]).normalize();
+        let wi = Vector3f::from([-0.6, 0.1, -0.3]).normalize();
+        assert_eq!(t.f(&wo, &wi), t.f(&wi, &wo));
+
+        // Energy conservation, the hemispherical-directional transmittance is `t`, never more
+        // than the light arriving.
+        let wo = Vector3f::from([0., 0., 1.]);
+        let transmitted = integrate_hemisphere(0., FRAC_PI_2, |w| {
+            let wi = Vector3f::from([w.x, w.y, -w.z]);
+            t.f(&wo, &wi).max_component_value() * w.z
+        });
+        assert!((transmitted - 0.8).abs() < 1e-3, "{}", transmitted);
+    }
+
+    #[test]
+    fn oren_nayar_sigma_zero_is_lambertian() {
+        let r = Spectrum::new(0.5);
+        let on = OrenNayar::new(r.clone(), 0.);
+        let l = LambertianReflection::new(r);
+        assert_eq!(on.bxdf_type(), l.bxdf_type());
+        let dirs = [
+            Vector3f::from([0., 0., 1.]),
+            Vector3f::from([0.3, 0.4, 0.5]).normalize(),
+            Vector3f::from([-0.8, 0.1, 0.2]).normalize(),
+        ];
+        for wo in &dirs {
+            for wi in &dirs {
+                let (a, b) = (on.f(wo, wi), l.f(wo, wi));
+                assert!((a.max_component_value() - b.max_component_value()).abs() < 1e-6);
+            }
+        }
+
+        // Rough surfaces scatter more light back towards grazing light sources.
+        let rough = OrenNayar::new(Spectrum::new(0.5), 20.);
+        let wo = Vector3f::from([0.8, 0., 0.2]).normalize();
+        assert!(rough.f(&wo, &wo).max_component_value() > l.f(&wo, &wo).max_component_value());
+    }
+
+    #[test]
+    fn bsdf_world_space() {
+        let mut bsdf = BSDF::new(&surface(), 1.);
+        bsdf.add(Box::new(LambertianReflection::new(Spectrum::new(1.))));
+        // The shading normal is +y in world space, which is +z locally.
+        assert_eq!(
+            bsdf.world_to_local(&Vector3f::from([0., 1., 0.])),
+            Vector3f::from([0., 0., 1.])
+        );
+        let v = Vector3f::from([0.3, 0.4, 0.5]);
+        assert!((bsdf.local_to_world(&bsdf.world_to_local(&v)) - v).length() < 1e-6);
+
+        let wo = Vector3f::from([0., 1., 0.]);
+        let above = Vector3f::from([1., 1., 0.]).normalize();
+        let below = Vector3f::from([1., -1., 0.]).normalize();
+        assert_eq!(bsdf.f(&wo, &above, BxDFType::ALL), Spectrum::new(FRAC_1_PI));
+        assert!(bsdf.f(&wo, &below, BxDFType::ALL).is_black());
+        assert!(bsdf.f(&wo, &above, BxDFType::SPECULAR).is_black());
+
+        let s = bsdf.sample_f(&wo, Point2f::from([0.2, 0.9]), BxDFType::ALL);
+        assert!(s.wi.y > 0.);
+        assert_eq!(s.sampled_type, BxDFType::REFLECTION | BxDFType::DIFFUSE);
+        assert!((s.pdf - bsdf.pdf(&wo, &s.wi, BxDFType::ALL)).abs() < 1e-6);
+        assert_eq!(
+            bsdf.sample_f(&wo, Point2f::from([0.2, 0.9]), BxDFType::SPECULAR)
+                .pdf,
+            0.
+        );
+    }
+
+    #[test]
+    fn microfacet_reflection() {
+        let m = MicrofacetReflection::new(
+            Spectrum::new(1.),
+            Box::new(BeckmannDistribution::new(0.3, 0.3)),
+            Box::new(FresnelDielectric::new(1., 1.5)),
+        );
+        assert_eq!(m.bxdf_type(), BxDFType::REFLECTION | BxDFType::GLOSSY);
+        let wo = Vector3f::from([0.3, 0., 1.]).normalize();
+        for u in &[[0.1, 0.2], [0.5, 0.5], [0.7, 0.9]] {
+            let s = m.sample_f(&wo, Point2f::from(*u));
+            if s.pdf == 0. {
+                continue;
+            }
+            assert!(s.wi.z > 0.);
+            assert!((s.pdf - m.pdf(&wo, &s.wi)).abs() < 1e-3 * s.pdf);
+            assert_eq!(s.f, m.f(&wo, &s.wi));
+        }
+        // Lobe is centered around the mirror direction.
+        let mirror = Vector3f::from([-wo.x, -wo.y, wo.z]);
+        let off = Vector3f::from([0.8, 0., 0.2]).normalize();
+        assert!(m.f(&wo, &mirror).max_component_value() > m.f(&wo, &off).max_component_value());
+        // Directions below the surface are never sampled.
+        assert_eq!(m.pdf(&wo, &Vector3f::from([0., 0., -1.])), 0.);
+    }
+
+    #[test]
+    fn fresnel_blend() {
+        let fb = FresnelBlend::new(
+            Spectrum::new(0.5),
+            Spectrum::new(0.04),
+            Box::new(BeckmannDistribution::new(0.3, 0.3)),
+        );
+        assert_eq!(fb.bxdf_type(), BxDFType::REFLECTION | BxDFType::GLOSSY);
+        let wo = Vector3f::from([0.3, 0., 1.]).normalize();
+        // Exercise both the diffuse (u[0] < 0.5) and glossy sampling strategies.
+        for u in &[[0.1, 0.2], [0.4, 0.7], [0.6, 0.5], [0.9, 0.9]] {
+            let s = fb.sample_f(&wo, Point2f::from(*u));
+            if s.pdf == 0. {
+                continue;
+            }
+            assert!(s.wi.z > 0.);
+            assert!((s.pdf - fb.pdf(&wo, &s.wi)).abs() < 1e-3 * s.pdf);
+            assert_eq!(s.f, fb.f(&wo, &s.wi));
+        }
+        assert_eq!(fb.pdf(&wo, &Vector3f::from([0., 0., -1.])), 0.);
+    }
+
+    /// Integrates `f` over the hemisphere around +z, restricted to polar angles in
+    /// [theta_min, theta_max), with the midpoint rule.
+    fn integrate_hemisphere(
+        theta_min: Float,
+        theta_max: Float,
+        f: impl Fn(&Vector3f) -> Float,
+    ) -> Float {
+        let (n_theta, n_phi) = (200, 400);
+        let d_theta = (theta_max - theta_min) / n_theta as Float;
+        let d_phi = 2. * PI / n_phi as Float;
+        let mut sum = 0.;
+        for i in 0..n_theta {
+            let theta = theta_min + (i as Float + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            for j in 0..n_phi {
+                let phi = (j as Float + 0.5) * d_phi;
+                let w = Vector3f::from([sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta]);
+                sum += f(&w) * sin_theta * d_theta * d_phi;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn beckmann_distribution_statistics() {
+        let wo = Vector3f::from([0., 0., 1.]);
+        for &(alpha_x, alpha_y) in &[(0.1, 0.1), (0.3, 0.3), (0.2, 0.6), (0.8, 0.8)] {
+            let d = BeckmannDistribution::new(alpha_x, alpha_y);
+            // The projected microfacet area is the macro surface's area.
+            let projected = integrate_hemisphere(0., FRAC_PI_2, |wh| d.d(wh) * wh.z);
+            assert!(
+                (projected - 1.).abs() < 2e-3,
+                "alpha ({}, {}): {}",
+                alpha_x,
+                alpha_y,
+                projected
+            );
+
+            // The polar angles sample_wh returns follow pdf.
+            let n_bins = 8;
+            let mut histogram = vec![0; n_bins];
+            let mut rng = Rng::new(0);
+            let n_samples = 100_000;
+            for _ in 0..n_samples {
+                let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+                let wh = d.sample_wh(&wo, u);
+                let theta = wh.z.min(1.).acos();
+                histogram[((theta / FRAC_PI_2 * n_bins as Float) as usize).min(n_bins - 1)] += 1;
+            }
+            for (i, &count) in histogram.iter().enumerate() {
+                let bin = FRAC_PI_2 / n_bins as Float;
+                let expected =
+                    integrate_hemisphere(i as Float * bin, (i + 1) as Float * bin, |wh| {
+                        d.pdf(&wo, wh)
+                    });
+                let observed = count as Float / n_samples as Float;
+                assert!(
+                    (observed - expected).abs() < 0.01,
+                    "alpha ({}, {}) bin {}: observed {} expected {}",
+                    alpha_x,
+                    alpha_y,
+                    i,
+                    observed,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn beckmann_sample_wh() {
+        let d = BeckmannDistribution::new(0.2, 0.4);
+        let wo = Vector3f::from([0., 0., 1.]);
+        for u in &[[0.1, 0.2], [0.5, 0.5], [0.7, 0.9], [0.99, 0.01]] {
+            let wh = d.sample_wh(&wo, Point2f::from(*u));
+            assert!(wh.z > 0.);
+            assert!((wh.length() - 1.).abs() < 1e-5);
+        }
+        // Rougher surfaces have more shadowing at grazing angles.
+        let grazing = Vector3f::from([1., 0., 0.1]).normalize();
+        assert!(d.g1(&grazing) < 1.);
+        assert!(BeckmannDistribution::new(0.8, 0.8).g1(&grazing) < d.g1(&grazing));
+    }
+
+    #[test]
+    fn fresnel_normal_incidence() {
+        for &n in &[1.33, 1.5, 2.42] {
+            let want = ((n - 1.) / (n + 1.)) * ((n - 1.) / (n + 1.));
+            let fr = FresnelDielectric::new(1., n);
+            // Light arriving from either side of the boundary is reflected equally.
+            for &cos_i in &[1., -1.] {
+                let got = fr.evaluate(cos_i).channels()[0];
+                assert!(
+                    (got - want).abs() < 1e-6,
+                    "n {} cos_i {}: {}",
+                    n,
+                    cos_i,
+                    got
+                );
+            }
+
+            // A conductor that doesn't absorb reflects like a dielectric at all angles.
+            let fc = FresnelConductor::new(Spectrum::new(1.), Spectrum::new(n), Spectrum::new(0.));
+            for &cos_i in &[1., 0.8, 0.5, 0.1] {
+                let got = fc.evaluate(cos_i).channels()[0];
+                let want = fr.evaluate(cos_i).channels()[0];
+                assert!(
+                    (got - want).abs() < 1e-5,
+                    "n {} cos_i {}: {}",
+                    n,
+                    cos_i,
+                    got
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fresnel_conductor() {
+        // Approximate RGB optical constants of gold.
+        let eta = Spectrum::from_rgb([0.143, 0.374, 1.442]);
+        let k = Spectrum::from_rgb([3.983, 2.385, 1.603]);
+        let fc = FresnelConductor::new(Spectrum::new(1.), eta.clone(), k.clone());
+        let r = fc.evaluate(1.);
+        let channels = eta.channels().iter().zip(k.channels());
+        for (got, (n, k)) in r.channels().iter().zip(channels) {
+            let want: Float = ((n - 1.) * (n - 1.) + k * k) / ((n + 1.) * (n + 1.) + k * k);
+            assert!((got - want).abs() < 1e-5, "got {} want {}", got, want);
+        }
+        // Gold reflects red more than blue.
+        assert!(r.channels()[0] > r.channels()[2]);
+        // Everything is reflected at grazing angles.
+        let grazing = fc.evaluate(0.);
+        assert!(
+            grazing.channels().iter().all(|c| (c - 1.).abs() < 1e-5),
+            "{:?}",
+            grazing
+        );
+        assert_eq!(FresnelNoOp.evaluate(0.3), Spectrum::new(1.));
+    }
+
+    #[test]
+    fn specular_reflection() {
+        let s = SpecularReflection::new(Spectrum::new(0.5), Box::new(FresnelNoOp));
+        let wo = Vector3f::from([0.6, 0., 0.8]);
+        let sample = s.sample_f(&wo, Point2f::from([0.3, 0.7]));
+        assert_eq!(sample.wi, Vector3f::from([-0.6, 0., 0.8]));
+        assert_eq!(sample.pdf, 1.);
+        assert_eq!(sample.f, Spectrum::new(0.5 / 0.8));
+        assert!(s.f(&wo, &sample.wi).is_black());
+        assert_eq!(s.pdf(&wo, &sample.wi), 0.);
+    }
+
+    #[test]
+    fn specular_transmission() {
+        let st = SpecularTransmission::new(Spectrum::new(1.), 1., 1.5, TransportMode::Radiance);
+        let wo = Vector3f::from([0., 0., 1.]);
+        let s = st.sample_f(&wo, Point2f::from([0.5, 0.5]));
+        assert_eq!(s.wi, Vector3f::from([0., 0., -1.]));
+        assert_eq!(s.pdf, 1.);
+        assert_eq!(s.sampled_type, BxDFType::TRANSMISSION | BxDFType::SPECULAR);
+        // 4% is reflected at normal incidence, the rest is compressed into the denser medium.
+        let want = 0.96 / (1.5 * 1.5);
+        assert!((s.f.max_component_value() - want).abs() < 1e-5);
+
+        // Importance isn't scaled by the change in solid angle.
+        let st = SpecularTransmission::new(Spectrum::new(1.), 1., 1.5, TransportMode::Importance);
+        let s = st.sample_f(&wo, Point2f::from([0.5, 0.5]));
+        assert!((s.f.max_component_value() - 0.96).abs() < 1e-5);
+    }
+
+    #[test]
+    fn total_internal_reflection() {
+        // Leaving glass at a grazing angle, past the critical angle of ~41.8 degrees.
+        let wo = Vector3f::from([0.9, 0., -0.1]).normalize();
+        let st = SpecularTransmission::new(Spectrum::new(1.), 1., 1.5, TransportMode::Radiance);
+        let s = st.sample_f(&wo, Point2f::from([0.5, 0.5]));
+        assert_eq!(s.pdf, 0.);
+        assert!(s.f.is_black());
+
+        let fs = FresnelSpecular::new(
+            Spectrum::new(1.),
+            Spectrum::new(1.),
+            1.,
+            1.5,
+            TransportMode::Radiance,
+        );
+        for u in &[0., 0.5, 0.999] {
+            let s = fs.sample_f(&wo, Point2f::from([*u, 0.5]));
+            assert_eq!(s.sampled_type, BxDFType::REFLECTION | BxDFType::SPECULAR);
+            assert_eq!(s.wi, Vector3f::from([-wo.x, -wo.y, wo.z]));
+            assert_eq!(s.pdf, 1.);
+        }
+
+        // Below the critical angle light escapes.
+        let wo = Vector3f::from([0.3, 0., -1.]).normalize();
+        let s = st.sample_f(&wo, Point2f::from([0.5, 0.5]));
+        assert!(s.wi.z > 0.);
+        assert!(!s.f.is_black());
+    }
+
+    #[test]
+    fn fresnel_specular_chooses_by_reflectance() {
+        let fs = FresnelSpecular::new(
+            Spectrum::new(1.),
+            Spectrum::new(1.),
+            1.,
+            1.5,
+            TransportMode::Radiance,
+        );
+        let wo = Vector3f::from([0., 0., 1.]);
+        // At normal incidence 4% of samples reflect.
+        let r = fs.sample_f(&wo, Point2f::from([0.01, 0.5]));
+        assert_eq!(r.sampled_type, BxDFType::REFLECTION | BxDFType::SPECULAR);
+        assert!((r.pdf - 0.04).abs() < 1e-6);
+        let t = fs.sample_f(&wo, Point2f::from([0.5, 0.5]));
+        assert_eq!(t.sampled_type, BxDFType::TRANSMISSION | BxDFType::SPECULAR);
+        assert!((t.pdf - 0.96).abs() < 1e-6);
+        assert!(t.wi.z < 0.);
+    }
+
+    /// Returns a uniformly distributed direction on the side of the surface given by the sign of
+    /// `side`.
+    fn uniform_direction(rng: &mut Rng, side: Float) -> Vector3f {
+        let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+        let mut w = uniform_sample_sphere(u);
+        if w.z * side < 0. {
+            w.z = -w.z;
+        }
+        w
+    }
+
+    #[test]
+    fn microfacet_reflection_reciprocity() {
+        let m = MicrofacetReflection::new(
+            Spectrum::new(1.),
+            Box::new(BeckmannDistribution::new(0.2, 0.4)),
+            Box::new(FresnelDielectric::new(1., 1.5)),
+        );
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let wo = uniform_direction(&mut rng, 1.);
+            let wi = uniform_direction(&mut rng, 1.);
+            let (a, b) = (m.f(&wo, &wi), m.f(&wi, &wo));
+            assert!(
+                (a.y() - b.y()).abs() <= 1e-4 * a.y() + 1e-6,
+                "{:?} != {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn microfacet_transmission() {
+        let eta = 1.5;
+        let new = |mode| {
+            MicrofacetTransmission::new(
+                Spectrum::new(1.),
+                Box::new(BeckmannDistribution::new(0.3, 0.3)),
+                1.,
+                eta,
+                mode,
+            )
+        };
+        let radiance = new(TransportMode::Radiance);
+        let importance = new(TransportMode::Importance);
+        assert_eq!(
+            radiance.bxdf_type(),
+            BxDFType::TRANSMISSION | BxDFType::GLOSSY
+        );
+
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let wo = uniform_direction(&mut rng, 1.);
+            let wi = uniform_direction(&mut rng, -1.);
+            // Light going from the outside in and vice versa.
+            let (f_in, f_out) = (radiance.f(&wo, &wi), radiance.f(&wi, &wo));
+            // Radiance is compressed into the smaller solid angle inside the denser medium, so
+            // the BTDF obeys the generalized reciprocity f(wo, wi) / eta_o^2 = f(wi, wo) / eta_i^2.
+            assert!(
+                (f_in.y() * eta * eta - f_out.y()).abs() <= 1e-3 * f_out.y() + 1e-6,
+                "{:?} {:?}",
+                f_in,
+                f_out
+            );
+            // Importance isn't scaled, so it's the adjoint of radiance.
+            let adjoint = importance.f(&wi, &wo);
+            assert!(
+                (f_in.y() - adjoint.y()).abs() <= 1e-3 * f_in.y() + 1e-6,
+                "{:?} {:?}",
+                f_in,
+                adjoint
+            );
+            assert_eq!(radiance.f(&wo, &wo).y(), 0.);
+        }
+
+        for wo in &[
+            Vector3f::from([0.3, 0., 1.]).normalize(),
+            Vector3f::from([0.3, 0.2, -1.]).normalize(),
+        ] {
+            for u in &[[0.1, 0.2], [0.5, 0.5], [0.7, 0.9]] {
+                let s = radiance.sample_f(wo, Point2f::from(*u));
+                if s.pdf == 0. {
+                    continue;
+                }
+                // Sampled directions refract through the surface.
+                assert!(s.wi.z * wo.z < 0.);
+                assert!((s.pdf - radiance.pdf(wo, &s.wi)).abs() < 1e-3 * s.pdf);
+                assert_eq!(s.f, radiance.f(wo, &s.wi));
+            }
+        }
+    }
+}
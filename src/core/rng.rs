@@ -16,7 +16,8 @@ use std::ops::Sub;
 
 use crate::Float;
 
-const ONE_MINUS_EPSILON: Float = 1. - Float::EPSILON;
+/// Largest representable `Float` less than one.
+pub const ONE_MINUS_EPSILON: Float = 1. - Float::EPSILON;
 
 const PCG32_DEFAULT_STATE: u64 = 0x853c49e6748fea9b;
 const PCG32_DEFAULT_STREAM: u64 = 0xda3e39cb94b95bdb;
@@ -27,7 +28,8 @@ const PCG32_MULT: u64 = 0x5851f42d4c957f2d;
 /// called anywhere in the C++ source tree:
 /// * Shuffle
 /// * Advance
-struct Rng {
+#[derive(Clone, Debug)]
+pub struct Rng {
     state: u64,
     inc: u64,
 }
@@ -54,7 +56,7 @@ impl Rng {
         self.state = 0;
         self.inc = (sequence_index << 1) | 1;
         self.uniform_u32();
-        self.state += PCG32_DEFAULT_STATE;
+        self.state = self.state.wrapping_add(PCG32_DEFAULT_STATE);
         self.uniform_u32();
     }
 
@@ -0,0 +1,303 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Common interface for the algorithms that generate sample points for the integrators.
+
+use std::fmt::Debug;
+
+use crate::{
+    core::geometry::{Point2f, Point2i},
+    Float,
+};
+
+/// Re-exported so samplers and integrators can name the values [Sampler::get_camera_sample]
+/// returns without depending on the camera module.
+pub use crate::core::camera::CameraSample;
+
+/// SampleArrays holds the arrays of sample values integrators request before rendering starts,
+/// e.g. to take several samples of each light at every intersection.  Samplers that support
+/// arrays own one, fill it in when they start a pixel, and return it from
+/// [Sampler::sample_arrays].
+#[derive(Clone, Debug, Default)]
+pub struct SampleArrays {
+    // Each array stores `count * samples_per_pixel` values, `count` for each pixel sample.
+    arrays_1d: Vec<(usize, Vec<Float>)>,
+    arrays_2d: Vec<(usize, Vec<Point2f>)>,
+    array_1d_offset: usize,
+    array_2d_offset: usize,
+    current_pixel_sample_index: usize,
+}
+
+impl SampleArrays {
+    /// Adds an array of `n` 1D values to each sample vector of a sampler taking
+    /// `samples_per_pixel` samples per pixel.
+    pub fn request_1d(&mut self, n: usize, samples_per_pixel: usize) {
+        self.arrays_1d.push((n, vec![0.; n * samples_per_pixel]));
+    }
+
+    /// Adds an array of `n` 2D values to each sample vector of a sampler taking
+    /// `samples_per_pixel` samples per pixel.
+    pub fn request_2d(&mut self, n: usize, samples_per_pixel: usize) {
+        self.arrays_2d
+            .push((n, vec![Point2f::default(); n * samples_per_pixel]));
+    }
+
+    /// Returns the size and storage, for all of a pixel's samples, of each requested 1D array.
+    /// Samplers fill these in when they start a pixel.
+    pub fn arrays_1d_mut(&mut self) -> impl Iterator<Item = (usize, &mut [Float])> {
+        self.arrays_1d
+            .iter_mut()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, a)| (*n, a.as_mut_slice()))
+    }
+
+    /// Returns the size and storage, for all of a pixel's samples, of each requested 2D array.
+    /// Samplers fill these in when they start a pixel.
+    pub fn arrays_2d_mut(&mut self) -> impl Iterator<Item = (usize, &mut [Point2f])> {
+        self.arrays_2d
+            .iter_mut()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, a)| (*n, a.as_mut_slice()))
+    }
+
+    /// Rewinds to the first array of the first sample in a pixel.
+    pub fn start_pixel(&mut self) {
+        self.array_1d_offset = 0;
+        self.array_2d_offset = 0;
+        self.current_pixel_sample_index = 0;
+    }
+
+    /// Rewinds to the first array of the next sample in the pixel.
+    pub fn start_next_sample(&mut self) {
+        self.array_1d_offset = 0;
+        self.array_2d_offset = 0;
+        self.current_pixel_sample_index += 1;
+    }
+
+    /// Returns the next 1D array of the current sample, or `None` if all requested arrays have
+    /// been consumed or the next one doesn't hold `n` values.
+    pub fn get_1d(&mut self, n: usize) -> Option<&[Float]> {
+        let (count, array) = self.arrays_1d.get(self.array_1d_offset)?;
+        if *count != n {
+            return None;
+        }
+        self.array_1d_offset += 1;
+        let start = self.current_pixel_sample_index * n;
+        array.get(start..start + n)
+    }
+
+    /// Returns the next 2D array of the current sample, or `None` if all requested arrays have
+    /// been consumed or the next one doesn't hold `n` values.
+    pub fn get_2d(&mut self, n: usize) -> Option<&[Point2f]> {
+        let (count, array) = self.arrays_2d.get(self.array_2d_offset)?;
+        if *count != n {
+            return None;
+        }
+        self.array_2d_offset += 1;
+        let start = self.current_pixel_sample_index * n;
+        array.get(start..start + n)
+    }
+}
+
+/// Sampler generates the sequence of n-dimensional sample vectors used to render each pixel.
+/// The values of each sample vector are consumed one or two dimensions at a time by calls to
+/// [get_1d] and [get_2d], or as arrays requested before rendering with [request_1d_array] and
+/// [request_2d_array].
+///
+/// [get_1d]: Sampler::get_1d
+/// [get_2d]: Sampler::get_2d
+/// [request_1d_array]: Sampler::request_1d_array
+/// [request_2d_array]: Sampler::request_2d_array
+pub trait Sampler: Debug + Send + Sync {
+    /// Prepares the sampler to generate samples for the pixel `p`.
+    fn start_pixel(&mut self, p: Point2i);
+
+    /// Returns the next dimension of the current sample vector.
+    fn get_1d(&mut self) -> Float;
+
+    /// Returns the next two dimensions of the current sample vector.
+    fn get_2d(&mut self) -> Point2f;
+
+    /// Returns the sample values needed to generate a camera ray for the pixel at `p_raster`.
+    /// The film offset within the pixel, the lens position and the time are always taken from
+    /// the first 2D, second 2D and first 1D dimensions respectively, so every sampler lays out
+    /// its sample vectors the same way.  `time` is left in [0, 1), the camera maps it to its
+    /// shutter interval.
+    fn get_camera_sample(&mut self, p_raster: Point2i) -> CameraSample {
+        let p_film = Point2f::from(p_raster) + self.get_2d();
+        let p_lens = self.get_2d();
+        let time = self.get_1d();
+        CameraSample {
+            p_film,
+            p_lens,
+            time,
+        }
+    }
+
+    /// Moves on to the next sample in the current pixel.  Returns false once all of the pixel's
+    /// samples have been taken.
+    fn start_next_sample(&mut self) -> bool;
+
+    /// Returns the number of samples taken for each pixel.
+    fn samples_per_pixel(&self) -> usize;
+
+    /// Returns a new `Sampler` with the same configuration as `self`, but whose random state is
+    /// seeded from `seed`.  Used to give each thread its own sampler.
+    fn clone_sampler(&self, seed: usize) -> Box<dyn Sampler>;
+
+    /// Returns the sample arrays requested so far, or `None` if the sampler doesn't support
+    /// arrays.  Requests are then ignored and integrators fall back to [get_1d] and [get_2d].
+    ///
+    /// [get_1d]: Sampler::get_1d
+    /// [get_2d]: Sampler::get_2d
+    fn sample_arrays(&mut self) -> Option<&mut SampleArrays> {
+        None
+    }
+
+    /// Returns the array size closest to `n` the sampler can generate well distributed arrays
+    /// of.  Integrators should round the sizes they request with this.
+    fn round_count(&self, n: usize) -> usize {
+        n
+    }
+
+    /// Requests an array of `n` 1D values in every sample vector.  Must be called before
+    /// rendering starts.
+    fn request_1d_array(&mut self, n: usize) {
+        debug_assert_eq!(self.round_count(n), n);
+        let spp = self.samples_per_pixel();
+        if let Some(arrays) = self.sample_arrays() {
+            arrays.request_1d(n, spp);
+        }
+    }
+
+    /// Requests an array of `n` 2D values in every sample vector.  Must be called before
+    /// rendering starts.
+    fn request_2d_array(&mut self, n: usize) {
+        debug_assert_eq!(self.round_count(n), n);
+        let spp = self.samples_per_pixel();
+        if let Some(arrays) = self.sample_arrays() {
+            arrays.request_2d(n, spp);
+        }
+    }
+
+    /// Returns the next requested 1D array of the current sample vector, in the order they were
+    /// requested.  Returns `None` once the requested arrays are used up, or if `n` doesn't match
+    /// the size requested.
+    fn get_1d_array(&mut self, n: usize) -> Option<&[Float]> {
+        self.sample_arrays()?.get_1d(n)
+    }
+
+    /// Returns the next requested 2D array of the current sample vector, in the order they were
+    /// requested.  Returns `None` once the requested arrays are used up, or if `n` doesn't match
+    /// the size requested.
+    fn get_2d_array(&mut self, n: usize) -> Option<&[Point2f]> {
+        self.sample_arrays()?.get_2d(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sampler;
+    use crate::{
+        core::geometry::{Point2f, Point2i},
+        samplers::stratified::StratifiedSampler,
+    };
+
+    #[test]
+    fn camera_sample_dimensions() {
+        let sampler = StratifiedSampler::new(2, 2, true, 2);
+        let mut s1 = sampler.clone_sampler(7);
+        let mut s2 = sampler.clone_sampler(7);
+        let p = Point2i::from([3, 5]);
+        s1.start_pixel(p);
+        s2.start_pixel(p);
+        loop {
+            let cs = s1.get_camera_sample(p);
+            assert_eq!(cs.p_film, Point2f::from(p) + s2.get_2d());
+            assert_eq!(cs.p_lens, s2.get_2d());
+            assert_eq!(cs.time, s2.get_1d());
+            // Both samplers continue from the same dimension.
+            assert_eq!(s1.get_2d(), s2.get_2d());
+            s2.start_next_sample();
+            if !s1.start_next_sample() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn boxed_sampler_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + ?Sized>(_: &T) {}
+        let sampler: Box<dyn Sampler> = StratifiedSampler::new(1, 1, false, 1).clone_sampler(0);
+        assert_send_sync(&*sampler);
+        assert_eq!(sampler.samples_per_pixel(), 1);
+    }
+
+    #[test]
+    fn camera_sample_in_pixel() {
+        let mut sampler = StratifiedSampler::new(4, 4, true, 4);
+        for p in [[0, 0], [10, 3], [639, 479]] {
+            let p = Point2i::from(p);
+            sampler.start_pixel(p);
+            loop {
+                let cs = sampler.get_camera_sample(p);
+                let (x, y) = (p.x as crate::Float, p.y as crate::Float);
+                assert!(cs.p_film.x >= x && cs.p_film.x < x + 1., "{:?}", cs);
+                assert!(cs.p_film.y >= y && cs.p_film.y < y + 1., "{:?}", cs);
+                assert!(cs.time >= 0. && cs.time < 1.);
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sample_arrays() {
+        let prototype = {
+            let mut sampler = StratifiedSampler::new(2, 2, true, 1);
+            sampler.request_1d_array(3);
+            sampler.request_2d_array(5);
+            sampler.request_2d_array(2);
+            sampler
+        };
+        // Requests carry over to cloned samplers.
+        let mut sampler = prototype.clone_sampler(1);
+        sampler.start_pixel(Point2i::from([0, 0]));
+        let mut n_samples = 0;
+        let mut first = Vec::new();
+        loop {
+            let a = sampler.get_1d_array(3).expect("1d array").to_vec();
+            assert_eq!(a.len(), 3);
+            first.push(a[0]);
+            assert_eq!(sampler.get_2d_array(5).map(<[_]>::len), Some(5));
+            // Asking for the wrong size doesn't consume the array.
+            assert!(sampler.get_2d_array(3).is_none());
+            assert_eq!(sampler.get_2d_array(2).map(<[_]>::len), Some(2));
+            // Everything requested has been used up.
+            assert!(sampler.get_1d_array(3).is_none());
+            assert!(sampler.get_2d_array(2).is_none());
+            // Arrays don't take dimensions from the regular sample vector.
+            let _ = sampler.get_2d();
+            n_samples += 1;
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(n_samples, 4);
+        // Each pixel sample has its own values.
+        first.dedup();
+        assert_eq!(first.len(), 4);
+    }
+}
@@ -14,6 +14,407 @@
 
 //! Module sampling holds a variety of implementations for 1D and 2D sampling algorithms.
 
-/// 2D sampler.
-#[derive(Debug)]
-pub struct Distribution2D {}
+use crate::{
+    core::{
+        geometry::{Point2f, Vector3f},
+        rng::{Rng, ONE_MINUS_EPSILON},
+    },
+    float::consts::{FRAC_1_PI, FRAC_PI_2, FRAC_PI_4, PI},
+    Float,
+};
+
+/// Distribution1D draws samples proportional to a piecewise-constant function over [0, 1), given
+/// as `n` equal width steps.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<Float>,
+    cdf: Vec<Float>,
+    func_int: Float,
+}
+
+impl Distribution1D {
+    /// Creates a `Distribution1D` for the step function with values `f`.  If `f` integrates to
+    /// zero, samples are distributed uniformly.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::sampling::Distribution1D;
+    ///
+    /// let d = Distribution1D::new(&[1., 3.]);
+    /// assert_eq!(d.count(), 2);
+    /// assert_eq!(d.func_int(), 2.);
+    /// assert_eq!(d.sample_discrete(0.2), (0, 0.25));
+    /// assert_eq!(d.sample_discrete(0.3), (1, 0.75));
+    /// assert_eq!(d.discrete_pdf(1), 0.75);
+    /// let (x, pdf, offset) = d.sample_continuous(0.625);
+    /// assert_eq!((x, pdf, offset), (0.75, 1.5, 1));
+    ///
+    /// let zero = Distribution1D::new(&[0., 0., 0., 0.]);
+    /// assert_eq!(zero.sample_discrete(0.6), (2, 0.25));
+    /// ```
+    pub fn new(f: &[Float]) -> Distribution1D {
+        let n = f.len();
+        let func = f.to_vec();
+        // Compute integral of step function at $x_i$
+        let mut cdf = vec![0.; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1] / n as Float;
+        }
+        // Transform step function integral into CDF
+        let func_int = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            if func_int == 0. {
+                *c = i as Float / n as Float;
+            } else {
+                *c /= func_int;
+            }
+        }
+        Distribution1D {
+            func,
+            cdf,
+            func_int,
+        }
+    }
+
+    /// Returns the number of steps in the function.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Returns the integral of the function over [0, 1).
+    pub fn func_int(&self) -> Float {
+        self.func_int
+    }
+
+    /// Returns the index of the step `u` falls in the CDF of.
+    fn find_interval(&self, u: Float) -> usize {
+        let first_above = self.cdf.partition_point(|&c| c <= u);
+        first_above.saturating_sub(1).min(self.count() - 1)
+    }
+
+    /// Maps `u` in [0, 1) to a sample in [0, 1) from the continuous distribution.  Returns the
+    /// sample, its probability density and the index of the step it falls in.
+    pub fn sample_continuous(&self, u: Float) -> (Float, Float, usize) {
+        let offset = self.find_interval(u);
+        // Compute offset along CDF segment
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0. {
+            du /= width;
+        }
+        let pdf = if self.func_int > 0. {
+            self.func[offset] / self.func_int
+        } else {
+            1.
+        };
+        ((offset as Float + du) / self.count() as Float, pdf, offset)
+    }
+
+    /// Maps `u` in [0, 1) to one of the steps of the function, with probability proportional to
+    /// its value.  Returns the index of the step and the probability of choosing it.
+    pub fn sample_discrete(&self, u: Float) -> (usize, Float) {
+        let offset = self.find_interval(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    /// Returns the probability of [sample_discrete] choosing step `index`.
+    ///
+    /// [sample_discrete]: Distribution1D::sample_discrete
+    pub fn discrete_pdf(&self, index: usize) -> Float {
+        if self.func_int > 0. {
+            self.func[index] / (self.func_int * self.count() as Float)
+        } else {
+            1. / self.count() as Float
+        }
+    }
+}
+
+/// Distribution2D draws samples proportional to a piecewise-constant function over [0, 1)^2,
+/// given as `nu` by `nv` equal sized cells stored a row at a time.  A row is picked from the
+/// marginal distribution of the rows' integrals, then a column from that row's conditional
+/// distribution.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    conditional_v: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Creates a `Distribution2D` for the `nu` by `nv` step function with values `f`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point2f, sampling::Distribution2D};
+    ///
+    /// // Only the top right cell has any weight.
+    /// let d = Distribution2D::new(&[0., 0., 0., 2.], 2, 2);
+    /// let (p, pdf) = d.sample_continuous(Point2f::from([0.5, 0.5]));
+    /// assert_eq!(p, Point2f::from([0.75, 0.75]));
+    /// assert_eq!(pdf, 4.);
+    /// assert_eq!(d.pdf(p), 4.);
+    /// assert_eq!(d.pdf(Point2f::from([0.25, 0.75])), 0.);
+    /// ```
+    pub fn new(f: &[Float], nu: usize, nv: usize) -> Distribution2D {
+        debug_assert_eq!(f.len(), nu * nv);
+        let conditional_v: Vec<_> = f.chunks(nu).map(Distribution1D::new).collect();
+        // Compute marginal sampling distribution $p[\tilde{v}]$
+        let marginal_func: Vec<_> = conditional_v.iter().map(|d| d.func_int()).collect();
+        let marginal = Distribution1D::new(&marginal_func);
+        Distribution2D {
+            conditional_v,
+            marginal,
+        }
+    }
+
+    /// Maps `u` in [0, 1)^2 to a sample in [0, 1)^2.  Returns the sample and its probability
+    /// density.
+    pub fn sample_continuous(&self, u: Point2f) -> (Point2f, Float) {
+        let (d1, pdf1, v) = self.marginal.sample_continuous(u[1]);
+        let (d0, pdf0, _) = self.conditional_v[v].sample_continuous(u[0]);
+        (Point2f::from([d0, d1]), pdf0 * pdf1)
+    }
+
+    /// Returns the probability density of [sample_continuous] returning `p`.
+    ///
+    /// [sample_continuous]: Distribution2D::sample_continuous
+    pub fn pdf(&self, p: Point2f) -> Float {
+        let nu = self.conditional_v[0].count();
+        let nv = self.marginal.count();
+        let iu = ((p[0] * nu as Float) as usize).min(nu - 1);
+        let iv = ((p[1] * nv as Float) as usize).min(nv - 1);
+        if self.marginal.func_int() == 0. {
+            // All zero functions are sampled uniformly.
+            return 1.;
+        }
+        self.conditional_v[iv].func[iu] / self.marginal.func_int()
+    }
+}
+
+/// Fills `samples` with one uniformly distributed value in each of `samples.len()` equal width
+/// strata of [0, 1).  If `jitter` is false, each value is placed at the center of its stratum.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{rng::Rng, sampling::stratified_sample_1d};
+///
+/// let mut rng = Rng::new(0);
+/// let mut samples = [0.; 4];
+/// stratified_sample_1d(&mut samples, &mut rng, false);
+/// assert_eq!(samples, [0.125, 0.375, 0.625, 0.875]);
+/// ```
+pub fn stratified_sample_1d(samples: &mut [Float], rng: &mut Rng, jitter: bool) {
+    let inv_n_samples = 1. / samples.len() as Float;
+    for (i, s) in samples.iter_mut().enumerate() {
+        let delta = if jitter { rng.uniform_float() } else { 0.5 };
+        *s = ((i as Float + delta) * inv_n_samples).min(ONE_MINUS_EPSILON);
+    }
+}
+
+/// Fills `samples` with one uniformly distributed point in each cell of an `nx` by `ny` grid
+/// over [0, 1)^2.  If `jitter` is false, each point is placed at the center of its cell.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, rng::Rng, sampling::stratified_sample_2d};
+///
+/// let mut rng = Rng::new(0);
+/// let mut samples = [Point2f::default(); 4];
+/// stratified_sample_2d(&mut samples, 2, 2, &mut rng, true);
+/// for (i, p) in samples.iter().enumerate() {
+///     let (x, y) = ((i % 2) as pbrt::Float, (i / 2) as pbrt::Float);
+///     assert!(p.x >= x / 2. && p.x < (x + 1.) / 2.);
+///     assert!(p.y >= y / 2. && p.y < (y + 1.) / 2.);
+/// }
+/// ```
+pub fn stratified_sample_2d(
+    samples: &mut [Point2f],
+    nx: usize,
+    ny: usize,
+    rng: &mut Rng,
+    jitter: bool,
+) {
+    debug_assert_eq!(samples.len(), nx * ny);
+    let dx = 1. / nx as Float;
+    let dy = 1. / ny as Float;
+    for (i, s) in samples.iter_mut().enumerate() {
+        let (x, y) = ((i % nx) as Float, (i / nx) as Float);
+        let jx = if jitter { rng.uniform_float() } else { 0.5 };
+        let jy = if jitter { rng.uniform_float() } else { 0.5 };
+        s.x = ((x + jx) * dx).min(ONE_MINUS_EPSILON);
+        s.y = ((y + jy) * dy).min(ONE_MINUS_EPSILON);
+    }
+}
+
+/// Fills `samples` with a 2D Latin hypercube pattern over [0, 1)^2: each of `samples.len()` equal
+/// width strata of either dimension contains exactly one point.  Unlike [stratified_sample_2d]
+/// it works for any number of samples.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, rng::Rng, sampling::latin_hypercube};
+///
+/// let mut rng = Rng::new(0);
+/// let mut samples = [Point2f::default(); 5];
+/// latin_hypercube(&mut samples, &mut rng);
+/// let mut xs: Vec<_> = samples.iter().map(|p| (p.x * 5.) as usize).collect();
+/// let mut ys: Vec<_> = samples.iter().map(|p| (p.y * 5.) as usize).collect();
+/// xs.sort();
+/// ys.sort();
+/// assert_eq!(xs, [0, 1, 2, 3, 4]);
+/// assert_eq!(ys, [0, 1, 2, 3, 4]);
+/// ```
+pub fn latin_hypercube(samples: &mut [Point2f], rng: &mut Rng) {
+    // Generate LHS samples along diagonal
+    let n = samples.len();
+    let inv_n = 1. / n as Float;
+    for (i, s) in samples.iter_mut().enumerate() {
+        s.x = ((i as Float + rng.uniform_float()) * inv_n).min(ONE_MINUS_EPSILON);
+        s.y = ((i as Float + rng.uniform_float()) * inv_n).min(ONE_MINUS_EPSILON);
+    }
+    // Permute LHS samples in each dimension
+    for i in 0..n {
+        let other = i + rng.uniform_u32_threshold((n - i) as u32) as usize;
+        let x = samples[i].x;
+        samples[i].x = samples[other].x;
+        samples[other].x = x;
+    }
+    for i in 0..n {
+        let other = i + rng.uniform_u32_threshold((n - i) as u32) as usize;
+        let y = samples[i].y;
+        samples[i].y = samples[other].y;
+        samples[other].y = y;
+    }
+}
+
+/// Randomly permutes `samples` using `rng`.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{rng::Rng, sampling::shuffle};
+///
+/// let mut rng = Rng::new(0);
+/// let mut samples = [1, 2, 3, 4, 5];
+/// shuffle(&mut samples, &mut rng);
+/// samples.sort();
+/// assert_eq!(samples, [1, 2, 3, 4, 5]);
+/// ```
+pub fn shuffle<T>(samples: &mut [T], rng: &mut Rng) {
+    let count = samples.len();
+    for i in 0..count {
+        let other = i + rng.uniform_u32_threshold((count - i) as u32) as usize;
+        samples.swap(i, other);
+    }
+}
+
+/// Maps a uniform sample `u` in [0, 1)^2 to a point on the unit disk, using Shirley's concentric
+/// mapping which preserves the relative area of regions of the square.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, sampling::concentric_sample_disk};
+///
+/// assert_eq!(
+///     concentric_sample_disk(Point2f::from([0.5, 0.5])),
+///     Point2f::from([0., 0.])
+/// );
+/// let p = concentric_sample_disk(Point2f::from([1., 0.5]));
+/// assert!((p.x - 1.).abs() < 1e-6 && p.y.abs() < 1e-6);
+/// ```
+pub fn concentric_sample_disk(u: Point2f) -> Point2f {
+    // Map uniform random numbers to [-1, 1]^2
+    let u_offset = u * 2. - Point2f::from([1., 1.]);
+
+    // Handle degeneracy at the origin
+    if u_offset.x == 0. && u_offset.y == 0. {
+        return Point2f::from([0., 0.]);
+    }
+
+    // Apply concentric mapping to point
+    let (r, theta) = if u_offset.x.abs() > u_offset.y.abs() {
+        (u_offset.x, FRAC_PI_4 * (u_offset.y / u_offset.x))
+    } else {
+        (
+            u_offset.y,
+            FRAC_PI_2 - FRAC_PI_4 * (u_offset.x / u_offset.y),
+        )
+    };
+    Point2f::from([theta.cos(), theta.sin()]) * r
+}
+
+/// Maps a uniform sample `u` in [0, 1)^2 to a direction in the hemisphere around +z, distributed
+/// proportionally to the cosine of the angle with +z.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, sampling::cosine_sample_hemisphere};
+///
+/// let w = cosine_sample_hemisphere(Point2f::from([0.3, 0.8]));
+/// assert!(w.z >= 0.);
+/// assert!((w.length() - 1.).abs() < 1e-6);
+/// ```
+pub fn cosine_sample_hemisphere(u: Point2f) -> Vector3f {
+    let d = concentric_sample_disk(u);
+    let z = (1. - d.x * d.x - d.y * d.y).max(0.).sqrt();
+    Vector3f::from([d.x, d.y, z])
+}
+
+/// Returns the PDF of a direction sampled by [cosine_sample_hemisphere], given the cosine of its
+/// angle with +z.
+pub fn cosine_hemisphere_pdf(cos_theta: Float) -> Float {
+    cos_theta * FRAC_1_PI
+}
+
+/// Maps a uniform sample `u` in [0, 1)^2 to a direction uniformly distributed over the unit
+/// sphere.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, sampling::uniform_sample_sphere};
+///
+/// let w = uniform_sample_sphere(Point2f::from([0.25, 0.6]));
+/// assert!((w.length() - 1.).abs() < 1e-6);
+/// ```
+pub fn uniform_sample_sphere(u: Point2f) -> Vector3f {
+    let z = 1. - 2. * u.x;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * u.y;
+    Vector3f::from([r * phi.cos(), r * phi.sin(), z])
+}
+
+/// Returns the PDF of a direction sampled by [uniform_sample_sphere].
+pub fn uniform_sphere_pdf() -> Float {
+    FRAC_1_PI / 4.
+}
+
+/// Maps a uniform sample `u` in [0, 1)^2 to barycentric coordinates `(b0, b1)` uniformly
+/// distributed over a triangle, the third coordinate is `1 - b0 - b1`.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{geometry::Point2f, sampling::uniform_sample_triangle};
+///
+/// let b = uniform_sample_triangle(Point2f::from([0.25, 0.6]));
+/// assert!(b.x >= 0. && b.y >= 0. && b.x + b.y <= 1.);
+/// assert_eq!(uniform_sample_triangle(Point2f::from([0., 0.5])), Point2f::from([1., 0.]));
+/// ```
+pub fn uniform_sample_triangle(u: Point2f) -> Point2f {
+    let su0 = u.x.sqrt();
+    Point2f::from([1. - su0, u.y * su0])
+}
+
+/// Weights a sample from one of two sampling strategies for multiple importance sampling.
+/// `nf` samples were taken from the distribution with PDF `f_pdf` and `ng` from the one with PDF
+/// `g_pdf`.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::sampling::power_heuristic;
+///
+/// assert_eq!(power_heuristic(1, 0.5, 1, 0.5), 0.5);
+/// assert_eq!(power_heuristic(1, 1., 1, 0.), 1.);
+/// ```
+pub fn power_heuristic(nf: usize, f_pdf: Float, ng: usize, g_pdf: Float) -> Float {
+    let f = nf as Float * f_pdf;
+    let g = ng as Float * g_pdf;
+    (f * f) / (f * f + g * g)
+}
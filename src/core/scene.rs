@@ -0,0 +1,110 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The scene to be rendered, as handed to an [Integrator].
+//!
+//! [Integrator]: crate::core::integrator::Integrator
+
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{Bounds3f, Ray, Vector3f},
+    interaction::SurfaceInteraction,
+    light::{Light, LightFlags},
+    primitive::Primitive,
+    sampler::Sampler,
+    spectrum::Spectrum,
+};
+
+/// Scene holds all of the geometry, via an aggregate primitive, and lights in the scene.
+#[derive(Debug)]
+pub struct Scene {
+    /// All the light sources in the scene.
+    pub lights: Vec<Arc<dyn Light>>,
+    /// The subset of `lights` that are infinitely far away, e.g. environment maps.  Rays that
+    /// escape the scene receive radiance from these.
+    pub infinite_lights: Vec<Arc<dyn Light>>,
+    aggregate: Arc<dyn Primitive>,
+    world_bound: Bounds3f,
+}
+
+impl Scene {
+    /// Create a new `Scene` with geometry `aggregate` lit by `lights`.  Each light's
+    /// [preprocess] is called once the scene is constructed.
+    ///
+    /// [preprocess]: Light::preprocess
+    pub fn new(aggregate: Arc<dyn Primitive>, lights: Vec<Arc<dyn Light>>) -> Scene {
+        let world_bound = aggregate.world_bound();
+        let infinite_lights = lights
+            .iter()
+            .filter(|l| l.flags().contains(LightFlags::INFINITE))
+            .cloned()
+            .collect();
+        let scene = Scene {
+            lights,
+            infinite_lights,
+            aggregate,
+            world_bound,
+        };
+        for light in &scene.lights {
+            light.preprocess(&scene);
+        }
+        scene
+    }
+
+    /// Returns the bounds of all the geometry in the scene.
+    pub fn world_bound(&self) -> Bounds3f {
+        self.world_bound
+    }
+
+    /// Finds the closest intersection of `ray` with the scene's geometry.  On a hit `ray.t_max`
+    /// is set to the distance to the intersection.
+    pub fn intersect(&self, ray: &Ray) -> Option<SurfaceInteraction> {
+        debug_assert_ne!(ray.d, Vector3f::default());
+        self.aggregate.intersect(ray)
+    }
+
+    /// Like [intersect], but passes through surfaces without a material, which only delimit
+    /// participating media.  Also returns the transmittance of the media along `ray` up to the
+    /// intersection, or up to `ray.t_max` if nothing is hit.
+    ///
+    /// [intersect]: Scene::intersect
+    pub fn intersect_tr(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+    ) -> (Option<SurfaceInteraction>, Spectrum) {
+        let mut tr = Spectrum::new(1.);
+        let mut ray = ray.clone();
+        loop {
+            let isect = self.intersect(&ray);
+            // Accumulate beam transmittance for ray segment
+            if let Some(medium) = &ray.medium {
+                tr *= medium.tr(&ray, sampler);
+            }
+
+            // Initialize next ray segment or terminate transmittance computation
+            match isect {
+                Some(isect) if isect.material.is_none() => ray = isect.spawn_ray(&ray.d),
+                isect => return (isect, tr),
+            }
+        }
+    }
+
+    /// Returns true if `ray` hits any of the scene's geometry before `ray.t_max`.
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        debug_assert_ne!(ray.d, Vector3f::default());
+        self.aggregate.intersect_p(ray)
+    }
+}
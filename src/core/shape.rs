@@ -0,0 +1,166 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Defines the trait all shapes implement.  See [shapes] for the currently implemented shapes.
+//!
+//! [shapes]: crate::shapes
+
+use std::fmt::Debug;
+
+use crate::{
+    core::{
+        geometry::{abs_dot, cross, dot, Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        transform::Transform,
+    },
+    Float,
+};
+
+/// ShapeData holds the state common to all shapes.
+#[derive(Clone, Debug)]
+pub struct ShapeData {
+    /// Places the shape in the world.
+    pub object_to_world: Transform,
+    /// Inverse of `object_to_world`.
+    pub world_to_object: Transform,
+    /// Whether the surface normal should point inward rather than outward.
+    pub reverse_orientation: bool,
+    /// Cached value of `object_to_world.swaps_handedness()`.
+    pub transform_swaps_handedness: bool,
+}
+
+impl ShapeData {
+    /// Create a new `ShapeData` with the given transforms and orientation.
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+    ) -> ShapeData {
+        ShapeData {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+        }
+    }
+}
+
+/// Shape is the interface for the geometry of objects in the scene.
+pub trait Shape: Debug + Send + Sync {
+    /// Returns the state common to all shapes.
+    fn data(&self) -> &ShapeData;
+
+    /// Returns a bounding box, in object space, that encloses the shape.
+    fn object_bound(&self) -> Bounds3f;
+
+    /// Returns a bounding box, in world space, that encloses the shape.
+    fn world_bound(&self) -> Bounds3f {
+        self.data()
+            .object_to_world
+            .transform_bounds(&self.object_bound())
+    }
+
+    /// Finds the closest intersection of `r` with the shape that is before `r.t_max`.  On a hit,
+    /// the parametric distance along `r` and the geometry at the hit point, in world space, are
+    /// returned.
+    fn intersect(&self, r: &Ray, test_alpha_texture: bool) -> Option<(Float, SurfaceInteraction)>;
+
+    /// Returns true if `r` intersects the shape before `r.t_max`.  The default implementation
+    /// calls [intersect].
+    ///
+    /// [intersect]: Shape::intersect
+    fn intersect_p(&self, r: &Ray, test_alpha_texture: bool) -> bool {
+        self.intersect(r, test_alpha_texture).is_some()
+    }
+
+    /// Returns the surface area of the shape, in object space.
+    fn area(&self) -> Float;
+
+    /// Samples a point on the surface of the shape using the uniform sample `u`.  The returned
+    /// `Float` is the probability density of the sample with respect to surface area.
+    fn sample(&self, u: Point2f) -> (Interaction, Float);
+
+    /// Samples a point on the surface of the shape as seen from `ref_`.  The returned `Float` is
+    /// the probability density of the sample with respect to solid angle at `ref_`.  The default
+    /// implementation calls [sample] and converts the area density to solid angle.
+    ///
+    /// [sample]: Shape::sample
+    fn sample_ref(&self, ref_: &Interaction, u: Point2f) -> (Interaction, Float) {
+        let (intr, pdf) = self.sample(u);
+        let wi = intr.p - ref_.p;
+        if wi.length_squared() == 0. {
+            return (intr, 0.);
+        }
+        let wi = wi.normalize();
+        // Convert from area measure, as returned by sample(), to solid angle measure.
+        let pdf =
+            pdf * Point3f::distance_squared(ref_.p, intr.p) / abs_dot(Vector3f::from(intr.n), -wi);
+        if pdf.is_infinite() {
+            return (intr, 0.);
+        }
+        (intr, pdf)
+    }
+
+    /// Returns the probability density, with respect to solid angle, of [sample_ref] sampling the
+    /// direction `wi` from `ref_`.  Zero is returned if a ray leaving `ref_` along `wi` misses
+    /// the shape.
+    ///
+    /// [sample_ref]: Shape::sample_ref
+    fn pdf_ref(&self, ref_: &Interaction, wi: &Vector3f) -> Float {
+        // Intersect sample ray with area light geometry
+        let ray = ref_.spawn_ray(wi);
+        let isect_light = match self.intersect(&ray, false) {
+            Some((_t_hit, isect_light)) => isect_light,
+            None => return 0.,
+        };
+        // Convert light sample weight to solid angle measure
+        let pdf = Point3f::distance_squared(ref_.p, isect_light.p)
+            / (abs_dot(Vector3f::from(isect_light.n), -*wi) * self.area());
+        if pdf.is_infinite() {
+            0.
+        } else {
+            pdf
+        }
+    }
+}
+
+/// Computes the partial derivatives of the surface normal, `dndu` and `dndv`, from the first
+/// and second partial derivatives of the surface position using the Weingarten equations.
+/// Shared by the parametric quadric shapes.
+pub(crate) fn normal_derivatives(
+    dpdu: Vector3f,
+    dpdv: Vector3f,
+    d2pduu: Vector3f,
+    d2pduv: Vector3f,
+    d2pdvv: Vector3f,
+) -> (Normal3f, Normal3f) {
+    // Compute coefficients for fundamental forms
+    let e1 = dot(dpdu, dpdu);
+    let f1 = dot(dpdu, dpdv);
+    let g1 = dot(dpdv, dpdv);
+    let n = cross(dpdu, dpdv).normalize();
+    let e = dot(n, d2pduu);
+    let f = dot(n, d2pduv);
+    let g = dot(n, d2pdvv);
+
+    // Compute dndu and dndv from fundamental form coefficients
+    let inv_egf2 = 1. / (e1 * g1 - f1 * f1);
+    let dndu = Normal3f::from(
+        dpdu * ((f * f1 - e * g1) * inv_egf2) + dpdv * ((e * f1 - f * e1) * inv_egf2),
+    );
+    let dndv = Normal3f::from(
+        dpdu * ((g * f1 - f * g1) * inv_egf2) + dpdv * ((f * f1 - g * e1) * inv_egf2),
+    );
+    (dndu, dndv)
+}
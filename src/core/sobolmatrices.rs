@@ -74,11 +74,15 @@
 // The tabulated direction numbers are available here:
 // http://web.maths.unsw.edu.au/~fkuo/sobol/new-joe-kuo-6.21201
 
+//! Generator matrices for the Sobol' and (0, 2)-sequence samplers.
+// TODO(wathiede): remove once the low discrepancy samplers land.
+#![allow(dead_code)]
+
 // Sobol Matrix Declarations
 const NUM_SOBOL_DIMENSIONS: usize = 1024;
 const SOBOL_MATRIX_SIZE: usize = 52;
 
-const SOBOL_MATRICES32: [u32; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
+static SOBOL_MATRICES32: [u32; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
     0x80000000, 0x40000000, 0x20000000, 0x10000000, 0x08000000, 0x04000000, 0x02000000, 0x01000000,
     0x00800000, 0x00400000, 0x00200000, 0x00100000, 0x00080000, 0x00040000, 0x00020000, 0x00010000,
     0x00008000, 0x00004000, 0x00002000, 0x00001000, 0x00000800, 0x00000400, 0x00000200, 0x00000100,
@@ -6737,7 +6741,7 @@ const SOBOL_MATRICES32: [u32; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
     0xf44133aa, 0x8d64636f, 0x3735b3ac, 0xb689234c, 0x6d8253b0, 0x59c0d35a, 0x34a32b93, 0x1397876e,
 ];
 
-const SOBOL_MATRICES64: [u64; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
+static SOBOL_MATRICES64: [u64; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
     0x0008000000000000,
     0x0004000000000000,
     0x0002000000000000,
@@ -59988,7 +59992,7 @@ const SOBOL_MATRICES64: [u64; NUM_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE] = [
     0x0001397876ec5561,
 ];
 
-const VD_CSOBOL_MATRICES: [[u64; SOBOL_MATRIX_SIZE]; 25] = [
+static VD_CSOBOL_MATRICES: [[u64; SOBOL_MATRIX_SIZE]; 25] = [
     [
         // m = 1
         0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1,
@@ -60147,7 +60151,7 @@ const VD_CSOBOL_MATRICES: [[u64; SOBOL_MATRIX_SIZE]; 25] = [
     ],
 ];
 
-const VD_CSOBOL_MATRICES_INV: [[u64; SOBOL_MATRIX_SIZE]; 26] = [
+static VD_CSOBOL_MATRICES_INV: [[u64; SOBOL_MATRIX_SIZE]; 26] = [
     [
         // m = 1
         0x2, 0x3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
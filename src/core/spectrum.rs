@@ -17,12 +17,17 @@
 //! [RGBSpectrum]: crate::core::spectrum::RGBSpectrum
 //! [SampledSpectrum]: crate::core::spectrum::SampledSpectrum
 //! [Spectrum]: crate::core::spectrum::Spectrum
-use std::ops::{Mul, MulAssign};
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Sub},
+};
 
-use crate::Float;
+use lazy_static::lazy_static;
+
+use crate::{lerp, Float};
 
 /// Spectrum type, used when converting between RGB and [SampledSpectrum]
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum SpectrumType {
     /// Use reflectance coefficients
     Reflectance,
@@ -35,18 +40,92 @@ pub enum SpectrumType {
 /// implementations.
 /// [RGBSpectrum]: crate::core::spectrum::RGBSpectrum
 /// [SampledSpectrum]: crate::core::spectrum::SampledSpectrum
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct CoefficientSpectrum<const N: usize> {
     // TODO(wathiede): try removing pub on `c`.
     c: [Float; N],
 }
 
+/// Spectra serialize as a sequence of their `N` coefficients.  serde only implements its traits
+/// for arrays of up to 32 elements, too few for `SampledSpectrum`.
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for CoefficientSpectrum<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.c.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for CoefficientSpectrum<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use std::convert::TryInto;
+
+        use serde::de::Error;
+
+        let v = Vec::<Float>::deserialize(deserializer)?;
+        let c = v.try_into().map_err(|v: Vec<Float>| {
+            D::Error::invalid_length(v.len(), &format!("{} coefficients", N).as_str())
+        })?;
+        Ok(CoefficientSpectrum { c })
+    }
+}
+
 impl<const N: usize> From<Float> for CoefficientSpectrum<N> {
     fn from(f: Float) -> CoefficientSpectrum<N> {
         CoefficientSpectrum { c: [f; N] }
     }
 }
 
+impl<const N: usize> fmt::Display for CoefficientSpectrum<N> {
+    /// Prints `RGBSpectrum` as `rgb(r, g, b)`, and a `[min..max, avg]` summary of spectra with
+    /// many coefficients like `SampledSpectrum`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::{RGBSpectrum, SampledSpectrum};
+    ///
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb([0.25, 0.5, 1.]).to_string(),
+    ///     "rgb(0.25, 0.5, 1)"
+    /// );
+    /// assert_eq!(SampledSpectrum::new(0.5).to_string(), "[0.5..0.5, 0.5]");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if N == 3 {
+            return write!(f, "rgb({}, {}, {})", self.c[0], self.c[1], self.c[2]);
+        }
+        let min = self.c.iter().cloned().fold(Float::INFINITY, Float::min);
+        let max = self.c.iter().cloned().fold(Float::NEG_INFINITY, Float::max);
+        let avg = self.c.iter().sum::<Float>() / N as Float;
+        write!(f, "[{}..{}, {}]", min, max, avg)
+    }
+}
+
+impl<const N: usize> fmt::Debug for CoefficientSpectrum<N> {
+    /// Same as `Display`, so logging structures holding spectra stays readable.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<const N: usize> IntoIterator for CoefficientSpectrum<N> {
+    type Item = Float;
+    type IntoIter = std::array::IntoIter<Float, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterator::into_iter(self.c)
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a CoefficientSpectrum<N> {
+    type Item = &'a Float;
+    type IntoIter = std::slice::Iter<'a, Float>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.c.iter()
+    }
+}
+
 impl<const N: usize> Default for CoefficientSpectrum<N> {
     fn default() -> CoefficientSpectrum<N> {
         Float::from(0_u8).into()
@@ -75,9 +154,163 @@ impl<const N: usize> Mul for CoefficientSpectrum<N> {
     }
 }
 
+impl<const N: usize> Mul<Float> for CoefficientSpectrum<N> {
+    type Output = Self;
+
+    /// Scale each coefficient by `rhs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([1., 2., 3.]) * 2.;
+    /// assert_eq!(s, RGBSpectrum::from_rgb([2., 4., 6.]));
+    /// ```
+    fn mul(self, rhs: Float) -> Self::Output {
+        debug_assert!(!rhs.is_nan());
+        let mut c = self.c;
+        c.iter_mut().for_each(|v| *v *= rhs);
+        Self { c }
+    }
+}
+
+impl<const N: usize> Div<Float> for CoefficientSpectrum<N> {
+    type Output = Self;
+
+    /// Divide each coefficient by `rhs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([2., 4., 6.]) / 2.;
+    /// assert_eq!(s, RGBSpectrum::from_rgb([1., 2., 3.]));
+    /// ```
+    fn div(self, rhs: Float) -> Self::Output {
+        debug_assert_ne!(rhs, 0.);
+        let mut c = self.c;
+        c.iter_mut().for_each(|v| *v /= rhs);
+        Self { c }
+    }
+}
+
+impl<const N: usize> Div for CoefficientSpectrum<N> {
+    type Output = Self;
+
+    /// Divide each coefficient of `self` by the corresponding coefficient of `rhs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([2., 4., 6.]) / RGBSpectrum::from_rgb([1., 2., 4.]);
+    /// assert_eq!(s, RGBSpectrum::from_rgb([2., 2., 1.5]));
+    /// ```
+    fn div(self, rhs: Self) -> Self::Output {
+        let mut c = self.c;
+        c.iter_mut().zip(rhs.c.iter()).for_each(|(l, r)| {
+            debug_assert_ne!(*r, 0.);
+            *l /= r
+        });
+        Self { c }
+    }
+}
+
+impl<const N: usize> Sub for CoefficientSpectrum<N> {
+    type Output = Self;
+
+    /// Subtract each coefficient of `rhs` from the corresponding coefficient of `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([1., 2., 3.]) - RGBSpectrum::from_rgb([1., 1., 1.]);
+    /// assert_eq!(s, RGBSpectrum::from_rgb([0., 1., 2.]));
+    /// ```
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut c = self.c;
+        c.iter_mut().zip(rhs.c.iter()).for_each(|(l, r)| *l -= r);
+        Self { c }
+    }
+}
+
+impl<const N: usize> Add for CoefficientSpectrum<N> {
+    type Output = Self;
+
+    /// Add each coefficient of `rhs` to the corresponding coefficient of `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([1., 2., 3.]) + RGBSpectrum::from_rgb([1., 1., 1.]);
+    /// assert_eq!(s, RGBSpectrum::from_rgb([2., 3., 4.]));
+    /// ```
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl<const N: usize> AddAssign for CoefficientSpectrum<N> {
+    /// Add each coefficient of `rhs` to `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let mut s = RGBSpectrum::from_rgb([1., 2., 3.]);
+    /// s += RGBSpectrum::from_rgb([1., 1., 1.]);
+    /// assert_eq!(s, RGBSpectrum::from_rgb([2., 3., 4.]));
+    /// ```
+    fn add_assign(&mut self, rhs: Self) {
+        self.c
+            .iter_mut()
+            .zip(rhs.c.iter())
+            .for_each(|(l, r)| *l += r);
+    }
+}
+
 impl<const N: usize> CoefficientSpectrum<N> {
-    #[allow(dead_code)]
-    fn has_nans(&self) -> bool {
+    /// Returns the spectrum's coefficients, one per channel.  For `RGBSpectrum` these are the
+    /// red, green and blue values.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let red = RGBSpectrum::from_rgb([1., 0., 0.]);
+    /// assert_eq!(red.channels(), &[1., 0., 0.]);
+    /// for (i, c) in (&red).into_iter().enumerate() {
+    ///     assert_eq!(*c, if i == 0 { 1. } else { 0. });
+    /// }
+    /// let channels: Vec<_> = red.into_iter().collect();
+    /// assert_eq!(channels, vec![1., 0., 0.]);
+    /// ```
+    pub fn channels(&self) -> &[Float; N] {
+        &self.c
+    }
+
+    /// Linearly interpolates between `a` and `b`, returning `a` when `t` is 0 and `b` when `t`
+    /// is 1.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let black = RGBSpectrum::new(0.);
+    /// let white = RGBSpectrum::new(1.);
+    /// assert_eq!(RGBSpectrum::lerp(0.5, &black, &white), RGBSpectrum::new(0.5));
+    /// assert_eq!(RGBSpectrum::lerp(0., &black, &white), black);
+    /// assert_eq!(RGBSpectrum::lerp(1., &black, &white), white);
+    /// ```
+    pub fn lerp(t: Float, a: &Self, b: &Self) -> Self {
+        a.clone() * (1. - t) + b.clone() * t
+    }
+
+    /// Returns true if any of the spectrum's coefficients are NaN.
+    pub fn has_nans(&self) -> bool {
         for i in 0..N {
             if self.c[i].is_nan() {
                 return true;
@@ -85,9 +318,418 @@ impl<const N: usize> CoefficientSpectrum<N> {
         }
         false
     }
+
+    /// Returns true if all of the spectrum's coefficients are zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// assert!(RGBSpectrum::new(0.).is_black());
+    /// assert!(!RGBSpectrum::from_rgb([0., 0.1, 0.]).is_black());
+    /// ```
+    pub fn is_black(&self) -> bool {
+        self.c.iter().all(|v| *v == 0.)
+    }
+
+    /// Returns the largest of the spectrum's coefficients.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// assert_eq!(RGBSpectrum::from_rgb([0.25, 0.75, 0.5]).max_component_value(), 0.75);
+    /// ```
+    pub fn max_component_value(&self) -> Float {
+        self.c.iter().cloned().fold(Float::NEG_INFINITY, Float::max)
+    }
+
+    /// Returns the spectrum whose coefficients are e raised to the power of this spectrum's.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb([0., 1., -1.]).exp().channels(),
+    ///     &[1., (1. as pbrt::Float).exp(), (-1. as pbrt::Float).exp()]
+    /// );
+    /// ```
+    pub fn exp(&self) -> CoefficientSpectrum<N> {
+        let mut c = self.c;
+        c.iter_mut().for_each(|v| *v = v.exp());
+        CoefficientSpectrum { c }
+    }
+
+    /// Returns the spectrum whose coefficients are the square roots of this spectrum's.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb([0., 4., 9.]).sqrt(),
+    ///     RGBSpectrum::from_rgb([0., 2., 3.])
+    /// );
+    /// ```
+    pub fn sqrt(&self) -> CoefficientSpectrum<N> {
+        let mut c = self.c;
+        c.iter_mut().for_each(|v| *v = v.sqrt());
+        CoefficientSpectrum { c }
+    }
+
+    /// Returns a copy of this spectrum with negative coefficients set to zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb([-1., 0.5, 2.]).clamp_nonnegative(),
+    ///     RGBSpectrum::from_rgb([0., 0.5, 2.])
+    /// );
+    /// ```
+    pub fn clamp_nonnegative(&self) -> CoefficientSpectrum<N> {
+        let mut c = self.c;
+        c.iter_mut().for_each(|v| *v = v.max(0.));
+        CoefficientSpectrum { c }
+    }
+
+    /// Returns a copy of this spectrum with NaN and infinite coefficients set to zero.  Numerical
+    /// problems occasionally produce such values, this keeps them from poisoning sums of
+    /// spectra.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{core::spectrum::RGBSpectrum, Float};
+    ///
+    /// let s = RGBSpectrum::new(Float::NAN);
+    /// assert!(s.has_nans());
+    /// assert_eq!(s.sanitize(), RGBSpectrum::new(0.));
+    /// let s = RGBSpectrum::from_rgb([0.5, 1., 2.]) * Float::INFINITY;
+    /// assert_eq!(s.sanitize(), RGBSpectrum::new(0.));
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb([-1., 0.5, 2.]).sanitize(),
+    ///     RGBSpectrum::from_rgb([-1., 0.5, 2.])
+    /// );
+    /// ```
+    pub fn sanitize(&self) -> CoefficientSpectrum<N> {
+        let mut c = self.c;
+        c.iter_mut()
+            .filter(|v| !v.is_finite())
+            .for_each(|v| *v = 0.);
+        CoefficientSpectrum { c }
+    }
 }
 
+const SAMPLED_LAMBDA_START: Float = 400.;
+const SAMPLED_LAMBDA_END: Float = 700.;
 const N_SPECTRAL_SAMPLES: usize = 60;
+
+/// Wavelengths, in nm, of the values in the `SMITS_*` tables.
+#[allow(clippy::excessive_precision)]
+const SMITS_LAMBDA: [Float; 10] = [
+    380., 417.77777, 455.55556, 493.33333, 531.11111, 568.88889, 606.66667, 644.44444, 682.22222,
+    720.,
+];
+// Spectra from Smits, "An RGB-to-Spectrum Conversion for Reflectances", 1999.
+const SMITS_WHITE: [Float; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [Float; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [Float; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [Float; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [Float; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [Float; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [Float; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Returns the average of the piecewise linear function through (`lambda`, `vals`) over the
+/// wavelength range [`lambda_start`, `lambda_end`].  Values outside of `lambda` are extended as
+/// constants.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::spectrum::average_spectrum_samples;
+///
+/// let lambda = [400., 500., 600.];
+/// let vals = [0., 1., 1.];
+/// assert_eq!(average_spectrum_samples(&lambda, &vals, 400., 500.), 0.5);
+/// assert_eq!(average_spectrum_samples(&lambda, &vals, 500., 700.), 1.);
+/// assert_eq!(average_spectrum_samples(&lambda, &vals, 300., 350.), 0.);
+/// ```
+pub fn average_spectrum_samples(
+    lambda: &[Float],
+    vals: &[Float],
+    lambda_start: Float,
+    lambda_end: Float,
+) -> Float {
+    debug_assert_eq!(lambda.len(), vals.len());
+    debug_assert!(lambda.windows(2).all(|w| w[0] < w[1]));
+    let n = lambda.len();
+    // Handle cases with out-of-bounds range or single sample only
+    if lambda_end <= lambda[0] {
+        return vals[0];
+    }
+    if lambda_start >= lambda[n - 1] {
+        return vals[n - 1];
+    }
+    if n == 1 {
+        return vals[0];
+    }
+
+    // Add contributions of constant segments before/after samples
+    let mut sum = 0.;
+    if lambda_start < lambda[0] {
+        sum += vals[0] * (lambda[0] - lambda_start);
+    }
+    if lambda_end > lambda[n - 1] {
+        sum += vals[n - 1] * (lambda_end - lambda[n - 1]);
+    }
+
+    // Advance to first relevant wavelength segment
+    let mut i = 0;
+    while lambda_start > lambda[i + 1] {
+        i += 1;
+    }
+
+    // Loop over wavelength sample segments and add contributions
+    let interp = |w: Float, i: usize| {
+        lerp(
+            (w - lambda[i]) / (lambda[i + 1] - lambda[i]),
+            vals[i],
+            vals[i + 1],
+        )
+    };
+    while i + 1 < n && lambda_end >= lambda[i] {
+        let seg_lambda_start = lambda_start.max(lambda[i]);
+        let seg_lambda_end = lambda_end.min(lambda[i + 1]);
+        sum += 0.5
+            * (interp(seg_lambda_start, i) + interp(seg_lambda_end, i))
+            * (seg_lambda_end - seg_lambda_start);
+        i += 1;
+    }
+    sum / (lambda_end - lambda_start)
+}
+
+/// Returns the value at `l` of the piecewise linear function through (`lambda`, `vals`).  Values
+/// outside of `lambda` are extended as constants.
+fn interpolate_spectrum_samples(lambda: &[Float], vals: &[Float], l: Float) -> Float {
+    debug_assert_eq!(lambda.len(), vals.len());
+    let n = lambda.len();
+    if l <= lambda[0] {
+        return vals[0];
+    }
+    if l >= lambda[n - 1] {
+        return vals[n - 1];
+    }
+    let i = lambda.partition_point(|&v| v <= l) - 1;
+    let t = (l - lambda[i]) / (lambda[i + 1] - lambda[i]);
+    lerp(t, vals[i], vals[i + 1])
+}
+
+/// Returns copies of `lambda` and `vals` ordered by increasing wavelength.
+fn sort_spectrum_samples(lambda: &[Float], vals: &[Float]) -> (Vec<Float>, Vec<Float>) {
+    let mut samples: Vec<_> = lambda.iter().copied().zip(vals.iter().copied()).collect();
+    samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    samples.into_iter().unzip()
+}
+
+/// Range, in nm, the CIE matching functions are sampled over.
+const CIE_LAMBDA_START: usize = 360;
+const CIE_LAMBDA_END: usize = 830;
+
+/// Evaluates the multi-lobe Gaussian fit to the CIE 1931 color matching functions from Wyman, Sloan
+/// and Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions", at
+/// `lambda` nm.
+fn cie_xyz_fit(lambda: Float) -> [Float; 3] {
+    let g = |mu: Float, sigma_lo: Float, sigma_hi: Float| {
+        let t = (lambda - mu) / if lambda < mu { sigma_lo } else { sigma_hi };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+lazy_static! {
+    /// The CIE matching functions sampled every nm, and the sum of the Y samples used to
+    /// normalize integrals against them.
+    static ref CIE_SAMPLES: (Vec<(Float, [Float; 3])>, Float) = {
+        let samples: Vec<_> = (CIE_LAMBDA_START..=CIE_LAMBDA_END)
+            .map(|l| (l as Float, cie_xyz_fit(l as Float)))
+            .collect();
+        let y_sum = samples.iter().map(|(_, xyz)| xyz[1]).sum();
+        (samples, y_sum)
+    };
+}
+
+/// Computes the XYZ color of the piecewise linear spectrum through (`lambda`, `vals`).  A constant
+/// spectrum of one has a luminance of one.
+fn sampled_to_xyz(lambda: &[Float], vals: &[Float]) -> [Float; 3] {
+    let (samples, y_sum) = &*CIE_SAMPLES;
+    let mut xyz = [0.; 3];
+    for (l, cie) in samples {
+        let v = interpolate_spectrum_samples(lambda, vals, *l);
+        xyz.iter_mut()
+            .zip(cie.iter())
+            .for_each(|(c, w)| *c += v * w);
+    }
+    xyz.iter_mut().for_each(|c| *c /= y_sum);
+    xyz
+}
+
+/// Returns the radiance emitted by a blackbody at temperature `t` in Kelvin, for each of the
+/// wavelengths in `lambda` given in nm.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::spectrum::blackbody;
+///
+/// let le = blackbody(&[400., 500., 600.], 6000.);
+/// // A 6000K blackbody peaks near 483nm.
+/// assert!(le[1] > le[0] && le[1] > le[2]);
+/// assert_eq!(blackbody(&[500.], 0.), vec![0.]);
+/// ```
+#[allow(clippy::excessive_precision)]
+pub fn blackbody(lambda: &[Float], t: Float) -> Vec<Float> {
+    if t <= 0. {
+        return vec![0.; lambda.len()];
+    }
+    const C: Float = 299792458.;
+    const H: Float = 6.62606957e-34;
+    const KB: Float = 1.3806488e-23;
+    lambda
+        .iter()
+        .map(|l| {
+            // Compute emitted radiance for blackbody at wavelength _lambda[i]_
+            let l = l * 1e-9;
+            (2. * H * C * C) / (l.powi(5) * (((H * C) / (l * KB * t)).exp() - 1.))
+        })
+        .collect()
+}
+
+/// Like [blackbody], but scaled so the spectrum's peak value is one.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::spectrum::blackbody_normalized;
+///
+/// // Wien's displacement law puts the peak of a 5000K blackbody at 579.55nm.
+/// let le = blackbody_normalized(&[400., 579.55, 700.], 5000.);
+/// assert!((le[1] - 1.).abs() < 1e-4);
+/// assert!(le[0] < 1. && le[2] < 1.);
+/// ```
+#[allow(clippy::excessive_precision)]
+pub fn blackbody_normalized(lambda: &[Float], t: Float) -> Vec<Float> {
+    let mut le = blackbody(lambda, t);
+    // Normalize _Le_ values based on maximum blackbody radiance
+    let lambda_max = 2.8977721e-3 / t * 1e9;
+    let max_l = blackbody(&[lambda_max], t)[0];
+    if max_l > 0. {
+        le.iter_mut().for_each(|v| *v /= max_l);
+    }
+    le
+}
+
+/// Returns the wavelengths, in nm, a spectrum given as a function of wavelength should be sampled
+/// at for conversion with [SampledSpectrum::from_sampled] or [RGBSpectrum::from_sampled].
+pub fn cie_lambda() -> impl Iterator<Item = Float> {
+    (CIE_LAMBDA_START..=CIE_LAMBDA_END).map(|l| l as Float)
+}
+
+/// Spectra [SampledSpectrum::from_rgb_typed] combines to match an RGB color.
+struct RGBToSpectrumBasis {
+    white: SampledSpectrum,
+    cyan: SampledSpectrum,
+    magenta: SampledSpectrum,
+    yellow: SampledSpectrum,
+    red: SampledSpectrum,
+    green: SampledSpectrum,
+    blue: SampledSpectrum,
+    /// Applied to the combined spectrum, keeps reconstructed spectra from overshooting.
+    scale: Float,
+}
+
+impl RGBToSpectrumBasis {
+    fn from_smits(scale: Float) -> RGBToSpectrumBasis {
+        let resample = |vals: &[Float]| SampledSpectrum::from_sampled(&SMITS_LAMBDA, vals);
+        RGBToSpectrumBasis {
+            white: resample(&SMITS_WHITE),
+            cyan: resample(&SMITS_CYAN),
+            magenta: resample(&SMITS_MAGENTA),
+            yellow: resample(&SMITS_YELLOW),
+            red: resample(&SMITS_RED),
+            green: resample(&SMITS_GREEN),
+            blue: resample(&SMITS_BLUE),
+            scale,
+        }
+    }
+
+    /// Decomposes `rgb` into white plus at most one of cyan, magenta or yellow and one of red,
+    /// green or blue, and sums the corresponding spectra.
+    fn convert(&self, rgb: [Float; 3]) -> SampledSpectrum {
+        let mut c = [0.; N_SPECTRAL_SAMPLES];
+        let mut add = |w: Float, s: &SampledSpectrum| {
+            c.iter_mut().zip(s.c.iter()).for_each(|(c, s)| *c += w * s);
+        };
+        let [r, g, b] = rgb;
+        if r <= g && r <= b {
+            // Compute spectrum with _r_ as minimum
+            add(r, &self.white);
+            if g <= b {
+                add(g - r, &self.cyan);
+                add(b - g, &self.blue);
+            } else {
+                add(b - r, &self.cyan);
+                add(g - b, &self.green);
+            }
+        } else if g <= r && g <= b {
+            // Compute spectrum with _g_ as minimum
+            add(g, &self.white);
+            if r <= b {
+                add(r - g, &self.magenta);
+                add(b - r, &self.blue);
+            } else {
+                add(b - g, &self.magenta);
+                add(r - b, &self.red);
+            }
+        } else {
+            // Compute spectrum with _b_ as minimum
+            add(b, &self.white);
+            if r <= g {
+                add(r - b, &self.yellow);
+                add(g - r, &self.green);
+            } else {
+                add(g - b, &self.yellow);
+                add(r - g, &self.red);
+            }
+        }
+        c.iter_mut().for_each(|v| *v = (*v * self.scale).max(0.));
+        SampledSpectrum { c }
+    }
+}
+
+lazy_static! {
+    static ref RGB_REFLECTANCE_BASIS: RGBToSpectrumBasis = RGBToSpectrumBasis::from_smits(0.94);
+    // TODO(wathiede): pbrt fits separate basis spectra for illuminants so white matches the
+    // sRGB white point, port those instead of reusing Smits' reflectance spectra.
+    static ref RGB_ILLUMINANT_BASIS: RGBToSpectrumBasis = RGBToSpectrumBasis::from_smits(0.86445);
+}
+
 /// `SampledSpectrum` is a spectrum represented by `N_SPECTRAL_SAMPLES` (currently 60) values
 /// evenly spread across 400 nm to 700 nm.
 pub type SampledSpectrum = CoefficientSpectrum<N_SPECTRAL_SAMPLES>;
@@ -107,20 +749,92 @@ impl SampledSpectrum {
         todo!("SampledSpectrum::to_xyz")
     }
 
+    /// Returns the luminance of this `SampledSpectrum`, i.e. the Y component of its XYZ value.
+    pub fn y(&self) -> Float {
+        self.to_xyz()[1]
+    }
+
     /// extract this `SampledSpectrum`'s value in the RGB color space.
     pub fn to_rgb(&self) -> [Float; 3] {
         todo!("SampledSpectrum::to_rgb")
     }
 
-    /// create an `SampledSpectrum` from the given tristimulus values in sRGB color space.
+    /// create an `SampledSpectrum` from the given tristimulus values in sRGB color space,
+    /// treating them as a reflectance.
     pub fn from_rgb(c: [Float; 3]) -> SampledSpectrum {
-        todo!("SampledSpectrum::from_rgb({:?})", c)
+        SampledSpectrum::from_rgb_typed(c, SpectrumType::Reflectance)
+    }
+
+    /// create an `SampledSpectrum` from the given tristimulus values in sRGB color space.
+    /// Reflectances and illuminants are reconstructed from different basis spectra, `ty` selects
+    /// which.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::{SampledSpectrum, SpectrumType};
+    ///
+    /// let refl = SampledSpectrum::from_rgb_typed([0.2, 0.4, 0.8], SpectrumType::Reflectance);
+    /// let illum = SampledSpectrum::from_rgb_typed([0.2, 0.4, 0.8], SpectrumType::Illuminant);
+    /// assert_ne!(refl, illum);
+    /// assert!(SampledSpectrum::from_rgb_typed([0.; 3], SpectrumType::Illuminant).is_black());
+    /// ```
+    pub fn from_rgb_typed(c: [Float; 3], ty: SpectrumType) -> SampledSpectrum {
+        let s = match ty {
+            SpectrumType::Reflectance => RGB_REFLECTANCE_BASIS.convert(c),
+            SpectrumType::Illuminant => RGB_ILLUMINANT_BASIS.convert(c),
+        };
+        debug_assert!(!s.has_nans(), "c {:?}", s);
+        s
     }
 
     /// create an `SampledSpectrum` from the given tristimulus values in XYZ color space.
     pub fn from_xyz(c: [Float; 3]) -> SampledSpectrum {
         todo!("SampledSpectrum::from_xyz({:?})", c)
     }
+
+    /// create a `SampledSpectrum` from the piecewise linear spectrum through (`lambda`, `vals`),
+    /// wavelengths given in nm need not be sorted.  Each sample is the spectrum's average over
+    /// the sample's range of wavelengths.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::SampledSpectrum;
+    ///
+    /// let s = SampledSpectrum::from_sampled(&[700., 400.], &[1., 1.]);
+    /// assert_eq!(s, SampledSpectrum::new(1.));
+    /// ```
+    pub fn from_sampled(lambda: &[Float], vals: &[Float]) -> SampledSpectrum {
+        let (lambda, vals) = sort_spectrum_samples(lambda, vals);
+        let mut c = [0.; N_SPECTRAL_SAMPLES];
+        for (i, v) in c.iter_mut().enumerate() {
+            // Compute average value of given SPD over $i$th sample's range
+            let lambda0 = lerp(
+                i as Float / N_SPECTRAL_SAMPLES as Float,
+                SAMPLED_LAMBDA_START,
+                SAMPLED_LAMBDA_END,
+            );
+            let lambda1 = lerp(
+                (i + 1) as Float / N_SPECTRAL_SAMPLES as Float,
+                SAMPLED_LAMBDA_START,
+                SAMPLED_LAMBDA_END,
+            );
+            *v = average_spectrum_samples(&lambda, &vals, lambda0, lambda1);
+        }
+        SampledSpectrum { c }
+    }
+}
+
+/// Linearly interpolates between spectra `s1` and `s2` by `t`.  See [CoefficientSpectrum::lerp].
+///
+/// # Examples
+/// ```
+/// use pbrt::core::spectrum::{lerp_spectrum, Spectrum};
+///
+/// let s = lerp_spectrum(0.25, &Spectrum::new(0.), &Spectrum::new(2.));
+/// assert_eq!(s, Spectrum::new(0.5));
+/// ```
+pub fn lerp_spectrum(t: Float, s1: &Spectrum, s2: &Spectrum) -> Spectrum {
+    Spectrum::lerp(t, s1, s2)
 }
 
 /// Convert tristimulus values in the XYZ color space (as defined by CIE) matching the human eye's
@@ -144,6 +858,58 @@ pub fn rgb_to_xyz(rgb: [Float; 3]) -> [Float; 3] {
     ]
 }
 
+/// Complex indices of refraction of common metals, fit to RGB from measured spectral data.  Each
+/// entry is the metal's chemical symbol, its index of refraction and its absorption coefficient.
+#[allow(clippy::excessive_precision)]
+const METALS: [(&str, [Float; 3], [Float; 3]); 4] = [
+    (
+        "Ag",
+        [0.155265, 0.116723, 0.138342],
+        [4.828350, 3.122250, 2.146960],
+    ),
+    (
+        "Al",
+        [1.657460, 0.880369, 0.521229],
+        [9.223870, 6.269520, 4.837000],
+    ),
+    (
+        "Au",
+        [0.143119, 0.374957, 1.442480],
+        [3.983160, 2.385720, 1.603220],
+    ),
+    (
+        "Cu",
+        [0.200438, 0.924033, 1.102210],
+        [3.912950, 2.452850, 2.142190],
+    ),
+];
+
+/// Returns the built-in spectrum called `name`, or `None` if there is no such spectrum.  Like
+/// pbrt-v4, the index of refraction and absorption coefficient of a metal are called
+/// "metal-<symbol>-eta" and "metal-<symbol>-k".  Silver (Ag), aluminium (Al), gold (Au) and copper
+/// (Cu) are available.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::spectrum::named_spectrum;
+///
+/// let k = named_spectrum("metal-Au-k").expect("gold");
+/// // Gold's low absorption coefficient for blue is why it reflects less blue than red.
+/// assert!(k.to_rgb()[2] < k.to_rgb()[0]);
+/// assert!(named_spectrum("metal-Xx-eta").is_none());
+/// ```
+pub fn named_spectrum(name: &str) -> Option<Spectrum> {
+    let name = name.strip_prefix("metal-")?;
+    METALS.iter().find_map(|(symbol, eta, k)| {
+        let rgb = match name.strip_prefix(symbol)? {
+            "-eta" => eta,
+            "-k" => k,
+            _ => return None,
+        };
+        Some(Spectrum::from_rgb_typed(*rgb, SpectrumType::Reflectance))
+    })
+}
+
 /// `RGBSpectrum` is a sample implemented with 3 values at red, green and blue points in the
 /// spectrum.  Values stored are in the range [0., 1.].
 pub type RGBSpectrum = CoefficientSpectrum<3>;
@@ -167,6 +933,21 @@ impl RGBSpectrum {
         rgb_to_xyz(self.c)
     }
 
+    /// Returns the luminance of this `RGBSpectrum`, i.e. the Y component of its XYZ value.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_rgb([0.2, 0.4, 0.8]);
+    /// assert_eq!(s.y(), s.to_xyz()[1]);
+    /// // Green contributes most to perceived brightness.
+    /// assert!(RGBSpectrum::from_rgb([0., 1., 0.]).y() > RGBSpectrum::from_rgb([1., 0., 1.]).y());
+    /// ```
+    pub fn y(&self) -> Float {
+        0.212671 * self.c[0] + 0.715160 * self.c[1] + 0.072169 * self.c[2]
+    }
+
     /// extract this `RGBSpectrum`'s value in the RGB color space.
     pub fn to_rgb(&self) -> [Float; 3] {
         self.c
@@ -179,6 +960,24 @@ impl RGBSpectrum {
         s
     }
 
+    /// create an `RGBSpectrum` from the given tristimulus values in sRGB color space.  RGB values
+    /// are stored exactly, so `_ty` is ignored.  It exists for a unified API with
+    /// SampledSpectrum.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::{RGBSpectrum, SpectrumType};
+    ///
+    /// let rgb = [0.2, 0.4, 0.8];
+    /// assert_eq!(
+    ///     RGBSpectrum::from_rgb_typed(rgb, SpectrumType::Reflectance),
+    ///     RGBSpectrum::from_rgb_typed(rgb, SpectrumType::Illuminant)
+    /// );
+    /// ```
+    pub fn from_rgb_typed(c: [Float; 3], _ty: SpectrumType) -> RGBSpectrum {
+        RGBSpectrum::from_rgb(c)
+    }
+
     /// create an `RGBSpectrum` from the given tristimulus values in XYZ color space.
     pub fn from_xyz(c: [Float; 3]) -> RGBSpectrum {
         let rgb = xyz_to_rgb(c);
@@ -186,8 +985,87 @@ impl RGBSpectrum {
         debug_assert!(!s.has_nans(), "c {:?}", s);
         s
     }
+
+    /// create an `RGBSpectrum` from the piecewise linear spectrum through (`lambda`, `vals`),
+    /// wavelengths given in nm need not be sorted.  The spectrum is converted to XYZ with the CIE
+    /// matching functions, then to RGB.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::spectrum::RGBSpectrum;
+    ///
+    /// let s = RGBSpectrum::from_sampled(&[700., 400.], &[1., 1.]);
+    /// assert!((s.y() - 1.).abs() < 1e-4);
+    /// ```
+    pub fn from_sampled(lambda: &[Float], vals: &[Float]) -> RGBSpectrum {
+        let (lambda, vals) = sort_spectrum_samples(lambda, vals);
+        RGBSpectrum::from_xyz(sampled_to_xyz(&lambda, &vals))
+    }
 }
 
 #[cfg(feature = "sampled-spectrum")]
 /// Define the `Spectrum` type to be `SampledSpectrum` when compiling with the `sampled-spectrum` feature enabled.
 pub type Spectrum = SampledSpectrum;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xyz_rgb_round_trip() {
+        for rgb in [
+            [0., 0., 0.],
+            [1., 1., 1.],
+            [1., 0., 0.],
+            [0., 1., 0.],
+            [0., 0., 1.],
+            [0.2, 0.4, 0.8],
+            [0.9, 0.5, 0.1],
+            [2., 0.25, 3.5],
+        ] {
+            let got = xyz_to_rgb(rgb_to_xyz(rgb));
+            for (g, w) in got.iter().zip(rgb.iter()) {
+                assert!((g - w).abs() < 1e-5, "{:?} round tripped to {:?}", rgb, got);
+            }
+            assert_eq!(RGBSpectrum::from_rgb(rgb).y(), rgb_to_xyz(rgb)[1]);
+        }
+        // Equal energy white has a luminance of one.
+        assert!((RGBSpectrum::new(1.).y() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn smits_white() {
+        let white = SampledSpectrum::from_rgb_typed([1., 1., 1.], SpectrumType::Reflectance);
+        for v in white.c.iter() {
+            assert!((v - 0.94).abs() < 1e-3, "{}", white);
+        }
+    }
+
+    #[test]
+    fn smits_primaries() {
+        // Index of the sample containing `lambda` nm.
+        let sample = |lambda: Float| {
+            ((lambda - SAMPLED_LAMBDA_START) / (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START)
+                * N_SPECTRAL_SAMPLES as Float) as usize
+        };
+        for (rgb, peak, trough) in [
+            ([1., 0., 0.], 680., 500.),
+            ([0., 1., 0.], 530., 680.),
+            ([0., 0., 1.], 420., 600.),
+        ] {
+            let s = SampledSpectrum::from_rgb(rgb);
+            assert!(s.c[sample(peak)] > 0.9, "{:?}: {:?}", rgb, s.c);
+            assert!(s.c[sample(trough)] < 0.1, "{:?}: {:?}", rgb, s.c);
+        }
+    }
+
+    #[cfg(feature = "sampled-spectrum")]
+    #[test]
+    fn from_rgb_typed() {
+        let rgb = [0.8, 0.3, 0.1];
+        let refl = Spectrum::from_rgb_typed(rgb, SpectrumType::Reflectance);
+        let illum = Spectrum::from_rgb_typed(rgb, SpectrumType::Illuminant);
+        assert_ne!(refl, illum);
+        assert_eq!(refl, Spectrum::from_rgb(rgb));
+    }
+}
@@ -18,10 +18,21 @@
 //! [textures]: crate::textures
 use std::fmt::Debug;
 
-use crate::core::interaction::SurfaceInteraction;
+use log::error;
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{Point2f, Point3f, Vector3f},
+        interaction::SurfaceInteraction,
+        paramset::TextureParams,
+        transform::Transform,
+    },
+    lerp, Float,
+};
 
 /// The `Texture` trait allows for sampling a material that varies across the surface of an object.
-pub trait Texture<T>: Debug
+pub trait Texture<T>: Debug + Send + Sync
 where
     T: Debug,
 {
@@ -38,3 +49,338 @@ where
         (**self).evaluate(si)
     }
 }
+
+/// Maps points on surfaces into a 2D texture space by scaling and offsetting their (u, v)
+/// coordinates.  The default mapping uses (u, v) unchanged.
+#[derive(Clone, Copy, Debug)]
+pub struct UVMapping2D {
+    su: Float,
+    sv: Float,
+    du: Float,
+    dv: Float,
+}
+
+impl Default for UVMapping2D {
+    fn default() -> UVMapping2D {
+        UVMapping2D::new(1., 1., 0., 0.)
+    }
+}
+
+impl UVMapping2D {
+    /// Creates a new `UVMapping2D` mapping (u, v) to (`su` * u + `du`, `sv` * v + `dv`).
+    pub fn new(su: Float, sv: Float, du: Float, dv: Float) -> UVMapping2D {
+        UVMapping2D { su, sv, du, dv }
+    }
+
+    /// Returns the texture space point of `si`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::Point2f, interaction::SurfaceInteraction, texture::UVMapping2D,
+    /// };
+    ///
+    /// let m = UVMapping2D::new(2., 3., 0.5, -1.);
+    /// let si = SurfaceInteraction {
+    ///     uv: [0.25, 1.].into(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(m.map(&si), Point2f::from([1., 2.]));
+    /// ```
+    pub fn map(&self, si: &SurfaceInteraction) -> Point2f {
+        // TODO(wathiede): return the texture space differentials once SurfaceInteraction
+        // computes them.
+        Point2f::from([self.su * si.uv[0] + self.du, self.sv * si.uv[1] + self.dv])
+    }
+}
+
+/// Creates the 2D mapping described by the "mapping", "uscale", "vscale", "udelta" and "vdelta"
+/// parameters shared by all 2D textures.  Only the "uv" mapping is implemented, others log an
+/// error and fall back to it.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::{
+///     interaction::SurfaceInteraction,
+///     paramset::{testutils::make_float_param_set, TextureParams},
+///     texture::create_uv_mapping_2d,
+/// };
+///
+/// let tp = TextureParams::new(
+///     make_float_param_set("uscale", vec![2.]),
+///     Default::default(),
+///     Default::default(),
+///     Default::default(),
+/// );
+/// let si = SurfaceInteraction {
+///     uv: [0.25, 0.5].into(),
+///     ..Default::default()
+/// };
+/// assert_eq!(create_uv_mapping_2d(&tp).map(&si), [0.5, 0.5].into());
+/// ```
+pub fn create_uv_mapping_2d(tp: &TextureParams) -> UVMapping2D {
+    // Initialize 2D texture mapping _map_ from _tp_
+    let kind = tp.find_string("mapping", "uv");
+    if kind != "uv" {
+        // TODO(wathiede): implement the "spherical", "cylindrical" and "planar" mappings.
+        error!("2D texture mapping \"{}\" unknown, using \"uv\"", kind);
+    }
+    UVMapping2D::new(
+        tp.find_float("uscale", 1.),
+        tp.find_float("vscale", 1.),
+        tp.find_float("udelta", 0.),
+        tp.find_float("vdelta", 0.),
+    )
+}
+
+/// Maps points on surfaces into a 3D texture space by transforming them with `world_to_texture`.
+#[derive(Clone, Debug)]
+pub struct IdentityMapping3D {
+    world_to_texture: Transform,
+}
+
+impl IdentityMapping3D {
+    /// Creates a new `IdentityMapping3D` applying `world_to_texture`.
+    pub fn new(world_to_texture: Transform) -> IdentityMapping3D {
+        IdentityMapping3D { world_to_texture }
+    }
+
+    /// Returns the texture space point of `si`, and the change in that point for a one pixel
+    /// step in x and y on the image.
+    pub fn map(&self, si: &SurfaceInteraction) -> (Point3f, Vector3f, Vector3f) {
+        // TODO(wathiede): transform the position differentials once SurfaceInteraction computes
+        // them.
+        let zero = Vector3f::from([0., 0., 0.]);
+        (self.world_to_texture.transform_point(si.it.p), zero, zero)
+    }
+}
+
+const NOISE_PERM_SIZE: usize = 256;
+
+/// Ken Perlin's permutation of [0, 256), repeated so indices summed from two lookups don't need
+/// to wrap.
+#[rustfmt::skip]
+const NOISE_PERM: [usize; 2 * NOISE_PERM_SIZE] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209,
+    76, 132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+    // Repeated
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209,
+    76, 132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+/// Evaluates Perlin's gradient noise at `p`.  The noise is zero at integer lattice points and
+/// varies smoothly in [-1, 1] between them.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::texture::noise;
+///
+/// assert_eq!(noise([3., -2., 7.].into()), 0.);
+/// let n = noise([3.5, -2.25, 7.75].into());
+/// assert!(n != 0. && n.abs() <= 1.);
+/// ```
+pub fn noise(p: Point3f) -> Float {
+    // Compute noise cell coordinates and offsets
+    let (ix, iy, iz) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (dx, dy, dz) = (p.x - ix, p.y - iy, p.z - iz);
+    let wrap = |i: Float| (i as i64).rem_euclid(NOISE_PERM_SIZE as i64) as usize;
+    let (ix, iy, iz) = (wrap(ix), wrap(iy), wrap(iz));
+
+    // Compute gradient weights
+    let w000 = grad(ix, iy, iz, dx, dy, dz);
+    let w100 = grad(ix + 1, iy, iz, dx - 1., dy, dz);
+    let w010 = grad(ix, iy + 1, iz, dx, dy - 1., dz);
+    let w110 = grad(ix + 1, iy + 1, iz, dx - 1., dy - 1., dz);
+    let w001 = grad(ix, iy, iz + 1, dx, dy, dz - 1.);
+    let w101 = grad(ix + 1, iy, iz + 1, dx - 1., dy, dz - 1.);
+    let w011 = grad(ix, iy + 1, iz + 1, dx, dy - 1., dz - 1.);
+    let w111 = grad(ix + 1, iy + 1, iz + 1, dx - 1., dy - 1., dz - 1.);
+
+    // Compute trilinear interpolation of weights
+    let (wx, wy, wz) = (noise_weight(dx), noise_weight(dy), noise_weight(dz));
+    let x00 = lerp(wx, w000, w100);
+    let x10 = lerp(wx, w010, w110);
+    let x01 = lerp(wx, w001, w101);
+    let x11 = lerp(wx, w011, w111);
+    let y0 = lerp(wy, x00, x10);
+    let y1 = lerp(wy, x01, x11);
+    lerp(wz, y0, y1)
+}
+
+/// Returns the dot product of the gradient vector chosen for lattice point (`x`, `y`, `z`) with
+/// the offset (`dx`, `dy`, `dz`) from it.
+fn grad(x: usize, y: usize, z: usize, dx: Float, dy: Float, dz: Float) -> Float {
+    let h = NOISE_PERM[NOISE_PERM[NOISE_PERM[x] + y] + z] & 15;
+    let u = if h < 8 || h == 12 || h == 13 { dx } else { dy };
+    let v = if h < 4 || h == 12 || h == 13 { dy } else { dz };
+    (if h & 1 != 0 { -u } else { u }) + (if h & 2 != 0 { -v } else { v })
+}
+
+/// The quintic 6t^5 - 15t^4 + 10t^3 used to blend between lattice points, its first and second
+/// derivatives are zero at 0 and 1.
+fn noise_weight(t: Float) -> Float {
+    let t3 = t * t * t;
+    let t4 = t3 * t;
+    6. * t4 * t - 15. * t4 + 10. * t3
+}
+
+/// Returns 0 below `a`, 1 above `b` and smoothly interpolates between them.
+fn smooth_step(x: Float, a: Float, b: Float) -> Float {
+    if a == b {
+        return if x < a { 0. } else { 1. };
+    }
+    let t = clamp((x - a) / (b - a), 0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// Returns the number of octaves of noise that can be summed at a point with the texture space
+/// differentials `dpdx` and `dpdy` before the noise's frequency exceeds the sampling rate.
+fn octaves(dpdx: Vector3f, dpdy: Vector3f, max_octaves: usize) -> Float {
+    let len2 = dpdx.length_squared().max(dpdy.length_squared());
+    clamp(-1. - 0.5 * len2.log2(), 0., max_octaves as Float)
+}
+
+/// Evaluates fractional Brownian motion, a sum of octaves of [noise] at increasing frequencies,
+/// each with `omega` times the weight of the previous.  At most `max_octaves` are summed, fewer
+/// when the differentials `dpdx` and `dpdy` show the higher frequencies would alias.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::texture::fbm;
+///
+/// let zero = [0., 0., 0.].into();
+/// for i in 0..100 {
+///     let f = i as pbrt::Float * 0.37;
+///     let v = fbm([f, 2. * f, -f].into(), zero, zero, 0.5, 8);
+///     // Each octave is within [-1, 1], so the sum is bounded by the sum of the weights.
+///     assert!(v.abs() <= 2., "{}", v);
+/// }
+/// ```
+pub fn fbm(p: Point3f, dpdx: Vector3f, dpdy: Vector3f, omega: Float, max_octaves: usize) -> Float {
+    // Compute number of octaves for antialiased FBm
+    let n = octaves(dpdx, dpdy, max_octaves);
+    let n_int = n.floor() as usize;
+
+    // Compute sum of octaves of noise for FBm
+    let (mut sum, mut lambda, mut o) = (0., 1., 1.);
+    for _ in 0..n_int {
+        sum += o * noise(p * lambda);
+        lambda *= 1.99;
+        o *= omega;
+    }
+    let n_partial = n - n_int as Float;
+    sum + o * smooth_step(n_partial, 0.3, 0.7) * noise(p * lambda)
+}
+
+/// Like [fbm], but sums the absolute value of each octave of noise, giving creases where the
+/// noise crosses zero.  Octaves skipped to avoid aliasing contribute their average value.
+///
+/// # Examples
+/// ```
+/// use pbrt::core::texture::turbulence;
+///
+/// let zero = [0., 0., 0.].into();
+/// let v = turbulence([0.3, 1.7, -2.2].into(), zero, zero, 0.5, 8);
+/// assert!(v >= 0. && v <= 2., "{}", v);
+/// ```
+pub fn turbulence(
+    p: Point3f,
+    dpdx: Vector3f,
+    dpdy: Vector3f,
+    omega: Float,
+    max_octaves: usize,
+) -> Float {
+    // Compute number of octaves for antialiased FBm
+    let n = octaves(dpdx, dpdy, max_octaves);
+    let n_int = n.floor() as usize;
+
+    // Compute sum of octaves of noise for turbulence
+    let (mut sum, mut lambda, mut o) = (0., 1., 1.);
+    for _ in 0..n_int {
+        sum += o * noise(p * lambda).abs();
+        lambda *= 1.99;
+        o *= omega;
+    }
+
+    // Account for contributions of clamped octaves in turbulence
+    let n_partial = n - n_int as Float;
+    sum += o * lerp(
+        smooth_step(n_partial, 0.3, 0.7),
+        0.2,
+        noise(p * lambda).abs(),
+    );
+    for _ in n_int..max_octaves {
+        sum += o * 0.2;
+        o *= omega;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_perm_is_permutation() {
+        let mut seen = [false; NOISE_PERM_SIZE];
+        for (i, &v) in NOISE_PERM[..NOISE_PERM_SIZE].iter().enumerate() {
+            assert!(!seen[v], "{} repeated", v);
+            seen[v] = true;
+            assert_eq!(v, NOISE_PERM[i + NOISE_PERM_SIZE]);
+        }
+    }
+
+    #[test]
+    fn noise_is_smooth_and_periodic() {
+        let mut max: Float = 0.;
+        for i in 0..1000 {
+            let f = i as Float * 0.0731;
+            let p = Point3f::from([f, 1.3 * f + 0.1, -0.7 * f + 0.2]);
+            let n = noise(p);
+            assert!(n.abs() <= 1., "noise({}) = {}", p, n);
+            max = max.max(n.abs());
+            // The lattice repeats every NOISE_PERM_SIZE cells.
+            let shifted = p + Vector3f::from([NOISE_PERM_SIZE as Float, 0., 0.]);
+            assert!((noise(shifted) - n).abs() < 1e-3);
+            // Small steps make small changes.
+            let step = p + Vector3f::from([1e-3, 1e-3, 1e-3]);
+            assert!((noise(step) - n).abs() < 1e-2);
+        }
+        assert!(max > 0.3, "noise is flat, max {}", max);
+    }
+
+    #[test]
+    fn octaves_limited_by_differentials() {
+        let p = Point3f::from([0.3, 0.6, 0.9]);
+        let zero = Vector3f::from([0., 0., 0.]);
+        // Differentials larger than the lattice spacing leave nothing but the average.
+        let big = Vector3f::from([4., 0., 0.]);
+        assert_eq!(fbm(p, big, big, 0.5, 8), 0.);
+        // As in the C++ implementation, the partial octave's average is counted along with the
+        // clamped octaves.
+        assert_eq!(turbulence(p, big, zero, 0.5, 2), 0.2 + 0.2 + 0.5 * 0.2);
+        assert_eq!(fbm(p, zero, zero, 0.5, 1), noise(p));
+    }
+}
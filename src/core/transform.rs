@@ -13,17 +13,28 @@
 // limitations under the License.
 
 //! Linear equation helpers.  Contains a [Matrix4x4] type, [Transform] type (which stores a
-//! [Matrix4x4] and its inverse), and helper for solving 2x2 linear systems.
+//! [Matrix4x4] and its inverse), a [TransformCache] for sharing identical transforms, and helper
+//! for solving 2x2 linear systems.
 //!
 //! [Matrix4x4]: crate::core::transform::Matrix4x4
 //! [Transform]: crate::core::transform::Transform
-use std::{fmt, ops::Mul};
+//! [TransformCache]: crate::core::transform::TransformCache
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{Index, Mul},
+    sync::Arc,
+};
 
 use log::error;
 
 use crate::{
-    core::geometry::{cross, Vector3f},
-    float, Degree, Float,
+    core::{
+        geometry::{cross, Bounds3f, Normal3f, Point3f, Ray, RayDifferential, Vector3f},
+        interaction::{Interaction, Shading, SurfaceInteraction},
+        quaternion::{dot_quaternion, slerp, Quaternion},
+    },
+    gamma, lerp, Degree, Float,
 };
 
 /// Solve a 2x2 linear system in the form Ax = B.  For parameters `a` and `b`, the solution to `x`
@@ -89,6 +100,13 @@ impl From<[Float; 16]> for Matrix4x4 {
     }
 }
 
+impl Index<usize> for Matrix4x4 {
+    type Output = [Float; 4];
+    fn index(&self, row: usize) -> &[Float; 4] {
+        &self.m[row]
+    }
+}
+
 impl Matrix4x4 {
     /// Create a `Matrix4x4` containing the identity, all zeros with ones along the diagonal.
     pub fn identity() -> Matrix4x4 {
@@ -190,13 +208,7 @@ impl Matrix4x4 {
             ipiv[icol] += 1;
             // Swap rows _irow_ and _icol_ for pivot
             if irow != icol {
-                // Can't figure out how to make swap work here.
-                #[allow(clippy::manual_swap)]
-                for k in 0..4 {
-                    let tmp = minv[irow][k];
-                    minv[irow][k] = minv[icol][k];
-                    minv[icol][k] = tmp;
-                }
+                minv.swap(irow, icol);
             }
             indxr[i] = irow;
             indxc[i] = icol;
@@ -207,8 +219,8 @@ impl Matrix4x4 {
             // Set $m[icol][icol]$ to one by scaling row _icol_ appropriately
             let pivinv: Float = minv[icol][icol].recip();
             minv[icol][icol] = 1.;
-            for j in 0..4 {
-                minv[icol][j] *= pivinv;
+            for v in minv[icol].iter_mut() {
+                *v *= pivinv;
             }
 
             // Subtract this row from others to zero out their columns
@@ -216,8 +228,9 @@ impl Matrix4x4 {
                 if j != icol {
                     let save = minv[j][icol];
                     minv[j][icol] = 0.;
-                    for k in 0..4 {
-                        minv[j][k] -= minv[icol][k] * save;
+                    let row = minv[icol];
+                    for (v, r) in minv[j].iter_mut().zip(row.iter()) {
+                        *v -= r * save;
                     }
                 }
             }
@@ -289,7 +302,7 @@ impl PartialEq for Matrix4x4 {
         for i in 0..4 {
             for j in 0..4 {
                 let d = (l[i][j] - r[i][j]).abs();
-                if d > float::EPSILON {
+                if d > Float::EPSILON {
                     return false;
                 }
             }
@@ -306,6 +319,12 @@ pub struct Transform {
 }
 
 impl Transform {
+    /// Creates a `Transform` from a matrix and its precomputed inverse.  The caller is responsible
+    /// for ensuring `m_inv` is the inverse of `m`.
+    pub fn new(m: Matrix4x4, m_inv: Matrix4x4) -> Transform {
+        Transform { m, m_inv }
+    }
+
     /// Returns a new `Transform` set to the identity matrix.
     ///
     /// # Examples
@@ -598,6 +617,296 @@ impl Transform {
     pub fn matrix_inverse(self) -> Matrix4x4 {
         self.m_inv
     }
+
+    /// Creates a perspective projection `Transform` with a field of view of `fov` degrees.  Points
+    /// at depth `n` map to z = 0 and points at depth `f` map to z = 1.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point3f, transform::Transform};
+    ///
+    /// let t = Transform::perspective(90., 1., 100.);
+    /// let p = t.transform_point(Point3f::from([1., 1., 1.]));
+    /// assert!((p.x - 1.).abs() < 1e-5 && (p.y - 1.).abs() < 1e-5 && p.z.abs() < 1e-5);
+    /// ```
+    pub fn perspective(fov: Float, n: Float, f: Float) -> Transform {
+        // Perform projective divide for perspective projection
+        let persp = Matrix4x4::new(
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., f / (f - n), -f * n / (f - n)],
+            [0., 0., 1., 0.],
+        );
+
+        // Scale canonical perspective view to specified field of view
+        let inv_tan_ang = 1. / (fov.to_radians() / 2.).tan();
+        Transform::scale(inv_tan_ang, inv_tan_ang, 1.) * Transform::from(persp)
+    }
+
+    /// Creates an orthographic projection `Transform` that maps points with depth between
+    /// `z_near` and `z_far` to z between 0 and 1.
+    pub fn orthographic(z_near: Float, z_far: Float) -> Transform {
+        Transform::scale(1., 1., 1. / (z_far - z_near)) * Transform::translate([0., 0., -z_near])
+    }
+
+    /// Returns true if this `Transform` is the identity transform.
+    pub fn is_identity(&self) -> bool {
+        self.m == Matrix4x4::identity()
+    }
+
+    /// Returns true if this `Transform` changes the length of any of the coordinate axes.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::transform::Transform;
+    ///
+    /// assert!(!Transform::translate([1., 2., 3.]).has_scale());
+    /// assert!(Transform::scale(1., 2., 1.).has_scale());
+    /// ```
+    pub fn has_scale(&self) -> bool {
+        let not_one = |x: Float| !(0.999..=1.001).contains(&x);
+        let la2 = self
+            .transform_vector(Vector3f::from([1., 0., 0.]))
+            .length_squared();
+        let lb2 = self
+            .transform_vector(Vector3f::from([0., 1., 0.]))
+            .length_squared();
+        let lc2 = self
+            .transform_vector(Vector3f::from([0., 0., 1.]))
+            .length_squared();
+        not_one(la2) || not_one(lb2) || not_one(lc2)
+    }
+
+    /// Returns true if applying this `Transform` changes a left-handed coordinate system into a
+    /// right-handed one, or vice versa.
+    pub fn swaps_handedness(&self) -> bool {
+        let m = &self.m.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        det < 0.
+    }
+
+    /// Apply this `Transform` to the point `p`, including the homogeneous divide.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point3f, transform::Transform};
+    ///
+    /// let t = Transform::translate([1., 2., 3.]);
+    /// assert_eq!(
+    ///     t.transform_point(Point3f::from([1., 1., 1.])),
+    ///     Point3f::from([2., 3., 4.])
+    /// );
+    /// ```
+    pub fn transform_point(&self, p: Point3f) -> Point3f {
+        let m = &self.m.m;
+        let xp = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let yp = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let zp = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let wp = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        debug_assert!(wp != 0.);
+        if wp == 1. {
+            Point3f::from([xp, yp, zp])
+        } else {
+            Point3f::from([xp, yp, zp]) / wp
+        }
+    }
+
+    /// Apply this `Transform` to the point `p`, also returning a conservative bound on the absolute
+    /// floating-point error introduced by the transformation.  Projective transforms aren't
+    /// supported.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Point3f, transform::Transform};
+    ///
+    /// let t = Transform::translate([1., 2., 3.]);
+    /// let p = Point3f::from([1., 1., 1.]);
+    /// let (pt, p_error) = t.transform_point_with_error(p);
+    /// assert_eq!(pt, t.transform_point(p));
+    /// assert!(p_error.x > 0. && p_error.x < 1e-5);
+    /// ```
+    pub fn transform_point_with_error(&self, p: Point3f) -> (Point3f, Vector3f) {
+        let m = &self.m.m;
+        // Compute absolute error for transformed point
+        let err = |r: usize| {
+            (m[r][0] * p.x).abs() + (m[r][1] * p.y).abs() + (m[r][2] * p.z).abs() + m[r][3].abs()
+        };
+        let p_error = Vector3f::from([err(0), err(1), err(2)]) * gamma(3);
+        (self.transform_point(p), p_error)
+    }
+
+    /// Apply this `Transform` to the point `p` that already carries the absolute error `p_error`,
+    /// returning the transformed point and a bound on its accumulated error.  Projective
+    /// transforms aren't supported.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::{Point3f, Vector3f},
+    ///     transform::Transform,
+    /// };
+    ///
+    /// let t = Transform::scale(2., 2., 2.);
+    /// let p = Point3f::from([1., 1., 1.]);
+    /// let (_, fresh) = t.transform_point_with_error(p);
+    /// let (pt, p_error) = t.transform_point_with_abs_error(p, Vector3f::from([0.1, 0.1, 0.1]));
+    /// assert_eq!(pt, Point3f::from([2., 2., 2.]));
+    /// // The incoming error is scaled by the transform, plus the error of the transform itself.
+    /// assert!(p_error.x > 0.2 && p_error.x > fresh.x);
+    /// ```
+    pub fn transform_point_with_abs_error(
+        &self,
+        p: Point3f,
+        p_error: Vector3f,
+    ) -> (Point3f, Vector3f) {
+        let m = &self.m.m;
+        let err = |r: usize| {
+            (gamma(3) + 1.)
+                * (m[r][0].abs() * p_error.x
+                    + m[r][1].abs() * p_error.y
+                    + m[r][2].abs() * p_error.z)
+                + gamma(3)
+                    * ((m[r][0] * p.x).abs()
+                        + (m[r][1] * p.y).abs()
+                        + (m[r][2] * p.z).abs()
+                        + m[r][3].abs())
+        };
+        (
+            self.transform_point(p),
+            Vector3f::from([err(0), err(1), err(2)]),
+        )
+    }
+
+    /// Apply this `Transform` to the vector `v`.  Vectors are unaffected by translation.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Vector3f, transform::Transform};
+    ///
+    /// let t = Transform::translate([1., 2., 3.]) * Transform::scale(2., 2., 2.);
+    /// assert_eq!(
+    ///     t.transform_vector(Vector3f::from([1., 1., 1.])),
+    ///     Vector3f::from([2., 2., 2.])
+    /// );
+    /// ```
+    pub fn transform_vector(&self, v: Vector3f) -> Vector3f {
+        let m = &self.m.m;
+        Vector3f::from([
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        ])
+    }
+
+    /// Apply this `Transform` to the surface normal `n`.  Normals are transformed by the inverse
+    /// transpose so they remain perpendicular to the transformed surface.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Normal3f, transform::Transform};
+    ///
+    /// let t = Transform::scale(1., 2., 4.);
+    /// assert_eq!(
+    ///     t.transform_normal(Normal3f::from([0., 0., 1.])),
+    ///     Normal3f::from([0., 0., 0.25])
+    /// );
+    /// ```
+    pub fn transform_normal(&self, n: Normal3f) -> Normal3f {
+        let m_inv = &self.m_inv.m;
+        Normal3f::from([
+            m_inv[0][0] * n.x + m_inv[1][0] * n.y + m_inv[2][0] * n.z,
+            m_inv[0][1] * n.x + m_inv[1][1] * n.y + m_inv[2][1] * n.z,
+            m_inv[0][2] * n.x + m_inv[1][2] * n.y + m_inv[2][2] * n.z,
+        ])
+    }
+
+    /// Apply this `Transform` to the origin and direction of `r`.
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        Ray {
+            o: self.transform_point(r.o),
+            d: self.transform_vector(r.d),
+            t_max: r.t_max.clone(),
+            time: r.time,
+            medium: r.medium.clone(),
+        }
+    }
+
+    /// Apply this `Transform` to the main and offset rays of `r`.
+    pub fn transform_ray_differential(&self, r: &RayDifferential) -> RayDifferential {
+        RayDifferential {
+            ray: self.transform_ray(&r.ray),
+            has_differentials: r.has_differentials,
+            rx_origin: self.transform_point(r.rx_origin),
+            ry_origin: self.transform_point(r.ry_origin),
+            rx_direction: self.transform_vector(r.rx_direction),
+            ry_direction: self.transform_vector(r.ry_direction),
+        }
+    }
+
+    /// Returns a bounding box that encloses `b` after it has been transformed.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{geometry::Bounds3f, transform::Transform};
+    ///
+    /// let b = Bounds3f::from([[-1., -1., -1.], [1., 1., 1.]]);
+    /// let t = Transform::translate([1., 2., 3.]) * Transform::scale(2., 2., 2.);
+    /// assert_eq!(
+    ///     t.transform_bounds(&b),
+    ///     Bounds3f::from([[-1., 0., 1.], [3., 4., 5.]])
+    /// );
+    /// ```
+    pub fn transform_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        let mut ret = Bounds3f::empty();
+        for i in 0..8 {
+            let corner = Point3f::from([
+                if i & 1 == 0 { b.p_min.x } else { b.p_max.x },
+                if i & 2 == 0 { b.p_min.y } else { b.p_max.y },
+                if i & 4 == 0 { b.p_min.z } else { b.p_max.z },
+            ]);
+            ret = Bounds3f::union_point(&ret, self.transform_point(corner));
+        }
+        ret
+    }
+
+    /// Apply this `Transform` to all the geometry of `si`.  The normals are renormalized and the
+    /// shading normal is flipped, if necessary, to lie in the same hemisphere as the geometric
+    /// normal.
+    pub fn transform_surface_interaction(&self, si: SurfaceInteraction) -> SurfaceInteraction {
+        let n = self.transform_normal(si.n).normalize();
+        let shading_n = self
+            .transform_normal(si.shading.n)
+            .normalize()
+            .face_forward(Vector3f::from(n));
+        let (p, p_error) = self.transform_point_with_abs_error(si.p, si.p_error);
+        SurfaceInteraction {
+            it: Interaction {
+                p,
+                time: si.time,
+                p_error,
+                wo: self.transform_vector(si.wo).normalize(),
+                n,
+                medium_interface: si.it.medium_interface,
+            },
+            uv: si.uv,
+            dpdu: self.transform_vector(si.dpdu),
+            dpdv: self.transform_vector(si.dpdv),
+            dndu: self.transform_normal(si.dndu),
+            dndv: self.transform_normal(si.dndv),
+            shading: Shading {
+                n: shading_n,
+                dpdu: self.transform_vector(si.shading.dpdu),
+                dpdv: self.transform_vector(si.shading.dpdv),
+                dndu: self.transform_normal(si.shading.dndu),
+                dndv: self.transform_normal(si.shading.dndv),
+            },
+            material: si.material,
+            area_light: si.area_light,
+            bsdf: si.bsdf,
+        }
+    }
 }
 
 impl From<Matrix4x4> for Transform {
@@ -617,19 +926,360 @@ impl From<[Float; 16]> for Transform {
 
 impl Mul<Transform> for Transform {
     type Output = Transform;
+
+    /// Compose two transforms, the result applies `rhs` first and then `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::transform::{Matrix4x4, Transform};
+    ///
+    /// let t = Transform::translate([1., 2., 3.]) * Transform::scale(2., 2., 2.);
+    /// assert_eq!(t.matrix() * t.matrix_inverse(), Matrix4x4::identity());
+    /// ```
     fn mul(self, rhs: Transform) -> Transform {
+        // (AB)^-1 = B^-1 A^-1
         Transform {
             m: self.m * rhs.m,
-            m_inv: self.m_inv * rhs.m_inv,
+            m_inv: rhs.m_inv * self.m_inv,
         }
     }
 }
-impl<'a, 'b> Mul<&'b mut Transform> for &'a mut Transform {
+impl Mul<&mut Transform> for &mut Transform {
     type Output = Transform;
-    fn mul(self, rhs: &'b mut Transform) -> Transform {
-        Transform {
-            m: self.m * rhs.m,
-            m_inv: self.m_inv * rhs.m_inv,
+    fn mul(self, rhs: &mut Transform) -> Transform {
+        *self * *rhs
+    }
+}
+
+/// `AnimatedTransform` interpolates between two `Transform`s over a span of time, allowing for
+/// moving cameras and objects.  The transforms are decomposed into translation, rotation and
+/// scale components, which are interpolated independently.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedTransform {
+    start_transform: Transform,
+    end_transform: Transform,
+    start_time: Float,
+    end_time: Float,
+    actually_animated: bool,
+    t: [Vector3f; 2],
+    r: [Quaternion; 2],
+    s: [Matrix4x4; 2],
+}
+
+impl AnimatedTransform {
+    /// Create an `AnimatedTransform` that is equal to `start_transform` at `start_time` and
+    /// `end_transform` at `end_time`.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::Point3f,
+    ///     transform::{AnimatedTransform, Transform},
+    /// };
+    ///
+    /// let at = AnimatedTransform::new(
+    ///     Transform::translate([0., 0., 0.]),
+    ///     0.,
+    ///     Transform::translate([2., 0., 0.]),
+    ///     1.,
+    /// );
+    /// assert!(at.is_animated());
+    /// let p = at.transform_point(0.5, Point3f::from([0., 0., 0.]));
+    /// assert!((p.x - 1.).abs() < 1e-5);
+    /// ```
+    pub fn new(
+        start_transform: Transform,
+        start_time: Float,
+        end_transform: Transform,
+        end_time: Float,
+    ) -> AnimatedTransform {
+        let actually_animated = start_transform != end_transform;
+        let mut at = AnimatedTransform {
+            start_transform,
+            end_transform,
+            start_time,
+            end_time,
+            actually_animated,
+            t: Default::default(),
+            r: Default::default(),
+            s: Default::default(),
+        };
+        if !actually_animated {
+            return at;
+        }
+        let (t0, r0, s0) = decompose(&start_transform.m);
+        let (t1, mut r1, s1) = decompose(&end_transform.m);
+        // Flip R[1] if needed to select shortest path
+        if dot_quaternion(r0, r1) < 0. {
+            r1 = -r1;
+        }
+        at.t = [t0, t1];
+        at.r = [r0, r1];
+        at.s = [s0, s1];
+        at
+    }
+
+    /// Returns true if the start and end transforms differ.
+    pub fn is_animated(&self) -> bool {
+        self.actually_animated
+    }
+
+    /// Returns the start time of the animation.
+    pub fn start_time(&self) -> Float {
+        self.start_time
+    }
+
+    /// Returns the end time of the animation.
+    pub fn end_time(&self) -> Float {
+        self.end_time
+    }
+
+    /// Computes the `Transform` in effect at `time`.  Times outside of the animation's range are
+    /// clamped to the start or end transform.
+    pub fn interpolate(&self, time: Float) -> Transform {
+        // Handle boundary conditions for matrix interpolation
+        if !self.actually_animated || time <= self.start_time {
+            return self.start_transform;
+        }
+        if time >= self.end_time {
+            return self.end_transform;
+        }
+        let dt = (time - self.start_time) / (self.end_time - self.start_time);
+        // Interpolate translation at _dt_
+        let trans = self.t[0] * (1. - dt) + self.t[1] * dt;
+
+        // Interpolate rotation at _dt_
+        let rotate = slerp(dt, self.r[0], self.r[1]);
+
+        // Interpolate scale at _dt_
+        let mut scale = Matrix4x4::default();
+        for i in 0..3 {
+            for j in 0..3 {
+                scale.m[i][j] = lerp(dt, self.s[0].m[i][j], self.s[1].m[i][j]);
+            }
+        }
+        scale.m[3][3] = 1.;
+
+        // Compute interpolated matrix as product of interpolated components
+        Transform::translate(trans) * rotate.to_transform() * Transform::from(scale)
+    }
+
+    /// Apply the `Transform` in effect at `time` to the point `p`.
+    pub fn transform_point(&self, time: Float, p: Point3f) -> Point3f {
+        if !self.actually_animated || time <= self.start_time {
+            return self.start_transform.transform_point(p);
+        }
+        if time >= self.end_time {
+            return self.end_transform.transform_point(p);
+        }
+        self.interpolate(time).transform_point(p)
+    }
+
+    /// Apply the `Transform` in effect at `time` to the vector `v`.
+    pub fn transform_vector(&self, time: Float, v: Vector3f) -> Vector3f {
+        if !self.actually_animated || time <= self.start_time {
+            return self.start_transform.transform_vector(v);
+        }
+        if time >= self.end_time {
+            return self.end_transform.transform_vector(v);
+        }
+        self.interpolate(time).transform_vector(v)
+    }
+
+    /// Returns a bounding box enclosing `b` as it's moved by this transform over the whole
+    /// animation.  Rotations sweep the box along curves, these are bounded by sampling the
+    /// transform at many times in between the start and end.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::core::{
+    ///     geometry::Bounds3f,
+    ///     transform::{AnimatedTransform, Transform},
+    /// };
+    ///
+    /// let at = AnimatedTransform::new(
+    ///     Transform::identity(),
+    ///     0.,
+    ///     Transform::translate([2., 0., 0.]),
+    ///     1.,
+    /// );
+    /// let b = Bounds3f::from([[-1., -1., -1.], [1., 1., 1.]]);
+    /// assert_eq!(
+    ///     at.motion_bounds(&b),
+    ///     Bounds3f::from([[-1., -1., -1.], [3., 1., 1.]])
+    /// );
+    /// ```
+    pub fn motion_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        if !self.actually_animated {
+            return self.start_transform.transform_bounds(b);
+        }
+        const STEPS: usize = 64;
+        let mut bounds = Bounds3f::union(
+            &self.start_transform.transform_bounds(b),
+            &self.end_transform.transform_bounds(b),
+        );
+        for i in 1..STEPS {
+            let t = lerp(i as Float / STEPS as Float, self.start_time, self.end_time);
+            bounds = Bounds3f::union(&bounds, &self.interpolate(t).transform_bounds(b));
+        }
+        bounds
+    }
+
+    /// Apply the `Transform` in effect at `r.time` to the ray `r`.
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        if !self.actually_animated || r.time <= self.start_time {
+            return self.start_transform.transform_ray(r);
         }
+        if r.time >= self.end_time {
+            return self.end_transform.transform_ray(r);
+        }
+        self.interpolate(r.time).transform_ray(r)
+    }
+
+    /// Apply the `Transform` in effect at `r.time` to the ray differential `r`.
+    pub fn transform_ray_differential(&self, r: &RayDifferential) -> RayDifferential {
+        if !self.actually_animated || r.time <= self.start_time {
+            return self.start_transform.transform_ray_differential(r);
+        }
+        if r.time >= self.end_time {
+            return self.end_transform.transform_ray_differential(r);
+        }
+        self.interpolate(r.time).transform_ray_differential(r)
+    }
+}
+
+impl From<Transform> for AnimatedTransform {
+    /// Creates an `AnimatedTransform` that is always `t`.
+    fn from(t: Transform) -> AnimatedTransform {
+        AnimatedTransform::new(t, 0., t, 1.)
+    }
+}
+
+/// TransformCache interns [Transform]s by their matrix, so scenes that use the same matrix many
+/// times share one `Transform` and only compute its inverse once.
+///
+/// Matrices are compared bit for bit, so values that compare equal as floats but have different
+/// representations, like `0.` and `-0.`, are cached separately.
+#[derive(Debug, Default)]
+pub struct TransformCache {
+    transforms: HashMap<[u64; 16], Arc<Transform>>,
+}
+
+impl TransformCache {
+    /// Returns the cached `Transform` for `m`, creating and caching it if this is the first time
+    /// `m` has been seen.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use pbrt::core::transform::{Matrix4x4, TransformCache};
+    ///
+    /// let mut cache = TransformCache::default();
+    /// let m = Matrix4x4::new(
+    ///     [2., 0., 0., 1.],
+    ///     [0., 2., 0., 2.],
+    ///     [0., 0., 2., 3.],
+    ///     [0., 0., 0., 1.],
+    /// );
+    /// let t1 = cache.lookup(m);
+    /// let t2 = cache.lookup(m);
+    /// assert!(Arc::ptr_eq(&t1, &t2));
+    /// assert_eq!(t1.matrix_inverse(), m.inverse());
+    /// assert!(!Arc::ptr_eq(&t1, &cache.lookup(Matrix4x4::identity())));
+    /// assert_eq!(cache.len(), 2);
+    /// ```
+    pub fn lookup(&mut self, m: Matrix4x4) -> Arc<Transform> {
+        Arc::clone(
+            self.transforms
+                .entry(TransformCache::key(&m))
+                .or_insert_with(|| Arc::new(Transform::from(m))),
+        )
+    }
+
+    /// Like [lookup], but if `t`'s matrix isn't cached yet `t` itself is cached, so its
+    /// existing inverse is used.
+    ///
+    /// [lookup]: TransformCache::lookup
+    pub fn lookup_transform(&mut self, t: &Transform) -> Arc<Transform> {
+        Arc::clone(
+            self.transforms
+                .entry(TransformCache::key(&t.m))
+                .or_insert_with(|| Arc::new(*t)),
+        )
     }
+
+    // to_bits already returns u64 when built with "float-as-double".
+    #[cfg_attr(feature = "float-as-double", allow(clippy::unnecessary_cast))]
+    fn key(m: &Matrix4x4) -> [u64; 16] {
+        let mut key = [0; 16];
+        for i in 0..4 {
+            for j in 0..4 {
+                key[i * 4 + j] = m.m[i][j].to_bits() as u64;
+            }
+        }
+        key
+    }
+
+    /// Returns the number of distinct transforms in the cache.
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Returns true if nothing has been cached.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Removes all transforms from the cache.  `Arc`s previously returned by [lookup] stay valid.
+    ///
+    /// [lookup]: TransformCache::lookup
+    pub fn clear(&mut self) {
+        self.transforms.clear();
+    }
+}
+
+/// Splits `m` into a translation, rotation and scale such that `m = T * R * S`.
+fn decompose(m: &Matrix4x4) -> (Vector3f, Quaternion, Matrix4x4) {
+    // Extract translation _T_ from transformation matrix
+    let t = Vector3f::from([m.m[0][3], m.m[1][3], m.m[2][3]]);
+
+    // Compute new transformation matrix _M_ without translation
+    let mut mm = *m;
+    for i in 0..3 {
+        mm.m[i][3] = 0.;
+        mm.m[3][i] = 0.;
+    }
+    mm.m[3][3] = 1.;
+
+    // Extract rotation _R_ from transformation matrix using polar decomposition.
+    let mut r = mm;
+    for _ in 0..100 {
+        // Compute next matrix _rnext_ in series
+        let r_it = r.transpose().inverse();
+        let mut rnext = Matrix4x4::default();
+        for i in 0..4 {
+            for j in 0..4 {
+                rnext.m[i][j] = 0.5 * (r.m[i][j] + r_it.m[i][j]);
+            }
+        }
+
+        // Compute norm of difference between _r_ and _rnext_
+        let mut norm: Float = 0.;
+        for i in 0..3 {
+            let n = (r.m[i][0] - rnext.m[i][0]).abs()
+                + (r.m[i][1] - rnext.m[i][1]).abs()
+                + (r.m[i][2] - rnext.m[i][2]).abs();
+            norm = norm.max(n);
+        }
+        r = rnext;
+        if norm < 0.0001 {
+            break;
+        }
+    }
+    let r_quat = Quaternion::from(Transform::from(r));
+
+    // Compute scale _S_ using rotation and original matrix
+    let s = r.inverse() * mm;
+    (t, r_quat, s)
 }
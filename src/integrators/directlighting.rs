@@ -0,0 +1,328 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Integrator] that only accounts for light arriving directly from light sources, plus perfect
+//! specular reflection and transmission.
+//!
+//! [Integrator]: crate::core::integrator::Integrator
+use std::sync::Arc;
+
+use log::error;
+
+use crate::core::{
+    camera::Camera,
+    geometry::RayDifferential,
+    integrator::{
+        uniform_sample_all_lights, uniform_sample_one_light, Integrator, SamplerIntegrator,
+        SamplerIntegratorData,
+    },
+    material::TransportMode,
+    paramset::ParamSet,
+    sampler::Sampler,
+    scene::Scene,
+    spectrum::Spectrum,
+};
+
+/// LightStrategy selects how [DirectLightingIntegrator] samples the lights in the scene.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightStrategy {
+    /// Take the number of samples each light asks for from every light.
+    UniformSampleAll,
+    /// Take a single sample from one randomly chosen light.
+    UniformSampleOne,
+}
+
+/// DirectLightingIntegrator computes the radiance arriving along camera rays from light that
+/// reflected off a single surface on its way from a light source.  Both the lights and the BSDFs
+/// are sampled and the results combined with multiple importance sampling.
+#[derive(Debug)]
+pub struct DirectLightingIntegrator {
+    data: SamplerIntegratorData,
+    strategy: LightStrategy,
+    max_depth: usize,
+    n_light_samples: Vec<usize>,
+}
+
+impl DirectLightingIntegrator {
+    /// Create a new `DirectLightingIntegrator` sampling lights with `strategy` and following
+    /// specular bounces up to `max_depth` deep.
+    pub fn new(
+        strategy: LightStrategy,
+        max_depth: usize,
+        data: SamplerIntegratorData,
+    ) -> DirectLightingIntegrator {
+        DirectLightingIntegrator {
+            data,
+            strategy,
+            max_depth,
+            n_light_samples: Vec::new(),
+        }
+    }
+
+    /// Returns how lights are sampled.
+    pub fn strategy(&self) -> LightStrategy {
+        self.strategy
+    }
+
+    /// Returns the maximum number of specular bounces followed.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn preprocess(&mut self, scene: &Scene, sampler: &mut dyn Sampler) {
+        if self.strategy == LightStrategy::UniformSampleAll {
+            // Compute number of samples to use for each light
+            self.n_light_samples = scene
+                .lights
+                .iter()
+                .map(|l| sampler.round_count(l.n_samples()))
+                .collect();
+
+            // Request samples for sampling all lights
+            for _ in 0..self.max_depth {
+                for &n in &self.n_light_samples {
+                    sampler.request_2d_array(n);
+                    sampler.request_2d_array(n);
+                }
+            }
+        }
+    }
+
+    fn render(&mut self, scene: &Scene) {
+        self.render_samples(scene)
+    }
+}
+
+impl SamplerIntegrator for DirectLightingIntegrator {
+    fn data(&self) -> &SamplerIntegratorData {
+        &self.data
+    }
+
+    fn li(
+        &self,
+        ray: &RayDifferential,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+    ) -> Spectrum {
+        let mut l = Spectrum::new(0.);
+        // Find closest ray intersection or return background radiance
+        let mut isect = match scene.intersect(ray) {
+            Some(isect) => isect,
+            None => {
+                for light in &scene.lights {
+                    l += light.le(ray);
+                }
+                return l;
+            }
+        };
+
+        // Compute scattering functions for surface interaction
+        isect.compute_scattering_functions(ray, false, TransportMode::Radiance);
+        if isect.bsdf.is_none() {
+            let r = RayDifferential::from(isect.spawn_ray(&ray.d));
+            return self.li(&r, scene, sampler, depth);
+        }
+        // Compute emitted light if ray hit an area light source
+        l += isect.le(&isect.wo);
+
+        // Compute direct lighting for _DirectLightingIntegrator_ integrator
+        if !scene.lights.is_empty() {
+            l += match self.strategy {
+                LightStrategy::UniformSampleAll => {
+                    uniform_sample_all_lights(&isect, scene, sampler, &self.n_light_samples, false)
+                }
+                LightStrategy::UniformSampleOne => {
+                    uniform_sample_one_light(&isect, scene, sampler, false, None)
+                }
+            };
+        }
+        if depth + 1 < self.max_depth {
+            // Trace rays for specular reflection and refraction
+            l += self.specular_reflect(ray, &isect, scene, sampler, depth);
+            l += self.specular_transmit(ray, &isect, scene, sampler, depth);
+        }
+        l
+    }
+}
+
+/// Creates a `DirectLightingIntegrator` from the given `params`.
+pub fn create_direct_lighting_integrator(
+    params: &ParamSet,
+    sampler: Box<dyn Sampler>,
+    camera: Arc<dyn Camera>,
+) -> DirectLightingIntegrator {
+    let max_depth = params.find_one_int("maxdepth", 5).max(0) as usize;
+    let st = params.find_one_string("strategy", "all");
+    let strategy = match st.as_str() {
+        "one" => LightStrategy::UniformSampleOne,
+        "all" => LightStrategy::UniformSampleAll,
+        _ => {
+            error!(
+                "Strategy \"{}\" for direct lighting unknown. Using \"all\".",
+                st
+            );
+            LightStrategy::UniformSampleAll
+        }
+    };
+    DirectLightingIntegrator::new(
+        strategy,
+        max_depth,
+        SamplerIntegratorData::new(camera, sampler, params),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{create_direct_lighting_integrator, DirectLightingIntegrator, LightStrategy};
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        cameras::perspective::PerspectiveCamera,
+        core::{
+            film::Film,
+            geometry::{Bounds2f, Point2i, Vector2f},
+            integrator::{Integrator, SamplerIntegratorData},
+            light::Light,
+            paramset::{testutils::make_string_param_set, ParamSet},
+            primitive::{GeometricPrimitive, Primitive},
+            sampler::Sampler,
+            scene::Scene,
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        filters::r#box::BoxFilter,
+        lights::{diffuse::DiffuseAreaLight, point::PointLight},
+        materials::matte::MatteMaterial,
+        samplers::{stratified::StratifiedSampler, zerotwosequence::ZeroTwoSequenceSampler},
+        shapes::sphere::Sphere,
+        textures::constant::ConstantTexture,
+    };
+
+    // A 16x16 film written to `filename`, and a camera looking down +z at it.
+    fn camera(filename: String) -> (Arc<Film>, Arc<PerspectiveCamera>) {
+        let film = Arc::new(Film::new(
+            Point2i::from([16, 16]),
+            Bounds2f::from([[0., 0.], [1., 1.]]),
+            Box::new(BoxFilter::new(Vector2f::from([0.5, 0.5]))),
+            35.,
+            filename,
+            1.,
+            crate::Float::INFINITY,
+        ));
+        let camera = Arc::new(PerspectiveCamera::new(
+            Transform::identity().into(),
+            Bounds2f::from([[-1., -1.], [1., 1.]]),
+            0.,
+            1.,
+            0.,
+            1e6,
+            45.,
+            Arc::clone(&film),
+            None,
+        ));
+        (film, camera)
+    }
+
+    fn render(params: &ParamSet) -> [crate::Float; 3] {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let (film, camera) = camera(dir.path().join("out.pfm").display().to_string());
+        let sampler = Box::new(StratifiedSampler::new(2, 2, true, 4));
+
+        // A white unit sphere in front of the camera, lit by a light at the camera.
+        let o2w = Transform::translate([0., 0., 5.]);
+        let sphere = Arc::new(Sphere::new(o2w, o2w.inverse(), false, 1., -1., 1., 360.));
+        let matte = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(1.))),
+            Arc::new(ConstantTexture::new(0.)),
+            None,
+        ));
+        let prims: Vec<Arc<dyn Primitive>> = vec![Arc::new(GeometricPrimitive::new(
+            sphere,
+            Some(matte),
+            None,
+            Default::default(),
+        ))];
+        let light: Arc<dyn Light> = Arc::new(PointLight::new(
+            &Transform::identity(),
+            Default::default(),
+            Spectrum::new(10.),
+        ));
+        let scene = Scene::new(
+            Arc::new(BVHAccel::new(prims, 1, SplitMethod::Sah)),
+            vec![light],
+        );
+
+        let mut integrator = create_direct_lighting_integrator(params, sampler, camera);
+        integrator.render(&scene);
+        assert!(dir.path().join("out.pfm").exists());
+        film.get_pixel_xyz(Point2i::from([8, 8]))
+    }
+
+    #[test]
+    fn lit_sphere_all() {
+        let xyz = render(&Default::default());
+        assert!(xyz[1] > 0., "center pixel is black: {:?}", xyz);
+    }
+
+    #[test]
+    fn lit_sphere_one() {
+        let xyz = render(&make_string_param_set("strategy", vec!["one".to_string()]));
+        assert!(xyz[1] > 0., "center pixel is black: {:?}", xyz);
+    }
+
+    #[test]
+    fn requests_light_sample_arrays() {
+        let (_, camera) = camera(String::new());
+        let t = Transform::identity();
+        let shape = Arc::new(Sphere::new(t, t, false, 1., -1., 1., 360.));
+        let lights: Vec<Arc<dyn Light>> = vec![
+            Arc::new(PointLight::new(&t, Default::default(), Spectrum::new(1.))),
+            Arc::new(DiffuseAreaLight::new(
+                &t,
+                Default::default(),
+                Spectrum::new(1.),
+                3,
+                shape,
+                false,
+            )),
+        ];
+        let scene = Scene::new(
+            Arc::new(BVHAccel::new(Vec::new(), 1, SplitMethod::Sah)),
+            lights,
+        );
+        let mut sampler = ZeroTwoSequenceSampler::new(4, 4);
+        let mut integrator = DirectLightingIntegrator::new(
+            LightStrategy::UniformSampleAll,
+            2,
+            SamplerIntegratorData::new(camera, sampler.clone_sampler(0), &Default::default()),
+        );
+        integrator.preprocess(&scene, &mut sampler);
+        // The area light's 3 samples are rounded up to a power of two for the (0,2)-sequence.
+        assert_eq!(integrator.n_light_samples, vec![1, 4]);
+
+        // Two arrays per light for each of the 2 bounces.
+        sampler.start_pixel(Point2i::from([0, 0]));
+        for _ in 0..2 {
+            for &n in &[1, 4] {
+                assert_eq!(sampler.get_2d_array(n).map(<[_]>::len), Some(n));
+                assert_eq!(sampler.get_2d_array(n).map(<[_]>::len), Some(n));
+            }
+        }
+        assert!(sampler.get_2d_array(1).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Integrator] implementing unidirectional path tracing.
+//!
+//! [Integrator]: crate::core::integrator::Integrator
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        camera::Camera,
+        geometry::{abs_dot, dot, RayDifferential, Vector3f},
+        integrator::{
+            uniform_sample_one_light, Integrator, SamplerIntegrator, SamplerIntegratorData,
+        },
+        lightdistrib::{create_light_sample_distribution, LightDistribution},
+        material::TransportMode,
+        paramset::ParamSet,
+        reflection::BxDFType,
+        sampler::Sampler,
+        scene::Scene,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+/// PathIntegrator computes the radiance arriving along camera rays by incrementally building
+/// paths that bounce through the scene.  At each vertex the direct lighting is estimated with
+/// multiple importance sampling, then the BSDF is sampled to extend the path.
+#[derive(Debug)]
+pub struct PathIntegrator {
+    data: SamplerIntegratorData,
+    max_depth: usize,
+    rr_threshold: Float,
+    light_sample_strategy: String,
+    light_distribution: Option<Box<dyn LightDistribution>>,
+}
+
+impl PathIntegrator {
+    /// Create a new `PathIntegrator` building paths up to `max_depth` bounces long.  Once the
+    /// path's throughput drops below `rr_threshold`, Russian roulette is used to terminate it.
+    /// The light sampled at each vertex is chosen with the [LightDistribution] named by
+    /// `light_sample_strategy`, see [create_light_sample_distribution].
+    pub fn new(
+        max_depth: usize,
+        rr_threshold: Float,
+        light_sample_strategy: &str,
+        data: SamplerIntegratorData,
+    ) -> PathIntegrator {
+        PathIntegrator {
+            data,
+            max_depth,
+            rr_threshold,
+            light_sample_strategy: light_sample_strategy.to_string(),
+            light_distribution: None,
+        }
+    }
+
+    /// Returns the maximum number of bounces in a path.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the throughput below which Russian roulette is applied.
+    pub fn rr_threshold(&self) -> Float {
+        self.rr_threshold
+    }
+
+    /// Returns the name of the strategy used to choose which light to sample.
+    pub fn light_sample_strategy(&self) -> &str {
+        &self.light_sample_strategy
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn preprocess(&mut self, scene: &Scene, _sampler: &mut dyn Sampler) {
+        self.light_distribution = Some(create_light_sample_distribution(
+            &self.light_sample_strategy,
+            scene,
+        ));
+    }
+
+    fn render(&mut self, scene: &Scene) {
+        self.render_samples(scene)
+    }
+}
+
+impl SamplerIntegrator for PathIntegrator {
+    fn data(&self) -> &SamplerIntegratorData {
+        &self.data
+    }
+
+    fn li(
+        &self,
+        r: &RayDifferential,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        _depth: usize,
+    ) -> Spectrum {
+        let mut l = Spectrum::new(0.);
+        let mut beta = Spectrum::new(1.);
+        let mut ray = r.clone();
+        let mut specular_bounce = false;
+        let mut bounces = 0;
+        // Added after book publication: etaScale tracks the accumulated effect of radiance
+        // scaling due to rays passing through refractive boundaries (see the derivation on p.
+        // 527 of the third edition). We track this value in order to remove it from beta when we
+        // apply Russian roulette; this is worthwhile, since it lets us sometimes avoid
+        // terminating refracted rays that are about to be refracted back out of a medium and thus
+        // have their beta value increased.
+        let mut eta_scale = 1.;
+
+        loop {
+            // Intersect _ray_ with scene and store intersection in _isect_
+            let isect = scene.intersect(&ray);
+
+            // Possibly add emitted light at intersection
+            if bounces == 0 || specular_bounce {
+                // Add emitted light at path vertex or from the environment
+                match &isect {
+                    Some(isect) => l += beta.clone() * isect.le(&-ray.d),
+                    None => {
+                        for light in &scene.infinite_lights {
+                            l += beta.clone() * light.le(&ray);
+                        }
+                    }
+                }
+            }
+
+            // Terminate path if ray escaped or _maxDepth_ was reached
+            let mut isect = match isect {
+                Some(isect) if bounces < self.max_depth => isect,
+                _ => break,
+            };
+
+            // Compute scattering functions and skip over medium boundaries
+            isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+            let bsdf = match &isect.bsdf {
+                Some(bsdf) => bsdf,
+                None => {
+                    ray = RayDifferential::from(isect.spawn_ray(&ray.d));
+                    continue;
+                }
+            };
+
+            // Sample illumination from lights to find path contribution.
+            // (But skip this for perfectly specular BSDFs.)
+            if bsdf.num_components(BxDFType::ALL & !BxDFType::SPECULAR) > 0 {
+                let distrib = self
+                    .light_distribution
+                    .as_ref()
+                    .map(|ld| ld.lookup(isect.p));
+                l += beta.clone()
+                    * uniform_sample_one_light(&isect, scene, sampler, false, distrib.as_deref());
+            }
+
+            // Sample BSDF to get new path direction
+            let wo = -ray.d;
+            let bs = bsdf.sample_f(&wo, sampler.get_2d(), BxDFType::ALL);
+            if bs.f.is_black() || bs.pdf == 0. {
+                break;
+            }
+            beta *= bs.f * (abs_dot(bs.wi, Vector3f::from(isect.shading.n)) / bs.pdf);
+            debug_assert!(
+                beta.max_component_value() >= 0.,
+                "negative throughput {}",
+                beta
+            );
+            specular_bounce = bs.sampled_type.contains(BxDFType::SPECULAR);
+            if bs
+                .sampled_type
+                .contains(BxDFType::SPECULAR | BxDFType::TRANSMISSION)
+            {
+                let eta = bsdf.eta;
+                // Update the term that tracks radiance scaling for refraction depending on
+                // whether the ray is entering or leaving the medium.
+                eta_scale *= if dot(wo, Vector3f::from(isect.n)) > 0. {
+                    eta * eta
+                } else {
+                    1. / (eta * eta)
+                };
+            }
+            ray = RayDifferential::from(isect.spawn_ray(&bs.wi));
+
+            // Possibly terminate the path with Russian roulette.
+            // Factor out radiance scaling due to refraction in rr_beta.
+            let rr_beta = beta.clone() * eta_scale;
+            if rr_beta.max_component_value() < self.rr_threshold && bounces > 3 {
+                let q = (1. - rr_beta.max_component_value()).max(0.05);
+                if sampler.get_1d() < q {
+                    break;
+                }
+                beta = beta / (1. - q);
+                debug_assert!(!beta.max_component_value().is_infinite());
+            }
+            bounces += 1;
+        }
+        l
+    }
+}
+
+/// Creates a `PathIntegrator` from the given `params`.
+pub fn create_path_integrator(
+    params: &ParamSet,
+    sampler: Box<dyn Sampler>,
+    camera: Arc<dyn Camera>,
+) -> PathIntegrator {
+    let max_depth = params.find_one_int("maxdepth", 5).max(0) as usize;
+    let rr_threshold = params.find_one_float("rrthreshold", 1.);
+    let light_strategy = params.find_one_string("lightsamplestrategy", "uniform");
+    PathIntegrator::new(
+        max_depth,
+        rr_threshold,
+        &light_strategy,
+        SamplerIntegratorData::new(camera, sampler, params),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::create_path_integrator;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        cameras::perspective::PerspectiveCamera,
+        core::{
+            film::Film,
+            geometry::{Bounds2f, Point2i, Vector2f},
+            integrator::Integrator,
+            light::Light,
+            paramset::{
+                testutils::{make_int_param_set, make_string_param_set},
+                ParamSet,
+            },
+            primitive::{GeometricPrimitive, Primitive},
+            scene::Scene,
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        filters::r#box::BoxFilter,
+        integrators::directlighting::create_direct_lighting_integrator,
+        lights::point::PointLight,
+        materials::matte::MatteMaterial,
+        samplers::stratified::StratifiedSampler,
+        shapes::sphere::Sphere,
+        textures::constant::ConstantTexture,
+        Float,
+    };
+
+    /// Radius of the spheres used as the walls of the box.  Large enough that the walls are
+    /// nearly flat, small enough to keep single precision intersections accurate.
+    const WALL_RADIUS: Float = 100.;
+
+    fn matte(rgb: [Float; 3]) -> Arc<MatteMaterial> {
+        Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::from_rgb(rgb))),
+            Arc::new(ConstantTexture::new(0.)),
+            None,
+        ))
+    }
+
+    /// Builds a Cornell box spanning [-1, 1] in x and y and [0, 4] in z, open towards the
+    /// camera at the origin, with red and green side walls and a light near the ceiling.
+    fn cornell_box() -> Scene {
+        let white = matte([0.75, 0.75, 0.75]);
+        let walls: Vec<([Float; 3], Arc<MatteMaterial>)> = vec![
+            ([-1. - WALL_RADIUS, 0., 0.], matte([0.75, 0.25, 0.25])),
+            ([1. + WALL_RADIUS, 0., 0.], matte([0.25, 0.75, 0.25])),
+            ([0., -1. - WALL_RADIUS, 0.], white.clone()),
+            ([0., 1. + WALL_RADIUS, 0.], white.clone()),
+            ([0., 0., 4. + WALL_RADIUS], white.clone()),
+        ];
+        let mut prims: Vec<Arc<dyn Primitive>> = walls
+            .into_iter()
+            .map(|(center, material)| {
+                let o2w = Transform::translate(center);
+                let p: Arc<dyn Primitive> = Arc::new(GeometricPrimitive::new(
+                    Arc::new(Sphere::new(
+                        o2w,
+                        o2w.inverse(),
+                        false,
+                        WALL_RADIUS,
+                        -WALL_RADIUS,
+                        WALL_RADIUS,
+                        360.,
+                    )),
+                    Some(material),
+                    None,
+                    Default::default(),
+                ));
+                p
+            })
+            .collect();
+        let o2w = Transform::translate([0.4, -0.6, 3.]);
+        prims.push(Arc::new(GeometricPrimitive::new(
+            Arc::new(Sphere::new(o2w, o2w.inverse(), false, 0.4, -0.4, 0.4, 360.)),
+            Some(white),
+            None,
+            Default::default(),
+        )));
+        let light: Arc<dyn Light> = Arc::new(PointLight::new(
+            &Transform::translate([0., 0.8, 2.5]),
+            Default::default(),
+            Spectrum::new(2.),
+        ));
+        Scene::new(
+            Arc::new(BVHAccel::new(prims, 4, SplitMethod::Sah)),
+            vec![light],
+        )
+    }
+
+    /// Renders `scene` with the integrator `create` makes and returns the average luminance of
+    /// the image.
+    fn render<I, F>(scene: &Scene, create: F) -> Float
+    where
+        I: Integrator,
+        F: FnOnce(Box<StratifiedSampler>, Arc<PerspectiveCamera>) -> I,
+    {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let resolution = 16;
+        let film = Arc::new(Film::new(
+            Point2i::from([resolution, resolution]),
+            Bounds2f::from([[0., 0.], [1., 1.]]),
+            Box::new(BoxFilter::new(Vector2f::from([0.5, 0.5]))),
+            35.,
+            dir.path().join("cornell.pfm").display().to_string(),
+            1.,
+            Float::INFINITY,
+        ));
+        let camera = Arc::new(PerspectiveCamera::new(
+            Transform::identity().into(),
+            Bounds2f::from([[-1., -1.], [1., 1.]]),
+            0.,
+            1.,
+            0.,
+            1e6,
+            40.,
+            Arc::clone(&film),
+            None,
+        ));
+        let sampler = Box::new(StratifiedSampler::new(2, 2, true, 16));
+        create(sampler, camera).render(scene);
+        assert!(dir.path().join("cornell.pfm").exists());
+
+        let mut sum = 0.;
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let xyz = film.get_pixel_xyz(Point2i::from([x, y]));
+                assert!(!xyz[1].is_nan(), "NaN at ({}, {})", x, y);
+                sum += xyz[1];
+            }
+        }
+        sum / (resolution * resolution) as Float
+    }
+
+    #[test]
+    fn cornell_box_smoke() {
+        let scene = cornell_box();
+        let params: ParamSet = make_int_param_set("maxdepth", vec![5]);
+        let path = render(&scene, |s, c| create_path_integrator(&params, s, c));
+        assert!(path > 0., "path traced image is black");
+
+        // Indirect lighting only adds light, so the path traced image should be brighter than
+        // one with direct lighting only.
+        let direct = render(&scene, |s, c| {
+            create_direct_lighting_integrator(&Default::default(), s, c)
+        });
+        assert!(
+            path > direct,
+            "path traced {} should be brighter than direct lighting {}",
+            path,
+            direct
+        );
+    }
+
+    #[test]
+    fn light_sample_strategy() {
+        let scene = cornell_box();
+        let sampler = || Box::new(StratifiedSampler::new(1, 1, false, 0));
+        let camera = || {
+            Arc::new(PerspectiveCamera::new(
+                Transform::identity().into(),
+                Bounds2f::from([[-1., -1.], [1., 1.]]),
+                0.,
+                1.,
+                0.,
+                1e6,
+                45.,
+                Arc::new(Film::new(
+                    Point2i::from([1, 1]),
+                    Bounds2f::from([[0., 0.], [1., 1.]]),
+                    Box::new(BoxFilter::new(Vector2f::from([0.5, 0.5]))),
+                    35.,
+                    String::new(),
+                    1.,
+                    Float::INFINITY,
+                )),
+                None,
+            ))
+        };
+        let path = create_path_integrator(&ParamSet::default(), sampler(), camera());
+        assert_eq!(path.light_sample_strategy(), "uniform");
+
+        for strategy in ["power", "spatial"] {
+            let params = make_string_param_set("lightsamplestrategy", vec![strategy.to_string()]);
+            assert_eq!(
+                create_path_integrator(&params, sampler(), camera()).light_sample_strategy(),
+                strategy
+            );
+            let l = render(&scene, |s, c| create_path_integrator(&params, s, c));
+            assert!(l > 0., "{} image is black", strategy);
+        }
+    }
+}
@@ -0,0 +1,415 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Integrator] implementing unidirectional path tracing with support for participating media.
+//!
+//! [Integrator]: crate::core::integrator::Integrator
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        camera::Camera,
+        geometry::{abs_dot, dot, RayDifferential, Vector3f},
+        integrator::{
+            uniform_sample_one_light, Integrator, SamplerIntegrator, SamplerIntegratorData,
+        },
+        material::TransportMode,
+        paramset::ParamSet,
+        reflection::BxDFType,
+        sampler::Sampler,
+        scene::Scene,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+/// VolPathIntegrator extends [PathIntegrator] to account for absorption, emission-free
+/// scattering and attenuation in participating media.  Each path segment samples the medium it
+/// travels through, and scattering events in media are treated as path vertices, with direct
+/// lighting estimated through the media between the vertex and the light.
+///
+/// [PathIntegrator]: crate::integrators::path::PathIntegrator
+#[derive(Debug)]
+pub struct VolPathIntegrator {
+    data: SamplerIntegratorData,
+    max_depth: usize,
+    rr_threshold: Float,
+}
+
+impl VolPathIntegrator {
+    /// Create a new `VolPathIntegrator` building paths up to `max_depth` bounces long.  Once the
+    /// path's throughput drops below `rr_threshold`, Russian roulette is used to terminate it.
+    pub fn new(
+        max_depth: usize,
+        rr_threshold: Float,
+        data: SamplerIntegratorData,
+    ) -> VolPathIntegrator {
+        VolPathIntegrator {
+            data,
+            max_depth,
+            rr_threshold,
+        }
+    }
+
+    /// Returns the maximum number of bounces in a path.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the throughput below which Russian roulette is applied.
+    pub fn rr_threshold(&self) -> Float {
+        self.rr_threshold
+    }
+}
+
+impl Integrator for VolPathIntegrator {
+    fn render(&mut self, scene: &Scene) {
+        self.render_samples(scene)
+    }
+}
+
+impl SamplerIntegrator for VolPathIntegrator {
+    fn data(&self) -> &SamplerIntegratorData {
+        &self.data
+    }
+
+    fn li(
+        &self,
+        r: &RayDifferential,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        _depth: usize,
+    ) -> Spectrum {
+        let mut l = Spectrum::new(0.);
+        let mut beta = Spectrum::new(1.);
+        let mut ray = r.clone();
+        let mut specular_bounce = false;
+        let mut bounces = 0;
+        // Added after book publication: etaScale tracks the accumulated effect of radiance
+        // scaling due to rays passing through refractive boundaries, see PathIntegrator::li.
+        let mut eta_scale = 1.;
+
+        loop {
+            // Intersect _ray_ with scene and store intersection in _isect_
+            let isect = scene.intersect(&ray);
+
+            // Sample the participating medium, if present
+            let mut mi = None;
+            if let Some(medium) = &ray.medium {
+                let (weight, sampled) = medium.sample(&ray, sampler);
+                beta *= weight;
+                mi = sampled;
+            }
+            if beta.is_black() {
+                break;
+            }
+
+            // Handle an interaction with a medium or a surface
+            if let Some(mi) = mi {
+                // Terminate path if _maxDepth_ was reached
+                if bounces >= self.max_depth {
+                    break;
+                }
+
+                // Handle scattering at point in medium for volumetric path tracer
+                l += beta.clone() * uniform_sample_one_light(&mi, scene, sampler, true, None);
+                let (_, wi) = mi.phase.sample_p(&-ray.d, sampler.get_2d());
+                ray = RayDifferential::from(mi.spawn_ray(&wi));
+                specular_bounce = false;
+            } else {
+                // Handle scattering at point on surface for volumetric path tracer
+
+                // Possibly add emitted light at intersection
+                if bounces == 0 || specular_bounce {
+                    // Add emitted light at path vertex or from the environment
+                    match &isect {
+                        Some(isect) => l += beta.clone() * isect.le(&-ray.d),
+                        None => {
+                            for light in &scene.infinite_lights {
+                                l += beta.clone() * light.le(&ray);
+                            }
+                        }
+                    }
+                }
+
+                // Terminate path if ray escaped or _maxDepth_ was reached
+                let mut isect = match isect {
+                    Some(isect) if bounces < self.max_depth => isect,
+                    _ => break,
+                };
+
+                // Compute scattering functions and skip over medium boundaries
+                isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+                let bsdf = match &isect.bsdf {
+                    Some(bsdf) => bsdf,
+                    None => {
+                        ray = RayDifferential::from(isect.spawn_ray(&ray.d));
+                        continue;
+                    }
+                };
+
+                // Sample illumination from lights to find attenuated path contribution.
+                // (But skip this for perfectly specular BSDFs.)
+                if bsdf.num_components(BxDFType::ALL & !BxDFType::SPECULAR) > 0 {
+                    l +=
+                        beta.clone() * uniform_sample_one_light(&isect, scene, sampler, true, None);
+                }
+
+                // Sample BSDF to get new path direction
+                let wo = -ray.d;
+                let bs = bsdf.sample_f(&wo, sampler.get_2d(), BxDFType::ALL);
+                if bs.f.is_black() || bs.pdf == 0. {
+                    break;
+                }
+                beta *= bs.f * (abs_dot(bs.wi, Vector3f::from(isect.shading.n)) / bs.pdf);
+                debug_assert!(
+                    beta.max_component_value() >= 0.,
+                    "negative throughput {}",
+                    beta
+                );
+                specular_bounce = bs.sampled_type.contains(BxDFType::SPECULAR);
+                if bs
+                    .sampled_type
+                    .contains(BxDFType::SPECULAR | BxDFType::TRANSMISSION)
+                {
+                    let eta = bsdf.eta;
+                    // Update the term that tracks radiance scaling for refraction depending on
+                    // whether the ray is entering or leaving the medium.
+                    eta_scale *= if dot(wo, Vector3f::from(isect.n)) > 0. {
+                        eta * eta
+                    } else {
+                        1. / (eta * eta)
+                    };
+                }
+                ray = RayDifferential::from(isect.spawn_ray(&bs.wi));
+            }
+
+            // Possibly terminate the path with Russian roulette.
+            // Factor out radiance scaling due to refraction in rr_beta.
+            let rr_beta = beta.clone() * eta_scale;
+            if rr_beta.max_component_value() < self.rr_threshold && bounces > 3 {
+                let q = (1. - rr_beta.max_component_value()).max(0.05);
+                if sampler.get_1d() < q {
+                    break;
+                }
+                beta = beta / (1. - q);
+                debug_assert!(!beta.max_component_value().is_infinite());
+            }
+            bounces += 1;
+        }
+        l
+    }
+}
+
+/// Creates a `VolPathIntegrator` from the given `params`.
+pub fn create_volpath_integrator(
+    params: &ParamSet,
+    sampler: Box<dyn Sampler>,
+    camera: Arc<dyn Camera>,
+) -> VolPathIntegrator {
+    let max_depth = params.find_one_int("maxdepth", 5).max(0) as usize;
+    let rr_threshold = params.find_one_float("rrthreshold", 1.);
+    VolPathIntegrator::new(
+        max_depth,
+        rr_threshold,
+        SamplerIntegratorData::new(camera, sampler, params),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::create_volpath_integrator;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        cameras::orthographic::OrthographicCamera,
+        core::{
+            film::Film,
+            geometry::{Bounds2f, Point2i, Point3f, Ray, RayDifferential, Vector2f, Vector3f},
+            integrator::SamplerIntegrator,
+            light::Light,
+            medium::{Medium, MediumInterface},
+            paramset::{testutils::make_int_param_set, ParamSet},
+            primitive::{GeometricPrimitive, Primitive},
+            scene::Scene,
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        filters::r#box::BoxFilter,
+        integrators::path::create_path_integrator,
+        lights::{diffuse::DiffuseAreaLight, point::PointLight},
+        materials::matte::MatteMaterial,
+        media::homogeneous::HomogeneousMedium,
+        samplers::stratified::StratifiedSampler,
+        shapes::sphere::Sphere,
+        textures::constant::ConstantTexture,
+        Float,
+    };
+
+    fn sphere(center: [Float; 3], radius: Float) -> Arc<Sphere> {
+        let o2w = Transform::translate(center);
+        Arc::new(Sphere::new(
+            o2w,
+            o2w.inverse(),
+            false,
+            radius,
+            -radius,
+            radius,
+            360.,
+        ))
+    }
+
+    /// Returns a sphere of radius 1 centered at (0, 0, 2) filled with `medium`.  It has no
+    /// material, so it only delimits the medium.
+    fn medium_sphere(medium: Arc<dyn Medium>) -> Arc<dyn Primitive> {
+        Arc::new(GeometricPrimitive::new(
+            sphere([0., 0., 2.], 1.),
+            None,
+            None,
+            MediumInterface {
+                inside: Some(medium),
+                outside: None,
+            },
+        ))
+    }
+
+    fn camera() -> Arc<OrthographicCamera> {
+        // An empty filename keeps the image from being written.
+        let film = Arc::new(Film::new(
+            Point2i::from([4, 4]),
+            Bounds2f::from([[0., 0.], [1., 1.]]),
+            Box::new(BoxFilter::new(Vector2f::from([0.5, 0.5]))),
+            35.,
+            String::new(),
+            1.,
+            Float::INFINITY,
+        ));
+        Arc::new(OrthographicCamera::new(
+            Transform::identity().into(),
+            Bounds2f::from([[-1., -1.], [1., 1.]]),
+            0.,
+            1.,
+            0.,
+            1e6,
+            film,
+            None,
+        ))
+    }
+
+    /// Returns a ray from the origin along +z, through the center of the medium sphere.
+    fn ray() -> RayDifferential {
+        RayDifferential::from(Ray::new(
+            Point3f::default(),
+            Vector3f::from([0., 0., 1.]),
+            Float::INFINITY,
+            0.,
+            None,
+        ))
+    }
+
+    #[test]
+    fn absorbing_medium_attenuates_light() {
+        // A light behind the medium sphere, which acts as a slab of thickness 2 along the ray.
+        let light_shape = sphere([0., 0., 10.], 1.);
+        let light = Arc::new(DiffuseAreaLight::new(
+            &Transform::identity(),
+            Default::default(),
+            Spectrum::new(1.),
+            1,
+            light_shape.clone(),
+            false,
+        ));
+        let black = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(0.))),
+            Arc::new(ConstantTexture::new(0.)),
+            None,
+        ));
+        let sigma_a = 0.75;
+        let fog = Arc::new(HomogeneousMedium::new(
+            Spectrum::new(sigma_a),
+            Spectrum::new(0.),
+            0.,
+        ));
+        let prims: Vec<Arc<dyn Primitive>> = vec![
+            medium_sphere(fog),
+            Arc::new(GeometricPrimitive::new(
+                light_shape,
+                Some(black),
+                Some(light.clone()),
+                Default::default(),
+            )),
+        ];
+        let lights: Vec<Arc<dyn Light>> = vec![light];
+        let scene = Scene::new(Arc::new(BVHAccel::new(prims, 1, SplitMethod::Sah)), lights);
+
+        // Paths are terminated by the depth check before passing through the medium's boundary,
+        // so a depth of one is needed to reach the light.  The black light source then ends the
+        // path, leaving only the light's emission attenuated by the medium.
+        let params = make_int_param_set("maxdepth", vec![1]);
+        let integrator = create_volpath_integrator(
+            &params,
+            Box::new(StratifiedSampler::new(1, 1, false, 0)),
+            camera(),
+        );
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        const N: usize = 20_000;
+        let mean = (0..N)
+            .map(|_| integrator.li(&ray(), &scene, &mut sampler, 0).channels()[0])
+            .sum::<Float>()
+            / N as Float;
+        let want = (-sigma_a * 2.).exp();
+        assert!((mean - want).abs() < 0.01, "mean {} want {}", mean, want);
+    }
+
+    #[test]
+    fn scattering_medium_is_lit() {
+        // A point light next to a scattering medium with nothing else in the scene: only
+        // in-scattered light reaches the camera.
+        let fog = Arc::new(HomogeneousMedium::new(
+            Spectrum::new(0.),
+            Spectrum::new(1.),
+            0.,
+        ));
+        let light: Arc<dyn Light> = Arc::new(PointLight::new(
+            &Transform::translate([2., 0., 2.]),
+            Default::default(),
+            Spectrum::new(10.),
+        ));
+        let scene = Scene::new(
+            Arc::new(BVHAccel::new(vec![medium_sphere(fog)], 1, SplitMethod::Sah)),
+            vec![light],
+        );
+
+        let params: ParamSet = make_int_param_set("maxdepth", vec![5]);
+        let integrator = create_volpath_integrator(
+            &params,
+            Box::new(StratifiedSampler::new(1, 1, false, 0)),
+            camera(),
+        );
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let l: Float = (0..1000)
+            .map(|_| integrator.li(&ray(), &scene, &mut sampler, 0).channels()[0])
+            .sum();
+        assert!(l > 0., "no light scattered by the medium");
+
+        // The path tracer ignores the medium, so sees nothing.
+        let path = create_path_integrator(
+            &params,
+            Box::new(StratifiedSampler::new(1, 1, false, 0)),
+            camera(),
+        );
+        assert_eq!(path.li(&ray(), &scene, &mut sampler, 0), Spectrum::new(0.));
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Integrator] implementing Whitted's ray tracing algorithm.
+//!
+//! [Integrator]: crate::core::integrator::Integrator
+use std::sync::Arc;
+
+use crate::core::{
+    camera::Camera,
+    geometry::{abs_dot, RayDifferential, Vector3f},
+    integrator::{Integrator, SamplerIntegrator, SamplerIntegratorData},
+    material::TransportMode,
+    paramset::ParamSet,
+    reflection::BxDFType,
+    sampler::Sampler,
+    scene::Scene,
+    spectrum::Spectrum,
+};
+
+/// WhittedIntegrator computes the radiance arriving along camera rays from the lights in the
+/// scene.
+#[derive(Debug)]
+pub struct WhittedIntegrator {
+    data: SamplerIntegratorData,
+    max_depth: usize,
+}
+
+impl WhittedIntegrator {
+    /// Create a new `WhittedIntegrator` that follows specular bounces up to `max_depth` deep.
+    pub fn new(max_depth: usize, data: SamplerIntegratorData) -> WhittedIntegrator {
+        WhittedIntegrator { data, max_depth }
+    }
+
+    /// Returns the maximum number of specular bounces followed.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn render(&mut self, scene: &Scene) {
+        self.render_samples(scene)
+    }
+}
+
+impl SamplerIntegrator for WhittedIntegrator {
+    fn data(&self) -> &SamplerIntegratorData {
+        &self.data
+    }
+
+    fn li(
+        &self,
+        ray: &RayDifferential,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        depth: usize,
+    ) -> Spectrum {
+        let mut l = Spectrum::new(0.);
+        // Find closest ray intersection or return background radiance
+        let mut isect = match scene.intersect(ray) {
+            Some(isect) => isect,
+            None => {
+                for light in &scene.infinite_lights {
+                    l += light.le(ray);
+                }
+                return l;
+            }
+        };
+
+        // Compute emitted and reflected light at ray intersection point
+
+        // Initialize common variables for Whitted integrator
+        let n = Vector3f::from(isect.shading.n);
+        let wo = isect.wo;
+
+        // Compute scattering functions for surface interaction
+        isect.compute_scattering_functions(ray, false, TransportMode::Radiance);
+        let bsdf = match &isect.bsdf {
+            Some(bsdf) => bsdf,
+            None => {
+                let r = RayDifferential::from(isect.spawn_ray(&ray.d));
+                return self.li(&r, scene, sampler, depth);
+            }
+        };
+        // Compute emitted light if ray hit an area light source
+        l += isect.le(&wo);
+
+        // Add contribution of each light source
+        for light in &scene.lights {
+            let ls = light.sample_li(&isect, sampler.get_2d());
+            if ls.li.is_black() || ls.pdf == 0. {
+                continue;
+            }
+            let f = bsdf.f(&wo, &ls.wi, BxDFType::ALL);
+            if !f.is_black() && ls.vis.unoccluded(scene) {
+                l += f * ls.li * (abs_dot(ls.wi, n) / ls.pdf);
+            }
+        }
+        if depth + 1 < self.max_depth {
+            // Trace rays for specular reflection and refraction
+            l += self.specular_reflect(ray, &isect, scene, sampler, depth);
+            l += self.specular_transmit(ray, &isect, scene, sampler, depth);
+        }
+        l
+    }
+}
+
+/// Creates a `WhittedIntegrator` from the given `params`.
+pub fn create_whitted_integrator(
+    params: &ParamSet,
+    sampler: Box<dyn Sampler>,
+    camera: Arc<dyn Camera>,
+) -> WhittedIntegrator {
+    let max_depth = params.find_one_int("maxdepth", 5).max(0) as usize;
+    WhittedIntegrator::new(
+        max_depth,
+        SamplerIntegratorData::new(camera, sampler, params),
+    )
+}
@@ -16,9 +16,20 @@
 
 //! pbrt is a rust implementation of http://www.pbr-book.org/3ed-2018/contents.html
 
+use lazy_static::lazy_static;
+
+use crate::core::{film::ToneMapper, geometry::Bounds2i};
+
+pub mod accelerators;
+pub mod cameras;
 pub mod core;
 pub mod filters;
+pub mod integrators;
 pub mod lights;
+pub mod materials;
+pub mod media;
+pub mod samplers;
+pub mod shapes;
 pub mod textures;
 
 #[cfg(feature = "float-as-double")]
@@ -55,11 +66,18 @@ impl From<Float> for Degree {
 
 /// Options for the renderer.  These are mostly passed through from commandline flags or from the
 /// configuration file parsed.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug)]
 pub struct Options {
-    /// number of threads to use when rendering.
+    /// Number of threads to use when rendering, zero, the default, uses one thread per core like
+    /// pbrt's `--nthreads`.  [API::init] sizes the global rayon thread pool with it, see
+    /// [init_threads] for when that can't take effect.
+    ///
+    /// [API::init]: crate::core::api::API::init
+    /// [init_threads]: crate::core::parallel::init_threads
     pub num_threads: u32,
-    /// Not implemented: enable some options for quick draft quality rendering.
+    /// Reduce the image resolution, pixel samples and light samples for quick draft quality
+    /// rendering.
     pub quick_render: bool,
     /// Squelch all non-error output.
     pub quiet: bool,
@@ -67,16 +85,32 @@ pub struct Options {
     pub verbose: bool,
     /// Path to stored rendered output.
     pub image_file: String,
+    /// Read scene files into memory instead of memory mapping them.
+    pub no_mmap: bool,
+    /// Write the parsed scene to stdout in normalized scene file syntax instead of rendering it.
+    pub cat: bool,
+    /// Like `cat`, but also write the geometry of triangle meshes to PLY files, replacing them
+    /// with `plymesh` shapes in the scene written.
+    pub to_ply: bool,
+    /// Operator used to map the rendered image into the range of 8-bit image formats.
+    pub tone_mapper: ToneMapper,
+    /// Only render the pixels within these bounds, in addition to the film's crop window.
+    pub pixel_bounds: Option<Bounds2i>,
 }
 
 impl Default for Options {
     fn default() -> Options {
         Options {
-            num_threads: 1,
+            num_threads: 0,
             quick_render: false,
             quiet: false,
             verbose: true,
             image_file: "".to_owned(),
+            no_mmap: false,
+            cat: false,
+            to_ply: false,
+            tone_mapper: ToneMapper::Clamp,
+            pixel_bounds: None,
         }
     }
 }
@@ -89,15 +123,72 @@ impl Default for Options {
 //const PI_OVER4: Float = 0.78539816339744830961;
 //const SQRT2: Float = 1.41421356237309504880;
 
+/// Largest linear value on the linear segment of the sRGB curve.
+const SRGB_LINEAR_THRESHOLD: Float = 0.0031308;
+
 /// Convert `value` into sRGB gamma-corrected value.
+///
+/// # Examples
+/// ```
+/// use pbrt::gamma_correct;
+///
+/// assert_eq!(gamma_correct(0.), 0.);
+/// assert!((gamma_correct(1.) - 1.).abs() < 1e-6);
+/// assert!((gamma_correct(0.2159) - 128. / 255.).abs() < 1e-4);
+/// ```
 pub fn gamma_correct(value: Float) -> Float {
-    if value <= 0.0031308 {
+    if value <= SRGB_LINEAR_THRESHOLD {
         12.92 * value
     } else {
         1.055 * value.powf(1. / 2.4) - 0.055
     }
 }
 
+/// Convert the sRGB gamma-corrected `value` back to a linear value, the inverse of
+/// [gamma_correct].
+///
+/// # Examples
+/// ```
+/// use pbrt::{gamma_correct, inverse_gamma_correct};
+///
+/// assert!((inverse_gamma_correct(128. / 255.) - 0.2159).abs() < 1e-4);
+/// assert!((inverse_gamma_correct(gamma_correct(0.75)) - 0.75).abs() < 1e-6);
+/// ```
+pub fn inverse_gamma_correct(value: Float) -> Float {
+    if value <= 12.92 * SRGB_LINEAR_THRESHOLD {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+lazy_static! {
+    static ref SRGB_TO_LINEAR_U8: [Float; 256] = {
+        let mut lut = [0.; 256];
+        for (i, v) in lut.iter_mut().enumerate() {
+            *v = inverse_gamma_correct(i as Float / 255.);
+        }
+        lut
+    };
+}
+
+/// Convert the 8-bit sRGB gamma-corrected `value` to a linear value in [0, 1].  This is
+/// equivalent to `inverse_gamma_correct(value as Float / 255.)`, but uses a lookup table so it's
+/// cheap enough to call for every texel of an image.
+///
+/// # Examples
+/// ```
+/// use pbrt::{inverse_gamma_correct, srgb_to_linear_u8, Float};
+///
+/// assert_eq!(srgb_to_linear_u8(0), 0.);
+/// assert_eq!(srgb_to_linear_u8(255), inverse_gamma_correct(1.));
+/// assert_eq!(srgb_to_linear_u8(128), inverse_gamma_correct(128. / 255.));
+/// assert!((srgb_to_linear_u8(128) - 0.2159).abs() < 1e-4);
+/// ```
+pub fn srgb_to_linear_u8(value: u8) -> Float {
+    SRGB_TO_LINEAR_U8[value as usize]
+}
+
 /// Clamp `val` between `low` and `high`.
 ///
 /// # Examples
@@ -140,6 +231,68 @@ pub fn lerp(t: Float, v1: Float, v2: Float) -> Float {
     (1. - t) * v1 + t * v2
 }
 
+/// Half the distance between 1 and the next representable `Float`, i.e. the bound on the relative
+/// error of a single correctly rounded floating-point operation.
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
+
+/// Returns the conservative bound on the relative error accumulated by `n` floating-point
+/// operations, (n ε) / (1 - n ε) where ε is [MACHINE_EPSILON].
+///
+/// # Examples
+/// ```
+/// use pbrt::{gamma, MACHINE_EPSILON};
+///
+/// assert_eq!(gamma(0), 0.);
+/// assert!(gamma(1) > MACHINE_EPSILON);
+/// assert!(gamma(3) < gamma(5));
+/// ```
+pub fn gamma(n: i32) -> Float {
+    let ne = n as Float * MACHINE_EPSILON;
+    ne / (1. - ne)
+}
+
+/// Returns the smallest representable `Float` greater than `v`.  Infinity is returned unchanged
+/// and negative zero is treated as zero.
+///
+/// # Examples
+/// ```
+/// use pbrt::{float::INFINITY, next_float_down, next_float_up, Float};
+///
+/// assert!(next_float_up(1.) > 1.);
+/// assert_eq!(next_float_up(1.) - 1., Float::EPSILON);
+/// assert!(next_float_up(-0.) > 0.);
+/// assert_eq!(next_float_up(INFINITY), INFINITY);
+/// assert_eq!(next_float_down(next_float_up(-2.)), -2.);
+/// ```
+pub fn next_float_up(v: Float) -> Float {
+    if v.is_infinite() && v > 0. {
+        return v;
+    }
+    let v = if v == -0. { 0. } else { v };
+    let bits = v.to_bits();
+    Float::from_bits(if v >= 0. { bits + 1 } else { bits - 1 })
+}
+
+/// Returns the largest representable `Float` less than `v`.  Negative infinity is returned
+/// unchanged and zero is treated as negative zero.
+///
+/// # Examples
+/// ```
+/// use pbrt::{float::NEG_INFINITY, next_float_down};
+///
+/// assert!(next_float_down(1.) < 1.);
+/// assert!(next_float_down(0.) < 0.);
+/// assert_eq!(next_float_down(NEG_INFINITY), NEG_INFINITY);
+/// ```
+pub fn next_float_down(v: Float) -> Float {
+    if v.is_infinite() && v < 0. {
+        return v;
+    }
+    let v = if v == 0. { -0. } else { v };
+    let bits = v.to_bits();
+    Float::from_bits(if v > 0. { bits - 1 } else { bits + 1 })
+}
+
 /// Note: assert_almost_equal_options exists only for doc tests, it is not part of the pbrt API.
 pub fn assert_almost_equal_options(l: Option<(Float, Float)>, r: Option<(Float, Float)>) {
     if l.is_none() && r.is_none() {
@@ -155,7 +308,7 @@ pub fn assert_almost_equal_options(l: Option<(Float, Float)>, r: Option<(Float,
 
 fn assert_almost_equal(f1: Float, f2: Float) {
     let diff = (f1 - f2).abs();
-    assert!(diff < float::EPSILON, "{} != {}, diff of {}", f1, f2, diff);
+    assert!(diff < Float::EPSILON, "{} != {}, diff of {}", f1, f2, diff);
 }
 
 /// Find roots of quadratic equation, if they exist.
@@ -178,6 +331,8 @@ fn assert_almost_equal(f1: Float, f2: Float) {
 /// let three: Float = 3.;
 /// assert_almost_equal_options(quadratic(1., 2., -2.),
 ///     Some(((-1.-three.sqrt()), (-1.+three.sqrt()))));
+// The casts are no-ops when built with "float-as-double".
+#[cfg_attr(feature = "float-as-double", allow(clippy::unnecessary_cast))]
 pub fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
     let a = a as f64;
     let b = b as f64;
@@ -201,3 +356,49 @@ pub fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
         Some((t0, t1))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{gamma_correct, inverse_gamma_correct, srgb_to_linear_u8, Float};
+
+    #[test]
+    fn gamma_roundtrip() {
+        const STEPS: usize = 10_000;
+        for i in 0..=STEPS {
+            let x = i as Float / STEPS as Float;
+            let y = gamma_correct(x);
+            assert!(
+                (0. ..=1. + 1e-6).contains(&y),
+                "gamma_correct({}) = {}",
+                x,
+                y
+            );
+            let got = inverse_gamma_correct(y);
+            assert!(
+                (got - x).abs() <= 1e-5 * x.max(1e-2),
+                "inverse_gamma_correct(gamma_correct({})) = {}",
+                x,
+                got
+            );
+            let got = gamma_correct(inverse_gamma_correct(x));
+            assert!(
+                (got - x).abs() <= 1e-5 * x.max(1e-2),
+                "gamma_correct(inverse_gamma_correct({})) = {}",
+                x,
+                got
+            );
+        }
+    }
+
+    #[test]
+    fn srgb_lut() {
+        let mut last = -1.;
+        for i in 0..=255_u8 {
+            let want = inverse_gamma_correct(i as Float / 255.);
+            assert_eq!(srgb_to_linear_u8(i), want);
+            // The table is monotonic.
+            assert!(want > last, "{} {} {}", i, want, last);
+            last = want;
+        }
+    }
+}
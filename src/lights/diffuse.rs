@@ -0,0 +1,280 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [AreaLight] implementation for a shape that emits light uniformly over its surface.
+//!
+//! [AreaLight]: crate::core::light::AreaLight
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        geometry::{dot, Point2f, Vector3f},
+        interaction::Interaction,
+        light::{AreaLight, LiSample, Light, LightData, LightFlags, VisibilityTester},
+        medium::MediumInterface,
+        paramset::ParamSet,
+        shape::Shape,
+        spectrum::Spectrum,
+        transform::Transform,
+    },
+    float::consts::PI,
+    Float, Options,
+};
+
+/// DiffuseAreaLight emits the same radiance in all directions from every point on its shape.
+#[derive(Debug)]
+pub struct DiffuseAreaLight {
+    light_data: LightData,
+    l_emit: Spectrum,
+    shape: Arc<dyn Shape>,
+    two_sided: bool,
+    area: Float,
+}
+
+impl DiffuseAreaLight {
+    /// Create a new `DiffuseAreaLight` emitting radiance `l_emit` from the surface of `shape`.
+    /// One-sided lights only emit on the side the surface normal points to.
+    pub fn new(
+        _light_to_world: &Transform,
+        medium_interface: MediumInterface,
+        l_emit: Spectrum,
+        n_samples: usize,
+        shape: Arc<dyn Shape>,
+        two_sided: bool,
+    ) -> DiffuseAreaLight {
+        let area = shape.area();
+        DiffuseAreaLight {
+            light_data: LightData::new(LightFlags::AREA, n_samples, medium_interface),
+            l_emit,
+            shape,
+            two_sided,
+            area,
+        }
+    }
+
+    /// Returns the surface area of the light's shape.
+    pub fn area(&self) -> Float {
+        self.area
+    }
+}
+
+impl AreaLight for DiffuseAreaLight {
+    /// Returns the emitted radiance for the surface point `intr` in direction `w`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Normal3f, Vector3f},
+    ///         interaction::Interaction,
+    ///         light::AreaLight,
+    ///         spectrum::Spectrum,
+    ///         transform::Transform,
+    ///     },
+    ///     lights::diffuse::DiffuseAreaLight,
+    ///     shapes::sphere::Sphere,
+    /// };
+    ///
+    /// let t = Transform::identity();
+    /// let sphere = Arc::new(Sphere::new(t, t, false, 1., -1., 1., 360.));
+    /// let l = DiffuseAreaLight::new(&t, Default::default(), Spectrum::new(2.), 1, sphere, false);
+    /// let it = Interaction {
+    ///     n: Normal3f::from([0., 0., 1.]),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(l.l(&it, &Vector3f::from([0., 0., 1.])), Spectrum::new(2.));
+    /// assert_eq!(l.l(&it, &Vector3f::from([0., 0., -1.])), Spectrum::new(0.));
+    /// ```
+    fn l(&self, intr: &Interaction, w: &Vector3f) -> Spectrum {
+        if self.two_sided || dot(Vector3f::from(intr.n), *w) > 0. {
+            self.l_emit.clone()
+        } else {
+            Spectrum::new(0.)
+        }
+    }
+}
+
+impl Light for DiffuseAreaLight {
+    fn flags(&self) -> LightFlags {
+        self.light_data.flags()
+    }
+
+    fn sample_li(&self, ref_: &Interaction, u: Point2f) -> LiSample {
+        let (mut p_shape, pdf) = self.shape.sample_ref(ref_, u);
+        if pdf == 0. || (p_shape.p - ref_.p).length_squared() == 0. {
+            return LiSample::default();
+        }
+        p_shape.time = ref_.time;
+        let wi = (p_shape.p - ref_.p).normalize();
+        LiSample {
+            li: self.l(&p_shape, &-wi),
+            wi,
+            pdf,
+            vis: VisibilityTester::new(ref_.clone(), p_shape),
+        }
+    }
+
+    fn pdf_li(&self, ref_: &Interaction, wi: &Vector3f) -> Float {
+        self.shape.pdf_ref(ref_, wi)
+    }
+
+    fn power(&self) -> Spectrum {
+        let sides = if self.two_sided { 2. } else { 1. };
+        self.l_emit.clone() * (sides * self.area * PI)
+    }
+
+    fn n_samples(&self) -> usize {
+        self.light_data.n_samples()
+    }
+}
+
+/// Creates a `DiffuseAreaLight` for `shape` with the given `Transform` and parameters.
+pub fn create_diffuse_area_light(
+    light2world: &Transform,
+    medium_interface: &MediumInterface,
+    params: &ParamSet,
+    shape: Arc<dyn Shape>,
+    opt: &Options,
+) -> Arc<DiffuseAreaLight> {
+    let l = params.find_one_spectrum("L", Spectrum::new(1.0));
+    let sc = params.find_one_spectrum("scale", Spectrum::new(1.0));
+    let mut n_samples = params.find_one_int("samples", params.find_one_int("nsamples", 1));
+    let two_sided = params.find_one_bool("twosided", false);
+    if opt.quick_render {
+        n_samples = (n_samples / 4).max(1);
+    }
+    Arc::new(DiffuseAreaLight::new(
+        light2world,
+        medium_interface.clone(),
+        l * sc,
+        n_samples.max(1) as usize,
+        shape,
+        two_sided,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{create_diffuse_area_light, DiffuseAreaLight};
+    use crate::{
+        core::{
+            geometry::{Point2f, Point3f, Vector3f},
+            interaction::Interaction,
+            light::{AreaLight, Light, LightFlags},
+            paramset::{
+                testutils::{make_bool, make_int, make_spectrum},
+                ParamSet,
+            },
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        float::consts::PI,
+        shapes::sphere::Sphere,
+        Options,
+    };
+
+    fn sphere_light(two_sided: bool) -> DiffuseAreaLight {
+        let o2w = Transform::translate([0., 0., 5.]);
+        let sphere = Arc::new(Sphere::new(o2w, o2w.inverse(), false, 1., -1., 1., 360.));
+        DiffuseAreaLight::new(
+            &o2w,
+            Default::default(),
+            Spectrum::from_rgb([1., 2., 3.]),
+            1,
+            sphere,
+            two_sided,
+        )
+    }
+
+    #[test]
+    fn emitted_spectrum() {
+        let one_sided = sphere_light(false);
+        let two_sided = sphere_light(true);
+        assert_eq!(one_sided.flags(), LightFlags::AREA);
+        let ref_ = Interaction::default();
+        for l in &[&one_sided, &two_sided] {
+            let ls = l.sample_li(&ref_, Point2f::from([0.9, 0.3]));
+            assert_eq!(ls.li, Spectrum::from_rgb([1., 2., 3.]));
+            assert!(ls.wi.z > 0., "wi {:?}", ls.wi);
+            let want = l.pdf_li(&ref_, &ls.wi);
+            assert!(
+                (ls.pdf - want).abs() / want < 1e-3,
+                "pdf {} want {}",
+                ls.pdf,
+                want
+            );
+            let d = Point3f::distance(ls.vis.p1.p, Point3f::from([0., 0., 5.]));
+            assert!((d - 1.).abs() < 1e-4, "p1 {:?}", ls.vis.p1.p);
+        }
+
+        // Looking at the back of the surface only sees light from two-sided lights.
+        let it = Interaction {
+            p: Point3f::from([0., 0., 4.]),
+            n: [0., 0., -1.].into(),
+            ..Default::default()
+        };
+        let w = Vector3f::from([0., 0., 1.]);
+        assert_eq!(one_sided.l(&it, &w), Spectrum::new(0.));
+        assert_eq!(two_sided.l(&it, &w), Spectrum::from_rgb([1., 2., 3.]));
+        assert_eq!(one_sided.l(&it, &-w), Spectrum::from_rgb([1., 2., 3.]));
+    }
+
+    #[test]
+    fn power() {
+        // A unit sphere has area 4π, emitting over the hemisphere above each point adds a π.
+        let p = sphere_light(false).power().to_rgb();
+        let want = 4. * PI * PI;
+        for (got, scale) in p.iter().zip(&[1., 2., 3.]) {
+            assert!((got - scale * want).abs() < 1e-4 * scale * want, "{:?}", p);
+        }
+        assert_eq!(sphere_light(true).power(), sphere_light(false).power() * 2.);
+    }
+
+    #[test]
+    fn create() {
+        let t = Transform::identity();
+        let sphere = Arc::new(Sphere::new(t, t, false, 1., -1., 1., 360.));
+        let ps: ParamSet = vec![
+            make_spectrum("L", vec![Spectrum::new(0.5)]),
+            make_spectrum("scale", vec![Spectrum::new(2.)]),
+            make_int("samples", vec![8]),
+            make_bool("twosided", vec![true]),
+        ]
+        .into();
+        let l = create_diffuse_area_light(
+            &t,
+            &Default::default(),
+            &ps,
+            sphere.clone(),
+            &Options::default(),
+        );
+        assert_eq!(l.n_samples(), 8);
+        let it = Interaction {
+            n: [0., 0., 1.].into(),
+            ..Default::default()
+        };
+        assert_eq!(l.l(&it, &[0., 0., -1.].into()), Spectrum::new(1.));
+
+        let opt = Options {
+            quick_render: true,
+            ..Default::default()
+        };
+        let l = create_diffuse_area_light(&t, &Default::default(), &ps, sphere, &opt);
+        assert_eq!(l.n_samples(), 2);
+    }
+}
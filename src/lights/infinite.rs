@@ -15,80 +15,438 @@
 //! [Light] implementation for infinite area light.
 //!
 //! [Light]: crate::core::light::Light
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::{
     core::{
-        geometry::Point3f,
-        imageio::read_image,
-        light::{Light, LightData},
-        mipmap::MIPMap,
+        geometry::{
+            coordinate_system, spherical_phi, spherical_theta, Normal3f, Point2f, Point2i, Point3f,
+            Ray, RayDifferential, Vector3f,
+        },
+        imageio::read_image_cached,
+        interaction::Interaction,
+        light::{LeSample, LiSample, Light, LightData, LightFlags, VisibilityTester},
+        medium::MediumInterface,
+        mipmap::{ImageWrap, MIPMap},
         paramset::ParamSet,
-        sampling::Distribution2D,
-        spectrum::{RGBSpectrum, Spectrum},
+        sampling::{concentric_sample_disk, Distribution2D},
+        scene::Scene,
+        spectrum::{RGBSpectrum, Spectrum, SpectrumType},
         transform::Transform,
     },
-    Float,
+    float::consts::PI,
+    Float, Options,
 };
 
-#[derive(Debug)]
 /// InfiniteAreaLight represents a light infinitely far away that surrounds the entire scene.
+/// Its radiance is given by an environment map in equirectangular (latitude-longitude) layout,
+/// which is importance sampled according to its luminance.
+#[derive(Debug)]
 pub struct InfiniteAreaLight {
     light_data: LightData,
+    light_to_world: Transform,
+    world_to_light: Transform,
     lmap: MIPMap<RGBSpectrum>,
-    world_center: Point3f,
-    world_radius: Float,
+    // Center and radius of the scene's bounding sphere, set in `preprocess`.
+    world_sphere: Mutex<(Point3f, Float)>,
     distribution: Distribution2D,
 }
 
-impl Light for InfiniteAreaLight {}
+impl Light for InfiniteAreaLight {
+    fn flags(&self) -> LightFlags {
+        self.light_data.flags()
+    }
+
+    fn preprocess(&self, scene: &Scene) {
+        *self.world_sphere.lock().unwrap() = scene.world_bound().bounding_sphere();
+    }
+
+    fn le(&self, ray: &RayDifferential) -> Spectrum {
+        let wh = self.world_to_light.transform_vector(ray.d).normalize();
+        let st = Point2f::from([spherical_phi(wh) / (2. * PI), spherical_theta(wh) / PI]);
+        self.lookup(st)
+    }
+
+    fn sample_li(&self, ref_: &Interaction, u: Point2f) -> LiSample {
+        // Find $(u,v)$ sample coordinates in infinite light texture
+        let (uv, map_pdf) = self.distribution.sample_continuous(u);
+        if map_pdf == 0. {
+            return LiSample::default();
+        }
+
+        // Convert infinite light sample point to direction
+        let wi = self.light_to_world.transform_vector(uv_to_direction(uv));
+
+        // Compute PDF for sampled infinite light direction
+        let pdf = map_pdf_to_solid_angle(map_pdf, uv);
+
+        // Return radiance value for infinite light direction
+        let world_radius = self.world_sphere.lock().unwrap().1;
+        let p1 = Interaction {
+            p: ref_.p + wi * (2. * world_radius),
+            time: ref_.time,
+            medium_interface: self.light_data.medium_interface(),
+            ..Default::default()
+        };
+        LiSample {
+            li: self.lookup(uv),
+            wi,
+            pdf,
+            vis: VisibilityTester::new(ref_.clone(), p1),
+        }
+    }
+
+    fn pdf_li(&self, _ref: &Interaction, w: &Vector3f) -> Float {
+        let wi = self.world_to_light.transform_vector(*w);
+        let uv = Point2f::from([spherical_phi(wi) / (2. * PI), spherical_theta(wi) / PI]);
+        map_pdf_to_solid_angle(self.distribution.pdf(uv), uv)
+    }
+
+    fn sample_le(&self, u1: Point2f, u2: Point2f, time: Float) -> LeSample {
+        // Compute direction for infinite light sample ray
+        let (uv, map_pdf) = self.distribution.sample_continuous(u1);
+        if map_pdf == 0. {
+            return LeSample::default();
+        }
+        let d = -self.light_to_world.transform_vector(uv_to_direction(uv));
+
+        // Compute origin for infinite light sample ray
+        let (world_center, world_radius) = *self.world_sphere.lock().unwrap();
+        let (v1, v2) = coordinate_system(-d);
+        let cd = concentric_sample_disk(u2);
+        let p_disk = world_center + (v1 * cd.x + v2 * cd.y) * world_radius;
+        LeSample {
+            le: self.lookup(uv),
+            ray: Ray::new(p_disk + -d * world_radius, d, Float::INFINITY, time, None),
+            n_light: Normal3f::from(d),
+            pdf_pos: 1. / (PI * world_radius * world_radius),
+            pdf_dir: map_pdf_to_solid_angle(map_pdf, uv),
+        }
+    }
+
+    fn power(&self) -> Spectrum {
+        // Approximate the emitted power with the average radiance over the environment map,
+        // arriving at a disk the size of the scene.
+        let l = self.lmap.lookup(Point2f::from([0.5, 0.5]), 0.5).to_rgb();
+        let world_radius = self.world_sphere.lock().unwrap().1;
+        Spectrum::from_rgb_typed(l, SpectrumType::Illuminant) * (PI * world_radius * world_radius)
+    }
+
+    fn n_samples(&self) -> usize {
+        self.light_data.n_samples()
+    }
+}
+
+/// Returns the light space direction for the point `uv` of the environment map, where `u` maps
+/// to φ and `v` to θ.
+fn uv_to_direction(uv: Point2f) -> Vector3f {
+    let (theta, phi) = (uv[1] * PI, uv[0] * 2. * PI);
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    Vector3f::from([sin_theta * cos_phi, sin_theta * sin_phi, cos_theta])
+}
+
+/// Converts the density `map_pdf` of sampling `uv` from the environment map to a density with
+/// respect to solid angle.  The map covers 2π by π radians and a texel's solid angle shrinks by
+/// sin θ.
+fn map_pdf_to_solid_angle(map_pdf: Float, uv: Point2f) -> Float {
+    let sin_theta = (uv[1] * PI).sin();
+    if sin_theta == 0. {
+        return 0.;
+    }
+    map_pdf / (2. * PI * PI * sin_theta)
+}
+
 impl InfiniteAreaLight {
     fn new(
-        _light2world: &Transform,
+        light_to_world: &Transform,
         l: &Spectrum,
-        _n_samples: isize,
+        n_samples: usize,
         texmap: &str,
     ) -> InfiniteAreaLight {
-        let (texels, resolution) = if !texmap.is_empty() {
-            if let Ok((mut texels, resolution)) = read_image(texmap) {
-                texels.iter_mut().for_each(|p| *p *= l.to_rgb_spectrum());
-                (texels, resolution)
-            } else {
-                (vec![l.to_rgb_spectrum()], [1, 1].into())
+        if !texmap.is_empty() {
+            if let Ok(img) = read_image_cached(texmap, true) {
+                let (texels, resolution) = &*img;
+                let texels = texels
+                    .iter()
+                    .map(|p| p.clone() * l.to_rgb_spectrum())
+                    .collect();
+                return InfiniteAreaLight::from_image(
+                    light_to_world,
+                    n_samples,
+                    resolution,
+                    texels,
+                );
             }
-        } else {
-            (vec![l.to_rgb_spectrum()], [1, 1].into())
-        };
-        let _ = texels;
-        let _ = resolution;
-        //lmap.reset(MIPMap::new(resolution, texels));
+        }
+        InfiniteAreaLight::from_image(
+            light_to_world,
+            n_samples,
+            &[1, 1].into(),
+            vec![l.to_rgb_spectrum()],
+        )
+    }
 
-        todo!("InfiniteAreaLight::new()");
-        /*
+    /// Create an `InfiniteAreaLight` whose radiance is given by the equirectangular image
+    /// `texels` of size `resolution`, stored a row at a time.  Rows run from the light's +z axis
+    /// (θ = 0) to its -z axis, columns sweep φ around it.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::Point2f,
+    ///         interaction::Interaction,
+    ///         light::Light,
+    ///         spectrum::{RGBSpectrum, Spectrum},
+    ///         transform::Transform,
+    ///     },
+    ///     lights::infinite::InfiniteAreaLight,
+    /// };
+    ///
+    /// let light = InfiniteAreaLight::from_image(
+    ///     &Transform::identity(),
+    ///     1,
+    ///     &[2, 2].into(),
+    ///     vec![RGBSpectrum::new(1.); 4],
+    /// );
+    /// let ls = light.sample_li(&Interaction::default(), Point2f::from([0.3, 0.6]));
+    /// assert_eq!(ls.li, Spectrum::new(1.));
+    /// assert_eq!(ls.pdf, light.pdf_li(&Interaction::default(), &ls.wi));
+    /// ```
+    pub fn from_image(
+        light_to_world: &Transform,
+        n_samples: usize,
+        resolution: &Point2i,
+        texels: Vec<RGBSpectrum>,
+    ) -> InfiniteAreaLight {
+        let lmap = MIPMap::new(resolution, texels, false, 8., ImageWrap::Repeat);
+
+        // Compute scalar-valued image _img_ from environment map
+        let resolution = lmap.resolution();
+        let (width, height) = (resolution.x, resolution.y);
+        let mut img = Vec::with_capacity((width * height) as usize);
+        for v in 0..height {
+            // Weight each row by sin θ, as texels near the poles cover less solid angle.
+            let sin_theta = (PI * (v as Float + 0.5) / height as Float).sin();
+            for u in 0..width {
+                img.push(lmap.texel(0, u, v).y().max(0.) * sin_theta);
+            }
+        }
+
+        // Compute sampling distributions for rows and columns of image
+        let distribution = Distribution2D::new(&img, width as usize, height as usize);
         InfiniteAreaLight {
-            light_data: LightData::new(LightFlags::Infinite, n_samples, MediumInterface::default()),
+            light_data: LightData::new(LightFlags::INFINITE, n_samples, MediumInterface::default()),
+            light_to_world: *light_to_world,
+            world_to_light: light_to_world.inverse(),
             lmap,
-            world_center,
-            world_radius,
+            world_sphere: Mutex::new((Point3f::default(), 0.)),
             distribution,
         }
-        */
+    }
+
+    /// Returns the radiance of the environment map texel containing `st`.  Lookups are not
+    /// filtered so the radiance matches the piecewise-constant distribution it is sampled with.
+    fn lookup(&self, st: Point2f) -> Spectrum {
+        let res = self.lmap.resolution();
+        let s = ((st[0] * res.x as Float) as isize).min(res.x - 1);
+        let t = ((st[1] * res.y as Float) as isize).min(res.y - 1);
+        Spectrum::from_rgb_typed(self.lmap.texel(0, s, t).to_rgb(), SpectrumType::Illuminant)
     }
 }
 
 /// Creates an InfiniteAreaLight with the given `Transform` and parameters.
-pub fn create_infinite_light(light2world: &Transform, params: &ParamSet) -> Arc<InfiniteAreaLight> {
+/// When `opt.quick_render` is set the number of light samples is divided by 4.
+pub fn create_infinite_light(
+    light2world: &Transform,
+    params: &ParamSet,
+    opt: &Options,
+) -> Arc<InfiniteAreaLight> {
     let l = params.find_one_spectrum("L", Spectrum::new(1.0));
     let sc = params.find_one_spectrum("scale", Spectrum::new(1.0));
     let texmap = params.find_one_filename("mapname", "");
-    let n_samples = params.find_one_int("samples", params.find_one_int("nsamples", 1));
-    // TODO(wathiede): do we plumb options into this constructor or make options a singleton random
-    // things can grab?
-    //if (PbrtOptions.quickRender) nSamples =  (n_samples / 4).max(1);
+    let mut n_samples = params.find_one_int("samples", params.find_one_int("nsamples", 1));
+    if opt.quick_render {
+        n_samples = (n_samples / 4).max(1);
+    }
     Arc::new(InfiniteAreaLight::new(
         light2world,
         &(l * sc),
-        n_samples,
+        n_samples.max(1) as usize,
         &texmap,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::InfiniteAreaLight;
+    use crate::{
+        accelerators::bvh::{BVHAccel, SplitMethod},
+        core::{
+            geometry::{
+                dot, spherical_phi, spherical_theta, Point2f, Point3f, Ray, RayDifferential,
+                Vector3f,
+            },
+            interaction::Interaction,
+            light::Light,
+            primitive::GeometricPrimitive,
+            rng::Rng,
+            sampling::{stratified_sample_2d, uniform_sample_sphere, uniform_sphere_pdf},
+            scene::Scene,
+            spectrum::RGBSpectrum,
+            transform::Transform,
+        },
+        float::consts::PI,
+        shapes::sphere::Sphere,
+        Degree, Float,
+    };
+
+    const WIDTH: isize = 16;
+    const HEIGHT: isize = 8;
+    const BRIGHT: (isize, isize) = (5, 2);
+
+    fn light_to_world() -> Transform {
+        Transform::rotate(Degree::from(30.), [1., 1., 0.])
+    }
+
+    // An environment map with one texel much brighter than all the others.
+    fn bright_texel_light() -> InfiniteAreaLight {
+        let texels = (0..HEIGHT)
+            .flat_map(|t| (0..WIDTH).map(move |s| (s, t)))
+            .map(|st| {
+                if st == BRIGHT {
+                    RGBSpectrum::new(100.)
+                } else {
+                    RGBSpectrum::new(0.01)
+                }
+            })
+            .collect();
+        InfiniteAreaLight::from_image(&light_to_world(), 1, &[WIDTH, HEIGHT].into(), texels)
+    }
+
+    // Returns the texel of the environment map world space direction `w` falls in.
+    fn texel(w: Vector3f) -> (isize, isize) {
+        let wl = light_to_world().inverse().transform_vector(w);
+        let s = (spherical_phi(wl) / (2. * PI) * WIDTH as Float) as isize;
+        let t = (spherical_theta(wl) / PI * HEIGHT as Float) as isize;
+        (s.min(WIDTH - 1), t.min(HEIGHT - 1))
+    }
+
+    #[test]
+    fn samples_bright_texel() {
+        let light = bright_texel_light();
+        let ref_ = Interaction::default();
+        let mut rng = Rng::new(0);
+        let n = 10000;
+        let mut hits = 0;
+        for _ in 0..n {
+            let u = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+            let ls = light.sample_li(&ref_, u);
+            assert!(ls.pdf > 0.);
+            let pdf = light.pdf_li(&ref_, &ls.wi);
+            assert!((ls.pdf - pdf).abs() <= 1e-3 * pdf, "{} vs {}", ls.pdf, pdf);
+            if texel(ls.wi) == BRIGHT {
+                assert!(ls.li.y() > 50., "li {:?}", ls.li);
+                hits += 1;
+            }
+        }
+        assert!(
+            hits as Float > 0.9 * n as Float,
+            "{} of {} samples",
+            hits,
+            n
+        );
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let ref_ = Interaction::default();
+        let constant = InfiniteAreaLight::from_image(
+            &light_to_world(),
+            1,
+            &[WIDTH, HEIGHT].into(),
+            vec![RGBSpectrum::new(1.); (WIDTH * HEIGHT) as usize],
+        );
+        for light in &[bright_texel_light(), constant] {
+            let mut rng = Rng::new(1);
+            let mut u = vec![Point2f::default(); 256 * 256];
+            stratified_sample_2d(&mut u, 256, 256, &mut rng, true);
+            let sum: Float = u
+                .iter()
+                .map(|u| light.pdf_li(&ref_, &uniform_sample_sphere(*u)) / uniform_sphere_pdf())
+                .sum();
+            let integral = sum / u.len() as Float;
+            assert!((integral - 1.).abs() < 0.01, "integral {}", integral);
+        }
+    }
+
+    #[test]
+    fn constant_map_is_uniform() {
+        // Without the sin θ weighting directions near the poles would be oversampled.
+        let light = InfiniteAreaLight::from_image(
+            &Transform::identity(),
+            1,
+            &[WIDTH, HEIGHT].into(),
+            vec![RGBSpectrum::new(1.); (WIDTH * HEIGHT) as usize],
+        );
+        for t in 0..HEIGHT {
+            let theta = (t as Float + 0.5) / HEIGHT as Float * PI;
+            let w = Vector3f::from([theta.sin(), 0., theta.cos()]);
+            let pdf = light.pdf_li(&Interaction::default(), &w);
+            assert!(
+                (pdf / uniform_sphere_pdf() - 1.).abs() < 0.01,
+                "row {} pdf {}",
+                t,
+                pdf
+            );
+        }
+    }
+
+    #[test]
+    fn sample_le() {
+        let light = Arc::new(bright_texel_light());
+        let t = Transform::translate([1., 2., 3.]);
+        let sphere = Arc::new(Sphere::new(t, t.inverse(), false, 2., -2., 2., 360.));
+        let prim = Arc::new(GeometricPrimitive::new(
+            sphere,
+            None,
+            None,
+            Default::default(),
+        ));
+        // Building the scene calls preprocess, which sizes the light to the scene.
+        let _scene = Scene::new(
+            Arc::new(BVHAccel::new(vec![prim], 1, SplitMethod::Sah)),
+            vec![light.clone()],
+        );
+        let (center, radius) = (Point3f::from([1., 2., 3.]), (3. as Float).sqrt() * 2.);
+
+        let mut rng = Rng::new(2);
+        for _ in 0..100 {
+            let u1 = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+            let u2 = Point2f::from([rng.uniform_float(), rng.uniform_float()]);
+            let les = light.sample_le(u1, u2, 0.5);
+            assert_eq!(les.ray.time, 0.5);
+            assert!((les.pdf_pos - 1. / (PI * radius * radius)).abs() < 1e-6);
+            // Rays start on the disk tangent to the scene's bounding sphere and point into it.
+            let to_origin = les.ray.o - center;
+            assert!((dot(to_origin, les.ray.d) + radius).abs() < 1e-3);
+            assert!(to_origin.length() <= radius * (2. as Float).sqrt() + 1e-3);
+            // The emitted radiance and direction density match those seen looking back along the
+            // ray.
+            let w = -les.ray.d;
+            let pdf = light.pdf_li(&Interaction::default(), &w);
+            assert!((les.pdf_dir - pdf).abs() <= 1e-3 * pdf);
+            let back = RayDifferential {
+                ray: Ray {
+                    d: w,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            assert_eq!(les.le, light.le(&back));
+        }
+    }
+}
@@ -15,4 +15,6 @@
 //! Module for the various [Light] implementations in PBRT.
 //!
 //! [Light]: crate::core::light::Light
+pub mod diffuse;
 pub mod infinite;
+pub mod point;
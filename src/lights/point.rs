@@ -0,0 +1,142 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Light] implementation for an isotropic point light.
+//!
+//! [Light]: crate::core::light::Light
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        geometry::{Point2f, Point3f, Vector3f},
+        interaction::Interaction,
+        light::{LiSample, Light, LightData, LightFlags, VisibilityTester},
+        medium::MediumInterface,
+        paramset::ParamSet,
+        spectrum::Spectrum,
+        transform::Transform,
+    },
+    float::consts::PI,
+    Float,
+};
+
+/// PointLight emits the same amount of light in all directions from a single point.
+#[derive(Debug)]
+pub struct PointLight {
+    light_data: LightData,
+    p_light: Point3f,
+    i: Spectrum,
+}
+
+impl PointLight {
+    /// Create a new `PointLight` at the origin of light space, emitting intensity `i`.
+    pub fn new(
+        light_to_world: &Transform,
+        medium_interface: MediumInterface,
+        i: Spectrum,
+    ) -> PointLight {
+        PointLight {
+            light_data: LightData::new(LightFlags::DELTA_POSITION, 1, medium_interface),
+            p_light: light_to_world.transform_point(Point3f::default()),
+            i,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn flags(&self) -> LightFlags {
+        self.light_data.flags()
+    }
+
+    /// Returns the light arriving at `ref_` from the point light, falling off with the square of
+    /// the distance.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Point2f, Point3f},
+    ///         interaction::Interaction,
+    ///         light::Light,
+    ///         spectrum::Spectrum,
+    ///         transform::Transform,
+    ///     },
+    ///     lights::point::PointLight,
+    /// };
+    ///
+    /// let l = PointLight::new(
+    ///     &Transform::translate([0., 0., 2.]),
+    ///     Default::default(),
+    ///     Spectrum::new(4.),
+    /// );
+    /// let it = Interaction {
+    ///     p: Point3f::from([0., 0., 0.]),
+    ///     ..Default::default()
+    /// };
+    /// let s = l.sample_li(&it, Point2f::default());
+    /// assert_eq!(s.li, Spectrum::new(1.));
+    /// assert_eq!(s.wi, [0., 0., 1.].into());
+    /// assert_eq!(s.pdf, 1.);
+    /// ```
+    fn sample_li(&self, ref_: &Interaction, _u: Point2f) -> LiSample {
+        let wi = (self.p_light - ref_.p).normalize();
+        let li = self.i.clone() / Point3f::distance_squared(self.p_light, ref_.p);
+        let p1 = Interaction {
+            p: self.p_light,
+            time: ref_.time,
+            ..Default::default()
+        };
+        LiSample {
+            li,
+            wi,
+            pdf: 1.,
+            vis: VisibilityTester::new(ref_.clone(), p1),
+        }
+    }
+
+    fn pdf_li(&self, _ref: &Interaction, _wi: &Vector3f) -> Float {
+        0.
+    }
+
+    /// Returns the power emitted by the point light, its intensity over the whole sphere of
+    /// directions.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{light::Light, spectrum::Spectrum, transform::Transform},
+    ///     float::consts::PI,
+    ///     lights::point::PointLight,
+    /// };
+    ///
+    /// let l = PointLight::new(&Transform::identity(), Default::default(), Spectrum::new(2.));
+    /// assert_eq!(l.power(), Spectrum::new(8. * PI));
+    /// ```
+    fn power(&self) -> Spectrum {
+        self.i.clone() * (4. * PI)
+    }
+}
+
+/// Creates a `PointLight` with the given `Transform` and parameters.
+pub fn create_point_light(
+    light2world: &Transform,
+    medium_interface: &MediumInterface,
+    params: &ParamSet,
+) -> Arc<PointLight> {
+    let i = params.find_one_spectrum("I", Spectrum::new(1.0));
+    let sc = params.find_one_spectrum("scale", Spectrum::new(1.0));
+    let p = params.find_one_point3f("from", Point3f::default());
+    let l2w = Transform::translate([p.x, p.y, p.z]) * *light2world;
+    Arc::new(PointLight::new(&l2w, medium_interface.clone(), i * sc))
+}
@@ -0,0 +1,281 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a glass [Material] that both reflects and transmits light.
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{
+            BeckmannDistribution, FresnelDielectric, FresnelSpecular, MicrofacetReflection,
+            MicrofacetTransmission, SpecularReflection, SpecularTransmission, BSDF,
+        },
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// GlassMaterial models a dielectric with index of refraction `eta`.  `kr` scales the reflected
+/// light and `kt` the transmitted light.  The surface is perfectly smooth if `u_roughness` and
+/// `v_roughness` are both zero, otherwise it is rough with a [BeckmannDistribution] of microfacets.
+/// If `remap_roughness` is true the roughness values are in [0, 1] and remapped with
+/// [BeckmannDistribution::roughness_to_alpha].
+#[derive(Debug)]
+pub struct GlassMaterial {
+    kr: Arc<dyn Texture<Spectrum>>,
+    kt: Arc<dyn Texture<Spectrum>>,
+    u_roughness: Arc<dyn Texture<Float>>,
+    v_roughness: Arc<dyn Texture<Float>>,
+    eta: Float,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+    remap_roughness: bool,
+}
+
+impl GlassMaterial {
+    /// Create a new `GlassMaterial` with reflectance `kr`, transmittance `kt`, roughness
+    /// `u_roughness` and `v_roughness` and index of refraction `eta`, optionally perturbed by the
+    /// displacement texture `bump_map`.
+    pub fn new(
+        kr: Arc<dyn Texture<Spectrum>>,
+        kt: Arc<dyn Texture<Spectrum>>,
+        u_roughness: Arc<dyn Texture<Float>>,
+        v_roughness: Arc<dyn Texture<Float>>,
+        eta: Float,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+        remap_roughness: bool,
+    ) -> GlassMaterial {
+        GlassMaterial {
+            kr,
+            kt,
+            u_roughness,
+            v_roughness,
+            eta,
+            bump_map,
+            remap_roughness,
+        }
+    }
+}
+
+impl Material for GlassMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        mode: TransportMode,
+        allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let mut bsdf = BSDF::new(si, self.eta);
+        let r = self.kr.evaluate(si).clamp_nonnegative();
+        let t = self.kt.evaluate(si).clamp_nonnegative();
+        let mut u_rough = self.u_roughness.evaluate(si);
+        let mut v_rough = self.v_roughness.evaluate(si);
+        let is_specular = u_rough == 0. && v_rough == 0.;
+        if self.remap_roughness {
+            u_rough = BeckmannDistribution::roughness_to_alpha(u_rough);
+            v_rough = BeckmannDistribution::roughness_to_alpha(v_rough);
+        }
+        if !r.is_black() || !t.is_black() {
+            if is_specular && allow_multiple_lobes {
+                // Choose between reflection and transmission by the Fresnel reflectance.
+                bsdf.add(Box::new(FresnelSpecular::new(r, t, 1., self.eta, mode)));
+            } else {
+                if !r.is_black() {
+                    let fresnel = Box::new(FresnelDielectric::new(1., self.eta));
+                    if is_specular {
+                        bsdf.add(Box::new(SpecularReflection::new(r, fresnel)));
+                    } else {
+                        bsdf.add(Box::new(MicrofacetReflection::new(
+                            r,
+                            Box::new(BeckmannDistribution::new(u_rough, v_rough)),
+                            fresnel,
+                        )));
+                    }
+                }
+                if !t.is_black() {
+                    if is_specular {
+                        bsdf.add(Box::new(SpecularTransmission::new(t, 1., self.eta, mode)));
+                    } else {
+                        bsdf.add(Box::new(MicrofacetTransmission::new(
+                            t,
+                            Box::new(BeckmannDistribution::new(u_rough, v_rough)),
+                            1.,
+                            self.eta,
+                            mode,
+                        )));
+                    }
+                }
+            }
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `GlassMaterial` from the given `TextureParams`.  The index of refraction may be given
+/// as either "eta" or "index".
+pub fn create_glass_material(mp: &TextureParams) -> GlassMaterial {
+    let kr = mp.get_spectrum_texture("Kr", Spectrum::from(1.));
+    let kt = mp.get_spectrum_texture("Kt", Spectrum::from(1.));
+    let eta = mp.find_float("eta", mp.find_float("index", 1.5));
+    let u_roughness = mp.get_float_texture("uroughness", 0.);
+    let v_roughness = mp.get_float_texture("vroughness", 0.);
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    let remap_roughness = mp.find_bool("remaproughness", true);
+    GlassMaterial::new(
+        kr,
+        kt,
+        u_roughness,
+        v_roughness,
+        eta,
+        bump_map,
+        remap_roughness,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_glass_material;
+    use crate::core::{
+        geometry::{Normal3f, Point2f, Vector3f},
+        interaction::{Interaction, Shading, SurfaceInteraction},
+        material::{Material, TransportMode},
+        paramset::{
+            testutils::{make_float, make_float_param_set},
+            ParamSet, TextureParams,
+        },
+        reflection::BxDFType,
+    };
+
+    fn surface() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 0., 1.]);
+        SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lobes() {
+        let m = create_glass_material(&TextureParams::new(
+            make_float_param_set("eta", vec![1.33]),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+
+        let mut si = surface();
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.eta, 1.33);
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 1);
+
+        let mut si = surface();
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, false);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 2);
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::SPECULAR),
+            1
+        );
+        assert_eq!(
+            bsdf.num_components(BxDFType::TRANSMISSION | BxDFType::SPECULAR),
+            1
+        );
+    }
+
+    #[test]
+    fn total_internal_reflection() {
+        let m = create_glass_material(&TextureParams::default());
+        // Light leaving the glass at a steep angle to the normal is reflected back in,
+        // whichever lobe is sampled.
+        let wo = Vector3f::from([0.95, 0., -0.2]).normalize();
+        for allow_multiple_lobes in &[true, false] {
+            let mut si = surface();
+            m.compute_scattering_functions(&mut si, TransportMode::Radiance, *allow_multiple_lobes);
+            let bsdf = si.bsdf.expect("bsdf");
+            for u in &[0.05, 0.3, 0.6, 0.95] {
+                let s = bsdf.sample_f(&wo, Point2f::from([*u, 0.5]), BxDFType::ALL);
+                if s.pdf == 0. {
+                    // The transmission lobe has nothing to contribute.
+                    assert!(s.f.is_black());
+                    continue;
+                }
+                assert_eq!(s.sampled_type, BxDFType::REFLECTION | BxDFType::SPECULAR);
+                assert!(s.wi.z < 0.);
+            }
+        }
+
+        // Close to the normal, some light is transmitted out of the glass.
+        let wo = Vector3f::from([0.1, 0., -1.]).normalize();
+        let mut si = surface();
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        let s = bsdf.sample_f(&wo, Point2f::from([0.5, 0.5]), BxDFType::ALL);
+        assert_eq!(s.sampled_type, BxDFType::TRANSMISSION | BxDFType::SPECULAR);
+        assert!(s.wi.z > 0.);
+    }
+
+    #[test]
+    fn rough() {
+        let params: ParamSet = vec![
+            make_float("uroughness", vec![0.2]),
+            make_float("vroughness", vec![0.2]),
+        ]
+        .into();
+        let m = create_glass_material(&TextureParams::new(
+            params,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        for allow_multiple_lobes in &[true, false] {
+            let mut si = surface();
+            m.compute_scattering_functions(&mut si, TransportMode::Radiance, *allow_multiple_lobes);
+            let bsdf = si.bsdf.expect("bsdf");
+            assert_eq!(bsdf.num_components(BxDFType::ALL), 2);
+            assert_eq!(
+                bsdf.num_components(BxDFType::REFLECTION | BxDFType::GLOSSY),
+                1
+            );
+            assert_eq!(
+                bsdf.num_components(BxDFType::TRANSMISSION | BxDFType::GLOSSY),
+                1
+            );
+
+            // Light is both reflected and transmitted around the specular directions.
+            let wo = Vector3f::from([0.3, 0., 1.]).normalize();
+            let mirror = Vector3f::from([-wo.x, -wo.y, wo.z]);
+            assert!(!bsdf.f(&wo, &mirror, BxDFType::ALL).is_black());
+            let refracted = Vector3f::from([-0.2, 0., -1.]).normalize();
+            assert!(!bsdf.f(&wo, &refracted, BxDFType::ALL).is_black());
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a purely diffuse [Material].
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    clamp,
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{LambertianReflection, OrenNayar, BSDF},
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// MatteMaterial models a diffuse surface with reflectance `kd`.  `sigma` is the roughness of the
+/// surface, in degrees.
+#[derive(Debug)]
+pub struct MatteMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    sigma: Arc<dyn Texture<Float>>,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+}
+
+impl MatteMaterial {
+    /// Create a new `MatteMaterial` with diffuse reflectance `kd` and roughness `sigma`,
+    /// optionally perturbed by the displacement texture `bump_map`.
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum>>,
+        sigma: Arc<dyn Texture<Float>>,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+    ) -> MatteMaterial {
+        MatteMaterial {
+            kd,
+            sigma,
+            bump_map,
+        }
+    }
+}
+
+impl Material for MatteMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        // Evaluate textures for _MatteMaterial_ material and allocate BRDF
+        let mut bsdf = BSDF::new(si, 1.);
+        let r = self.kd.evaluate(si).clamp_nonnegative();
+        let sig = clamp(self.sigma.evaluate(si), 0., 90.);
+        if !r.is_black() {
+            if sig == 0. {
+                bsdf.add(Box::new(LambertianReflection::new(r)));
+            } else {
+                bsdf.add(Box::new(OrenNayar::new(r, sig)));
+            }
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `MatteMaterial` from the given `TextureParams`.
+pub fn create_matte_material(mp: &TextureParams) -> MatteMaterial {
+    let kd = mp.get_spectrum_texture("Kd", Spectrum::from(0.5));
+    let sigma = mp.get_float_texture("sigma", 0.);
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    MatteMaterial::new(kd, sigma, bump_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::create_matte_material;
+    use crate::{
+        core::{
+            geometry::{dot, Normal3f, Vector3f},
+            interaction::{Interaction, Shading, SurfaceInteraction},
+            material::{Material, TransportMode},
+            paramset::{
+                testutils::{make_spectrum_param_set, make_texture_param_set},
+                TextureParams,
+            },
+            reflection::BxDFType,
+            spectrum::Spectrum,
+            texture::{IdentityMapping3D, Texture},
+            transform::Transform,
+        },
+        float::consts::FRAC_1_PI,
+        textures::wrinkled::WrinkledTexture,
+        Float,
+    };
+
+    #[test]
+    fn lambertian_bsdf() {
+        let n = Normal3f::from([0., 0., 1.]);
+        let mut si = SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let m = create_matte_material(&TextureParams::new(
+            make_spectrum_param_set("Kd", vec![Spectrum::from(1.)]),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 1);
+        assert_eq!(bsdf.num_components(BxDFType::SPECULAR), 0);
+    }
+
+    #[test]
+    fn rough_matte_uses_oren_nayar() {
+        let bsdf = |sigma: Float| {
+            let n = Normal3f::from([0., 0., 1.]);
+            let mut si = SurfaceInteraction {
+                it: Interaction {
+                    n,
+                    ..Default::default()
+                },
+                shading: Shading {
+                    n,
+                    dpdu: Vector3f::from([1., 0., 0.]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let mut ps = make_spectrum_param_set("Kd", vec![Spectrum::from(1.)]);
+            ps.add_float("sigma", vec![sigma]);
+            let m = create_matte_material(&TextureParams::new(
+                ps,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            ));
+            m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+            si.bsdf.expect("bsdf")
+        };
+        // Light arriving from, and reflected back towards, a grazing direction.
+        let w = Vector3f::from([0.8, 0., 0.2]).normalize();
+        let smooth = bsdf(0.).f(&w, &w, BxDFType::ALL).max_component_value();
+        let rough = bsdf(20.).f(&w, &w, BxDFType::ALL).max_component_value();
+        assert!((smooth - FRAC_1_PI).abs() < 1e-6, "{}", smooth);
+        assert!(rough > smooth, "{} <= {}", rough, smooth);
+    }
+
+    #[test]
+    fn bump_map() {
+        let n = Normal3f::from([0., 0., 1.]);
+        let mut si = SurfaceInteraction {
+            it: Interaction {
+                p: [0.3, 0.7, 0.1].into(),
+                n,
+                ..Default::default()
+            },
+            dpdu: Vector3f::from([1., 0., 0.]),
+            dpdv: Vector3f::from([0., 1., 0.]),
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                dpdv: Vector3f::from([0., 1., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut float_textures: HashMap<String, Arc<dyn Texture<Float>>> = HashMap::new();
+        float_textures.insert(
+            "bumps".to_string(),
+            Arc::new(WrinkledTexture::new(
+                IdentityMapping3D::new(Transform::identity()),
+                8,
+                0.5,
+            )),
+        );
+        let m = create_matte_material(&TextureParams::new(
+            make_texture_param_set("bumpmap", vec!["bumps".to_string()]),
+            Default::default(),
+            float_textures,
+            Default::default(),
+        ));
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        // The shading normal is perturbed, the geometric normal isn't.
+        assert_eq!(si.n, n);
+        assert_ne!(si.shading.n, n);
+        assert!(dot(Vector3f::from(si.shading.n), Vector3f::from(n)) > 0.);
+    }
+}
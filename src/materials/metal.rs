@@ -0,0 +1,208 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements the metal [Material], a rough conductor.
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{BeckmannDistribution, FresnelConductor, MicrofacetReflection, BSDF},
+        spectrum::{named_spectrum, Spectrum},
+        texture::Texture,
+    },
+    Float,
+};
+
+/// MetalMaterial models a conductor with index of refraction `eta` and absorption coefficient
+/// `k`, using a [MicrofacetReflection] BRDF with a [BeckmannDistribution] of microfacets.  The
+/// surface's `roughness` may be overridden separately in the u and v directions by
+/// `u_roughness` and `v_roughness`.  If `remap_roughness` is true the roughness values are in
+/// [0, 1] and remapped with [BeckmannDistribution::roughness_to_alpha].
+#[derive(Debug)]
+pub struct MetalMaterial {
+    eta: Arc<dyn Texture<Spectrum>>,
+    k: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    u_roughness: Option<Arc<dyn Texture<Float>>>,
+    v_roughness: Option<Arc<dyn Texture<Float>>>,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+    remap_roughness: bool,
+}
+
+impl MetalMaterial {
+    /// Create a new `MetalMaterial` with index of refraction `eta`, absorption coefficient `k` and
+    /// `roughness`, optionally overridden by `u_roughness` and `v_roughness`, and optionally
+    /// perturbed by the displacement texture `bump_map`.
+    pub fn new(
+        eta: Arc<dyn Texture<Spectrum>>,
+        k: Arc<dyn Texture<Spectrum>>,
+        roughness: Arc<dyn Texture<Float>>,
+        u_roughness: Option<Arc<dyn Texture<Float>>>,
+        v_roughness: Option<Arc<dyn Texture<Float>>>,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+        remap_roughness: bool,
+    ) -> MetalMaterial {
+        MetalMaterial {
+            eta,
+            k,
+            roughness,
+            u_roughness,
+            v_roughness,
+            bump_map,
+            remap_roughness,
+        }
+    }
+}
+
+impl Material for MetalMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let mut bsdf = BSDF::new(si, 1.);
+        let mut u_rough = self
+            .u_roughness
+            .as_ref()
+            .unwrap_or(&self.roughness)
+            .evaluate(si);
+        let mut v_rough = self
+            .v_roughness
+            .as_ref()
+            .unwrap_or(&self.roughness)
+            .evaluate(si);
+        if self.remap_roughness {
+            u_rough = BeckmannDistribution::roughness_to_alpha(u_rough);
+            v_rough = BeckmannDistribution::roughness_to_alpha(v_rough);
+        }
+        let fresnel = FresnelConductor::new(
+            Spectrum::new(1.),
+            self.eta.evaluate(si),
+            self.k.evaluate(si),
+        );
+        bsdf.add(Box::new(MicrofacetReflection::new(
+            Spectrum::new(1.),
+            Box::new(BeckmannDistribution::new(u_rough, v_rough)),
+            Box::new(fresnel),
+        )));
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `MetalMaterial` from the given `TextureParams`.  Like pbrt, the metal is copper
+/// unless "eta" and "k" are given.
+pub fn create_metal_material(mp: &TextureParams) -> MetalMaterial {
+    let eta = mp.get_spectrum_texture("eta", named_spectrum("metal-Cu-eta").unwrap());
+    let k = mp.get_spectrum_texture("k", named_spectrum("metal-Cu-k").unwrap());
+    let roughness = mp.get_float_texture("roughness", 0.01);
+    let u_roughness = mp.get_float_texture_or_none("uroughness");
+    let v_roughness = mp.get_float_texture_or_none("vroughness");
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    let remap_roughness = mp.find_bool("remaproughness", true);
+    MetalMaterial::new(
+        eta,
+        k,
+        roughness,
+        u_roughness,
+        v_roughness,
+        bump_map,
+        remap_roughness,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_metal_material;
+    use crate::{
+        core::{
+            geometry::{Normal3f, Vector3f},
+            interaction::{Interaction, Shading, SurfaceInteraction},
+            material::{Material, TransportMode},
+            paramset::{ParamSet, TextureParams},
+            reflection::BxDFType,
+            spectrum::Spectrum,
+        },
+        Float,
+    };
+
+    fn surface() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 0., 1.]);
+        SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Returns the light reflected towards the mirror direction of light arriving at 30 degrees
+    /// from the normal.
+    fn reflected(params: ParamSet) -> Spectrum {
+        let m = create_metal_material(&TextureParams::new(
+            params,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        let mut si = surface();
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::GLOSSY),
+            1
+        );
+        let wo = Vector3f::from([0.5, 0., (0.75 as Float).sqrt()]);
+        let wi = Vector3f::from([-wo.x, -wo.y, wo.z]);
+        bsdf.f(&wo, &wi, BxDFType::ALL)
+    }
+
+    #[test]
+    fn copper_is_reddish() {
+        let [r, g, b] = reflected(ParamSet::default()).to_rgb();
+        assert!(r > g && g > b, "copper reflects {:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn named_metals() {
+        let mut params = ParamSet::default();
+        params.add_sampled_spectrum_files("eta", vec!["metal-Ag-eta".to_string()]);
+        params.add_sampled_spectrum_files("k", vec!["metal-Ag-k".to_string()]);
+        let [r, g, b] = reflected(params).to_rgb();
+        // Silver is a nearly neutral reflector.
+        assert!(
+            r > 0. && (r - b).abs() < 0.1 * r,
+            "silver reflects {:?}",
+            [r, g, b]
+        );
+        let copper = reflected(ParamSet::default()).to_rgb();
+        assert!(b > copper[2], "silver {:?} copper {:?}", [r, g, b], copper);
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a perfectly specular mirror [Material].
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{FresnelNoOp, SpecularReflection, BSDF},
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// MirrorMaterial models a perfect mirror that reflects the fraction `kr` of incident light in
+/// the mirror direction.
+#[derive(Debug)]
+pub struct MirrorMaterial {
+    kr: Arc<dyn Texture<Spectrum>>,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+}
+
+impl MirrorMaterial {
+    /// Create a new `MirrorMaterial` with reflectance `kr`, optionally perturbed by the
+    /// displacement texture `bump_map`.
+    pub fn new(
+        kr: Arc<dyn Texture<Spectrum>>,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+    ) -> MirrorMaterial {
+        MirrorMaterial { kr, bump_map }
+    }
+}
+
+impl Material for MirrorMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let mut bsdf = BSDF::new(si, 1.);
+        let r = self.kr.evaluate(si).clamp_nonnegative();
+        if !r.is_black() {
+            bsdf.add(Box::new(SpecularReflection::new(r, Box::new(FresnelNoOp))));
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `MirrorMaterial` from the given `TextureParams`.
+pub fn create_mirror_material(mp: &TextureParams) -> MirrorMaterial {
+    let kr = mp.get_spectrum_texture("Kr", Spectrum::from(0.9));
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    MirrorMaterial::new(kr, bump_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_mirror_material;
+    use crate::core::{
+        geometry::{dot, Normal3f, Point2f, Vector3f},
+        interaction::{Interaction, Shading, SurfaceInteraction},
+        material::{Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{reflect, BxDFType},
+    };
+
+    #[test]
+    fn reflects_along_specular_direction() {
+        let n = Normal3f::from([0., 1., 0.]);
+        let mut si = SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let m = create_mirror_material(&TextureParams::default());
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 1);
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::SPECULAR),
+            1
+        );
+
+        let nv = Vector3f::from(n);
+        for wo in &[
+            [0., 1., 0.],
+            [1., 1., 0.],
+            [-0.2, 0.5, 0.7],
+            [0.9, 0.1, -0.3],
+        ] {
+            let wo = Vector3f::from(*wo).normalize();
+            for u in &[[0.1, 0.2], [0.5, 0.5], [0.9, 0.8]] {
+                let s = bsdf.sample_f(&wo, Point2f::from(*u), BxDFType::ALL);
+                assert!((s.wi - reflect(&wo, &nv)).length() < 1e-6);
+                assert_eq!(s.sampled_type, BxDFType::REFLECTION | BxDFType::SPECULAR);
+                assert_eq!(s.pdf, 1.);
+                assert!(((s.f * dot(s.wi, nv).abs()).max_component_value() - 0.9).abs() < 1e-5);
+                // Delta distributions can't be evaluated for arbitrary direction pairs.
+                assert!(bsdf.f(&wo, &s.wi, BxDFType::ALL).is_black());
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a plastic [Material], a mixture of diffuse and glossy scattering.
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{
+            BeckmannDistribution, FresnelDielectric, LambertianReflection, MicrofacetReflection,
+            BSDF,
+        },
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// PlasticMaterial models plastic as a diffuse base with reflectance `kd` under a glossy specular
+/// layer with reflectance `ks`.  `roughness` controls the size of the specular highlight, if
+/// `remap_roughness` is true it is in [0, 1] and remapped with
+/// [BeckmannDistribution::roughness_to_alpha], otherwise it is used as the distribution's alpha
+/// directly.
+#[derive(Debug)]
+pub struct PlasticMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    ks: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    remap_roughness: bool,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+}
+
+impl PlasticMaterial {
+    /// Create a new `PlasticMaterial` with diffuse reflectance `kd`, specular reflectance `ks`
+    /// and specular `roughness`, optionally perturbed by the displacement texture `bump_map`.
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum>>,
+        ks: Arc<dyn Texture<Spectrum>>,
+        roughness: Arc<dyn Texture<Float>>,
+        remap_roughness: bool,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+    ) -> PlasticMaterial {
+        PlasticMaterial {
+            kd,
+            ks,
+            roughness,
+            remap_roughness,
+            bump_map,
+        }
+    }
+}
+
+impl Material for PlasticMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let mut bsdf = BSDF::new(si, 1.);
+        // Initialize diffuse component of plastic material
+        let kd = self.kd.evaluate(si).clamp_nonnegative();
+        if !kd.is_black() {
+            bsdf.add(Box::new(LambertianReflection::new(kd)));
+        }
+
+        // Initialize specular component of plastic material
+        let ks = self.ks.evaluate(si).clamp_nonnegative();
+        if !ks.is_black() {
+            let fresnel = FresnelDielectric::new(1.5, 1.);
+            // Create microfacet distribution _distrib_ for plastic material
+            let mut rough = self.roughness.evaluate(si);
+            if self.remap_roughness {
+                rough = BeckmannDistribution::roughness_to_alpha(rough);
+            }
+            let distrib = BeckmannDistribution::new(rough, rough);
+            bsdf.add(Box::new(MicrofacetReflection::new(
+                ks,
+                Box::new(distrib),
+                Box::new(fresnel),
+            )));
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `PlasticMaterial` from the given `TextureParams`.
+pub fn create_plastic_material(mp: &TextureParams) -> PlasticMaterial {
+    let kd = mp.get_spectrum_texture("Kd", Spectrum::from(0.25));
+    let ks = mp.get_spectrum_texture("Ks", Spectrum::from(0.25));
+    let roughness = mp.get_float_texture("roughness", 0.1);
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    let remap_roughness = mp.find_bool("remaproughness", true);
+    PlasticMaterial::new(kd, ks, roughness, remap_roughness, bump_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_plastic_material;
+    use crate::core::{
+        geometry::{Normal3f, Vector3f},
+        interaction::{Interaction, Shading, SurfaceInteraction},
+        material::{Material, TransportMode},
+        paramset::{testutils::make_spectrum_param_set, TextureParams},
+        reflection::BxDFType,
+        spectrum::Spectrum,
+    };
+
+    fn surface() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 0., 1.]);
+        SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn diffuse_and_glossy_bsdf() {
+        let mut si = surface();
+        let m = create_plastic_material(&TextureParams::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 2);
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::DIFFUSE),
+            1
+        );
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::GLOSSY),
+            1
+        );
+        assert_eq!(bsdf.num_components(BxDFType::SPECULAR), 0);
+
+        // The glossy lobe makes the mirror direction brighter than an off-specular one.
+        let wo = Vector3f::from([0.5, 0., 1.]).normalize();
+        let mirror = Vector3f::from([-wo.x, -wo.y, wo.z]);
+        let off = Vector3f::from([0.5, 0.5, 1.]).normalize();
+        let glossy = BxDFType::REFLECTION | BxDFType::GLOSSY;
+        assert!(
+            bsdf.f(&wo, &mirror, glossy).max_component_value()
+                > bsdf.f(&wo, &off, glossy).max_component_value()
+        );
+    }
+
+    #[test]
+    fn black_specular() {
+        let mut si = surface();
+        let m = create_plastic_material(&TextureParams::new(
+            make_spectrum_param_set("Ks", vec![Spectrum::from(0.)]),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(bsdf.num_components(BxDFType::ALL), 1);
+        assert_eq!(bsdf.num_components(BxDFType::GLOSSY), 0);
+    }
+}
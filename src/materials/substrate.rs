@@ -0,0 +1,170 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements the substrate [Material], a diffuse base under a glossy coating whose reflectance
+//! increases at grazing angles.
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{BeckmannDistribution, FresnelBlend, BSDF},
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// SubstrateMaterial models a diffuse surface with reflectance `kd` under a glossy coating with
+/// normal incidence reflectance `ks`, using a [FresnelBlend] BRDF.  The coating's roughness is
+/// given separately in the u and v directions by `roughness_u` and `roughness_v`.
+#[derive(Debug)]
+pub struct SubstrateMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    ks: Arc<dyn Texture<Spectrum>>,
+    roughness_u: Arc<dyn Texture<Float>>,
+    roughness_v: Arc<dyn Texture<Float>>,
+    remap_roughness: bool,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+}
+
+impl SubstrateMaterial {
+    /// Create a new `SubstrateMaterial` with diffuse reflectance `kd`, coating reflectance `ks`
+    /// and coating roughness `roughness_u` and `roughness_v`, optionally perturbed by the
+    /// displacement texture `bump_map`.
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum>>,
+        ks: Arc<dyn Texture<Spectrum>>,
+        roughness_u: Arc<dyn Texture<Float>>,
+        roughness_v: Arc<dyn Texture<Float>>,
+        remap_roughness: bool,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+    ) -> SubstrateMaterial {
+        SubstrateMaterial {
+            kd,
+            ks,
+            roughness_u,
+            roughness_v,
+            remap_roughness,
+            bump_map,
+        }
+    }
+}
+
+impl Material for SubstrateMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let mut bsdf = BSDF::new(si, 1.);
+        let d = self.kd.evaluate(si).clamp_nonnegative();
+        let s = self.ks.evaluate(si).clamp_nonnegative();
+        if !d.is_black() || !s.is_black() {
+            let mut rough_u = self.roughness_u.evaluate(si);
+            let mut rough_v = self.roughness_v.evaluate(si);
+            if self.remap_roughness {
+                rough_u = BeckmannDistribution::roughness_to_alpha(rough_u);
+                rough_v = BeckmannDistribution::roughness_to_alpha(rough_v);
+            }
+            bsdf.add(Box::new(FresnelBlend::new(
+                d,
+                s,
+                Box::new(BeckmannDistribution::new(rough_u, rough_v)),
+            )));
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates a `SubstrateMaterial` from the given `TextureParams`.
+pub fn create_substrate_material(mp: &TextureParams) -> SubstrateMaterial {
+    let kd = mp.get_spectrum_texture("Kd", Spectrum::from(0.5));
+    let ks = mp.get_spectrum_texture("Ks", Spectrum::from(0.5));
+    let roughness_u = mp.get_float_texture("uroughness", 0.1);
+    let roughness_v = mp.get_float_texture("vroughness", 0.1);
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    let remap_roughness = mp.find_bool("remaproughness", true);
+    SubstrateMaterial::new(kd, ks, roughness_u, roughness_v, remap_roughness, bump_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_substrate_material;
+    use crate::core::{
+        geometry::{Normal3f, Vector3f},
+        interaction::{Interaction, Shading, SurfaceInteraction},
+        material::{Material, TransportMode},
+        paramset::TextureParams,
+        reflection::BxDFType,
+        rng::Rng,
+        sampling::cosine_sample_hemisphere,
+    };
+
+    #[test]
+    fn reciprocity() {
+        let n = Normal3f::from([0., 0., 1.]);
+        let mut si = SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let m = create_substrate_material(&TextureParams::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        m.compute_scattering_functions(&mut si, TransportMode::Radiance, true);
+        let bsdf = si.bsdf.expect("bsdf");
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::GLOSSY),
+            1
+        );
+
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let wo = cosine_sample_hemisphere([rng.uniform_float(), rng.uniform_float()].into());
+            let wi = cosine_sample_hemisphere([rng.uniform_float(), rng.uniform_float()].into());
+            let f_oi = bsdf.f(&wo, &wi, BxDFType::ALL).max_component_value();
+            let f_io = bsdf.f(&wi, &wo, BxDFType::ALL).max_component_value();
+            assert!(f_oi > 0.);
+            assert!(
+                (f_oi - f_io).abs() <= 1e-4 * f_oi,
+                "f({:?}, {:?}) = {} != {}",
+                wo,
+                wi,
+                f_oi,
+                f_io
+            );
+        }
+    }
+}
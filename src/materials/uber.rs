@@ -0,0 +1,281 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements the "uber" [Material], a kitchen-sink combination of diffuse, glossy and specular
+//! scattering with partial transparency.
+//!
+//! [Material]: crate::core::material::Material
+
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        material::{bump, Material, TransportMode},
+        paramset::TextureParams,
+        reflection::{
+            BeckmannDistribution, FresnelDielectric, LambertianReflection, MicrofacetReflection,
+            SpecularReflection, SpecularTransmission, BSDF,
+        },
+        spectrum::Spectrum,
+        texture::Texture,
+    },
+    Float,
+};
+
+/// UberMaterial combines a diffuse lobe `kd`, a glossy lobe `ks`, perfect specular reflection
+/// `kr` and perfect specular transmission `kt` in a single material.  The glossy lobe's roughness
+/// may be set per direction with `roughness_u` and `roughness_v`, falling back to `roughness`.
+/// `opacity` below one lets a portion of the light pass straight through the surface.  An optional
+/// `bump_map` perturbs the shading normal.
+#[derive(Debug)]
+pub struct UberMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    ks: Arc<dyn Texture<Spectrum>>,
+    kr: Arc<dyn Texture<Spectrum>>,
+    kt: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    roughness_u: Option<Arc<dyn Texture<Float>>>,
+    roughness_v: Option<Arc<dyn Texture<Float>>>,
+    opacity: Arc<dyn Texture<Spectrum>>,
+    eta: Arc<dyn Texture<Float>>,
+    remap_roughness: bool,
+    bump_map: Option<Arc<dyn Texture<Float>>>,
+}
+
+impl UberMaterial {
+    /// Create a new `UberMaterial`.  See the type documentation for the meaning of each texture.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum>>,
+        ks: Arc<dyn Texture<Spectrum>>,
+        kr: Arc<dyn Texture<Spectrum>>,
+        kt: Arc<dyn Texture<Spectrum>>,
+        roughness: Arc<dyn Texture<Float>>,
+        roughness_u: Option<Arc<dyn Texture<Float>>>,
+        roughness_v: Option<Arc<dyn Texture<Float>>>,
+        opacity: Arc<dyn Texture<Spectrum>>,
+        eta: Arc<dyn Texture<Float>>,
+        remap_roughness: bool,
+        bump_map: Option<Arc<dyn Texture<Float>>>,
+    ) -> UberMaterial {
+        UberMaterial {
+            kd,
+            ks,
+            kr,
+            kt,
+            roughness,
+            roughness_u,
+            roughness_v,
+            opacity,
+            eta,
+            remap_roughness,
+            bump_map,
+        }
+    }
+}
+
+impl Material for UberMaterial {
+    fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        mode: TransportMode,
+        _allow_multiple_lobes: bool,
+    ) {
+        // Perform bump mapping with _bumpMap_, if present
+        if let Some(bump_map) = &self.bump_map {
+            bump(bump_map, si);
+        }
+        let e = self.eta.evaluate(si);
+        let op = self.opacity.evaluate(si).clamp_nonnegative();
+        let t = (Spectrum::new(1.) + op.clone() * -1.).clamp_nonnegative();
+        // Light passing through a partially transparent surface isn't refracted, so the BSDF
+        // only gets the material's index of refraction when it's fully opaque.
+        let mut bsdf = if !t.is_black() {
+            let mut bsdf = BSDF::new(si, 1.);
+            bsdf.add(Box::new(SpecularTransmission::new(t, 1., 1., mode)));
+            bsdf
+        } else {
+            BSDF::new(si, e)
+        };
+
+        let kd = op.clone() * self.kd.evaluate(si).clamp_nonnegative();
+        if !kd.is_black() {
+            bsdf.add(Box::new(LambertianReflection::new(kd)));
+        }
+
+        let ks = op.clone() * self.ks.evaluate(si).clamp_nonnegative();
+        if !ks.is_black() {
+            let mut rough_u = self
+                .roughness_u
+                .as_ref()
+                .unwrap_or(&self.roughness)
+                .evaluate(si);
+            let mut rough_v = self
+                .roughness_v
+                .as_ref()
+                .unwrap_or(&self.roughness)
+                .evaluate(si);
+            if self.remap_roughness {
+                rough_u = BeckmannDistribution::roughness_to_alpha(rough_u);
+                rough_v = BeckmannDistribution::roughness_to_alpha(rough_v);
+            }
+            bsdf.add(Box::new(MicrofacetReflection::new(
+                ks,
+                Box::new(BeckmannDistribution::new(rough_u, rough_v)),
+                Box::new(FresnelDielectric::new(1., e)),
+            )));
+        }
+
+        let kr = op.clone() * self.kr.evaluate(si).clamp_nonnegative();
+        if !kr.is_black() {
+            bsdf.add(Box::new(SpecularReflection::new(
+                kr,
+                Box::new(FresnelDielectric::new(1., e)),
+            )));
+        }
+
+        let kt = op * self.kt.evaluate(si).clamp_nonnegative();
+        if !kt.is_black() {
+            bsdf.add(Box::new(SpecularTransmission::new(kt, 1., e, mode)));
+        }
+        si.bsdf = Some(bsdf);
+    }
+}
+
+/// Creates an `UberMaterial` from the given `TextureParams`.  The index of refraction is read from
+/// "eta", or "index" if that isn't set.
+pub fn create_uber_material(mp: &TextureParams) -> UberMaterial {
+    let kd = mp.get_spectrum_texture("Kd", Spectrum::from(0.25));
+    let ks = mp.get_spectrum_texture("Ks", Spectrum::from(0.25));
+    let kr = mp.get_spectrum_texture("Kr", Spectrum::from(0.));
+    let kt = mp.get_spectrum_texture("Kt", Spectrum::from(0.));
+    let roughness = mp.get_float_texture("roughness", 0.1);
+    let roughness_u = mp.get_float_texture_or_none("uroughness");
+    let roughness_v = mp.get_float_texture_or_none("vroughness");
+    let eta = mp
+        .get_float_texture_or_none("eta")
+        .unwrap_or_else(|| mp.get_float_texture("index", 1.5));
+    let opacity = mp.get_spectrum_texture("opacity", Spectrum::from(1.));
+    let bump_map = mp.get_float_texture_or_none("bumpmap");
+    let remap_roughness = mp.find_bool("remaproughness", true);
+    UberMaterial::new(
+        kd,
+        ks,
+        kr,
+        kt,
+        roughness,
+        roughness_u,
+        roughness_v,
+        opacity,
+        eta,
+        remap_roughness,
+        bump_map,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_uber_material;
+    use crate::{
+        core::{
+            geometry::{Normal3f, Vector3f},
+            interaction::{Interaction, Shading, SurfaceInteraction},
+            material::{Material, TransportMode},
+            paramset::{testutils::make_spectrum_param_set, ParamSet, TextureParams},
+            reflection::BxDFType,
+            spectrum::Spectrum,
+        },
+        materials::matte::create_matte_material,
+    };
+
+    fn surface() -> SurfaceInteraction {
+        let n = Normal3f::from([0., 0., 1.]);
+        SurfaceInteraction {
+            it: Interaction {
+                n,
+                ..Default::default()
+            },
+            shading: Shading {
+                n,
+                dpdu: Vector3f::from([1., 0., 0.]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn texture_params(ps: ParamSet) -> TextureParams {
+        TextureParams::new(
+            ps,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn diffuse_only_matches_matte() {
+        let kd = Spectrum::from_rgb([0.2, 0.4, 0.6]);
+        let mut ps = make_spectrum_param_set("Kd", vec![kd.clone()]);
+        ps.add_rgb_spectrum("Ks", vec![0., 0., 0.]);
+        let mut uber_si = surface();
+        create_uber_material(&texture_params(ps)).compute_scattering_functions(
+            &mut uber_si,
+            TransportMode::Radiance,
+            true,
+        );
+        let mut matte_si = surface();
+        create_matte_material(&texture_params(make_spectrum_param_set("Kd", vec![kd])))
+            .compute_scattering_functions(&mut matte_si, TransportMode::Radiance, true);
+
+        let uber = uber_si.bsdf.expect("uber bsdf");
+        let matte = matte_si.bsdf.expect("matte bsdf");
+        assert_eq!(uber.num_components(BxDFType::ALL), 1);
+        let wo = Vector3f::from([0.3, 0.2, 1.]).normalize();
+        for wi in &[
+            Vector3f::from([0., 0., 1.]),
+            Vector3f::from([-0.5, 0.1, 0.4]).normalize(),
+            Vector3f::from([0.5, 0.5, -0.4]).normalize(),
+        ] {
+            assert_eq!(
+                uber.f(&wo, wi, BxDFType::ALL),
+                matte.f(&wo, wi, BxDFType::ALL)
+            );
+        }
+    }
+
+    #[test]
+    fn opacity_passes_light_through() {
+        let mut ps = make_spectrum_param_set("opacity", vec![Spectrum::from(0.25)]);
+        ps.add_rgb_spectrum("Ks", vec![0., 0., 0.]);
+        let mut si = surface();
+        create_uber_material(&texture_params(ps)).compute_scattering_functions(
+            &mut si,
+            TransportMode::Radiance,
+            true,
+        );
+        let bsdf = si.bsdf.expect("bsdf");
+        // The pass-through lobe doesn't bend light, so the BSDF's eta is 1.
+        assert_eq!(bsdf.eta, 1.);
+        assert_eq!(
+            bsdf.num_components(BxDFType::TRANSMISSION | BxDFType::SPECULAR),
+            1
+        );
+        assert_eq!(
+            bsdf.num_components(BxDFType::REFLECTION | BxDFType::DIFFUSE),
+            1
+        );
+    }
+}
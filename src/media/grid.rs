@@ -0,0 +1,393 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a heterogeneous [Medium] whose density is given by a 3D grid of samples, as
+//! referenced by `MakeNamedMedium "name" "string type" "heterogeneous"` in scene files.
+//!
+//! [Medium]: crate::core::medium::Medium
+use std::sync::Arc;
+
+use log::error;
+
+use crate::{
+    core::{
+        geometry::{Bounds3f, Point3f, Ray},
+        interaction::MediumInteraction,
+        medium::{HenyeyGreenstein, Medium},
+        paramset::ParamSet,
+        sampler::Sampler,
+        spectrum::Spectrum,
+        transform::Transform,
+    },
+    lerp, Float,
+};
+
+/// GridDensityMedium is a medium whose scattering coefficients are scaled by a density
+/// trilinearly interpolated from a regular grid of samples.  The grid covers the unit cube in
+/// medium space.
+#[derive(Debug)]
+pub struct GridDensityMedium {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    g: Float,
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    world_to_medium: Transform,
+    density: Vec<Float>,
+    sigma_t: Float,
+    inv_max_density: Float,
+}
+
+impl GridDensityMedium {
+    /// Create a new `GridDensityMedium` with the `nx` x `ny` x `nz` samples in `density`, stored
+    /// with x varying fastest.  `medium_to_world` maps the unit cube the grid covers to world
+    /// space.  Only attenuation coefficients `sigma_a + sigma_s` that are the same at all
+    /// wavelengths are supported, otherwise the first channel is used.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sigma_a: Spectrum,
+        sigma_s: Spectrum,
+        g: Float,
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        medium_to_world: &Transform,
+        density: Vec<Float>,
+    ) -> GridDensityMedium {
+        assert_eq!(density.len(), nx * ny * nz);
+        let sigma_t = sigma_a.clone() + sigma_s.clone();
+        let channels = sigma_t.channels();
+        if channels.iter().any(|&c| c != channels[0]) {
+            error!("GridDensityMedium requires a spectrally uniform attenuation coefficient!");
+        }
+        let sigma_t = channels[0];
+        let max_density = density.iter().cloned().fold(0., Float::max);
+        GridDensityMedium {
+            sigma_a,
+            sigma_s,
+            g,
+            nx,
+            ny,
+            nz,
+            world_to_medium: medium_to_world.inverse(),
+            density,
+            sigma_t,
+            inv_max_density: 1. / max_density,
+        }
+    }
+
+    /// Returns the density sample at integer grid coordinates `(x, y, z)`, or zero outside the
+    /// grid.
+    fn d(&self, x: isize, y: isize, z: isize) -> Float {
+        if x < 0
+            || y < 0
+            || z < 0
+            || x as usize >= self.nx
+            || y as usize >= self.ny
+            || z as usize >= self.nz
+        {
+            return 0.;
+        }
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        self.density[(z * self.ny + y) * self.nx + x]
+    }
+
+    /// Returns the density at `p` in medium space, interpolated from the eight nearest samples.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{geometry::Point3f, spectrum::Spectrum, transform::Transform},
+    ///     media::grid::GridDensityMedium,
+    /// };
+    ///
+    /// // Density increasing along x.
+    /// let m = GridDensityMedium::new(
+    ///     Spectrum::new(1.),
+    ///     Spectrum::new(0.),
+    ///     0.,
+    ///     2,
+    ///     1,
+    ///     1,
+    ///     &Transform::identity(),
+    ///     vec![0., 1.],
+    /// );
+    /// assert_eq!(m.density(Point3f::from([0.25, 0.5, 0.5])), 0.);
+    /// assert_eq!(m.density(Point3f::from([0.5, 0.5, 0.5])), 0.5);
+    /// assert_eq!(m.density(Point3f::from([0.75, 0.5, 0.5])), 1.);
+    /// ```
+    pub fn density(&self, p: Point3f) -> Float {
+        // Compute voxel coordinates and offsets for _p_
+        let p_samples = Point3f::from([
+            p.x * self.nx as Float - 0.5,
+            p.y * self.ny as Float - 0.5,
+            p.z * self.nz as Float - 0.5,
+        ]);
+        let pi = p_samples.floor();
+        let (x, y, z) = (pi.x as isize, pi.y as isize, pi.z as isize);
+        let d = p_samples - pi;
+
+        // Trilinearly interpolate density values to compute local density
+        let d00 = lerp(d.x, self.d(x, y, z), self.d(x + 1, y, z));
+        let d10 = lerp(d.x, self.d(x, y + 1, z), self.d(x + 1, y + 1, z));
+        let d01 = lerp(d.x, self.d(x, y, z + 1), self.d(x + 1, y, z + 1));
+        let d11 = lerp(d.x, self.d(x, y + 1, z + 1), self.d(x + 1, y + 1, z + 1));
+        let d0 = lerp(d.y, d00, d10);
+        let d1 = lerp(d.y, d01, d11);
+        lerp(d.z, d0, d1)
+    }
+
+    /// Returns `ray` transformed to medium space with a normalized direction, along with the
+    /// parametric range where it overlaps the unit cube, or `None` if it misses.
+    fn medium_ray(&self, ray: &Ray) -> Option<(Ray, Float, Float)> {
+        let d_len = ray.d.length();
+        let ray = self.world_to_medium.transform_ray(&Ray::new(
+            ray.o,
+            ray.d / d_len,
+            ray.t_max.get() * d_len,
+            ray.time,
+            None,
+        ));
+        // Compute $[t_{min}, t_{max}]$ interval of _ray_'s overlap with medium bounds
+        let b = Bounds3f::from([[0., 0., 0.], [1., 1., 1.]]);
+        let (t_min, t_max) = b.intersect_p(&ray)?;
+        Some((ray, t_min, t_max))
+    }
+
+    /// Returns the absorption coefficient at unit density.
+    pub fn sigma_a(&self) -> &Spectrum {
+        &self.sigma_a
+    }
+
+    /// Returns the scattering coefficient at unit density.
+    pub fn sigma_s(&self) -> &Spectrum {
+        &self.sigma_s
+    }
+
+    /// Returns the asymmetry parameter of the Henyey-Greenstein phase function.
+    pub fn g(&self) -> Float {
+        self.g
+    }
+}
+
+impl Medium for GridDensityMedium {
+    /// Returns the transmittance along `ray` from its origin to `ray.t_max`.  The estimate is
+    /// computed with ratio tracking, using `sampler` for the random distances, so it is unbiased
+    /// but noisy.
+    fn tr(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Spectrum {
+        if self.sigma_t <= 0. || self.inv_max_density.is_infinite() {
+            return Spectrum::new(1.);
+        }
+        let (ray, t_min, t_max) = match self.medium_ray(ray) {
+            Some(r) => r,
+            None => return Spectrum::new(1.),
+        };
+
+        // Perform ratio tracking to estimate the transmittance value
+        let mut tr = 1.;
+        let mut t = t_min;
+        loop {
+            t -= (1. - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
+            if t >= t_max {
+                break;
+            }
+            let density = self.density(ray.at(t));
+            tr *= 1. - (density * self.inv_max_density).max(0.);
+            // Added after book publication: when transmittance gets low, start applying Russian
+            // roulette to terminate sampling.
+            const RR_THRESHOLD: Float = 0.1;
+            if tr < RR_THRESHOLD {
+                let q = (1. - tr).max(0.05);
+                if sampler.get_1d() < q {
+                    return Spectrum::new(0.);
+                }
+                tr /= 1. - q;
+            }
+        }
+        Spectrum::new(tr)
+    }
+
+    /// Samples a scattering event along `ray` with delta tracking.  The returned value is the
+    /// single scattering albedo when a scattering event is found, and one otherwise.
+    fn sample(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+    ) -> (Spectrum, Option<MediumInteraction>) {
+        if self.sigma_t <= 0. || self.inv_max_density.is_infinite() {
+            return (Spectrum::new(1.), None);
+        }
+        let (m_ray, t_min, t_max) = match self.medium_ray(ray) {
+            Some(r) => r,
+            None => return (Spectrum::new(1.), None),
+        };
+
+        // Run delta-tracking iterations to sample a medium interaction
+        let d_len = ray.d.length();
+        let mut t = t_min;
+        loop {
+            t -= (1. - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
+            if t >= t_max {
+                return (Spectrum::new(1.), None);
+            }
+            if self.density(m_ray.at(t)) * self.inv_max_density > sampler.get_1d() {
+                // Populate _mi_ with medium interaction information and return
+                let mi = MediumInteraction::new(
+                    ray.at(t / d_len),
+                    -ray.d,
+                    ray.time,
+                    ray.medium.clone(),
+                    Arc::new(HenyeyGreenstein::new(self.g)),
+                );
+                return (self.sigma_s.clone() / self.sigma_t, Some(mi));
+            }
+        }
+    }
+}
+
+/// Creates a `GridDensityMedium` from the given `params`, the grid's corners `p0` and `p1` are
+/// transformed to world space with `medium_to_world`.  Returns `None` and logs an error if the
+/// density samples are missing or don't match the grid's resolution.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{paramset::testutils::make_float_param_set, transform::Transform},
+///     media::grid::create_grid_density_medium,
+/// };
+///
+/// let params = make_float_param_set("density", vec![0.5]);
+/// assert!(create_grid_density_medium(&params, &Transform::identity()).is_some());
+/// let params = make_float_param_set("scale", vec![0.5]);
+/// assert!(create_grid_density_medium(&params, &Transform::identity()).is_none());
+/// ```
+pub fn create_grid_density_medium(
+    params: &ParamSet,
+    medium_to_world: &Transform,
+) -> Option<GridDensityMedium> {
+    // TODO(wathiede): support the named "preset" scattering properties.
+    let scale = params.find_one_float("scale", 1.);
+    let g = params.find_one_float("g", 0.);
+    let sigma_a =
+        params.find_one_spectrum("sigma_a", Spectrum::from_rgb([0.0011, 0.0024, 0.014])) * scale;
+    let sigma_s =
+        params.find_one_spectrum("sigma_s", Spectrum::from_rgb([2.55, 3.21, 3.77])) * scale;
+    let density = match params.find_float("density") {
+        Some(density) => density,
+        None => {
+            error!("No \"density\" values provided for heterogeneous medium?");
+            return None;
+        }
+    };
+    let nx = params.find_one_int("nx", 1);
+    let ny = params.find_one_int("ny", 1);
+    let nz = params.find_one_int("nz", 1);
+    if nx < 1 || ny < 1 || nz < 1 || density.len() != (nx * ny * nz) as usize {
+        error!(
+            "GridDensityMedium has {} density values; expected nx*ny*nz = {}",
+            density.len(),
+            nx * ny * nz
+        );
+        return None;
+    }
+    let p0 = params.find_one_point3f("p0", Point3f::from([0., 0., 0.]));
+    let p1 = params.find_one_point3f("p1", Point3f::from([1., 1., 1.]));
+    let data_to_medium = Transform::translate([p0.x, p0.y, p0.z])
+        * Transform::scale(p1.x - p0.x, p1.y - p0.y, p1.z - p0.z);
+    Some(GridDensityMedium::new(
+        sigma_a,
+        sigma_s,
+        g,
+        nx as usize,
+        ny as usize,
+        nz as usize,
+        &(*medium_to_world * data_to_medium),
+        density,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridDensityMedium;
+    use crate::{
+        core::{
+            geometry::{Point3f, Ray, Vector3f},
+            medium::Medium,
+            spectrum::Spectrum,
+            transform::Transform,
+        },
+        samplers::stratified::StratifiedSampler,
+        Float,
+    };
+
+    fn medium(density: Vec<Float>, t: &Transform) -> GridDensityMedium {
+        GridDensityMedium::new(
+            Spectrum::new(0.5),
+            Spectrum::new(0.5),
+            0.,
+            2,
+            2,
+            2,
+            t,
+            density,
+        )
+    }
+
+    fn ray(t_max: Float) -> Ray {
+        Ray::new(
+            Point3f::from([0.5, 0.5, -1.]),
+            Vector3f::from([0., 0., 2.]),
+            t_max,
+            0.,
+            None,
+        )
+    }
+
+    #[test]
+    fn zero_density() {
+        let m = medium(vec![0.; 8], &Transform::identity());
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        assert_eq!(m.tr(&ray(Float::INFINITY), &mut sampler), Spectrum::new(1.));
+    }
+
+    #[test]
+    fn uniform_density() {
+        // Scale the unit cube to [0, 2] along z, so the ray covers a distance of 2 in the
+        // medium with sigma_t 1.
+        let m = medium(vec![1.; 8], &Transform::scale(1., 1., 2.));
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        // Rays that miss, or end before, the medium aren't attenuated.
+        assert_eq!(m.tr(&ray(0.5), &mut sampler), Spectrum::new(1.));
+        let miss = Ray::new(
+            Point3f::from([5., 0.5, -1.]),
+            Vector3f::from([0., 0., 1.]),
+            Float::INFINITY,
+            0.,
+            None,
+        );
+        assert_eq!(m.tr(&miss, &mut sampler), Spectrum::new(1.));
+
+        // Outside the outer sample centers the density is interpolated towards zero, so it
+        // ramps from 0.5 at the faces to 1 over [0, 0.5] and [1.5, 2], giving an optical depth
+        // of 1.75.
+        const N: usize = 20_000;
+        let mean = (0..N)
+            .map(|_| m.tr(&ray(Float::INFINITY), &mut sampler).channels()[0])
+            .sum::<Float>()
+            / N as Float;
+        let want = (-1.75 as Float).exp();
+        assert!((mean - want).abs() < 0.01, "mean {} want {}", mean, want);
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Medium] with constant scattering properties throughout, as referenced by
+//! `MakeNamedMedium "name" "string type" "homogeneous"` in scene files.
+//!
+//! [Medium]: crate::core::medium::Medium
+use std::sync::Arc;
+
+use crate::{
+    core::{
+        geometry::Ray,
+        interaction::MediumInteraction,
+        medium::{HenyeyGreenstein, Medium},
+        paramset::ParamSet,
+        sampler::Sampler,
+        spectrum::Spectrum,
+    },
+    Float,
+};
+
+/// HomogeneousMedium is a medium with the same absorption and scattering coefficients
+/// everywhere, so its transmittance is given in closed form by the Beer-Lambert law.
+#[derive(Debug)]
+pub struct HomogeneousMedium {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    sigma_t: Spectrum,
+    g: Float,
+}
+
+impl HomogeneousMedium {
+    /// Create a new `HomogeneousMedium` with absorption coefficient `sigma_a`, scattering
+    /// coefficient `sigma_s` and Henyey-Greenstein asymmetry parameter `g`.
+    pub fn new(sigma_a: Spectrum, sigma_s: Spectrum, g: Float) -> HomogeneousMedium {
+        let sigma_t = sigma_a.clone() + sigma_s.clone();
+        HomogeneousMedium {
+            sigma_a,
+            sigma_s,
+            sigma_t,
+            g,
+        }
+    }
+
+    /// Returns the absorption coefficient.
+    pub fn sigma_a(&self) -> &Spectrum {
+        &self.sigma_a
+    }
+
+    /// Returns the scattering coefficient.
+    pub fn sigma_s(&self) -> &Spectrum {
+        &self.sigma_s
+    }
+
+    /// Returns the asymmetry parameter of the Henyey-Greenstein phase function.
+    pub fn g(&self) -> Float {
+        self.g
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    /// Returns the transmittance along `ray` from its origin to `ray.t_max`, `exp(-sigma_t * d)`
+    /// where `d` is the distance covered.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Point3f, Ray, Vector3f},
+    ///         medium::Medium,
+    ///         spectrum::Spectrum,
+    ///     },
+    ///     media::homogeneous::HomogeneousMedium,
+    ///     samplers::stratified::StratifiedSampler,
+    ///     Float,
+    /// };
+    ///
+    /// let m = HomogeneousMedium::new(Spectrum::new(0.25), Spectrum::new(0.25), 0.);
+    /// let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+    /// let ray = Ray::new(
+    ///     Point3f::default(),
+    ///     Vector3f::from([0., 0., 1.]),
+    ///     2.,
+    ///     0.,
+    ///     None,
+    /// );
+    /// let tr = m.tr(&ray, &mut sampler);
+    /// assert!((tr.channels()[0] - (-1. as Float).exp()).abs() < 1e-6);
+    /// ```
+    fn tr(&self, ray: &Ray, _sampler: &mut dyn Sampler) -> Spectrum {
+        (self.sigma_t.clone() * -(ray.t_max.get() * ray.d.length()).min(Float::MAX)).exp()
+    }
+
+    /// Samples a distance along `ray` proportional to the transmittance of a randomly chosen
+    /// spectral channel.
+    fn sample(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+    ) -> (Spectrum, Option<MediumInteraction>) {
+        // Sample a channel and distance along the ray
+        let sigma_t = self.sigma_t.channels();
+        let n = sigma_t.len();
+        let channel = ((sampler.get_1d() * n as Float) as usize).min(n - 1);
+        let d_len = ray.d.length();
+        let dist = -(1. - sampler.get_1d()).ln() / sigma_t[channel];
+        let t = (dist / d_len).min(ray.t_max.get());
+        let sampled_medium = t < ray.t_max.get();
+        let mi = if sampled_medium {
+            Some(MediumInteraction::new(
+                ray.at(t),
+                -ray.d,
+                ray.time,
+                ray.medium.clone(),
+                Arc::new(HenyeyGreenstein::new(self.g)),
+            ))
+        } else {
+            None
+        };
+
+        // Compute the transmittance and sampling density
+        let tr = (self.sigma_t.clone() * -(t.min(Float::MAX) * d_len)).exp();
+
+        // Return weighting factor for scattering from homogeneous medium
+        let density = if sampled_medium {
+            self.sigma_t.clone() * tr.clone()
+        } else {
+            tr.clone()
+        };
+        let pdf = density.channels().iter().sum::<Float>() / n as Float;
+        let pdf = if pdf == 0. { 1. } else { pdf };
+        if sampled_medium {
+            (tr * self.sigma_s.clone() / pdf, mi)
+        } else {
+            (tr / pdf, mi)
+        }
+    }
+}
+
+/// Creates a `HomogeneousMedium` from the given `params`.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::paramset::testutils::make_float_param_set, media::homogeneous::create_homogeneous_medium,
+/// };
+///
+/// let params = make_float_param_set("g", vec![0.5]);
+/// let m = create_homogeneous_medium(&params);
+/// assert_eq!(m.g(), 0.5);
+/// ```
+pub fn create_homogeneous_medium(params: &ParamSet) -> HomogeneousMedium {
+    // TODO(wathiede): support the named "preset" scattering properties.
+    let scale = params.find_one_float("scale", 1.);
+    let g = params.find_one_float("g", 0.);
+    let sigma_a =
+        params.find_one_spectrum("sigma_a", Spectrum::from_rgb([0.0011, 0.0024, 0.014])) * scale;
+    let sigma_s =
+        params.find_one_spectrum("sigma_s", Spectrum::from_rgb([2.55, 3.21, 3.77])) * scale;
+    HomogeneousMedium::new(sigma_a, sigma_s, g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HomogeneousMedium;
+    use crate::{
+        core::{
+            geometry::{Point3f, Ray, Vector3f},
+            medium::Medium,
+            spectrum::Spectrum,
+        },
+        samplers::stratified::StratifiedSampler,
+        Float,
+    };
+
+    #[test]
+    fn beer_lambert_transmittance() {
+        let m = HomogeneousMedium::new(
+            Spectrum::from_rgb([0.1, 0.2, 0.3]),
+            Spectrum::from_rgb([0.4, 0.1, 0.]),
+            0.,
+        );
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        // A non-unit direction: the ray covers a distance of 3 * 1.5 = 4.5.
+        let ray = Ray::new(
+            Point3f::from([1., 2., 3.]),
+            Vector3f::from([0., 3., 0.]),
+            1.5,
+            0.,
+            None,
+        );
+        let tr = m.tr(&ray, &mut sampler);
+        for (got, sigma_t) in tr.channels().iter().zip(&[0.5 as Float, 0.3, 0.3]) {
+            let want = (-sigma_t * 4.5).exp();
+            assert!((got - want).abs() < 1e-6, "got {} want {}", got, want);
+        }
+
+        // Infinite rays are fully attenuated.
+        let ray = Ray::new(
+            Point3f::default(),
+            Vector3f::from([1., 0., 0.]),
+            Float::INFINITY,
+            0.,
+            None,
+        );
+        assert_eq!(m.tr(&ray, &mut sampler), Spectrum::new(0.));
+    }
+
+    #[test]
+    fn unit_optical_depth() {
+        // sigma_t = 1 split unevenly between absorption and scattering per channel.
+        let m = HomogeneousMedium::new(
+            Spectrum::from_rgb([1., 0.5, 0.]),
+            Spectrum::from_rgb([0., 0.5, 1.]),
+            0.,
+        );
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let ray = Ray::new(
+            Point3f::from([0., 0., -0.5]),
+            Vector3f::from([0., 0., 1.]),
+            1.,
+            0.,
+            None,
+        );
+        let want = (-1. as Float).exp();
+        for got in m.tr(&ray, &mut sampler).channels().iter() {
+            assert!((got - want).abs() < 1e-6, "got {} want {}", got, want);
+        }
+    }
+
+    #[test]
+    fn sample_is_unbiased() {
+        // With a grey medium whose albedo is one, the sampled weight is one when passing through
+        // the ray segment or scattering, and the fraction of rays passing through is the
+        // transmittance.
+        let m = HomogeneousMedium::new(Spectrum::new(0.), Spectrum::new(0.5), 0.);
+        let mut sampler = StratifiedSampler::new(1, 1, false, 0);
+        let ray = Ray::new(
+            Point3f::default(),
+            Vector3f::from([0., 0., 2.]),
+            1.,
+            0.,
+            None,
+        );
+        const N: usize = 20_000;
+        let mut passed = 0;
+        for _ in 0..N {
+            let (beta, mi) = m.sample(&ray, &mut sampler);
+            assert!((beta.channels()[0] - 1.).abs() < 1e-4, "beta {:?}", beta);
+            match mi {
+                Some(mi) => assert!(mi.p.z >= 0. && mi.p.z < 2.),
+                None => passed += 1,
+            }
+        }
+        let frac = passed as Float / N as Float;
+        let want = (-1. as Float).exp();
+        assert!((frac - want).abs() < 0.01, "frac {} want {}", frac, want);
+    }
+}
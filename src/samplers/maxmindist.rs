@@ -0,0 +1,258 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Sampler] implementation whose image plane samples are spread out like a Poisson disk
+//! pattern, while still being stratified like a (0,2)-sequence.
+//!
+//! [Sampler]: crate::core::sampler::Sampler
+use log::warn;
+
+use crate::{
+    core::{
+        geometry::{Point2f, Point2i},
+        lowdiscrepancy::{sample_generator_matrix, sobol_2d, van_der_corput, C_MAX_MIN_DIST},
+        paramset::ParamSet,
+        rng::Rng,
+        sampler::{SampleArrays, Sampler},
+        sampling::shuffle,
+    },
+    Float, Options,
+};
+
+/// MaxMinDistSampler generates a power of two number of samples per pixel, at most 2^16.  The
+/// first 2D dimension, used for positions on the film, comes from a generator matrix chosen to
+/// maximize the minimum distance between samples.  The other sampled dimensions are van der
+/// Corput and (0,2)-sequence points as in [ZeroTwoSequenceSampler], and any remaining
+/// dimensions are uniform random values.
+///
+/// [ZeroTwoSequenceSampler]: crate::samplers::zerotwosequence::ZeroTwoSequenceSampler
+#[derive(Clone, Debug)]
+pub struct MaxMinDistSampler {
+    samples_per_pixel: usize,
+    c_pixel: &'static [u32; 32],
+    samples_1d: Vec<Vec<Float>>,
+    samples_2d: Vec<Vec<Point2f>>,
+    current_1d_dimension: usize,
+    current_2d_dimension: usize,
+    current_pixel_sample_index: usize,
+    rng: Rng,
+    arrays: SampleArrays,
+}
+
+impl MaxMinDistSampler {
+    /// Create a new `MaxMinDistSampler` that takes `samples_per_pixel` samples per pixel, rounded
+    /// up to the next power of two.  At least the first 2D dimension is always sampled.
+    pub fn new(samples_per_pixel: usize, n_sampled_dimensions: usize) -> MaxMinDistSampler {
+        let max_spp = 1 << (C_MAX_MIN_DIST.len() - 1);
+        let mut spp = samples_per_pixel.max(1);
+        if spp > max_spp {
+            warn!(
+                "No more than {} samples per pixel are supported with MaxMinDistSampler. Rounding down.",
+                max_spp
+            );
+            spp = max_spp;
+        }
+        if !spp.is_power_of_two() {
+            spp = spp.next_power_of_two();
+            warn!(
+                "Non power-of-two sample count rounded up to {} for MaxMinDistSampler.",
+                spp
+            );
+        }
+        let c_index = spp.trailing_zeros() as usize;
+        MaxMinDistSampler {
+            samples_per_pixel: spp,
+            c_pixel: &C_MAX_MIN_DIST[c_index],
+            samples_1d: vec![vec![0.; spp]; n_sampled_dimensions],
+            samples_2d: vec![vec![Point2f::default(); spp]; n_sampled_dimensions.max(1)],
+            current_1d_dimension: 0,
+            current_2d_dimension: 0,
+            current_pixel_sample_index: 0,
+            rng: Rng::default(),
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for MaxMinDistSampler {
+    fn start_pixel(&mut self, _p: Point2i) {
+        let inv_spp = 1. / self.samples_per_pixel as Float;
+        for (i, s) in self.samples_2d[0].iter_mut().enumerate() {
+            *s = Point2f::from([
+                i as Float * inv_spp,
+                sample_generator_matrix(self.c_pixel, i as u32, 0),
+            ]);
+        }
+        shuffle(&mut self.samples_2d[0], &mut self.rng);
+        // Generate remaining samples for _MaxMinDistSampler_
+        for samples in self.samples_1d.iter_mut() {
+            van_der_corput(1, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        for samples in self.samples_2d.iter_mut().skip(1) {
+            sobol_2d(1, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        // Generate arrays of $(0,2)$-sequence samples for the pixel
+        for (n, samples) in self.arrays.arrays_1d_mut() {
+            van_der_corput(n, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        for (n, samples) in self.arrays.arrays_2d_mut() {
+            sobol_2d(n, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        self.arrays.start_pixel();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index = 0;
+    }
+
+    fn get_1d(&mut self) -> Float {
+        match self.samples_1d.get(self.current_1d_dimension) {
+            Some(samples) => {
+                self.current_1d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => self.rng.uniform_float(),
+        }
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        match self.samples_2d.get(self.current_2d_dimension) {
+            Some(samples) => {
+                self.current_2d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => Point2f::from([self.rng.uniform_float(), self.rng.uniform_float()]),
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.arrays.start_next_sample();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index += 1;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn clone_sampler(&self, seed: usize) -> Box<dyn Sampler> {
+        let mut mmds = self.clone();
+        mmds.rng.set_sequence(seed as u64);
+        Box::new(mmds)
+    }
+
+    fn sample_arrays(&mut self) -> Option<&mut SampleArrays> {
+        Some(&mut self.arrays)
+    }
+
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+}
+
+/// Creates a `MaxMinDistSampler` from the given `params`.  When `opt.quick_render` is set, the
+/// number of samples per pixel is divided by 4.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{paramset::testutils::make_int_param_set, sampler::Sampler},
+///     samplers::maxmindist::create_max_min_dist_sampler,
+///     Options,
+/// };
+///
+/// let params = make_int_param_set("pixelsamples", vec![32]);
+/// let sampler = create_max_min_dist_sampler(&params, &Options::default());
+/// assert_eq!(sampler.samples_per_pixel(), 32);
+///
+/// let quick = Options {
+///     quick_render: true,
+///     ..Default::default()
+/// };
+/// let sampler = create_max_min_dist_sampler(&params, &quick);
+/// assert_eq!(sampler.samples_per_pixel(), 8);
+/// ```
+pub fn create_max_min_dist_sampler(params: &ParamSet, opt: &Options) -> MaxMinDistSampler {
+    let mut nsamp = params.find_one_int("pixelsamples", 16).max(1) as usize;
+    if opt.quick_render {
+        nsamp = (nsamp / 4).max(1);
+    }
+    let sd = params.find_one_int("dimensions", 4).max(0) as usize;
+    MaxMinDistSampler::new(nsamp, sd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::stratified::StratifiedSampler;
+
+    /// Returns the smallest distance between any two of the pixel's samples from the first 2D
+    /// dimension, measured on the unit torus.
+    fn min_distance(sampler: &mut dyn Sampler) -> Float {
+        sampler.start_pixel(Point2i::from([0, 0]));
+        let mut points = Vec::new();
+        loop {
+            points.push(sampler.get_2d());
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        let mut min_d = Float::INFINITY;
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                let dx = (a.x - b.x).abs();
+                let dy = (a.y - b.y).abs();
+                let (dx, dy) = (dx.min(1. - dx), dy.min(1. - dy));
+                min_d = min_d.min((dx * dx + dy * dy).sqrt());
+            }
+        }
+        min_d
+    }
+
+    #[test]
+    fn farther_apart_than_stratified() {
+        for &(nx, ny) in &[(4, 4), (8, 8), (16, 16)] {
+            let mut mmd = MaxMinDistSampler::new(nx * ny, 1);
+            let mut stratified = StratifiedSampler::new(nx, ny, true, 1);
+            let mmd = min_distance(&mut mmd);
+            let stratified = min_distance(&mut stratified);
+            assert!(
+                mmd > stratified,
+                "{} samples: maxmindist {} stratified {}",
+                nx * ny,
+                mmd,
+                stratified
+            );
+        }
+    }
+
+    #[test]
+    fn stratified_rows_and_columns() {
+        // Each of the 16 columns and rows has exactly one of the 16 samples.
+        let mut sampler = MaxMinDistSampler::new(16, 1);
+        sampler.start_pixel(Point2i::from([0, 0]));
+        let (mut cols, mut rows) = ([0; 16], [0; 16]);
+        loop {
+            let p = sampler.get_2d();
+            cols[(p.x * 16.) as usize] += 1;
+            rows[(p.y * 16.) as usize] += 1;
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(cols, [1; 16]);
+        assert_eq!(rows, [1; 16]);
+    }
+}
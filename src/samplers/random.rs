@@ -0,0 +1,213 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Sampler] implementation that returns independent uniform random values for every dimension.
+//! It's the baseline the other samplers are compared against.
+//!
+//! [Sampler]: crate::core::sampler::Sampler
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    core::{
+        geometry::{Point2f, Point2i},
+        paramset::ParamSet,
+        rng::Rng,
+        sampler::{SampleArrays, Sampler},
+    },
+    Float, Options,
+};
+
+/// RandomSampler generates `samples_per_pixel` samples per pixel with no stratification.  The
+/// random number generator is reseeded at the start of each pixel, so a pixel's samples don't
+/// depend on which pixels were rendered before it.
+#[derive(Clone, Debug)]
+pub struct RandomSampler {
+    samples_per_pixel: usize,
+    seed: usize,
+    current_pixel_sample_index: usize,
+    rng: Rng,
+    arrays: SampleArrays,
+}
+
+impl RandomSampler {
+    /// Create a new `RandomSampler` that takes `samples_per_pixel` samples per pixel, with its
+    /// random values determined by `seed`.
+    pub fn new(samples_per_pixel: usize, seed: usize) -> RandomSampler {
+        RandomSampler {
+            samples_per_pixel,
+            seed,
+            current_pixel_sample_index: 0,
+            rng: Rng::new(seed as u64),
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn start_pixel(&mut self, p: Point2i) {
+        let mut hasher = DefaultHasher::new();
+        (p.x, p.y, self.seed).hash(&mut hasher);
+        self.rng.set_sequence(hasher.finish());
+        let rng = &mut self.rng;
+        for (_, samples) in self.arrays.arrays_1d_mut() {
+            samples.iter_mut().for_each(|s| *s = rng.uniform_float());
+        }
+        for (_, samples) in self.arrays.arrays_2d_mut() {
+            samples
+                .iter_mut()
+                .for_each(|s| *s = Point2f::from([rng.uniform_float(), rng.uniform_float()]));
+        }
+        self.arrays.start_pixel();
+        self.current_pixel_sample_index = 0;
+    }
+
+    fn get_1d(&mut self) -> Float {
+        self.rng.uniform_float()
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        Point2f::from([self.rng.uniform_float(), self.rng.uniform_float()])
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.arrays.start_next_sample();
+        self.current_pixel_sample_index += 1;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn clone_sampler(&self, seed: usize) -> Box<dyn Sampler> {
+        Box::new(RandomSampler {
+            arrays: self.arrays.clone(),
+            ..RandomSampler::new(self.samples_per_pixel, seed)
+        })
+    }
+
+    fn sample_arrays(&mut self) -> Option<&mut SampleArrays> {
+        Some(&mut self.arrays)
+    }
+}
+
+/// Creates a `RandomSampler` from the given `params`.  When `opt.quick_render` is set, the
+/// number of samples per pixel is divided by 4.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{paramset::testutils::make_int_param_set, sampler::Sampler},
+///     samplers::random::create_random_sampler,
+///     Options,
+/// };
+///
+/// let params = make_int_param_set("pixelsamples", vec![8]);
+/// let sampler = create_random_sampler(&params, &Options::default());
+/// assert_eq!(sampler.samples_per_pixel(), 8);
+///
+/// let quick = Options {
+///     quick_render: true,
+///     ..Default::default()
+/// };
+/// let sampler = create_random_sampler(&params, &quick);
+/// assert_eq!(sampler.samples_per_pixel(), 2);
+/// ```
+pub fn create_random_sampler(params: &ParamSet, opt: &Options) -> RandomSampler {
+    let mut ns = params.find_one_int("pixelsamples", 4).max(1) as usize;
+    if opt.quick_render {
+        ns = (ns / 4).max(1);
+    }
+    RandomSampler::new(ns, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel_values(sampler: &mut dyn Sampler, p: Point2i) -> Vec<Float> {
+        sampler.start_pixel(p);
+        let mut values = Vec::new();
+        loop {
+            values.push(sampler.get_1d());
+            let u = sampler.get_2d();
+            values.extend_from_slice(&[u.x, u.y]);
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn uniform() {
+        let mut sampler = RandomSampler::new(1024, 0);
+        let values = pixel_values(&mut sampler, Point2i::from([0, 0]));
+        assert_eq!(values.len(), 3 * 1024);
+        assert!(values.iter().all(|v| (0. ..1.).contains(v)));
+        let mean = values.iter().sum::<Float>() / values.len() as Float;
+        assert!((mean - 0.5).abs() < 0.02, "mean {}", mean);
+    }
+
+    #[test]
+    fn reproducible_per_pixel() {
+        let mut sampler = RandomSampler::new(4, 0);
+        let a = pixel_values(&mut sampler, Point2i::from([1, 2]));
+        let b = pixel_values(&mut sampler, Point2i::from([2, 1]));
+        assert_ne!(a, b);
+        // Revisiting a pixel, or visiting it first with a fresh clone, repeats its samples.
+        assert_eq!(a, pixel_values(&mut sampler, Point2i::from([1, 2])));
+        assert_eq!(
+            a,
+            pixel_values(&mut *sampler.clone_sampler(0), Point2i::from([1, 2]))
+        );
+        assert_ne!(
+            a,
+            pixel_values(&mut *sampler.clone_sampler(1), Point2i::from([1, 2]))
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let mut sampler = RandomSampler::new(2, 0);
+        sampler.request_1d_array(3);
+        sampler.request_2d_array(5);
+        let p = Point2i::from([3, 4]);
+        let mut first = Vec::new();
+        sampler.start_pixel(p);
+        loop {
+            let a = sampler.get_1d_array(3).expect("1d array").to_vec();
+            let b = sampler.get_2d_array(5).expect("2d array").to_vec();
+            assert!(a.iter().all(|v| (0. ..1.).contains(v)));
+            assert!(b
+                .iter()
+                .all(|u| (0. ..1.).contains(&u.x) && (0. ..1.).contains(&u.y)));
+            assert!(sampler.get_1d_array(3).is_none());
+            assert!(sampler.get_2d_array(5).is_none());
+            first.push((a, b));
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(first.len(), 2);
+        assert_ne!(first[0], first[1]);
+
+        sampler.start_pixel(p);
+        assert_eq!(sampler.get_1d_array(3).expect("1d array"), &first[0].0[..]);
+        assert_eq!(sampler.get_2d_array(5).expect("2d array"), &first[0].1[..]);
+    }
+}
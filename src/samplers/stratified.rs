@@ -0,0 +1,247 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Sampler] implementation that divides each pixel into a grid of strata and takes one jittered
+//! sample in each.
+//!
+//! [Sampler]: crate::core::sampler::Sampler
+
+use crate::{
+    core::{
+        geometry::{Point2f, Point2i},
+        paramset::ParamSet,
+        rng::Rng,
+        sampler::{SampleArrays, Sampler},
+        sampling::{latin_hypercube, shuffle, stratified_sample_1d, stratified_sample_2d},
+    },
+    Float, Options,
+};
+
+/// StratifiedSampler generates `x_pixel_samples * y_pixel_samples` samples per pixel.  The first
+/// `n_sampled_dimensions` 1D and 2D dimensions are stratified, the remaining dimensions are
+/// uniform random values.
+#[derive(Clone, Debug)]
+pub struct StratifiedSampler {
+    x_pixel_samples: usize,
+    y_pixel_samples: usize,
+    jitter_samples: bool,
+    samples_1d: Vec<Vec<Float>>,
+    samples_2d: Vec<Vec<Point2f>>,
+    current_1d_dimension: usize,
+    current_2d_dimension: usize,
+    current_pixel_sample_index: usize,
+    rng: Rng,
+    arrays: SampleArrays,
+}
+
+impl StratifiedSampler {
+    /// Create a new `StratifiedSampler` that takes `x_pixel_samples * y_pixel_samples` samples
+    /// per pixel.
+    pub fn new(
+        x_pixel_samples: usize,
+        y_pixel_samples: usize,
+        jitter_samples: bool,
+        n_sampled_dimensions: usize,
+    ) -> StratifiedSampler {
+        let spp = x_pixel_samples * y_pixel_samples;
+        StratifiedSampler {
+            x_pixel_samples,
+            y_pixel_samples,
+            jitter_samples,
+            samples_1d: vec![vec![0.; spp]; n_sampled_dimensions],
+            samples_2d: vec![vec![Point2f::default(); spp]; n_sampled_dimensions],
+            current_1d_dimension: 0,
+            current_2d_dimension: 0,
+            current_pixel_sample_index: 0,
+            rng: Rng::default(),
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, _p: Point2i) {
+        // Generate single stratified samples for the pixel
+        for samples in self.samples_1d.iter_mut() {
+            stratified_sample_1d(samples, &mut self.rng, self.jitter_samples);
+            shuffle(samples, &mut self.rng);
+        }
+        for samples in self.samples_2d.iter_mut() {
+            stratified_sample_2d(
+                samples,
+                self.x_pixel_samples,
+                self.y_pixel_samples,
+                &mut self.rng,
+                self.jitter_samples,
+            );
+            shuffle(samples, &mut self.rng);
+        }
+        // Generate arrays of stratified samples for the pixel
+        for (n, samples) in self.arrays.arrays_1d_mut() {
+            for samples in samples.chunks_mut(n) {
+                stratified_sample_1d(samples, &mut self.rng, self.jitter_samples);
+                shuffle(samples, &mut self.rng);
+            }
+        }
+        for (n, samples) in self.arrays.arrays_2d_mut() {
+            for samples in samples.chunks_mut(n) {
+                latin_hypercube(samples, &mut self.rng);
+            }
+        }
+        self.arrays.start_pixel();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index = 0;
+    }
+
+    fn get_1d(&mut self) -> Float {
+        match self.samples_1d.get(self.current_1d_dimension) {
+            Some(samples) => {
+                self.current_1d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => self.rng.uniform_float(),
+        }
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        match self.samples_2d.get(self.current_2d_dimension) {
+            Some(samples) => {
+                self.current_2d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => Point2f::from([self.rng.uniform_float(), self.rng.uniform_float()]),
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.arrays.start_next_sample();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index += 1;
+        self.current_pixel_sample_index < self.samples_per_pixel()
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.x_pixel_samples * self.y_pixel_samples
+    }
+
+    fn clone_sampler(&self, seed: usize) -> Box<dyn Sampler> {
+        let mut ss = self.clone();
+        ss.rng.set_sequence(seed as u64);
+        Box::new(ss)
+    }
+
+    fn sample_arrays(&mut self) -> Option<&mut SampleArrays> {
+        Some(&mut self.arrays)
+    }
+}
+
+/// Creates a `StratifiedSampler` from the given `params`.  When `opt.quick_render` is set, the
+/// number of samples per pixel is divided by 4.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{paramset::testutils::make_int_param_set, sampler::Sampler},
+///     samplers::stratified::create_stratified_sampler,
+///     Options,
+/// };
+///
+/// let params = make_int_param_set("xsamples", vec![2]);
+/// let sampler = create_stratified_sampler(&params, &Options::default());
+/// assert_eq!(sampler.samples_per_pixel(), 2 * 4);
+///
+/// let quick = Options {
+///     quick_render: true,
+///     ..Default::default()
+/// };
+/// let sampler = create_stratified_sampler(&params, &quick);
+/// assert_eq!(sampler.samples_per_pixel(), 1 * 2);
+/// ```
+pub fn create_stratified_sampler(params: &ParamSet, opt: &Options) -> StratifiedSampler {
+    let jitter = params.find_one_bool("jitter", true);
+    let mut xsamp = params.find_one_int("xsamples", 4).max(1) as usize;
+    let mut ysamp = params.find_one_int("ysamples", 4).max(1) as usize;
+    if opt.quick_render {
+        xsamp = (xsamp / 2).max(1);
+        ysamp = (ysamp / 2).max(1);
+    }
+    let sd = params.find_one_int("dimensions", 4).max(0) as usize;
+    StratifiedSampler::new(xsamp, ysamp, jitter, sd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_are_stratified() {
+        let mut sampler = StratifiedSampler::new(2, 2, true, 1);
+        sampler.start_pixel([0, 0].into());
+        let mut strata = [false; 4];
+        loop {
+            let p = sampler.get_2d();
+            strata[(p.x * 2.) as usize + 2 * (p.y * 2.) as usize] = true;
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(strata, [true; 4]);
+    }
+
+    #[test]
+    fn clone_sampler_reseeds() {
+        let sampler = StratifiedSampler::new(2, 2, true, 1);
+        let mut s1 = sampler.clone_sampler(1);
+        let mut s2 = sampler.clone_sampler(2);
+        let mut s1_again = sampler.clone_sampler(1);
+        s1.start_pixel([0, 0].into());
+        s2.start_pixel([0, 0].into());
+        s1_again.start_pixel([0, 0].into());
+        let v1 = s1.get_1d();
+        assert_ne!(v1, s2.get_1d());
+        assert_eq!(v1, s1_again.get_1d());
+    }
+
+    #[test]
+    fn arrays_are_stratified() {
+        let mut sampler = StratifiedSampler::new(2, 1, true, 0);
+        sampler.request_1d_array(4);
+        sampler.request_2d_array(5);
+        sampler.start_pixel([0, 0].into());
+        loop {
+            let mut strata: Vec<_> = sampler
+                .get_1d_array(4)
+                .unwrap()
+                .iter()
+                .map(|v| (v * 4.) as usize)
+                .collect();
+            strata.sort_unstable();
+            assert_eq!(strata, [0, 1, 2, 3]);
+
+            // 2D arrays are Latin hypercube samples, stratified in each dimension.
+            let points = sampler.get_2d_array(5).unwrap();
+            let mut xs: Vec<_> = points.iter().map(|p| (p.x * 5.) as usize).collect();
+            let mut ys: Vec<_> = points.iter().map(|p| (p.y * 5.) as usize).collect();
+            xs.sort_unstable();
+            ys.sort_unstable();
+            assert_eq!(xs, [0, 1, 2, 3, 4]);
+            assert_eq!(ys, [0, 1, 2, 3, 4]);
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+    }
+}
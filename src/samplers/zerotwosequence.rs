@@ -0,0 +1,284 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Sampler] implementation that takes each pixel's samples from scrambled (0,2)-sequences,
+//! which are well stratified for every power of two subset of the samples.
+//!
+//! [Sampler]: crate::core::sampler::Sampler
+use log::warn;
+
+use crate::{
+    core::{
+        geometry::{Point2f, Point2i},
+        lowdiscrepancy::{sobol_2d, van_der_corput},
+        paramset::ParamSet,
+        rng::Rng,
+        sampler::{SampleArrays, Sampler},
+    },
+    Float, Options,
+};
+
+/// ZeroTwoSequenceSampler generates a power of two number of samples per pixel.  The first
+/// `n_sampled_dimensions` 1D dimensions are van der Corput points and 2D dimensions are
+/// (0,2)-sequence points, the remaining dimensions are uniform random values.
+#[derive(Clone, Debug)]
+pub struct ZeroTwoSequenceSampler {
+    samples_per_pixel: usize,
+    samples_1d: Vec<Vec<Float>>,
+    samples_2d: Vec<Vec<Point2f>>,
+    current_1d_dimension: usize,
+    current_2d_dimension: usize,
+    current_pixel_sample_index: usize,
+    rng: Rng,
+    arrays: SampleArrays,
+}
+
+impl ZeroTwoSequenceSampler {
+    /// Create a new `ZeroTwoSequenceSampler` that takes `samples_per_pixel` samples per pixel,
+    /// rounded up to the next power of two.
+    pub fn new(samples_per_pixel: usize, n_sampled_dimensions: usize) -> ZeroTwoSequenceSampler {
+        let spp = samples_per_pixel.max(1).next_power_of_two();
+        if spp != samples_per_pixel {
+            warn!(
+                "Pixel samples being rounded up to power of 2 (from {} to {}).",
+                samples_per_pixel, spp
+            );
+        }
+        ZeroTwoSequenceSampler {
+            samples_per_pixel: spp,
+            samples_1d: vec![vec![0.; spp]; n_sampled_dimensions],
+            samples_2d: vec![vec![Point2f::default(); spp]; n_sampled_dimensions],
+            current_1d_dimension: 0,
+            current_2d_dimension: 0,
+            current_pixel_sample_index: 0,
+            rng: Rng::default(),
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for ZeroTwoSequenceSampler {
+    fn start_pixel(&mut self, _p: Point2i) {
+        // Generate 1D and 2D pixel sample components using $(0,2)$-sequence
+        for samples in self.samples_1d.iter_mut() {
+            van_der_corput(1, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        for samples in self.samples_2d.iter_mut() {
+            sobol_2d(1, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        // Generate arrays of $(0,2)$-sequence samples for the pixel
+        for (n, samples) in self.arrays.arrays_1d_mut() {
+            van_der_corput(n, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        for (n, samples) in self.arrays.arrays_2d_mut() {
+            sobol_2d(n, self.samples_per_pixel, samples, &mut self.rng);
+        }
+        self.arrays.start_pixel();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index = 0;
+    }
+
+    fn get_1d(&mut self) -> Float {
+        match self.samples_1d.get(self.current_1d_dimension) {
+            Some(samples) => {
+                self.current_1d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => self.rng.uniform_float(),
+        }
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        match self.samples_2d.get(self.current_2d_dimension) {
+            Some(samples) => {
+                self.current_2d_dimension += 1;
+                samples[self.current_pixel_sample_index]
+            }
+            None => Point2f::from([self.rng.uniform_float(), self.rng.uniform_float()]),
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.arrays.start_next_sample();
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.current_pixel_sample_index += 1;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn clone_sampler(&self, seed: usize) -> Box<dyn Sampler> {
+        let mut lds = self.clone();
+        lds.rng.set_sequence(seed as u64);
+        Box::new(lds)
+    }
+
+    fn sample_arrays(&mut self) -> Option<&mut SampleArrays> {
+        Some(&mut self.arrays)
+    }
+
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+}
+
+/// Creates a `ZeroTwoSequenceSampler` from the given `params`.  When `opt.quick_render` is set,
+/// the number of samples per pixel is divided by 4.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{paramset::testutils::make_int_param_set, sampler::Sampler},
+///     samplers::zerotwosequence::create_zero_two_sequence_sampler,
+///     Options,
+/// };
+///
+/// let params = make_int_param_set("pixelsamples", vec![12]);
+/// let sampler = create_zero_two_sequence_sampler(&params, &Options::default());
+/// assert_eq!(sampler.samples_per_pixel(), 16);
+///
+/// let quick = Options {
+///     quick_render: true,
+///     ..Default::default()
+/// };
+/// let sampler = create_zero_two_sequence_sampler(&params, &quick);
+/// assert_eq!(sampler.samples_per_pixel(), 4);
+/// ```
+pub fn create_zero_two_sequence_sampler(
+    params: &ParamSet,
+    opt: &Options,
+) -> ZeroTwoSequenceSampler {
+    let mut nsamp = params.find_one_int("pixelsamples", 16).max(1) as usize;
+    if opt.quick_render {
+        nsamp = (nsamp / 4).max(1);
+    }
+    let sd = params.find_one_int("dimensions", 4).max(0) as usize;
+    ZeroTwoSequenceSampler::new(nsamp, sd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::stratified::StratifiedSampler;
+
+    /// Returns the star discrepancy of `points`, the largest difference between the fraction of
+    /// points inside a box anchored at the origin and the box's area.
+    fn star_discrepancy(points: &[Point2f]) -> Float {
+        let n = points.len() as Float;
+        let mut edges_x: Vec<_> = points.iter().map(|p| p.x).collect();
+        let mut edges_y: Vec<_> = points.iter().map(|p| p.y).collect();
+        edges_x.push(1.);
+        edges_y.push(1.);
+        let mut d: Float = 0.;
+        for &x in &edges_x {
+            for &y in &edges_y {
+                let open = points.iter().filter(|p| p.x < x && p.y < y).count() as Float;
+                let closed = points.iter().filter(|p| p.x <= x && p.y <= y).count() as Float;
+                let area = x * y;
+                d = d
+                    .max((open / n - area).abs())
+                    .max((closed / n - area).abs());
+            }
+        }
+        d
+    }
+
+    /// Returns the average star discrepancy of the first 2D dimension over several pixels.
+    fn mean_discrepancy(sampler: &mut dyn Sampler) -> Float {
+        let n_pixels = 64;
+        let mut sum = 0.;
+        for i in 0..n_pixels {
+            sampler.start_pixel(Point2i::from([i, 0]));
+            let mut points = Vec::new();
+            loop {
+                points.push(sampler.get_2d());
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+            sum += star_discrepancy(&points);
+        }
+        sum / n_pixels as Float
+    }
+
+    #[test]
+    fn discrepancy() {
+        let mut zero_two = ZeroTwoSequenceSampler::new(16, 1);
+        let mut stratified = StratifiedSampler::new(4, 4, true, 1);
+        let zero_two = mean_discrepancy(&mut zero_two);
+        let stratified = mean_discrepancy(&mut stratified);
+        assert!(
+            zero_two < stratified,
+            "zero two {} stratified {}",
+            zero_two,
+            stratified
+        );
+    }
+
+    #[test]
+    fn rounds_to_power_of_two() {
+        let mut sampler = ZeroTwoSequenceSampler::new(5, 2);
+        assert_eq!(sampler.samples_per_pixel(), 8);
+        sampler.start_pixel(Point2i::from([0, 0]));
+        let mut strata = [0; 8];
+        loop {
+            strata[(sampler.get_1d() * 8.) as usize] += 1;
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(strata, [1; 8]);
+    }
+
+    #[test]
+    fn arrays_are_low_discrepancy() {
+        let mut sampler = ZeroTwoSequenceSampler::new(4, 0);
+        assert_eq!(sampler.round_count(5), 8);
+        assert_eq!(sampler.round_count(8), 8);
+        sampler.request_1d_array(8);
+        sampler.request_2d_array(8);
+        for pixel in 0..4 {
+            sampler.start_pixel(Point2i::from([pixel, 0]));
+            loop {
+                let mut strata: Vec<_> = sampler
+                    .get_1d_array(8)
+                    .unwrap()
+                    .iter()
+                    .map(|v| (v * 8.) as usize)
+                    .collect();
+                strata.sort_unstable();
+                assert_eq!(strata, (0..8).collect::<Vec<_>>());
+
+                // Every elementary interval of area 1/8 holds exactly one point.
+                let points = sampler.get_2d_array(8).unwrap();
+                for &(nx, ny) in &[(1, 8), (2, 4), (4, 2), (8, 1)] {
+                    let mut cells: Vec<_> = points
+                        .iter()
+                        .map(|p| (p.x * nx as Float) as usize * ny + (p.y * ny as Float) as usize)
+                        .collect();
+                    cells.sort_unstable();
+                    assert_eq!(cells, (0..8).collect::<Vec<_>>(), "{}x{}", nx, ny);
+                }
+                assert!(sampler.get_2d_array(8).is_none());
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+        }
+    }
+}
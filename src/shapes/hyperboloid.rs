@@ -0,0 +1,359 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a (possibly partial) hyperboloid of one sheet [Shape] around the z axis of object
+//! space.
+//!
+//! [Shape]: crate::core::shape::Shape
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{Bounds3f, Point2f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        paramset::ParamSet,
+        shape::{normal_derivatives, Shape, ShapeData},
+        transform::Transform,
+    },
+    float::consts::{PI, SQRT_2},
+    gamma, quadratic, Float,
+};
+
+/// Hyperboloid of one sheet, `x² + y² - z² = radius²`, i.e. `radius` is the radius of its waist
+/// at `z = 0`.  The surface is cut off below `z_min` and above `z_max`, and may be swept less
+/// than a full circle around the z axis.
+///
+/// Unlike the C++ implementation, which fits a general hyperboloid through two points, this is
+/// the one-sheet hyperboloid described by `radius`, `zmin` and `zmax` like the other quadrics.
+#[derive(Debug)]
+pub struct Hyperboloid {
+    data: ShapeData,
+    radius: Float,
+    z_min: Float,
+    z_max: Float,
+    phi_max: Float,
+}
+
+impl Hyperboloid {
+    /// Create a new `Hyperboloid` clipped to [`z_min`, `z_max`] and swept up to `phi_max` degrees
+    /// around the z axis.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Point3f, Ray, Vector3f},
+    ///         shape::Shape,
+    ///         transform::Transform,
+    ///     },
+    ///     shapes::hyperboloid::Hyperboloid,
+    /// };
+    ///
+    /// let h = Hyperboloid::new(Transform::identity(), Transform::identity(), false, 1., -1., 1., 360.);
+    /// let r = Ray::new(
+    ///     Point3f::from([-5., 0., 0.]),
+    ///     Vector3f::from([1., 0., 0.]),
+    ///     pbrt::Float::INFINITY,
+    ///     0.,
+    ///     None,
+    /// );
+    /// let (t_hit, si) = h.intersect(&r, false).expect("ray should hit hyperboloid");
+    /// assert!((t_hit - 4.).abs() < 1e-5);
+    /// assert!((si.n.x + 1.).abs() < 1e-5);
+    /// ```
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Hyperboloid {
+        Hyperboloid {
+            data: ShapeData::new(object_to_world, world_to_object, reverse_orientation),
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: clamp(phi_max, 0., 360.).to_radians(),
+        }
+    }
+
+    /// Returns the largest distance of the surface from the z axis, found at whichever end is
+    /// furthest from the waist.
+    fn r_max(&self) -> Float {
+        let z = self.z_min.abs().max(self.z_max.abs());
+        (self.radius * self.radius + z * z).sqrt()
+    }
+
+    /// Returns the parametric distance to the closest hit of object space `ray` with the
+    /// hyperboloid before `ray.t_max`, along with the object space hit point and its azimuth.
+    fn closest_hit(&self, ray: &Ray) -> Option<(Float, Point3f, Float)> {
+        let (o, d) = (ray.o, ray.d);
+        let a = d.x * d.x + d.y * d.y - d.z * d.z;
+        let b = 2. * (d.x * o.x + d.y * o.y - d.z * o.z);
+        let c = o.x * o.x + o.y * o.y - o.z * o.z - self.radius * self.radius;
+        let (t0, t1) = quadratic(a, b, c)?;
+
+        // Check quadric shape _t0_ and _t1_ for nearest intersection
+        let t_max = ray.t_max.get();
+        if t0 > t_max || t1 <= 0. {
+            return None;
+        }
+        for t in [t0, t1] {
+            if t <= 0. || t > t_max {
+                continue;
+            }
+            // Compute hyperboloid inverse mapping
+            let p_hit = ray.at(t);
+            let mut phi = p_hit.y.atan2(p_hit.x);
+            if phi < 0. {
+                phi += 2. * PI;
+            }
+            // Test hyperboloid intersection against clipping parameters
+            if p_hit.z < self.z_min || p_hit.z > self.z_max || phi > self.phi_max {
+                continue;
+            }
+            return Some((t, p_hit, phi));
+        }
+        None
+    }
+}
+
+impl Shape for Hyperboloid {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn object_bound(&self) -> Bounds3f {
+        let r_max = self.r_max();
+        Bounds3f::from([[-r_max, -r_max, self.z_min], [r_max, r_max, self.z_max]])
+    }
+
+    fn intersect(&self, r: &Ray, _test_alpha_texture: bool) -> Option<(Float, SurfaceInteraction)> {
+        // Transform _Ray_ to object space
+        let ray = self.data.world_to_object.transform_ray(r);
+        let (t_shape_hit, p_hit, phi) = self.closest_hit(&ray)?;
+
+        // Find parametric representation of hyperboloid hit
+        let u = phi / self.phi_max;
+        let dz = self.z_max - self.z_min;
+        let v = (p_hit.z - self.z_min) / dz;
+
+        // Compute hyperboloid dpdu and dpdv.  The distance from the z axis is
+        // rho(z) = sqrt(radius^2 + z^2), so d(rho)/dz = z / rho.
+        let rho2 = p_hit.x * p_hit.x + p_hit.y * p_hit.y;
+        let dpdu = Vector3f::from([-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.]);
+        let dpdv = Vector3f::from([p_hit.x * p_hit.z / rho2, p_hit.y * p_hit.z / rho2, 1.]) * dz;
+
+        // Compute hyperboloid dndu and dndv, d^2(rho)/dz^2 = radius^2 / rho^3.
+        let d2pduu = Vector3f::from([p_hit.x, p_hit.y, 0.]) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::from([-p_hit.y * p_hit.z / rho2, p_hit.x * p_hit.z / rho2, 0.])
+            * (dz * self.phi_max);
+        let k = self.radius * self.radius / (rho2 * rho2);
+        let d2pdvv = Vector3f::from([p_hit.x * k, p_hit.y * k, 0.]) * (dz * dz);
+        let (dndu, dndv) = normal_derivatives(dpdu, dpdv, d2pduu, d2pduv, d2pdvv);
+
+        // Bound the error of the hit point found with the ray equation, o + t * d.
+        let p_error = (Vector3f::from(ray.o).abs() + (ray.d * t_shape_hit).abs()) * gamma(7);
+        let si = SurfaceInteraction::new(
+            p_hit,
+            p_error,
+            Point2f::from([u, v]),
+            -ray.d,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            ray.time,
+            &self.data,
+        );
+        Some((
+            t_shape_hit,
+            self.data.object_to_world.transform_surface_interaction(si),
+        ))
+    }
+
+    fn intersect_p(&self, r: &Ray, _test_alpha_texture: bool) -> bool {
+        let ray = self.data.world_to_object.transform_ray(r);
+        self.closest_hit(&ray).is_some()
+    }
+
+    /// The area of the surface of revolution is `phi_max * ∫ sqrt(radius² + 2z²) dz` over
+    /// [`z_min`, `z_max`], which has a closed form.
+    fn area(&self) -> Float {
+        let r2 = self.radius * self.radius;
+        let antiderivative = |z: Float| {
+            let s = (2. * z * z + r2).sqrt();
+            z / 2. * s + r2 / (2. * SQRT_2) * (SQRT_2 * z + s).ln()
+        };
+        self.phi_max * (antiderivative(self.z_max) - antiderivative(self.z_min))
+    }
+
+    /// Sampling hyperboloids isn't supported, as in the C++ implementation, so they can't be
+    /// used as area lights.
+    fn sample(&self, _u: Point2f) -> (Interaction, Float) {
+        unimplemented!("Hyperboloid::sample not implemented");
+    }
+}
+
+/// Creates a `Hyperboloid` from the given `params`.
+pub fn create_hyperboloid_shape(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Hyperboloid {
+    let radius = params.find_one_float("radius", 1.);
+    let z_min = params.find_one_float("zmin", -1.);
+    let z_max = params.find_one_float("zmax", 1.);
+    let phi_max = params.find_one_float("phimax", 360.);
+    Hyperboloid::new(
+        *object_to_world,
+        *world_to_object,
+        reverse_orientation,
+        radius,
+        z_min,
+        z_max,
+        phi_max,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_hyperboloid_shape, Hyperboloid};
+    use crate::{
+        core::{
+            geometry::{dot, Point3f, Ray, Vector3f},
+            paramset::testutils::make_float_param_set,
+            shape::Shape,
+            transform::Transform,
+        },
+        float::consts::PI,
+        Float,
+    };
+
+    fn ray(o: [Float; 3], d: [Float; 3]) -> Ray {
+        Ray::new(
+            Point3f::from(o),
+            Vector3f::from(d),
+            Float::INFINITY,
+            0.,
+            None,
+        )
+    }
+
+    fn unit() -> Hyperboloid {
+        Hyperboloid::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            1.,
+            -1.,
+            1.,
+            360.,
+        )
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let h = unit();
+        // At z = 1 the surface is sqrt(2) from the axis.
+        let (t, si) = h
+            .intersect(&ray([-5., 0., 1.], [1., 0., 0.]), false)
+            .expect("hit");
+        assert!((t - (5. - (2. as Float).sqrt())).abs() < 1e-4, "t {}", t);
+        assert!((si.uv.y - 1.).abs() < 1e-4, "uv {:?}", si.uv);
+        // The normal is perpendicular to the surface's profile, dx/dz = z / x.
+        let n = Vector3f::from(si.n);
+        let x = si.p.x;
+        assert!(
+            dot(n, Vector3f::from([1. / x, 0., 1.])).abs() < 1e-4,
+            "n {:?}",
+            n
+        );
+
+        // Straight down the axis never touches the surface.
+        assert!(!h.intersect_p(&ray([0., 0., 5.], [0., 0., -1.]), false));
+        // Passes outside the waist.
+        assert!(!h.intersect_p(&ray([-5., 1.5, 0.], [1., 0., 0.]), false));
+        // Starting inside, hits the wall in front.
+        let (t, si) = h
+            .intersect(&ray([0., 0., 0.], [0., 1., 0.]), false)
+            .expect("hit");
+        assert!((t - 1.).abs() < 1e-4, "t {}", t);
+        assert!((si.n.y - 1.).abs() < 1e-4, "n {:?}", si.n);
+    }
+
+    #[test]
+    fn clipped() {
+        let h = Hyperboloid::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            1.,
+            0.,
+            1.,
+            180.,
+        );
+        // Below z_min is clipped away.
+        assert!(!h.intersect_p(&ray([-5., 0.1, -0.5], [1., 0., 0.]), false));
+        // phi > 180 degrees is clipped away, the near wall is skipped for the far one.
+        let (t, si) = h
+            .intersect(&ray([0., -5., 0.5], [0., 1., 0.]), false)
+            .expect("hit");
+        assert!(t > 5., "t {}", t);
+        assert!(si.p.y > 0., "p {:?}", si.p);
+    }
+
+    #[test]
+    fn bounds_and_area() {
+        let t = Transform::translate([0., 0., 1.]);
+        let h = create_hyperboloid_shape(
+            &t,
+            &t.inverse(),
+            false,
+            &make_float_param_set("zmax", vec![2.]),
+        );
+        let r_max = (5. as Float).sqrt();
+        let b = h.world_bound();
+        assert!(
+            (b.p_min - Point3f::from([-r_max, -r_max, 0.])).length() < 1e-5,
+            "{:?}",
+            b
+        );
+        assert!(
+            (b.p_max - Point3f::from([r_max, r_max, 3.])).length() < 1e-5,
+            "{:?}",
+            b
+        );
+
+        // Compare against numerically integrating the surface of revolution.
+        let h = unit();
+        let n = 10_000;
+        let dz = 2. / n as Float;
+        let want: Float = (0..n)
+            .map(|i| {
+                let z = -1. + (i as Float + 0.5) * dz;
+                2. * PI * (1. + 2. * z * z).sqrt() * dz
+            })
+            .sum();
+        assert!(
+            (h.area() - want).abs() < 1e-3,
+            "area {} want {}",
+            h.area(),
+            want
+        );
+    }
+}
@@ -0,0 +1,473 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements Loop subdivision surfaces, as referenced by `Shape "loopsubdiv"` in scene files.
+//!
+//! The control mesh is refined the requested number of times, then each vertex is moved to its
+//! position on the limit surface and given the limit surface normal.  The result is rendered as a
+//! [TriangleMesh].  The faces of the control mesh must be consistently oriented and every edge
+//! may be shared by at most two faces.
+//!
+//! [TriangleMesh]: crate::shapes::triangle::TriangleMesh
+use std::{collections::HashMap, sync::Arc};
+
+use log::error;
+
+use crate::{
+    core::{
+        geometry::{cross, Normal3f, Point3f, Vector3f},
+        paramset::ParamSet,
+        transform::Transform,
+    },
+    float::consts::PI,
+    shapes::triangle::{create_triangles, Triangle, TriangleMesh},
+    Float,
+};
+
+/// Weight given to each neighbor of a vertex with `valence` neighbors when refining the mesh.
+fn beta(valence: usize) -> Float {
+    if valence == 3 {
+        3. / 16.
+    } else {
+        3. / (8. * valence as Float)
+    }
+}
+
+/// Weight given to each neighbor of a vertex with `valence` neighbors when moving it to the limit
+/// surface.
+fn loop_gamma(valence: usize) -> Float {
+    1. / (valence as Float + 3. / (8. * beta(valence)))
+}
+
+/// Connectivity of a triangle mesh, used to walk the faces around each vertex.
+struct Topology<'a> {
+    /// Three vertex indices per face.
+    indices: &'a [usize],
+    /// The face containing each directed edge, in the order given by its vertices.
+    edge_faces: HashMap<(usize, usize), usize>,
+    /// One of the faces containing each vertex, `None` for unused vertices.
+    start_face: Vec<Option<usize>>,
+}
+
+impl<'a> Topology<'a> {
+    /// Builds the topology of the mesh with `n_vertices` vertices and faces given by `indices`.
+    /// An error describing the problem is returned if the mesh isn't manifold or its faces aren't
+    /// consistently oriented.
+    fn new(indices: &'a [usize], n_vertices: usize) -> Result<Topology<'a>, String> {
+        let mut edge_faces = HashMap::with_capacity(indices.len());
+        let mut start_face = vec![None; n_vertices];
+        for (f, face) in indices.chunks(3).enumerate() {
+            for i in 0..3 {
+                let (v0, v1) = (face[i], face[(i + 1) % 3]);
+                if v0 == v1 {
+                    return Err(format!("face {} is degenerate", f));
+                }
+                if edge_faces.insert((v0, v1), f).is_some() {
+                    return Err(format!(
+                        "edge {}-{} is shared by more than two faces, or they aren't \
+                         consistently oriented",
+                        v0, v1
+                    ));
+                }
+                start_face[v0] = Some(f);
+            }
+        }
+        Ok(Topology {
+            indices,
+            edge_faces,
+            start_face,
+        })
+    }
+
+    /// Returns the index of the vertex at position `i` in face `f`.
+    fn vert(&self, f: usize, i: usize) -> usize {
+        self.indices[3 * f + i]
+    }
+
+    /// Returns the position of vertex `v` in face `f`.
+    fn vnum(&self, f: usize, v: usize) -> usize {
+        (0..3)
+            .find(|&i| self.vert(f, i) == v)
+            .expect("vertex not in face")
+    }
+
+    fn next_vert(&self, f: usize, v: usize) -> usize {
+        self.vert(f, (self.vnum(f, v) + 1) % 3)
+    }
+
+    fn prev_vert(&self, f: usize, v: usize) -> usize {
+        self.vert(f, (self.vnum(f, v) + 2) % 3)
+    }
+
+    /// Returns the face sharing the edge from `v` to the next vertex of face `f`.
+    fn next_face(&self, f: usize, v: usize) -> Option<usize> {
+        self.edge_faces.get(&(self.next_vert(f, v), v)).cloned()
+    }
+
+    /// Returns the face sharing the edge from the previous vertex of face `f` to `v`.
+    fn prev_face(&self, f: usize, v: usize) -> Option<usize> {
+        self.edge_faces.get(&(v, self.prev_vert(f, v))).cloned()
+    }
+
+    /// Returns the neighbors of vertex `v` in order around it, and whether `v` is on the boundary
+    /// of the mesh.  The first and last neighbors of boundary vertices are along the boundary.
+    fn one_ring(&self, v: usize) -> (Vec<usize>, bool) {
+        let start = match self.start_face[v] {
+            Some(f) => f,
+            None => return (Vec::new(), false),
+        };
+        let mut ring = Vec::new();
+        let mut face = start;
+        loop {
+            ring.push(self.next_vert(face, v));
+            match self.next_face(face, v) {
+                Some(f) if f == start => return (ring, false),
+                Some(f) => face = f,
+                None => break,
+            }
+        }
+        // Hit the boundary, the last face visited is at one end of the faces around `v`.  Walk
+        // back to the other end.
+        ring.clear();
+        ring.push(self.next_vert(face, v));
+        loop {
+            ring.push(self.prev_vert(face, v));
+            match self.prev_face(face, v) {
+                Some(f) => face = f,
+                None => return (ring, true),
+            }
+        }
+    }
+}
+
+/// Returns `p` moved towards its neighbors `ring`, each weighted by `beta`.
+fn weight_one_ring(p: &[Point3f], v: usize, ring: &[usize], beta: Float) -> Point3f {
+    ring.iter()
+        .fold(p[v] * (1. - ring.len() as Float * beta), |acc, &r| {
+            acc + p[r] * beta
+        })
+}
+
+/// Returns `p` moved towards its two neighbors along the boundary, each weighted by `beta`.
+fn weight_boundary(p: &[Point3f], v: usize, ring: &[usize], beta: Float) -> Point3f {
+    p[v] * (1. - 2. * beta) + p[ring[0]] * beta + p[ring[ring.len() - 1]] * beta
+}
+
+/// Refines the mesh once, returning the new vertex positions and faces.
+fn refine(topo: &Topology, p: &[Point3f]) -> (Vec<Point3f>, Vec<usize>) {
+    // Update the positions of the existing vertices.
+    let mut new_p: Vec<_> = (0..p.len())
+        .map(|v| {
+            let (ring, boundary) = topo.one_ring(v);
+            if ring.is_empty() {
+                p[v]
+            } else if boundary {
+                weight_boundary(p, v, &ring, 1. / 8.)
+            } else {
+                weight_one_ring(p, v, &ring, beta(ring.len()))
+            }
+        })
+        .collect();
+
+    // Add a vertex along each edge, and split each face into four.
+    let mut edge_verts: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut edge_vert = |f: usize, i: usize| -> usize {
+        let (v0, v1) = (topo.vert(f, i), topo.vert(f, (i + 1) % 3));
+        *edge_verts
+            .entry((v0.min(v1), v0.max(v1)))
+            .or_insert_with(|| {
+                let ep = match topo.edge_faces.get(&(v1, v0)) {
+                    // Interior edge, also weight the vertices opposite the edge in both faces.
+                    Some(&f2) => {
+                        let v2 = topo.vert(f, (i + 2) % 3);
+                        let v3 = topo.next_vert(f2, v0);
+                        (p[v0] + p[v1]) * (3. / 8.) + (p[v2] + p[v3]) * (1. / 8.)
+                    }
+                    None => (p[v0] + p[v1]) * 0.5,
+                };
+                new_p.push(ep);
+                new_p.len() - 1
+            })
+    };
+    let mut new_indices = Vec::with_capacity(4 * topo.indices.len());
+    for f in 0..topo.indices.len() / 3 {
+        let v = [topo.vert(f, 0), topo.vert(f, 1), topo.vert(f, 2)];
+        let e = [edge_vert(f, 0), edge_vert(f, 1), edge_vert(f, 2)];
+        new_indices.extend_from_slice(&[v[0], e[0], e[2]]);
+        new_indices.extend_from_slice(&[v[1], e[1], e[0]]);
+        new_indices.extend_from_slice(&[v[2], e[2], e[1]]);
+        new_indices.extend_from_slice(&[e[0], e[1], e[2]]);
+    }
+    (new_p, new_indices)
+}
+
+/// Returns the limit surface normal at vertex `v`.
+fn limit_normal(p: &[Point3f], v: usize, ring: &[usize], boundary: bool) -> Normal3f {
+    let valence = ring.len();
+    let pv = |i: usize| Vector3f::from(p[ring[i]]);
+    let (s, t) = if !boundary {
+        (0..valence).fold((Vector3f::default(), Vector3f::default()), |(s, t), i| {
+            let theta = 2. * PI * i as Float / valence as Float;
+            (s + pv(i) * theta.cos(), t + pv(i) * theta.sin())
+        })
+    } else {
+        let pc = Vector3f::from(p[v]);
+        let s = pv(valence - 1) - pv(0);
+        let t = match valence {
+            2 => pv(0) + pv(1) - pc * 2.,
+            3 => pv(1) - pc,
+            4 => pv(0) * -1. + pv(1) * 2. + pv(2) * 2. + pv(3) * -1. + pc * -2.,
+            _ => {
+                let theta = PI / (valence - 1) as Float;
+                let t = (1..valence - 1).fold((pv(0) + pv(valence - 1)) * theta.sin(), |t, k| {
+                    t + pv(k) * ((2. * theta.cos() - 2.) * (k as Float * theta).sin())
+                });
+                -t
+            }
+        };
+        (s, t)
+    };
+    // The ring is ordered clockwise when viewed from the side the faces are wound
+    // counter-clockwise, so `t` x `s` points to that side, matching the faces' geometric normals.
+    Normal3f::from(cross(t, s))
+}
+
+/// Applies `n_levels` of Loop subdivision to the mesh with vertices `p` and faces
+/// `vertex_indices`, and returns the vertices projected to the limit surface, along with their
+/// normals, as a mesh transformed to world space by `object_to_world`.  An error describing the
+/// problem is returned if the mesh can't be subdivided.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{geometry::Point3f, transform::Transform},
+///     shapes::loopsubdiv::loop_subdivide,
+/// };
+///
+/// let p = vec![
+///     Point3f::from([0., 0., 0.]),
+///     Point3f::from([1., 0., 0.]),
+///     Point3f::from([0., 1., 0.]),
+/// ];
+/// let mesh = loop_subdivide(&Transform::identity(), 2, &[0, 1, 2], &p).expect("subdivide");
+/// assert_eq!(mesh.n_triangles, 16);
+/// assert_eq!(mesh.p.len(), 15);
+/// ```
+pub fn loop_subdivide(
+    object_to_world: &Transform,
+    n_levels: usize,
+    vertex_indices: &[usize],
+    p: &[Point3f],
+) -> Result<TriangleMesh, String> {
+    if let Some(&i) = vertex_indices.iter().find(|&&i| i >= p.len()) {
+        return Err(format!(
+            "vertex index {} out of range, {} vertices",
+            i,
+            p.len()
+        ));
+    }
+    let mut p = p.to_vec();
+    let mut indices = vertex_indices.to_vec();
+    for _ in 0..n_levels {
+        let (new_p, new_indices) = refine(&Topology::new(&indices, p.len())?, &p);
+        p = new_p;
+        indices = new_indices;
+    }
+
+    // Push the vertices to the limit surface, computing normals from the refined positions.
+    let topo = Topology::new(&indices, p.len())?;
+    let (p_limit, n): (Vec<_>, Vec<_>) = (0..p.len())
+        .map(|v| {
+            let (ring, boundary) = topo.one_ring(v);
+            if ring.is_empty() {
+                (p[v], Normal3f::default())
+            } else if boundary {
+                (
+                    weight_boundary(&p, v, &ring, 1. / 5.),
+                    limit_normal(&p, v, &ring, boundary),
+                )
+            } else {
+                (
+                    weight_one_ring(&p, v, &ring, loop_gamma(ring.len())),
+                    limit_normal(&p, v, &ring, boundary),
+                )
+            }
+        })
+        .unzip();
+    Ok(TriangleMesh::new(
+        object_to_world,
+        indices,
+        p_limit,
+        Vec::new(),
+        n,
+        Vec::new(),
+    ))
+}
+
+/// Creates the triangles of the Loop subdivision surface described by `params`.  Invalid meshes
+/// log an error and produce no triangles.
+pub fn create_loop_subdiv(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Triangle>> {
+    let n_levels = params.find_one_int("nlevels", 3);
+    let n_levels = params.find_one_int("levels", n_levels);
+    let indices = params.find_int("indices").unwrap_or_default();
+    let p = params.find_point3f("P").unwrap_or_default();
+    if indices.is_empty() {
+        error!("Vertex indices \"indices\" not provided for LoopSubdiv shape.");
+        return Vec::new();
+    }
+    if p.is_empty() {
+        error!("Vertex positions \"P\" not provided for LoopSubdiv shape.");
+        return Vec::new();
+    }
+    let excess = indices.len() % 3;
+    if excess != 0 {
+        error!(
+            "Number of vertex indices {} not a multiple of 3. Discarding {} excess.",
+            indices.len(),
+            excess
+        );
+    }
+    if n_levels < 0 {
+        error!("Negative \"nlevels\" {} for LoopSubdiv shape.", n_levels);
+        return Vec::new();
+    }
+    if indices.iter().any(|&i| i < 0) {
+        error!("Negative vertex index in \"indices\" for LoopSubdiv shape.");
+        return Vec::new();
+    }
+    let indices: Vec<_> = indices[..indices.len() - excess]
+        .iter()
+        .map(|&i| i as usize)
+        .collect();
+    match loop_subdivide(object_to_world, n_levels as usize, &indices, &p) {
+        Ok(mesh) => create_triangles(
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            Arc::new(mesh),
+        ),
+        Err(err) => {
+            error!("Couldn't subdivide LoopSubdiv shape: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_loop_subdiv, loop_subdivide};
+    use crate::{
+        core::{
+            geometry::{dot, Point3f, Vector3f},
+            paramset::{
+                testutils::{make_int, make_point3f},
+                ParamSet,
+            },
+            shape::Shape,
+            transform::Transform,
+        },
+        Float,
+    };
+
+    /// Regular tetrahedron centered on the origin with outward facing faces.
+    fn tetrahedron() -> (Vec<usize>, Vec<Point3f>) {
+        (
+            vec![0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2],
+            vec![
+                Point3f::from([1., 1., 1.]),
+                Point3f::from([1., -1., -1.]),
+                Point3f::from([-1., 1., -1.]),
+                Point3f::from([-1., -1., 1.]),
+            ],
+        )
+    }
+
+    #[test]
+    fn tetrahedron_one_level() {
+        let (indices, p) = tetrahedron();
+        let mesh = loop_subdivide(&Transform::identity(), 1, &indices, &p).expect("subdivide");
+        // Each of the 6 edges gains a vertex, and each face is split into 4.
+        assert_eq!(mesh.p.len(), 4 + 6);
+        assert_eq!(mesh.n_triangles, 4 * 4);
+        assert_eq!(mesh.n.len(), mesh.p.len());
+        for (p, n) in mesh.p.iter().zip(&mesh.n) {
+            let v = Vector3f::from(*p);
+            // Smoothing pulls the surface inside the sphere through the control vertices.
+            assert!(v.length() < (3. as Float).sqrt(), "{:?}", p);
+            assert!(
+                dot(v, Vector3f::from(*n)) > 0.,
+                "normal {:?} at {:?} isn't outward facing",
+                n,
+                p
+            );
+        }
+
+        let mesh = loop_subdivide(&Transform::identity(), 2, &indices, &p).expect("subdivide");
+        assert_eq!(mesh.p.len(), 10 + 24);
+        assert_eq!(mesh.n_triangles, 64);
+    }
+
+    #[test]
+    fn boundary() {
+        // A single triangle has only boundary vertices, which stay in its plane.
+        let p = vec![
+            Point3f::from([0., 0., 0.]),
+            Point3f::from([1., 0., 0.]),
+            Point3f::from([0., 1., 0.]),
+        ];
+        let mesh = loop_subdivide(&Transform::identity(), 1, &[0, 1, 2], &p).expect("subdivide");
+        assert_eq!(mesh.p.len(), 6);
+        assert_eq!(mesh.n_triangles, 4);
+        for (p, n) in mesh.p.iter().zip(&mesh.n) {
+            assert_eq!(p.z, 0.);
+            assert!(n.z > 0. && n.x == 0. && n.y == 0., "{:?}", n);
+        }
+
+        // Faces with inconsistent orientation can't be subdivided.
+        let p = vec![
+            Point3f::from([0., 0., 0.]),
+            Point3f::from([1., 0., 0.]),
+            Point3f::from([0., 1., 0.]),
+            Point3f::from([1., 1., 0.]),
+        ];
+        assert!(loop_subdivide(&Transform::identity(), 1, &[0, 1, 2, 1, 2, 3], &p).is_err());
+        assert!(loop_subdivide(&Transform::identity(), 1, &[0, 1, 4], &p).is_err());
+    }
+
+    #[test]
+    fn create() {
+        let (indices, p) = tetrahedron();
+        let t = Transform::translate([0., 0., 10.]);
+        let params: ParamSet = vec![
+            make_int("nlevels", vec![1]),
+            make_int("indices", indices.iter().map(|&i| i as isize).collect()),
+            make_point3f("P", p),
+        ]
+        .into();
+        let tris = create_loop_subdiv(&t, &t.inverse(), false, &params);
+        assert_eq!(tris.len(), 16);
+        for tri in &tris {
+            let b = tri.world_bound();
+            assert!(b.p_min.z > 8. && b.p_max.z < 12., "{:?}", b);
+        }
+
+        let params: ParamSet = vec![make_int("nlevels", vec![1])].into();
+        assert!(create_loop_subdiv(&t, &t.inverse(), false, &params).is_empty());
+    }
+}
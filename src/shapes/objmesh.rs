@@ -0,0 +1,89 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creates triangle meshes from Wavefront [OBJ] files, as referenced by `Shape "objmesh"` in
+//! scene files.  The files are read with [read_obj].
+//!
+//! [OBJ]: http://paulbourke.net/dataformats/obj/
+//! [read_obj]: crate::core::objutil::read_obj
+use std::sync::Arc;
+
+use log::error;
+
+use crate::{
+    core::{
+        fileutil::resolve_filename, objutil::read_obj, paramset::ParamSet, transform::Transform,
+    },
+    shapes::triangle::{create_triangle_mesh, Triangle},
+};
+
+/// Creates the triangles of the mesh in the OBJ file named by the `filename` parameter.  Files
+/// that can't be read log an error and produce no triangles.
+pub fn create_obj_mesh(
+    object_to_world: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Triangle>> {
+    let filename = resolve_filename(params.find_one_filename("filename", ""));
+    let (p, n, uv, indices) = match read_obj(&filename) {
+        Ok(mesh) => mesh,
+        Err(err) => {
+            error!("Couldn't read OBJ file '{}': {}", filename.display(), err);
+            return Vec::new();
+        }
+    };
+    if indices.is_empty() {
+        error!("OBJ file '{}' has no faces", filename.display());
+        return Vec::new();
+    }
+    create_triangle_mesh(
+        object_to_world,
+        &object_to_world.inverse(),
+        reverse_orientation,
+        indices,
+        p,
+        Vec::new(),
+        n,
+        uv,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_obj_mesh;
+    use crate::{
+        core::{paramset::testutils::make_filename_param_set, shape::Shape, transform::Transform},
+        Float,
+    };
+
+    #[test]
+    fn cube() {
+        let t = Transform::identity();
+        let tris = create_obj_mesh(
+            &t,
+            false,
+            &make_filename_param_set("filename", vec!["src/shapes/testdata/cube.obj".to_string()]),
+        );
+        assert_eq!(tris.len(), 12);
+        let area: Float = tris.iter().map(|t| t.area()).sum();
+        assert!((area - 6.).abs() < 1e-5);
+
+        let tris = create_obj_mesh(
+            &t,
+            false,
+            &make_filename_param_set("filename", vec!["does-not-exist.obj".to_string()]),
+        );
+        assert!(tris.is_empty());
+    }
+}
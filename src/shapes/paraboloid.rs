@@ -0,0 +1,331 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a (possibly partial) paraboloid [Shape] opening up the z axis of object space.
+//!
+//! [Shape]: crate::core::shape::Shape
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{Bounds3f, Point2f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        paramset::ParamSet,
+        shape::{normal_derivatives, Shape, ShapeData},
+        transform::Transform,
+    },
+    float::consts::PI,
+    gamma, quadratic, Float,
+};
+
+/// Paraboloid with its vertex at the object space origin, `z = z_max * (x² + y²) / radius²`,
+/// i.e. `radius` is the radius of the paraboloid at `z_max`.  The surface is cut off below
+/// `z_min` and above `z_max`, and may be swept less than a full circle around the z axis.
+#[derive(Debug)]
+pub struct Paraboloid {
+    data: ShapeData,
+    radius: Float,
+    z_min: Float,
+    z_max: Float,
+    phi_max: Float,
+}
+
+impl Paraboloid {
+    /// Create a new `Paraboloid` clipped to [`z_min`, `z_max`] and swept up to `phi_max` degrees
+    /// around the z axis.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Point3f, Ray, Vector3f},
+    ///         shape::Shape,
+    ///         transform::Transform,
+    ///     },
+    ///     shapes::paraboloid::Paraboloid,
+    /// };
+    ///
+    /// let p = Paraboloid::new(Transform::identity(), Transform::identity(), false, 1., 0., 1., 360.);
+    /// let r = Ray::new(
+    ///     Point3f::from([0.5, 0., -5.]),
+    ///     Vector3f::from([0., 0., 1.]),
+    ///     pbrt::Float::INFINITY,
+    ///     0.,
+    ///     None,
+    /// );
+    /// let (t_hit, si) = p.intersect(&r, false).expect("ray should hit paraboloid");
+    /// assert!((t_hit - 5.25).abs() < 1e-5);
+    /// assert!((si.p.z - 0.25).abs() < 1e-5);
+    /// ```
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Paraboloid {
+        Paraboloid {
+            data: ShapeData::new(object_to_world, world_to_object, reverse_orientation),
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: clamp(phi_max, 0., 360.).to_radians(),
+        }
+    }
+
+    /// Returns the parametric distance to the closest hit of object space `ray` with the
+    /// paraboloid before `ray.t_max`, along with the object space hit point and its azimuth.
+    fn closest_hit(&self, ray: &Ray) -> Option<(Float, Point3f, Float)> {
+        let (o, d) = (ray.o, ray.d);
+        let k = self.z_max / (self.radius * self.radius);
+        let a = k * (d.x * d.x + d.y * d.y);
+        let b = 2. * k * (d.x * o.x + d.y * o.y) - d.z;
+        let c = k * (o.x * o.x + o.y * o.y) - o.z;
+        let (t0, t1) = quadratic(a, b, c)?;
+
+        // Check quadric shape _t0_ and _t1_ for nearest intersection
+        let t_max = ray.t_max.get();
+        if t0 > t_max || t1 <= 0. {
+            return None;
+        }
+        for t in [t0, t1] {
+            if t <= 0. || t > t_max {
+                continue;
+            }
+            // Compute paraboloid inverse mapping
+            let p_hit = ray.at(t);
+            let mut phi = p_hit.y.atan2(p_hit.x);
+            if phi < 0. {
+                phi += 2. * PI;
+            }
+            // Test paraboloid intersection against clipping parameters
+            if p_hit.z < self.z_min || p_hit.z > self.z_max || phi > self.phi_max {
+                continue;
+            }
+            return Some((t, p_hit, phi));
+        }
+        None
+    }
+}
+
+impl Shape for Paraboloid {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::from([
+            [-self.radius, -self.radius, self.z_min],
+            [self.radius, self.radius, self.z_max],
+        ])
+    }
+
+    fn intersect(&self, r: &Ray, _test_alpha_texture: bool) -> Option<(Float, SurfaceInteraction)> {
+        // Transform _Ray_ to object space
+        let ray = self.data.world_to_object.transform_ray(r);
+        let (t_shape_hit, p_hit, phi) = self.closest_hit(&ray)?;
+
+        // Find parametric representation of paraboloid hit
+        let u = phi / self.phi_max;
+        let dz = self.z_max - self.z_min;
+        let v = (p_hit.z - self.z_min) / dz;
+
+        // Compute paraboloid dpdu and dpdv
+        let dpdu = Vector3f::from([-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.]);
+        let dpdv = Vector3f::from([p_hit.x / (2. * p_hit.z), p_hit.y / (2. * p_hit.z), 1.]) * dz;
+
+        // Compute paraboloid dndu and dndv
+        let d2pduu = Vector3f::from([p_hit.x, p_hit.y, 0.]) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::from([-p_hit.y / (2. * p_hit.z), p_hit.x / (2. * p_hit.z), 0.])
+            * (dz * self.phi_max);
+        let z2 = 4. * p_hit.z * p_hit.z;
+        let d2pdvv = Vector3f::from([p_hit.x / z2, p_hit.y / z2, 0.]) * (-dz * dz);
+        let (dndu, dndv) = normal_derivatives(dpdu, dpdv, d2pduu, d2pduv, d2pdvv);
+
+        // Bound the error of the hit point found with the ray equation, o + t * d.
+        let p_error = (Vector3f::from(ray.o).abs() + (ray.d * t_shape_hit).abs()) * gamma(7);
+        let si = SurfaceInteraction::new(
+            p_hit,
+            p_error,
+            Point2f::from([u, v]),
+            -ray.d,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            ray.time,
+            &self.data,
+        );
+        Some((
+            t_shape_hit,
+            self.data.object_to_world.transform_surface_interaction(si),
+        ))
+    }
+
+    fn intersect_p(&self, r: &Ray, _test_alpha_texture: bool) -> bool {
+        let ray = self.data.world_to_object.transform_ray(r);
+        self.closest_hit(&ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        let radius2 = self.radius * self.radius;
+        let k = 4. * self.z_max / radius2;
+        (radius2 * radius2 * self.phi_max / (12. * self.z_max * self.z_max))
+            * ((k * self.z_max + 1.).powf(1.5) - (k * self.z_min + 1.).powf(1.5))
+    }
+
+    /// Sampling paraboloids isn't supported, as in the C++ implementation, so they can't be used
+    /// as area lights.
+    fn sample(&self, _u: Point2f) -> (Interaction, Float) {
+        unimplemented!("Paraboloid::sample not implemented");
+    }
+}
+
+/// Creates a `Paraboloid` from the given `params`.
+pub fn create_paraboloid_shape(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Paraboloid {
+    let radius = params.find_one_float("radius", 1.);
+    let z_min = params.find_one_float("zmin", 0.);
+    let z_max = params.find_one_float("zmax", 1.);
+    let phi_max = params.find_one_float("phimax", 360.);
+    Paraboloid::new(
+        *object_to_world,
+        *world_to_object,
+        reverse_orientation,
+        radius,
+        z_min,
+        z_max,
+        phi_max,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_paraboloid_shape, Paraboloid};
+    use crate::{
+        core::{
+            geometry::{dot, Point3f, Ray, Vector3f},
+            paramset::testutils::make_float_param_set,
+            shape::Shape,
+            transform::Transform,
+        },
+        float::consts::PI,
+        Float,
+    };
+
+    fn ray(o: [Float; 3], d: [Float; 3]) -> Ray {
+        Ray::new(
+            Point3f::from(o),
+            Vector3f::from(d),
+            Float::INFINITY,
+            0.,
+            None,
+        )
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let p = Paraboloid::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            2.,
+            0.,
+            4.,
+            360.,
+        );
+        // z = 4 * (x^2 + y^2) / 4, so x = 1 is hit at z = 1.
+        let (t, si) = p
+            .intersect(&ray([1., 0., 10.], [0., 0., -1.]), false)
+            .expect("hit");
+        assert!((t - 9.).abs() < 1e-4, "t {}", t);
+        assert!((si.p.z - 1.).abs() < 1e-4, "p {:?}", si.p);
+        // The normal is perpendicular to the surface's slope of 2 at x = 1.
+        let n = Vector3f::from(si.n);
+        assert!(
+            dot(n, Vector3f::from([1., 0., 2.])).abs() < 1e-4,
+            "n {:?}",
+            n
+        );
+        assert!((si.uv.y - 0.25).abs() < 1e-4, "uv {:?}", si.uv);
+
+        // Beyond the radius at z_max the ray passes above the cap.
+        assert!(!p.intersect_p(&ray([2.5, 0., 10.], [0., 0., -1.]), false));
+        // Pointing away from the surface.
+        assert!(!p.intersect_p(&ray([0., 0., -1.], [0., 0., -1.]), false));
+        // Horizontal ray through the inside hits the wall.
+        let (t, si) = p
+            .intersect(&ray([0., 0., 1.], [1., 0., 0.]), false)
+            .expect("hit");
+        assert!((t - 1.).abs() < 1e-4, "t {}", t);
+        assert!((si.p.x - 1.).abs() < 1e-4, "p {:?}", si.p);
+    }
+
+    #[test]
+    fn clipped() {
+        let p = Paraboloid::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            1.,
+            0.5,
+            1.,
+            180.,
+        );
+        // Below z_min the vertex is clipped away, the ray continues to nothing.
+        assert!(!p.intersect_p(&ray([0., 0., -5.], [0., 0., 1.]), false));
+        // phi > 180 degrees is clipped away, the near wall is skipped for the far one.
+        let (t, si) = p
+            .intersect(&ray([0., -5., 0.75], [0., 1., 0.]), false)
+            .expect("hit");
+        assert!(t > 5., "t {}", t);
+        assert!(si.p.y > 0., "p {:?}", si.p);
+    }
+
+    #[test]
+    fn create() {
+        let t = Transform::translate([0., 0., 1.]);
+        let p = create_paraboloid_shape(
+            &t,
+            &t.inverse(),
+            false,
+            &make_float_param_set("radius", vec![2.]),
+        );
+        assert_eq!(p.world_bound().p_min, Point3f::from([-2., -2., 1.]));
+        assert_eq!(p.world_bound().p_max, Point3f::from([2., 2., 2.]));
+        // Closed form of the surface of revolution for radius 1, height 1.
+        let p = Paraboloid::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            1.,
+            0.,
+            1.,
+            360.,
+        );
+        let want = PI / 6. * ((5. as Float).powf(1.5) - 1.);
+        assert!(
+            (p.area() - want).abs() < 1e-4,
+            "area {} want {}",
+            p.area(),
+            want
+        );
+    }
+}
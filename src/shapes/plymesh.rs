@@ -0,0 +1,91 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creates triangle meshes from [PLY] files, as referenced by `Shape "plymesh"` in scene files.
+//! The files are read with [read_ply].
+//!
+//! [PLY]: http://paulbourke.net/dataformats/ply/
+//! [read_ply]: crate::core::plyutil::read_ply
+use std::sync::Arc;
+
+use log::error;
+
+use crate::{
+    core::{
+        fileutil::resolve_filename, paramset::ParamSet, plyutil::read_ply, transform::Transform,
+    },
+    shapes::triangle::{create_triangle_mesh, Triangle},
+};
+
+/// Creates the triangles of the mesh in the PLY file named by the `filename` parameter.  Files
+/// that can't be read log an error and produce no triangles.
+pub fn create_ply_mesh(
+    object_to_world: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Triangle>> {
+    let filename = resolve_filename(params.find_one_filename("filename", ""));
+    let (p, n, uv, indices) = match read_ply(&filename) {
+        Ok(mesh) => mesh,
+        Err(err) => {
+            error!("Couldn't read PLY file '{}': {}", filename.display(), err);
+            return Vec::new();
+        }
+    };
+    if indices.is_empty() {
+        error!("PLY file '{}' has no triangles", filename.display());
+        return Vec::new();
+    }
+    // TODO(wathiede): support "alpha" and "shadowalpha" textures.
+    create_triangle_mesh(
+        object_to_world,
+        &object_to_world.inverse(),
+        reverse_orientation,
+        indices,
+        p,
+        Vec::new(),
+        n,
+        uv,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_ply_mesh;
+    use crate::core::{
+        paramset::testutils::make_filename_param_set, shape::Shape, transform::Transform,
+    };
+
+    #[test]
+    fn single_triangle() {
+        let t = Transform::identity();
+        let tris = create_ply_mesh(
+            &t,
+            false,
+            &make_filename_param_set(
+                "filename",
+                vec!["src/shapes/testdata/triangle.ply".to_string()],
+            ),
+        );
+        assert_eq!(tris.len(), 1);
+        assert!((tris[0].area() - 0.5).abs() < 1e-6);
+
+        let tris = create_ply_mesh(
+            &t,
+            false,
+            &make_filename_param_set("filename", vec!["does-not-exist.ply".to_string()]),
+        );
+        assert!(tris.is_empty());
+    }
+}
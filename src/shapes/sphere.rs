@@ -0,0 +1,382 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a (possibly partial) sphere [Shape] centered at the origin of object space.
+//!
+//! [Shape]: crate::core::shape::Shape
+
+use crate::{
+    clamp,
+    core::{
+        geometry::{dot, Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        paramset::ParamSet,
+        sampling::uniform_sample_sphere,
+        shape::{normal_derivatives, Shape, ShapeData},
+        transform::Transform,
+    },
+    float::consts::PI,
+    gamma, quadratic, Float,
+};
+
+/// Sphere of `radius` centered at the object space origin.  The sphere may be cut off along the
+/// z axis and swept less than a full circle around it.
+#[derive(Debug)]
+pub struct Sphere {
+    data: ShapeData,
+    radius: Float,
+    z_min: Float,
+    z_max: Float,
+    theta_min: Float,
+    theta_max: Float,
+    phi_max: Float,
+}
+
+impl Sphere {
+    /// Create a new `Sphere` clipped to [`z_min`, `z_max`] and swept up to `phi_max` degrees
+    /// around the z axis.
+    ///
+    /// # Examples
+    /// ```
+    /// use pbrt::{
+    ///     core::{
+    ///         geometry::{Point3f, Ray, Vector3f},
+    ///         shape::Shape,
+    ///         transform::Transform,
+    ///     },
+    ///     shapes::sphere::Sphere,
+    /// };
+    ///
+    /// let s = Sphere::new(
+    ///     Transform::identity(),
+    ///     Transform::identity(),
+    ///     false,
+    ///     1.,
+    ///     -1.,
+    ///     1.,
+    ///     360.,
+    /// );
+    /// let r = Ray::new(
+    ///     Point3f::from([0., 0., -5.]),
+    ///     Vector3f::from([0., 0., 1.]),
+    ///     pbrt::Float::INFINITY,
+    ///     0.,
+    ///     None,
+    /// );
+    /// let (t_hit, si) = s.intersect(&r, false).expect("ray should hit sphere");
+    /// assert!((t_hit - 4.).abs() < 1e-5);
+    /// assert!((si.n.z + 1.).abs() < 1e-5);
+    /// ```
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Sphere {
+        let (z0, z1) = (z_min.min(z_max), z_min.max(z_max));
+        Sphere {
+            data: ShapeData::new(object_to_world, world_to_object, reverse_orientation),
+            radius,
+            z_min: clamp(z0, -radius, radius),
+            z_max: clamp(z1, -radius, radius),
+            theta_min: clamp(z0 / radius, -1., 1.).acos(),
+            theta_max: clamp(z1 / radius, -1., 1.).acos(),
+            phi_max: clamp(phi_max, 0., 360.).to_radians(),
+        }
+    }
+
+    /// Returns the object space hit point and azimuth of the intersection of `ray` with the
+    /// sphere at `t`, or None if the hit is on a clipped away part of the sphere.
+    fn hit(&self, ray: &Ray, t: Float) -> Option<(Point3f, Float)> {
+        let mut p_hit = ray.at(t);
+        // Refine sphere intersection point
+        p_hit = p_hit * (self.radius / Point3f::distance(p_hit, Point3f::default()));
+        if p_hit.x == 0. && p_hit.y == 0. {
+            p_hit.x = 1e-5 * self.radius;
+        }
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0. {
+            phi += 2. * PI;
+        }
+        // Test sphere intersection against clipping parameters
+        if (self.z_min > -self.radius && p_hit.z < self.z_min)
+            || (self.z_max < self.radius && p_hit.z > self.z_max)
+            || phi > self.phi_max
+        {
+            return None;
+        }
+        Some((p_hit, phi))
+    }
+
+    /// Returns the parametric distance to the closest hit of object space `ray` with the sphere
+    /// before `ray.t_max`, along with the object space hit point and its azimuth.
+    fn closest_hit(&self, ray: &Ray) -> Option<(Float, Point3f, Float)> {
+        let o = Vector3f::from(ray.o);
+        let a = ray.d.length_squared();
+        let b = 2. * dot(ray.d, o);
+        let c = o.length_squared() - self.radius * self.radius;
+        let (t0, t1) = quadratic(a, b, c)?;
+
+        // Check quadric shape _t0_ and _t1_ for nearest intersection
+        let t_max = ray.t_max.get();
+        if t0 > t_max || t1 <= 0. {
+            return None;
+        }
+        for t in [t0, t1] {
+            if t <= 0. || t > t_max {
+                continue;
+            }
+            if let Some((p_hit, phi)) = self.hit(ray, t) {
+                return Some((t, p_hit, phi));
+            }
+        }
+        None
+    }
+}
+
+impl Shape for Sphere {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::from([
+            [-self.radius, -self.radius, self.z_min],
+            [self.radius, self.radius, self.z_max],
+        ])
+    }
+
+    fn intersect(&self, r: &Ray, _test_alpha_texture: bool) -> Option<(Float, SurfaceInteraction)> {
+        // Transform _Ray_ to object space
+        let ray = self.data.world_to_object.transform_ray(r);
+        let (t_shape_hit, p_hit, phi) = self.closest_hit(&ray)?;
+
+        // Find parametric representation of sphere hit
+        let u = phi / self.phi_max;
+        let cos_theta = clamp(p_hit.z / self.radius, -1., 1.);
+        let theta = cos_theta.acos();
+        let v = (theta - self.theta_min) / (self.theta_max - self.theta_min);
+
+        // Compute sphere dpdu and dpdv
+        let z_radius = (p_hit.x * p_hit.x + p_hit.y * p_hit.y).sqrt();
+        let inv_z_radius = 1. / z_radius;
+        let cos_phi = p_hit.x * inv_z_radius;
+        let sin_phi = p_hit.y * inv_z_radius;
+        let dpdu = Vector3f::from([-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.]);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let dpdv = Vector3f::from([
+            p_hit.z * cos_phi,
+            p_hit.z * sin_phi,
+            -self.radius * sin_theta,
+        ]) * (self.theta_max - self.theta_min);
+
+        // Compute sphere dndu and dndv
+        let d2pduu = Vector3f::from([p_hit.x, p_hit.y, 0.]) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::from([-sin_phi, cos_phi, 0.])
+            * ((self.theta_max - self.theta_min) * p_hit.z * self.phi_max);
+        let d2pdvv = Vector3f::from(p_hit)
+            * (-(self.theta_max - self.theta_min) * (self.theta_max - self.theta_min));
+
+        let (dndu, dndv) = normal_derivatives(dpdu, dpdv, d2pduu, d2pduv, d2pdvv);
+
+        // Compute error bounds for sphere intersection
+        let p_error = Vector3f::from(p_hit).abs() * gamma(5);
+        let si = SurfaceInteraction::new(
+            p_hit,
+            p_error,
+            Point2f::from([u, v]),
+            -ray.d,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            ray.time,
+            &self.data,
+        );
+        Some((
+            t_shape_hit,
+            self.data.object_to_world.transform_surface_interaction(si),
+        ))
+    }
+
+    fn intersect_p(&self, r: &Ray, _test_alpha_texture: bool) -> bool {
+        let ray = self.data.world_to_object.transform_ray(r);
+        self.closest_hit(&ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.phi_max * self.radius * (self.z_max - self.z_min)
+    }
+
+    /// Samples a point uniformly over the full sphere, partial spheres are not accounted for.
+    fn sample(&self, u: Point2f) -> (Interaction, Float) {
+        let mut p_obj = Point3f::from(uniform_sample_sphere(u) * self.radius);
+        let mut n = self
+            .data
+            .object_to_world
+            .transform_normal(Normal3f::from([p_obj.x, p_obj.y, p_obj.z]))
+            .normalize();
+        if self.data.reverse_orientation {
+            n = -n;
+        }
+        // Reproject _p_obj_ to sphere surface
+        p_obj = p_obj * (self.radius / Point3f::distance(p_obj, Point3f::default()));
+        let p_obj_error = Vector3f::from(p_obj).abs() * gamma(5);
+        let (p, p_error) = self
+            .data
+            .object_to_world
+            .transform_point_with_abs_error(p_obj, p_obj_error);
+        let it = Interaction {
+            p,
+            p_error,
+            n,
+            ..Default::default()
+        };
+        (it, 1. / self.area())
+    }
+}
+
+/// Creates a `Sphere` from the given `params`.
+pub fn create_sphere_shape(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Sphere {
+    let radius = params.find_one_float("radius", 1.);
+    let z_min = params.find_one_float("zmin", -radius);
+    let z_max = params.find_one_float("zmax", radius);
+    let phi_max = params.find_one_float("phimax", 360.);
+    Sphere::new(
+        *object_to_world,
+        *world_to_object,
+        reverse_orientation,
+        radius,
+        z_min,
+        z_max,
+        phi_max,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sphere;
+    use crate::{
+        core::{
+            geometry::{dot, Point2f, Point3f, Ray, Vector3f},
+            interaction::Interaction,
+            shape::Shape,
+            transform::Transform,
+        },
+        float::consts::PI,
+        Float,
+    };
+
+    fn ray(o: [Float; 3], d: [Float; 3]) -> Ray {
+        Ray::new(
+            Point3f::from(o),
+            Vector3f::from(d),
+            Float::INFINITY,
+            0.,
+            None,
+        )
+    }
+
+    #[test]
+    fn translated() {
+        let o2w = Transform::translate([0., 0., 5.]);
+        let s = Sphere::new(o2w, o2w.inverse(), false, 1., -1., 1., 360.);
+        let (t, si) = s
+            .intersect(&ray([0., 0., 0.], [0., 0., 1.]), false)
+            .expect("hit");
+        assert!((t - 4.).abs() < 1e-4, "t {}", t);
+        assert!((si.p.z - 4.).abs() < 1e-4, "p {:?}", si.p);
+        assert!((si.n.z + 1.).abs() < 1e-4, "n {:?}", si.n);
+        assert!(!s.intersect_p(&ray([0., 2., 0.], [0., 0., 1.]), false));
+        let b = s.world_bound();
+        assert_eq!(b.p_min, Point3f::from([-1., -1., 4.]));
+        assert_eq!(b.p_max, Point3f::from([1., 1., 6.]));
+    }
+
+    #[test]
+    fn inside_and_reversed() {
+        let s = Sphere::new(
+            Transform::identity(),
+            Transform::identity(),
+            true,
+            2.,
+            -2.,
+            2.,
+            360.,
+        );
+        let (t, si) = s
+            .intersect(&ray([0., 0., 0.], [1., 0., 0.]), false)
+            .expect("hit");
+        assert!((t - 2.).abs() < 1e-4);
+        // Reversed orientation makes the normal point inward.
+        assert!((si.n.x + 1.).abs() < 1e-4, "n {:?}", si.n);
+    }
+
+    #[test]
+    fn partial() {
+        let s = Sphere::new(
+            Transform::identity(),
+            Transform::identity(),
+            false,
+            1.,
+            0.,
+            1.,
+            180.,
+        );
+        // Bottom half is clipped away, hits the inside of the top half from below.
+        let (t, _) = s
+            .intersect(&ray([0.1, 0.1, -5.], [0., 0., 1.]), false)
+            .expect("hit");
+        assert!(t > 5.);
+        // phi > 180 degrees is clipped away.
+        assert!(!s.intersect_p(&ray([0.1, -0.5, -5.], [0., 0., 1.]), false));
+        assert!((s.area() - 2. * crate::float::consts::PI / 2.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sample() {
+        let o2w = Transform::translate([0., 0., 5.]);
+        let s = Sphere::new(o2w, o2w.inverse(), false, 2., -2., 2., 360.);
+        let (it, pdf) = s.sample(Point2f::from([0.3, 0.7]));
+        assert!(
+            (Point3f::distance(it.p, Point3f::from([0., 0., 5.])) - 2.).abs() < 1e-4,
+            "p {:?}",
+            it.p
+        );
+        assert!((pdf - 1. / (16. * PI)).abs() < 1e-6, "pdf {}", pdf);
+        // Normal points away from the center.
+        assert!(dot(Vector3f::from(it.n), it.p - Point3f::from([0., 0., 5.])) > 0.);
+
+        // The solid angle density from sample_ref agrees with pdf_ref for the sampled direction.
+        let ref_ = Interaction::default();
+        let (it, pdf) = s.sample_ref(&ref_, Point2f::from([0.9, 0.2]));
+        let wi = (it.p - ref_.p).normalize();
+        let want = s.pdf_ref(&ref_, &wi);
+        assert!(
+            (pdf - want).abs() / want < 1e-3,
+            "pdf {} want {}",
+            pdf,
+            want
+        );
+        assert_eq!(s.pdf_ref(&ref_, &Vector3f::from([1., 0., 0.])), 0.);
+    }
+}
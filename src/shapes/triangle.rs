@@ -0,0 +1,740 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements [TriangleMesh], the vertex data shared by a mesh, and the [Triangle] [Shape]
+//! referencing one face of it.
+//!
+//! [Shape]: crate::core::shape::Shape
+use std::sync::Arc;
+
+use log::error;
+
+use crate::{
+    core::{
+        geometry::{coordinate_system, cross, Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f},
+        interaction::{Interaction, SurfaceInteraction},
+        paramset::ParamSet,
+        sampling::uniform_sample_triangle,
+        shape::{Shape, ShapeData},
+        transform::Transform,
+    },
+    gamma, Float,
+};
+
+/// TriangleMesh holds the vertex data for all the triangles of a mesh.  Positions, normals and
+/// tangents are transformed to world space when the mesh is created, so triangles don't need to
+/// transform rays to intersect them.
+#[derive(Debug)]
+pub struct TriangleMesh {
+    /// Number of triangles in the mesh.
+    pub n_triangles: usize,
+    /// Three indices into the per-vertex data for each triangle.
+    pub vertex_indices: Vec<usize>,
+    /// World space vertex positions.
+    pub p: Vec<Point3f>,
+    /// Optional world space per-vertex shading normals, empty if not given.
+    pub n: Vec<Normal3f>,
+    /// Optional world space per-vertex tangents, empty if not given.
+    pub s: Vec<Vector3f>,
+    /// Optional per-vertex parametric coordinates, empty if not given.
+    pub uv: Vec<Point2f>,
+}
+
+impl TriangleMesh {
+    /// Create a new `TriangleMesh`, transforming `p`, `n` and `s` from object space to world
+    /// space with `object_to_world`.  `n`, `s` and `uv` may be empty, otherwise they must have
+    /// one entry per vertex in `p`.
+    pub fn new(
+        object_to_world: &Transform,
+        vertex_indices: Vec<usize>,
+        p: Vec<Point3f>,
+        s: Vec<Vector3f>,
+        n: Vec<Normal3f>,
+        uv: Vec<Point2f>,
+    ) -> TriangleMesh {
+        debug_assert_eq!(vertex_indices.len() % 3, 0);
+        TriangleMesh {
+            n_triangles: vertex_indices.len() / 3,
+            vertex_indices,
+            p: p.into_iter()
+                .map(|p| object_to_world.transform_point(p))
+                .collect(),
+            n: n.into_iter()
+                .map(|n| object_to_world.transform_normal(n))
+                .collect(),
+            s: s.into_iter()
+                .map(|s| object_to_world.transform_vector(s))
+                .collect(),
+            uv,
+        }
+    }
+}
+
+/// Triangle is a single face of a [TriangleMesh].
+#[derive(Debug)]
+pub struct Triangle {
+    data: ShapeData,
+    mesh: Arc<TriangleMesh>,
+    /// Offset of this triangle's first vertex index in `mesh.vertex_indices`.
+    v: usize,
+}
+
+impl Triangle {
+    /// Create the `Triangle` for face `tri_number` of `mesh`.
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        mesh: Arc<TriangleMesh>,
+        tri_number: usize,
+    ) -> Triangle {
+        debug_assert!(tri_number < mesh.n_triangles);
+        Triangle {
+            data: ShapeData::new(object_to_world, world_to_object, reverse_orientation),
+            mesh,
+            v: 3 * tri_number,
+        }
+    }
+
+    /// Returns the mesh's indices of the triangle's three vertices.
+    fn indices(&self) -> [usize; 3] {
+        let vi = &self.mesh.vertex_indices;
+        [vi[self.v], vi[self.v + 1], vi[self.v + 2]]
+    }
+
+    /// Returns the world space positions of the triangle's vertices.
+    fn positions(&self) -> [Point3f; 3] {
+        let [i0, i1, i2] = self.indices();
+        [self.mesh.p[i0], self.mesh.p[i1], self.mesh.p[i2]]
+    }
+
+    /// Returns the parametric coordinates of the triangle's vertices, defaulting to (0, 0),
+    /// (1, 0) and (1, 1) when the mesh doesn't have any.
+    fn uvs(&self) -> [Point2f; 3] {
+        if self.mesh.uv.is_empty() {
+            return [
+                Point2f::from([0., 0.]),
+                Point2f::from([1., 0.]),
+                Point2f::from([1., 1.]),
+            ];
+        }
+        let [i0, i1, i2] = self.indices();
+        [self.mesh.uv[i0], self.mesh.uv[i1], self.mesh.uv[i2]]
+    }
+
+    /// Returns the mesh's shading normals at the triangle's vertices, if it has any.
+    fn normals(&self) -> Option<[Normal3f; 3]> {
+        if self.mesh.n.is_empty() {
+            return None;
+        }
+        let [i0, i1, i2] = self.indices();
+        Some([self.mesh.n[i0], self.mesh.n[i1], self.mesh.n[i2]])
+    }
+
+    /// Returns the parametric distance to the hit of `ray` with the triangle before `ray.t_max`
+    /// and its barycentric coordinates, using the watertight ray-triangle test.
+    fn hit(&self, ray: &Ray) -> Option<(Float, [Float; 3])> {
+        let [p0, p1, p2] = self.positions();
+
+        // Translate vertices based on ray origin
+        let mut p0t = p0 - ray.o;
+        let mut p1t = p1 - ray.o;
+        let mut p2t = p2 - ray.o;
+
+        // Permute components of triangle vertices and ray direction
+        let kz = ray.d.abs().max_dimension();
+        let kx = if kz + 1 == 3 { 0 } else { kz + 1 };
+        let ky = if kx + 1 == 3 { 0 } else { kx + 1 };
+        let d = ray.d.permute(kx, ky, kz);
+        p0t = p0t.permute(kx, ky, kz);
+        p1t = p1t.permute(kx, ky, kz);
+        p2t = p2t.permute(kx, ky, kz);
+
+        // Apply shear transformation to translated vertex positions
+        let sx = -d.x / d.z;
+        let sy = -d.y / d.z;
+        let sz = 1. / d.z;
+        for pt in [&mut p0t, &mut p1t, &mut p2t] {
+            pt.x += sx * pt.z;
+            pt.y += sy * pt.z;
+        }
+
+        // Compute edge function coefficients _e0_, _e1_, and _e2_
+        let mut e0 = p1t.x * p2t.y - p1t.y * p2t.x;
+        let mut e1 = p2t.x * p0t.y - p2t.y * p0t.x;
+        let mut e2 = p0t.x * p1t.y - p0t.y * p1t.x;
+
+        // Fall back to double precision test at triangle edges
+        if e0 == 0. || e1 == 0. || e2 == 0. {
+            // Already double precision when built with "float-as-double".
+            #[cfg_attr(feature = "float-as-double", allow(clippy::unnecessary_cast))]
+            let edge = |a: Vector3f, b: Vector3f| {
+                (a.x as f64 * b.y as f64 - a.y as f64 * b.x as f64) as Float
+            };
+            e0 = edge(p1t, p2t);
+            e1 = edge(p2t, p0t);
+            e2 = edge(p0t, p1t);
+        }
+
+        // Perform triangle edge and determinant tests
+        if (e0 < 0. || e1 < 0. || e2 < 0.) && (e0 > 0. || e1 > 0. || e2 > 0.) {
+            return None;
+        }
+        let det = e0 + e1 + e2;
+        if det == 0. {
+            return None;
+        }
+
+        // Compute scaled hit distance to triangle and test against ray $t$ range
+        let t_scaled = (e0 * p0t.z + e1 * p1t.z + e2 * p2t.z) * sz;
+        let t_max = ray.t_max.get();
+        if (det < 0. && (t_scaled >= 0. || t_scaled < t_max * det))
+            || (det > 0. && (t_scaled <= 0. || t_scaled > t_max * det))
+        {
+            return None;
+        }
+
+        // Compute barycentric coordinates and $t$ value for triangle intersection
+        let inv_det = 1. / det;
+        let t = t_scaled * inv_det;
+        // Ensure that computed triangle $t$ is conservatively greater than zero
+        let max3 = |a: Float, b: Float, c: Float| a.abs().max(b.abs()).max(c.abs());
+        // Compute $\delta_z$ term for triangle $t$ error bounds
+        let max_zt = max3(p0t.z, p1t.z, p2t.z) * sz.abs();
+        let delta_z = gamma(3) * max_zt;
+        // Compute $\delta_x$ and $\delta_y$ terms for triangle $t$ error bounds
+        let max_xt = max3(p0t.x, p1t.x, p2t.x);
+        let max_yt = max3(p0t.y, p1t.y, p2t.y);
+        let delta_x = gamma(5) * (max_xt + max_zt);
+        let delta_y = gamma(5) * (max_yt + max_zt);
+        // Compute $\delta_e$ term for triangle $t$ error bounds
+        let delta_e = 2. * (gamma(2) * max_xt * max_yt + delta_y * max_xt + delta_x * max_yt);
+        // Compute $\delta_t$ term for triangle $t$ error bounds and check _t_
+        let max_e = max3(e0, e1, e2);
+        let delta_t =
+            3. * (gamma(3) * max_e * max_zt + delta_e * max_zt + delta_z * max_e) * inv_det.abs();
+        if t <= delta_t {
+            return None;
+        }
+        Some((t, [e0 * inv_det, e1 * inv_det, e2 * inv_det]))
+    }
+}
+
+impl Shape for Triangle {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn object_bound(&self) -> Bounds3f {
+        let [p0, p1, p2] = self.positions();
+        let w2o = &self.data.world_to_object;
+        Bounds3f::union_point(
+            &Bounds3f::from([w2o.transform_point(p0), w2o.transform_point(p1)]),
+            w2o.transform_point(p2),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        let [p0, p1, p2] = self.positions();
+        Bounds3f::union_point(&Bounds3f::from([p0, p1]), p2)
+    }
+
+    fn intersect(
+        &self,
+        ray: &Ray,
+        _test_alpha_texture: bool,
+    ) -> Option<(Float, SurfaceInteraction)> {
+        let (t, [b0, b1, b2]) = self.hit(ray)?;
+        let [p0, p1, p2] = self.positions();
+
+        // Compute triangle partial derivatives
+        let uv = self.uvs();
+        let duv02 = uv[0] - uv[2];
+        let duv12 = uv[1] - uv[2];
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+        let determinant = duv02.x * duv12.y - duv02.y * duv12.x;
+        let degenerate_uv = determinant.abs() < 1e-8;
+        let inv_det = 1. / determinant;
+        let (mut dpdu, mut dpdv) = if degenerate_uv {
+            (Vector3f::default(), Vector3f::default())
+        } else {
+            (
+                (dp02 * duv12.y - dp12 * duv02.y) * inv_det,
+                (dp12 * duv02.x - dp02 * duv12.x) * inv_det,
+            )
+        };
+        if degenerate_uv || cross(dpdu, dpdv).length_squared() == 0. {
+            // Handle zero determinant for triangle partial derivative matrix
+            let ng = cross(p2 - p0, p1 - p0);
+            if ng.length_squared() == 0. {
+                // The triangle is actually degenerate; the intersection is bogus.
+                return None;
+            }
+            let (u, v) = coordinate_system(ng.normalize());
+            dpdu = u;
+            dpdv = v;
+        }
+
+        // Interpolate $(u,v)$ parametric coordinates and hit point
+        let p_hit = p0 * b0 + p1 * b1 + p2 * b2;
+        let uv_hit = uv[0] * b0 + uv[1] * b1 + uv[2] * b2;
+
+        // Compute error bounds for triangle intersection
+        let p_error = (Vector3f::from(p0 * b0).abs()
+            + Vector3f::from(p1 * b1).abs()
+            + Vector3f::from(p2 * b2).abs())
+            * gamma(7);
+        let mut isect = SurfaceInteraction::new(
+            p_hit,
+            p_error,
+            uv_hit,
+            -ray.d,
+            dpdu,
+            dpdv,
+            Normal3f::default(),
+            Normal3f::default(),
+            ray.time,
+            &self.data,
+        );
+
+        // Override surface normal in _isect_ for triangle
+        let mut n = Normal3f::from(cross(dp02, dp12).normalize());
+        if self.data.reverse_orientation ^ self.data.transform_swaps_handedness {
+            n = -n;
+        }
+        isect.it.n = n;
+        isect.shading.n = n;
+
+        if self.mesh.n.is_empty() && self.mesh.s.is_empty() {
+            return Some((t, isect));
+        }
+
+        // Initialize _Triangle_ shading geometry
+        // Compute shading normal _ns_ for triangle
+        let normals = self.normals();
+        let ns = match normals {
+            Some([n0, n1, n2]) => {
+                let ns = n0 * b0 + n1 * b1 + n2 * b2;
+                if ns.length_squared() > 0. {
+                    ns.normalize()
+                } else {
+                    isect.n
+                }
+            }
+            None => isect.n,
+        };
+
+        // Compute shading tangent _ss_ for triangle
+        let mut ss = if self.mesh.s.is_empty() {
+            isect.dpdu
+        } else {
+            let [i0, i1, i2] = self.indices();
+            self.mesh.s[i0] * b0 + self.mesh.s[i1] * b1 + self.mesh.s[i2] * b2
+        };
+        if ss.length_squared() == 0. {
+            ss = isect.dpdu;
+        }
+
+        // Compute shading bitangent _ts_ for triangle and adjust _ss_
+        let ns_v = Vector3f::from(ns);
+        let mut ts = cross(ss, ns_v);
+        if ts.length_squared() > 0. {
+            ts = ts.normalize();
+            ss = cross(ts, ns_v);
+        } else {
+            let (s, t) = coordinate_system(ns_v);
+            ss = s;
+            ts = t;
+        }
+
+        // Compute $\dndu$ and $\dndv$ for triangle shading geometry
+        let (dndu, dndv) = match normals {
+            Some([n0, n1, n2]) => {
+                if degenerate_uv {
+                    // Compute a somewhat arbitrary coordinate system from the normal
+                    // difference, so that the BSDF still has valid derivatives.
+                    let dn = cross(Vector3f::from(n2 - n0), Vector3f::from(n1 - n0));
+                    if dn.length_squared() == 0. {
+                        (Normal3f::default(), Normal3f::default())
+                    } else {
+                        let (dnu, dnv) = coordinate_system(dn);
+                        (Normal3f::from(dnu), Normal3f::from(dnv))
+                    }
+                } else {
+                    let dn1 = n0 - n2;
+                    let dn2 = n1 - n2;
+                    (
+                        (dn1 * duv12.y - dn2 * duv02.y) * inv_det,
+                        (dn2 * duv02.x - dn1 * duv12.x) * inv_det,
+                    )
+                }
+            }
+            None => (Normal3f::default(), Normal3f::default()),
+        };
+        isect.set_shading_geometry(ss, ts, dndu, dndv, true);
+        Some((t, isect))
+    }
+
+    fn intersect_p(&self, ray: &Ray, _test_alpha_texture: bool) -> bool {
+        self.hit(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        let [p0, p1, p2] = self.positions();
+        0.5 * cross(p1 - p0, p2 - p0).length()
+    }
+
+    fn sample(&self, u: Point2f) -> (Interaction, Float) {
+        let b = uniform_sample_triangle(u);
+        let b2 = 1. - b.x - b.y;
+        let [p0, p1, p2] = self.positions();
+        // Compute surface normal for sampled point on triangle
+        let mut n = Normal3f::from(cross(p1 - p0, p2 - p0).normalize());
+        // Ensure correct orientation of the geometric normal; normal flipped if indicated by
+        // the mesh's shading normals, or the shape's orientation otherwise.
+        match self.normals() {
+            Some([n0, n1, n2]) => {
+                let ns = n0 * b.x + n1 * b.y + n2 * b2;
+                n = n.face_forward(Vector3f::from(ns));
+            }
+            None => {
+                if self.data.reverse_orientation ^ self.data.transform_swaps_handedness {
+                    n = -n;
+                }
+            }
+        }
+        // Compute error bounds for sampled point on triangle
+        let p_error = (Vector3f::from(p0 * b.x).abs()
+            + Vector3f::from(p1 * b.y).abs()
+            + Vector3f::from(p2 * b2).abs())
+            * gamma(6);
+        let it = Interaction {
+            p: p0 * b.x + p1 * b.y + p2 * b2,
+            p_error,
+            n,
+            ..Default::default()
+        };
+        (it, 1. / self.area())
+    }
+}
+
+/// Creates a `Triangle` for each face of the mesh described by `vertex_indices` and the
+/// per-vertex data `p`, `s`, `n` and `uv`.  See [TriangleMesh::new].
+#[allow(clippy::too_many_arguments)]
+pub fn create_triangle_mesh(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    vertex_indices: Vec<usize>,
+    p: Vec<Point3f>,
+    s: Vec<Vector3f>,
+    n: Vec<Normal3f>,
+    uv: Vec<Point2f>,
+) -> Vec<Arc<Triangle>> {
+    let mesh = Arc::new(TriangleMesh::new(
+        object_to_world,
+        vertex_indices,
+        p,
+        s,
+        n,
+        uv,
+    ));
+    create_triangles(object_to_world, world_to_object, reverse_orientation, mesh)
+}
+
+/// Creates a `Triangle` for each face of `mesh`.
+pub fn create_triangles(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    mesh: Arc<TriangleMesh>,
+) -> Vec<Arc<Triangle>> {
+    (0..mesh.n_triangles)
+        .map(|i| {
+            Arc::new(Triangle::new(
+                *object_to_world,
+                *world_to_object,
+                reverse_orientation,
+                Arc::clone(&mesh),
+                i,
+            ))
+        })
+        .collect()
+}
+
+/// Creates the triangles of a `trianglemesh` shape from the given `params`.  Invalid meshes log
+/// an error and produce no triangles.
+pub fn create_triangle_mesh_shape(
+    object_to_world: &Transform,
+    world_to_object: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Triangle>> {
+    let indices = params.find_int("indices").unwrap_or_default();
+    let p = params.find_point3f("P").unwrap_or_default();
+    let mut uv = params.find_point2f("uv").unwrap_or_default();
+    if uv.is_empty() {
+        uv = params.find_point2f("st").unwrap_or_default();
+    }
+    let mut s = params.find_vector3f("S").unwrap_or_default();
+    let mut n = params.find_normal3f("N").unwrap_or_default();
+
+    if indices.is_empty() {
+        if p.len() == 3 {
+            return create_triangle_mesh(
+                object_to_world,
+                world_to_object,
+                reverse_orientation,
+                vec![0, 1, 2],
+                p,
+                s,
+                n,
+                uv,
+            );
+        }
+        error!("Vertex indices \"indices\" not provided with triangle mesh shape");
+        return Vec::new();
+    }
+    let excess = indices.len() % 3;
+    if excess != 0 {
+        error!(
+            "Number of vertex indices {} not a multiple of 3. Discarding {} excess.",
+            indices.len(),
+            excess
+        );
+    }
+    if p.is_empty() {
+        error!("Vertex positions \"P\" not provided with triangle mesh shape");
+        return Vec::new();
+    }
+    if !uv.is_empty() && uv.len() != p.len() {
+        error!("Number of \"uv\"s for triangle mesh must match \"P\"s. Discarding \"uv\"s.");
+        uv.clear();
+    }
+    if !s.is_empty() && s.len() != p.len() {
+        error!("Number of \"S\"s for triangle mesh must match \"P\"s. Discarding \"S\"s.");
+        s.clear();
+    }
+    if !n.is_empty() && n.len() != p.len() {
+        error!("Number of \"N\"s for triangle mesh must match \"P\"s. Discarding \"N\"s.");
+        n.clear();
+    }
+    let indices = &indices[..indices.len() - excess];
+    let mut vertex_indices = Vec::with_capacity(indices.len());
+    for &i in indices {
+        if i < 0 || i as usize >= p.len() {
+            error!(
+                "trianglemesh has out of-bounds vertex index {} ({} \"P\" values were given)",
+                i,
+                p.len()
+            );
+            return Vec::new();
+        }
+        vertex_indices.push(i as usize);
+    }
+    // TODO(wathiede): support "alpha" and "shadowalpha" textures.
+    create_triangle_mesh(
+        object_to_world,
+        world_to_object,
+        reverse_orientation,
+        vertex_indices,
+        p,
+        s,
+        n,
+        uv,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{create_triangle_mesh, create_triangle_mesh_shape, Triangle};
+    use crate::{
+        core::{
+            geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f},
+            paramset::{
+                testutils::{make_int, make_normal3f, make_point3f},
+                ParamSet,
+            },
+            shape::Shape,
+            transform::Transform,
+        },
+        Degree, Float,
+    };
+
+    fn ray(o: [Float; 3], d: [Float; 3]) -> Ray {
+        Ray::new(
+            Point3f::from(o),
+            Vector3f::from(d),
+            Float::INFINITY,
+            0.,
+            None,
+        )
+    }
+
+    /// Unit square in the z = 0 plane made of two triangles.
+    fn quad(t: &Transform, n: Vec<Normal3f>) -> Vec<Arc<Triangle>> {
+        create_triangle_mesh(
+            t,
+            &t.inverse(),
+            false,
+            vec![0, 1, 2, 0, 2, 3],
+            vec![
+                Point3f::from([0., 0., 0.]),
+                Point3f::from([1., 0., 0.]),
+                Point3f::from([1., 1., 0.]),
+                Point3f::from([0., 1., 0.]),
+            ],
+            Vec::new(),
+            n,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let tris = quad(&Transform::translate([0., 0., 1.]), Vec::new());
+        assert_eq!(tris.len(), 2);
+        let r = ray([0.75, 0.25, 5.], [0., 0., -1.]);
+        let (t, si) = tris[0].intersect(&r, false).expect("hit");
+        assert!((t - 4.).abs() < 1e-5, "t {}", t);
+        assert!((si.p - Point3f::from([0.75, 0.25, 1.])).length() < 1e-5);
+        assert!((si.n.z.abs() - 1.).abs() < 1e-5, "n {:?}", si.n);
+        // The point is in the other triangle of the quad.
+        assert!(!tris[1].intersect_p(&r, false));
+        // Outside the quad, parallel to it and pointing away.
+        assert!(!tris[0].intersect_p(&ray([1.5, 0.25, 5.], [0., 0., -1.]), false));
+        assert!(!tris[0].intersect_p(&ray([0.75, 0.25, 5.], [1., 0., 0.]), false));
+        assert!(!tris[0].intersect_p(&ray([0.75, 0.25, 5.], [0., 0., 1.]), false));
+        // Beyond t_max.
+        let r = Ray::new(
+            Point3f::from([0.75, 0.25, 5.]),
+            Vector3f::from([0., 0., -1.]),
+            3.,
+            0.,
+            None,
+        );
+        assert!(!tris[0].intersect_p(&r, false));
+    }
+
+    #[test]
+    fn no_self_intersection() {
+        // A large, tilted ground plane far from the origin, where a fixed epsilon offset is much
+        // smaller than the floating-point error of hit points.
+        let t = Transform::translate([5000., -3000., 2000.])
+            * Transform::rotate(Degree::from(17.), [1., 2., 3.])
+            * Transform::scale(2000., 2000., 1.)
+            * Transform::translate([-0.5, -0.5, 0.]);
+        let tris = quad(&t, Vec::new());
+        let occluded = |r: &Ray| tris.iter().any(|tri| tri.intersect_p(r, false));
+        let n = Vector3f::from(tris[0].intersect(&ray_to(&t, 0.3, 0.1), false).unwrap().1.n);
+        let light = t.transform_point(Point3f::from([0.5, 0.5, 0.])) + n * 500.;
+
+        const STEPS: usize = 16;
+        for i in 0..STEPS {
+            for j in 0..STEPS {
+                let (u, v) = (
+                    (i as Float + 0.5) / STEPS as Float,
+                    (j as Float + 0.5) / STEPS as Float,
+                );
+                let r = ray_to(&t, u, v);
+                let (_, si) = tris
+                    .iter()
+                    .filter_map(|tri| tri.intersect(&r, false))
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .expect("ray missed ground plane");
+                assert!(
+                    !occluded(&si.spawn_ray_to(light)),
+                    "shadow ray from {} self-intersected",
+                    si.p
+                );
+                // Grazing rays leaving the surface, on both sides.
+                let grazing = Vector3f::from(si.dpdu).normalize() + n * 1e-3;
+                assert!(!occluded(&si.spawn_ray(&grazing)), "at {}", si.p);
+                assert!(!occluded(&si.spawn_ray(&(grazing * -1.))), "at {}", si.p);
+            }
+        }
+    }
+
+    /// Returns a ray aimed at the point (u, v) of the unit square in the z = 0 plane transformed
+    /// by `t`, starting well above it.
+    fn ray_to(t: &Transform, u: Float, v: Float) -> Ray {
+        let target = t.transform_point(Point3f::from([u, v, 0.]));
+        let o = t.transform_point(Point3f::from([u * 0.9, v * 1.1, 300.]));
+        Ray::new(o, target - o, Float::INFINITY, 0., None)
+    }
+
+    #[test]
+    fn shading_normals() {
+        let n = Normal3f::from([0., 0., -1.]);
+        let tris = quad(&Transform::identity(), vec![n, n, n, n]);
+        // The geometric normal points up, it is flipped to agree with the shading normal.
+        let (_, si) = tris[0]
+            .intersect(&ray([0.75, 0.25, -1.], [0., 0., 1.]), false)
+            .expect("hit");
+        assert_eq!(si.n, n);
+        assert!((si.shading.n - n).length() < 1e-5, "{:?}", si.shading.n);
+    }
+
+    #[test]
+    fn area_and_sample() {
+        let tris = quad(&Transform::scale(2., 2., 2.), Vec::new());
+        assert!((tris[0].area() - 2.).abs() < 1e-5);
+        assert_eq!(
+            tris[0].world_bound().p_max,
+            Point3f::from([2., 2., 0.]),
+            "{:?}",
+            tris[0].world_bound()
+        );
+        assert_eq!(tris[0].object_bound().p_max, Point3f::from([1., 1., 0.]));
+        let (it, pdf) = tris[0].sample(Point2f::from([0.3, 0.6]));
+        assert!((pdf - 0.5).abs() < 1e-5);
+        assert_eq!(it.p.z, 0.);
+        assert!(it.p.x >= it.p.y, "{:?} outside triangle", it.p);
+    }
+
+    #[test]
+    fn create() {
+        let t = Transform::identity();
+        let p = vec![
+            Point3f::from([0., 0., 0.]),
+            Point3f::from([1., 0., 0.]),
+            Point3f::from([1., 1., 0.]),
+            Point3f::from([0., 1., 0.]),
+        ];
+        let ps: ParamSet = vec![
+            make_int("indices", vec![0, 1, 2, 0, 2, 3]),
+            make_point3f("P", p.clone()),
+        ]
+        .into();
+        assert_eq!(create_triangle_mesh_shape(&t, &t, false, &ps).len(), 2);
+
+        // A single triangle doesn't need indices.
+        let ps: ParamSet = vec![make_point3f("P", p[..3].to_vec())].into();
+        assert_eq!(create_triangle_mesh_shape(&t, &t, false, &ps).len(), 1);
+
+        // Mismatched normals are dropped, bad indices reject the mesh.
+        let ps: ParamSet = vec![
+            make_int("indices", vec![0, 1, 2]),
+            make_point3f("P", p.clone()),
+            make_normal3f("N", vec![Normal3f::from([0., 0., 1.])]),
+        ]
+        .into();
+        assert_eq!(create_triangle_mesh_shape(&t, &t, false, &ps).len(), 1);
+        let ps: ParamSet = vec![make_int("indices", vec![0, 1, 4]), make_point3f("P", p)].into();
+        assert!(create_triangle_mesh_shape(&t, &t, false, &ps).is_empty());
+    }
+}
@@ -131,7 +131,7 @@ where
 
 impl<T> Texture<T> for ConstantTexture<T>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Send + Sync,
 {
     /// Implements [evaluate] that just returns the same value for any `SurfaceInteraction`
     ///
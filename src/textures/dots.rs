@@ -0,0 +1,196 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Texture] of randomly placed polka dots.
+//!
+//! [Texture]: crate::core::texture::Texture
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        paramset::TextureParams,
+        spectrum::Spectrum,
+        texture::{create_uv_mapping_2d, Texture, UVMapping2D},
+        transform::Transform,
+    },
+    Float,
+};
+
+/// Radius of a dot, in units of the cells (u, v) space is divided into.
+const RADIUS: Float = 0.35;
+
+/// Implements trait [Texture] by dividing the (s, t) space `mapping` gives into unit cells, about half of which
+/// contain a dot at a slightly jittered position.  Points inside a dot take their value from the
+/// `inside` texture, all others from `outside`.  Which cells have dots, and where, is decided by
+/// hashing the cell's indices, so the pattern is the same on every evaluation.
+///
+/// [Texture]: crate::core::texture::Texture
+#[derive(Debug)]
+pub struct DotsTexture<T>
+where
+    T: Debug,
+{
+    mapping: UVMapping2D,
+    inside: Arc<dyn Texture<T>>,
+    outside: Arc<dyn Texture<T>>,
+}
+
+impl<T> DotsTexture<T>
+where
+    T: Debug,
+{
+    /// Create a new `DotsTexture` that evaluates `inside` within the dots and `outside`
+    /// elsewhere.
+    pub fn new(
+        mapping: UVMapping2D,
+        inside: Arc<dyn Texture<T>>,
+        outside: Arc<dyn Texture<T>>,
+    ) -> DotsTexture<T> {
+        DotsTexture {
+            mapping,
+            inside,
+            outside,
+        }
+    }
+}
+
+/// Returns a value in [0, 1) that is a pseudo-random function of the cell at (`s`, `t`) and
+/// `salt`, which allows several independent values to be derived for the same cell.
+fn cell_random(s: isize, t: isize, salt: u8) -> Float {
+    let mut hasher = DefaultHasher::new();
+    (s, t, salt).hash(&mut hasher);
+    (hasher.finish() >> 40) as Float / (1 << 24) as Float
+}
+
+impl<T> Texture<T> for DotsTexture<T>
+where
+    T: Debug,
+{
+    /// Implements [evaluate] returning the `inside` texture's value if `si` is within a dot and
+    /// the `outside` texture's value otherwise.
+    ///
+    /// [evaluate]: crate::core::texture::Texture
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let st = self.mapping.map(si);
+        let s_cell = (st.x + 0.5).floor();
+        let t_cell = (st.y + 0.5).floor();
+        let (s, t) = (s_cell as isize, t_cell as isize);
+
+        // Return _inside_ texture if point is inside dot
+        if cell_random(s, t, 0) < 0.5 {
+            let max_shift = 0.5 - RADIUS;
+            let s_center = s_cell + max_shift * (2. * cell_random(s, t, 1) - 1.);
+            let t_center = t_cell + max_shift * (2. * cell_random(s, t, 2) - 1.);
+            let (ds, dt) = (st.x - s_center, st.y - t_center);
+            if ds * ds + dt * dt < RADIUS * RADIUS {
+                return self.inside.evaluate(si);
+            }
+        }
+        self.outside.evaluate(si)
+    }
+}
+
+/// Creates a new `DotsTexture` of `Float`s from the "inside" and "outside" textures in `tp`, which
+/// default to 1 and 0 respectively.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{interaction::SurfaceInteraction, texture::Texture, transform::Transform},
+///     textures::dots::create_dots_float_texture,
+/// };
+///
+/// let t = create_dots_float_texture(&Transform::identity(), &Default::default());
+/// let si = SurfaceInteraction {
+///     uv: [3.25, -1.5].into(),
+///     ..Default::default()
+/// };
+/// let v = t.evaluate(&si);
+/// assert!(v == 0. || v == 1.);
+/// assert_eq!(t.evaluate(&si), v);
+/// ```
+pub fn create_dots_float_texture(_tex2world: &Transform, tp: &TextureParams) -> DotsTexture<Float> {
+    DotsTexture::new(
+        create_uv_mapping_2d(tp),
+        tp.get_float_texture("inside", 1.),
+        tp.get_float_texture("outside", 0.),
+    )
+}
+
+/// Creates a new `DotsTexture` of `Spectrum`s from the "inside" and "outside" textures in `tp`,
+/// which default to 1 and 0 respectively.
+pub fn create_dots_spectrum_texture(
+    _tex2world: &Transform,
+    tp: &TextureParams,
+) -> DotsTexture<Spectrum> {
+    DotsTexture::new(
+        create_uv_mapping_2d(tp),
+        tp.get_spectrum_texture("inside", Spectrum::from(1.)),
+        tp.get_spectrum_texture("outside", Spectrum::from(0.)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn si(u: Float, v: Float) -> SurfaceInteraction {
+        SurfaceInteraction {
+            uv: [u, v].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn deterministic_with_both_values() {
+        let t = create_dots_spectrum_texture(&Transform::identity(), &Default::default());
+        let t2 = create_dots_spectrum_texture(&Transform::identity(), &Default::default());
+        let (mut inside, mut outside) = (0, 0);
+        for i in 0..64 {
+            for j in 0..64 {
+                let si = si(i as Float * 0.25 - 8., j as Float * 0.25 - 8.);
+                let v = t.evaluate(&si);
+                assert_eq!(v, t.evaluate(&si));
+                assert_eq!(v, t2.evaluate(&si));
+                if v == Spectrum::from(1.) {
+                    inside += 1;
+                } else {
+                    assert_eq!(v, Spectrum::from(0.));
+                    outside += 1;
+                }
+            }
+        }
+        assert!(inside > 0, "no dots");
+        assert!(outside > inside, "{} inside, {} outside", inside, outside);
+    }
+
+    #[test]
+    fn dot_centers() {
+        let t = create_dots_float_texture(&Transform::identity(), &Default::default());
+        for s in -10..10 {
+            for u in -10..10 {
+                // Cells with dots contain their center, as the jitter is smaller than the radius.
+                let has_dot = cell_random(s, u, 0) < 0.5;
+                let v = t.evaluate(&si(s as Float, u as Float));
+                assert_eq!(v, if has_dot { 1. } else { 0. }, "cell {} {}", s, u);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Texture] that looks up values in an image file, as referenced by
+//! `Texture "name" "float"|"spectrum" "imagemap"` in scene files.
+//!
+//! [Texture]: crate::core::texture::Texture
+use log::{error, warn};
+
+use crate::{
+    core::{
+        fileutil::resolve_filename,
+        geometry::{Point2i, Vector2f},
+        imageio::{is_8_bit, read_image_cached},
+        interaction::SurfaceInteraction,
+        mipmap::{ImageWrap, MIPMap, Texel},
+        paramset::TextureParams,
+        spectrum::{RGBSpectrum, Spectrum},
+        texture::{create_uv_mapping_2d, Texture, UVMapping2D},
+        transform::Transform,
+    },
+    Float,
+};
+
+/// ImageTexture filters the texels of an image with a [MIPMap], indexed by the texture
+/// coordinates `mapping` gives.  (0, 0) is the lower left corner of the image.
+///
+/// [MIPMap]: crate::core::mipmap::MIPMap
+#[derive(Debug)]
+pub struct ImageTexture<T> {
+    mapping: UVMapping2D,
+    mipmap: MIPMap<T>,
+}
+
+/// Parses the `wrap` parameter of an image texture, unknown values are treated as `repeat`.
+fn parse_wrap(wrap: &str) -> ImageWrap {
+    match wrap {
+        "repeat" => ImageWrap::Repeat,
+        "black" => ImageWrap::Black,
+        "clamp" => ImageWrap::Clamp,
+        _ => {
+            warn!("Unknown wrap mode \"{}\", using \"repeat\"", wrap);
+            ImageWrap::Repeat
+        }
+    }
+}
+
+impl<T: Texel> ImageTexture<T> {
+    /// Create a new `ImageTexture`, mapped with `mapping`, from the image file `filename`, with
+    /// each texel converted by `convert` after being multiplied by `scale`.  When `gamma` is set
+    /// 8-bit images are converted from sRGB to linear values.  If the image can't be read, the
+    /// texture is the constant `scale`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mapping: UVMapping2D,
+        filename: &str,
+        do_trilinear: bool,
+        max_aniso: Float,
+        wrap_mode: ImageWrap,
+        scale: Float,
+        gamma: bool,
+        convert: impl Fn(&RGBSpectrum) -> T,
+    ) -> ImageTexture<T> {
+        let (texels, resolution) = match read_image_cached(filename, gamma) {
+            Ok(img) => {
+                let (texels, res) = &*img;
+                // Flip image in y; texture coordinate space has (0,0) at the lower left corner.
+                let texels = texels
+                    .chunks(res.x as usize)
+                    .rev()
+                    .flatten()
+                    .map(|t| convert(&(t.clone() * scale)))
+                    .collect();
+                (texels, *res)
+            }
+            Err(err) => {
+                error!(
+                    "Couldn't read image texture '{}': {}, using a constant texture",
+                    filename, err
+                );
+                (
+                    vec![convert(&RGBSpectrum::new(scale))],
+                    Point2i::from([1, 1]),
+                )
+            }
+        };
+        ImageTexture {
+            mapping,
+            mipmap: MIPMap::new(&resolution, texels, do_trilinear, max_aniso, wrap_mode),
+        }
+    }
+}
+
+impl<T: Texel> Texture<T> for ImageTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        // TODO(wathiede): filter over the footprint given by ray differentials once
+        // SurfaceInteraction computes them.
+        let zero = Vector2f::from([0., 0.]);
+        self.mipmap
+            .lookup_differentials(self.mapping.map(si), zero, zero)
+    }
+}
+
+/// Reads the parameters shared by float and spectrum image textures, and creates the texture
+/// with `convert`.
+fn create_image_texture<T: Texel>(
+    tp: &TextureParams,
+    convert: impl Fn(&RGBSpectrum) -> T,
+) -> ImageTexture<T> {
+    let mapping = create_uv_mapping_2d(tp);
+    let max_aniso = tp.find_float("maxanisotropy", 8.);
+    let trilerp = tp.find_bool("trilinear", false);
+    let wrap_mode = parse_wrap(&tp.find_string("wrap", "repeat"));
+    let scale = tp.find_float("scale", 1.);
+    let filename = resolve_filename(tp.find_filename("filename", ""));
+    let filename = filename.to_string_lossy();
+    let gamma = tp.find_bool("gamma", is_8_bit(&filename));
+    ImageTexture::new(
+        mapping, &filename, trilerp, max_aniso, wrap_mode, scale, gamma, convert,
+    )
+}
+
+/// Creates new `ImageTexture` from the given `TextureParams` with `Float` as the data type.
+/// Texels are converted to their luminance.
+pub fn create_image_float_texture(
+    _tex2world: &Transform,
+    tp: &TextureParams,
+) -> ImageTexture<Float> {
+    create_image_texture(tp, |rgb| rgb.y())
+}
+
+/// Creates new `ImageTexture` from the given `TextureParams` with `Spectrum` as the data type.
+pub fn create_image_spectrum_texture(
+    _tex2world: &Transform,
+    tp: &TextureParams,
+) -> ImageTexture<Spectrum> {
+    create_image_texture(tp, |rgb| Spectrum::from_rgb(rgb.to_rgb()))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{save_buffer_with_format, ColorType, ImageFormat};
+
+    use super::*;
+    use crate::core::{
+        geometry::Point2f,
+        paramset::{
+            testutils::{make_bool, make_filename, make_string},
+            ParamSet,
+        },
+    };
+
+    fn si(uv: [Float; 2]) -> SurfaceInteraction {
+        SurfaceInteraction {
+            uv: Point2f::from(uv),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn corners() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let path = dir.path().join("2x2.png");
+        // Top row red and green, bottom row blue and white.
+        #[rustfmt::skip]
+        let pixels = [
+            255, 0, 0,    0, 255, 0,
+            0, 0, 255,    255, 255, 255,
+        ];
+        save_buffer_with_format(&path, &pixels, 2, 2, ColorType::Rgb8, ImageFormat::Png)
+            .expect("failed to write png");
+        let params: ParamSet = vec![
+            make_filename("filename", vec![path.to_string_lossy().to_string()]),
+            make_string("wrap", vec!["clamp".to_string()]),
+            make_bool("gamma", vec![false]),
+        ]
+        .into();
+        let tp = TextureParams::new(
+            params,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        let tex = create_image_spectrum_texture(&Transform::identity(), &tp);
+        for (uv, want) in [
+            ([0., 0.], [0., 0., 1.]),
+            ([1., 0.], [1., 1., 1.]),
+            ([0., 1.], [1., 0., 0.]),
+            ([1., 1.], [0., 1., 0.]),
+        ] {
+            assert_eq!(tex.evaluate(&si(uv)), Spectrum::from_rgb(want), "{:?}", uv);
+        }
+        // The center blends all four.
+        assert_eq!(
+            tex.evaluate(&si([0.5, 0.5])),
+            Spectrum::from_rgb([0.5, 0.5, 0.5])
+        );
+
+        let tex = create_image_float_texture(&Transform::identity(), &tp);
+        assert!((tex.evaluate(&si([1., 0.])) - 1.).abs() < 1e-5);
+        assert!((tex.evaluate(&si([0., 0.])) - 0.072169).abs() < 1e-5);
+    }
+
+    #[test]
+    fn missing_file() {
+        let params: ParamSet = vec![make_filename(
+            "filename",
+            vec!["does-not-exist.png".to_string()],
+        )]
+        .into();
+        let tp = TextureParams::new(
+            params,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let tex = create_image_float_texture(&Transform::identity(), &tp);
+        assert_eq!(tex.evaluate(&si([0.3, 0.7])), 1.);
+    }
+}
@@ -16,3 +16,9 @@
 //!
 //! [Texture]: crate::core::texture::Texture
 pub mod constant;
+pub mod dots;
+pub mod fbm;
+pub mod image;
+pub mod uv;
+pub mod windy;
+pub mod wrinkled;
@@ -0,0 +1,114 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Texture] that shows the surface's (u, v) parameterization as colors, useful for
+//! debugging shapes.
+//!
+//! [Texture]: crate::core::texture::Texture
+use crate::core::{
+    interaction::SurfaceInteraction,
+    paramset::TextureParams,
+    spectrum::Spectrum,
+    texture::{create_uv_mapping_2d, Texture, UVMapping2D},
+    transform::Transform,
+};
+
+/// Implements trait [Texture] to return the RGB color (s, t, 0) for the texture coordinates
+/// (s, t) `mapping` gives, wrapped to [0, 1).
+///
+/// [Texture]: crate::core::texture::Texture
+#[derive(Clone, Debug, Default)]
+pub struct UVTexture {
+    mapping: UVMapping2D,
+}
+
+impl UVTexture {
+    /// Create a new `UVTexture` showing the texture coordinates given by `mapping`.
+    pub fn new(mapping: UVMapping2D) -> UVTexture {
+        UVTexture { mapping }
+    }
+}
+
+/// Creates a new `UVTexture`.  It only takes the 2D mapping parameters.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{
+///         interaction::SurfaceInteraction, spectrum::Spectrum, texture::Texture,
+///         transform::Transform,
+///     },
+///     textures::uv::create_uv_spectrum_texture,
+/// };
+///
+/// let t = create_uv_spectrum_texture(&Transform::identity(), &Default::default());
+/// let si = SurfaceInteraction {
+///     uv: [0.5, 0.25].into(),
+///     ..Default::default()
+/// };
+/// assert_eq!(t.evaluate(&si), Spectrum::from_rgb([0.5, 0.25, 0.]));
+/// ```
+pub fn create_uv_spectrum_texture(_tex2world: &Transform, tp: &TextureParams) -> UVTexture {
+    UVTexture::new(create_uv_mapping_2d(tp))
+}
+
+impl Texture<Spectrum> for UVTexture {
+    /// Implements [evaluate] returning the wrapped texture coordinates of `si` as red and green.
+    ///
+    /// [evaluate]: crate::core::texture::Texture
+    fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
+        let st = self.mapping.map(si);
+        Spectrum::from_rgb([st.x - st.x.floor(), st.y - st.y.floor(), 0.])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::paramset::testutils::make_float_param_set;
+
+    #[test]
+    fn wraps() {
+        let t = UVTexture::default();
+        for (uv, want) in [
+            ([0., 0.], [0., 0., 0.]),
+            ([1.25, 2.5], [0.25, 0.5, 0.]),
+            ([-0.25, -1.75], [0.75, 0.25, 0.]),
+        ] {
+            let si = SurfaceInteraction {
+                uv: uv.into(),
+                ..Default::default()
+            };
+            assert_eq!(t.evaluate(&si), Spectrum::from_rgb(want), "{:?}", uv);
+        }
+    }
+
+    #[test]
+    fn uscale_doubles_s() {
+        let tp = TextureParams::new(
+            make_float_param_set("uscale", vec![2.]),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let t = create_uv_spectrum_texture(&Transform::identity(), &tp);
+        let si = SurfaceInteraction {
+            uv: [0.2, 0.3].into(),
+            ..Default::default()
+        };
+        let rgb = t.evaluate(&si);
+        assert!((rgb.channels()[0] - 0.4).abs() < 1e-6, "{:?}", rgb);
+        assert!((rgb.channels()[1] - 0.3).abs() < 1e-6, "{:?}", rgb);
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Texture] that looks like waves on water blown by gusts of wind.
+//!
+//! [Texture]: crate::core::texture::Texture
+use std::{fmt::Debug, marker::PhantomData};
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        paramset::TextureParams,
+        spectrum::Spectrum,
+        texture::{fbm, IdentityMapping3D, Texture},
+        transform::Transform,
+    },
+    Float,
+};
+
+/// Implements trait [Texture] by scaling high frequency [fbm] waves by the magnitude of low
+/// frequency [fbm] wind strength.
+///
+/// [Texture]: crate::core::texture::Texture
+/// [fbm]: crate::core::texture::fbm
+#[derive(Debug)]
+pub struct WindyTexture<T> {
+    mapping: IdentityMapping3D,
+    phantom: PhantomData<T>,
+}
+
+impl<T> WindyTexture<T> {
+    /// Create a new `WindyTexture` evaluated at points mapped to texture space by `mapping`.
+    pub fn new(mapping: IdentityMapping3D) -> WindyTexture<T> {
+        WindyTexture {
+            mapping,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Texture<T> for WindyTexture<T>
+where
+    T: Debug + From<Float> + Send + Sync,
+{
+    /// Implements [evaluate] returning the height of the waves at `si`.
+    ///
+    /// [evaluate]: crate::core::texture::Texture
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let (p, dpdx, dpdy) = self.mapping.map(si);
+        let wind_strength = fbm(p * 0.1, dpdx * 0.1, dpdy * 0.1, 0.5, 3);
+        let wave_height = fbm(p, dpdx, dpdy, 0.5, 6);
+        T::from(wind_strength.abs() * wave_height)
+    }
+}
+
+/// Creates a new `WindyTexture` of `Float`s.  It has no parameters.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{texture::Texture, transform::Transform},
+///     textures::windy::create_windy_float_texture,
+/// };
+///
+/// let t = create_windy_float_texture(&Transform::identity(), &Default::default());
+/// // The origin is a lattice point of the noise, so there's no wind there.
+/// assert_eq!(t.evaluate(&Default::default()), 0.);
+/// ```
+pub fn create_windy_float_texture(
+    tex2world: &Transform,
+    _tp: &TextureParams,
+) -> WindyTexture<Float> {
+    // Initialize 3D texture mapping _map_ from _tp_
+    WindyTexture::new(IdentityMapping3D::new(tex2world.inverse()))
+}
+
+/// Creates a new `WindyTexture` of `Spectrum`s.  It has no parameters.
+pub fn create_windy_spectrum_texture(
+    tex2world: &Transform,
+    _tp: &TextureParams,
+) -> WindyTexture<Spectrum> {
+    // Initialize 3D texture mapping _map_ from _tp_
+    WindyTexture::new(IdentityMapping3D::new(tex2world.inverse()))
+}
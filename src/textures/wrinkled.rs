@@ -0,0 +1,129 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements a [Texture] of turbulence, a sum of the absolute values of octaves of Perlin noise
+//! that looks like wrinkled material.
+//!
+//! [Texture]: crate::core::texture::Texture
+use std::{fmt::Debug, marker::PhantomData};
+
+use crate::{
+    core::{
+        interaction::SurfaceInteraction,
+        paramset::TextureParams,
+        spectrum::Spectrum,
+        texture::{turbulence, IdentityMapping3D, Texture},
+        transform::Transform,
+    },
+    Float,
+};
+
+/// Implements trait [Texture] returning [turbulence] evaluated at the texture space position of
+/// the surface.  At most `octaves` octaves are summed, each weighted `omega` times the previous.
+///
+/// [Texture]: crate::core::texture::Texture
+/// [turbulence]: crate::core::texture::turbulence
+#[derive(Debug)]
+pub struct WrinkledTexture<T> {
+    mapping: IdentityMapping3D,
+    octaves: usize,
+    omega: Float,
+    phantom: PhantomData<T>,
+}
+
+impl<T> WrinkledTexture<T> {
+    /// Create a new `WrinkledTexture` evaluated at points mapped to texture space by `mapping`.
+    pub fn new(mapping: IdentityMapping3D, octaves: usize, omega: Float) -> WrinkledTexture<T> {
+        WrinkledTexture {
+            mapping,
+            octaves,
+            omega,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Texture<T> for WrinkledTexture<T>
+where
+    T: Debug + From<Float> + Send + Sync,
+{
+    /// Implements [evaluate] returning the noise at `si`.
+    ///
+    /// [evaluate]: crate::core::texture::Texture
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let (p, dpdx, dpdy) = self.mapping.map(si);
+        T::from(turbulence(p, dpdx, dpdy, self.omega, self.octaves))
+    }
+}
+
+/// Reads the "octaves" and "roughness" parameters shared by the float and spectrum textures.
+fn create_wrinkled_texture<T>(tex2world: &Transform, tp: &TextureParams) -> WrinkledTexture<T> {
+    // Initialize 3D texture mapping _map_ from _tp_
+    let mapping = IdentityMapping3D::new(tex2world.inverse());
+    WrinkledTexture::new(
+        mapping,
+        tp.find_int("octaves", 8).max(0) as usize,
+        tp.find_float("roughness", 0.5),
+    )
+}
+
+/// Creates a new `WrinkledTexture` of `Float`s from `tp`.  "integer octaves" defaults to 8 and "float
+/// roughness", the weight of each octave relative to the previous, to 0.5.
+///
+/// # Examples
+/// ```
+/// use pbrt::{
+///     core::{
+///         interaction::{Interaction, SurfaceInteraction},
+///         paramset::{testutils::make_int_param_set, TextureParams},
+///         texture::{noise, Texture},
+///         transform::Transform,
+///     },
+///     textures::wrinkled::create_wrinkled_float_texture,
+/// };
+///
+/// let tp = TextureParams::new(
+///     make_int_param_set("octaves", vec![1]),
+///     Default::default(),
+///     Default::default(),
+///     Default::default(),
+/// );
+/// let t = create_wrinkled_float_texture(&Transform::translate([0.5, 0., 0.]), &tp);
+/// let si = SurfaceInteraction {
+///     it: Interaction {
+///         p: [1.75, 0.3, 0.6].into(),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// // With one octave it's the magnitude of noise, evaluated in texture space, plus the average
+/// // value of the next, half weight, octave.
+/// let want = noise([1.25, 0.3, 0.6].into()).abs() + 0.5 * 0.2;
+/// assert!((t.evaluate(&si) - want).abs() < 1e-6);
+/// ```
+pub fn create_wrinkled_float_texture(
+    tex2world: &Transform,
+    tp: &TextureParams,
+) -> WrinkledTexture<Float> {
+    create_wrinkled_texture(tex2world, tp)
+}
+
+/// Creates a new `WrinkledTexture` of `Spectrum`s from `tp`, with the same parameters as
+/// [create_wrinkled_float_texture].
+pub fn create_wrinkled_spectrum_texture(
+    tex2world: &Transform,
+    tp: &TextureParams,
+) -> WrinkledTexture<Spectrum> {
+    create_wrinkled_texture(tex2world, tp)
+}
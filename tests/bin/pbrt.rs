@@ -0,0 +1,40 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+#[test]
+#[ignore = "runs the pbrt binary, use `cargo test -- --ignored` to include it"]
+fn scene_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pbrt"))
+        .args(["--verbose", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn pbrt");
+    child
+        .stdin
+        .take()
+        .expect("child stdin")
+        .write_all(br#"Sampler "stratified" "integer xsamples" [ 2 ]"#)
+        .expect("failed to write scene");
+    let output = child.wait_with_output().expect("pbrt failed");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Rendered -"), "{}", stdout);
+    assert!(stdout.contains(r#"Sampler "stratified""#), "{}", stdout);
+}
@@ -0,0 +1,49 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::process::Command;
+
+fn pbrt_lint(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_pbrt-lint"))
+        .args(args)
+        .output()
+        .expect("failed to run pbrt-lint")
+}
+
+#[test]
+fn clean_scene() {
+    let output = pbrt_lint(&["src/core/testdata/lint/clean.pbrt"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn json_report() {
+    let output = pbrt_lint(&[
+        "--json",
+        "src/core/testdata/lint/clean.pbrt",
+        "src/core/testdata/lint/undefined.pbrt",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let findings: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("invalid JSON report");
+    let findings = findings.as_array().expect("findings array");
+    assert_eq!(findings.len(), 7, "{:?}", findings);
+    assert_eq!(findings[0]["file"], "src/core/testdata/lint/undefined.pbrt");
+    assert_eq!(findings[0]["kind"], "undefined-reference");
+    assert_eq!(findings[0]["directive"], "CoordSysTransform");
+    assert_eq!(
+        findings[0]["message"],
+        "coordinate system \"camera\" undefined"
+    );
+}
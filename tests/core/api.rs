@@ -0,0 +1,114 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::io::{self, Read};
+
+use pbrt::core::api::{PbrtAPI, API};
+
+#[test]
+fn world_end_renders_image() {
+    let dir = tempfile::tempdir().expect("failed to create tempdir");
+    let path = dir.path().join("out.pfm");
+    let scene = format!(
+        r#"
+Film "image" "integer xresolution" [ 8 ] "integer yresolution" [ 8 ]
+    "string filename" [ "{}" ]
+Sampler "stratified" "integer xsamples" [ 1 ] "integer ysamples" [ 1 ]
+Integrator "whitted"
+WorldBegin
+WorldEnd
+"#,
+        path.display()
+    );
+
+    let mut pbrt = PbrtAPI::default();
+    pbrt.init();
+    pbrt.parse_string(scene.as_bytes())
+        .expect("failed to parse scene");
+    assert!(path.exists(), "{} not written", path.display());
+}
+
+#[test]
+fn render_unknown_integrator() {
+    let mut pbrt = PbrtAPI::default();
+    pbrt.init();
+    pbrt.integrator("bogus", Default::default());
+    pbrt.world_begin();
+    assert!(pbrt.render().is_err());
+}
+
+#[test]
+fn parse_reader_streams_scene() {
+    /// Hands out at most 3 bytes per read, like a slow pipe, so tokens span reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(3).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let mut pbrt = PbrtAPI::default();
+    pbrt.init();
+    pbrt.parse_reader(Trickle(
+        br#"
+        # Comments and strings are split across reads too.
+        Sampler "stratified" "integer xsamples" [ 2 ] "integer ysamples" [ 3 ]
+        Camera "orthographic" "float screenwindow" [ -1 1 -1 1 ]
+        "#,
+    ))
+    .expect("failed to parse stream");
+    let (name, params) = pbrt.sampler_settings();
+    assert_eq!(name, "stratified");
+    assert_eq!(params.find_one_int("xsamples", 0), 2);
+    assert_eq!(params.find_one_int("ysamples", 0), 3);
+    assert_eq!(pbrt.camera_settings().0, "orthographic");
+
+    // Errors reading the stream are reported.
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+    }
+    assert!(pbrt.parse_reader(Broken).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn parse_file_from_pipe() {
+    use std::{
+        os::unix::io::AsRawFd,
+        process::{Command, Stdio},
+    };
+
+    // Pipes can't be memory mapped, parse_file must fall back to reading them.
+    let mut child = Command::new("echo")
+        .arg(r#"Sampler "stratified" "integer xsamples" [ 2 ]"#)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn echo");
+    let stdout = child.stdout.take().expect("child stdout");
+    let mut pbrt = PbrtAPI::default();
+    pbrt.init();
+    pbrt.parse_file(format!("/proc/self/fd/{}", stdout.as_raw_fd()))
+        .expect("failed to parse pipe");
+    child.wait().expect("echo failed");
+    let (name, params) = pbrt.sampler_settings();
+    assert_eq!(name, "stratified");
+    assert_eq!(params.find_one_int("xsamples", 0), 2);
+}